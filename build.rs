@@ -0,0 +1,5 @@
+fn main() {
+    // Link flags for loading the crate as a Node.js addon
+    #[cfg(feature = "node")]
+    napi_build::setup();
+}
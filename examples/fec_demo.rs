@@ -9,6 +9,9 @@
 //! - CRC32 integrity checking
 //! - Repair simulation
 
+// Written against the shard layer's own `FecParams`, kept for one release
+#![allow(deprecated)]
+
 use anyhow::Result;
 use saorsa_fec::fec::{self, FecParams, RepairHooks, Shard};
 use std::collections::HashMap;
@@ -63,7 +66,7 @@ impl DemoStorage {
 }
 
 impl RepairHooks for DemoStorage {
-    fn fetch_shards(&self, key: Vec<u8>, need: usize) -> Result<Vec<Shard>> {
+    fn fetch_shards(&self, key: Vec<u8>, need: usize) -> fec::Result<Vec<Shard>> {
         let storage = self.shards.read();
         if let Some(entry) = storage.get(&key) {
             let shards: Vec<Shard> = entry.values().take(need).cloned().collect();
@@ -80,7 +83,7 @@ impl RepairHooks for DemoStorage {
         }
     }
 
-    fn reseed(&self, key: Vec<u8>, shards: Vec<Shard>) -> Result<()> {
+    fn reseed(&self, key: Vec<u8>, shards: Vec<Shard>) -> fec::Result<()> {
         self.store_shards(key, shards);
         Ok(())
     }
@@ -0,0 +1,229 @@
+/* Generated with cbindgen from src/ffi.rs. Do not edit by hand; run
+ *   cbindgen --config cbindgen.toml --output include/saorsa_fec.h
+ * after changing the C ABI. Build the library with
+ *   cargo rustc --release --lib --features ffi --crate-type cdylib
+ */
+
+#ifndef SAORSA_FEC_H
+#define SAORSA_FEC_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Outcome of a call
+ */
+typedef enum SaorsaFecStatus {
+  /**
+   * The call succeeded
+   */
+  SAORSA_FEC_STATUS_OK = 0,
+  /**
+   * A required pointer was null
+   */
+  SAORSA_FEC_STATUS_NULL_POINTER = 1,
+  /**
+   * The FEC parameters are out of range
+   */
+  SAORSA_FEC_STATUS_INVALID_PARAMS = 2,
+  /**
+   * A shard or manifest could not be deserialized
+   */
+  SAORSA_FEC_STATUS_MALFORMED = 3,
+  /**
+   * A shard's data does not match its CRC
+   */
+  SAORSA_FEC_STATUS_CORRUPT_SHARD = 4,
+  /**
+   * The output array has too few entries
+   */
+  SAORSA_FEC_STATUS_BUFFER_TOO_SMALL = 5,
+  /**
+   * Encoding, decoding or repair failed
+   */
+  SAORSA_FEC_STATUS_FAILED = 6,
+  /**
+   * The library panicked; no outputs were written
+   */
+  SAORSA_FEC_STATUS_PANIC = 7,
+} SaorsaFecStatus;
+
+/**
+ * FEC parameters, as in [`FecParams`]
+ */
+typedef struct SaorsaFecParams {
+  /**
+   * Number of data shards (k)
+   */
+  uint16_t data_shares;
+  /**
+   * Number of parity shards (m)
+   */
+  uint16_t parity_shares;
+  /**
+   * Size of each shard's data in bytes
+   */
+  uint32_t symbol_size;
+} SaorsaFecParams;
+
+/**
+ * Bytes owned by the library, released with [`saorsa_fec_buffer_free`]
+ */
+typedef struct SaorsaFecBuffer {
+  uint8_t *data;
+  size_t len;
+} SaorsaFecBuffer;
+
+/**
+ * Bytes borrowed from the caller
+ */
+typedef struct SaorsaFecSlice {
+  const uint8_t *data;
+  size_t len;
+} SaorsaFecSlice;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Library version as a NUL-terminated string
+ */
+const char *saorsa_fec_version(void);
+
+/**
+ * Message describing the last failed call on this thread, or null
+ *
+ * The string stays valid until the next call into the library on the same
+ * thread.
+ */
+const char *saorsa_fec_last_error(void);
+
+/**
+ * Release a buffer returned by the library
+ *
+ * # Safety
+ * `buffer` must be null or point to a buffer filled in by this library that
+ * has not been freed already. It is reset to empty.
+ */
+void saorsa_fec_buffer_free(struct SaorsaFecBuffer *buffer);
+
+/**
+ * Encode `data` into `data_shares + parity_shares` serialized shards
+ *
+ * `data` may be at most `data_shares * symbol_size` bytes and is zero-padded
+ * to that length. Shard `i` is written to `out[i]`.
+ *
+ * # Safety
+ * `data` must be valid for reads of `data_len` bytes and `out` for writes
+ * of `out_len` buffers.
+ */
+SaorsaFecStatus saorsa_fec_encode(struct SaorsaFecParams params,
+                                  const uint8_t *data,
+                                  size_t data_len,
+                                  struct SaorsaFecBuffer *out,
+                                  size_t out_len);
+
+/**
+ * Decode the original data from serialized shards
+ *
+ * Shards failing their CRC are ignored. The output is padded to
+ * `data_shares * symbol_size` bytes; truncate it to the manifest's original
+ * size.
+ *
+ * # Safety
+ * `shards` must point to `shard_count` slices of readable bytes and `out`
+ * must be valid for writes.
+ */
+SaorsaFecStatus saorsa_fec_decode(struct SaorsaFecParams params,
+                                  const struct SaorsaFecSlice *shards,
+                                  size_t shard_count,
+                                  struct SaorsaFecBuffer *out);
+
+/**
+ * Regenerate the shards missing from, or corrupted in, `shards`
+ *
+ * The regenerated shards are written to the start of `out` and their
+ * number to `out_written`. `data_shares + parity_shares` entries are always
+ * enough.
+ *
+ * # Safety
+ * `shards` must point to `shard_count` slices of readable bytes, `out` must
+ * be valid for writes of `out_len` buffers and `out_written` for a write.
+ */
+SaorsaFecStatus saorsa_fec_repair(struct SaorsaFecParams params,
+                                  const struct SaorsaFecSlice *shards,
+                                  size_t shard_count,
+                                  struct SaorsaFecBuffer *out,
+                                  size_t out_len,
+                                  size_t *out_written);
+
+/**
+ * Serialize a shard with index `idx` holding `data`, computing its CRC
+ *
+ * # Safety
+ * `data` must be valid for reads of `data_len` bytes and `out` for writes.
+ */
+SaorsaFecStatus saorsa_fec_shard_serialize(uint16_t idx,
+                                           const uint8_t *data,
+                                           size_t data_len,
+                                           struct SaorsaFecBuffer *out);
+
+/**
+ * Read the index and data of a serialized shard, checking its CRC
+ *
+ * # Safety
+ * `bytes` must be valid for reads of `len` bytes, and `out_idx` and
+ * `out_data` for writes.
+ */
+SaorsaFecStatus saorsa_fec_shard_deserialize(const uint8_t *bytes,
+                                             size_t len,
+                                             uint16_t *out_idx,
+                                             struct SaorsaFecBuffer *out_data);
+
+/**
+ * Serialize a manifest for an object of `original_size` bytes
+ *
+ * # Safety
+ * `object_id` must be valid for reads of `object_id_len` bytes and `out`
+ * for writes.
+ */
+SaorsaFecStatus saorsa_fec_manifest_serialize(const uint8_t *object_id,
+                                              size_t object_id_len,
+                                              struct SaorsaFecParams params,
+                                              uint64_t original_size,
+                                              struct SaorsaFecBuffer *out);
+
+/**
+ * Read the parameters, original size and object ID of a serialized manifest
+ *
+ * # Safety
+ * `bytes` must be valid for reads of `len` bytes, and the outputs for
+ * writes.
+ */
+SaorsaFecStatus saorsa_fec_manifest_deserialize(const uint8_t *bytes,
+                                                size_t len,
+                                                struct SaorsaFecParams *out_params,
+                                                uint64_t *out_original_size,
+                                                struct SaorsaFecBuffer *out_object_id);
+
+/**
+ * Copy the 32-byte storage key of shard `idx` from a serialized manifest
+ *
+ * # Safety
+ * `bytes` must be valid for reads of `len` bytes and `out_key` for writes
+ * of 32 bytes.
+ */
+SaorsaFecStatus saorsa_fec_manifest_shard_key(const uint8_t *bytes,
+                                              size_t len,
+                                              uint16_t idx,
+                                              uint8_t *out_key);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SAORSA_FEC_H */
@@ -0,0 +1,291 @@
+//! Tamper-evident log of key usage
+//!
+//! [`AuditLog`] records which key encrypted or decrypted which file, and
+//! when. Every entry commits to the hash of the one before it, so editing,
+//! reordering or dropping an entry anywhere but the tail breaks the chain.
+//! Entries are appended to a JSON-lines file when the log is persisted and
+//! can be exported and checked with [`AuditLog::verify`] elsewhere. Keep a
+//! copy of the latest [`AuditEntry::hash`] off-site to detect truncation.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::quantum_crypto::QuantumEncryptionMetadata;
+
+/// Errors from reading, writing or verifying an audit log
+#[derive(Debug, Error)]
+pub enum AuditError {
+    #[error("Audit entry {0} out of sequence")]
+    OutOfSequence(u64),
+
+    #[error("Audit entry {0} does not follow its predecessor")]
+    BrokenChain(usize),
+
+    #[error("Audit entry {0} has been modified")]
+    Modified(usize),
+
+    #[error("Audit log {} is corrupted: {message}", path.display())]
+    Corrupted { path: PathBuf, message: String },
+
+    #[error("Serialization error: {0}")]
+    Serialization(String),
+
+    #[error("{}: {error}", path.display())]
+    Io { path: PathBuf, error: io::Error },
+}
+
+pub type Result<T> = std::result::Result<T, AuditError>;
+
+fn io_error(path: &Path) -> impl FnOnce(io::Error) -> AuditError + '_ {
+    |error| AuditError::Io {
+        path: path.to_path_buf(),
+        error,
+    }
+}
+
+/// Key operation being recorded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditOperation {
+    /// A file was encrypted
+    Encrypt,
+    /// A file was decrypted
+    Decrypt,
+}
+
+/// One recorded key usage
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    /// Unix timestamp of the operation
+    pub timestamp: u64,
+    /// What the key was used for
+    pub operation: AuditOperation,
+    /// Identifier of the key used, see [`AuditEvent::key_id_of`]
+    pub key_id: String,
+    /// File the key was used on
+    pub file_id: [u8; 32],
+}
+
+impl AuditEvent {
+    /// Event for `operation` on `file_id` with the key `key_id`, timestamped now
+    pub fn new(operation: AuditOperation, key_id: impl Into<String>, file_id: [u8; 32]) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            operation,
+            key_id: key_id.into(),
+            file_id,
+        }
+    }
+
+    /// Identifier of the key recorded in encryption metadata
+    ///
+    /// This is the master key ID under envelope encryption, otherwise the
+    /// hex ML-KEM key or convergence secret ID, or `"convergent"` for keys
+    /// derived from the content alone.
+    pub fn key_id_of(metadata: &QuantumEncryptionMetadata) -> String {
+        if let Some(wrapped) = &metadata.wrapped_key {
+            wrapped.master_key_id.clone()
+        } else if let Some(id) = metadata.kem_key_id {
+            format!("kem/{}", hex::encode(id))
+        } else if let Some(id) = metadata.convergence_secret_id {
+            format!("convergence-secret/{}", hex::encode(id))
+        } else {
+            "convergent".to_string()
+        }
+    }
+}
+
+/// Hash-chained log entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Position in the log, starting at 0
+    pub sequence: u64,
+    /// The recorded event
+    pub event: AuditEvent,
+    /// Hash of the previous entry, all zeros for the first
+    pub prev_hash: [u8; 32],
+    /// Hash of this entry
+    pub hash: [u8; 32],
+}
+
+impl AuditEntry {
+    fn compute_hash(sequence: u64, event: &AuditEvent, prev_hash: &[u8; 32]) -> Result<[u8; 32]> {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(b"saorsa-fec-audit-v1");
+        hasher.update(prev_hash);
+        hasher.update(&sequence.to_le_bytes());
+        hasher.update(
+            &bincode::serialize(event).map_err(|e| AuditError::Serialization(e.to_string()))?,
+        );
+        Ok(*hasher.finalize().as_bytes())
+    }
+}
+
+/// Append-only, hash-chained audit log
+pub struct AuditLog {
+    state: Mutex<LogState>,
+}
+
+struct LogState {
+    entries: Vec<AuditEntry>,
+    /// Backing file and its path, for persisted logs
+    file: Option<(PathBuf, File)>,
+}
+
+impl AuditLog {
+    /// Log kept only in memory
+    pub fn in_memory() -> Self {
+        Self {
+            state: Mutex::new(LogState {
+                entries: Vec::new(),
+                file: None,
+            }),
+        }
+    }
+
+    /// Open the log file at `path`, verifying any existing entries
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut entries = Vec::new();
+        if path.exists() {
+            let corrupted = |message: String| AuditError::Corrupted {
+                path: path.to_path_buf(),
+                message,
+            };
+            let reader = BufReader::new(File::open(path).map_err(io_error(path))?);
+            for line in reader.lines() {
+                let line = line.map_err(io_error(path))?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                entries.push(serde_json::from_str(&line).map_err(|e| corrupted(e.to_string()))?);
+            }
+            Self::verify(&entries).map_err(|e| corrupted(e.to_string()))?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(io_error(path))?;
+        Ok(Self {
+            state: Mutex::new(LogState {
+                entries,
+                file: Some((path.to_path_buf(), file)),
+            }),
+        })
+    }
+
+    /// Append an event, returning the new entry
+    pub fn record(&self, event: AuditEvent) -> Result<AuditEntry> {
+        let mut state = self.state.lock();
+        let (sequence, prev_hash) = match state.entries.last() {
+            Some(last) => (last.sequence + 1, last.hash),
+            None => (0, [0u8; 32]),
+        };
+        let hash = AuditEntry::compute_hash(sequence, &event, &prev_hash)?;
+        let entry = AuditEntry {
+            sequence,
+            event,
+            prev_hash,
+            hash,
+        };
+
+        if let Some((path, file)) = &mut state.file {
+            let mut line =
+                serde_json::to_vec(&entry).map_err(|e| AuditError::Serialization(e.to_string()))?;
+            line.push(b'\n');
+            file.write_all(&line).map_err(io_error(path))?;
+            file.sync_data().map_err(io_error(path))?;
+        }
+        state.entries.push(entry.clone());
+        Ok(entry)
+    }
+
+    /// All entries, oldest first
+    pub fn export(&self) -> Vec<AuditEntry> {
+        self.state.lock().entries.clone()
+    }
+
+    /// Hash of the newest entry, all zeros for an empty log
+    pub fn head(&self) -> [u8; 32] {
+        self.state
+            .lock()
+            .entries
+            .last()
+            .map_or([0u8; 32], |entry| entry.hash)
+    }
+
+    /// Check that `entries` form an unbroken chain from the start of a log
+    pub fn verify(entries: &[AuditEntry]) -> Result<()> {
+        let mut prev_hash = [0u8; 32];
+        for (i, entry) in entries.iter().enumerate() {
+            if entry.sequence != i as u64 {
+                return Err(AuditError::OutOfSequence(entry.sequence));
+            }
+            if entry.prev_hash != prev_hash {
+                return Err(AuditError::BrokenChain(i));
+            }
+            let hash = AuditEntry::compute_hash(entry.sequence, &entry.event, &prev_hash)?;
+            if hash != entry.hash {
+                return Err(AuditError::Modified(i));
+            }
+            prev_hash = hash;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_audit_log_detects_tampering() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("audit.jsonl");
+
+        let log = AuditLog::open(&path)?;
+        log.record(AuditEvent::new(
+            AuditOperation::Encrypt,
+            "master",
+            [1u8; 32],
+        ))?;
+        log.record(AuditEvent::new(
+            AuditOperation::Decrypt,
+            "master",
+            [1u8; 32],
+        ))?;
+        let head = log.head();
+        drop(log);
+
+        // Reopening continues the chain
+        let log = AuditLog::open(&path)?;
+        let entry = log.record(AuditEvent::new(AuditOperation::Encrypt, "other", [2u8; 32]))?;
+        assert_eq!(entry.sequence, 2);
+        assert_eq!(entry.prev_hash, head);
+
+        let mut entries = log.export();
+        AuditLog::verify(&entries)?;
+
+        entries[1].event.file_id = [9u8; 32];
+        assert!(matches!(
+            AuditLog::verify(&entries),
+            Err(AuditError::Modified(1))
+        ));
+        entries.remove(1);
+        assert!(matches!(
+            AuditLog::verify(&entries),
+            Err(AuditError::OutOfSequence(2))
+        ));
+        Ok(())
+    }
+}
@@ -4,6 +4,7 @@
 //! FEC backend implementations
 
 use crate::{FecBackend, Result};
+use alloc::boxed::Box;
 
 pub mod pure_rust;
 
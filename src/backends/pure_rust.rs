@@ -4,7 +4,8 @@
 //! High-performance Reed-Solomon implementation using reed-solomon-simd
 
 use crate::{FecBackend, FecError, FecParams, Result};
-use reed_solomon_simd::ReedSolomonEncoder;
+use alloc::{format, string::ToString, vec, vec::Vec};
+use reed_solomon_simd::{ReedSolomonDecoder, ReedSolomonEncoder};
 
 /// High-performance Reed-Solomon backend using SIMD optimizations
 #[derive(Debug)]
@@ -53,7 +54,7 @@ impl PureRustBackend {
         }
 
         // Ensure block size is even (requirement of reed-solomon-simd)
-        if block_size % 2 != 0 {
+        if !block_size.is_multiple_of(2) {
             return Err(FecError::Backend(
                 "Shard size must be even for reed-solomon-simd".to_string(),
             ));
@@ -111,42 +112,33 @@ impl PureRustBackend {
             .find_map(|s| s.as_ref().map(|data| data.len()))
             .ok_or(FecError::InsufficientShares { have: 0, need: k })?;
 
-        // For reconstruction with reed-solomon-simd v3, we need to re-encode and replace missing shards
-        // Create encoder
-        let _encoder = ReedSolomonEncoder::new(k, m, block_size)
-            .map_err(|e| FecError::Backend(format!("Failed to create encoder: {:?}", e)))?;
-
-        // Convert Option<Vec<u8>> to Vec<Vec<u8>> for processing
-        // Missing shards will be replaced with zeros temporarily
-        let mut work_shards: Vec<Vec<u8>> = Vec::with_capacity(n);
-        let mut missing_indices = Vec::new();
-
-        for (i, shard) in shares.iter().enumerate() {
-            if let Some(data) = shard {
-                work_shards.push(data.clone());
-            } else {
-                work_shards.push(vec![0u8; block_size]);
+        // Feed every surviving share into the decoder; it restores the missing originals
+        let mut decoder = ReedSolomonDecoder::new(k, m, block_size)
+            .map_err(|e| FecError::Backend(format!("Failed to create decoder: {:?}", e)))?;
+
+        for (i, share) in shares.iter().enumerate() {
+            if let Some(data) = share {
+                if data.len() != block_size {
+                    return Err(FecError::SizeMismatch {
+                        expected: block_size,
+                        actual: data.len(),
+                    });
+                }
                 if i < k {
-                    missing_indices.push(i);
+                    decoder.add_original_shard(i, data)
+                } else {
+                    decoder.add_recovery_shard(i - k, data)
                 }
+                .map_err(|e| FecError::Backend(e.to_string()))?;
             }
         }
 
-        // If we have missing data shards, we need to reconstruct them
-        if !missing_indices.is_empty() {
-            // reed-solomon-simd v3 doesn't expose direct reconstruction
-            // We can only use it for encoding, not for decoding missing data shards
-            // For now, return an error if we need complex reconstruction
-            return Err(FecError::Backend(
-                "Reed-Solomon reconstruction with missing data shards is not supported in reed-solomon-simd v3".to_string(),
-            ));
-        }
+        let result = decoder
+            .decode()
+            .map_err(|e| FecError::Backend(e.to_string()))?;
 
-        // Copy reconstructed shards back to the output
-        for (i, shard) in work_shards.into_iter().enumerate() {
-            if shares[i].is_none() {
-                shares[i] = Some(shard);
-            }
+        for (i, restored) in result.restored_original_iter() {
+            shares[i] = Some(restored.to_vec());
         }
 
         Ok(())
@@ -298,4 +290,34 @@ mod tests {
             assert_eq!(shares[i].as_ref().unwrap(), &data[i]);
         }
     }
+
+    #[test]
+    fn test_reconstruct_missing_data_shares() {
+        let backend = PureRustBackend::new();
+        let params = FecParams::new(4, 2).unwrap();
+
+        let data: Vec<Vec<u8>> = (0..4).map(|i| vec![i as u8 * 7 + 1; 64]).collect();
+        let data_refs: Vec<&[u8]> = data.iter().map(|v| v.as_slice()).collect();
+
+        let mut parity = vec![vec![]; 2];
+        backend
+            .encode_blocks(&data_refs, &mut parity, params)
+            .unwrap();
+
+        // Lose two data shares, keep both parity shares
+        let mut shares: Vec<Option<Vec<u8>>> = vec![
+            None,
+            Some(data[1].clone()),
+            None,
+            Some(data[3].clone()),
+            Some(parity[0].clone()),
+            Some(parity[1].clone()),
+        ];
+
+        backend.decode_blocks(&mut shares, params).unwrap();
+
+        for i in 0..4 {
+            assert_eq!(shares[i].as_ref().unwrap(), &data[i]);
+        }
+    }
 }
@@ -0,0 +1,580 @@
+//! `encode` and `decode`: offline archiving to shard files
+//!
+//! `encode` cuts a file into chunks and codes each into `k + m` shards. Shard
+//! file `i` holds share `i` of every chunk as [wire](saorsa_fec::wire)
+//! containers written back to back, and a manifest container records the
+//! chunk hashes and shard CIDs. `decode` restores the file from the manifest
+//! and any `k` intact shard files. Shards are not encrypted, and their
+//! headers mark them as plaintext.
+
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use saorsa_fec::chunk_codec::ChunkCodec;
+use saorsa_fec::integrity::ChunkHealth;
+use saorsa_fec::metadata::{FileMetadata, LocalMetadata};
+use saorsa_fec::storage::Shard;
+use saorsa_fec::wire::{self, WireFormat};
+use saorsa_fec::FecParams;
+
+use crate::{CliError, Result};
+
+/// Chunk size used unless `--chunk-size` is given (1 MiB)
+const DEFAULT_CHUNK_SIZE: &str = "1048576";
+/// Extension of shard files
+const SHARD_EXTENSION: &str = "shard";
+/// Extension of manifest files
+const MANIFEST_EXTENSION: &str = "manifest";
+
+pub fn encode_command() -> Command {
+    Command::new("encode")
+        .about("Encode a file into k + m shard files and a manifest")
+        .arg(
+            Arg::new("file")
+                .value_name("FILE")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("k")
+                .long("k")
+                .help("Data shards; any k shard files restore the file")
+                .default_value("16")
+                .value_parser(value_parser!(u8).range(1..)),
+        )
+        .arg(
+            Arg::new("m")
+                .long("m")
+                .help("Parity shards; up to m shard files may be lost")
+                .default_value("4")
+                .value_parser(value_parser!(u8)),
+        )
+        .arg(
+            Arg::new("chunk-size")
+                .long("chunk-size")
+                .value_name("BYTES")
+                .help("Bytes coded at a time")
+                .default_value(DEFAULT_CHUNK_SIZE)
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("out")
+                .long("out")
+                .short('o')
+                .value_name("DIR")
+                .help("Directory for the shards and manifest [default: next to FILE]")
+                .value_parser(value_parser!(PathBuf)),
+        )
+}
+
+pub fn decode_command() -> Command {
+    Command::new("decode")
+        .about("Restore a file from its manifest and any k of its shard files")
+        .arg(
+            Arg::new("manifest")
+                .value_name("MANIFEST")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("shards")
+                .value_name("SHARD")
+                .help("Shard files [default: every .shard file next to MANIFEST]")
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .value_name("PATH")
+                .required(true)
+                .help("Where to write the restored file")
+                .value_parser(value_parser!(PathBuf)),
+        )
+}
+
+pub fn encode(args: &ArgMatches) -> Result<()> {
+    let input = args.get_one::<PathBuf>("file").expect("required");
+    let k = *args.get_one::<u8>("k").expect("defaulted");
+    let m = *args.get_one::<u8>("m").expect("defaulted");
+    let chunk_size = *args.get_one::<usize>("chunk-size").expect("defaulted");
+    let out_dir = match args.get_one::<PathBuf>("out") {
+        Some(dir) => dir.clone(),
+        None => input.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+
+    let report = encode_file(input, &out_dir, k, m, chunk_size)?;
+    println!(
+        "Encoded {} ({} bytes, {} chunks) into {} shards; any {} restore it",
+        input.display(),
+        report.file_size,
+        report.chunks,
+        report.shards.len(),
+        k
+    );
+    println!("{}", report.manifest.display());
+    for path in &report.shards {
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+pub fn decode(args: &ArgMatches) -> Result<()> {
+    let manifest = args.get_one::<PathBuf>("manifest").expect("required");
+    let output = args.get_one::<PathBuf>("output").expect("required");
+    let shards: Vec<PathBuf> = match args.get_many::<PathBuf>("shards") {
+        Some(paths) => paths.cloned().collect(),
+        None => shard_files_near(manifest)?,
+    };
+
+    let metadata = decode_file(manifest, &shards, output)?;
+    println!(
+        "Restored {} ({} bytes) to {}",
+        hex::encode(metadata.file_id),
+        metadata.file_size,
+        output.display()
+    );
+    Ok(())
+}
+
+/// Files written by [`encode_file`]
+#[derive(Debug)]
+pub struct EncodeReport {
+    pub manifest: PathBuf,
+    pub shards: Vec<PathBuf>,
+    pub file_size: u64,
+    pub chunks: usize,
+}
+
+/// Code `input` into shard files and a manifest in `out_dir`
+pub fn encode_file(
+    input: &Path,
+    out_dir: &Path,
+    k: u8,
+    m: u8,
+    chunk_size: usize,
+) -> Result<EncodeReport> {
+    if chunk_size == 0 {
+        return Err(CliError::Invalid("--chunk-size must be positive".into()));
+    }
+    let params = FecParams::new(k as u16, m as u16).map_err(saorsa_fec::Error::from)?;
+    let name = input
+        .file_name()
+        .ok_or_else(|| CliError::Invalid(format!("{} is not a file", input.display())))?
+        .to_string_lossy()
+        .into_owned();
+    let codec = ChunkCodec::new()?;
+
+    let mut reader = BufReader::new(File::open(input).map_err(CliError::io(input))?);
+    let shard_paths: Vec<PathBuf> = (0..params.total_shares())
+        .map(|i| out_dir.join(shard_name(&name, i, params.total_shares())))
+        .collect();
+    let mut writers = shard_paths
+        .iter()
+        .map(|path| {
+            File::create(path)
+                .map(BufWriter::new)
+                .map_err(CliError::io(path))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut hasher = blake3::Hasher::new();
+    let mut file_size = 0u64;
+    let mut chunks = Vec::new();
+    let mut chunk = Vec::with_capacity(chunk_size);
+    loop {
+        chunk.clear();
+        (&mut reader)
+            .take(chunk_size as u64)
+            .read_to_end(&mut chunk)
+            .map_err(CliError::io(input))?;
+        if chunk.is_empty() {
+            break;
+        }
+        let index = u16::try_from(chunks.len()).map_err(|_| {
+            CliError::Invalid(format!(
+                "{} needs more than {} chunks; raise --chunk-size",
+                input.display(),
+                u16::MAX
+            ))
+        })?;
+        hasher.update(&chunk);
+        file_size += chunk.len() as u64;
+
+        let encoded = codec.encode_plaintext(index, &chunk, params)?;
+        for ((_, shard), (writer, path)) in encoded
+            .shards
+            .iter()
+            .zip(writers.iter_mut().zip(&shard_paths))
+        {
+            let bytes = shard.to_wire().map_err(CliError::wire(path))?;
+            writer.write_all(&bytes).map_err(CliError::io(path))?;
+        }
+        chunks.push(encoded.reference);
+    }
+    for (writer, path) in writers.iter_mut().zip(&shard_paths) {
+        writer.flush().map_err(CliError::io(path))?;
+    }
+
+    let chunk_count = chunks.len();
+    let metadata = FileMetadata::new(hasher.finalize().into(), file_size, None, chunks)
+        .with_local_metadata(LocalMetadata::new().with_filename(name.clone()));
+    let manifest = out_dir.join(format!("{name}.{MANIFEST_EXTENSION}"));
+    let bytes = metadata.to_wire().map_err(CliError::wire(&manifest))?;
+    fs::write(&manifest, bytes).map_err(CliError::io(&manifest))?;
+
+    Ok(EncodeReport {
+        manifest,
+        shards: shard_paths,
+        file_size,
+        chunks: chunk_count,
+    })
+}
+
+/// Restore the file described by `manifest` from `shards` into `output`
+///
+/// Shard files are matched to share indices by content, so they may be
+/// renamed or listed in any order; unreadable ones are skipped. The file is
+/// restored beside `output` and only renamed to it once its hash checks out,
+/// so a failed decode leaves no partial file behind.
+pub fn decode_file(manifest: &Path, shards: &[PathBuf], output: &Path) -> Result<FileMetadata> {
+    let metadata = read_manifest(manifest)?;
+    let mut temp = output.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+
+    let restored = restore(manifest, &metadata, shards, &temp)
+        .and_then(|()| fs::rename(&temp, output).map_err(CliError::io(output)));
+    if restored.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    restored.map(|()| metadata)
+}
+
+/// Decode the chunks of `metadata` into `output`, checking the file ID
+fn restore(
+    manifest: &Path,
+    metadata: &FileMetadata,
+    shards: &[PathBuf],
+    output: &Path,
+) -> Result<()> {
+    let codec = ChunkCodec::new()?;
+    let mut sources = ShardSources::open(metadata, shards);
+
+    let mut writer = BufWriter::new(File::create(output).map_err(CliError::io(output))?);
+    let mut hasher = blake3::Hasher::new();
+    for (position, chunk_ref) in metadata.chunks.iter().enumerate() {
+        let fetched = sources.shards_at(position);
+        let (shards, health) = ChunkCodec::verify_shards(chunk_ref, fetched);
+        let required = health.required_shards.unwrap_or(0);
+        if required == 0 || health.available() < required {
+            return Err(CliError::Invalid(format!(
+                "chunk {} has {} intact shards, too few to restore it",
+                chunk_ref.shard_index,
+                health.available()
+            )));
+        }
+        let data = codec.decode(chunk_ref, &shards)?;
+        hasher.update(&data);
+        writer.write_all(&data).map_err(CliError::io(output))?;
+    }
+    let file = writer
+        .into_inner()
+        .map_err(|e| CliError::io(output)(e.into_error()))?;
+    file.sync_all().map_err(CliError::io(output))?;
+
+    if hasher.finalize().as_bytes() != &metadata.file_id {
+        return Err(CliError::Invalid(format!(
+            "restored data does not match the file ID in {}",
+            manifest.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Health of every chunk of `metadata` across the shard files `paths`
+pub fn shard_file_health(metadata: &FileMetadata, paths: &[PathBuf]) -> Vec<ChunkHealth> {
+    let mut sources = ShardSources::open(metadata, paths);
+    metadata
+        .chunks
+        .iter()
+        .enumerate()
+        .map(|(position, chunk_ref)| {
+            ChunkCodec::verify_shards(chunk_ref, sources.shards_at(position)).1
+        })
+        .collect()
+}
+
+/// Parse a manifest file
+pub fn read_manifest(path: &Path) -> Result<FileMetadata> {
+    let bytes = fs::read(path).map_err(CliError::io(path))?;
+    FileMetadata::from_wire(&bytes).map_err(CliError::wire(path))
+}
+
+/// Shard files in the directory of `manifest`
+pub fn shard_files_near(manifest: &Path) -> Result<Vec<PathBuf>> {
+    let dir = match manifest.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir).map_err(CliError::io(dir))? {
+        let path = entry.map_err(CliError::io(dir))?.path();
+        if path.extension().is_some_and(|ext| ext == SHARD_EXTENSION) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Name of shard file `index` of `total`, zero-padded so names sort in order
+fn shard_name(name: &str, index: u16, total: u16) -> String {
+    let width = total.saturating_sub(1).to_string().len().max(2);
+    format!("{name}.{index:0width$}.{SHARD_EXTENSION}")
+}
+
+/// Share index in a file named by [`shard_name`], if any
+fn shard_index_in_name(path: &Path) -> Option<usize> {
+    let stem = Path::new(path.file_stem()?);
+    stem.extension()?.to_str()?.parse().ok()
+}
+
+/// Open shard files, each read one chunk at a time
+struct ShardSources {
+    /// Reader for each share index
+    readers: Vec<Option<ShardFile>>,
+}
+
+/// A shard file and the shard last read from it
+struct ShardFile {
+    reader: BufReader<File>,
+    /// Chunk position of `pending`, or of the next container once taken
+    position: usize,
+    pending: Option<Shard>,
+}
+
+impl ShardSources {
+    /// Assign each file to the share index of its first readable shard,
+    /// found among the shard IDs the manifest records for that chunk
+    fn open(metadata: &FileMetadata, paths: &[PathBuf]) -> Self {
+        let shares = metadata
+            .chunks
+            .iter()
+            .map(|c| c.shard_ids.len())
+            .max()
+            .unwrap_or(0);
+        let mut readers: Vec<Option<ShardFile>> = (0..shares).map(|_| None).collect();
+        for path in paths {
+            let mut reader = match File::open(path) {
+                Ok(file) => BufReader::new(file),
+                Err(e) => {
+                    eprintln!("warning: skipping {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            let Some((position, shard)) = first_shard(&mut reader, metadata.chunks.len()) else {
+                eprintln!("warning: skipping {}: no readable shard", path.display());
+                continue;
+            };
+            // Identical shares (e.g. of constant data) share a CID, so the
+            // index in the file name settles which of them this file holds
+            let ids = &metadata.chunks[position].shard_ids;
+            let matching: Vec<usize> = match shard.cid() {
+                Ok(cid) => (0..ids.len())
+                    .filter(|&i| ids[i] == *cid.as_bytes())
+                    .collect(),
+                Err(_) => Vec::new(),
+            };
+            let named = shard_index_in_name(path);
+            let free = matching
+                .iter()
+                .copied()
+                .filter(|&i| readers[i].is_none())
+                .min_by_key(|&i| Some(i) != named);
+            match free {
+                Some(index) => {
+                    readers[index] = Some(ShardFile {
+                        reader,
+                        position,
+                        pending: Some(shard),
+                    })
+                }
+                None if !matching.is_empty() => {
+                    eprintln!("warning: skipping duplicate {}", path.display())
+                }
+                None => eprintln!(
+                    "warning: skipping {}: not a shard of this manifest",
+                    path.display()
+                ),
+            }
+        }
+        Self { readers }
+    }
+
+    /// The shard of chunk `position` from each file, `None` where a file is
+    /// missing, ended or holds a damaged container. Positions must ascend.
+    fn shards_at(&mut self, position: usize) -> Vec<Option<Shard>> {
+        self.readers
+            .iter_mut()
+            .map(|slot| {
+                let file = slot.as_mut()?;
+                while file.position < position {
+                    if file.pending.take().is_none() && read_container(&mut file.reader).is_none() {
+                        *slot = None;
+                        return None;
+                    }
+                    file.position += 1;
+                }
+                if file.position > position {
+                    return None;
+                }
+                let shard = match file.pending.take() {
+                    Some(shard) => Some(shard),
+                    None => match read_container(&mut file.reader) {
+                        Some(bytes) => Shard::from_wire(&bytes).ok(),
+                        None => {
+                            *slot = None;
+                            return None;
+                        }
+                    },
+                };
+                file.position += 1;
+                shard
+            })
+            .collect()
+    }
+}
+
+/// The first shard that parses in a shard file and its chunk position, past
+/// damaged containers, looking no further than `chunks` containers
+fn first_shard(reader: &mut impl Read, chunks: usize) -> Option<(usize, Shard)> {
+    for position in 0..chunks {
+        if let Ok(shard) = Shard::from_wire(&read_container(reader)?) {
+            return Some((position, shard));
+        }
+    }
+    None
+}
+
+/// Read the next container unparsed, `None` at the end of the file or
+/// where no complete container follows
+pub fn read_container(reader: &mut impl Read) -> Option<Vec<u8>> {
+    let mut bytes = vec![0u8; wire::HEADER_SIZE];
+    reader.read_exact(&mut bytes).ok()?;
+    let len = wire::container_len(&bytes).ok()?;
+    bytes.resize(len, 0);
+    reader.read_exact(&mut bytes[wire::HEADER_SIZE..]).ok()?;
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use saorsa_fec::storage::Cid;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_decode_from_any_k_shard_files() {
+        let dir = TempDir::new().unwrap();
+        let input = dir.path().join("archive.bin");
+        let data: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+        fs::write(&input, &data).unwrap();
+
+        let report = encode_file(&input, dir.path(), 4, 2, 3000).unwrap();
+        assert_eq!(report.chunks, 4);
+        assert_eq!(report.shards.len(), 6);
+        assert!(report.shards[0].ends_with("archive.bin.00.shard"));
+
+        // Lose two shard files and pass the rest out of order
+        fs::remove_file(&report.shards[1]).unwrap();
+        fs::remove_file(&report.shards[4]).unwrap();
+        let mut remaining = shard_files_near(&report.manifest).unwrap();
+        remaining.reverse();
+        let output = dir.path().join("restored.bin");
+        let metadata = decode_file(&report.manifest, &remaining, &output).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), data);
+        assert_eq!(metadata.file_size, data.len() as u64);
+        assert_eq!(
+            metadata.local_metadata.unwrap().filename.as_deref(),
+            Some("archive.bin")
+        );
+    }
+
+    #[test]
+    fn test_decode_constant_data_with_identical_shares() {
+        let dir = TempDir::new().unwrap();
+        let input = dir.path().join("zeros.bin");
+        fs::write(&input, vec![0u8; 5000]).unwrap();
+        let report = encode_file(&input, dir.path(), 3, 2, 2000).unwrap();
+        let metadata = read_manifest(&report.manifest).unwrap();
+        let ids = &metadata.chunks[0].shard_ids;
+        assert_eq!(ids[0], ids[1]);
+
+        fs::remove_file(&report.shards[0]).unwrap();
+        let mut remaining = shard_files_near(&report.manifest).unwrap();
+        remaining.reverse();
+        let output = dir.path().join("restored.bin");
+        decode_file(&report.manifest, &remaining, &output).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), vec![0u8; 5000]);
+        assert!(shard_file_health(&metadata, &remaining)
+            .iter()
+            .all(|h| h.available() == 4));
+    }
+
+    #[test]
+    fn test_decode_fails_with_fewer_than_k_shards() {
+        let dir = TempDir::new().unwrap();
+        let input = dir.path().join("small.txt");
+        fs::write(&input, b"short archive").unwrap();
+        let report = encode_file(&input, dir.path(), 4, 2, 1 << 20).unwrap();
+
+        let metadata = read_manifest(&report.manifest).unwrap();
+        let bytes = read_container(&mut File::open(&report.shards[0]).unwrap()).unwrap();
+        let shard = Shard::from_wire(&bytes).unwrap();
+        assert!(shard.header.is_plaintext());
+        assert_eq!(
+            shard.cid().unwrap(),
+            Cid::new(metadata.chunks[0].shard_ids[0])
+        );
+
+        let output = dir.path().join("out.txt");
+        let err = decode_file(&report.manifest, &report.shards[..3], &output).unwrap_err();
+        assert!(err.to_string().contains("too few"));
+        assert!(!output.exists());
+        assert!(fs::read_dir(dir.path()).unwrap().all(|entry| !entry
+            .unwrap()
+            .path()
+            .to_string_lossy()
+            .ends_with(".tmp")));
+    }
+
+    #[test]
+    fn test_shard_file_matched_past_damaged_first_container() {
+        let dir = TempDir::new().unwrap();
+        let input = dir.path().join("archive.bin");
+        let data: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+        fs::write(&input, &data).unwrap();
+        let report = encode_file(&input, dir.path(), 4, 2, 3000).unwrap();
+
+        // Damage the first shard of a file whose name no longer gives its index
+        let mut bytes = fs::read(&report.shards[0]).unwrap();
+        bytes[wire::HEADER_SIZE + 1] ^= 0xff;
+        let renamed = dir.path().join("unnamed.shard");
+        fs::write(&renamed, bytes).unwrap();
+        fs::remove_file(&report.shards[0]).unwrap();
+
+        let metadata = read_manifest(&report.manifest).unwrap();
+        let paths = shard_files_near(&report.manifest).unwrap();
+        let health = shard_file_health(&metadata, &paths);
+        assert_eq!(health[0].available(), 5);
+        assert!(health[1..].iter().all(|h| h.available() == 6));
+
+        let output = dir.path().join("restored.bin");
+        decode_file(&report.manifest, &paths, &output).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), data);
+    }
+}
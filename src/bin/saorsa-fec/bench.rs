@@ -0,0 +1,194 @@
+//! `bench`: coding throughput on this machine
+//!
+//! Codes random chunks of `k` shards of each size with every FEC backend
+//! built in, through [`ChunkCodec`] as the pipeline does, so the figures
+//! include shard hashing. Decoding and repair run with `m` shards lost, the
+//! most a chunk survives, and data shards lost first as they cost the most.
+
+use clap::{value_parser, Arg, ArgMatches, Command};
+use std::time::{Duration, Instant};
+
+use saorsa_fec::backends::pure_rust::PureRustBackend;
+use saorsa_fec::{ChunkCodec, EncryptionMode, FecBackend, FecParams};
+
+use crate::{CliError, Result};
+
+const MIB: f64 = (1 << 20) as f64;
+
+pub fn command() -> Command {
+    Command::new("bench")
+        .about("Measure encode, decode and repair throughput for a range of parameters")
+        .arg(
+            Arg::new("k")
+                .long("k")
+                .help("Data shard counts to try, comma-separated")
+                .value_delimiter(',')
+                .default_value("4,8,16")
+                .value_parser(value_parser!(u8).range(1..)),
+        )
+        .arg(
+            Arg::new("m")
+                .long("m")
+                .help("Parity shard counts to try, comma-separated")
+                .value_delimiter(',')
+                .default_value("2,4")
+                .value_parser(value_parser!(u8).range(1..)),
+        )
+        .arg(
+            Arg::new("shard-size")
+                .long("shard-size")
+                .value_name("BYTES")
+                .help("Shard sizes to try, comma-separated")
+                .value_delimiter(',')
+                .default_value("4096,65536,1048576")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("time")
+                .long("time")
+                .value_name("MS")
+                .help("Time spent measuring each operation")
+                .default_value("200")
+                .value_parser(value_parser!(u64).range(1..)),
+        )
+}
+
+pub fn run(args: &ArgMatches) -> Result<()> {
+    let ks: Vec<u8> = args.get_many("k").expect("defaulted").copied().collect();
+    let ms: Vec<u8> = args.get_many("m").expect("defaulted").copied().collect();
+    let sizes: Vec<usize> = args
+        .get_many("shard-size")
+        .expect("defaulted")
+        .copied()
+        .collect();
+    let budget = Duration::from_millis(*args.get_one::<u64>("time").expect("defaulted"));
+    if let Some(size) = sizes.iter().find(|&&size| size == 0 || size % 2 == 1) {
+        return Err(CliError::Invalid(format!(
+            "shard size {size} must be even and positive"
+        )));
+    }
+
+    println!(
+        "{:<18} {:>3} {:>3} {:>9} {:>12} {:>12} {:>12}",
+        "backend", "k", "m", "shard", "encode MiB/s", "decode MiB/s", "repair MiB/s"
+    );
+    for backend in available_backends() {
+        let name = backend.name();
+        let codec = ChunkCodec::with_backend(backend);
+        for &k in &ks {
+            for &m in &ms {
+                let params = FecParams::new(k.into(), m.into()).map_err(saorsa_fec::Error::from)?;
+                for &size in &sizes {
+                    let result = measure(&codec, params, size, budget)?;
+                    println!(
+                        "{:<18} {:>3} {:>3} {:>9} {:>12.1} {:>12.1} {:>12.1}",
+                        name,
+                        k,
+                        m,
+                        format_size(size),
+                        result.encode,
+                        result.decode,
+                        result.repair
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Every FEC backend usable on this machine
+fn available_backends() -> Vec<Box<dyn FecBackend>> {
+    let pure_rust: Box<dyn FecBackend> = Box::new(PureRustBackend::new());
+    #[cfg(all(target_arch = "x86_64", feature = "isa-l"))]
+    if is_x86_feature_detected!("avx2") {
+        if let Ok(isa_l) = saorsa_fec::backends::isa_l::IsaLBackend::new() {
+            return vec![pure_rust, Box::new(isa_l)];
+        }
+    }
+    vec![pure_rust]
+}
+
+/// Chunk bytes coded per second, in MiB/s
+#[derive(Debug)]
+struct Throughput {
+    encode: f64,
+    decode: f64,
+    repair: f64,
+}
+
+/// Time each operation on one chunk of `k` shards of `shard_size` bytes
+fn measure(
+    codec: &ChunkCodec,
+    params: FecParams,
+    shard_size: usize,
+    budget: Duration,
+) -> Result<Throughput> {
+    let mut chunk = vec![0u8; params.data_shares as usize * shard_size];
+    blake3::Hasher::new().finalize_xof().fill(&mut chunk);
+
+    let encoded = codec.encode(0, &chunk, params, EncryptionMode::RandomKey, None)?;
+    let reference = &encoded.reference;
+    let mut fetched: Vec<_> = encoded.shards.into_iter().map(|(_, s)| Some(s)).collect();
+    for shard in fetched.iter_mut().take(params.parity_shares as usize) {
+        *shard = None;
+    }
+    let (shards, _) = ChunkCodec::verify_shards(reference, fetched.clone());
+
+    let encode = rate(chunk.len(), budget, || {
+        codec.encode(0, &chunk, params, EncryptionMode::RandomKey, None)?;
+        Ok(())
+    })?;
+    let decode = rate(chunk.len(), budget, || {
+        codec.decode(reference, &shards)?;
+        Ok(())
+    })?;
+    let repair = rate(chunk.len(), budget, || {
+        let (shards, health) = ChunkCodec::verify_shards(reference, fetched.clone());
+        codec.rebuild(reference, &shards, &health)?;
+        Ok(())
+    })?;
+
+    Ok(Throughput {
+        encode,
+        decode,
+        repair,
+    })
+}
+
+/// Run `op` for at least `budget`, after one warm-up run
+fn rate(bytes: usize, budget: Duration, mut op: impl FnMut() -> Result<()>) -> Result<f64> {
+    op()?;
+    let start = Instant::now();
+    let mut runs = 0u32;
+    while runs == 0 || start.elapsed() < budget {
+        op()?;
+        runs += 1;
+    }
+    Ok(bytes as f64 * f64::from(runs) / start.elapsed().as_secs_f64() / MIB)
+}
+
+/// `bytes` in the largest binary unit dividing it
+fn format_size(bytes: usize) -> String {
+    match bytes {
+        b if b >= 1 << 20 && b % (1 << 20) == 0 => format!("{} MiB", b >> 20),
+        b if b >= 1 << 10 && b % (1 << 10) == 0 => format!("{} KiB", b >> 10),
+        b => format!("{b} B"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_measure_reports_every_operation() {
+        let codec = ChunkCodec::with_backend(Box::new(PureRustBackend::new()));
+        let params = FecParams::new(4, 2).unwrap();
+        let result = measure(&codec, params, 1024, Duration::from_millis(1)).unwrap();
+        assert!(result.encode > 0.0 && result.decode > 0.0 && result.repair > 0.0);
+        assert_eq!(format_size(65536), "64 KiB");
+        assert_eq!(format_size(3 << 20), "3 MiB");
+        assert_eq!(format_size(1000), "1000 B");
+    }
+}
@@ -0,0 +1,551 @@
+//! `inspect`: describe shard files, manifests and file metadata
+//!
+//! Accepts wire containers (shard files written by `encode`, single shards,
+//! manifests and IDA descriptors), raw shards as kept by the storage
+//! backends, and file metadata as JSON. A manifest's health is checked
+//! against the shard files next to it, or through the pipeline with
+//! `--config`.
+
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use saorsa_fec::integrity::{ChunkHealth, HealthStatus};
+use saorsa_fec::metadata::FileMetadata;
+use saorsa_fec::storage::Shard;
+use saorsa_fec::wire::{self, Container, Kind, WireFormat};
+use saorsa_fec::{EncryptionMode, IDADescriptor};
+
+use crate::archive::{read_container, shard_file_health, shard_files_near};
+use crate::{store, CliError, Result};
+
+pub fn command() -> Command {
+    store::store_args(
+        Command::new("inspect")
+            .about("Describe a shard file, manifest or file metadata")
+            .arg(
+                Arg::new("file")
+                    .value_name("FILE")
+                    .required(true)
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(
+                Arg::new("shards")
+                    .value_name("SHARD")
+                    .help("Shard files checked for a manifest [default: .shard files next to it]")
+                    .action(ArgAction::Append)
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(
+                Arg::new("json")
+                    .long("json")
+                    .help("Print JSON instead of text")
+                    .action(ArgAction::SetTrue),
+            ),
+    )
+    .mut_arg("config", |arg| {
+        arg.required(false)
+            .help("Check a manifest's health through the pipeline configured here")
+    })
+}
+
+pub fn run(args: &ArgMatches) -> Result<()> {
+    let path = args.get_one::<PathBuf>("file").expect("required");
+    let mut report = inspect_file(path)?;
+
+    if let Report::Manifest(manifest) = &mut report {
+        let metadata = read_metadata(path)?;
+        let health = if args.contains_id("config") {
+            store::with_pipeline(args, |pipeline| async move {
+                Ok(pipeline.verify_file(&metadata).await?.chunks)
+            })?
+        } else {
+            let shards: Vec<PathBuf> = match args.get_many::<PathBuf>("shards") {
+                Some(paths) => paths.cloned().collect(),
+                None => shard_files_near(path)?,
+            };
+            if shards.is_empty() {
+                Vec::new()
+            } else {
+                shard_file_health(&metadata, &shards)
+            }
+        };
+        if !health.is_empty() {
+            manifest.health = Some(health.iter().map(HealthEntry::from).collect());
+        }
+    }
+
+    if args.get_flag("json") {
+        let json = serde_json::to_string_pretty(&report)
+            .map_err(|e| CliError::Invalid(format!("cannot render JSON: {e}")))?;
+        println!("{json}");
+    } else {
+        print!("{report}");
+    }
+    Ok(())
+}
+
+/// What a file turned out to hold
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Report {
+    /// One or more shards, in file order
+    Shards {
+        /// `wire` for containers, `raw` for a backend's shard encoding
+        format: &'static str,
+        shards: Vec<ShardEntry>,
+    },
+    Manifest(Box<ManifestReport>),
+    IdaDescriptor {
+        k: u16,
+        n: u16,
+        stripe_size: u32,
+        file_size: u64,
+        code: String,
+        checksum: String,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShardEntry {
+    /// Position in the file, which is the chunk index for `encode` output
+    pub position: usize,
+    #[serde(flatten)]
+    pub shard: Option<ShardInfo>,
+    /// Why the shard could not be read
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShardInfo {
+    pub cid: String,
+    pub version: u8,
+    pub encryption_mode: EncryptionMode,
+    pub plaintext: bool,
+    pub data_shards: u8,
+    pub parity_shards: u8,
+    pub data_size: u32,
+    pub nonce: String,
+    pub expires_at: Option<u64>,
+}
+
+impl ShardEntry {
+    fn new(position: usize, shard: &Shard) -> Self {
+        let header = &shard.header;
+        match shard.cid() {
+            Ok(cid) => Self {
+                position,
+                shard: Some(ShardInfo {
+                    cid: cid.to_hex(),
+                    version: header.version,
+                    encryption_mode: header.encryption_mode,
+                    plaintext: header.is_plaintext(),
+                    data_shards: header.nspec.0,
+                    parity_shards: header.nspec.1,
+                    data_size: header.data_size,
+                    nonce: hex::encode(header.nonce),
+                    expires_at: header.expires_at(),
+                }),
+                error: None,
+            },
+            Err(e) => Self::damaged(position, e),
+        }
+    }
+
+    fn damaged(position: usize, error: impl ToString) -> Self {
+        Self {
+            position,
+            shard: None,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ManifestReport {
+    pub file_id: String,
+    pub file_size: u64,
+    pub filename: Option<String>,
+    pub encryption_mode: Option<EncryptionMode>,
+    pub data_shards: Option<u16>,
+    pub parity_shards: Option<u16>,
+    pub chunk_size: Option<usize>,
+    pub compression_level: Option<u8>,
+    /// Cipher and key handling, `None` for plain data
+    pub encryption: Option<String>,
+    pub signed: bool,
+    pub parent_version: Option<String>,
+    pub chunks: Vec<ChunkEntry>,
+    /// Shard health per chunk, when shards could be checked
+    pub health: Option<Vec<HealthEntry>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChunkEntry {
+    pub index: u16,
+    pub chunk_id: String,
+    pub size: u32,
+    /// Shard CIDs by share index
+    pub shard_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthEntry {
+    pub chunk_id: String,
+    pub status: &'static str,
+    pub available: usize,
+    pub total: usize,
+    pub required: Option<usize>,
+    pub missing: Vec<usize>,
+    pub corrupted: Vec<usize>,
+}
+
+impl From<&ChunkHealth> for HealthEntry {
+    fn from(health: &ChunkHealth) -> Self {
+        Self {
+            chunk_id: hex::encode(health.chunk_id),
+            status: match health.status() {
+                HealthStatus::Healthy => "healthy",
+                HealthStatus::Degraded => "degraded",
+                HealthStatus::Unrecoverable => "unrecoverable",
+            },
+            available: health.available(),
+            total: health.total_shards,
+            required: health.required_shards,
+            missing: health.missing.clone(),
+            corrupted: health.corrupted.clone(),
+        }
+    }
+}
+
+impl From<&FileMetadata> for ManifestReport {
+    fn from(metadata: &FileMetadata) -> Self {
+        let params = metadata.params;
+        let encryption = match (
+            &metadata.quantum_encryption_metadata,
+            &metadata.encryption_metadata,
+        ) {
+            (Some(quantum), _) => {
+                let mut text = format!("{:?}, {:?} key", quantum.algorithm, quantum.key_derivation);
+                if let Some(segment_size) = quantum.stream_segment_size {
+                    text.push_str(&format!(", streamed in {segment_size}-byte segments"));
+                }
+                if let Some(wrapped) = &quantum.wrapped_key {
+                    text.push_str(&format!(", wrapped under {}", wrapped.master_key_id));
+                }
+                if let Some(split) = &quantum.key_split {
+                    text.push_str(&format!(
+                        ", split {} of {}",
+                        split.threshold,
+                        split.shares.len()
+                    ));
+                }
+                Some(text)
+            }
+            (None, Some(legacy)) => Some(format!("legacy, {:?} key", legacy.key_derivation)),
+            (None, None) => None,
+        };
+        Self {
+            file_id: hex::encode(metadata.file_id),
+            file_size: metadata.file_size,
+            filename: metadata
+                .local_metadata
+                .as_ref()
+                .and_then(|local| local.filename.clone()),
+            encryption_mode: params.map(|p| p.encryption_mode),
+            data_shards: params.map(|p| p.data_shards),
+            parity_shards: params.map(|p| p.parity_shards),
+            chunk_size: params.and_then(|p| p.chunk_size),
+            compression_level: params.and_then(|p| p.compression_level),
+            encryption,
+            signed: metadata.signature.is_some(),
+            parent_version: metadata.parent_version.map(hex::encode),
+            chunks: metadata
+                .chunks
+                .iter()
+                .map(|chunk| ChunkEntry {
+                    index: chunk.shard_index,
+                    chunk_id: hex::encode(chunk.chunk_id),
+                    size: chunk.size,
+                    shard_ids: chunk.shard_ids.iter().map(hex::encode).collect(),
+                })
+                .collect(),
+            health: None,
+        }
+    }
+}
+
+/// Work out what `path` holds and describe it
+pub fn inspect_file(path: &Path) -> Result<Report> {
+    let bytes = fs::read(path).map_err(CliError::io(path))?;
+    if bytes.starts_with(&wire::MAGIC) {
+        return inspect_containers(path, &bytes);
+    }
+    if bytes.first() == Some(&b'{') {
+        let metadata: FileMetadata = serde_json::from_slice(&bytes).map_err(|e| {
+            CliError::Invalid(format!("{}: not valid file metadata: {e}", path.display()))
+        })?;
+        return Ok(Report::Manifest(Box::new(ManifestReport::from(&metadata))));
+    }
+    let shard = Shard::from_bytes(&bytes).map_err(|_| {
+        CliError::Invalid(format!(
+            "{}: not a shard, manifest or file metadata",
+            path.display()
+        ))
+    })?;
+    Ok(Report::Shards {
+        format: "raw",
+        shards: vec![ShardEntry::new(0, &shard)],
+    })
+}
+
+/// Describe one container, or shard containers written back to back
+fn inspect_containers(path: &Path, bytes: &[u8]) -> Result<Report> {
+    let first_len = wire::container_len(bytes).map_err(CliError::wire(path))?;
+    let first = bytes.get(..first_len).unwrap_or(bytes);
+    let container = Container::from_bytes(first).map_err(CliError::wire(path))?;
+    match container.kind {
+        Kind::Manifest if first_len == bytes.len() => {
+            let metadata = FileMetadata::from_wire(bytes).map_err(CliError::wire(path))?;
+            Ok(Report::Manifest(Box::new(ManifestReport::from(&metadata))))
+        }
+        Kind::IdaDescriptor if first_len == bytes.len() => {
+            let descriptor = IDADescriptor::from_wire(bytes).map_err(CliError::wire(path))?;
+            Ok(Report::IdaDescriptor {
+                k: descriptor.k,
+                n: descriptor.n,
+                stripe_size: descriptor.stripe_size,
+                file_size: descriptor.file_size,
+                code: descriptor.code,
+                checksum: hex::encode(descriptor.checksum),
+            })
+        }
+        Kind::Shard => {
+            let mut reader = bytes;
+            let mut shards = Vec::new();
+            while !reader.is_empty() {
+                let position = shards.len();
+                let Some(container) = read_container(&mut reader) else {
+                    shards.push(ShardEntry::damaged(
+                        position,
+                        "truncated or unreadable container; the rest of the file is skipped",
+                    ));
+                    break;
+                };
+                shards.push(match Shard::from_wire(&container) {
+                    Ok(shard) => ShardEntry::new(position, &shard),
+                    Err(e) => ShardEntry::damaged(position, e),
+                });
+            }
+            Ok(Report::Shards {
+                format: "wire",
+                shards,
+            })
+        }
+        kind @ (Kind::FecShard | Kind::ShardManifest) => Err(CliError::Invalid(format!(
+            "{}: {kind:?} containers are written by the C ABI and not described",
+            path.display()
+        ))),
+        _ => Err(CliError::Invalid(format!(
+            "{}: only shard containers may be concatenated",
+            path.display()
+        ))),
+    }
+}
+
+/// Parse file metadata from a manifest container or JSON
+fn read_metadata(path: &Path) -> Result<FileMetadata> {
+    let bytes = fs::read(path).map_err(CliError::io(path))?;
+    if bytes.starts_with(&wire::MAGIC) {
+        FileMetadata::from_wire(&bytes).map_err(CliError::wire(path))
+    } else {
+        serde_json::from_slice(&bytes).map_err(|e| {
+            CliError::Invalid(format!("{}: not valid file metadata: {e}", path.display()))
+        })
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Report::Shards { format, shards } => {
+                let damaged = shards.iter().filter(|s| s.shard.is_none()).count();
+                writeln!(
+                    f,
+                    "{} shard(s), {} format, {} damaged",
+                    shards.len(),
+                    format,
+                    damaged
+                )?;
+                for entry in shards {
+                    let Some(shard) = &entry.shard else {
+                        let error = entry.error.as_deref().unwrap_or("unreadable");
+                        writeln!(f, "  #{:<4} damaged: {}", entry.position, error)?;
+                        continue;
+                    };
+                    writeln!(f, "  #{:<4} {}", entry.position, shard.cid)?;
+                    let mode = if shard.plaintext {
+                        "plaintext".to_string()
+                    } else {
+                        format!("{:?}", shard.encryption_mode)
+                    };
+                    writeln!(
+                        f,
+                        "        {}, {}+{} shares, {} bytes, header v{}",
+                        mode,
+                        shard.data_shards,
+                        shard.parity_shards,
+                        shard.data_size,
+                        shard.version
+                    )?;
+                    if let Some(expires_at) = shard.expires_at {
+                        writeln!(f, "        expires at {expires_at}")?;
+                    }
+                }
+                Ok(())
+            }
+            Report::Manifest(manifest) => manifest.fmt(f),
+            Report::IdaDescriptor {
+                k,
+                n,
+                stripe_size,
+                file_size,
+                code,
+                checksum,
+            } => {
+                writeln!(f, "IDA descriptor")?;
+                writeln!(f, "  code         {code}, {k} of {n} shares")?;
+                writeln!(f, "  stripe size  {stripe_size} bytes")?;
+                writeln!(f, "  file size    {file_size} bytes")?;
+                writeln!(f, "  checksum     {checksum}")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ManifestReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Manifest")?;
+        writeln!(f, "  file id      {}", self.file_id)?;
+        writeln!(f, "  file size    {} bytes", self.file_size)?;
+        if let Some(filename) = &self.filename {
+            writeln!(f, "  filename     {filename}")?;
+        }
+        if let (Some(k), Some(m)) = (self.data_shards, self.parity_shards) {
+            writeln!(f, "  fec          {k} data + {m} parity shares per chunk")?;
+        }
+        if let Some(mode) = self.encryption_mode {
+            writeln!(f, "  mode         {mode:?}")?;
+        }
+        writeln!(
+            f,
+            "  encryption   {}",
+            self.encryption.as_deref().unwrap_or("none")
+        )?;
+        if let Some(level) = self.compression_level {
+            writeln!(f, "  compression  level {level}")?;
+        }
+        if let Some(chunk_size) = self.chunk_size {
+            writeln!(f, "  chunk size   {chunk_size} bytes")?;
+        }
+        writeln!(
+            f,
+            "  signed       {}",
+            if self.signed { "yes" } else { "no" }
+        )?;
+        if let Some(parent) = &self.parent_version {
+            writeln!(f, "  parent       {parent}")?;
+        }
+        writeln!(f, "  chunks       {}", self.chunks.len())?;
+        for chunk in &self.chunks {
+            writeln!(
+                f,
+                "  chunk {:<5} {}  {} bytes",
+                chunk.index, chunk.chunk_id, chunk.size
+            )?;
+            for (share, cid) in chunk.shard_ids.iter().enumerate() {
+                writeln!(f, "    share {share:<3} {cid}")?;
+            }
+        }
+        match &self.health {
+            None => writeln!(f, "  health       not checked, no shards found"),
+            Some(health) => {
+                let count = |status| health.iter().filter(|h| h.status == status).count();
+                writeln!(
+                    f,
+                    "  health       {} healthy, {} degraded, {} unrecoverable",
+                    count("healthy"),
+                    count("degraded"),
+                    count("unrecoverable")
+                )?;
+                for (chunk, entry) in self.chunks.iter().zip(health) {
+                    if entry.status != "healthy" {
+                        writeln!(
+                            f,
+                            "    chunk {} {}: {} of {} shards, missing {:?}, corrupted {:?}",
+                            chunk.index,
+                            entry.status,
+                            entry.available,
+                            entry.total,
+                            entry.missing,
+                            entry.corrupted
+                        )?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::encode_file;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_inspect_shard_file_and_manifest() {
+        let dir = TempDir::new().unwrap();
+        let input = dir.path().join("data.bin");
+        let data: Vec<u8> = (0..=255).cycle().take(5000).collect();
+        fs::write(&input, data).unwrap();
+        let report = encode_file(&input, dir.path(), 3, 2, 2000).unwrap();
+
+        let Report::Shards { format, shards } = inspect_file(&report.shards[4]).unwrap() else {
+            panic!("expected shards");
+        };
+        assert_eq!(format, "wire");
+        assert_eq!(shards.len(), 3);
+        let info = shards[0].shard.as_ref().unwrap();
+        assert_eq!((info.data_shards, info.parity_shards), (3, 2));
+
+        // Corrupt the last byte of the second container of one shard file
+        let mut bytes = fs::read(&report.shards[0]).unwrap();
+        let first = wire::container_len(&bytes).unwrap();
+        let second = wire::container_len(&bytes[first..]).unwrap();
+        bytes[first + second - 1] ^= 0xff;
+        fs::write(&report.shards[0], &bytes).unwrap();
+        let Report::Shards { shards, .. } = inspect_file(&report.shards[0]).unwrap() else {
+            panic!("expected shards");
+        };
+        assert!(shards[1].shard.is_none() && shards[1].error.is_some());
+        assert!(shards[2].shard.is_some());
+
+        let Report::Manifest(manifest) = inspect_file(&report.manifest).unwrap() else {
+            panic!("expected a manifest");
+        };
+        assert_eq!(manifest.file_size, 5000);
+        assert_eq!(manifest.chunks.len(), 3);
+        assert_eq!(manifest.encryption, None);
+
+        let metadata = read_metadata(&report.manifest).unwrap();
+        let health = shard_file_health(&metadata, &report.shards);
+        assert_eq!(health[1].corrupted, Vec::<usize>::new());
+        assert_eq!(health[1].missing, vec![0]);
+        assert_eq!(health[2].available(), 5);
+        let json = serde_json::to_value(HealthEntry::from(&health[1])).unwrap();
+        assert_eq!(json["status"], "degraded");
+    }
+}
@@ -0,0 +1,119 @@
+//! `saorsa-fec` command-line tool
+//!
+//! Built with the `cli` feature. Each subcommand lives in its own module,
+//! which provides the clap [`Command`] and the function running it.
+//!
+//! The tool exits with status 1 on errors and [`EXIT_DAMAGED`] when `scrub`
+//! or `repair` leave damage behind, so scheduled runs can alert on either.
+
+use clap::Command;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use thiserror::Error;
+
+mod archive;
+mod bench;
+mod inspect;
+mod scrub;
+mod store;
+
+/// Exit status when stored data is found damaged beyond what was repaired
+pub const EXIT_DAMAGED: u8 = 2;
+
+/// Errors reported by the tool
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error("{path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error(transparent)]
+    Fec(#[from] saorsa_fec::Error),
+
+    #[error("{path}: {source}")]
+    Wire {
+        path: PathBuf,
+        source: saorsa_fec::wire::WireError,
+    },
+
+    #[error("{0}")]
+    Invalid(String),
+
+    /// Stored data is damaged; exits with [`EXIT_DAMAGED`]
+    #[error("{0}")]
+    Damaged(String),
+}
+
+pub type Result<T> = std::result::Result<T, CliError>;
+
+impl CliError {
+    /// Wrap an IO error on `path`
+    pub fn io(path: &Path) -> impl FnOnce(std::io::Error) -> CliError + '_ {
+        move |source| CliError::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    /// Wrap a container parse error for `path`
+    pub fn wire(path: &Path) -> impl FnOnce(saorsa_fec::wire::WireError) -> CliError + '_ {
+        move |source| CliError::Wire {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
+
+fn command() -> Command {
+    Command::new("saorsa-fec")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Erasure-code files into shards and store them")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(archive::encode_command())
+        .subcommand(archive::decode_command())
+        .subcommand(store::put_command())
+        .subcommand(store::get_command())
+        .subcommand(inspect::command())
+        .subcommand(scrub::scrub_command())
+        .subcommand(scrub::repair_command())
+        .subcommand(bench::command())
+}
+
+fn main() -> ExitCode {
+    let matches = command().get_matches();
+    let result = match matches.subcommand() {
+        Some(("encode", args)) => archive::encode(args),
+        Some(("decode", args)) => archive::decode(args),
+        Some(("put", args)) => store::put(args),
+        Some(("get", args)) => store::get(args),
+        Some(("inspect", args)) => inspect::run(args),
+        Some(("scrub", args)) => scrub::scrub(args),
+        Some(("repair", args)) => scrub::repair(args),
+        Some(("bench", args)) => bench::run(args),
+        _ => unreachable!("clap requires a known subcommand"),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e @ CliError::Damaged(_)) => {
+            eprintln!("{e}");
+            ExitCode::from(EXIT_DAMAGED)
+        }
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_definition_is_valid() {
+        command().debug_assert();
+    }
+}
@@ -0,0 +1,281 @@
+//! `scrub` and `repair`: checking and restoring a chunk store
+//!
+//! `scrub` reads every shard in the configured backend and checks it against
+//! its CID. It needs no manifests, so it finds corrupt shards but not missing
+//! ones. `repair` checks the files named by manifests and rebuilds their
+//! missing or corrupt shards through [`StoragePipeline::repair_file`]. Both
+//! keep going past damage and exit with [`EXIT_DAMAGED`](crate::EXIT_DAMAGED)
+//! when some remains.
+
+use clap::{value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command};
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use saorsa_fec::storage::{Cid, StorageBackend};
+use saorsa_fec::{EncryptionMode, FecError, StoragePipeline};
+
+use crate::archive::read_manifest;
+use crate::{store, CliError, Result};
+
+/// Shards listed from the backend at a time
+const PAGE_SIZE: usize = 1000;
+
+pub fn scrub_command() -> Command {
+    store::backend_args(
+        Command::new("scrub")
+            .about("Verify the checksum of every shard in a store")
+            .arg(
+                Arg::new("store")
+                    .value_name("STORE")
+                    .required(true)
+                    .help("Configuration file (TOML, YAML or JSON) describing the backend")
+                    .value_parser(value_parser!(PathBuf)),
+            ),
+    )
+}
+
+pub fn repair_command() -> Command {
+    store::backend_args(
+        Command::new("repair")
+            .about("Rebuild the missing and corrupt shards of stored files")
+            .arg(
+                Arg::new("config")
+                    .long("config")
+                    .short('c')
+                    .value_name("PATH")
+                    .required(true)
+                    .help("Configuration file (TOML, YAML or JSON) describing the backend")
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(
+                Arg::new("manifest")
+                    .value_name("MANIFEST")
+                    .help("Manifests of the files to repair")
+                    .action(ArgAction::Append)
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(
+                Arg::new("all")
+                    .long("all")
+                    .value_name("DIR")
+                    .num_args(0..=1)
+                    .default_missing_value(".")
+                    .help("Repair every .manifest file in DIR [default: .]")
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .group(
+                ArgGroup::new("objects")
+                    .args(["manifest", "all"])
+                    .required(true),
+            ),
+    )
+}
+
+pub fn scrub(args: &ArgMatches) -> Result<()> {
+    let store = args.get_one::<PathBuf>("store").expect("required");
+    let report = store::with_backend(store, args, |_, backend| async move {
+        scrub_backend(backend.as_ref()).await
+    })?;
+
+    println!(
+        "Scrubbed {} shards: {} intact, {} corrupt, {} unreadable",
+        report.checked,
+        report.checked - report.corrupt - report.unreadable,
+        report.corrupt,
+        report.unreadable
+    );
+    if report.corrupt + report.unreadable > 0 {
+        return Err(CliError::Damaged(format!(
+            "{} damaged shards in {}; run `saorsa-fec repair` to rebuild them",
+            report.corrupt + report.unreadable,
+            store.display()
+        )));
+    }
+    Ok(())
+}
+
+pub fn repair(args: &ArgMatches) -> Result<()> {
+    let config_path = args.get_one::<PathBuf>("config").expect("required");
+    let manifests = match args.get_one::<PathBuf>("all") {
+        Some(dir) => manifests_in(dir)?,
+        None => args
+            .get_many::<PathBuf>("manifest")
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect(),
+    };
+    let total = manifests.len();
+
+    let damaged = store::with_backend(config_path, args, |config, backend| async move {
+        // Repair never encrypts or decrypts, so it runs without the keystore
+        // a ConvergentWithSecret configuration would otherwise require
+        let config = config.with_encryption_mode(EncryptionMode::RandomKey);
+        let pipeline = StoragePipeline::builder(config, backend).build()?;
+        let mut damaged = 0;
+        for (i, path) in manifests.iter().enumerate() {
+            let outcome = match read_manifest(path) {
+                Ok(metadata) => pipeline
+                    .repair_file(&metadata)
+                    .await
+                    .map_err(CliError::from),
+                Err(e) => Err(e),
+            };
+            let summary = match outcome {
+                Ok(report) if report.chunks_unrecoverable > 0 => {
+                    damaged += 1;
+                    format!(
+                        "{} chunks unrecoverable, {} repaired, {} shards restored",
+                        report.chunks_unrecoverable, report.chunks_repaired, report.shards_restored
+                    )
+                }
+                Ok(report) if report.chunks_repaired > 0 => format!(
+                    "repaired {} chunks, {} shards restored",
+                    report.chunks_repaired, report.shards_restored
+                ),
+                Ok(_) => "healthy".to_string(),
+                Err(e) => {
+                    damaged += 1;
+                    format!("failed: {e}")
+                }
+            };
+            println!("[{}/{}] {}: {}", i + 1, total, path.display(), summary);
+        }
+        Ok(damaged)
+    })?;
+
+    if damaged > 0 {
+        return Err(CliError::Damaged(format!(
+            "{damaged} of {total} files could not be fully repaired"
+        )));
+    }
+    Ok(())
+}
+
+/// Outcome of checking every shard in a backend
+#[derive(Debug, Default)]
+struct ScrubReport {
+    checked: usize,
+    corrupt: usize,
+    unreadable: usize,
+}
+
+/// Verify every shard in `backend`, listing the damaged ones
+async fn scrub_backend(backend: &dyn StorageBackend) -> Result<ScrubReport> {
+    let progress = std::io::stderr().is_terminal();
+    let mut report = ScrubReport::default();
+    let mut after: Option<Cid> = None;
+    loop {
+        let page = backend
+            .list_shards_paged(after.as_ref(), PAGE_SIZE)
+            .await
+            .map_err(saorsa_fec::Error::from)?;
+        for cid in &page.cids {
+            match backend.get_shard_verified(cid).await {
+                Ok(_) => {}
+                Err(FecError::CorruptShard { .. }) => {
+                    report.corrupt += 1;
+                    println!("corrupt {}", cid.to_hex());
+                }
+                Err(e) => {
+                    report.unreadable += 1;
+                    println!("unreadable {}: {e}", cid.to_hex());
+                }
+            }
+            report.checked += 1;
+            if progress {
+                eprint!("\rchecked {} shards", report.checked);
+                let _ = std::io::stderr().flush();
+            }
+        }
+        match page.next {
+            Some(next) => after = Some(next),
+            None => break,
+        }
+    }
+    if progress {
+        eprintln!();
+    }
+    Ok(report)
+}
+
+/// Manifest files in `dir`, sorted
+fn manifests_in(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir).map_err(CliError::io(dir))? {
+        let path = entry.map_err(CliError::io(dir))?.path();
+        if path.extension().is_some_and(|ext| ext == "manifest") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use saorsa_fec::config::{Config, StorageBackend as BackendConfig};
+    use saorsa_fec::wire::WireFormat;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_scrub_finds_and_repair_restores_damaged_shards() {
+        let dir = TempDir::new().unwrap();
+        let config_path = dir.path().join("store.toml");
+        let mut config = Config::default().with_fec_params(4, 2);
+        config.storage.backend = BackendConfig::Local {
+            path: dir.path().join("shards").to_string_lossy().into_owned(),
+        };
+        config.to_file(&config_path).unwrap();
+        let path = |p: &Path| p.to_string_lossy().into_owned();
+        let run = |argv: &[&str]| {
+            let matches = crate::command().get_matches_from(argv);
+            match matches.subcommand().unwrap() {
+                ("scrub", args) => scrub(args),
+                ("repair", args) => repair(args),
+                _ => unreachable!(),
+            }
+        };
+
+        // Store a file, then corrupt one of its shards and delete another
+        let data: Vec<u8> = (0..=255).cycle().take(20_000).collect();
+        let args = crate::command().get_matches_from(["saorsa-fec", "scrub", &path(&config_path)]);
+        let args = args.subcommand_matches("scrub").unwrap();
+        let metadata = store::with_backend(&config_path, args, |config, backend| async move {
+            let mut pipeline = StoragePipeline::builder(config, backend.clone()).build()?;
+            let metadata = pipeline.process_file([7u8; 32], &data, None).await?;
+            let ids = &metadata.chunks[0].shard_ids;
+            let (first, second) = (Cid::new(ids[0]), Cid::new(ids[1]));
+            let mut shard = backend.get_shard(&first).await.unwrap();
+            shard.data[0] ^= 0xff;
+            backend.put_shard(&first, &shard).await.unwrap();
+            backend.delete_shard(&second).await.unwrap();
+            Ok(metadata)
+        })
+        .unwrap();
+        let manifests = dir.path().join("manifests");
+        fs::create_dir(&manifests).unwrap();
+        fs::write(manifests.join("data.manifest"), metadata.to_wire().unwrap()).unwrap();
+
+        let scrub_argv = ["saorsa-fec", "scrub", &path(&config_path)];
+        assert!(matches!(run(&scrub_argv), Err(CliError::Damaged(_))));
+
+        let repair_argv = [
+            "saorsa-fec",
+            "repair",
+            "-c",
+            &path(&config_path),
+            "--all",
+            &path(&manifests),
+        ];
+        run(&repair_argv).unwrap();
+        run(&scrub_argv).unwrap();
+
+        // A manifest that cannot be read leaves the run damaged
+        let missing = path(&manifests.join("missing.manifest"));
+        let argv = ["saorsa-fec", "repair", "-c", &path(&config_path), &missing];
+        assert!(matches!(run(&argv), Err(CliError::Damaged(_))));
+    }
+}
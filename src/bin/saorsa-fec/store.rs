@@ -0,0 +1,346 @@
+//! `put` and `get`: the full storage pipeline against a configured backend
+//!
+//! The backend, FEC and encryption settings come from a configuration file
+//! read with [`Config::from_file`] and built with [`BackendFactory`]. Content
+//! keys are wrapped under a master key in a passphrase-protected
+//! [`FileKeystore`], which also holds the convergence secret, so `get` can
+//! decrypt in a later run. `put` writes the file's manifest for `get`.
+//! Network backends need the `quic` feature, which adds the options to reach
+//! the nodes.
+
+use clap::{value_parser, Arg, ArgMatches, Command};
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use saorsa_fec::storage::{BackendFactory, StorageBackend};
+use saorsa_fec::wire::WireFormat;
+use saorsa_fec::{Config, FileKeystore, Keystore, Meta, QuantumCryptoEngine, StoragePipeline};
+
+use crate::archive::read_manifest;
+use crate::{CliError, Result};
+
+/// Variable holding the keystore passphrase
+pub const PASSPHRASE_VAR: &str = "SAORSA_KEYSTORE_PASSPHRASE";
+/// Keystore file used unless `--keystore` is given, next to the configuration
+const DEFAULT_KEYSTORE: &str = "saorsa-fec.keystore";
+/// Keystore entry content keys are wrapped under
+const MASTER_KEY_ID: &str = "cli-master-key";
+
+pub fn put_command() -> Command {
+    store_args(
+        Command::new("put")
+            .about("Store a file through the configured pipeline and write its manifest")
+            .arg(
+                Arg::new("file")
+                    .value_name("FILE")
+                    .required(true)
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(
+                Arg::new("manifest")
+                    .long("manifest")
+                    .value_name("PATH")
+                    .help("Where to write the manifest [default: FILE.manifest]")
+                    .value_parser(value_parser!(PathBuf)),
+            ),
+    )
+}
+
+pub fn get_command() -> Command {
+    store_args(
+        Command::new("get")
+            .about("Retrieve a stored file from its manifest")
+            .arg(
+                Arg::new("manifest")
+                    .value_name("MANIFEST")
+                    .required(true)
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(
+                Arg::new("output")
+                    .long("output")
+                    .short('o')
+                    .value_name("PATH")
+                    .required(true)
+                    .help("Where to write the retrieved file")
+                    .value_parser(value_parser!(PathBuf)),
+            ),
+    )
+}
+
+/// Options shared by every command running the pipeline
+pub fn store_args(command: Command) -> Command {
+    let command = command
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .short('c')
+                .value_name("PATH")
+                .required(true)
+                .help("Configuration file (TOML, YAML or JSON) describing the backend")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("keystore")
+                .long("keystore")
+                .value_name("PATH")
+                .help(format!(
+                    "Keystore, created on first use [default: {DEFAULT_KEYSTORE} next to the \
+                     configuration]"
+                ))
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("passphrase-file")
+                .long("passphrase-file")
+                .value_name("PATH")
+                .help(format!(
+                    "File holding the keystore passphrase [default: ${PASSPHRASE_VAR}]"
+                ))
+                .value_parser(value_parser!(PathBuf)),
+        );
+    backend_args(command)
+}
+
+/// Options reaching the configured backend
+pub fn backend_args(command: Command) -> Command {
+    #[cfg(feature = "quic")]
+    let command = quic::args(command);
+    command
+}
+
+pub fn put(args: &ArgMatches) -> Result<()> {
+    let input = args.get_one::<PathBuf>("file").expect("required");
+    let manifest = match args.get_one::<PathBuf>("manifest") {
+        Some(path) => path.clone(),
+        None => {
+            let mut path = input.clone().into_os_string();
+            path.push(".manifest");
+            PathBuf::from(path)
+        }
+    };
+    let data = fs::read(input).map_err(CliError::io(input))?;
+    let file_id: [u8; 32] = blake3::hash(&data).into();
+    let mut meta = Meta::new();
+    if let Some(name) = input.file_name() {
+        meta = meta.with_filename(name.to_string_lossy());
+    }
+
+    let metadata = with_pipeline(args, |mut pipeline| async move {
+        Ok(pipeline.process_file(file_id, &data, Some(meta)).await?)
+    })?;
+    let bytes = metadata.to_wire().map_err(CliError::wire(&manifest))?;
+    fs::write(&manifest, bytes).map_err(CliError::io(&manifest))?;
+
+    println!("{}", hex::encode(metadata.file_id));
+    println!("{}", manifest.display());
+    Ok(())
+}
+
+pub fn get(args: &ArgMatches) -> Result<()> {
+    let manifest = args.get_one::<PathBuf>("manifest").expect("required");
+    let output = args.get_one::<PathBuf>("output").expect("required");
+    let metadata = read_manifest(manifest)?;
+
+    let data = with_pipeline(args, |pipeline| async move {
+        Ok(pipeline.retrieve_file(&metadata).await?)
+    })?;
+    fs::write(output, &data).map_err(CliError::io(output))?;
+    println!("Retrieved {} bytes to {}", data.len(), output.display());
+    Ok(())
+}
+
+/// Pipeline over the configured backend
+pub type Pipeline = StoragePipeline<Arc<dyn StorageBackend>>;
+
+/// Build the pipeline described by `args` and run `task` with it
+pub fn with_pipeline<T, F, Fut>(args: &ArgMatches, task: F) -> Result<T>
+where
+    F: FnOnce(Pipeline) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let config_path = args.get_one::<PathBuf>("config").expect("required");
+    let keystore_path = match args.get_one::<PathBuf>("keystore") {
+        Some(path) => path.clone(),
+        None => config_path.with_file_name(DEFAULT_KEYSTORE),
+    };
+    let passphrase = match args.get_one::<PathBuf>("passphrase-file") {
+        Some(path) => read_passphrase(path)?,
+        None => std::env::var(PASSPHRASE_VAR).map_err(|_| {
+            CliError::Invalid(format!(
+                "set {PASSPHRASE_VAR} or pass --passphrase-file to unlock the keystore {}",
+                keystore_path.display()
+            ))
+        })?,
+    };
+    let keystore = open_keystore(&keystore_path, &passphrase)?;
+
+    with_backend(config_path, args, |config, backend| async move {
+        let crypto = QuantumCryptoEngine::new().with_master_key(keystore.clone(), MASTER_KEY_ID);
+        let pipeline = StoragePipeline::builder(config, backend)
+            .crypto_provider(crypto)
+            .keystore(keystore)
+            .build()?;
+        task(pipeline).await
+    })
+}
+
+/// Build the backend configured in `config_path` and run `task` with it
+pub fn with_backend<T, F, Fut>(config_path: &Path, args: &ArgMatches, task: F) -> Result<T>
+where
+    F: FnOnce(Config, Arc<dyn StorageBackend>) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let config = Config::from_file(config_path).map_err(saorsa_fec::Error::from)?;
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|source| CliError::Io {
+            path: PathBuf::from("tokio runtime"),
+            source,
+        })?;
+    runtime.block_on(async {
+        let factory = BackendFactory::new();
+        #[cfg(feature = "quic")]
+        let factory = quic::with_transport(factory, args)?;
+        #[cfg(not(feature = "quic"))]
+        let _ = args;
+        let backend = factory
+            .build_storage(&config.storage)
+            .await
+            .map_err(saorsa_fec::Error::from)?;
+        task(config, backend).await
+    })
+}
+
+/// The passphrase in `path`, without its trailing line break
+fn read_passphrase(path: &Path) -> Result<String> {
+    let contents = fs::read_to_string(path).map_err(CliError::io(path))?;
+    Ok(contents.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Open the keystore at `path`, creating it if missing
+fn open_keystore(path: &Path, passphrase: &str) -> Result<Arc<dyn Keystore>> {
+    let keystore = if path.exists() {
+        FileKeystore::open(path, passphrase)
+    } else {
+        FileKeystore::create(path, passphrase)
+    };
+    Ok(Arc::new(keystore.map_err(saorsa_fec::Error::from)?))
+}
+
+/// Reaching network backend nodes over QUIC
+#[cfg(feature = "quic")]
+mod quic {
+    use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use saorsa_fec::network::quic::{CertificateDer, QuicClientConfig, QuicTransport};
+    use saorsa_fec::storage::BackendFactory;
+
+    use crate::{CliError, Result};
+
+    pub fn args(command: Command) -> Command {
+        command
+            .arg(
+                Arg::new("trust-cert")
+                    .long("trust-cert")
+                    .value_name("DER")
+                    .help("Certificate of a storage node (DER), for network backends")
+                    .action(ArgAction::Append)
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(
+                Arg::new("auth-token")
+                    .long("auth-token")
+                    .value_name("HEX")
+                    .help("Pre-shared token of the storage nodes, 64 hex digits"),
+            )
+    }
+
+    /// Give `factory` a QUIC transport when a token is configured
+    pub fn with_transport(factory: BackendFactory, args: &ArgMatches) -> Result<BackendFactory> {
+        let Some(token) = args.get_one::<String>("auth-token") else {
+            return Ok(factory);
+        };
+        let auth_token = hex::decode(token)
+            .ok()
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .ok_or_else(|| CliError::Invalid("--auth-token must be 64 hex digits".into()))?;
+        let mut trusted_certs = Vec::new();
+        for path in args.get_many::<PathBuf>("trust-cert").into_iter().flatten() {
+            let der = std::fs::read(path).map_err(CliError::io(path))?;
+            trusted_certs.push(CertificateDer::from(der));
+        }
+        let transport = QuicTransport::new(QuicClientConfig {
+            trusted_certs,
+            auth_token,
+            server_name: None,
+        })
+        .map_err(saorsa_fec::Error::from)?;
+        Ok(factory.with_transport(Arc::new(transport)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_put_then_get_in_separate_pipelines() {
+        let dir = TempDir::new().unwrap();
+        let config_path = dir.path().join("store.toml");
+        let mut config = Config::default().with_fec_params(4, 2);
+        config.storage.backend = saorsa_fec::config::StorageBackend::Local {
+            path: dir.path().join("shards").to_string_lossy().into_owned(),
+        };
+        config.to_file(&config_path).unwrap();
+        // Few KDF rounds keep the test fast; opening reads them from the file
+        let passphrase_file = dir.path().join("passphrase");
+        fs::write(&passphrase_file, "test passphrase\n").unwrap();
+        let keystore_path = dir.path().join(DEFAULT_KEYSTORE);
+        FileKeystore::create_with_iterations(&keystore_path, "test passphrase", 1_000).unwrap();
+
+        let input = dir.path().join("notes.txt");
+        fs::write(&input, b"kept across runs").unwrap();
+        let manifest = dir.path().join("notes.manifest");
+        let output = dir.path().join("restored.txt");
+        let path = |p: &Path| p.to_string_lossy().into_owned();
+
+        let cli = crate::command();
+        let put_args = cli.clone().get_matches_from([
+            "saorsa-fec",
+            "put",
+            "--config",
+            &path(&config_path),
+            "--passphrase-file",
+            &path(&passphrase_file),
+            "--manifest",
+            &path(&manifest),
+            &path(&input),
+        ]);
+        put(put_args.subcommand_matches("put").unwrap()).unwrap();
+
+        let get_args = cli.get_matches_from([
+            "saorsa-fec",
+            "get",
+            "-c",
+            &path(&config_path),
+            "--passphrase-file",
+            &path(&passphrase_file),
+            &path(&manifest),
+            "-o",
+            &path(&output),
+        ]);
+        get(get_args.subcommand_matches("get").unwrap()).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), b"kept across runs");
+
+        // The master key wrapping the content key was persisted
+        let keystore = FileKeystore::open(&keystore_path, "test passphrase").unwrap();
+        assert!(keystore.get_secret(MASTER_KEY_ID).unwrap().is_some());
+    }
+}
@@ -0,0 +1,176 @@
+//! Synchronous storage pipeline
+//!
+//! With the `blocking` feature, [`StoragePipeline`] wraps the async
+//! [`crate::StoragePipeline`] together with a single-threaded tokio runtime
+//! it owns, for CLI tools and applications that have no runtime of their own:
+//!
+//! ```no_run
+//! use saorsa_fec::blocking::StoragePipeline;
+//! use saorsa_fec::Config;
+//!
+//! let mut pipeline = StoragePipeline::local(Config::default(), "./shards".into())?;
+//! let metadata = pipeline.process_file([1u8; 32], b"hello", None)?;
+//! assert_eq!(pipeline.retrieve_file(&metadata)?, b"hello");
+//! # Ok::<(), saorsa_fec::Error>(())
+//! ```
+//!
+//! The calls block the current thread, so they must not be made from inside
+//! an async runtime; use the async pipeline there.
+
+use std::path::PathBuf;
+use tokio::runtime::{Builder, Runtime};
+
+use crate::gc::CollectionReport;
+use crate::integrity::{FileHealth, RepairReport};
+use crate::metadata::FileMetadata;
+use crate::pipeline::{Meta, Result};
+use crate::storage::{LocalStorage, MemoryStorage, StorageBackend};
+use crate::{Config, FecError};
+
+/// A [`crate::StoragePipeline`] driven from synchronous code
+pub struct StoragePipeline<B: StorageBackend> {
+    inner: crate::StoragePipeline<B>,
+    runtime: Runtime,
+}
+
+impl<B: StorageBackend + 'static> StoragePipeline<B> {
+    /// Create a pipeline over `backend` with the default components
+    pub fn new(config: Config, backend: B) -> Result<Self> {
+        Self::from_async(crate::StoragePipeline::builder(config, backend).build()?)
+    }
+
+    /// Drive a pipeline built with [`crate::StoragePipeline::builder`]
+    pub fn from_async(inner: crate::StoragePipeline<B>) -> Result<Self> {
+        Ok(Self {
+            inner,
+            runtime: runtime()?,
+        })
+    }
+
+    /// The wrapped async pipeline, for its synchronous methods such as
+    /// [`rollback`](crate::StoragePipeline::rollback) or
+    /// [`stats`](crate::StoragePipeline::stats)
+    pub fn inner(&self) -> &crate::StoragePipeline<B> {
+        &self.inner
+    }
+
+    /// Mutable access to the wrapped async pipeline
+    pub fn inner_mut(&mut self) -> &mut crate::StoragePipeline<B> {
+        &mut self.inner
+    }
+
+    /// Unwrap the async pipeline, dropping the runtime
+    pub fn into_inner(self) -> crate::StoragePipeline<B> {
+        self.inner
+    }
+
+    /// Encrypt, encode and store a file; see
+    /// [`crate::StoragePipeline::process_file`]
+    pub fn process_file(
+        &mut self,
+        file_id: [u8; 32],
+        data: &[u8],
+        meta: Option<Meta>,
+    ) -> Result<FileMetadata> {
+        self.runtime
+            .block_on(self.inner.process_file(file_id, data, meta))
+    }
+
+    /// Retrieve and decrypt a file
+    pub fn retrieve_file(&self, meta: &FileMetadata) -> Result<Vec<u8>> {
+        self.runtime.block_on(self.inner.retrieve_file(meta))
+    }
+
+    /// Retrieve `len` bytes of a file starting at `offset`
+    pub fn retrieve_range(&self, meta: &FileMetadata, offset: u64, len: usize) -> Result<Vec<u8>> {
+        self.runtime
+            .block_on(self.inner.retrieve_range(meta, offset, len))
+    }
+
+    /// Check every shard of a file without decoding it
+    pub fn verify_file(&self, meta: &FileMetadata) -> Result<FileHealth> {
+        self.runtime.block_on(self.inner.verify_file(meta))
+    }
+
+    /// Rebuild and rewrite any missing or corrupted shards of a file
+    pub fn repair_file(&self, meta: &FileMetadata) -> Result<RepairReport> {
+        self.runtime.block_on(self.inner.repair_file(meta))
+    }
+
+    /// Run garbage collection
+    pub fn run_gc(&self) -> Result<CollectionReport> {
+        self.runtime.block_on(self.inner.run_gc())
+    }
+}
+
+impl StoragePipeline<LocalStorage> {
+    /// Create a pipeline keeping shards in a local directory
+    pub fn local(config: Config, path: PathBuf) -> Result<Self> {
+        let runtime = runtime()?;
+        let backend = runtime.block_on(LocalStorage::new(path))?;
+        Ok(Self {
+            inner: crate::StoragePipeline::builder(config, backend).build()?,
+            runtime,
+        })
+    }
+}
+
+impl StoragePipeline<MemoryStorage> {
+    /// Create a pipeline keeping shards in memory
+    pub fn in_memory(config: Config) -> Result<Self> {
+        Self::new(config, MemoryStorage::new())
+    }
+}
+
+fn runtime() -> Result<Runtime> {
+    Ok(Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(FecError::Io)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HealthStatus;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_blocking_pipeline_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::default().with_fec_params(4, 2);
+        let mut pipeline = StoragePipeline::local(config, temp_dir.path().to_path_buf()).unwrap();
+
+        let data: Vec<u8> = (0..=255).cycle().take(5000).collect();
+        let metadata = pipeline.process_file([2u8; 32], &data, None).unwrap();
+        assert_eq!(pipeline.retrieve_file(&metadata).unwrap(), data);
+        assert_eq!(
+            pipeline.retrieve_range(&metadata, 10, 5).unwrap(),
+            &data[10..15]
+        );
+        assert_eq!(
+            pipeline.verify_file(&metadata).unwrap().status(),
+            HealthStatus::Healthy
+        );
+    }
+
+    #[test]
+    fn test_blocking_pipeline_in_memory_repair() {
+        let mut pipeline =
+            StoragePipeline::in_memory(Config::default().with_fec_params(4, 2)).unwrap();
+        let metadata = pipeline
+            .process_file([3u8; 32], &[9u8; 2000], None)
+            .unwrap();
+
+        let lost = crate::storage::Cid::new(metadata.chunks[0].shard_ids[5]);
+        let backend = pipeline.inner().backend().clone();
+        pipeline
+            .runtime
+            .block_on(backend.delete_shard(&lost))
+            .unwrap();
+
+        let report = pipeline.repair_file(&metadata).unwrap();
+        assert_eq!(report.shards_restored, 1);
+        assert_eq!(pipeline.retrieve_file(&metadata).unwrap(), vec![9u8; 2000]);
+    }
+}
@@ -0,0 +1,98 @@
+//! Bloom filter for cheap negative membership checks
+//!
+//! A [`BloomFilter`] answers "definitely absent" or "possibly present" for a
+//! set of byte strings using a fixed bit array. It never forgets an item, so
+//! removals are not supported; rebuild the filter from the live set instead.
+
+use serde::{Deserialize, Serialize};
+
+/// Fixed-size Bloom filter over byte strings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    hashes: u32,
+    capacity: usize,
+    items: usize,
+}
+
+impl BloomFilter {
+    /// Filter sized for `capacity` items at a false positive rate of `fp_rate`
+    pub fn with_capacity(capacity: usize, fp_rate: f64) -> Self {
+        let capacity = capacity.max(1);
+        let fp_rate = fp_rate.clamp(1e-9, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-(capacity as f64) * fp_rate.ln() / (ln2 * ln2)).ceil() as usize;
+        let words = bits.div_ceil(64).max(1);
+        let hashes = ((words * 64) as f64 / capacity as f64 * ln2).round() as u32;
+        Self {
+            bits: vec![0; words],
+            hashes: hashes.clamp(1, 16),
+            capacity,
+            items: 0,
+        }
+    }
+
+    /// Add `item` to the set
+    pub fn insert(&mut self, item: &[u8]) {
+        for bit in self.positions(item) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+        self.items += 1;
+    }
+
+    /// `false` if `item` was never inserted, `true` if it may have been
+    pub fn might_contain(&self, item: &[u8]) -> bool {
+        self.positions(item)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Number of insertions so far
+    pub fn len(&self) -> usize {
+        self.items
+    }
+
+    /// Whether nothing has been inserted
+    pub fn is_empty(&self) -> bool {
+        self.items == 0
+    }
+
+    /// Number of items the filter was sized for
+    ///
+    /// Past this the false positive rate climbs above the one requested.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Bit positions for `item`, by double hashing one BLAKE3 digest
+    fn positions(&self, item: &[u8]) -> impl Iterator<Item = usize> {
+        let digest = blake3::hash(item);
+        let bytes = digest.as_bytes();
+        let mut h1 = [0u8; 8];
+        let mut h2 = [0u8; 8];
+        h1.copy_from_slice(&bytes[..8]);
+        h2.copy_from_slice(&bytes[8..16]);
+        let h1 = u64::from_le_bytes(h1);
+        let h2 = u64::from_le_bytes(h2) | 1;
+        let bits = (self.bits.len() * 64) as u64;
+        (0..self.hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % bits) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bloom_filter_has_no_false_negatives() {
+        let mut filter = BloomFilter::with_capacity(1000, 0.01);
+        for i in 0u32..1000 {
+            filter.insert(&i.to_le_bytes());
+        }
+        assert!((0u32..1000).all(|i| filter.might_contain(&i.to_le_bytes())));
+
+        let false_positives = (1000u32..11000)
+            .filter(|i| filter.might_contain(&i.to_le_bytes()))
+            .count();
+        assert!(false_positives < 300, "{false_positives} false positives");
+    }
+}
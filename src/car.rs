@@ -0,0 +1,337 @@
+// Copyright 2024 Saorsa Labs
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! # CAR archive export and import
+//!
+//! Moves a file's shards between a [`StorageBackend`] and the Content
+//! Addressable aRchive format used by IPFS tooling. Every shard becomes a
+//! `raw` block addressed by a CIDv1 with a BLAKE3 multihash, which is exactly
+//! the shard's own [`Cid`], so other tools can verify blocks without knowing
+//! anything about saorsa-fec. The file metadata is stored as a JSON `raw`
+//! block and listed as the archive's single root.
+//!
+//! [`export_car`] writes CARv2 without an index; [`import_car`] reads both
+//! CARv1 and CARv2.
+
+use crate::storage::{Cid, FileMetadata, Shard, StorageBackend};
+use crate::FecError;
+
+/// Fixed pragma opening every CARv2 archive
+const CARV2_PRAGMA: [u8; 11] = [
+    0x0a, 0xa1, 0x67, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x02,
+];
+/// Size of the CARv2 header following the pragma
+const CARV2_HEADER_SIZE: usize = 40;
+/// Multicodec for raw binary blocks
+const CODEC_RAW: u64 = 0x55;
+/// Multihash code for BLAKE3 with a 32-byte digest
+const MULTIHASH_BLAKE3: u64 = 0x1e;
+
+/// Export the shards of `metadata` and the metadata itself as a CARv2 archive
+pub async fn export_car(
+    backend: &dyn StorageBackend,
+    metadata: &FileMetadata,
+) -> Result<Vec<u8>, FecError> {
+    let manifest = serde_json::to_vec(metadata)
+        .map_err(|e| FecError::Backend(format!("Failed to serialize metadata: {}", e)))?;
+    let root = Cid::from_data(&manifest);
+
+    let mut payload = Vec::new();
+    let header = encode_header(&root);
+    write_varint(&mut payload, header.len() as u64);
+    payload.extend_from_slice(&header);
+    write_block(&mut payload, &root, &manifest);
+
+    let mut written = std::collections::HashSet::new();
+    for chunk in &metadata.chunks {
+        for shard_id in &chunk.shard_ids {
+            let cid = parse_hex_cid(shard_id)?;
+            if !written.insert(cid) {
+                continue;
+            }
+            let shard = backend.get_shard_verified(&cid).await?;
+            write_block(&mut payload, &cid, &shard.to_bytes()?);
+        }
+    }
+
+    let data_offset = (CARV2_PRAGMA.len() + CARV2_HEADER_SIZE) as u64;
+    let mut car = Vec::with_capacity(data_offset as usize + payload.len());
+    car.extend_from_slice(&CARV2_PRAGMA);
+    car.extend_from_slice(&[0u8; 16]); // characteristics
+    car.extend_from_slice(&data_offset.to_le_bytes());
+    car.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    car.extend_from_slice(&0u64.to_le_bytes()); // no index
+    car.extend_from_slice(&payload);
+    Ok(car)
+}
+
+/// Import a CAR archive produced by [`export_car`] into `backend`
+///
+/// Every block is checked against its CID before it is stored. Returns the
+/// file metadata found at the archive root, which is stored as well.
+pub async fn import_car(
+    backend: &dyn StorageBackend,
+    car: &[u8],
+) -> Result<FileMetadata, FecError> {
+    let payload = if car.starts_with(&CARV2_PRAGMA) {
+        let header = car
+            .get(CARV2_PRAGMA.len()..CARV2_PRAGMA.len() + CARV2_HEADER_SIZE)
+            .ok_or_else(|| invalid("truncated CARv2 header"))?;
+        let data_offset = u64::from_le_bytes(header[16..24].try_into().unwrap()) as usize;
+        let data_size = u64::from_le_bytes(header[24..32].try_into().unwrap()) as usize;
+        data_offset
+            .checked_add(data_size)
+            .and_then(|end| car.get(data_offset..end))
+            .ok_or_else(|| invalid("CARv2 data section out of bounds"))?
+    } else {
+        car
+    };
+
+    let mut reader = Reader::new(payload);
+    let header_len = reader.varint()? as usize;
+    let root = decode_header(reader.take(header_len)?)?;
+
+    let mut metadata = None;
+    let mut shards = Vec::new();
+    while !reader.is_empty() {
+        let block_len = reader.varint()? as usize;
+        let mut block = Reader::new(reader.take(block_len)?);
+        let cid = read_cid(&mut block)?;
+        let data = block.rest();
+        if Cid::from_data(data) != cid {
+            return Err(FecError::CorruptShard {
+                cid: cid.to_hex(),
+                actual: Cid::from_data(data).to_hex(),
+            });
+        }
+
+        if cid == root {
+            metadata = Some(serde_json::from_slice::<FileMetadata>(data).map_err(|e| {
+                FecError::Backend(format!("Failed to deserialize metadata: {}", e))
+            })?);
+        } else {
+            shards.push((cid, Shard::from_bytes(data)?));
+        }
+    }
+
+    let metadata = metadata.ok_or_else(|| invalid("root block missing"))?;
+    backend.put_shards(&shards).await?;
+    backend.put_metadata(&metadata).await?;
+    Ok(metadata)
+}
+
+fn invalid(reason: &str) -> FecError {
+    FecError::Backend(format!("Invalid CAR archive: {}", reason))
+}
+
+fn parse_hex_cid(hex_cid: &str) -> Result<Cid, FecError> {
+    hex::decode(hex_cid)
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .map(Cid::new)
+        .ok_or_else(|| FecError::Backend(format!("Invalid shard id: {}", hex_cid)))
+}
+
+/// Binary CIDv1: version, codec, then the multihash code, length and digest
+fn cid_bytes(cid: &Cid) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(36);
+    write_varint(&mut bytes, 1);
+    write_varint(&mut bytes, CODEC_RAW);
+    write_varint(&mut bytes, MULTIHASH_BLAKE3);
+    write_varint(&mut bytes, 32);
+    bytes.extend_from_slice(cid.as_bytes());
+    bytes
+}
+
+fn read_cid(reader: &mut Reader<'_>) -> Result<Cid, FecError> {
+    let version = reader.varint()?;
+    let codec = reader.varint()?;
+    let hash = reader.varint()?;
+    let len = reader.varint()?;
+    if version != 1 || hash != MULTIHASH_BLAKE3 || len != 32 {
+        return Err(invalid("only CIDv1 with BLAKE3-256 hashes are supported"));
+    }
+    if codec != CODEC_RAW {
+        return Err(invalid("only raw blocks are supported"));
+    }
+    let digest = reader.take(32)?;
+    Ok(Cid::new(digest.try_into().unwrap()))
+}
+
+fn write_block(out: &mut Vec<u8>, cid: &Cid, data: &[u8]) {
+    let cid = cid_bytes(cid);
+    write_varint(out, (cid.len() + data.len()) as u64);
+    out.extend_from_slice(&cid);
+    out.extend_from_slice(data);
+}
+
+/// DAG-CBOR `{"roots": [root], "version": 1}`
+fn encode_header(root: &Cid) -> Vec<u8> {
+    let cid = cid_bytes(root);
+    let mut header = vec![0xa2, 0x65];
+    header.extend_from_slice(b"roots");
+    // Array of one, tag 42, byte string with the multibase identity prefix
+    header.extend_from_slice(&[0x81, 0xd8, 0x2a, 0x58, (cid.len() + 1) as u8, 0x00]);
+    header.extend_from_slice(&cid);
+    header.push(0x67);
+    header.extend_from_slice(b"version");
+    header.push(0x01);
+    header
+}
+
+/// Read the single root out of a CARv1 header
+fn decode_header(header: &[u8]) -> Result<Cid, FecError> {
+    let mut reader = Reader::new(header);
+    let (major, entries) = reader.cbor_head()?;
+    if major != 5 {
+        return Err(invalid("header is not a map"));
+    }
+
+    let mut root = None;
+    let mut version = None;
+    for _ in 0..entries {
+        let (major, len) = reader.cbor_head()?;
+        if major != 3 {
+            return Err(invalid("header key is not a string"));
+        }
+        match reader.take(len as usize)? {
+            b"version" => version = Some(reader.cbor_head()?),
+            b"roots" => {
+                let (major, count) = reader.cbor_head()?;
+                if major != 4 || count != 1 {
+                    return Err(invalid("expected exactly one root"));
+                }
+                if reader.cbor_head()? != (6, 42) {
+                    return Err(invalid("root is not a CID"));
+                }
+                let (major, len) = reader.cbor_head()?;
+                let bytes = reader.take(len as usize)?;
+                if major != 2 || bytes.first() != Some(&0x00) {
+                    return Err(invalid("root is not a binary CID"));
+                }
+                root = Some(read_cid(&mut Reader::new(&bytes[1..]))?);
+            }
+            _ => return Err(invalid("unexpected header field")),
+        }
+    }
+
+    if version != Some((0, 1)) {
+        return Err(invalid("unsupported CAR version"));
+    }
+    root.ok_or_else(|| invalid("header has no roots"))
+}
+
+/// Unsigned LEB128, as used for CAR section lengths and inside CIDs
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Cursor over a byte slice with bounds-checked reads
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn rest(self) -> &'a [u8] {
+        self.bytes
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], FecError> {
+        if len > self.bytes.len() {
+            return Err(invalid("unexpected end of data"));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn varint(&mut self) -> Result<u64, FecError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("varint too long"))
+    }
+
+    /// CBOR major type and argument
+    fn cbor_head(&mut self) -> Result<(u8, u64), FecError> {
+        let initial = self.take(1)?[0];
+        let extra = match initial & 0x1f {
+            n @ 0..=23 => n as u64,
+            24 => self.take(1)?[0] as u64,
+            25 => u16::from_be_bytes(self.take(2)?.try_into().unwrap()) as u64,
+            26 => u32::from_be_bytes(self.take(4)?.try_into().unwrap()) as u64,
+            27 => u64::from_be_bytes(self.take(8)?.try_into().unwrap()),
+            _ => return Err(invalid("unsupported CBOR encoding")),
+        };
+        Ok((initial >> 5, extra))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{ChunkMeta, MemoryStorage, ShardHeader};
+    use crate::EncryptionMode;
+
+    #[tokio::test]
+    async fn test_car_round_trip() {
+        let source = MemoryStorage::new();
+        let mut shard_ids = Vec::new();
+        for i in 0..3u8 {
+            let header = ShardHeader::new(EncryptionMode::Convergent, (2, 1), 8, [i; 32]);
+            let shard = Shard::new(header, vec![i; 8]);
+            let cid = shard.cid().unwrap();
+            source.put_shard(&cid, &shard).await.unwrap();
+            shard_ids.push(cid.to_hex());
+        }
+        let metadata = FileMetadata::new(
+            [7u8; 32],
+            16,
+            vec![ChunkMeta::new(
+                (2, 1),
+                EncryptionMode::Convergent,
+                shard_ids,
+            )],
+        );
+
+        let car = export_car(&source, &metadata).await.unwrap();
+        assert!(car.starts_with(&CARV2_PRAGMA));
+
+        let target = MemoryStorage::new();
+        let imported = import_car(&target, &car).await.unwrap();
+        assert_eq!(imported.file_id, metadata.file_id);
+        assert_eq!(target.shard_count(), 3);
+        assert!(target.get_metadata(&[7u8; 32]).await.is_ok());
+        for shard_id in &imported.chunks[0].shard_ids {
+            let cid = parse_hex_cid(shard_id).unwrap();
+            target.get_shard_verified(&cid).await.unwrap();
+        }
+
+        // The CARv1 payload alone imports too, and tampering is caught
+        let payload = &car[CARV2_PRAGMA.len() + CARV2_HEADER_SIZE..];
+        assert!(import_car(&MemoryStorage::new(), payload).await.is_ok());
+        let mut tampered = car.clone();
+        *tampered.last_mut().unwrap() ^= 0xff;
+        assert!(matches!(
+            import_car(&MemoryStorage::new(), &tampered).await,
+            Err(FecError::CorruptShard { .. })
+        ));
+    }
+}
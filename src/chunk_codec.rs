@@ -0,0 +1,373 @@
+//! Sans-IO chunk coding
+//!
+//! [`ChunkCodec`] turns a chunk into content-addressed shards and shards back
+//! into the chunk, without touching storage or an async runtime. The
+//! [`StoragePipeline`](crate::StoragePipeline) drives it over a
+//! [`StorageBackend`](crate::storage::StorageBackend); callers with their own
+//! IO, blocking or on another executor, can drive it directly:
+//!
+//! ```
+//! use saorsa_fec::chunk_codec::ChunkCodec;
+//! use saorsa_fec::{EncryptionMode, FecParams};
+//! use std::collections::HashMap;
+//!
+//! let codec = ChunkCodec::new()?;
+//! let params = FecParams::new(4, 2)?;
+//! let encoded = codec.encode(0, b"hello shards", params, EncryptionMode::Convergent, None)?;
+//!
+//! // Keep the shards anywhere, then fetch what survived
+//! let store: HashMap<_, _> = encoded.shards.into_iter().collect();
+//! let fetched = encoded
+//!     .reference
+//!     .shard_ids
+//!     .iter()
+//!     .map(|id| store.get(&saorsa_fec::storage::Cid::new(*id)).cloned())
+//!     .collect();
+//! let (shards, health) = ChunkCodec::verify_shards(&encoded.reference, fetched);
+//! assert_eq!(health.available(), 6);
+//! assert_eq!(codec.decode(&encoded.reference, &shards)?, b"hello shards");
+//! # Ok::<(), saorsa_fec::Error>(())
+//! ```
+
+use crate::backends;
+use crate::config::EncryptionMode;
+use crate::fec::ShardError;
+use crate::integrity::ChunkHealth;
+use crate::metadata::ChunkReference;
+use crate::pipeline::{PipelineError, Result};
+use crate::storage::{Cid, Shard, ShardHeader};
+use crate::{FecBackend, FecParams};
+
+/// A chunk coded into shards
+#[derive(Debug, Clone)]
+pub struct EncodedChunk {
+    /// Reference recording the chunk hash, size and shard CIDs
+    pub reference: ChunkReference,
+    /// Shards in share order, keyed by CID
+    pub shards: Vec<(Cid, Shard)>,
+}
+
+/// Codes chunks to and from shards with a [`FecBackend`]
+pub struct ChunkCodec {
+    backend: Box<dyn FecBackend>,
+}
+
+impl ChunkCodec {
+    /// Codec over the best FEC backend available
+    pub fn new() -> Result<Self> {
+        Ok(Self::with_backend(backends::create_backend()?))
+    }
+
+    /// Codec over a specific FEC backend
+    pub fn with_backend(backend: Box<dyn FecBackend>) -> Self {
+        Self { backend }
+    }
+
+    /// Code chunk number `index` of a file into `k + m` shards
+    ///
+    /// Shards carry an expiry when `expires_at`, in seconds since the Unix
+    /// epoch, is set.
+    pub fn encode(
+        &self,
+        index: u16,
+        chunk: &[u8],
+        params: FecParams,
+        encryption_mode: EncryptionMode,
+        expires_at: Option<u64>,
+    ) -> Result<EncodedChunk> {
+        let mut template = ShardHeader::new(encryption_mode, nspec(params), 0, [0u8; 32]);
+        if let Some(expires_at) = expires_at {
+            template = template.with_expiry(expires_at);
+        }
+        self.encode_with(index, chunk, params, template)
+    }
+
+    /// Code chunk number `index` of an unencrypted file into `k + m` shards
+    ///
+    /// The shards are marked [plaintext](ShardHeader::is_plaintext), for
+    /// data stored without the pipeline's encryption.
+    pub fn encode_plaintext(
+        &self,
+        index: u16,
+        chunk: &[u8],
+        params: FecParams,
+    ) -> Result<EncodedChunk> {
+        let template = ShardHeader::new(EncryptionMode::Convergent, nspec(params), 0, [0u8; 32])
+            .with_plaintext();
+        self.encode_with(index, chunk, params, template)
+    }
+
+    /// Code a chunk into shards whose headers copy `template`
+    fn encode_with(
+        &self,
+        index: u16,
+        chunk: &[u8],
+        params: FecParams,
+        template: ShardHeader,
+    ) -> Result<EncodedChunk> {
+        let _span = tracing::debug_span!("encode_chunk", index, size = chunk.len()).entered();
+        let mut shards = Vec::with_capacity(params.total_shares() as usize);
+        for share in self.encode_shares(chunk, params)? {
+            let header = ShardHeader {
+                data_size: share.len() as u32,
+                ..template.clone()
+            };
+            let shard = Shard::new(header, share);
+            shards.push((shard.cid()?, shard));
+        }
+
+        let reference =
+            ChunkReference::new(blake3::hash(chunk).into(), 0, index, chunk.len() as u32)
+                .with_shards(shards.iter().map(|(cid, _)| *cid.as_bytes()).collect());
+        Ok(EncodedChunk { reference, shards })
+    }
+
+    /// Check fetched shards against the CIDs in `chunk_ref`
+    ///
+    /// `fetched` holds whatever storage returned for each shard ID, in order.
+    /// Shards whose content no longer matches their CID are dropped and
+    /// reported as corrupted.
+    pub fn verify_shards(
+        chunk_ref: &ChunkReference,
+        fetched: Vec<Option<Shard>>,
+    ) -> (Vec<Option<Shard>>, ChunkHealth) {
+        let mut shards = Vec::with_capacity(chunk_ref.shard_ids.len());
+        let mut health = ChunkHealth {
+            chunk_id: chunk_ref.chunk_id,
+            total_shards: chunk_ref.shard_ids.len(),
+            required_shards: None,
+            missing: Vec::new(),
+            corrupted: Vec::new(),
+        };
+
+        for (idx, (shard_id, fetched)) in chunk_ref.shard_ids.iter().zip(fetched).enumerate() {
+            match fetched {
+                Some(shard) if shard.cid().is_ok_and(|cid| cid.as_bytes() == shard_id) => {
+                    health
+                        .required_shards
+                        .get_or_insert(shard.header.nspec.0 as usize);
+                    shards.push(Some(shard));
+                }
+                Some(_) => {
+                    tracing::warn!("Shard {} failed CID verification", hex::encode(shard_id));
+                    health.corrupted.push(idx);
+                    shards.push(None);
+                }
+                None => {
+                    tracing::debug!("Shard {} unavailable", hex::encode(shard_id));
+                    health.missing.push(idx);
+                    shards.push(None);
+                }
+            }
+        }
+        // Storage returning too few entries leaves the rest missing
+        for idx in shards.len()..chunk_ref.shard_ids.len() {
+            health.missing.push(idx);
+            shards.push(None);
+        }
+
+        (shards, health)
+    }
+
+    /// Reconstruct a chunk from whichever of its shards are available
+    pub fn decode(&self, chunk_ref: &ChunkReference, shards: &[Option<Shard>]) -> Result<Vec<u8>> {
+        let _span = tracing::debug_span!(
+            "decode_chunk",
+            chunk_id = %hex::encode(chunk_ref.chunk_id),
+            available = shards.iter().flatten().count()
+        )
+        .entered();
+        let (k, m) = shards
+            .iter()
+            .flatten()
+            .map(|shard| shard.header.nspec)
+            .next()
+            .ok_or_else(|| PipelineError::NoShards(hex::encode(chunk_ref.chunk_id)))?;
+        let params = FecParams::new(k as u16, m as u16)?;
+        let mut shares: Vec<Option<Vec<u8>>> = shards
+            .iter()
+            .map(|shard| shard.as_ref().map(|s| s.data.clone()))
+            .collect();
+        self.backend.decode_blocks(&mut shares, params)?;
+
+        let mut chunk = Vec::with_capacity(chunk_ref.size as usize);
+        for (i, share) in shares.into_iter().take(k as usize).enumerate() {
+            chunk.extend(share.ok_or(ShardError::MissingDataShard(i))?);
+        }
+        chunk.truncate(chunk_ref.size as usize);
+
+        if blake3::hash(&chunk).as_bytes() != &chunk_ref.chunk_id {
+            return Err(PipelineError::ChunkCorrupted(hex::encode(chunk_ref.chunk_id)).into());
+        }
+
+        Ok(chunk)
+    }
+
+    /// Rebuild the shards `health` reports missing or corrupted
+    ///
+    /// Re-encoding the verified chunk reproduces the original shares
+    /// exactly, so each rebuilt shard has the CID recorded in `chunk_ref`.
+    pub fn rebuild(
+        &self,
+        chunk_ref: &ChunkReference,
+        shards: &[Option<Shard>],
+        health: &ChunkHealth,
+    ) -> Result<Vec<(Cid, Shard)>> {
+        let _span = tracing::debug_span!(
+            "rebuild_chunk",
+            chunk_id = %hex::encode(chunk_ref.chunk_id),
+            lost = health.missing.len() + health.corrupted.len()
+        )
+        .entered();
+        let chunk = self.decode(chunk_ref, shards)?;
+        let template = shards
+            .iter()
+            .flatten()
+            .map(|shard| shard.header.clone())
+            .next()
+            .ok_or_else(|| PipelineError::NoShards(hex::encode(chunk_ref.chunk_id)))?;
+        let params = FecParams::new(template.nspec.0 as u16, template.nspec.1 as u16)?;
+        let shares = self.encode_shares(&chunk, params)?;
+
+        let mut rebuilt = Vec::new();
+        for idx in health.missing.iter().chain(&health.corrupted).copied() {
+            let share = shares
+                .get(idx)
+                .ok_or(ShardError::IndexOutOfRange(idx as u16))?
+                .clone();
+            // Copy the reserved bytes too so an expiry keeps the same CID
+            let header = ShardHeader {
+                reserved: template.reserved.clone(),
+                ..ShardHeader::new(
+                    template.encryption_mode,
+                    template.nspec,
+                    share.len() as u32,
+                    template.nonce,
+                )
+            };
+            let shard = Shard::new(header, share);
+            let cid = shard.cid()?;
+            if cid.as_bytes() != &chunk_ref.shard_ids[idx] {
+                return Err(PipelineError::RebuiltShardMismatch(hex::encode(
+                    chunk_ref.shard_ids[idx],
+                ))
+                .into());
+            }
+            rebuilt.push((cid, shard));
+        }
+        Ok(rebuilt)
+    }
+
+    /// Split a chunk into k data shares and compute m parity shares
+    fn encode_shares(&self, chunk: &[u8], params: FecParams) -> Result<Vec<Vec<u8>>> {
+        let k = params.data_shares as usize;
+        let m = params.parity_shares as usize;
+
+        // reed-solomon-simd requires non-empty, even-sized shares
+        let share_size = chunk.len().div_ceil(k).max(1).next_multiple_of(2);
+        let mut shares = vec![vec![0u8; share_size]; k];
+        for (share, piece) in shares.iter_mut().zip(chunk.chunks(share_size)) {
+            share[..piece.len()].copy_from_slice(piece);
+        }
+
+        let data_refs: Vec<&[u8]> = shares.iter().map(|s| s.as_slice()).collect();
+        let mut parity = vec![Vec::new(); m];
+        self.backend
+            .encode_blocks(&data_refs, &mut parity, params)?;
+
+        shares.extend(parity);
+        Ok(shares)
+    }
+}
+
+/// Share counts as recorded in shard headers
+fn nspec(params: FecParams) -> (u8, u8) {
+    (params.data_shares as u8, params.parity_shares as u8)
+}
+
+/// Gzip `data` at `level` (0-9)
+pub fn compress(data: &[u8], level: u8) -> Result<Vec<u8>> {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::new(level as u32));
+    encoder
+        .write_all(data)
+        .map_err(PipelineError::Compression)?;
+    Ok(encoder.finish().map_err(PipelineError::Compression)?)
+}
+
+/// Reverse [`compress`]
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    let mut decompressed = Vec::new();
+    GzDecoder::new(data)
+        .read_to_end(&mut decompressed)
+        .map_err(PipelineError::Compression)?;
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrity::HealthStatus;
+
+    #[test]
+    fn test_chunk_codec_repairs_without_storage() {
+        let codec = ChunkCodec::new().unwrap();
+        let chunk: Vec<u8> = (0..=255).cycle().take(1001).collect();
+        let params = FecParams::new(4, 2).unwrap();
+        let encoded = codec
+            .encode(3, &chunk, params, EncryptionMode::Convergent, Some(1 << 40))
+            .unwrap();
+        assert_eq!(encoded.reference.shard_index, 3);
+        assert_eq!(encoded.shards.len(), 6);
+
+        // Lose one shard and corrupt another
+        let mut fetched: Vec<Option<Shard>> = encoded
+            .shards
+            .iter()
+            .map(|(_, shard)| Some(shard.clone()))
+            .collect();
+        fetched[1] = None;
+        if let Some(shard) = &mut fetched[4] {
+            shard.data[0] ^= 0xff;
+        }
+        let (shards, health) = ChunkCodec::verify_shards(&encoded.reference, fetched);
+        assert_eq!(health.missing, vec![1]);
+        assert_eq!(health.corrupted, vec![4]);
+        assert_eq!(health.status(), HealthStatus::Degraded);
+        assert_eq!(codec.decode(&encoded.reference, &shards).unwrap(), chunk);
+
+        let rebuilt = codec.rebuild(&encoded.reference, &shards, &health).unwrap();
+        assert_eq!(rebuilt.len(), 2);
+        assert_eq!(rebuilt[0].0, encoded.shards[1].0);
+        assert_eq!(rebuilt[1].0, encoded.shards[4].0);
+    }
+
+    #[test]
+    fn test_chunk_codec_short_fetch_counts_as_missing() {
+        let codec = ChunkCodec::new().unwrap();
+        let params = FecParams::new(2, 1).unwrap();
+        let encoded = codec
+            .encode(0, b"abc", params, EncryptionMode::Convergent, None)
+            .unwrap();
+        let (shards, health) = ChunkCodec::verify_shards(&encoded.reference, Vec::new());
+        assert_eq!(health.missing, vec![0, 1, 2]);
+        assert!(matches!(
+            codec.decode(&encoded.reference, &shards),
+            Err(crate::Error::Pipeline(PipelineError::NoShards(_)))
+        ));
+    }
+
+    #[test]
+    fn test_compress_roundtrip() {
+        let data = vec![7u8; 4096];
+        let compressed = compress(&data, 6).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(decompress(&compressed).unwrap(), data);
+    }
+}
@@ -2,18 +2,334 @@
 //!
 //! This module tracks all chunks in the system, their reference counts,
 //! and manages chunk lifecycle for garbage collection.
+//!
+//! A registry opened with [`ChunkRegistry::open`] is backed by a directory
+//! holding a snapshot and a write-ahead log. Every change is appended to the
+//! log and synced before it takes effect in memory, so reference counts
+//! survive a crash and garbage collection never acts on counts that were
+//! lost. Changes waiting on the sync at the same time share one, and the
+//! log itself is only locked while a change is written to it. The log is folded into the snapshot every
+//! [`CHECKPOINT_INTERVAL`] writes or on [`ChunkRegistry::checkpoint`].
+//!
+//! Existence checks go through a [`BloomFilter`] first, so the common case
+//! of asking about a chunk that was never registered does not touch the
+//! chunk map. The filter is saved next to the snapshot and rebuilt on load
+//! if it does not match it.
 
-use anyhow::{Context, Result};
+use parking_lot::{Mutex, RwLock, RwLockWriteGuard};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::bloom::BloomFilter;
+use crate::metadata::{ChunkReference, FileMetadata};
+use crate::storage::sync_dir_blocking;
+
+/// Errors from the chunk registry
+#[derive(Debug, Error)]
+pub enum RegistryError {
+    #[error("Chunk not found in registry: {0}")]
+    ChunkNotFound(String),
+
+    #[error("Reference count overflow")]
+    RefCountOverflow,
+
+    #[error("Cannot decrement reference count below zero")]
+    RefCountUnderflow,
+
+    #[error("Cannot remove chunk with non-zero reference count")]
+    StillReferenced,
+
+    #[error("Cannot remove pinned chunk")]
+    Pinned,
+
+    #[error("Chunk {0} is being garbage collected")]
+    Tombstoned(String),
+
+    #[error("Not a chunk registry snapshot")]
+    NotASnapshot,
+
+    #[error("Unsupported registry snapshot format {0}")]
+    UnsupportedFormat(u16),
+
+    #[error("Registry snapshot checksum mismatch")]
+    ChecksumMismatch,
+
+    #[error("Registry snapshot {} is corrupted: {error}", path.display())]
+    CorruptSnapshot {
+        path: PathBuf,
+        error: Box<RegistryError>,
+    },
+
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] bincode::Error),
+
+    #[error("{}: {error}", path.display())]
+    Io { path: PathBuf, error: io::Error },
+}
+
+pub type Result<T> = std::result::Result<T, RegistryError>;
+
+fn io_error(path: &Path) -> impl FnOnce(io::Error) -> RegistryError + '_ {
+    |error| RegistryError::Io {
+        path: path.to_path_buf(),
+        error,
+    }
+}
+
+fn chunk_not_found(chunk_id: &[u8; 32]) -> RegistryError {
+    RegistryError::ChunkNotFound(hex::encode(chunk_id))
+}
+
+fn tombstoned(chunk_id: &[u8; 32]) -> RegistryError {
+    RegistryError::Tombstoned(hex::encode(chunk_id))
+}
+
+/// Log writes after which the journal is folded into the snapshot
+pub const CHECKPOINT_INTERVAL: usize = 1024;
+
+const SNAPSHOT_FILE: &str = "registry.snapshot";
+const WAL_FILE: &str = "registry.wal";
+const BLOOM_FILE: &str = "registry.bloom";
+
+/// Smallest number of chunks the existence filter is sized for
+const BLOOM_MIN_CAPACITY: usize = 4096;
+/// False positive rate of the existence filter
+const BLOOM_FP_RATE: f64 = 0.01;
+
+/// Upper bounds in seconds of the age buckets in [`RegistryStats`]
+pub const STATS_AGE_BOUNDS: [u64; 4] = [3600, 86_400, 7 * 86_400, 30 * 86_400];
+/// Upper bounds in bytes of the size buckets in [`RegistryStats`]
+pub const STATS_SIZE_BOUNDS: [u64; 4] = [4 << 10, 64 << 10, 1 << 20, 16 << 20];
+/// Number of largest chunks listed in [`RegistryStats`]
+pub const STATS_TOP_CHUNKS: usize = 10;
+
+/// Default number of lock domains, see [`ChunkRegistry::with_lock_shards`]
+pub const DEFAULT_LOCK_SHARDS: usize = 16;
 
-use crate::metadata::ChunkReference;
+/// New state of each chunk touched by one change, `None` once removed
+type Batch = Vec<([u8; 32], Option<ChunkMetadata>)>;
 
 /// Registry for tracking chunk metadata and references
-#[derive(Debug, Clone)]
+///
+/// Chunks are spread over independently locked shards by chunk ID prefix,
+/// so registrations from concurrent pipelines only contend when they touch
+/// the same shard. Share a registry as `Arc<ChunkRegistry>`; every method
+/// takes `&self`.
+#[derive(Debug)]
 pub struct ChunkRegistry {
-    /// All chunks indexed by their ID
+    /// Lock domains, each holding the chunks whose ID maps to it
+    shards: Vec<RwLock<Shard>>,
+    /// On-disk log, for registries opened from a directory
+    journal: Option<Wal>,
+}
+
+/// Clones are in-memory copies detached from the journal
+impl Clone for ChunkRegistry {
+    fn clone(&self) -> Self {
+        Self {
+            shards: self
+                .shards
+                .iter()
+                .map(|shard| RwLock::new(shard.read().clone()))
+                .collect(),
+            journal: None,
+        }
+    }
+}
+
+/// One lock domain of a registry
+#[derive(Debug, Clone)]
+struct Shard {
+    /// Chunks of this shard indexed by their ID
     chunks: HashMap<[u8; 32], ChunkMetadata>,
+    /// Every chunk ID ever inserted, for fast negative lookups
+    bloom: BloomFilter,
+}
+
+impl Shard {
+    fn new(min_capacity: usize) -> Self {
+        Self::with_chunks(HashMap::new(), min_capacity)
+    }
+
+    /// Shard holding `chunks` with an existence filter sized for twice as many
+    fn with_chunks(chunks: HashMap<[u8; 32], ChunkMetadata>, min_capacity: usize) -> Self {
+        let capacity = (chunks.len() * 2).max(min_capacity);
+        let mut bloom = BloomFilter::with_capacity(capacity, BLOOM_FP_RATE);
+        for chunk_id in chunks.keys() {
+            bloom.insert(chunk_id);
+        }
+        Self { chunks, bloom }
+    }
+
+    fn apply(&mut self, chunk_id: [u8; 32], metadata: Option<ChunkMetadata>) {
+        match metadata {
+            Some(metadata) => {
+                if self.chunks.insert(chunk_id, metadata).is_none() {
+                    self.bloom.insert(&chunk_id);
+                }
+            }
+            None => {
+                self.chunks.remove(&chunk_id);
+            }
+        }
+    }
+
+    /// Rebuild the filter once removed chunks have filled it up
+    fn refresh_bloom(&mut self, min_capacity: usize) {
+        if self.bloom.len() > self.bloom.capacity() {
+            *self = Self::with_chunks(std::mem::take(&mut self.chunks), min_capacity);
+        }
+    }
+}
+
+/// Write guards on some shards of a registry, held for one atomic change
+struct Locked<'a> {
+    count: usize,
+    min_capacity: usize,
+    guards: BTreeMap<usize, RwLockWriteGuard<'a, Shard>>,
+}
+
+impl Locked<'_> {
+    fn get(&self, chunk_id: &[u8; 32]) -> Option<&ChunkMetadata> {
+        self.guards
+            .get(&shard_index(chunk_id, self.count))?
+            .chunks
+            .get(chunk_id)
+    }
+
+    /// Current state of `chunk_id`, as already changed in `batch`
+    fn staged<'b>(
+        &self,
+        batch: &'b mut HashMap<[u8; 32], ChunkMetadata>,
+        chunk_id: &[u8; 32],
+    ) -> Option<&'b mut ChunkMetadata> {
+        if !batch.contains_key(chunk_id) {
+            let metadata = self.get(chunk_id)?.clone();
+            batch.insert(*chunk_id, metadata);
+        }
+        batch.get_mut(chunk_id)
+    }
+
+    fn apply(&mut self, batch: Batch) {
+        for (chunk_id, metadata) in batch {
+            // Batches only name chunks whose shards were locked for them
+            if let Some(shard) = self.guards.get_mut(&shard_index(&chunk_id, self.count)) {
+                shard.apply(chunk_id, metadata);
+            }
+        }
+        for shard in self.guards.values_mut() {
+            shard.refresh_bloom(self.min_capacity);
+        }
+    }
+}
+
+/// Shard of `count` that holds `chunk_id`
+fn shard_index(chunk_id: &[u8; 32], count: usize) -> usize {
+    u16::from_le_bytes([chunk_id[0], chunk_id[1]]) as usize % count
+}
+
+/// Write-ahead log of a persistent registry, synced in groups
+#[derive(Debug)]
+struct Wal {
+    journal: Mutex<Journal>,
+    /// Taken after, never while holding, `journal`
+    sync: Mutex<WalSync>,
+}
+
+/// Handle syncing the log and how much of it is durable
+#[derive(Debug)]
+struct WalSync {
+    wal: File,
+    /// Frames written before the last sync
+    synced: u64,
+}
+
+impl Wal {
+    fn new(journal: Journal) -> io::Result<Self> {
+        let sync = WalSync {
+            wal: journal.wal.try_clone()?,
+            synced: journal.appended,
+        };
+        Ok(Self {
+            journal: Mutex::new(journal),
+            sync: Mutex::new(sync),
+        })
+    }
+
+    /// Wait until frame `seq` is durable, syncing every frame written so far
+    /// unless a concurrent sync already covered it
+    fn sync_through(&self, seq: u64) -> Result<()> {
+        let mut sync = self.sync.lock();
+        if sync.synced >= seq {
+            return Ok(());
+        }
+        let (appended, path) = {
+            let journal = self.journal.lock();
+            (journal.appended, journal.dir.join(WAL_FILE))
+        };
+        sync.wal.sync_data().map_err(io_error(&path))?;
+        sync.synced = appended;
+        Ok(())
+    }
+}
+
+/// Snapshot plus write-ahead log of a persistent registry
+#[derive(Debug)]
+struct Journal {
+    dir: PathBuf,
+    wal: File,
+    /// Frames written since the last checkpoint
+    writes: usize,
+    /// Frames written since the registry was opened
+    appended: u64,
+}
+
+impl Journal {
+    /// Append `batch` as one checksummed frame, returning its sequence
+    /// number for [`Wal::sync_through`]
+    ///
+    /// Frames hold the resulting chunk state rather than the operation, so
+    /// replaying a frame twice is harmless.
+    fn append(&mut self, batch: &Batch) -> Result<u64> {
+        let payload = bincode::serialize(batch)?;
+        let mut frame = Vec::with_capacity(payload.len() + 36);
+        frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        frame.extend_from_slice(blake3::hash(&payload).as_bytes());
+        frame.extend_from_slice(&payload);
+        let path = self.dir.join(WAL_FILE);
+        self.wal.write_all(&frame).map_err(io_error(&path))?;
+        self.writes += 1;
+        self.appended += 1;
+        Ok(self.appended)
+    }
+
+    /// Complete frames in `log` and the length they cover
+    ///
+    /// Reading stops at the first short or corrupt frame, which is what a
+    /// write interrupted by a crash leaves behind.
+    fn replay(log: &[u8]) -> Result<(Vec<Batch>, usize)> {
+        let mut batches = Vec::new();
+        let mut offset = 0;
+        while log.len() - offset >= 36 {
+            let mut len = [0u8; 4];
+            len.copy_from_slice(&log[offset..offset + 4]);
+            let len = u32::from_le_bytes(len) as usize;
+            let start = offset + 36;
+            let Some(payload) = log.get(start..start + len) else {
+                break;
+            };
+            if blake3::hash(payload).as_bytes()[..] != log[offset + 4..start] {
+                break;
+            }
+            batches.push(bincode::deserialize(payload)?);
+            offset = start + len;
+        }
+        Ok((batches, offset))
+    }
 }
 
 /// Information about a chunk
@@ -40,65 +356,404 @@ use crate::types::{ChunkId, DataId, ShareId};
 impl ChunkRegistry {
     /// Create a new chunk registry
     pub fn new() -> Self {
+        Self::with_lock_shards(DEFAULT_LOCK_SHARDS)
+    }
+
+    /// Create a registry split into `count` lock domains
+    ///
+    /// More shards let more threads register chunks at once, at the cost of
+    /// a little memory per shard.
+    pub fn with_lock_shards(count: usize) -> Self {
+        let count = count.max(1);
+        let min_capacity = Self::shard_min_capacity(count);
         Self {
-            chunks: HashMap::new(),
+            shards: (0..count)
+                .map(|_| RwLock::new(Shard::new(min_capacity)))
+                .collect(),
+            journal: None,
         }
     }
 
-    /// Increment reference counts for multiple chunks
-    pub fn increment_refs(&mut self, chunk_refs: &[ChunkReference]) -> Result<()> {
-        for chunk_ref in chunk_refs {
-            self.increment_ref(&chunk_ref.chunk_id)?;
-
-            // Update size if not already recorded
-            if let Some(metadata) = self.chunks.get_mut(&chunk_ref.chunk_id) {
-                if metadata.size == 0 {
-                    metadata.size = chunk_ref.size;
+    /// Number of lock domains
+    pub fn lock_shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    fn shard_min_capacity(count: usize) -> usize {
+        (BLOOM_MIN_CAPACITY / count).max(64)
+    }
+
+    /// Registry holding the chunks of `snapshot`
+    ///
+    /// `blooms` must hold one filter per shard covering every chunk of that
+    /// shard; without them the filters are rebuilt.
+    fn from_snapshot(snapshot: RegistrySnapshot, blooms: Option<Vec<BloomFilter>>) -> Self {
+        let count = DEFAULT_LOCK_SHARDS;
+        let min_capacity = Self::shard_min_capacity(count);
+        let mut split: Vec<HashMap<[u8; 32], ChunkMetadata>> = vec![HashMap::new(); count];
+        for (chunk_id, metadata) in snapshot.chunks {
+            split[shard_index(&chunk_id, count)].insert(chunk_id, metadata);
+        }
+
+        let shards = match blooms.filter(|b| b.len() == count) {
+            Some(blooms) => split
+                .into_iter()
+                .zip(blooms)
+                .map(|(chunks, bloom)| Shard { chunks, bloom })
+                .collect::<Vec<_>>(),
+            None => split
+                .into_iter()
+                .map(|chunks| Shard::with_chunks(chunks, min_capacity))
+                .collect(),
+        };
+        Self {
+            shards: shards.into_iter().map(RwLock::new).collect(),
+            journal: None,
+        }
+    }
+
+    /// Open the persistent registry in `dir`, creating it if needed
+    ///
+    /// Loads the snapshot, replays the write-ahead log on top of it and
+    /// drops any frame left half-written by a crash.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir).map_err(io_error(dir))?;
+
+        let snapshot = dir.join(SNAPSHOT_FILE);
+        let mut registry = if snapshot.exists() {
+            let data = std::fs::read(&snapshot).map_err(io_error(&snapshot))?;
+            let blooms = std::fs::read(dir.join(BLOOM_FILE))
+                .ok()
+                .and_then(|saved| Self::saved_blooms(&saved, &data));
+            let snapshot = RegistrySnapshot::from_bytes(&data).map_err(|error| {
+                RegistryError::CorruptSnapshot {
+                    path: snapshot.clone(),
+                    error: Box::new(error),
                 }
+            })?;
+            Self::from_snapshot(snapshot, blooms)
+        } else {
+            Self::new()
+        };
+
+        let wal_path = dir.join(WAL_FILE);
+        let mut wal = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&wal_path)
+            .map_err(io_error(&wal_path))?;
+        let mut log = Vec::new();
+        wal.read_to_end(&mut log).map_err(io_error(&wal_path))?;
+        let (batches, valid) = Journal::replay(&log)?;
+        if valid < log.len() {
+            wal.set_len(valid as u64).map_err(io_error(&wal_path))?;
+            wal.sync_data().map_err(io_error(&wal_path))?;
+        }
+        let writes = batches.len();
+        {
+            let mut locked = registry.lock_all();
+            for batch in batches {
+                locked.apply(batch);
             }
         }
+
+        let journal = Journal {
+            dir: dir.to_path_buf(),
+            wal,
+            writes,
+            appended: 0,
+        };
+        registry.journal = Some(Wal::new(journal).map_err(io_error(&wal_path))?);
+        Ok(registry)
+    }
+
+    /// Whether changes are persisted to disk
+    pub fn is_persistent(&self) -> bool {
+        self.journal.is_some()
+    }
+
+    /// Fold the write-ahead log into the snapshot
+    ///
+    /// The snapshot is replaced atomically, and its directory synced, before
+    /// the log is truncated, so a crash at any point leaves a state that
+    /// opens correctly. Does nothing
+    /// for in-memory registries.
+    pub fn checkpoint(&self) -> Result<()> {
+        let Some(wal) = &self.journal else {
+            return Ok(());
+        };
+
+        // Shards before the journal, in the order changes take them
+        let shards: Vec<_> = self.shards.iter().map(|shard| shard.read()).collect();
+        let mut journal = wal.journal.lock();
+
+        let chunks = shards
+            .iter()
+            .flat_map(|shard| shard.chunks.iter())
+            .map(|(id, metadata)| (*id, metadata.clone()))
+            .collect();
+        let data = RegistrySnapshot::new(chunks).to_bytes()?;
+        let tmp = journal.dir.join(format!("{SNAPSHOT_FILE}.tmp"));
+        let mut file = File::create(&tmp).map_err(io_error(&tmp))?;
+        file.write_all(&data).map_err(io_error(&tmp))?;
+        file.sync_all().map_err(io_error(&tmp))?;
+        std::fs::rename(&tmp, journal.dir.join(SNAPSHOT_FILE)).map_err(io_error(&tmp))?;
+        // The new snapshot must be durable before the log it replaces is cut
+        sync_dir_blocking(&journal.dir).map_err(io_error(&journal.dir))?;
+
+        // Tie the filters to this snapshot so stale ones are never loaded
+        let blooms: Vec<&BloomFilter> = shards.iter().map(|shard| &shard.bloom).collect();
+        let mut saved = blake3::hash(&data).as_bytes().to_vec();
+        saved.extend(bincode::serialize(&blooms)?);
+        let tmp = journal.dir.join(format!("{BLOOM_FILE}.tmp"));
+        std::fs::write(&tmp, &saved).map_err(io_error(&tmp))?;
+        std::fs::rename(&tmp, journal.dir.join(BLOOM_FILE)).map_err(io_error(&tmp))?;
+
+        let wal_path = journal.dir.join(WAL_FILE);
+        journal.wal.set_len(0).map_err(io_error(&wal_path))?;
+        journal.wal.sync_data().map_err(io_error(&wal_path))?;
+        journal.writes = 0;
         Ok(())
     }
 
-    /// Increment reference count for a single chunk
-    pub fn increment_ref(&mut self, chunk_id: &[u8; 32]) -> Result<()> {
-        let metadata = self
-            .chunks
-            .entry(*chunk_id)
-            .or_insert_with(|| ChunkMetadata::new(0));
+    /// Filters saved by [`Self::checkpoint`], if they were saved with `snapshot`
+    fn saved_blooms(saved: &[u8], snapshot: &[u8]) -> Option<Vec<BloomFilter>> {
+        if saved.len() < 32 || saved[..32] != blake3::hash(snapshot).as_bytes()[..] {
+            return None;
+        }
+        bincode::deserialize(&saved[32..]).ok()
+    }
 
-        metadata.ref_count = metadata
-            .ref_count
-            .checked_add(1)
-            .context("Reference count overflow")?;
+    /// Write-lock the shards holding `chunk_ids`, in ascending order so
+    /// concurrent changes cannot deadlock
+    fn lock<'a>(&self, chunk_ids: impl IntoIterator<Item = &'a [u8; 32]>) -> Locked<'_> {
+        let count = self.shards.len();
+        let indices: std::collections::BTreeSet<usize> = chunk_ids
+            .into_iter()
+            .map(|id| shard_index(id, count))
+            .collect();
+        Locked {
+            count,
+            min_capacity: Self::shard_min_capacity(count),
+            guards: indices
+                .into_iter()
+                .map(|i| (i, self.shards[i].write()))
+                .collect(),
+        }
+    }
+
+    fn lock_all(&self) -> Locked<'_> {
+        let count = self.shards.len();
+        Locked {
+            count,
+            min_capacity: Self::shard_min_capacity(count),
+            guards: self
+                .shards
+                .iter()
+                .enumerate()
+                .map(|(i, shard)| (i, shard.write()))
+                .collect(),
+        }
+    }
+
+    /// Log `batch` if persistent, then apply it and release the shards
+    ///
+    /// The frame is written under the shards' locks, which keeps the log in
+    /// the order changes apply, but the log itself is released before the
+    /// sync so concurrent changes to other shards share it.
+    fn commit(&self, mut locked: Locked<'_>, batch: Batch) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let checkpoint_due = match &self.journal {
+            Some(wal) => {
+                let (seq, due) = {
+                    let mut journal = wal.journal.lock();
+                    let seq = journal.append(&batch)?;
+                    (seq, journal.writes >= CHECKPOINT_INTERVAL)
+                };
+                wal.sync_through(seq)?;
+                due
+            }
+            None => false,
+        };
+        locked.apply(batch);
+        drop(locked);
 
+        if checkpoint_due {
+            self.checkpoint()?;
+        }
         Ok(())
     }
 
-    /// Decrement reference counts for multiple chunks
-    /// Returns chunks that are now unreferenced
-    pub fn decrement_refs(&mut self, chunk_ids: &[[u8; 32]]) -> Result<Vec<[u8; 32]>> {
-        let mut unreferenced = Vec::new();
+    fn commit_staged(
+        &self,
+        locked: Locked<'_>,
+        batch: HashMap<[u8; 32], ChunkMetadata>,
+    ) -> Result<()> {
+        self.commit(
+            locked,
+            batch
+                .into_iter()
+                .map(|(id, metadata)| (id, Some(metadata)))
+                .collect(),
+        )
+    }
 
+    /// Change the chunks `chunk_ids` with `f` as one update
+    ///
+    /// Fails without changing anything if a chunk is not registered.
+    fn update_each(
+        &self,
+        chunk_ids: &[[u8; 32]],
+        mut f: impl FnMut(&mut ChunkMetadata),
+    ) -> Result<()> {
+        let locked = self.lock(chunk_ids);
+        let mut batch = HashMap::new();
         for chunk_id in chunk_ids {
-            if self.decrement_ref(chunk_id)? == 0 {
-                unreferenced.push(*chunk_id);
+            f(locked
+                .staged(&mut batch, chunk_id)
+                .ok_or_else(|| chunk_not_found(chunk_id))?);
+        }
+        self.commit_staged(locked, batch)
+    }
+
+    /// Run `f` on the metadata of `chunk_id` under its shard's read lock
+    fn with_chunk<T>(&self, chunk_id: &[u8; 32], f: impl FnOnce(&ChunkMetadata) -> T) -> Option<T> {
+        let shard = self.shards[shard_index(chunk_id, self.shards.len())].read();
+        shard.chunks.get(chunk_id).map(f)
+    }
+
+    /// Fold `f` over every chunk, one shard at a time
+    fn fold<T>(&self, init: T, mut f: impl FnMut(T, &[u8; 32], &ChunkMetadata) -> T) -> T {
+        let mut acc = init;
+        for shard in &self.shards {
+            for (id, metadata) in &shard.read().chunks {
+                acc = f(acc, id, metadata);
+            }
+        }
+        acc
+    }
+
+    /// IDs of the chunks for which `f` holds
+    fn filter_ids(&self, mut f: impl FnMut(&ChunkMetadata) -> bool) -> Vec<[u8; 32]> {
+        self.fold(Vec::new(), |mut ids, id, metadata| {
+            if f(metadata) {
+                ids.push(*id);
+            }
+            ids
+        })
+    }
+
+    /// Apply every change in `update` or, on error, none of them
+    ///
+    /// Increments are applied before decrements, and the whole update is a
+    /// single write to the log of a persistent registry, so a crash never
+    /// leaves it half applied. Returns the decremented chunks that end up
+    /// unreferenced.
+    pub fn apply_refs(&self, update: &RefUpdate) -> Result<Vec<[u8; 32]>> {
+        let locked = self.lock(
+            update
+                .increments
+                .iter()
+                .map(|c| &c.chunk_id)
+                .chain(&update.decrements),
+        );
+        let mut batch = HashMap::new();
+        for chunk_ref in &update.increments {
+            if locked.staged(&mut batch, &chunk_ref.chunk_id).is_none() {
+                batch.insert(chunk_ref.chunk_id, ChunkMetadata::new(0));
+            }
+            let metadata = batch
+                .get_mut(&chunk_ref.chunk_id)
+                .ok_or_else(|| chunk_not_found(&chunk_ref.chunk_id))?;
+            if metadata.tombstoned {
+                return Err(tombstoned(&chunk_ref.chunk_id));
             }
+            metadata.ref_count = metadata
+                .ref_count
+                .checked_add(1)
+                .ok_or(RegistryError::RefCountOverflow)?;
+
+            // Update size and shards if not already recorded
+            if metadata.size == 0 {
+                metadata.size = chunk_ref.size;
+            }
+            if metadata.shard_ids.is_empty() {
+                metadata.shard_ids = chunk_ref.shard_ids.clone();
+            }
+        }
+
+        for chunk_id in &update.decrements {
+            let metadata = locked
+                .staged(&mut batch, chunk_id)
+                .ok_or_else(|| chunk_not_found(chunk_id))?;
+            if metadata.ref_count == 0 {
+                return Err(RegistryError::RefCountUnderflow);
+            }
+            metadata.ref_count -= 1;
+            metadata.update_access_time();
         }
 
+        let mut unreferenced: Vec<[u8; 32]> = update
+            .decrements
+            .iter()
+            .filter(|id| batch.get(*id).is_some_and(|m| m.ref_count == 0))
+            .copied()
+            .collect();
+        unreferenced.sort_unstable();
+        unreferenced.dedup();
+
+        self.commit_staged(locked, batch)?;
         Ok(unreferenced)
     }
 
+    /// Increment reference counts for multiple chunks
+    pub fn increment_refs(&self, chunk_refs: &[ChunkReference]) -> Result<()> {
+        self.apply_refs(&RefUpdate::new().increment_all(chunk_refs.iter().cloned()))?;
+        Ok(())
+    }
+
+    /// Increment reference count for a single chunk
+    pub fn increment_ref(&self, chunk_id: &[u8; 32]) -> Result<()> {
+        let locked = self.lock([chunk_id]);
+        let mut metadata = locked
+            .get(chunk_id)
+            .cloned()
+            .unwrap_or_else(|| ChunkMetadata::new(0));
+        if metadata.tombstoned {
+            return Err(tombstoned(chunk_id));
+        }
+
+        metadata.ref_count = metadata
+            .ref_count
+            .checked_add(1)
+            .ok_or(RegistryError::RefCountOverflow)?;
+
+        self.commit(locked, vec![(*chunk_id, Some(metadata))])
+    }
+
+    /// Decrement reference counts for multiple chunks
+    /// Returns chunks that are now unreferenced
+    ///
+    /// Either every count is decremented or, on error, none are.
+    pub fn decrement_refs(&self, chunk_ids: &[[u8; 32]]) -> Result<Vec<[u8; 32]>> {
+        self.apply_refs(&RefUpdate::new().decrement_all(chunk_ids.iter().copied()))
+    }
+
     /// Decrement reference count for a single chunk
     /// Returns the new reference count
-    pub fn decrement_ref(&mut self, chunk_id: &[u8; 32]) -> Result<u32> {
-        let metadata = self
-            .chunks
-            .get_mut(chunk_id)
-            .context("Chunk not found in registry")?;
+    pub fn decrement_ref(&self, chunk_id: &[u8; 32]) -> Result<u32> {
+        let locked = self.lock([chunk_id]);
+        let mut metadata = locked
+            .get(chunk_id)
+            .cloned()
+            .ok_or_else(|| chunk_not_found(chunk_id))?;
 
         if metadata.ref_count == 0 {
-            anyhow::bail!("Cannot decrement reference count below zero");
+            return Err(RegistryError::RefCountUnderflow);
         }
 
         metadata.ref_count -= 1;
@@ -106,117 +761,186 @@ impl ChunkRegistry {
         // Update last accessed time
         metadata.update_access_time();
 
-        Ok(metadata.ref_count)
+        let ref_count = metadata.ref_count;
+        self.commit(locked, vec![(*chunk_id, Some(metadata))])?;
+        Ok(ref_count)
     }
 
     /// Get all unreferenced chunks
     pub fn get_unreferenced(&self) -> Vec<[u8; 32]> {
-        self.chunks
-            .iter()
-            .filter_map(|(id, metadata)| {
-                if metadata.ref_count == 0 {
-                    Some(*id)
-                } else {
-                    None
-                }
-            })
-            .collect()
+        self.filter_ids(|metadata| metadata.ref_count == 0)
     }
 
-    /// Get chunk metadata
-    pub fn get_metadata(&self, chunk_id: &[u8; 32]) -> Option<&ChunkMetadata> {
-        self.chunks.get(chunk_id)
+    /// Get a copy of a chunk's metadata
+    pub fn get_metadata(&self, chunk_id: &[u8; 32]) -> Option<ChunkMetadata> {
+        self.with_chunk(chunk_id, ChunkMetadata::clone)
     }
 
     /// Get chunk size
     pub fn get_chunk_size(&self, chunk_id: &[u8; 32]) -> Option<u32> {
-        self.chunks.get(chunk_id).map(|m| m.size)
+        self.with_chunk(chunk_id, |m| m.size)
     }
 
     /// Get reference count for a chunk
     pub fn get_ref_count(&self, chunk_id: &[u8; 32]) -> Option<u32> {
-        self.chunks.get(chunk_id).map(|m| m.ref_count)
+        self.with_chunk(chunk_id, |m| m.ref_count)
     }
 
     /// Check if a chunk exists in the registry
     pub fn contains(&self, chunk_id: &[u8; 32]) -> bool {
-        self.chunks.contains_key(chunk_id)
+        let shard = self.shards[shard_index(chunk_id, self.shards.len())].read();
+        shard.bloom.might_contain(chunk_id) && shard.chunks.contains_key(chunk_id)
     }
 
-    /// Add version that uses a chunk
-    pub fn add_version_ref(&mut self, chunk_id: &[u8; 32], version_id: [u8; 32]) -> Result<()> {
-        let metadata = self
-            .chunks
-            .get_mut(chunk_id)
-            .context("Chunk not found in registry")?;
+    /// `false` if the chunk is definitely not registered
+    ///
+    /// Answers from the existence filter alone, so a `true` may be a false
+    /// positive; confirm with [`Self::contains`].
+    pub fn might_contain(&self, chunk_id: &[u8; 32]) -> bool {
+        self.shards[shard_index(chunk_id, self.shards.len())]
+            .read()
+            .bloom
+            .might_contain(chunk_id)
+    }
 
-        metadata.versions_using.insert(version_id);
-        Ok(())
+    /// Add version that uses a chunk
+    pub fn add_version_ref(&self, chunk_id: &[u8; 32], version_id: [u8; 32]) -> Result<()> {
+        self.update_each(&[*chunk_id], |metadata| {
+            metadata.versions_using.insert(version_id);
+        })
     }
 
     /// Remove version reference from a chunk
-    pub fn remove_version_ref(&mut self, chunk_id: &[u8; 32], version_id: &[u8; 32]) -> Result<()> {
-        let metadata = self
-            .chunks
-            .get_mut(chunk_id)
-            .context("Chunk not found in registry")?;
+    pub fn remove_version_ref(&self, chunk_id: &[u8; 32], version_id: &[u8; 32]) -> Result<()> {
+        self.update_each(&[*chunk_id], |metadata| {
+            metadata.versions_using.remove(version_id);
+        })
+    }
 
-        metadata.versions_using.remove(version_id);
-        Ok(())
+    /// Get a copy of the versions using a chunk
+    pub fn get_versions_using(&self, chunk_id: &[u8; 32]) -> Option<HashSet<[u8; 32]>> {
+        self.with_chunk(chunk_id, |m| m.versions_using.clone())
     }
 
-    /// Get all versions using a chunk
-    pub fn get_versions_using(&self, chunk_id: &[u8; 32]) -> Option<&HashSet<[u8; 32]>> {
-        self.chunks.get(chunk_id).map(|m| &m.versions_using)
+    /// Mark an unreferenced, unpinned chunk as being deleted
+    ///
+    /// From then on adding a reference to the chunk fails with
+    /// [`RegistryError::Tombstoned`], so a writer cannot come to rely on
+    /// shards that garbage collection is about to delete. The mark stays
+    /// until [`Self::remove_chunk`]. Returns the chunk's metadata, or `None`
+    /// if it is referenced, pinned or unknown and must be kept.
+    pub fn tombstone(&self, chunk_id: &[u8; 32]) -> Result<Option<ChunkMetadata>> {
+        let locked = self.lock([chunk_id]);
+        let mut metadata = match locked.get(chunk_id) {
+            Some(metadata) if metadata.ref_count == 0 && !metadata.pinned => metadata.clone(),
+            _ => return Ok(None),
+        };
+        if !metadata.tombstoned {
+            metadata.tombstoned = true;
+            self.commit(locked, vec![(*chunk_id, Some(metadata.clone()))])?;
+        }
+        Ok(Some(metadata))
     }
 
     /// Remove chunk from registry (after successful deletion)
-    pub fn remove_chunk(&mut self, chunk_id: &[u8; 32]) -> Result<()> {
-        let metadata = self
-            .chunks
-            .remove(chunk_id)
-            .context("Chunk not found in registry")?;
+    pub fn remove_chunk(&self, chunk_id: &[u8; 32]) -> Result<()> {
+        let locked = self.lock([chunk_id]);
+        let metadata = locked
+            .get(chunk_id)
+            .ok_or_else(|| chunk_not_found(chunk_id))?;
 
         if metadata.ref_count > 0 {
-            // Restore it - this is a safety check
-            self.chunks.insert(*chunk_id, metadata);
-            anyhow::bail!("Cannot remove chunk with non-zero reference count");
+            return Err(RegistryError::StillReferenced);
+        }
+        if metadata.pinned {
+            return Err(RegistryError::Pinned);
         }
 
-        Ok(())
+        self.commit(locked, vec![(*chunk_id, None)])
+    }
+
+    /// Pin chunks so garbage collection never deletes them
+    ///
+    /// Pins hold regardless of reference counts and retention policy.
+    pub fn pin(&self, chunk_ids: &[[u8; 32]]) -> Result<()> {
+        self.update_each(chunk_ids, |metadata| metadata.pinned = true)
+    }
+
+    /// Release pins set with [`Self::pin`]
+    pub fn unpin(&self, chunk_ids: &[[u8; 32]]) -> Result<()> {
+        self.update_each(chunk_ids, |metadata| metadata.pinned = false)
+    }
+
+    /// Record that chunks were just read
+    ///
+    /// Feeds least-recently-read retention; chunks missing from the
+    /// registry are ignored.
+    pub fn record_read(&self, chunk_ids: &[[u8; 32]]) -> Result<()> {
+        let locked = self.lock(chunk_ids);
+        let mut batch = HashMap::new();
+        for chunk_id in chunk_ids {
+            if let Some(metadata) = locked.staged(&mut batch, chunk_id) {
+                metadata.update_access_time();
+            }
+        }
+        self.commit_staged(locked, batch)
+    }
+
+    /// Pin every chunk of a file version
+    pub fn pin_file(&self, metadata: &FileMetadata) -> Result<()> {
+        self.pin(&Self::file_chunks(metadata))
+    }
+
+    /// Unpin every chunk of a file version
+    pub fn unpin_file(&self, metadata: &FileMetadata) -> Result<()> {
+        self.unpin(&Self::file_chunks(metadata))
+    }
+
+    /// Whether a chunk is pinned
+    pub fn is_pinned(&self, chunk_id: &[u8; 32]) -> bool {
+        self.with_chunk(chunk_id, |m| m.pinned).unwrap_or(false)
+    }
+
+    /// IDs of all pinned chunks
+    pub fn list_pinned(&self) -> Vec<[u8; 32]> {
+        self.filter_ids(|metadata| metadata.pinned)
+    }
+
+    fn file_chunks(metadata: &FileMetadata) -> Vec<[u8; 32]> {
+        metadata.chunks.iter().map(|c| c.chunk_id).collect()
     }
 
     /// Get total size of all chunks
     pub fn total_size(&self) -> u64 {
-        self.chunks.values().map(|m| m.size as u64).sum()
+        self.fold(0, |total, _, m| total + m.size as u64)
     }
 
     /// Get total size of referenced chunks
     pub fn referenced_size(&self) -> u64 {
-        self.chunks
-            .values()
-            .filter(|m| m.ref_count > 0)
-            .map(|m| m.size as u64)
-            .sum()
+        self.fold(0, |total, _, m| {
+            total + if m.ref_count > 0 { m.size as u64 } else { 0 }
+        })
     }
 
     /// Get total size of unreferenced chunks
     pub fn unreferenced_size(&self) -> u64 {
-        self.chunks
-            .values()
-            .filter(|m| m.ref_count == 0)
-            .map(|m| m.size as u64)
-            .sum()
+        self.fold(0, |total, _, m| {
+            total + if m.ref_count == 0 { m.size as u64 } else { 0 }
+        })
     }
 
     /// Register a new chunk
-    pub fn register_chunk(&mut self, chunk_info: ChunkInfo) {
+    pub fn register_chunk(&self, chunk_info: ChunkInfo) -> Result<()> {
+        let locked = self.lock([&chunk_info.encryption_key_hash]);
         let metadata = ChunkMetadata::new(chunk_info.size as u32);
-        self.chunks.insert(chunk_info.encryption_key_hash, metadata);
+        self.commit(
+            locked,
+            vec![(chunk_info.encryption_key_hash, Some(metadata))],
+        )
     }
 
     /// Unregister a chunk
-    pub fn unregister_chunk(&mut self, _chunk_id: &ChunkId) {
+    pub fn unregister_chunk(&self, _chunk_id: &ChunkId) {
         // Simplified implementation - would need proper mapping
     }
 
@@ -226,48 +950,216 @@ impl ChunkRegistry {
         None
     }
 
+    /// Record that `data_id` is stored in the chunks `chunk_ids`
+    pub fn add_data_ref(&self, chunk_ids: &[[u8; 32]], data_id: &DataId) -> Result<()> {
+        self.update_each(chunk_ids, |metadata| {
+            metadata.data_ids.insert(*data_id.as_bytes());
+        })
+    }
+
+    /// IDs of the chunks matching every filter of `query`
+    pub fn query(&self, query: &ChunkQuery) -> Vec<[u8; 32]> {
+        self.filter_ids(|metadata| query.matches(metadata))
+    }
+
+    /// Up to `limit` IDs of chunks matching `query`, in ascending order
+    /// starting after `after`
+    ///
+    /// Passing the last ID of one page as `after` returns the next page, so
+    /// large registries can be walked in bounded steps.
+    pub fn query_page(
+        &self,
+        query: &ChunkQuery,
+        after: Option<&[u8; 32]>,
+        limit: usize,
+    ) -> Vec<[u8; 32]> {
+        let mut ids = self.fold(Vec::new(), |mut ids, id, metadata| {
+            if after.is_none_or(|after| id > after) && query.matches(metadata) {
+                ids.push(*id);
+            }
+            ids
+        });
+        if ids.len() > limit {
+            if limit == 0 {
+                return Vec::new();
+            }
+            ids.select_nth_unstable(limit - 1);
+            ids.truncate(limit);
+        }
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Chunk totals grouped by age since first seen
+    ///
+    /// `bounds` are ascending upper limits in seconds; each bucket holds the
+    /// chunks younger than its bound and not in an earlier bucket, and a
+    /// final open-ended bucket holds the rest. Chunks of unknown age are
+    /// left out.
+    pub fn age_buckets(&self, bounds: &[u64]) -> Vec<RegistryBucket> {
+        self.bucket(bounds, ChunkMetadata::age_seconds)
+    }
+
+    /// Chunk totals grouped by size, with `bounds` in bytes as for
+    /// [`Self::age_buckets`]
+    pub fn size_buckets(&self, bounds: &[u64]) -> Vec<RegistryBucket> {
+        self.bucket(bounds, |m| Some(m.size as u64))
+    }
+
+    fn bucket(
+        &self,
+        bounds: &[u64],
+        value: impl Fn(&ChunkMetadata) -> Option<u64>,
+    ) -> Vec<RegistryBucket> {
+        let buckets: Vec<RegistryBucket> = bounds
+            .iter()
+            .map(|bound| Some(*bound))
+            .chain(std::iter::once(None))
+            .map(RegistryBucket::new)
+            .collect();
+        self.fold(buckets, |mut buckets, _, metadata| {
+            if let Some(value) = value(metadata) {
+                let i = bounds
+                    .iter()
+                    .position(|bound| value < *bound)
+                    .unwrap_or(bounds.len());
+                buckets[i].add(metadata);
+            }
+            buckets
+        })
+    }
+
     /// Get statistics about the registry
     pub fn stats(&self) -> RegistryStats {
+        let mut ref_counts = BTreeMap::new();
+        let mut largest: Vec<([u8; 32], u32)> = Vec::new();
+        let (total_chunks, referenced_chunks) = self.fold((0, 0), |(total, referenced), id, m| {
+            *ref_counts.entry(m.ref_count).or_insert(0) += 1;
+            largest.push((*id, m.size));
+            (total + 1, referenced + usize::from(m.ref_count > 0))
+        });
+        largest.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        largest.truncate(STATS_TOP_CHUNKS);
+
         RegistryStats {
-            total_chunks: self.chunks.len(),
-            referenced_chunks: self.chunks.values().filter(|m| m.ref_count > 0).count(),
-            unreferenced_chunks: self.chunks.values().filter(|m| m.ref_count == 0).count(),
+            total_chunks,
+            referenced_chunks,
+            unreferenced_chunks: total_chunks - referenced_chunks,
             total_size: self.total_size(),
             referenced_size: self.referenced_size(),
             unreferenced_size: self.unreferenced_size(),
+            age_histogram: self.age_buckets(&STATS_AGE_BOUNDS),
+            size_histogram: self.size_buckets(&STATS_SIZE_BOUNDS),
+            ref_counts,
+            largest,
         }
     }
 
+    /// Shrink the registry and its on-disk state
+    ///
+    /// Rebuilds the existence filters without the chunks removed since they
+    /// were built and folds the write-ahead log into the snapshot.
+    pub fn compact(&self) -> Result<CompactionReport> {
+        let log_bytes = match &self.journal {
+            Some(wal) => {
+                let journal = wal.journal.lock();
+                let wal_path = journal.dir.join(WAL_FILE);
+                journal.wal.metadata().map_err(io_error(&wal_path))?.len()
+            }
+            None => 0,
+        };
+
+        let min_capacity = Self::shard_min_capacity(self.shards.len());
+        let mut chunks = 0;
+        for shard in &self.shards {
+            let mut shard = shard.write();
+            let mut shard_chunks = std::mem::take(&mut shard.chunks);
+            shard_chunks.shrink_to_fit();
+            chunks += shard_chunks.len();
+            *shard = Shard::with_chunks(shard_chunks, min_capacity);
+        }
+        self.checkpoint()?;
+
+        let snapshot_bytes = match &self.journal {
+            Some(wal) => {
+                let path = wal.journal.lock().dir.join(SNAPSHOT_FILE);
+                std::fs::metadata(&path).map_err(io_error(&path))?.len()
+            }
+            None => 0,
+        };
+        Ok(CompactionReport {
+            chunks,
+            log_bytes_reclaimed: log_bytes,
+            snapshot_bytes,
+        })
+    }
+
     /// Export registry to persistent storage
     pub fn export(&self) -> Result<Vec<u8>> {
-        bincode::serialize(&self.chunks).context("Failed to serialize chunk registry")
+        self.snapshot().to_bytes()
     }
 
     /// Import registry from persistent storage
     pub fn import(data: &[u8]) -> Result<Self> {
-        let chunks = bincode::deserialize(data).context("Failed to deserialize chunk registry")?;
+        let snapshot = RegistrySnapshot::from_bytes(data)?;
 
-        Ok(Self { chunks })
+        Ok(Self::from_snapshot(snapshot, None))
+    }
+
+    /// Copy of the full registry state
+    pub fn snapshot(&self) -> RegistrySnapshot {
+        RegistrySnapshot::new(self.fold(HashMap::new(), |mut chunks, id, metadata| {
+            chunks.insert(*id, metadata.clone());
+            chunks
+        }))
+    }
+
+    /// Replace the registry state with `snapshot`
+    ///
+    /// Used to restore a backup or take over the registry of another node.
+    /// A persistent registry records the change as a single log write and
+    /// then checkpoints.
+    pub fn restore(&self, snapshot: RegistrySnapshot) -> Result<()> {
+        let locked = self.lock_all();
+        let mut batch: Batch = locked
+            .guards
+            .values()
+            .flat_map(|shard| shard.chunks.keys())
+            .filter(|id| !snapshot.chunks.contains_key(*id))
+            .map(|id| (*id, None))
+            .collect();
+        batch.extend(
+            snapshot
+                .chunks
+                .into_iter()
+                .map(|(id, metadata)| (id, Some(metadata))),
+        );
+        self.commit(locked, batch)?;
+        self.checkpoint()
     }
 
     /// Merge another registry into this one
-    pub fn merge(&mut self, other: &ChunkRegistry) -> Result<()> {
-        for (chunk_id, other_metadata) in &other.chunks {
-            match self.chunks.get_mut(chunk_id) {
+    pub fn merge(&self, other: &ChunkRegistry) -> Result<()> {
+        let other = other.snapshot().chunks;
+        let locked = self.lock(other.keys());
+        let mut batch = Vec::with_capacity(other.len());
+        for (chunk_id, other_metadata) in other {
+            let merged = match locked.get(&chunk_id) {
                 Some(metadata) => {
                     // Merge metadata - take maximum ref count
+                    let mut metadata = metadata.clone();
                     metadata.ref_count = metadata.ref_count.max(other_metadata.ref_count);
                     metadata
                         .versions_using
                         .extend(&other_metadata.versions_using);
+                    metadata
                 }
-                None => {
-                    // Add new chunk
-                    self.chunks.insert(*chunk_id, other_metadata.clone());
-                }
-            }
+                // Add new chunk
+                None => other_metadata,
+            };
+            batch.push((chunk_id, Some(merged)));
         }
-        Ok(())
+        self.commit(locked, batch)
     }
 }
 
@@ -277,6 +1169,66 @@ impl Default for ChunkRegistry {
     }
 }
 
+/// Portable copy of a registry, for backup and node migration
+///
+/// Serialized snapshots start with a magic number and format version and
+/// carry a checksum, so a truncated or foreign file is rejected on import.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistrySnapshot {
+    /// Unix timestamp when the snapshot was taken
+    pub created_at: u64,
+    /// Every chunk with its reference count, indexed by chunk ID
+    pub chunks: HashMap<[u8; 32], ChunkMetadata>,
+}
+
+const SNAPSHOT_MAGIC: &[u8; 4] = b"SFRS";
+const SNAPSHOT_FORMAT: u16 = 1;
+
+impl RegistrySnapshot {
+    /// Snapshot of `chunks`, timestamped now
+    pub fn new(chunks: HashMap<[u8; 32], ChunkMetadata>) -> Self {
+        Self {
+            created_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            chunks,
+        }
+    }
+
+    /// Serialize for storage or transfer
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let payload = bincode::serialize(self)?;
+        let mut bytes = Vec::with_capacity(payload.len() + 38);
+        bytes.extend_from_slice(SNAPSHOT_MAGIC);
+        bytes.extend_from_slice(&SNAPSHOT_FORMAT.to_le_bytes());
+        bytes.extend_from_slice(blake3::hash(&payload).as_bytes());
+        bytes.extend_from_slice(&payload);
+        Ok(bytes)
+    }
+
+    /// Deserialize a snapshot, checking its format and checksum
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 38 || &bytes[..4] != SNAPSHOT_MAGIC {
+            return Err(RegistryError::NotASnapshot);
+        }
+        let format = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if format != SNAPSHOT_FORMAT {
+            return Err(RegistryError::UnsupportedFormat(format));
+        }
+        let payload = &bytes[38..];
+        if blake3::hash(payload).as_bytes()[..] != bytes[6..38] {
+            return Err(RegistryError::ChecksumMismatch);
+        }
+        Ok(bincode::deserialize(payload)?)
+    }
+
+    /// Sum of the reference counts of all chunks
+    pub fn total_refs(&self) -> u64 {
+        self.chunks.values().map(|m| m.ref_count as u64).sum()
+    }
+}
+
 /// Metadata for a single chunk
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChunkMetadata {
@@ -287,11 +1239,23 @@ pub struct ChunkMetadata {
     /// Set of version IDs that reference this chunk
     pub versions_using: HashSet<[u8; 32]>,
     /// Unix timestamp when first seen locally
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub first_seen_locally: Option<u64>,
     /// Unix timestamp when last accessed locally
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub last_accessed_locally: Option<u64>,
+    /// CIDs of the shards storing this chunk, if known
+    #[serde(default)]
+    pub shard_ids: Vec<[u8; 32]>,
+    /// Data IDs of the encrypted files stored in this chunk
+    #[serde(default)]
+    pub data_ids: HashSet<[u8; 32]>,
+    /// Whether garbage collection must keep this chunk
+    #[serde(default)]
+    pub pinned: bool,
+    /// Whether garbage collection has started deleting this chunk
+    #[serde(default)]
+    pub tombstoned: bool,
 }
 
 impl ChunkMetadata {
@@ -308,6 +1272,10 @@ impl ChunkMetadata {
             versions_using: HashSet::new(),
             first_seen_locally: now,
             last_accessed_locally: now,
+            shard_ids: Vec::new(),
+            data_ids: HashSet::new(),
+            pinned: false,
+            tombstoned: false,
         }
     }
 
@@ -347,6 +1315,163 @@ impl ChunkMetadata {
     }
 }
 
+/// Reference count changes applied together by [`ChunkRegistry::apply_refs`]
+#[derive(Debug, Clone, Default)]
+pub struct RefUpdate {
+    increments: Vec<ChunkReference>,
+    decrements: Vec<[u8; 32]>,
+}
+
+impl RefUpdate {
+    /// Update changing nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add one reference to each chunk, registering unknown ones
+    pub fn increment_all(mut self, chunks: impl IntoIterator<Item = ChunkReference>) -> Self {
+        self.increments.extend(chunks);
+        self
+    }
+
+    /// Drop one reference from each chunk
+    pub fn decrement_all(mut self, chunk_ids: impl IntoIterator<Item = [u8; 32]>) -> Self {
+        self.decrements.extend(chunk_ids);
+        self
+    }
+
+    /// Whether the update changes no counts
+    pub fn is_empty(&self) -> bool {
+        self.increments.is_empty() && self.decrements.is_empty()
+    }
+}
+
+/// Filter for [`ChunkRegistry::query`]; an empty query matches every chunk
+#[derive(Debug, Clone, Default)]
+pub struct ChunkQuery {
+    orphaned: bool,
+    unpinned: bool,
+    min_age: Option<u64>,
+    min_idle: Option<u64>,
+    data_id: Option<[u8; 32]>,
+    min_size: Option<u32>,
+    max_size: Option<u32>,
+}
+
+impl ChunkQuery {
+    /// Query matching every chunk
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only chunks with a reference count of zero
+    pub fn orphaned(mut self) -> Self {
+        self.orphaned = true;
+        self
+    }
+
+    /// Only chunks that are not pinned
+    pub fn unpinned(mut self) -> Self {
+        self.unpinned = true;
+        self
+    }
+
+    /// Only chunks first seen at least `seconds` ago
+    ///
+    /// Chunks of unknown age never match.
+    pub fn older_than(mut self, seconds: u64) -> Self {
+        self.min_age = Some(seconds);
+        self
+    }
+
+    /// Only chunks not accessed for at least `seconds`
+    ///
+    /// Chunks with no recorded access never match.
+    pub fn idle_longer_than(mut self, seconds: u64) -> Self {
+        self.min_idle = Some(seconds);
+        self
+    }
+
+    /// Only chunks storing `data_id`
+    pub fn data_id(mut self, data_id: &DataId) -> Self {
+        self.data_id = Some(*data_id.as_bytes());
+        self
+    }
+
+    /// Only chunks of at least `bytes`
+    pub fn min_size(mut self, bytes: u32) -> Self {
+        self.min_size = Some(bytes);
+        self
+    }
+
+    /// Only chunks of at most `bytes`
+    pub fn max_size(mut self, bytes: u32) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Whether a chunk with `metadata` passes every filter
+    pub fn matches(&self, metadata: &ChunkMetadata) -> bool {
+        if self.orphaned && metadata.ref_count > 0 {
+            return false;
+        }
+        if self.unpinned && metadata.pinned {
+            return false;
+        }
+        if let Some(min_age) = self.min_age {
+            if metadata.age_seconds().is_none_or(|age| age < min_age) {
+                return false;
+            }
+        }
+        if let Some(min_idle) = self.min_idle {
+            if metadata.idle_seconds().is_none_or(|idle| idle < min_idle) {
+                return false;
+            }
+        }
+        if let Some(data_id) = &self.data_id {
+            if !metadata.data_ids.contains(data_id) {
+                return false;
+            }
+        }
+        if self.min_size.is_some_and(|min| metadata.size < min) {
+            return false;
+        }
+        self.max_size.is_none_or(|max| metadata.size <= max)
+    }
+}
+
+/// Chunk totals for one age or size range
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistryBucket {
+    /// Exclusive upper bound of the range, `None` for the last bucket
+    pub upper_bound: Option<u64>,
+    /// Chunks in the range
+    pub chunks: usize,
+    /// Bytes stored in those chunks
+    pub bytes: u64,
+    /// How many of the chunks have no references
+    pub orphaned: usize,
+}
+
+impl RegistryBucket {
+    fn new(upper_bound: Option<u64>) -> Self {
+        Self {
+            upper_bound,
+            chunks: 0,
+            bytes: 0,
+            orphaned: 0,
+        }
+    }
+
+    fn add(&mut self, metadata: &ChunkMetadata) {
+        self.chunks += 1;
+        self.bytes += metadata.size as u64;
+        if metadata.ref_count == 0 {
+            self.orphaned += 1;
+        }
+    }
+}
+
 /// Statistics about the chunk registry
 #[derive(Debug, Clone)]
 pub struct RegistryStats {
@@ -362,6 +1487,25 @@ pub struct RegistryStats {
     pub referenced_size: u64,
     /// Size of unreferenced chunks
     pub unreferenced_size: u64,
+    /// Chunk totals by age, bucketed by [`STATS_AGE_BOUNDS`]
+    pub age_histogram: Vec<RegistryBucket>,
+    /// Chunk totals by size, bucketed by [`STATS_SIZE_BOUNDS`]
+    pub size_histogram: Vec<RegistryBucket>,
+    /// Number of chunks with each reference count
+    pub ref_counts: BTreeMap<u32, usize>,
+    /// The [`STATS_TOP_CHUNKS`] largest chunks with their sizes, largest first
+    pub largest: Vec<([u8; 32], u32)>,
+}
+
+/// Outcome of [`ChunkRegistry::compact`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactionReport {
+    /// Chunks in the registry
+    pub chunks: usize,
+    /// Bytes of write-ahead log folded into the snapshot
+    pub log_bytes_reclaimed: u64,
+    /// Size of the new snapshot, 0 for in-memory registries
+    pub snapshot_bytes: u64,
 }
 
 impl RegistryStats {
@@ -378,10 +1522,11 @@ impl RegistryStats {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use anyhow::Result;
 
     #[test]
     fn test_chunk_registry_basic() {
-        let mut registry = ChunkRegistry::new();
+        let registry = ChunkRegistry::new();
         let chunk_id = [1u8; 32];
 
         // Initial state
@@ -409,9 +1554,25 @@ mod tests {
         assert_eq!(unreferenced[0], chunk_id);
     }
 
+    #[test]
+    fn test_decrement_refs_is_all_or_nothing() {
+        let registry = ChunkRegistry::new();
+        registry.increment_ref(&[1u8; 32]).unwrap();
+        registry.increment_ref(&[2u8; 32]).unwrap();
+
+        assert!(registry
+            .decrement_refs(&[[1u8; 32], [2u8; 32], [2u8; 32]])
+            .is_err());
+        assert_eq!(registry.get_ref_count(&[1u8; 32]), Some(1));
+        assert_eq!(registry.get_ref_count(&[2u8; 32]), Some(1));
+
+        let unreferenced = registry.decrement_refs(&[[1u8; 32], [2u8; 32]]).unwrap();
+        assert_eq!(unreferenced.len(), 2);
+    }
+
     #[test]
     fn test_chunk_registry_versions() {
-        let mut registry = ChunkRegistry::new();
+        let registry = ChunkRegistry::new();
         let chunk_id = [1u8; 32];
         let version1 = [10u8; 32];
         let version2 = [20u8; 32];
@@ -432,7 +1593,7 @@ mod tests {
 
     #[test]
     fn test_chunk_registry_stats() {
-        let mut registry = ChunkRegistry::new();
+        let registry = ChunkRegistry::new();
 
         // Add some chunks
         let chunk_refs = vec![
@@ -457,7 +1618,7 @@ mod tests {
 
     #[test]
     fn test_chunk_registry_export_import() {
-        let mut registry = ChunkRegistry::new();
+        let registry = ChunkRegistry::new();
 
         // Add some data
         registry.increment_ref(&[1u8; 32]).unwrap();
@@ -480,7 +1641,7 @@ mod tests {
 
     #[test]
     fn test_chunk_removal_safety() {
-        let mut registry = ChunkRegistry::new();
+        let registry = ChunkRegistry::new();
         let chunk_id = [1u8; 32];
 
         registry.increment_ref(&chunk_id).unwrap();
@@ -497,4 +1658,278 @@ mod tests {
         assert!(result.is_ok());
         assert!(!registry.contains(&chunk_id));
     }
+
+    #[test]
+    fn test_persistent_registry_survives_crash() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let chunk = ChunkReference::new([1u8; 32], 0, 0, 1024);
+
+        let registry = ChunkRegistry::open(dir.path())?;
+        registry.increment_refs(&[chunk.clone(), chunk])?;
+        registry.decrement_ref(&[1u8; 32])?;
+        registry.increment_ref(&[2u8; 32])?;
+        drop(registry);
+
+        // A write torn by a crash is dropped on reopen
+        let wal = dir.path().join(WAL_FILE);
+        let mut file = OpenOptions::new().append(true).open(&wal)?;
+        file.write_all(&[9u8; 20])?;
+        drop(file);
+
+        let registry = ChunkRegistry::open(dir.path())?;
+        assert_eq!(registry.get_ref_count(&[1u8; 32]), Some(1));
+        assert_eq!(registry.get_chunk_size(&[1u8; 32]), Some(1024));
+        assert_eq!(registry.get_ref_count(&[2u8; 32]), Some(1));
+
+        registry.checkpoint()?;
+        assert_eq!(std::fs::metadata(&wal)?.len(), 0);
+        registry.decrement_ref(&[2u8; 32])?;
+        registry.remove_chunk(&[2u8; 32])?;
+        drop(registry);
+
+        let registry = ChunkRegistry::open(dir.path())?;
+        assert_eq!(registry.get_ref_count(&[1u8; 32]), Some(1));
+        assert!(!registry.contains(&[2u8; 32]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_registry_snapshot_round_trip() -> Result<()> {
+        let source = ChunkRegistry::new();
+        source.increment_refs(&[ChunkReference::new([1u8; 32], 0, 0, 512)])?;
+        source.increment_ref(&[1u8; 32])?;
+        let bytes = source.export()?;
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert!(RegistrySnapshot::from_bytes(&corrupted).is_err());
+        assert!(RegistrySnapshot::from_bytes(&bytes[..20]).is_err());
+
+        // Restoring on another node replaces its state and persists it
+        let dir = tempfile::TempDir::new()?;
+        let target = ChunkRegistry::open(dir.path())?;
+        target.increment_ref(&[2u8; 32])?;
+        let snapshot = RegistrySnapshot::from_bytes(&bytes)?;
+        assert_eq!(snapshot.total_refs(), 2);
+        target.restore(snapshot)?;
+        drop(target);
+
+        let target = ChunkRegistry::open(dir.path())?;
+        assert_eq!(target.get_ref_count(&[1u8; 32]), Some(2));
+        assert_eq!(target.get_chunk_size(&[1u8; 32]), Some(512));
+        assert!(!target.contains(&[2u8; 32]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_registry_queries() -> Result<()> {
+        let registry = ChunkRegistry::new();
+        registry.increment_refs(&[
+            ChunkReference::new([1u8; 32], 0, 0, 100),
+            ChunkReference::new([2u8; 32], 0, 1, 5000),
+        ])?;
+        registry.increment_ref(&[3u8; 32])?;
+        registry.decrement_ref(&[2u8; 32])?;
+        let data_id = DataId::new([7u8; 32]);
+        registry.add_data_ref(&[[1u8; 32], [2u8; 32]], &data_id)?;
+
+        let mut hits = registry.query(&ChunkQuery::new().data_id(&data_id));
+        hits.sort();
+        assert_eq!(hits, vec![[1u8; 32], [2u8; 32]]);
+        assert_eq!(
+            registry.query(&ChunkQuery::new().orphaned().min_size(1000)),
+            vec![[2u8; 32]]
+        );
+        assert_eq!(registry.query(&ChunkQuery::new().older_than(0)).len(), 3);
+        assert!(registry
+            .query(&ChunkQuery::new().older_than(3600))
+            .is_empty());
+
+        let sizes = registry.size_buckets(&[1, 1024]);
+        assert_eq!(
+            sizes.iter().map(|b| b.chunks).collect::<Vec<_>>(),
+            vec![1, 1, 1]
+        );
+        assert_eq!(sizes[2].bytes, 5000);
+        assert_eq!(sizes[2].orphaned, 1);
+        assert_eq!(registry.age_buckets(&[3600])[0].chunks, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_existence_filter_persists_with_snapshot() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let registry = ChunkRegistry::open(dir.path())?;
+        for i in 0..10u8 {
+            registry.increment_ref(&[i; 32])?;
+        }
+        registry.checkpoint()?;
+        registry.increment_ref(&[200u8; 32])?;
+        drop(registry);
+
+        let registry = ChunkRegistry::open(dir.path())?;
+        assert!((0..10u8).all(|i| registry.might_contain(&[i; 32])));
+        assert!(registry.contains(&[200u8; 32]));
+        assert!(!registry.contains(&[100u8; 32]));
+
+        // A filter that does not match the snapshot is rebuilt
+        std::fs::write(dir.path().join(BLOOM_FILE), [0u8; 40])?;
+        let registry = ChunkRegistry::open(dir.path())?;
+        assert!((0..10u8).all(|i| registry.contains(&[i; 32])));
+        Ok(())
+    }
+
+    #[test]
+    fn test_ref_update_applies_as_one() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let registry = ChunkRegistry::open(dir.path())?;
+        registry.increment_ref(&[1u8; 32])?;
+
+        // A failing decrement rolls back the increments with it
+        let bad = RefUpdate::new()
+            .increment_all([ChunkReference::new([2u8; 32], 0, 0, 64)])
+            .decrement_all([[1u8; 32], [1u8; 32]]);
+        assert!(registry.apply_refs(&bad).is_err());
+        assert!(!registry.contains(&[2u8; 32]));
+        assert_eq!(registry.get_ref_count(&[1u8; 32]), Some(1));
+
+        let update = RefUpdate::new()
+            .increment_all([ChunkReference::new([2u8; 32], 0, 0, 64)])
+            .decrement_all([[1u8; 32]]);
+        assert_eq!(registry.apply_refs(&update)?, vec![[1u8; 32]]);
+        drop(registry);
+
+        let registry = ChunkRegistry::open(dir.path())?;
+        assert_eq!(registry.get_ref_count(&[1u8; 32]), Some(0));
+        assert_eq!(registry.get_ref_count(&[2u8; 32]), Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_compaction_and_extended_stats() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let registry = ChunkRegistry::open(dir.path())?;
+        registry.increment_refs(&[
+            ChunkReference::new([1u8; 32], 0, 0, 100),
+            ChunkReference::new([2u8; 32], 0, 1, 100 << 10),
+            ChunkReference::new([2u8; 32], 0, 2, 100 << 10),
+            ChunkReference::new([3u8; 32], 0, 3, 2 << 20),
+        ])?;
+        registry.decrement_ref(&[1u8; 32])?;
+
+        let stats = registry.stats();
+        assert_eq!(stats.ref_counts, BTreeMap::from([(0, 1), (1, 1), (2, 1)]));
+        assert_eq!(stats.largest[0], ([3u8; 32], 2 << 20));
+        assert_eq!(
+            stats
+                .size_histogram
+                .iter()
+                .map(|b| b.chunks)
+                .collect::<Vec<_>>(),
+            vec![1, 0, 1, 1, 0]
+        );
+        assert_eq!(stats.age_histogram[0].chunks, 3);
+
+        let report = registry.compact()?;
+        assert!(report.log_bytes_reclaimed > 0);
+        assert!(report.snapshot_bytes > 0);
+        assert_eq!(std::fs::metadata(dir.path().join(WAL_FILE))?.len(), 0);
+        drop(registry);
+        assert_eq!(ChunkRegistry::open(dir.path())?.stats().total_chunks, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pinned_chunks_cannot_be_removed() -> Result<()> {
+        let registry = ChunkRegistry::new();
+        let metadata = FileMetadata::new(
+            [1u8; 32],
+            128,
+            None,
+            vec![ChunkReference::new([5u8; 32], 0, 0, 128)],
+        );
+        registry.increment_refs(&metadata.chunks)?;
+        registry.decrement_ref(&[5u8; 32])?;
+
+        registry.pin_file(&metadata)?;
+        assert_eq!(registry.list_pinned(), vec![[5u8; 32]]);
+        assert!(registry
+            .query(&ChunkQuery::new().orphaned().unpinned())
+            .is_empty());
+        assert!(matches!(
+            registry.remove_chunk(&[5u8; 32]),
+            Err(RegistryError::Pinned)
+        ));
+        assert!(matches!(
+            registry.pin(&[[6u8; 32]]),
+            Err(RegistryError::ChunkNotFound(_))
+        ));
+
+        registry.unpin_file(&metadata)?;
+        assert!(!registry.is_pinned(&[5u8; 32]));
+        registry.remove_chunk(&[5u8; 32])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_persistent_commits_share_syncs() -> Result<()> {
+        let dir = tempfile::TempDir::new().unwrap();
+        let registry = std::sync::Arc::new(ChunkRegistry::open(dir.path())?);
+
+        let threads: Vec<_> = (0..4u8)
+            .map(|t| {
+                let registry = registry.clone();
+                std::thread::spawn(move || -> Result<()> {
+                    for i in 0..64u8 {
+                        registry.increment_ref(&[i; 32])?;
+                        registry.increment_ref(&[t.wrapping_mul(64).wrapping_add(i); 32])?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().expect("worker panicked")?;
+        }
+        drop(registry);
+
+        // Every frame was durable and replays in the order it applied
+        let reopened = ChunkRegistry::open(dir.path())?;
+        assert_eq!(reopened.get_ref_count(&[0u8; 32]), Some(5));
+        assert!((1..64u8).all(|i| reopened.get_ref_count(&[i; 32]) == Some(5)));
+        assert!((64..=255u8).all(|i| reopened.get_ref_count(&[i; 32]) == Some(1)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_updates_across_shards() -> Result<()> {
+        let registry = std::sync::Arc::new(ChunkRegistry::with_lock_shards(8));
+        assert_eq!(registry.lock_shards(), 8);
+
+        let threads: Vec<_> = (0..4u8)
+            .map(|t| {
+                let registry = registry.clone();
+                std::thread::spawn(move || -> Result<()> {
+                    for i in 0..=255u8 {
+                        // Every thread touches every chunk, in a different order
+                        let id = [i.wrapping_add(t * 64); 32];
+                        registry.increment_refs(&[ChunkReference::new(id, 0, 0, 10)])?;
+                        registry.apply_refs(
+                            &RefUpdate::new()
+                                .increment_all([ChunkReference::new([i; 32], 0, 0, 10)])
+                                .decrement_all([id]),
+                        )?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().expect("worker panicked")?;
+        }
+
+        assert_eq!(registry.stats().total_chunks, 256);
+        assert!((0..=255u8).all(|i| registry.get_ref_count(&[i; 32]) == Some(4)));
+        Ok(())
+    }
 }
@@ -4,8 +4,66 @@
 //! storage settings, and FEC parameters. The v0.3 specification requires
 //! a builder pattern for configuration.
 
+use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use std::time::Duration;
+use thiserror::Error;
+
+use crate::crypto::{ConvergenceSecret, EncryptionAlgorithm};
+use crate::gc::RetentionPolicy;
+use crate::quantum_crypto::{QuantumEncryptionMetadata, QuantumKeyDerivation};
+
+/// Errors from loading or validating a configuration
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    /// The configuration has the errors found by [`Config::check`]
+    #[error("{}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    Invalid(Vec<ConfigIssue>),
+
+    #[error("{0} is not allowed under the FIPS policy")]
+    Policy(String),
+
+    #[error("Unsupported config file {}: expected a .toml, .yaml, .yml or .json extension", .0.display())]
+    UnsupportedFormat(PathBuf),
+
+    #[error("Failed to access {}: {error}", path.display())]
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+
+    #[error("Failed to parse {}: {message}", path.display())]
+    Parse { path: PathBuf, message: String },
+
+    #[error("Failed to serialize config: {0}")]
+    Serialize(String),
+
+    /// A field has a value of the wrong type
+    #[error("{field}: {message}")]
+    Field { field: String, message: String },
+
+    /// An environment override could not be applied
+    #[error("Invalid value for {name}: {message}")]
+    Override { name: String, message: String },
+
+    #[error("Invalid config file {}: {error}", path.display())]
+    File {
+        path: PathBuf,
+        error: Box<ConfigError>,
+    },
+
+    #[error("Invalid profile {name}: {error}")]
+    Profile {
+        name: String,
+        error: Box<ConfigError>,
+    },
+
+    #[error("Profile name {0:?} is empty or built in")]
+    ReservedProfile(String),
+}
 
 /// Encryption mode selection for the v0.3 API
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
@@ -18,9 +76,67 @@ pub enum EncryptionMode {
     RandomKey,
 }
 
+/// Which primitives the pipeline may encrypt with
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CryptoPolicy {
+    /// Any supported primitive
+    Standard,
+    /// FIPS-approved primitives only: AES-256-GCM under SHA-2 HKDF or
+    /// ML-KEM keys
+    ///
+    /// Plain Convergent mode is refused, since its keys are derived from
+    /// public content with no secret keying material.
+    Fips,
+}
+
+impl Default for CryptoPolicy {
+    /// [`CryptoPolicy::Fips`] when built with the `fips` feature
+    fn default() -> Self {
+        if cfg!(feature = "fips") {
+            Self::Fips
+        } else {
+            Self::Standard
+        }
+    }
+}
+
+impl CryptoPolicy {
+    /// Check that the policy allows encrypting in `mode`
+    pub fn check_mode(self, mode: EncryptionMode) -> Result<(), ConfigError> {
+        if self == Self::Fips && mode == EncryptionMode::Convergent {
+            return Err(ConfigError::Policy("Convergent mode".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Check that data described by `metadata` was encrypted as the policy allows
+    pub fn check_metadata(self, metadata: &QuantumEncryptionMetadata) -> Result<(), ConfigError> {
+        if self == Self::Standard {
+            return Ok(());
+        }
+        if metadata.algorithm != EncryptionAlgorithm::Aes256Gcm {
+            return Err(ConfigError::Policy(format!("{:?}", metadata.algorithm)));
+        }
+        if !matches!(
+            metadata.key_derivation,
+            QuantumKeyDerivation::Sha256Convergent | QuantumKeyDerivation::QuantumRandom
+        ) {
+            return Err(ConfigError::Policy(format!(
+                "{:?} key derivation",
+                metadata.key_derivation
+            )));
+        }
+        if metadata.wrapped_key.is_some() {
+            return Err(ConfigError::Policy("Key wrapping".to_string()));
+        }
+        Ok(())
+    }
+}
+
 /// Main configuration for the Saorsa FEC system
 /// Supports builder pattern as specified in v0.3
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     /// Encryption mode
     pub encryption_mode: EncryptionMode,
@@ -34,6 +150,9 @@ pub struct Config {
     pub compression_enabled: bool,
     /// Compression level (1-9)
     pub compression_level: u8,
+    /// Primitives the pipeline may encrypt with
+    #[serde(default)]
+    pub crypto_policy: CryptoPolicy,
     /// Legacy fields for backward compatibility
     pub encryption: EncryptionConfig,
     pub fec: FecConfig,
@@ -47,12 +166,13 @@ impl Config {
     /// Required by v0.3 specification
     pub fn new() -> Self {
         Self {
-            encryption_mode: EncryptionMode::Convergent,
+            encryption_mode: convergent_mode(),
             data_shards: 16,
             parity_shards: 4,
             chunk_size: 64 * 1024, // 64 KiB as specified
             compression_enabled: true,
             compression_level: 6,
+            crypto_policy: CryptoPolicy::default(),
             // Legacy fields
             encryption: EncryptionConfig::default(),
             fec: FecConfig::default(),
@@ -70,20 +190,32 @@ impl Config {
 
     /// Set FEC parameters (v0.3 builder pattern)
     /// overhead = parity_shards / data_shards
+    ///
+    /// Fixing the parameters turns off `fec.auto_params`.
     pub fn with_fec_params(mut self, data_shards: u8, parity_shards: u8) -> Self {
         self.data_shards = data_shards;
         self.parity_shards = parity_shards;
         // Update legacy fields for compatibility
         self.fec.data_shares = data_shards as u16;
         self.fec.parity_shares = parity_shards as u16;
+        self.fec.auto_params = false;
         self
     }
 
     /// Set chunk size (v0.3 builder pattern)
+    ///
+    /// Fixing the chunk size turns off `fec.auto_params`.
     pub fn with_chunk_size(mut self, bytes: usize) -> Self {
         self.chunk_size = bytes;
         // Update legacy field
         self.fec.stripe_size = bytes;
+        self.fec.auto_params = false;
+        self
+    }
+
+    /// Restrict the primitives used for encryption
+    pub fn with_crypto_policy(mut self, policy: CryptoPolicy) -> Self {
+        self.crypto_policy = policy;
         self
     }
 
@@ -100,22 +232,26 @@ impl Config {
     /// Create a high-performance configuration
     pub fn high_performance() -> Self {
         Self {
-            encryption_mode: EncryptionMode::Convergent,
+            encryption_mode: convergent_mode(),
             data_shards: 16,
             parity_shards: 4,
             chunk_size: 128 * 1024,
             compression_enabled: true,
             compression_level: 3,
+            crypto_policy: CryptoPolicy::default(),
             encryption: EncryptionConfig {
                 mode: EncryptionMode::Convergent,
                 compress_before_encrypt: true,
                 compression_level: 3,
+                ..Default::default()
             },
             fec: FecConfig {
                 data_shares: 16,
                 parity_shares: 4,
                 stripe_size: 128 * 1024,
                 auto_params: true,
+                repair_threshold: 1,
+                target_durability: 0.999_999,
             },
             storage: StorageConfig {
                 backend: StorageBackend::Local {
@@ -123,15 +259,22 @@ impl Config {
                 },
                 cache_size: 1024 * 1024 * 1024,
                 parallel_operations: 8,
+                secure_delete: false,
+                max_in_flight_bytes: default_max_in_flight_bytes(),
             },
             gc: GcConfig {
                 enabled: true,
                 retention_days: 30,
                 min_free_space_gb: 10,
                 run_interval: Duration::from_secs(3600),
+                dry_run: false,
+                policy: GcPolicy::KeepRecent,
+                time_budget: None,
+                batch_size: default_gc_batch_size(),
             },
             version: VersionConfig {
                 max_versions: 100,
+                keep_tagged: true,
                 auto_tag_interval: 10,
                 diff_compression: true,
             },
@@ -147,16 +290,20 @@ impl Config {
             chunk_size: 64 * 1024,
             compression_enabled: true,
             compression_level: 6,
+            crypto_policy: CryptoPolicy::default(),
             encryption: EncryptionConfig {
                 mode: EncryptionMode::RandomKey,
                 compress_before_encrypt: true,
                 compression_level: 6,
+                ..Default::default()
             },
             fec: FecConfig {
                 data_shares: 10,
                 parity_shares: 10,
                 stripe_size: 64 * 1024,
                 auto_params: false,
+                repair_threshold: 1,
+                target_durability: 0.999_999_999,
             },
             storage: StorageConfig {
                 backend: StorageBackend::Multi {
@@ -171,15 +318,22 @@ impl Config {
                 },
                 cache_size: 512 * 1024 * 1024,
                 parallel_operations: 4,
+                secure_delete: false,
+                max_in_flight_bytes: default_max_in_flight_bytes(),
             },
             gc: GcConfig {
                 enabled: true,
                 retention_days: 90,
                 min_free_space_gb: 50,
                 run_interval: Duration::from_secs(7200),
+                dry_run: false,
+                policy: GcPolicy::KeepRecent,
+                time_budget: None,
+                batch_size: default_gc_batch_size(),
             },
             version: VersionConfig {
                 max_versions: 1000,
+                keep_tagged: true,
                 auto_tag_interval: 1,
                 diff_compression: true,
             },
@@ -189,22 +343,26 @@ impl Config {
     /// Create a minimal storage configuration
     pub fn minimal_storage() -> Self {
         Self {
-            encryption_mode: EncryptionMode::Convergent,
+            encryption_mode: convergent_mode(),
             data_shards: 20,
             parity_shards: 2,
             chunk_size: 32 * 1024,
             compression_enabled: true,
             compression_level: 9,
+            crypto_policy: CryptoPolicy::default(),
             encryption: EncryptionConfig {
                 mode: EncryptionMode::Convergent,
                 compress_before_encrypt: true,
                 compression_level: 9,
+                ..Default::default()
             },
             fec: FecConfig {
                 data_shares: 20,
                 parity_shares: 2,
                 stripe_size: 32 * 1024,
                 auto_params: true,
+                repair_threshold: 1,
+                target_durability: 0.999_9,
             },
             storage: StorageConfig {
                 backend: StorageBackend::Local {
@@ -212,15 +370,22 @@ impl Config {
                 },
                 cache_size: 64 * 1024 * 1024,
                 parallel_operations: 2,
+                secure_delete: false,
+                max_in_flight_bytes: default_max_in_flight_bytes(),
             },
             gc: GcConfig {
                 enabled: true,
                 retention_days: 7,
                 min_free_space_gb: 1,
                 run_interval: Duration::from_secs(1800),
+                dry_run: false,
+                policy: GcPolicy::KeepRecent,
+                time_budget: None,
+                batch_size: default_gc_batch_size(),
             },
             version: VersionConfig {
                 max_versions: 10,
+                keep_tagged: true,
                 auto_tag_interval: 0,
                 diff_compression: true,
             },
@@ -228,24 +393,605 @@ impl Config {
     }
 
     /// Validate configuration
-    pub fn validate(&self) -> anyhow::Result<()> {
+    ///
+    /// Fails listing every error found by [`Self::check`]; warnings are
+    /// ignored.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let check = self.check();
+        if !check.errors.is_empty() {
+            return Err(ConfigError::Invalid(check.errors));
+        }
+        Ok(())
+    }
+
+    /// Look for errors and for settings that work but are likely mistakes
+    ///
+    /// Unlike [`Self::validate`] this reports every problem found, including
+    /// storage paths that cannot be written. Those are only warnings, since
+    /// the path may be created or mounted before the pipeline runs.
+    pub fn check(&self) -> ConfigCheck {
+        let mut check = ConfigCheck::default();
+
+        // Erasure coding
+        if self.data_shards == 0 {
+            check.error("data_shards", "must be greater than 0");
+        }
+        if self.data_shards as u16 + self.parity_shards as u16 > 255 {
+            check.error(
+                "parity_shards",
+                format!(
+                    "total shards cannot exceed 255, got {}",
+                    self.data_shards as u16 + self.parity_shards as u16
+                ),
+            );
+        }
+        if self.parity_shards == 0 {
+            check.warn("parity_shards", "0 parity shards cannot recover any loss");
+        }
         if self.fec.data_shares == 0 {
-            anyhow::bail!("Data shares must be greater than 0");
+            check.error("fec.data_shares", "must be greater than 0");
         }
         if self.fec.parity_shares == 0 {
-            anyhow::bail!("Parity shares must be greater than 0");
+            check.error("fec.parity_shares", "must be greater than 0");
         }
         if self.fec.data_shares + self.fec.parity_shares > 255 {
-            anyhow::bail!("Total shares cannot exceed 255");
+            check.error(
+                "fec.parity_shares",
+                format!(
+                    "total shares cannot exceed 255, got {}",
+                    self.fec.data_shares + self.fec.parity_shares
+                ),
+            );
+        }
+        if self.fec.data_shares != self.data_shards as u16
+            || self.fec.parity_shares != self.parity_shards as u16
+        {
+            check.warn(
+                "fec",
+                format!(
+                    "{}+{} shares differ from data_shards/parity_shards {}+{}; \
+                     set both with Config::with_fec_params",
+                    self.fec.data_shares,
+                    self.fec.parity_shares,
+                    self.data_shards,
+                    self.parity_shards
+                ),
+            );
         }
         if self.fec.stripe_size == 0 {
-            anyhow::bail!("Stripe size must be greater than 0");
+            check.error("fec.stripe_size", "must be greater than 0");
+        }
+        if self.fec.repair_threshold == 0 {
+            check.error("fec.repair_threshold", "must be greater than 0");
+        } else if self.fec.repair_threshold > self.parity_shards as usize {
+            check.warn(
+                "fec.repair_threshold",
+                format!(
+                    "chunks are unrecoverable before {} of their shards are lost",
+                    self.fec.repair_threshold
+                ),
+            );
+        }
+        if !(self.fec.target_durability > 0.0 && self.fec.target_durability < 1.0) {
+            check.error("fec.target_durability", "must be between 0 and 1");
+        }
+
+        // Chunking
+        if self.chunk_size == 0 {
+            check.error("chunk_size", "must be greater than 0");
+        } else if self.data_shards > 0 {
+            if self.chunk_size < self.data_shards as usize {
+                check.error(
+                    "chunk_size",
+                    format!(
+                        "{} bytes cannot be split into {} data shards",
+                        self.chunk_size, self.data_shards
+                    ),
+                );
+            } else if !self.chunk_size.is_multiple_of(self.data_shards as usize) {
+                check.warn(
+                    "chunk_size",
+                    format!(
+                        "{} is not a multiple of {} data shards, so every chunk is padded",
+                        self.chunk_size, self.data_shards
+                    ),
+                );
+            }
+        }
+        if self.fec.stripe_size > 0 && !self.chunk_size.is_multiple_of(self.fec.stripe_size) {
+            check.warn(
+                "chunk_size",
+                format!(
+                    "{} is not a multiple of fec.stripe_size {}",
+                    self.chunk_size, self.fec.stripe_size
+                ),
+            );
+        }
+
+        // Compression; levels are those of DEFLATE
+        if self.compression_level > 9 {
+            check.error(
+                "compression_level",
+                format!("must be between 0 and 9, got {}", self.compression_level),
+            );
+        } else if self.compression_enabled && self.compression_level == 0 {
+            check.warn(
+                "compression_level",
+                "level 0 stores data uncompressed despite compression_enabled",
+            );
+        }
+        if self.encryption.compression_level > 9 {
+            check.error(
+                "encryption.compression_level",
+                format!(
+                    "must be between 0 and 9, got {}",
+                    self.encryption.compression_level
+                ),
+            );
         }
+
+        // Storage
         if self.storage.cache_size == 0 {
-            anyhow::bail!("Cache size must be greater than 0");
+            check.error("storage.cache_size", "must be greater than 0");
+        }
+        if self.storage.max_in_flight_bytes == 0 {
+            check.error("storage.max_in_flight_bytes", "must be greater than 0");
+        }
+        if self.storage.parallel_operations == 0 {
+            check.error("storage.parallel_operations", "must be greater than 0");
+        }
+        self.storage.backend.check(&mut check);
+
+        // Garbage collection
+        if self.gc.enabled && self.gc.run_interval.is_zero() {
+            check.error(
+                "gc.run_interval",
+                "must be greater than 0 while GC is enabled",
+            );
+        }
+        if self.gc.batch_size == 0 {
+            check.warn("gc.batch_size", "0 is treated as 1");
+        }
+        if self.gc.enabled && self.gc.retention_days == 0 {
+            match self.gc.policy {
+                GcPolicy::KeepRecent | GcPolicy::LeastRecentlyRead => check.warn(
+                    "gc.retention_days",
+                    "0 deletes unreferenced chunks as soon as GC runs",
+                ),
+                GcPolicy::TargetSize { .. } => {}
+            }
+        }
+        if let GcPolicy::TargetSize { max_bytes: 0 } = self.gc.policy {
+            check.warn(
+                "gc.policy",
+                "a target size of 0 deletes every unreferenced chunk",
+            );
+        }
+
+        // Crypto
+        if cfg!(feature = "fips") && self.crypto_policy != CryptoPolicy::Fips {
+            check.error(
+                "crypto_policy",
+                "builds with the fips feature require the FIPS crypto policy",
+            );
+        }
+        if let Err(e) = self.crypto_policy.check_mode(self.encryption_mode) {
+            check.error("encryption_mode", e.to_string());
+        }
+
+        check
+    }
+
+    /// Apply the settings present in `update`
+    pub fn apply(&mut self, update: &ConfigUpdate) {
+        if let Some(parallel_operations) = update.parallel_operations {
+            self.storage.parallel_operations = parallel_operations;
+        }
+        if let Some(run_interval) = update.gc_run_interval {
+            self.gc.run_interval = run_interval;
+        }
+        if let Some(compression_level) = update.compression_level {
+            self.compression_level = compression_level;
+        }
+        if let Some(repair_threshold) = update.repair_threshold {
+            self.fec.repair_threshold = repair_threshold;
+        }
+    }
+
+    /// Look up a named profile
+    ///
+    /// Built-in presets (`default`, `high_performance`, `high_reliability`
+    /// and `minimal_storage`) come first, then profiles defined in
+    /// [`ProfileRegistry::global`].
+    pub fn profile(name: &str) -> Option<Self> {
+        ProfileRegistry::builtin(name).or_else(|| ProfileRegistry::global().read().get(name))
+    }
+
+    /// Load a configuration file, apply environment overrides and validate
+    ///
+    /// The format follows the file extension, see [`ConfigFormat`]. Fields
+    /// missing from the file keep their defaults, and `SAORSA_FEC_*`
+    /// variables override fields as described at [`ENV_PREFIX`].
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let format = ConfigFormat::from_path(path)?;
+        let text = read_file(path)?;
+        let value = format.parse(path, &text)?;
+        Self::from_value(value, std::env::vars()).map_err(|e| ConfigError::File {
+            path: path.to_path_buf(),
+            error: Box::new(e),
+        })
+    }
+
+    /// Write the configuration to `path` in the format of its extension
+    pub fn to_file(&self, path: impl AsRef<Path>) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+        let text = ConfigFormat::from_path(path)?.render(self)?;
+        write_file(path, text)
+    }
+
+    /// Apply `SAORSA_FEC_*` overrides from the process environment and
+    /// validate the result
+    pub fn with_env_overrides(self) -> Result<Self, ConfigError> {
+        self.with_overrides(std::env::vars())
+    }
+
+    /// Apply overrides given as environment variable names and values
+    ///
+    /// Variables without the [`ENV_PREFIX`] are ignored. The result is
+    /// validated.
+    pub fn with_overrides(
+        self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let value =
+            serde_json::to_value(&self).map_err(|e| ConfigError::Serialize(e.to_string()))?;
+        Self::from_value(value, vars)
+    }
+
+    fn from_value(
+        mut value: serde_json::Value,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        for (name, raw) in vars {
+            if let Some(field) = name.strip_prefix(ENV_PREFIX) {
+                apply_override(&mut value, field, &raw)
+                    .map_err(|message| ConfigError::Override { name, message })?;
+            }
+        }
+        let config: Self =
+            serde_path_to_error::deserialize(value).map_err(|e| ConfigError::Field {
+                field: e.path().to_string(),
+                message: e.inner().to_string(),
+            })?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// Settings that can be changed on a running pipeline
+///
+/// Fields left as `None` keep their current value. See
+/// `StoragePipeline::reconfigure`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfigUpdate {
+    /// New `storage.parallel_operations`
+    pub parallel_operations: Option<usize>,
+    /// New `gc.run_interval`
+    pub gc_run_interval: Option<Duration>,
+    /// New `compression_level`
+    pub compression_level: Option<u8>,
+    /// New `fec.repair_threshold`
+    pub repair_threshold: Option<usize>,
+}
+
+impl ConfigUpdate {
+    /// Update changing nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set `storage.parallel_operations`
+    pub fn parallel_operations(mut self, operations: usize) -> Self {
+        self.parallel_operations = Some(operations);
+        self
+    }
+
+    /// Set `gc.run_interval`
+    pub fn gc_run_interval(mut self, interval: Duration) -> Self {
+        self.gc_run_interval = Some(interval);
+        self
+    }
+
+    /// Set `compression_level`
+    pub fn compression_level(mut self, level: u8) -> Self {
+        self.compression_level = Some(level);
+        self
+    }
+
+    /// Set `fec.repair_threshold`
+    pub fn repair_threshold(mut self, shards: usize) -> Self {
+        self.repair_threshold = Some(shards);
+        self
+    }
+}
+
+/// A problem with one configuration field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// Path of the field, such as `fec.data_shares`
+    pub field: &'static str,
+    /// What is wrong with it
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Problems found by [`Config::check`]
+#[derive(Debug, Clone, Default)]
+pub struct ConfigCheck {
+    /// Problems that make the configuration unusable
+    pub errors: Vec<ConfigIssue>,
+    /// Settings that work but are probably not what was meant
+    pub warnings: Vec<ConfigIssue>,
+}
+
+impl ConfigCheck {
+    /// Whether no errors were found; warnings are allowed
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    fn error(&mut self, field: &'static str, message: impl Into<String>) {
+        self.errors.push(ConfigIssue {
+            field,
+            message: message.into(),
+        });
+    }
+
+    fn warn(&mut self, field: &'static str, message: impl Into<String>) {
+        self.warnings.push(ConfigIssue {
+            field,
+            message: message.into(),
+        });
+    }
+}
+
+/// Prefix of environment variables that override configuration fields
+///
+/// The rest of the name is the field path, with nested fields separated by
+/// a double underscore: `SAORSA_FEC_CHUNK_SIZE` sets `chunk_size` and
+/// `SAORSA_FEC_GC__RUN_INTERVAL__SECS` sets `gc.run_interval.secs`. Names
+/// are case-insensitive.
+pub const ENV_PREFIX: &str = "SAORSA_FEC_";
+
+/// Set the field at `path` in `value` from the string `raw`
+///
+/// The string is read as the type of the value it replaces; new fields and
+/// structured values take JSON, falling back to a plain string.
+fn apply_override(value: &mut serde_json::Value, path: &str, raw: &str) -> Result<(), String> {
+    use serde_json::Value;
+
+    let mut target = value;
+    for segment in path.split("__") {
+        let Value::Object(fields) = target else {
+            return Err(format!("{} is not a section", segment));
+        };
+        let key = fields
+            .keys()
+            .find(|key| key.eq_ignore_ascii_case(segment))
+            .cloned()
+            .unwrap_or_else(|| segment.to_ascii_lowercase());
+        target = fields.entry(key).or_insert(Value::Null);
+    }
+
+    *target = match target {
+        Value::Bool(_) => Value::Bool(
+            raw.parse()
+                .map_err(|_| format!("expected true or false, got {:?}", raw))?,
+        ),
+        Value::Number(_) => Value::Number(
+            raw.parse()
+                .map_err(|_| format!("expected a number, got {:?}", raw))?,
+        ),
+        Value::String(_) => Value::String(raw.to_string()),
+        _ => serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string())),
+    };
+    Ok(())
+}
+
+/// File format of a configuration file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    /// `.toml`
+    Toml,
+    /// `.yaml` or `.yml`
+    Yaml,
+    /// `.json`
+    Json,
+}
+
+impl ConfigFormat {
+    /// Format named by the extension of `path`
+    pub fn from_path(path: &Path) -> Result<Self, ConfigError> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("toml") => Ok(Self::Toml),
+            Some("yaml") | Some("yml") => Ok(Self::Yaml),
+            Some("json") => Ok(Self::Json),
+            _ => Err(ConfigError::UnsupportedFormat(path.to_path_buf())),
+        }
+    }
+
+    fn parse(self, path: &Path, text: &str) -> Result<serde_json::Value, ConfigError> {
+        let parsed = match self {
+            Self::Toml => toml::from_str(text).map_err(|e| e.to_string()),
+            Self::Yaml => serde_yaml::from_str(text).map_err(|e| e.to_string()),
+            Self::Json => serde_json::from_str(text).map_err(|e| e.to_string()),
+        };
+        parsed.map_err(|message| ConfigError::Parse {
+            path: path.to_path_buf(),
+            message,
+        })
+    }
+
+    fn render(self, config: &impl Serialize) -> Result<String, ConfigError> {
+        let rendered = match self {
+            Self::Toml => toml::to_string_pretty(config).map_err(|e| e.to_string()),
+            // Through JSON so enums are written as maps rather than YAML tags
+            Self::Yaml => serde_json::to_value(config)
+                .map_err(|e| e.to_string())
+                .and_then(|value| serde_yaml::to_string(&value).map_err(|e| e.to_string())),
+            Self::Json => serde_json::to_string_pretty(config).map_err(|e| e.to_string()),
+        };
+        rendered.map_err(ConfigError::Serialize)
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, ConfigError> {
+    std::fs::read_to_string(path).map_err(|error| ConfigError::Io {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+fn write_file(path: &Path, text: String) -> Result<(), ConfigError> {
+    std::fs::write(path, text).map_err(|error| ConfigError::Io {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+/// Names of the built-in presets, which profiles cannot redefine
+pub const BUILTIN_PROFILES: [&str; 4] = [
+    "default",
+    "high_performance",
+    "high_reliability",
+    "minimal_storage",
+];
+
+/// Named configuration profiles defined by the application
+///
+/// Profiles are validated when defined or loaded. A profile file maps each
+/// name to a (possibly partial) configuration in any [`ConfigFormat`]:
+///
+/// ```toml
+/// [archive.fec]
+/// data_shares = 8
+/// parity_shares = 8
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ProfileRegistry {
+    profiles: BTreeMap<String, Config>,
+}
+
+impl ProfileRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Process-wide registry consulted by [`Config::profile`]
+    pub fn global() -> &'static RwLock<ProfileRegistry> {
+        static GLOBAL: OnceLock<RwLock<ProfileRegistry>> = OnceLock::new();
+        GLOBAL.get_or_init(Default::default)
+    }
+
+    /// Built-in preset called `name`
+    pub fn builtin(name: &str) -> Option<Config> {
+        match name {
+            "default" => Some(Config::default()),
+            "high_performance" => Some(Config::high_performance()),
+            "high_reliability" => Some(Config::high_reliability()),
+            "minimal_storage" => Some(Config::minimal_storage()),
+            _ => None,
+        }
+    }
+
+    /// Define or replace the profile `name`
+    pub fn define(&mut self, name: impl Into<String>, config: Config) -> Result<(), ConfigError> {
+        let name = name.into();
+        if name.is_empty() || BUILTIN_PROFILES.contains(&name.as_str()) {
+            return Err(ConfigError::ReservedProfile(name));
+        }
+        if let Err(e) = config.validate() {
+            return Err(ConfigError::Profile {
+                name,
+                error: Box::new(e),
+            });
+        }
+        self.profiles.insert(name, config);
+        Ok(())
+    }
+
+    /// Remove the profile `name`, returning it if it was defined
+    pub fn remove(&mut self, name: &str) -> Option<Config> {
+        self.profiles.remove(name)
+    }
+
+    /// User-defined profile called `name`
+    pub fn get(&self, name: &str) -> Option<Config> {
+        self.profiles.get(name).cloned()
+    }
+
+    /// Names of the user-defined profiles, in order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+
+    /// Load profiles from `path`, replacing any with the same names
+    ///
+    /// Nothing is defined if any profile in the file is invalid.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+        let format = ConfigFormat::from_path(path)?;
+        let text = read_file(path)?;
+        let values: BTreeMap<String, serde_json::Value> =
+            serde_json::from_value(format.parse(path, &text)?).map_err(|e| ConfigError::Parse {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            })?;
+
+        let mut loaded = self.clone();
+        for (name, value) in values {
+            let config = match Config::from_value(value, std::iter::empty()) {
+                Ok(config) => config,
+                Err(e) => {
+                    return Err(ConfigError::Profile {
+                        name,
+                        error: Box::new(e),
+                    })
+                }
+            };
+            loaded.define(name, config)?;
         }
+        *self = loaded;
         Ok(())
     }
+
+    /// Write all user-defined profiles to `path`
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+        let text = ConfigFormat::from_path(path)?.render(&self.profiles)?;
+        write_file(path, text)
+    }
+}
+
+/// Deduplicating mode for presets; plain convergent keys are not FIPS-approved
+fn convergent_mode() -> EncryptionMode {
+    if cfg!(feature = "fips") {
+        EncryptionMode::ConvergentWithSecret
+    } else {
+        EncryptionMode::Convergent
+    }
 }
 
 impl Default for Config {
@@ -254,7 +1000,12 @@ impl Default for Config {
     }
 }
 /// Encryption configuration
+///
+/// Also exported as `crypto::EncryptionConfig`. Convergence secrets are
+/// held by a [`Keystore`](crate::keystore::Keystore), not here; the
+/// `enabled` field older versions serialized is ignored when read.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct EncryptionConfig {
     /// Encryption mode to use
     pub mode: EncryptionMode,
@@ -262,19 +1013,34 @@ pub struct EncryptionConfig {
     pub compress_before_encrypt: bool,
     /// Compression level (1-9)
     pub compression_level: u32,
+    /// Kept from `crypto::EncryptionConfig`; ignored, as every mode encrypts
+    #[deprecated(since = "0.4.12", note = "ignored; every mode encrypts")]
+    #[serde(skip)]
+    pub enabled: bool,
+    /// Kept from `crypto::EncryptionConfig`; ignored by the pipeline
+    #[deprecated(
+        since = "0.4.12",
+        note = "ignored; hold convergence secrets in a `Keystore`"
+    )]
+    #[serde(skip)]
+    pub convergence_secret: Option<ConvergenceSecret>,
 }
+#[allow(deprecated)]
 impl Default for EncryptionConfig {
     fn default() -> Self {
         Self {
             mode: EncryptionMode::Convergent,
             compress_before_encrypt: true,
             compression_level: 6,
+            enabled: true,
+            convergence_secret: None,
         }
     }
 }
 
 /// FEC configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FecConfig {
     /// Number of data shares
     pub data_shares: u16,
@@ -282,8 +1048,24 @@ pub struct FecConfig {
     pub parity_shares: u16,
     /// Size of each stripe in bytes
     pub stripe_size: usize,
-    /// Automatically adjust parameters based on content
+    /// Choose parameters per file with an adaptive
+    /// [`FecPolicy`](crate::fec_policy::FecPolicy)
     pub auto_params: bool,
+    /// Repair a chunk once at least this many of its shards are missing or
+    /// corrupted
+    #[serde(default = "default_repair_threshold")]
+    pub repair_threshold: usize,
+    /// Probability each chunk should survive, used by `auto_params`
+    #[serde(default = "default_target_durability")]
+    pub target_durability: f64,
+}
+
+fn default_repair_threshold() -> usize {
+    1
+}
+
+fn default_target_durability() -> f64 {
+    0.999_999
 }
 
 impl Default for FecConfig {
@@ -293,12 +1075,15 @@ impl Default for FecConfig {
             parity_shares: 4,
             stripe_size: 64 * 1024,
             auto_params: true,
+            repair_threshold: default_repair_threshold(),
+            target_durability: default_target_durability(),
         }
     }
 }
 
 /// Storage configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct StorageConfig {
     /// Storage backend to use
     pub backend: StorageBackend,
@@ -306,6 +1091,16 @@ pub struct StorageConfig {
     pub cache_size: usize,
     /// Number of parallel storage operations
     pub parallel_operations: usize,
+    /// Overwrite deleted shards and metadata with random data before unlinking
+    #[serde(default)]
+    pub secure_delete: bool,
+    /// Upper bound on shard bytes being written at once; producers wait beyond it
+    #[serde(default = "default_max_in_flight_bytes")]
+    pub max_in_flight_bytes: usize,
+}
+
+fn default_max_in_flight_bytes() -> usize {
+    64 * 1024 * 1024
 }
 
 impl Default for StorageConfig {
@@ -316,6 +1111,8 @@ impl Default for StorageConfig {
             },
             cache_size: 256 * 1024 * 1024,
             parallel_operations: 4,
+            secure_delete: false,
+            max_in_flight_bytes: default_max_in_flight_bytes(),
         }
     }
 }
@@ -342,8 +1139,64 @@ pub enum StorageBackend {
     },
 }
 
+impl StorageBackend {
+    /// Report problems with the backend settings, such as local paths that
+    /// are not directories or cannot be written
+    fn check(&self, check: &mut ConfigCheck) {
+        match self {
+            Self::Local { path } => {
+                let path = Path::new(path);
+                if path.exists() && !path.is_dir() {
+                    check.error(
+                        "storage.backend.path",
+                        format!("{} is not a directory", path.display()),
+                    );
+                    return;
+                }
+                // The directory itself, or the ancestor it would be created in
+                let existing = path.ancestors().find(|p| p.exists());
+                let writable = existing
+                    .and_then(|p| std::fs::metadata(p).ok())
+                    .is_some_and(|m| !m.permissions().readonly());
+                if !writable {
+                    check.warn(
+                        "storage.backend.path",
+                        format!("{} is not writable", path.display()),
+                    );
+                }
+            }
+            Self::Network { nodes, replication } => {
+                if nodes.is_empty() {
+                    check.error("storage.backend.nodes", "at least one node is required");
+                } else if *replication > nodes.len() {
+                    check.warn(
+                        "storage.backend.replication",
+                        format!(
+                            "{} copies cannot be placed on {} nodes",
+                            replication,
+                            nodes.len()
+                        ),
+                    );
+                }
+            }
+            Self::Multi { backends } => {
+                if backends.is_empty() {
+                    check.error(
+                        "storage.backend.backends",
+                        "at least one backend is required",
+                    );
+                }
+                for backend in backends {
+                    backend.check(check);
+                }
+            }
+        }
+    }
+}
+
 /// Garbage collection configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GcConfig {
     /// Whether GC is enabled
     pub enabled: bool,
@@ -353,6 +1206,49 @@ pub struct GcConfig {
     pub min_free_space_gb: u32,
     /// How often to run GC
     pub run_interval: Duration,
+    /// Report what GC would delete without deleting anything
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Which unreferenced chunks GC deletes
+    #[serde(default)]
+    pub policy: GcPolicy,
+    /// Sweep incrementally, spending at most this long per run
+    ///
+    /// `None` sweeps the whole registry every run.
+    #[serde(default)]
+    pub time_budget: Option<Duration>,
+    /// Chunks examined per batch of an incremental sweep
+    #[serde(default = "default_gc_batch_size")]
+    pub batch_size: usize,
+}
+
+impl GcConfig {
+    /// The retention policy selected by [`Self::policy`]
+    pub fn retention_policy(&self) -> RetentionPolicy {
+        let retention_secs = self.retention_days as u64 * 24 * 3600;
+        match self.policy {
+            GcPolicy::KeepRecent => RetentionPolicy::KeepRecent(retention_secs),
+            GcPolicy::LeastRecentlyRead => RetentionPolicy::LeastRecentlyRead(retention_secs),
+            GcPolicy::TargetSize { max_bytes } => RetentionPolicy::TargetSize(max_bytes),
+        }
+    }
+}
+
+/// Retention policy selection for [`GcConfig`]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum GcPolicy {
+    /// Delete unreferenced chunks first seen more than `retention_days` ago
+    #[default]
+    KeepRecent,
+    /// Delete unreferenced chunks not read for `retention_days`, least
+    /// recently read first
+    LeastRecentlyRead,
+    /// Delete the oldest unreferenced chunks until the store is at most
+    /// `max_bytes`
+    TargetSize {
+        /// Target store size in bytes
+        max_bytes: u64,
+    },
 }
 
 impl Default for GcConfig {
@@ -362,18 +1258,33 @@ impl Default for GcConfig {
             retention_days: 30,
             min_free_space_gb: 10,
             run_interval: Duration::from_secs(3600),
+            dry_run: false,
+            policy: GcPolicy::KeepRecent,
+            time_budget: None,
+            batch_size: default_gc_batch_size(),
         }
     }
 }
 
+fn default_gc_batch_size() -> usize {
+    1000
+}
+
 /// Version management configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct VersionConfig {
-    /// Maximum number of versions to keep
+    /// Maximum number of versions to keep per file (0 = unlimited)
+    ///
+    /// The oldest versions beyond the limit are pruned when a new version
+    /// is created.
     pub max_versions: usize,
+    /// Never prune tagged versions, even past `max_versions`
+    #[serde(default = "default_keep_tagged")]
+    pub keep_tagged: bool,
     /// Auto-tag every N versions (0 = disabled)
     pub auto_tag_interval: usize,
-    /// Use compression for version diffs
+    /// Gzip encoded version nodes and patches
     pub diff_compression: bool,
 }
 
@@ -381,12 +1292,17 @@ impl Default for VersionConfig {
     fn default() -> Self {
         Self {
             max_versions: 100,
+            keep_tagged: true,
             auto_tag_interval: 10,
             diff_compression: true,
         }
     }
 }
 
+fn default_keep_tagged() -> bool {
+    true
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -437,4 +1353,165 @@ mod tests {
         config.fec.stripe_size = 0;
         assert!(config.validate().is_err());
     }
+
+    #[test]
+    fn test_config_file_round_trip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = Config::high_reliability();
+        for name in ["config.toml", "config.yaml", "config.json"] {
+            let path = dir.path().join(name);
+            config.to_file(&path).unwrap();
+            let loaded = Config::from_file(&path).unwrap();
+            assert_eq!(loaded.fec.data_shares, 10, "{}", name);
+            assert_eq!(loaded.gc.run_interval, config.gc.run_interval, "{}", name);
+        }
+        assert!(Config::default()
+            .to_file(dir.path().join("config.ini"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_profile_registry() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut registry = ProfileRegistry::new();
+        registry
+            .define("archive", Config::default().with_fec_params(8, 8))
+            .unwrap();
+        assert!(registry
+            .define("minimal_storage", Config::default())
+            .is_err());
+        assert!(registry
+            .define("broken", Config::default().with_fec_params(0, 4))
+            .is_err());
+
+        for name in ["profiles.toml", "profiles.yaml", "profiles.json"] {
+            let path = dir.path().join(name);
+            registry.save(&path).unwrap();
+            let mut loaded = ProfileRegistry::new();
+            loaded.load(&path).unwrap();
+            assert_eq!(loaded.names().collect::<Vec<_>>(), ["archive"], "{}", name);
+            assert_eq!(loaded.get("archive").unwrap().parity_shards, 8, "{}", name);
+        }
+
+        // Partial profiles keep defaults; one bad profile rejects the file
+        let path = dir.path().join("partial.toml");
+        std::fs::write(&path, "[fast]\ncompression_enabled = false\n").unwrap();
+        registry.load(&path).unwrap();
+        assert!(!registry.get("fast").unwrap().compression_enabled);
+        std::fs::write(&path, "[ok]\n[bad]\nchunk_size = 0\n").unwrap();
+        assert!(registry.load(&path).is_err());
+        assert!(registry.get("ok").is_none());
+    }
+
+    #[test]
+    fn test_config_profile_lookup() {
+        assert_eq!(
+            Config::profile("high_reliability")
+                .unwrap()
+                .fec
+                .parity_shares,
+            10
+        );
+        assert!(Config::profile("test-lookup").is_none());
+        ProfileRegistry::global()
+            .write()
+            .define("test-lookup", Config::default().with_chunk_size(4096))
+            .unwrap();
+        assert_eq!(Config::profile("test-lookup").unwrap().chunk_size, 4096);
+        ProfileRegistry::global().write().remove("test-lookup");
+    }
+
+    #[test]
+    fn test_partial_config_file_and_overrides() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "chunk_size = 131072\n[gc]\nenabled = false\n").unwrap();
+        let config = Config::from_file(&path).unwrap();
+        assert_eq!(config.chunk_size, 131072);
+        assert!(!config.gc.enabled);
+        assert_eq!(config.fec.data_shares, FecConfig::default().data_shares);
+
+        let vars = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let config = config
+            .with_overrides(vars(&[
+                ("SAORSA_FEC_GC__ENABLED", "true"),
+                ("SAORSA_FEC_STORAGE__PARALLEL_OPERATIONS", "8"),
+                ("SAORSA_FEC_GC__TIME_BUDGET", r#"{"secs": 5, "nanos": 0}"#),
+                ("PATH", "/usr/bin"),
+            ]))
+            .unwrap();
+        assert!(config.gc.enabled);
+        assert_eq!(config.storage.parallel_operations, 8);
+        assert_eq!(config.gc.time_budget, Some(Duration::from_secs(5)));
+
+        let err = config
+            .clone()
+            .with_overrides(vars(&[("SAORSA_FEC_GC__ENABLED", "maybe")]))
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("SAORSA_FEC_GC__ENABLED"));
+        std::fs::write(&path, "[fec]\ndata_shares = \"many\"\n").unwrap();
+        let err = Config::from_file(&path).unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("fec.data_shares"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_check_reports_every_problem() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("not-a-dir");
+        std::fs::write(&file, b"").unwrap();
+
+        let mut config = Config {
+            compression_level: 12,
+            chunk_size: 1000,
+            ..Config::default()
+        };
+        config.storage.parallel_operations = 0;
+        config.storage.backend = StorageBackend::Local {
+            path: file.display().to_string(),
+        };
+        config.gc.retention_days = 0;
+
+        let check = config.check();
+        let fields = |issues: &[ConfigIssue]| issues.iter().map(|i| i.field).collect::<Vec<_>>();
+        assert_eq!(
+            fields(&check.errors),
+            [
+                "compression_level",
+                "storage.parallel_operations",
+                "storage.backend.path"
+            ]
+        );
+        assert_eq!(
+            fields(&check.warnings),
+            ["chunk_size", "chunk_size", "gc.retention_days"]
+        );
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("compression_level: must be between 0 and 9, got 12"));
+        assert!(err.contains("storage.parallel_operations"));
+
+        assert!(Config::default().check().is_ok());
+    }
+
+    #[test]
+    fn test_fips_policy_rejects_plain_convergent() {
+        let config = Config::default().with_crypto_policy(CryptoPolicy::Fips);
+        assert!(config
+            .clone()
+            .with_encryption_mode(EncryptionMode::Convergent)
+            .validate()
+            .is_err());
+        assert!(config
+            .with_encryption_mode(EncryptionMode::ConvergentWithSecret)
+            .validate()
+            .is_ok());
+    }
 }
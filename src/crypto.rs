@@ -9,59 +9,140 @@ use aes_gcm::{
     aead::{Aead, AeadCore, KeyInit, OsRng},
     Aes256Gcm, Key, Nonce,
 };
-use anyhow::{Context, Result};
 // blake3::Hasher removed as we're using SHA-256 for v0.3 spec
 use hkdf::Hkdf;
 use rand_core::RngCore;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use subtle::ConstantTimeEq;
+use thiserror::Error;
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
-/// Encryption configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct EncryptionConfig {
-    /// Whether encryption is enabled
-    pub enabled: bool,
-    /// Encryption mode to use
-    pub mode: EncryptionMode,
-    /// Optional convergence secret for controlled deduplication
-    #[serde(skip_serializing, skip_deserializing)]
-    pub convergence_secret: Option<ConvergenceSecret>,
+use crate::keystore::KeystoreError;
+use crate::secure_memory::SecretBytes;
+use crate::stream::StreamError;
+
+pub use crate::config::{EncryptionConfig, EncryptionMode};
+
+/// Errors from encryption, decryption and key handling
+#[derive(Debug, Error)]
+pub enum CryptoError {
+    #[error("{0} encryption failed")]
+    Encryption(&'static str),
+
+    /// The ciphertext failed authentication: the key is wrong or the data
+    /// has been modified
+    #[error("{0} decryption failed")]
+    Decryption(&'static str),
+
+    #[error("{0} too short")]
+    TooShort(&'static str),
+
+    #[error("Nonce mismatch in encrypted data")]
+    NonceMismatch,
+
+    #[error("Key derivation failed: {0}")]
+    KeyDerivation(String),
+
+    /// An input the operation needs was not supplied
+    #[error("{0}")]
+    Missing(&'static str),
+
+    #[error("Random keys cannot be reconstructed without external storage")]
+    KeyNotRecoverable,
+
+    /// The engine does not support the requested mode or format
+    #[error("{0}")]
+    Unsupported(String),
+
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+
+    #[error("KEM operation failed: {0}")]
+    Kem(String),
+
+    #[error("HPKE operation failed: {0}")]
+    Hpke(String),
+
+    #[error("Invalid key split: {0}")]
+    KeySplit(String),
+
+    #[error("Master key {0} not in keystore")]
+    MasterKeyNotFound(String),
+
+    #[error(transparent)]
+    Keystore(#[from] KeystoreError),
+
+    /// Sealing or opening segmented data failed
+    #[error(transparent)]
+    Stream(#[from] StreamError),
 }
 
+pub type Result<T> = std::result::Result<T, CryptoError>;
+
 /// Secret used for convergent encryption with controlled deduplication
 #[derive(Debug, Clone, Zeroize, ZeroizeOnDrop)]
-pub struct ConvergenceSecret([u8; 32]);
+pub struct ConvergenceSecret(SecretBytes<32>);
 
 impl ConvergenceSecret {
     /// Create a new convergence secret
     pub fn new(secret: [u8; 32]) -> Self {
-        Self(secret)
+        Self(SecretBytes::new(secret))
     }
 
     /// Get the secret as bytes
     pub fn as_bytes(&self) -> &[u8; 32] {
-        &self.0
+        self.0.as_bytes()
     }
 }
 
-/// Encryption mode selection
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub enum EncryptionMode {
-    /// Pure convergent encryption (deduplication across all users)
-    Convergent,
-    /// Convergent encryption with secret (controlled deduplication)
-    ConvergentWithSecret,
-    /// Random key encryption (no deduplication)
-    RandomKey,
-}
-
 /// Encryption algorithm selection
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum EncryptionAlgorithm {
     /// AES-256-GCM authenticated encryption
     Aes256Gcm,
+    /// ChaCha20-Poly1305 authenticated encryption
+    ChaCha20Poly1305,
+    /// AES-256-GCM-SIV, which stays secure if a nonce is ever reused
+    Aes256GcmSiv,
+}
+
+impl EncryptionAlgorithm {
+    /// Fastest AEAD on this CPU
+    ///
+    /// AES-GCM when the CPU has AES instructions, ChaCha20-Poly1305 otherwise,
+    /// since software AES is both slower and prone to timing leaks.
+    pub fn detect() -> Self {
+        if has_aes_instructions() {
+            Self::Aes256Gcm
+        } else {
+            Self::ChaCha20Poly1305
+        }
+    }
+
+    /// Name used in error messages
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Aes256Gcm => "AES-256-GCM",
+            Self::ChaCha20Poly1305 => "ChaCha20Poly1305",
+            Self::Aes256GcmSiv => "AES-256-GCM-SIV",
+        }
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn has_aes_instructions() -> bool {
+    std::arch::is_x86_feature_detected!("aes") && std::arch::is_x86_feature_detected!("pclmulqdq")
+}
+
+#[cfg(target_arch = "aarch64")]
+fn has_aes_instructions() -> bool {
+    std::arch::is_aarch64_feature_detected!("aes")
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+fn has_aes_instructions() -> bool {
+    false
 }
 
 /// Key derivation method
@@ -88,17 +169,22 @@ pub struct EncryptionMetadata {
 
 /// Encryption key wrapper with secure handling
 #[derive(Zeroize, ZeroizeOnDrop)]
-pub struct EncryptionKey([u8; 32]);
+pub struct EncryptionKey(SecretBytes<32>);
 
 impl EncryptionKey {
     /// Create a new encryption key
     pub fn new(key: [u8; 32]) -> Self {
-        Self(key)
+        Self(SecretBytes::new(key))
     }
 
     /// Get the key as bytes
     pub fn as_bytes(&self) -> &[u8; 32] {
-        &self.0
+        self.0.as_bytes()
+    }
+
+    /// Whether the key is locked into RAM (see the `mlock` feature)
+    pub fn is_locked(&self) -> bool {
+        self.0.is_locked()
     }
 }
 
@@ -116,13 +202,27 @@ impl CryptoEngine {
 
     /// Encrypt data using the specified key
     pub fn encrypt(&mut self, data: &[u8], key: &EncryptionKey) -> Result<Vec<u8>> {
-        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key.as_bytes()));
         let nonce_bytes = Aes256Gcm::generate_nonce(&mut OsRng);
-        self.last_nonce = Some(nonce_bytes.into());
+        self.encrypt_with_nonce(data, key, nonce_bytes.into())
+    }
+
+    /// Encrypt data under the given key and nonce
+    ///
+    /// The nonce must never repeat for the same key; see
+    /// [`derive_convergent_nonce`] for convergent keys.
+    pub fn encrypt_with_nonce(
+        &mut self,
+        data: &[u8],
+        key: &EncryptionKey,
+        nonce: [u8; 12],
+    ) -> Result<Vec<u8>> {
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key.as_bytes()));
+        let nonce_bytes = Nonce::from(nonce);
+        self.last_nonce = Some(nonce);
 
         let ciphertext = cipher
             .encrypt(&nonce_bytes, data)
-            .map_err(|_| anyhow::anyhow!("Encryption failed"))?;
+            .map_err(|_| CryptoError::Encryption(EncryptionAlgorithm::Aes256Gcm.name()))?;
 
         // Prepend nonce to ciphertext for storage
         let mut result = Vec::with_capacity(12 + ciphertext.len());
@@ -135,7 +235,7 @@ impl CryptoEngine {
     /// Decrypt data using the specified key
     pub fn decrypt(&self, encrypted_data: &[u8], key: &EncryptionKey) -> Result<Vec<u8>> {
         if encrypted_data.len() < 12 {
-            anyhow::bail!("Encrypted data too short to contain nonce");
+            return Err(CryptoError::TooShort("Encrypted data"));
         }
 
         let (nonce_bytes, ciphertext) = encrypted_data.split_at(12);
@@ -144,7 +244,7 @@ impl CryptoEngine {
         let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key.as_bytes()));
         let plaintext = cipher
             .decrypt(nonce, ciphertext)
-            .map_err(|_| anyhow::anyhow!("Decryption failed"))?;
+            .map_err(|_| CryptoError::Decryption(EncryptionAlgorithm::Aes256Gcm.name()))?;
 
         Ok(plaintext)
     }
@@ -163,12 +263,13 @@ impl CryptoEngine {
     ) -> Result<EncryptionKey> {
         let metadata = metadata
             .as_ref()
-            .context("No encryption metadata available")?;
+            .ok_or(CryptoError::Missing("No encryption metadata available"))?;
 
         match metadata.key_derivation {
             KeyDerivation::Blake3Convergent => {
-                let data = original_data
-                    .context("Original data required for convergent key reconstruction")?;
+                let data = original_data.ok_or(CryptoError::Missing(
+                    "Original data required for convergent key reconstruction",
+                ))?;
 
                 let secret = if metadata.convergence_secret_id.is_some() {
                     convergence_secret.map(|s| s.as_bytes())
@@ -178,9 +279,7 @@ impl CryptoEngine {
 
                 derive_convergent_key(data, secret)
             }
-            KeyDerivation::Random => {
-                anyhow::bail!("Random keys cannot be reconstructed without external storage")
-            }
+            KeyDerivation::Random => Err(CryptoError::KeyNotRecoverable),
         }
     }
 }
@@ -223,7 +322,7 @@ pub fn derive_convergent_key(content: &[u8], secret: Option<&[u8; 32]>) -> Resul
     let hkdf = Hkdf::<Sha256>::new(Some(&salt), &content_hash);
     let mut key = [0u8; 32];
     hkdf.expand(b"saorsa-fec:aead:v1", &mut key)
-        .map_err(|_| anyhow::anyhow!("HKDF expand failed unexpectedly"))?;
+        .map_err(|e| CryptoError::KeyDerivation(e.to_string()))?;
 
     let encryption_key = EncryptionKey::new(key);
 
@@ -281,6 +380,19 @@ pub fn generate_deterministic_nonce(
     nonce
 }
 
+/// Derive the nonce for a convergent key
+///
+/// Each convergent key encrypts exactly one plaintext, so a nonce derived
+/// from the key alone never repeats under it and keeps the ciphertext
+/// deterministic for deduplication.
+pub fn derive_convergent_nonce(key: &EncryptionKey) -> Result<[u8; 12]> {
+    let hkdf = Hkdf::<Sha256>::new(None, key.as_bytes());
+    let mut nonce = [0u8; 12];
+    hkdf.expand(b"saorsa-fec:nonce:v1", &mut nonce)
+        .map_err(|e| CryptoError::KeyDerivation(e.to_string()))?;
+    Ok(nonce)
+}
+
 /// Verify MAC in constant time to prevent timing attacks
 ///
 /// **SECURITY**: Uses constant-time comparison to prevent timing-based
@@ -308,7 +420,7 @@ pub fn derive_mac_key(encryption_key: &EncryptionKey) -> Result<[u8; 32]> {
     let hkdf = Hkdf::<Sha256>::new(Some(&salt), encryption_key.as_bytes());
     let mut mac_key = [0u8; 32];
     hkdf.expand(b"saorsa-fec:mac:v1", &mut mac_key)
-        .map_err(|_| anyhow::anyhow!("HKDF expand failed unexpectedly"))?;
+        .map_err(|e| CryptoError::KeyDerivation(e.to_string()))?;
 
     Ok(mac_key)
 }
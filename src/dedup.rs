@@ -0,0 +1,236 @@
+//! Oblivious deduplication checks
+//!
+//! Asking a server whether it already holds a shard normally means sending
+//! the shard's CID, and under convergent encryption that CID follows from
+//! the plaintext, so the server learns exactly which content the client has.
+//! The exchange here reveals only a short CID prefix instead:
+//!
+//! 1. The client sends the first [`DedupClient::with_prefix_len`] bytes of the
+//!    CID and a fresh random salt.
+//! 2. The server answers with the salted hash of every CID it holds that
+//!    starts with the prefix, in sorted order.
+//! 3. The client salts its own CID and checks whether it is in the answer.
+//!
+//! The server sees only which bucket of CIDs the client is interested in,
+//! and the salt keeps the client from learning the other CIDs in it.
+//! Implement [`DedupOracle`] to run the server side over any transport.
+
+use async_trait::async_trait;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::storage::{Cid, MaybeSendSync, StorageBackend};
+use crate::FecError;
+
+/// Default CID prefix length: one bucket in 65536
+pub const DEFAULT_PREFIX_LEN: usize = 2;
+/// CIDs listed from the backend at a time while reading a bucket
+const BUCKET_PAGE_SIZE: usize = 1024;
+
+/// Client request for the salted CIDs in one bucket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupQuery {
+    /// Leading bytes shared by every CID in the bucket
+    pub prefix: Vec<u8>,
+    /// Fresh per-query salt for the answer
+    pub salt: [u8; 32],
+}
+
+impl DedupQuery {
+    /// Server-side answer to this query from the CIDs held
+    pub fn answer(&self, cids: impl IntoIterator<Item = [u8; 32]>) -> DedupResponse {
+        let mut blinded: Vec<[u8; 32]> = cids
+            .into_iter()
+            .filter(|cid| cid.starts_with(&self.prefix))
+            .map(|cid| blind(&self.salt, &cid))
+            .collect();
+        // Sorting hides the server's storage order
+        blinded.sort_unstable();
+        DedupResponse { blinded }
+    }
+}
+
+/// Salted CIDs in the queried bucket
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DedupResponse {
+    /// Keyed BLAKE3 hashes of the CIDs, sorted
+    pub blinded: Vec<[u8; 32]>,
+}
+
+/// Server side of the oblivious dedup exchange
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait DedupOracle: MaybeSendSync {
+    /// Answer a bucket query, typically with [`DedupQuery::answer`]
+    ///
+    /// Transport failures are reported as [`FecError::Backend`].
+    async fn query(&self, query: &DedupQuery) -> Result<DedupResponse, FecError>;
+}
+
+/// Client side of the oblivious dedup exchange
+#[derive(Clone)]
+pub struct DedupClient {
+    oracle: Arc<dyn DedupOracle>,
+    prefix_len: usize,
+}
+
+impl DedupClient {
+    /// Client querying `oracle` with [`DEFAULT_PREFIX_LEN`]-byte prefixes
+    pub fn new(oracle: Arc<dyn DedupOracle>) -> Self {
+        Self {
+            oracle,
+            prefix_len: DEFAULT_PREFIX_LEN,
+        }
+    }
+
+    /// Reveal `prefix_len` bytes of each CID
+    ///
+    /// Longer prefixes mean smaller answers but less privacy.
+    pub fn with_prefix_len(mut self, prefix_len: usize) -> Self {
+        self.prefix_len = prefix_len.min(32);
+        self
+    }
+
+    /// Whether the server holds the shard with CID `cid`
+    pub async fn contains(&self, cid: &[u8; 32]) -> Result<bool, FecError> {
+        let mut salt = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let query = DedupQuery {
+            prefix: cid[..self.prefix_len].to_vec(),
+            salt,
+        };
+        let response = self.oracle.query(&query).await?;
+        Ok(response.blinded.binary_search(&blind(&salt, cid)).is_ok())
+    }
+}
+
+/// Oracle answering from the shards in a storage backend
+///
+/// Each query lists only the queried bucket, starting the backend's paged
+/// listing at the bucket's first possible CID, so answers are always current
+/// and cost the bucket's size on backends that page natively.
+pub struct StorageDedupOracle<B: StorageBackend> {
+    backend: Arc<B>,
+}
+
+impl<B: StorageBackend> StorageDedupOracle<B> {
+    /// Answer queries from `backend`
+    pub fn new(backend: Arc<B>) -> Self {
+        Self { backend }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl<B: StorageBackend> DedupOracle for StorageDedupOracle<B> {
+    async fn query(&self, query: &DedupQuery) -> Result<DedupResponse, FecError> {
+        if query.prefix.len() > 32 {
+            return Ok(DedupResponse::default());
+        }
+        let mut cids = Vec::new();
+        let mut after = bucket_cursor(&query.prefix);
+        loop {
+            let page = self
+                .backend
+                .list_shards_paged(after.as_ref(), BUCKET_PAGE_SIZE)
+                .await?;
+            let listed = page.cids.len();
+            let before = cids.len();
+            cids.extend(
+                page.cids
+                    .iter()
+                    .map(|cid| *cid.as_bytes())
+                    .take_while(|cid| cid.starts_with(&query.prefix)),
+            );
+            // The listing is sorted, so the bucket ends at the first CID outside it
+            if cids.len() - before < listed {
+                break;
+            }
+            match page.next {
+                Some(next) => after = Some(next),
+                None => break,
+            }
+        }
+        Ok(query.answer(cids))
+    }
+}
+
+/// Cursor just before the first CID starting with `prefix`, `None` when that
+/// is the lowest CID
+fn bucket_cursor(prefix: &[u8]) -> Option<Cid> {
+    let mut cursor = [0u8; 32];
+    cursor[..prefix.len()].copy_from_slice(prefix);
+    // Step back one from the bucket's first CID, borrowing as in subtraction
+    for byte in cursor.iter_mut().rev() {
+        let (value, borrow) = byte.overflowing_sub(1);
+        *byte = value;
+        if !borrow {
+            return Some(Cid::new(cursor));
+        }
+    }
+    None
+}
+
+fn blind(salt: &[u8; 32], cid: &[u8; 32]) -> [u8; 32] {
+    *blake3::keyed_hash(salt, cid).as_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{MemoryStorage, Shard, ShardHeader};
+    use crate::EncryptionMode;
+
+    #[tokio::test]
+    async fn test_oblivious_dedup_check() -> Result<(), FecError> {
+        let backend = Arc::new(MemoryStorage::new());
+        let header = ShardHeader::new(EncryptionMode::Convergent, (1, 1), 4, [0u8; 32]);
+        let shard = Shard::new(header, vec![1, 2, 3, 4]);
+        let cid = shard.cid()?;
+        backend.put_shard(&cid, &shard).await?;
+
+        let client = DedupClient::new(Arc::new(StorageDedupOracle::new(backend)));
+        assert!(client.contains(cid.as_bytes()).await?);
+        assert!(!client.contains(&[0xAB; 32]).await?);
+
+        // The query carries only the prefix, and the answer no plain CIDs
+        let query = DedupQuery {
+            prefix: cid.as_bytes()[..2].to_vec(),
+            salt: [7u8; 32],
+        };
+        let response = query.answer([*cid.as_bytes(), [0xAB; 32]]);
+        assert_eq!(response.blinded.len(), 1);
+        assert_ne!(&response.blinded[0], cid.as_bytes());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_oracle_reads_only_the_queried_bucket() -> Result<(), FecError> {
+        let backend = Arc::new(MemoryStorage::new());
+        let oracle = StorageDedupOracle::new(backend.clone());
+        let mut cids = Vec::new();
+        for i in 0..64u8 {
+            let header = ShardHeader::new(EncryptionMode::Convergent, (1, 1), 1, [0u8; 32]);
+            let shard = Shard::new(header, vec![i]);
+            let cid = shard.cid()?;
+            backend.put_shard(&cid, &shard).await?;
+            cids.push(*cid.as_bytes());
+        }
+
+        let query = DedupQuery {
+            prefix: cids[0][..1].to_vec(),
+            salt: [3u8; 32],
+        };
+        let expected = query.answer(cids.iter().copied());
+        assert!(!expected.blinded.is_empty());
+        assert_eq!(oracle.query(&query).await?.blinded, expected.blinded);
+
+        assert_eq!(bucket_cursor(&[]), None);
+        assert_eq!(bucket_cursor(&[0, 0]), None);
+        let mut before = [0xffu8; 32];
+        before[0] = 0x11;
+        assert_eq!(bucket_cursor(&[0x12]), Some(Cid::new(before)));
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+//! Crate-level error type
+//!
+//! Each module reports failures with its own error enum. [`Error`] wraps
+//! them so code driving a [`StoragePipeline`](crate::StoragePipeline) can
+//! handle a single type, and [`Error::kind`] sorts failures into the few
+//! categories callers usually act on.
+
+use thiserror::Error;
+
+use crate::audit::AuditError;
+use crate::chunk_registry::RegistryError;
+use crate::config::ConfigError;
+use crate::crypto::CryptoError;
+use crate::fec::ShardError;
+use crate::gc::GcError;
+use crate::keystore::KeystoreError;
+use crate::metadata::MetadataError;
+use crate::pipeline::PipelineError;
+use crate::stream::StreamError;
+use crate::version::VersionError;
+use crate::wire::WireError;
+use crate::FecError;
+
+/// Any error returned by this crate
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Fec(#[from] FecError),
+
+    #[error(transparent)]
+    Shard(#[from] ShardError),
+
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+
+    #[error(transparent)]
+    Keystore(#[from] KeystoreError),
+
+    #[error(transparent)]
+    Stream(#[from] StreamError),
+
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
+    #[error(transparent)]
+    Metadata(#[from] MetadataError),
+
+    #[error(transparent)]
+    Version(#[from] VersionError),
+
+    #[error(transparent)]
+    Registry(#[from] RegistryError),
+
+    #[error(transparent)]
+    Audit(#[from] AuditError),
+
+    #[error(transparent)]
+    Gc(#[from] GcError),
+
+    #[error(transparent)]
+    Pipeline(#[from] PipelineError),
+
+    #[error(transparent)]
+    Wire(#[from] WireError),
+}
+
+/// Broad category of an [`Error`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// A shard, file, version, chunk or key does not exist
+    NotFound,
+    /// Stored data failed an integrity check
+    Corruption,
+    /// Too few shares survive to reconstruct the data
+    InsufficientShares,
+    /// An argument, parameter or configuration value was rejected
+    InvalidInput,
+    /// The filesystem or another I/O channel failed
+    Io,
+    /// Anything else
+    Other,
+}
+
+impl Error {
+    /// Category of this error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Fec(e) => fec_kind(e),
+            Error::Shard(e) => shard_kind(e),
+            Error::Crypto(e) => crypto_kind(e),
+            Error::Keystore(e) => keystore_kind(e),
+            Error::Stream(e) => match e {
+                StreamError::CorruptSegment(_) | StreamError::Truncated => ErrorKind::Corruption,
+                StreamError::ZeroSegmentSize | StreamError::SegmentOutOfRange { .. } => {
+                    ErrorKind::InvalidInput
+                }
+                StreamError::Io(_) => ErrorKind::Io,
+                _ => ErrorKind::Other,
+            },
+            Error::Config(e) => match e {
+                ConfigError::Io { .. } => ErrorKind::Io,
+                _ => ErrorKind::InvalidInput,
+            },
+            Error::Metadata(e) => metadata_kind(e),
+            Error::Version(e) => match e {
+                VersionError::VersionNotFound(_)
+                | VersionError::NoVersions(_)
+                | VersionError::BranchNotFound(_)
+                | VersionError::TagNotFound(_)
+                | VersionError::UnknownName(_) => ErrorKind::NotFound,
+                VersionError::NameTaken(_) | VersionError::WrongFile => ErrorKind::InvalidInput,
+                VersionError::Unverified { .. }
+                | VersionError::InvalidPatch(_)
+                | VersionError::EmptyDelta
+                | VersionError::UnknownDeltaFormat(_) => ErrorKind::Corruption,
+                VersionError::Metadata(e) => metadata_kind(e),
+                VersionError::Registry(e) => registry_kind(e),
+                _ => ErrorKind::Other,
+            },
+            Error::Registry(e) => registry_kind(e),
+            Error::Audit(e) => match e {
+                AuditError::Io { .. } => ErrorKind::Io,
+                AuditError::Serialization(_) => ErrorKind::Other,
+                _ => ErrorKind::Corruption,
+            },
+            Error::Gc(e) => match e {
+                GcError::CorruptCursor(_) => ErrorKind::Corruption,
+                GcError::Io { .. } => ErrorKind::Io,
+            },
+            Error::Pipeline(e) => match e {
+                PipelineError::NoShards(_) => ErrorKind::InsufficientShares,
+                PipelineError::ChunkCorrupted(_)
+                | PipelineError::RebuiltShardMismatch(_)
+                | PipelineError::UnexpectedShard(_)
+                | PipelineError::EmptyChunk => ErrorKind::Corruption,
+                _ => ErrorKind::Other,
+            },
+            Error::Wire(e) => match e {
+                WireError::UnsupportedVersion(_) | WireError::UnknownRequiredFlags(_) => {
+                    ErrorKind::InvalidInput
+                }
+                _ => ErrorKind::Corruption,
+            },
+        }
+    }
+}
+
+fn fec_kind(e: &FecError) -> ErrorKind {
+    match e {
+        FecError::ShardNotFound { .. } | FecError::MetadataNotFound { .. } => ErrorKind::NotFound,
+        FecError::CorruptShard { .. } => ErrorKind::Corruption,
+        FecError::InsufficientShares { .. } => ErrorKind::InsufficientShares,
+        FecError::InvalidParameters { .. }
+        | FecError::InvalidShareIndex { .. }
+        | FecError::SizeMismatch { .. } => ErrorKind::InvalidInput,
+        FecError::Io(_) => ErrorKind::Io,
+        _ => ErrorKind::Other,
+    }
+}
+
+fn shard_kind(e: &ShardError) -> ErrorKind {
+    match e {
+        ShardError::InsufficientShards { .. } => ErrorKind::InsufficientShares,
+        ShardError::UnknownObject(_) => ErrorKind::NotFound,
+        ShardError::DataTooLarge { .. } | ShardError::IndexOutOfRange(_) => ErrorKind::InvalidInput,
+        ShardError::Storage(e) => fec_kind(e),
+        _ => ErrorKind::Other,
+    }
+}
+
+fn crypto_kind(e: &CryptoError) -> ErrorKind {
+    match e {
+        CryptoError::Decryption(_) | CryptoError::TooShort(_) | CryptoError::NonceMismatch => {
+            ErrorKind::Corruption
+        }
+        CryptoError::MasterKeyNotFound(_) => ErrorKind::NotFound,
+        CryptoError::Missing(_) | CryptoError::InvalidKey(_) | CryptoError::Unsupported(_) => {
+            ErrorKind::InvalidInput
+        }
+        CryptoError::Keystore(e) => keystore_kind(e),
+        _ => ErrorKind::Other,
+    }
+}
+
+fn keystore_kind(e: &KeystoreError) -> ErrorKind {
+    match e {
+        KeystoreError::KeyNotFound(_) | KeystoreError::UnknownSecret(_) => ErrorKind::NotFound,
+        KeystoreError::Malformed(_) => ErrorKind::Corruption,
+        KeystoreError::Io { .. } => ErrorKind::Io,
+        _ => ErrorKind::Other,
+    }
+}
+
+fn metadata_kind(e: &MetadataError) -> ErrorKind {
+    match e {
+        MetadataError::NotFound(_) => ErrorKind::NotFound,
+        MetadataError::InvalidSignature
+        | MetadataError::MalformedSignature(_)
+        | MetadataError::DuplicateChunk { .. } => ErrorKind::Corruption,
+        MetadataError::NotSigned | MetadataError::InvalidKey(_) => ErrorKind::InvalidInput,
+        MetadataError::Io { .. } => ErrorKind::Io,
+        _ => ErrorKind::Other,
+    }
+}
+
+fn registry_kind(e: &RegistryError) -> ErrorKind {
+    match e {
+        RegistryError::ChunkNotFound(_) => ErrorKind::NotFound,
+        RegistryError::NotASnapshot
+        | RegistryError::UnsupportedFormat(_)
+        | RegistryError::ChecksumMismatch
+        | RegistryError::CorruptSnapshot { .. } => ErrorKind::Corruption,
+        RegistryError::Io { .. } => ErrorKind::Io,
+        _ => ErrorKind::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_kind_sees_through_wrappers() {
+        let missing: Error = FecError::ShardNotFound { cid: "00".into() }.into();
+        assert_eq!(missing.kind(), ErrorKind::NotFound);
+
+        let lost: Error = ShardError::InsufficientShards { have: 2, need: 4 }.into();
+        assert_eq!(lost.kind(), ErrorKind::InsufficientShares);
+
+        let tampered: Error = VersionError::Metadata(MetadataError::InvalidSignature).into();
+        assert_eq!(tampered.kind(), ErrorKind::Corruption);
+        assert_eq!(
+            tampered.to_string(),
+            "Signature does not match the signed contents"
+        );
+    }
+}
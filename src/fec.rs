@@ -7,16 +7,49 @@
 //! Features Reed-Solomon/LRC codec with pluggable backends, fixed shard size,
 //! CRC validation, and proactive repair hooks.
 
-use anyhow::Result;
 use blake3;
 use crc32fast::Hasher as Crc32Hasher;
-use reed_solomon_simd::ReedSolomonEncoder;
+use reed_solomon_simd::{ReedSolomonDecoder, ReedSolomonEncoder};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use thiserror::Error;
 use tracing::{debug, info, warn};
 
-/// FEC parameters for encoding/decoding
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+use crate::FecError;
+
+/// Errors from the shard layer
+#[derive(Debug, Error)]
+pub enum ShardError {
+    #[error("Data size {size} exceeds maximum {max} for given parameters")]
+    DataTooLarge { size: usize, max: usize },
+
+    #[error("Insufficient shards for reconstruction: have {have}, need {need}")]
+    InsufficientShards { have: usize, need: usize },
+
+    #[error("Missing data shard {0}")]
+    MissingDataShard(usize),
+
+    #[error("Shard index {0} out of range")]
+    IndexOutOfRange(u16),
+
+    #[error("Repair hooks do not manage object {0}")]
+    UnknownObject(String),
+
+    #[error("Reed-Solomon coding failed: {0}")]
+    Codec(#[from] reed_solomon_simd::Error),
+
+    /// Failure in the storage behind a set of [`RepairHooks`]
+    #[error(transparent)]
+    Storage(#[from] FecError),
+}
+
+pub type Result<T> = std::result::Result<T, ShardError>;
+
+/// Shard-layer parameters from before the crate had one [`crate::FecParams`]
+///
+/// Converts into [`crate::FecParams`], which every function here accepts.
+#[deprecated(since = "0.4.12", note = "use `saorsa_fec::FecParams`")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct FecParams {
     /// Number of data shards (k)
     pub k: u16,
@@ -26,23 +59,17 @@ pub struct FecParams {
     pub shard_size: usize,
 }
 
+#[allow(deprecated)]
 impl FecParams {
-    /// Create new FEC parameters
+    /// Create new FEC parameters, checked as [`crate::FecParams`] checks them
     pub fn new(k: u16, m: u16, shard_size: usize) -> Result<Self> {
-        if k == 0 || m == 0 {
-            anyhow::bail!("Invalid parameters: k={}, m={}", k, m);
-        }
-
-        // GF(256) limits us to 255 total shards
-        if k as u32 + m as u32 > 255 {
-            anyhow::bail!("Total shards (k+m) cannot exceed 255");
-        }
-
-        if shard_size == 0 {
-            anyhow::bail!("Shard size must be greater than 0");
-        }
-
-        Ok(Self { k, m, shard_size })
+        let symbol_size = u32::try_from(shard_size).map_err(|_| FecError::InvalidParameters {
+            k: k as usize,
+            n: k as usize + m as usize,
+        })?;
+        Ok(crate::FecParams::new(k, m)?
+            .with_symbol_size(symbol_size)?
+            .into())
     }
 
     /// Get total number of shards (n = k + m)
@@ -56,6 +83,29 @@ impl FecParams {
     }
 }
 
+#[allow(deprecated)]
+impl From<FecParams> for crate::FecParams {
+    fn from(params: FecParams) -> Self {
+        Self {
+            data_shares: params.k,
+            parity_shares: params.m,
+            // Too large to encode either way; saturate rather than wrap
+            symbol_size: u32::try_from(params.shard_size).unwrap_or(u32::MAX),
+        }
+    }
+}
+
+#[allow(deprecated)]
+impl From<crate::FecParams> for FecParams {
+    fn from(params: crate::FecParams) -> Self {
+        Self {
+            k: params.data_shares,
+            m: params.parity_shares,
+            shard_size: params.symbol_size as usize,
+        }
+    }
+}
+
 /// Individual shard with data and integrity check
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Shard {
@@ -107,10 +157,11 @@ pub trait RepairHooks: Send + Sync {
 }
 
 /// Encode data into erasure coded shards
-pub fn encode(data: &[u8], params: FecParams) -> Result<Vec<Shard>> {
-    let k = params.k as usize;
-    let m = params.m as usize;
-    let shard_size = params.shard_size;
+pub fn encode(data: &[u8], params: impl Into<crate::FecParams>) -> Result<Vec<Shard>> {
+    let params = params.into();
+    let k = params.data_shares as usize;
+    let m = params.parity_shares as usize;
+    let shard_size = params.symbol_size as usize;
 
     // Pad data to multiple of k * shard_size
     let total_size = k * shard_size;
@@ -118,11 +169,10 @@ pub fn encode(data: &[u8], params: FecParams) -> Result<Vec<Shard>> {
     if padded_data.len() < total_size {
         padded_data.resize(total_size, 0);
     } else if padded_data.len() > total_size {
-        anyhow::bail!(
-            "Data size {} exceeds maximum {} for given parameters",
-            data.len(),
-            total_size
-        );
+        return Err(ShardError::DataTooLarge {
+            size: data.len(),
+            max: total_size,
+        });
     }
 
     // Split data into k data shards
@@ -162,18 +212,17 @@ pub fn encode(data: &[u8], params: FecParams) -> Result<Vec<Shard>> {
 }
 
 /// Decode original data from available shards
-pub fn decode(shards: &[Shard], params: FecParams) -> Result<Vec<u8>> {
-    let k = params.k as usize;
-    let _m = params.m as usize;
-    let shard_size = params.shard_size;
+pub fn decode(shards: &[Shard], params: impl Into<crate::FecParams>) -> Result<Vec<u8>> {
+    let params = params.into();
+    let k = params.data_shares as usize;
+    let shard_size = params.symbol_size as usize;
 
     // Verify we have at least k shards
     if shards.len() < k {
-        anyhow::bail!(
-            "Insufficient shards for reconstruction: have {}, need {}",
-            shards.len(),
-            k
-        );
+        return Err(ShardError::InsufficientShards {
+            have: shards.len(),
+            need: k,
+        });
     }
 
     // Verify CRC for all shards
@@ -193,11 +242,10 @@ pub fn decode(shards: &[Shard], params: FecParams) -> Result<Vec<u8>> {
 
     // Check if we have enough valid shards
     if shard_map.len() < k {
-        anyhow::bail!(
-            "Insufficient valid shards: have {}, need {}",
-            shard_map.len(),
-            k
-        );
+        return Err(ShardError::InsufficientShards {
+            have: shard_map.len(),
+            need: k,
+        });
     }
 
     // Check if we have all data shards (no reconstruction needed)
@@ -212,76 +260,67 @@ pub fn decode(shards: &[Shard], params: FecParams) -> Result<Vec<u8>> {
         return Ok(result);
     }
 
-    // For reed-solomon-simd v3, we need all data shards to be present for simple recovery
-    // If some data shards are missing, we need to use a different approach
-
-    // Collect available indices and sort them
-    let mut available_indices: Vec<usize> = shard_map.keys().cloned().collect();
-    available_indices.sort();
-
-    // Check if we can use simple recovery (all data shards present)
-    let missing_data_shards: Vec<usize> = (0..k).filter(|i| !shard_map.contains_key(i)).collect();
-
-    if !missing_data_shards.is_empty() {
-        // Reed-solomon-simd v3 doesn't support direct reconstruction of missing data shards
-        // We need to use the original shards that we have and try a different approach
-        // For now, we'll attempt to use the available shards in order
-
-        // Take first k available shards
-        let mut result = Vec::with_capacity(k * shard_size);
-        let mut used_shards = Vec::new();
-
-        for idx in &available_indices {
-            if used_shards.len() >= k {
-                break;
-            }
-            if let Some(data) = shard_map.get(idx) {
-                used_shards.push((*idx, data.clone()));
-            }
-        }
-
-        // If we still don't have enough shards, fail
-        if used_shards.len() < k {
-            anyhow::bail!(
-                "Cannot reconstruct: only {} valid shards available, need {}",
-                used_shards.len(),
-                k
-            );
-        }
-
-        // For this simplified version, if we have any k shards and they're all data shards,
-        // we can just concatenate them
-        if used_shards.iter().all(|(idx, _)| *idx < k) {
-            // Sort by index and concatenate
-            used_shards.sort_by_key(|(idx, _)| *idx);
-            for (_, data) in used_shards {
-                result.extend_from_slice(&data);
-            }
+    // Feed every surviving shard into the decoder; it restores the missing data shards
+    let m = params.parity_shares as usize;
+    let mut decoder = ReedSolomonDecoder::new(k, m, shard_size)?;
+    for (&idx, data) in &shard_map {
+        if idx < k {
+            decoder.add_original_shard(idx, data)?;
+        } else if idx < k + m {
+            decoder.add_recovery_shard(idx - k, data)?;
         } else {
-            // Complex reconstruction needed - not fully supported by reed-solomon-simd v3
-            anyhow::bail!("Complex reconstruction with missing data shards is not yet supported");
+            return Err(ShardError::IndexOutOfRange(idx as u16));
         }
-
-        return Ok(result);
     }
+    let restored: HashMap<usize, Vec<u8>> = decoder
+        .decode()?
+        .restored_original_iter()
+        .map(|(idx, data)| (idx, data.to_vec()))
+        .collect();
 
-    // All data shards present - simple concatenation
     let mut result = Vec::with_capacity(k * shard_size);
     for i in 0..k {
-        if let Some(data) = &shard_map.get(&i) {
-            result.extend_from_slice(data);
-        } else {
-            anyhow::bail!("Missing data shard {}", i);
+        match shard_map.get(&i).or_else(|| restored.get(&i)) {
+            Some(data) => result.extend_from_slice(data),
+            None => return Err(ShardError::MissingDataShard(i)),
         }
     }
 
     Ok(result)
 }
 
+/// Regenerate the shards missing from `shards`
+///
+/// Decodes the object and re-encodes it, returning every shard whose index
+/// is absent from `shards` or present only with a bad CRC.
+pub fn repair(shards: &[Shard], params: impl Into<crate::FecParams>) -> Result<Vec<Shard>> {
+    let params = params.into();
+    let data = decode(shards, params)?;
+    let intact: std::collections::HashSet<u16> = shards
+        .iter()
+        .filter(|s| s.verify_crc())
+        .map(|s| s.idx)
+        .collect();
+
+    Ok(encode(&data, params)?
+        .into_iter()
+        .filter(|s| !intact.contains(&s.idx))
+        .collect())
+}
+
 /// Maintain shard health and trigger repair when needed
-pub fn maintain(key: Key, params: FecParams, hooks: &impl RepairHooks) -> Result<()> {
-    let k = params.k as usize;
-    let m = params.m as usize;
+#[tracing::instrument(name = "maintain", skip_all, fields(key = %hex::encode(&key), k, m))]
+pub fn maintain(
+    key: Key,
+    params: impl Into<crate::FecParams>,
+    hooks: &impl RepairHooks,
+) -> Result<()> {
+    let params = params.into();
+    let span = tracing::Span::current();
+    span.record("k", params.data_shares);
+    span.record("m", params.parity_shares);
+    let k = params.data_shares as usize;
+    let m = params.parity_shares as usize;
     let total = k + m;
 
     // Define repair threshold (when live < k + m - delta)
@@ -304,27 +343,13 @@ pub fn maintain(key: Key, params: FecParams, hooks: &impl RepairHooks) -> Result
         );
 
         if live_count < k {
-            anyhow::bail!(
-                "Cannot repair: only {} shards available, need at least {}",
-                live_count,
-                k
-            );
+            return Err(ShardError::InsufficientShards {
+                have: live_count,
+                need: k,
+            });
         }
 
-        // Decode original data
-        let data = decode(&available_shards, params)?;
-
-        // Re-encode to get all shards
-        let all_shards = encode(&data, params)?;
-
-        // Find missing shard indices
-        let available_indices: std::collections::HashSet<u16> =
-            available_shards.iter().map(|s| s.idx).collect();
-
-        let missing_shards: Vec<Shard> = all_shards
-            .into_iter()
-            .filter(|s| !available_indices.contains(&s.idx))
-            .collect();
+        let missing_shards = repair(&available_shards, params)?;
 
         info!("Reseeding {} missing shards", missing_shards.len());
 
@@ -345,7 +370,7 @@ pub struct ShardManifest {
     /// Object identifier
     pub object_id: Vec<u8>,
     /// FEC parameters used
-    pub params: FecParams,
+    pub params: crate::FecParams,
     /// Original data size (before padding)
     pub original_size: usize,
     /// List of shard storage keys
@@ -354,8 +379,13 @@ pub struct ShardManifest {
 
 impl ShardManifest {
     /// Create a new manifest
-    pub fn new(object_id: Vec<u8>, params: FecParams, original_size: usize) -> Self {
-        let total_shards = params.total_shards() as usize;
+    pub fn new(
+        object_id: Vec<u8>,
+        params: impl Into<crate::FecParams>,
+        original_size: usize,
+    ) -> Self {
+        let params = params.into();
+        let total_shards = params.total_shares() as usize;
         let mut shard_keys = Vec::with_capacity(total_shards);
 
         // Generate storage keys for all shards
@@ -378,6 +408,7 @@ impl ShardManifest {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::FecParams;
     use std::sync::Arc;
 
     // Type aliases to reduce complexity
@@ -430,7 +461,9 @@ mod tests {
 
     #[test]
     fn test_encode_decode_basic() {
-        let params = FecParams::new(3, 2, 1024).unwrap();
+        let params = FecParams::new(3, 2)
+            .and_then(|p| p.with_symbol_size(1024))
+            .unwrap();
         let data = vec![42u8; 3072]; // 3 * 1024
 
         // Encode
@@ -450,17 +483,16 @@ mod tests {
 
     #[test]
     fn test_decode_with_k_shards() {
-        let params = FecParams::new(3, 2, 1024).unwrap();
+        let params = FecParams::new(3, 2)
+            .and_then(|p| p.with_symbol_size(1024))
+            .unwrap();
         let data = vec![1, 2, 3, 4, 5, 6, 7, 8, 9];
 
         // Encode
         let shards = encode(&data, params).unwrap();
 
-        // Test scenarios that work with current implementation
-        // Currently only supports decoding when all data shards are present
-        let scenarios = vec![
-            vec![0, 1, 2], // First k data shards - should work
-        ];
+        // Any k shards decode, whichever data shards are missing
+        let scenarios = vec![vec![0, 1, 2], vec![0, 1, 3], vec![2, 3, 4], vec![4, 0, 3]];
 
         for indices in scenarios {
             let subset: Vec<Shard> = indices.iter().map(|&i| shards[i].clone()).collect();
@@ -468,22 +500,13 @@ mod tests {
             let decoded = decode(&subset, params).unwrap();
             assert_eq!(decoded[..data.len()], data[..]);
         }
-
-        // Test that we properly detect when reconstruction is needed but not supported
-        let parity_scenario = [0, 1, 3]; // Mix of data and parity
-        let subset: Vec<Shard> = parity_scenario.iter().map(|&i| shards[i].clone()).collect();
-
-        // This should fail with the expected error message
-        let result = decode(&subset, params);
-        assert!(result.is_err());
-        if let Err(e) = result {
-            assert!(e.to_string().contains("Complex reconstruction"));
-        }
     }
 
     #[test]
     fn test_crc_mismatch_detection() {
-        let params = FecParams::new(3, 2, 1024).unwrap();
+        let params = FecParams::new(3, 2)
+            .and_then(|p| p.with_symbol_size(1024))
+            .unwrap();
         let data = vec![42u8; 3072];
 
         let shards = encode(&data, params).unwrap();
@@ -516,7 +539,9 @@ mod tests {
 
     #[test]
     fn test_repair_when_below_threshold() {
-        let params = FecParams::new(3, 2, 1024).unwrap();
+        let params = FecParams::new(3, 2)
+            .and_then(|p| p.with_symbol_size(1024))
+            .unwrap();
         let data = vec![42u8; 3072];
         let key = b"test_key".to_vec();
 
@@ -527,8 +552,8 @@ mod tests {
         let shards = encode(&data, params).unwrap();
         hooks.store_shards(key.clone(), shards.clone());
 
-        // Remove some shards to trigger repair
-        hooks.remove_shard(&key, 3);
+        // Remove a data and a parity shard to trigger repair
+        hooks.remove_shard(&key, 1);
         hooks.remove_shard(&key, 4);
 
         // Run maintenance (should trigger repair)
@@ -538,12 +563,15 @@ mod tests {
         let storage = hooks.storage.read();
         let entry = storage.get(&key).unwrap();
         assert_eq!(entry.len(), 5); // All shards should be present
+        assert_eq!(entry[&1].data, shards[1].data);
     }
 
     #[test]
     fn test_rs_14_10_overhead() {
         // Demo RS(14,10) with 1.4x overhead
-        let params = FecParams::new(10, 4, 64 * 1024).unwrap();
+        let params = FecParams::new(10, 4)
+            .and_then(|p| p.with_symbol_size(64 * 1024))
+            .unwrap();
 
         // Verify overhead ratio
         let overhead = params.overhead_ratio();
@@ -593,7 +621,9 @@ mod tests {
     #[test]
     fn test_manifest_creation() {
         let object_id = b"test_object".to_vec();
-        let params = FecParams::new(3, 2, 1024).unwrap();
+        let params = FecParams::new(3, 2)
+            .and_then(|p| p.with_symbol_size(1024))
+            .unwrap();
         let manifest = ShardManifest::new(object_id.clone(), params, 2500);
 
         assert_eq!(manifest.object_id, object_id);
@@ -605,4 +635,40 @@ mod tests {
         let unique_keys: std::collections::HashSet<_> = manifest.shard_keys.iter().collect();
         assert_eq!(unique_keys.len(), 5);
     }
+
+    #[test]
+    fn test_manifest_reads_legacy_params() {
+        let params: FecParams =
+            serde_json::from_str(r#"{"k": 3, "m": 2, "shard_size": 1024}"#).unwrap();
+        assert_eq!(
+            params,
+            FecParams::new(3, 2)
+                .and_then(|p| p.with_symbol_size(1024))
+                .unwrap()
+        );
+        assert!(FecParams::new(3, 2).unwrap().with_symbol_size(0).is_err());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_legacy_params_shim() {
+        let legacy = super::FecParams::new(3, 2, 1024).unwrap();
+        let params = FecParams::from(legacy);
+        assert_eq!(
+            params,
+            FecParams::new(3, 2)
+                .and_then(|p| p.with_symbol_size(1024))
+                .unwrap()
+        );
+        assert_eq!(super::FecParams::from(params), legacy);
+        assert_eq!(legacy.total_shards(), 5);
+        assert_eq!((params.k(), params.m(), params.shard_size()), (3, 2, 1024));
+
+        let data = vec![7u8; 3000];
+        let shards = encode(&data, legacy).unwrap();
+        assert_eq!(decode(&shards[2..], legacy).unwrap()[..3000], data[..]);
+
+        assert!(super::FecParams::new(3, 2, 0).is_err());
+        assert!(super::FecParams::new(0, 2, 1024).is_err());
+    }
 }
@@ -0,0 +1,243 @@
+//! Choice of FEC parameters per file
+//!
+//! A [`FecPolicy`] picks the data and parity share counts and the chunk size
+//! for each file the [`StoragePipeline`](crate::StoragePipeline) stores.
+//! With `fec.auto_params` set the pipeline uses an [`AdaptivePolicy`], which
+//! sizes parity for the shard loss the pipeline has observed; otherwise a
+//! [`StaticPolicy`] keeps the configured values.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::FecParams;
+
+/// What a policy knows when choosing parameters for a file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FecInputs {
+    /// Size of the file in bytes
+    pub content_size: u64,
+    /// Fraction of shards found missing or corrupted on reads, once enough
+    /// reads have been made to tell
+    pub shard_loss_rate: Option<f64>,
+    /// Required probability that a chunk survives, e.g. `0.999999`
+    pub target_durability: f64,
+    /// Configured data shares
+    pub data_shards: u16,
+    /// Configured parity shares
+    pub parity_shards: u16,
+    /// Configured chunk size in bytes
+    pub chunk_size: usize,
+}
+
+/// Parameters chosen for a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FecChoice {
+    /// Share counts for every chunk of the file
+    pub params: FecParams,
+    /// Size of each chunk in bytes
+    pub chunk_size: usize,
+}
+
+/// Chooses FEC parameters for each file
+pub trait FecPolicy: Send + Sync {
+    /// Parameters for a file described by `inputs`
+    fn choose(&self, inputs: &FecInputs) -> crate::Result<FecChoice>;
+}
+
+/// Always the configured parameters
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StaticPolicy;
+
+impl FecPolicy for StaticPolicy {
+    fn choose(&self, inputs: &FecInputs) -> crate::Result<FecChoice> {
+        Ok(FecChoice {
+            params: FecParams::new(inputs.data_shards, inputs.parity_shards)?,
+            chunk_size: inputs.chunk_size,
+        })
+    }
+}
+
+/// Parity sized to meet the target durability at the observed loss rate
+///
+/// Data shares follow [`FecParams::from_content_size`]. Parity is the least
+/// that keeps the chance of losing more shards than that below
+/// `1 - target_durability`, treating shard losses as independent. Until
+/// enough reads have been made the loss rate is assumed, and it is never
+/// taken to be below a floor, so a quiet start does not strip parity away.
+/// Large files get larger chunks so they split into at most
+/// [`max_chunks`](Self::with_max_chunks) chunks.
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptivePolicy {
+    assumed_loss_rate: f64,
+    min_loss_rate: f64,
+    max_chunks: u64,
+    max_chunk_size: usize,
+}
+
+impl Default for AdaptivePolicy {
+    fn default() -> Self {
+        Self {
+            assumed_loss_rate: 0.01,
+            min_loss_rate: 0.001,
+            max_chunks: 1024,
+            max_chunk_size: 4 * 1024 * 1024,
+        }
+    }
+}
+
+impl AdaptivePolicy {
+    /// Create a policy with the default assumptions
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loss rate to plan for before any has been measured
+    pub fn with_assumed_loss_rate(mut self, rate: f64) -> Self {
+        self.assumed_loss_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Lowest loss rate to plan for, however reliable storage appears
+    pub fn with_min_loss_rate(mut self, rate: f64) -> Self {
+        self.min_loss_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Grow chunks so a file splits into at most `chunks` of them
+    pub fn with_max_chunks(mut self, chunks: u64) -> Self {
+        self.max_chunks = chunks.max(1);
+        self
+    }
+
+    fn chunk_size(&self, inputs: &FecInputs) -> usize {
+        let wanted = inputs.content_size.div_ceil(self.max_chunks) as usize;
+        if wanted <= inputs.chunk_size {
+            inputs.chunk_size
+        } else {
+            wanted
+                .next_power_of_two()
+                .min(self.max_chunk_size.max(inputs.chunk_size))
+        }
+    }
+}
+
+impl FecPolicy for AdaptivePolicy {
+    fn choose(&self, inputs: &FecInputs) -> crate::Result<FecChoice> {
+        let data_shards = FecParams::from_content_size(inputs.content_size as usize).data_shares;
+        let loss_rate = inputs
+            .shard_loss_rate
+            .unwrap_or(self.assumed_loss_rate)
+            .max(self.min_loss_rate);
+        let allowed = 1.0 - inputs.target_durability;
+
+        let max_parity = 255 - data_shards;
+        let parity_shards = (1..=max_parity)
+            .find(|&parity| loss_probability(data_shards + parity, parity, loss_rate) <= allowed)
+            .unwrap_or(max_parity);
+
+        Ok(FecChoice {
+            params: FecParams::new(data_shards, parity_shards)?,
+            chunk_size: self.chunk_size(inputs),
+        })
+    }
+}
+
+/// Chance that more than `tolerated` of `shards` are lost, each with
+/// probability `rate`
+fn loss_probability(shards: u16, tolerated: u16, rate: f64) -> f64 {
+    if rate <= 0.0 {
+        return 0.0;
+    }
+    if rate >= 1.0 {
+        return 1.0;
+    }
+    let n = shards as f64;
+    // P(X = i) built up term by term to stay within f64 range
+    let mut term = (1.0 - rate).powf(n);
+    let mut survive = 0.0;
+    for i in 0..=tolerated {
+        survive += term;
+        let i = i as f64;
+        term *= (n - i) / (i + 1.0) * rate / (1.0 - rate);
+    }
+    (1.0 - survive).max(0.0)
+}
+
+/// Counts shards read and lost to estimate the shard loss rate
+#[derive(Debug, Default)]
+pub struct ShardLossTracker {
+    fetched: AtomicU64,
+    lost: AtomicU64,
+}
+
+impl ShardLossTracker {
+    /// Reads needed before [`rate`](Self::rate) reports a measurement
+    pub const MIN_SAMPLES: u64 = 100;
+
+    /// Create a tracker with nothing recorded
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a read of `fetched` shards of which `lost` were missing or
+    /// corrupted
+    pub fn record(&self, fetched: usize, lost: usize) {
+        self.fetched.fetch_add(fetched as u64, Ordering::Relaxed);
+        self.lost.fetch_add(lost as u64, Ordering::Relaxed);
+    }
+
+    /// Fraction of shards lost, once [`MIN_SAMPLES`](Self::MIN_SAMPLES)
+    /// shards have been read
+    pub fn rate(&self) -> Option<f64> {
+        let fetched = self.fetched.load(Ordering::Relaxed);
+        (fetched >= Self::MIN_SAMPLES)
+            .then(|| self.lost.load(Ordering::Relaxed) as f64 / fetched as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs(content_size: u64, shard_loss_rate: Option<f64>) -> FecInputs {
+        FecInputs {
+            content_size,
+            shard_loss_rate,
+            target_durability: 0.999_999,
+            data_shards: 16,
+            parity_shards: 4,
+            chunk_size: 64 * 1024,
+        }
+    }
+
+    #[test]
+    fn test_adaptive_policy_follows_loss_rate() {
+        let policy = AdaptivePolicy::new();
+        let reliable = policy.choose(&inputs(1000, Some(0.0))).unwrap();
+        let assumed = policy.choose(&inputs(1000, None)).unwrap();
+        let lossy = policy.choose(&inputs(1000, Some(0.1))).unwrap();
+        assert_eq!(reliable.params.data_shares, 8);
+        assert!(reliable.params.parity_shares < assumed.params.parity_shares);
+        assert!(assumed.params.parity_shares < lossy.params.parity_shares);
+        assert!(
+            loss_probability(lossy.params.total_shares(), lossy.params.parity_shares, 0.1) <= 1e-6
+        );
+
+        // Large files split into at most max_chunks chunks
+        let large = policy.choose(&inputs(1 << 30, None)).unwrap();
+        assert_eq!(large.params.data_shares, 20);
+        assert_eq!(large.chunk_size, 1 << 20);
+        assert_eq!(
+            policy.choose(&inputs(1000, None)).unwrap().chunk_size,
+            64 * 1024
+        );
+    }
+
+    #[test]
+    fn test_shard_loss_tracker() {
+        let tracker = ShardLossTracker::new();
+        tracker.record(50, 5);
+        assert_eq!(tracker.rate(), None);
+        tracker.record(50, 0);
+        assert_eq!(tracker.rate(), Some(0.05));
+    }
+}
@@ -0,0 +1,789 @@
+// Copyright 2024 Saorsa Labs
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! C ABI over the shard layer
+//!
+//! Exposes [`fec::encode`], [`fec::decode`] and [`fec::repair`] together with
+//! shard and manifest (de)serialization, so nodes written in C, Go or Swift
+//! produce and read exactly the shards a Rust node does. The declarations
+//! are in `include/saorsa_fec.h`, generated from this module with
+//! `cbindgen --config cbindgen.toml --output include/saorsa_fec.h`.
+//!
+//! Shards and manifests cross the boundary in the versioned container of
+//! [`wire`](crate::wire), as its FEC shard and shard manifest kinds, so other
+//! implementations can read them from the documented layout alone.
+//!
+//! Every function returns a [`SaorsaFecStatus`]; on failure
+//! [`saorsa_fec_last_error`] describes what went wrong. Buffers handed out
+//! by the library are released with [`saorsa_fec_buffer_free`].
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CString};
+use std::fmt::Display;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use crate::fec::{self, Shard, ShardManifest};
+use crate::wire::{WireError, WireFormat, SHARD_KEY_LEN};
+use crate::FecParams;
+
+/// Outcome of a call
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaorsaFecStatus {
+    /// The call succeeded
+    Ok = 0,
+    /// A required pointer was null
+    NullPointer = 1,
+    /// The FEC parameters are out of range
+    InvalidParams = 2,
+    /// A shard or manifest could not be deserialized
+    Malformed = 3,
+    /// A shard's data does not match its CRC
+    CorruptShard = 4,
+    /// The output array has too few entries
+    BufferTooSmall = 5,
+    /// Encoding, decoding or repair failed
+    Failed = 6,
+    /// The library panicked; no outputs were written
+    Panic = 7,
+}
+
+/// FEC parameters, as in [`FecParams`]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SaorsaFecParams {
+    /// Number of data shards (k)
+    pub data_shares: u16,
+    /// Number of parity shards (m)
+    pub parity_shares: u16,
+    /// Size of each shard's data in bytes
+    pub symbol_size: u32,
+}
+
+/// Bytes borrowed from the caller
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SaorsaFecSlice {
+    pub data: *const u8,
+    pub len: usize,
+}
+
+/// Bytes owned by the library, released with [`saorsa_fec_buffer_free`]
+#[repr(C)]
+#[derive(Debug)]
+pub struct SaorsaFecBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl SaorsaFecBuffer {
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        if len == 0 {
+            return Self {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+        }
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        Self { data, len }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// A failed call: the status returned and the message kept for
+/// [`saorsa_fec_last_error`]
+struct FfiError {
+    status: SaorsaFecStatus,
+    message: String,
+}
+
+impl FfiError {
+    fn new(status: SaorsaFecStatus, message: impl Display) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Run `f`, recording its error and turning panics into a status
+fn guard(f: impl FnOnce() -> Result<(), FfiError>) -> SaorsaFecStatus {
+    let result = catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|_| Err(FfiError::new(SaorsaFecStatus::Panic, "saorsa-fec panicked")));
+    let (status, message) = match result {
+        Ok(()) => (SaorsaFecStatus::Ok, None),
+        Err(e) => (
+            e.status,
+            Some(CString::new(e.message.replace('\0', " ")).unwrap_or_default()),
+        ),
+    };
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+    status
+}
+
+fn params(params: &SaorsaFecParams) -> Result<FecParams, FfiError> {
+    FecParams::new(params.data_shares, params.parity_shares)
+        .and_then(|p| p.with_symbol_size(params.symbol_size))
+        .map_err(|e| FfiError::new(SaorsaFecStatus::InvalidParams, e))
+}
+
+/// # Safety
+/// `data` must be valid for reads of `len` bytes unless `len` is zero
+unsafe fn borrow<'a>(data: *const u8, len: usize, what: &str) -> Result<&'a [u8], FfiError> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+    if data.is_null() {
+        return Err(FfiError::new(
+            SaorsaFecStatus::NullPointer,
+            format!("{} is null", what),
+        ));
+    }
+    Ok(std::slice::from_raw_parts(data, len))
+}
+
+/// # Safety
+/// `out` must be null or valid for writes of a `T`
+unsafe fn write<T>(out: *mut T, value: T, what: &str) -> Result<(), FfiError> {
+    if out.is_null() {
+        return Err(FfiError::new(
+            SaorsaFecStatus::NullPointer,
+            format!("{} is null", what),
+        ));
+    }
+    out.write(value);
+    Ok(())
+}
+
+/// Parse the shards passed in, leaving out any whose container is damaged
+///
+/// # Safety
+/// `shards` must point to `count` slices, each valid as for [`borrow`]
+unsafe fn read_shards(shards: *const SaorsaFecSlice, count: usize) -> Result<Vec<Shard>, FfiError> {
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+    if shards.is_null() {
+        return Err(FfiError::new(
+            SaorsaFecStatus::NullPointer,
+            "shards is null",
+        ));
+    }
+    let mut parsed = Vec::with_capacity(count);
+    for slice in std::slice::from_raw_parts(shards, count) {
+        match deserialize_shard(borrow(slice.data, slice.len, "shard data")?) {
+            Ok(shard) => parsed.push(shard),
+            // Damaged shards count as missing, like those failing their CRC
+            Err(e) if e.status == SaorsaFecStatus::CorruptShard => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(parsed)
+}
+
+/// # Safety
+/// `out` must be valid for writes of `out_len` buffers
+unsafe fn write_shards(
+    shards: Vec<Shard>,
+    out: *mut SaorsaFecBuffer,
+    out_len: usize,
+) -> Result<(), FfiError> {
+    if shards.len() > out_len {
+        return Err(FfiError::new(
+            SaorsaFecStatus::BufferTooSmall,
+            format!("{} shards need writing, room for {}", shards.len(), out_len),
+        ));
+    }
+    let serialized = shards
+        .iter()
+        .map(serialize_shard)
+        .collect::<Result<Vec<_>, _>>()?;
+    if out.is_null() && !serialized.is_empty() {
+        return Err(FfiError::new(SaorsaFecStatus::NullPointer, "out is null"));
+    }
+    for (i, bytes) in serialized.into_iter().enumerate() {
+        out.add(i).write(SaorsaFecBuffer::from_vec(bytes));
+    }
+    Ok(())
+}
+
+fn serialize_shard(shard: &Shard) -> Result<Vec<u8>, FfiError> {
+    shard
+        .to_wire()
+        .map_err(|e| FfiError::new(SaorsaFecStatus::Failed, e))
+}
+
+fn deserialize_shard(bytes: &[u8]) -> Result<Shard, FfiError> {
+    Shard::from_wire(bytes).map_err(|e| {
+        let status = match e {
+            WireError::ChecksumMismatch => SaorsaFecStatus::CorruptShard,
+            _ => SaorsaFecStatus::Malformed,
+        };
+        FfiError::new(status, format!("Invalid shard: {}", e))
+    })
+}
+
+fn deserialize_manifest(bytes: &[u8]) -> Result<ShardManifest, FfiError> {
+    ShardManifest::from_wire(bytes).map_err(|e| {
+        FfiError::new(
+            SaorsaFecStatus::Malformed,
+            format!("Invalid manifest: {}", e),
+        )
+    })
+}
+
+/// Library version as a NUL-terminated string
+#[no_mangle]
+pub extern "C" fn saorsa_fec_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// Message describing the last failed call on this thread, or null
+///
+/// The string stays valid until the next call into the library on the same
+/// thread.
+#[no_mangle]
+pub extern "C" fn saorsa_fec_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Release a buffer returned by the library
+///
+/// # Safety
+/// `buffer` must be null or point to a buffer filled in by this library that
+/// has not been freed already. It is reset to empty.
+#[no_mangle]
+pub unsafe extern "C" fn saorsa_fec_buffer_free(buffer: *mut SaorsaFecBuffer) {
+    let Some(buffer) = buffer.as_mut() else {
+        return;
+    };
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+    buffer.data = ptr::null_mut();
+    buffer.len = 0;
+}
+
+/// Encode `data` into `data_shares + parity_shares` serialized shards
+///
+/// `data` may be at most `data_shares * symbol_size` bytes and is zero-padded
+/// to that length. Shard `i` is written to `out[i]`.
+///
+/// # Safety
+/// `data` must be valid for reads of `data_len` bytes and `out` for writes
+/// of `out_len` buffers.
+#[no_mangle]
+pub unsafe extern "C" fn saorsa_fec_encode(
+    params: SaorsaFecParams,
+    data: *const u8,
+    data_len: usize,
+    out: *mut SaorsaFecBuffer,
+    out_len: usize,
+) -> SaorsaFecStatus {
+    guard(|| {
+        let params = self::params(&params)?;
+        let data = borrow(data, data_len, "data")?;
+        let shards =
+            fec::encode(data, params).map_err(|e| FfiError::new(SaorsaFecStatus::Failed, e))?;
+        write_shards(shards, out, out_len)
+    })
+}
+
+/// Decode the original data from serialized shards
+///
+/// Shards failing their CRC are ignored. The output is padded to
+/// `data_shares * symbol_size` bytes; truncate it to the manifest's original
+/// size.
+///
+/// # Safety
+/// `shards` must point to `shard_count` slices of readable bytes and `out`
+/// must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn saorsa_fec_decode(
+    params: SaorsaFecParams,
+    shards: *const SaorsaFecSlice,
+    shard_count: usize,
+    out: *mut SaorsaFecBuffer,
+) -> SaorsaFecStatus {
+    guard(|| {
+        let params = self::params(&params)?;
+        let shards = read_shards(shards, shard_count)?;
+        let data =
+            fec::decode(&shards, params).map_err(|e| FfiError::new(SaorsaFecStatus::Failed, e))?;
+        write(out, SaorsaFecBuffer::from_vec(data), "out")
+    })
+}
+
+/// Regenerate the shards missing from, or corrupted in, `shards`
+///
+/// The regenerated shards are written to the start of `out` and their
+/// number to `out_written`. `data_shares + parity_shares` entries are always
+/// enough.
+///
+/// # Safety
+/// `shards` must point to `shard_count` slices of readable bytes, `out` must
+/// be valid for writes of `out_len` buffers and `out_written` for a write.
+#[no_mangle]
+pub unsafe extern "C" fn saorsa_fec_repair(
+    params: SaorsaFecParams,
+    shards: *const SaorsaFecSlice,
+    shard_count: usize,
+    out: *mut SaorsaFecBuffer,
+    out_len: usize,
+    out_written: *mut usize,
+) -> SaorsaFecStatus {
+    guard(|| {
+        let params = self::params(&params)?;
+        let shards = read_shards(shards, shard_count)?;
+        let missing =
+            fec::repair(&shards, params).map_err(|e| FfiError::new(SaorsaFecStatus::Failed, e))?;
+        let count = missing.len();
+        if out_written.is_null() {
+            return Err(FfiError::new(
+                SaorsaFecStatus::NullPointer,
+                "out_written is null",
+            ));
+        }
+        write_shards(missing, out, out_len)?;
+        write(out_written, count, "out_written")
+    })
+}
+
+/// Serialize a shard with index `idx` holding `data`, computing its CRC
+///
+/// # Safety
+/// `data` must be valid for reads of `data_len` bytes and `out` for writes.
+#[no_mangle]
+pub unsafe extern "C" fn saorsa_fec_shard_serialize(
+    idx: u16,
+    data: *const u8,
+    data_len: usize,
+    out: *mut SaorsaFecBuffer,
+) -> SaorsaFecStatus {
+    guard(|| {
+        let shard = Shard::new(idx, borrow(data, data_len, "data")?.to_vec());
+        write(
+            out,
+            SaorsaFecBuffer::from_vec(serialize_shard(&shard)?),
+            "out",
+        )
+    })
+}
+
+/// Read the index and data of a serialized shard, checking its CRC
+///
+/// # Safety
+/// `bytes` must be valid for reads of `len` bytes, and `out_idx` and
+/// `out_data` for writes.
+#[no_mangle]
+pub unsafe extern "C" fn saorsa_fec_shard_deserialize(
+    bytes: *const u8,
+    len: usize,
+    out_idx: *mut u16,
+    out_data: *mut SaorsaFecBuffer,
+) -> SaorsaFecStatus {
+    guard(|| {
+        let shard = deserialize_shard(borrow(bytes, len, "bytes")?)?;
+        if !shard.verify_crc() {
+            return Err(FfiError::new(
+                SaorsaFecStatus::CorruptShard,
+                format!("Shard {} failed CRC verification", shard.idx),
+            ));
+        }
+        if out_data.is_null() {
+            return Err(FfiError::new(
+                SaorsaFecStatus::NullPointer,
+                "out_data is null",
+            ));
+        }
+        write(out_idx, shard.idx, "out_idx")?;
+        write(out_data, SaorsaFecBuffer::from_vec(shard.data), "out_data")
+    })
+}
+
+/// Serialize a manifest for an object of `original_size` bytes
+///
+/// # Safety
+/// `object_id` must be valid for reads of `object_id_len` bytes and `out`
+/// for writes.
+#[no_mangle]
+pub unsafe extern "C" fn saorsa_fec_manifest_serialize(
+    object_id: *const u8,
+    object_id_len: usize,
+    params: SaorsaFecParams,
+    original_size: u64,
+    out: *mut SaorsaFecBuffer,
+) -> SaorsaFecStatus {
+    guard(|| {
+        let params = self::params(&params)?;
+        let object_id = borrow(object_id, object_id_len, "object_id")?.to_vec();
+        let manifest = ShardManifest::new(object_id, params, original_size as usize);
+        let bytes = manifest
+            .to_wire()
+            .map_err(|e| FfiError::new(SaorsaFecStatus::Failed, e))?;
+        write(out, SaorsaFecBuffer::from_vec(bytes), "out")
+    })
+}
+
+/// Read the parameters, original size and object ID of a serialized manifest
+///
+/// # Safety
+/// `bytes` must be valid for reads of `len` bytes, and the outputs for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn saorsa_fec_manifest_deserialize(
+    bytes: *const u8,
+    len: usize,
+    out_params: *mut SaorsaFecParams,
+    out_original_size: *mut u64,
+    out_object_id: *mut SaorsaFecBuffer,
+) -> SaorsaFecStatus {
+    guard(|| {
+        let manifest = deserialize_manifest(borrow(bytes, len, "bytes")?)?;
+        if out_params.is_null() || out_original_size.is_null() || out_object_id.is_null() {
+            return Err(FfiError::new(
+                SaorsaFecStatus::NullPointer,
+                "an output pointer is null",
+            ));
+        }
+        let params = SaorsaFecParams {
+            data_shares: manifest.params.data_shares,
+            parity_shares: manifest.params.parity_shares,
+            symbol_size: manifest.params.symbol_size,
+        };
+        write(out_params, params, "out_params")?;
+        write(
+            out_original_size,
+            manifest.original_size as u64,
+            "out_original_size",
+        )?;
+        write(
+            out_object_id,
+            SaorsaFecBuffer::from_vec(manifest.object_id),
+            "out_object_id",
+        )
+    })
+}
+
+/// Copy the 32-byte storage key of shard `idx` from a serialized manifest
+///
+/// # Safety
+/// `bytes` must be valid for reads of `len` bytes and `out_key` for writes
+/// of 32 bytes.
+#[no_mangle]
+pub unsafe extern "C" fn saorsa_fec_manifest_shard_key(
+    bytes: *const u8,
+    len: usize,
+    idx: u16,
+    out_key: *mut u8,
+) -> SaorsaFecStatus {
+    guard(|| {
+        let manifest = deserialize_manifest(borrow(bytes, len, "bytes")?)?;
+        let key = manifest.shard_keys.get(idx as usize).ok_or_else(|| {
+            FfiError::new(
+                SaorsaFecStatus::InvalidParams,
+                format!(
+                    "Shard index {} out of range for {} shards",
+                    idx,
+                    manifest.shard_keys.len()
+                ),
+            )
+        })?;
+        if out_key.is_null() {
+            return Err(FfiError::new(
+                SaorsaFecStatus::NullPointer,
+                "out_key is null",
+            ));
+        }
+        if key.len() != SHARD_KEY_LEN {
+            return Err(FfiError::new(
+                SaorsaFecStatus::Malformed,
+                format!("Shard key is {} bytes, expected 32", key.len()),
+            ));
+        }
+        ptr::copy_nonoverlapping(key.as_ptr(), out_key, SHARD_KEY_LEN);
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAMS: SaorsaFecParams = SaorsaFecParams {
+        data_shares: 3,
+        parity_shares: 2,
+        symbol_size: 16,
+    };
+
+    fn empty() -> SaorsaFecBuffer {
+        SaorsaFecBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    fn slice(buffer: &SaorsaFecBuffer) -> SaorsaFecSlice {
+        SaorsaFecSlice {
+            data: buffer.data,
+            len: buffer.len,
+        }
+    }
+
+    fn bytes(buffer: &SaorsaFecBuffer) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(buffer.data, buffer.len) }
+    }
+
+    #[test]
+    fn test_ffi_encode_decode_repair() {
+        let data: Vec<u8> = (0..40).collect();
+        let mut shards: Vec<SaorsaFecBuffer> = (0..5).map(|_| empty()).collect();
+        unsafe {
+            assert_eq!(
+                saorsa_fec_encode(PARAMS, data.as_ptr(), data.len(), shards.as_mut_ptr(), 4),
+                SaorsaFecStatus::BufferTooSmall
+            );
+            assert!(!saorsa_fec_last_error().is_null());
+            assert_eq!(
+                saorsa_fec_encode(PARAMS, data.as_ptr(), data.len(), shards.as_mut_ptr(), 5),
+                SaorsaFecStatus::Ok
+            );
+            assert!(saorsa_fec_last_error().is_null());
+
+            // Shards are the Rust shard layer's, byte for byte
+            let expected = fec::encode(&data, super::params(&PARAMS).ok().unwrap()).unwrap();
+            assert_eq!(bytes(&shards[4]), expected[4].to_wire().unwrap());
+
+            let mut idx = 0u16;
+            let mut payload = empty();
+            assert_eq!(
+                saorsa_fec_shard_deserialize(shards[1].data, shards[1].len, &mut idx, &mut payload),
+                SaorsaFecStatus::Ok
+            );
+            assert_eq!((idx, bytes(&payload)), (1, &data[16..32]));
+            saorsa_fec_buffer_free(&mut payload);
+
+            // Drop both parity shards, then regenerate them
+            let present: Vec<SaorsaFecSlice> = shards[..3].iter().map(slice).collect();
+            let mut decoded = empty();
+            assert_eq!(
+                saorsa_fec_decode(PARAMS, present.as_ptr(), present.len(), &mut decoded),
+                SaorsaFecStatus::Ok
+            );
+            assert_eq!(&bytes(&decoded)[..data.len()], &data[..]);
+            saorsa_fec_buffer_free(&mut decoded);
+
+            let mut repaired: Vec<SaorsaFecBuffer> = (0..5).map(|_| empty()).collect();
+            let mut written = 0usize;
+            assert_eq!(
+                saorsa_fec_repair(
+                    PARAMS,
+                    present.as_ptr(),
+                    present.len(),
+                    repaired.as_mut_ptr(),
+                    repaired.len(),
+                    &mut written,
+                ),
+                SaorsaFecStatus::Ok
+            );
+            assert_eq!(written, 2);
+            assert_eq!(bytes(&repaired[0]), bytes(&shards[3]));
+            assert_eq!(bytes(&repaired[1]), bytes(&shards[4]));
+
+            for buffer in repaired.iter_mut().chain(shards.iter_mut()) {
+                saorsa_fec_buffer_free(buffer);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ffi_decode_and_repair_without_data_shards() {
+        let data: Vec<u8> = (0..48).collect();
+        let mut shards: Vec<SaorsaFecBuffer> = (0..5).map(|_| empty()).collect();
+        unsafe {
+            assert_eq!(
+                saorsa_fec_encode(PARAMS, data.as_ptr(), data.len(), shards.as_mut_ptr(), 5),
+                SaorsaFecStatus::Ok
+            );
+
+            // Lose data shards 0 and 2, keeping one data and both parity shards
+            let present: Vec<SaorsaFecSlice> =
+                [1, 3, 4].iter().map(|&i| slice(&shards[i])).collect();
+            let mut decoded = empty();
+            assert_eq!(
+                saorsa_fec_decode(PARAMS, present.as_ptr(), present.len(), &mut decoded),
+                SaorsaFecStatus::Ok
+            );
+            assert_eq!(bytes(&decoded), &data[..]);
+            saorsa_fec_buffer_free(&mut decoded);
+
+            let mut repaired: Vec<SaorsaFecBuffer> = (0..5).map(|_| empty()).collect();
+            let mut written = 0usize;
+            assert_eq!(
+                saorsa_fec_repair(
+                    PARAMS,
+                    present.as_ptr(),
+                    present.len(),
+                    repaired.as_mut_ptr(),
+                    repaired.len(),
+                    &mut written,
+                ),
+                SaorsaFecStatus::Ok
+            );
+            assert_eq!(written, 2);
+            assert_eq!(bytes(&repaired[0]), bytes(&shards[0]));
+            assert_eq!(bytes(&repaired[1]), bytes(&shards[2]));
+
+            for buffer in repaired.iter_mut().chain(shards.iter_mut()) {
+                saorsa_fec_buffer_free(buffer);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ffi_shard_and_manifest_serialization() {
+        unsafe {
+            let mut shard = empty();
+            assert_eq!(
+                saorsa_fec_shard_serialize(7, b"abc".as_ptr(), 3, &mut shard),
+                SaorsaFecStatus::Ok
+            );
+            // Flip a data byte, just before the container checksum
+            let mut corrupted = bytes(&shard).to_vec();
+            let at = corrupted.len() - 5;
+            corrupted[at] ^= 1;
+            let mut idx = 0u16;
+            let mut payload = empty();
+            assert_eq!(
+                saorsa_fec_shard_deserialize(
+                    corrupted.as_ptr(),
+                    corrupted.len(),
+                    &mut idx,
+                    &mut payload
+                ),
+                SaorsaFecStatus::CorruptShard
+            );
+            assert_eq!(
+                saorsa_fec_shard_deserialize(b"xy".as_ptr(), 2, &mut idx, &mut payload),
+                SaorsaFecStatus::Malformed
+            );
+            saorsa_fec_buffer_free(&mut shard);
+
+            let mut manifest = empty();
+            assert_eq!(
+                saorsa_fec_manifest_serialize(
+                    b"object".as_ptr(),
+                    6,
+                    SaorsaFecParams {
+                        data_shares: 0,
+                        ..PARAMS
+                    },
+                    40,
+                    &mut manifest
+                ),
+                SaorsaFecStatus::InvalidParams
+            );
+            assert_eq!(
+                saorsa_fec_manifest_serialize(b"object".as_ptr(), 6, PARAMS, 40, &mut manifest),
+                SaorsaFecStatus::Ok
+            );
+
+            let mut params = SaorsaFecParams {
+                data_shares: 0,
+                parity_shares: 0,
+                symbol_size: 0,
+            };
+            let mut original_size = 0u64;
+            let mut object_id = empty();
+            assert_eq!(
+                saorsa_fec_manifest_deserialize(
+                    manifest.data,
+                    manifest.len,
+                    &mut params,
+                    &mut original_size,
+                    &mut object_id
+                ),
+                SaorsaFecStatus::Ok
+            );
+            assert_eq!((params, original_size), (PARAMS, 40));
+            assert_eq!(bytes(&object_id), b"object");
+
+            let mut key = [0u8; 32];
+            assert_eq!(
+                saorsa_fec_manifest_shard_key(manifest.data, manifest.len, 2, key.as_mut_ptr()),
+                SaorsaFecStatus::Ok
+            );
+            assert_eq!(
+                key.to_vec(),
+                Shard::new(2, Vec::new()).storage_key(b"object")
+            );
+            assert_eq!(
+                saorsa_fec_manifest_shard_key(manifest.data, manifest.len, 5, key.as_mut_ptr()),
+                SaorsaFecStatus::InvalidParams
+            );
+
+            saorsa_fec_buffer_free(&mut object_id);
+            saorsa_fec_buffer_free(&mut manifest);
+            assert!(manifest.data.is_null());
+        }
+    }
+
+    #[test]
+    fn test_manifest_shard_key_rejects_oversized_keys() {
+        let params = FecParams::new(3, 2)
+            .and_then(|p| p.with_symbol_size(16))
+            .unwrap();
+        let bytes = ShardManifest::new(b"object".to_vec(), params, 40)
+            .to_wire()
+            .unwrap();
+        let mut container = crate::wire::Container::from_bytes(&bytes).unwrap();
+        container.payload = [&64u16.to_le_bytes()[..], &[0xAA; 64]].concat().repeat(5);
+        let malicious = container.to_bytes();
+
+        // Guard bytes after the 32-byte key must survive
+        let mut key = [0u8; 64];
+        unsafe {
+            assert_eq!(
+                saorsa_fec_manifest_shard_key(
+                    malicious.as_ptr(),
+                    malicious.len(),
+                    0,
+                    key.as_mut_ptr()
+                ),
+                SaorsaFecStatus::Malformed
+            );
+        }
+        assert_eq!(key, [0u8; 64]);
+    }
+
+    #[test]
+    fn test_header_declares_every_export() {
+        let header = include_str!("../include/saorsa_fec.h");
+        let exports: Vec<&str> = include_str!("ffi.rs")
+            .lines()
+            .filter_map(|line| line.split("extern \"C\" fn ").nth(1))
+            .filter_map(|rest| rest.split('(').next())
+            .filter(|name| name.starts_with("saorsa_fec_"))
+            .collect();
+        assert_eq!(exports.len(), 11);
+        for name in exports {
+            assert!(
+                header.contains(&format!("{}(", name)),
+                "{} missing from include/saorsa_fec.h",
+                name
+            );
+        }
+    }
+}
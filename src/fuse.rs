@@ -0,0 +1,391 @@
+//! Read-only FUSE mount of a pipeline's stored files
+//!
+//! [`mount`] exposes every file in a [`StoragePipeline`]'s catalog as a
+//! regular file in one flat directory, named after its local filename or,
+//! failing that, its hex file ID. Reads go through
+//! [`StoragePipeline::retrieve_range`], decoding chunks and reconstructing
+//! lost shards as they are read, and the first open of a file repairs its
+//! degraded chunks in the background.
+
+use fuser::{
+    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry,
+    ReplyOpen, Request, FUSE_ROOT_ID,
+};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::runtime::Handle;
+
+use crate::metadata::FileMetadata;
+use crate::pipeline::StoragePipeline;
+use crate::storage::StorageBackend;
+
+pub use fuser::BackgroundSession;
+
+/// Errors from mounting
+#[derive(Debug, Error)]
+pub enum FuseError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Mount point {0} is not a directory")]
+    NotADirectory(PathBuf),
+
+    #[error("No Tokio runtime to serve the mount from")]
+    NoRuntime,
+}
+
+pub type Result<T> = std::result::Result<T, FuseError>;
+
+/// How long the kernel may cache names and attributes; the catalog changes
+/// as files are stored
+const TTL: Duration = Duration::from_secs(1);
+
+/// Read-only filesystem over a pipeline's catalog
+///
+/// [`mount`] serves it with the usual options; it can also be passed to
+/// [`fuser::spawn_mount2`] directly.
+pub struct CatalogFs<B: StorageBackend + 'static> {
+    pipeline: Arc<StoragePipeline<B>>,
+    handle: Handle,
+    /// Inodes handed out to the kernel, stable for the life of the mount
+    inodes: HashMap<[u8; 32], u64>,
+    files: Vec<[u8; 32]>,
+    /// Files already repaired since the mount
+    repaired: HashSet<[u8; 32]>,
+    uid: u32,
+    gid: u32,
+}
+
+impl<B: StorageBackend + 'static> CatalogFs<B> {
+    /// Create a filesystem driving the pipeline on the given runtime
+    ///
+    /// Reads block on `handle`, so it should belong to a multi-threaded
+    /// runtime.
+    pub fn new(pipeline: Arc<StoragePipeline<B>>, handle: Handle) -> Self {
+        // SAFETY: getuid and getgid cannot fail
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        Self {
+            pipeline,
+            handle,
+            inodes: HashMap::new(),
+            files: Vec::new(),
+            repaired: HashSet::new(),
+            uid,
+            gid,
+        }
+    }
+
+    /// Catalog files by name, in file ID order
+    ///
+    /// A file is named after its local filename unless that is missing, not
+    /// a valid name or taken by an earlier file, in which case its hex file
+    /// ID is used.
+    fn entries(&self) -> Vec<(String, FileMetadata)> {
+        let mut files = self.pipeline.list_files();
+        files.sort_by_key(|meta| meta.file_id);
+        let mut names = HashSet::new();
+        files
+            .into_iter()
+            .map(|meta| {
+                let filename = meta
+                    .local_metadata
+                    .as_ref()
+                    .and_then(|local| local.filename.clone())
+                    .filter(|name| valid_name(name) && !names.contains(name));
+                let name = filename.unwrap_or_else(|| hex::encode(meta.file_id));
+                names.insert(name.clone());
+                (name, meta)
+            })
+            .collect()
+    }
+
+    fn inode(&mut self, file_id: [u8; 32]) -> u64 {
+        *self.inodes.entry(file_id).or_insert_with(|| {
+            self.files.push(file_id);
+            self.files.len() as u64 + FUSE_ROOT_ID
+        })
+    }
+
+    /// Latest metadata of the file behind `ino`
+    fn file(&self, ino: u64) -> Option<FileMetadata> {
+        let index = usize::try_from(ino.checked_sub(FUSE_ROOT_ID + 1)?).ok()?;
+        let file_id = self.files.get(index)?;
+        self.pipeline.file_metadata(file_id)
+    }
+
+    fn root_attr(&self) -> FileAttr {
+        self.attr(FUSE_ROOT_ID, 0, FileType::Directory, 0o555, UNIX_EPOCH)
+    }
+
+    fn file_attr(&self, ino: u64, meta: &FileMetadata) -> FileAttr {
+        let mtime = meta
+            .local_metadata
+            .as_ref()
+            .and_then(|local| local.modified_at.or(local.created_at))
+            .map_or(UNIX_EPOCH, |secs| UNIX_EPOCH + Duration::from_secs(secs));
+        self.attr(ino, meta.file_size, FileType::RegularFile, 0o444, mtime)
+    }
+
+    fn attr(&self, ino: u64, size: u64, kind: FileType, perm: u16, mtime: SystemTime) -> FileAttr {
+        FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: mtime,
+            mtime,
+            ctime: mtime,
+            crtime: mtime,
+            kind,
+            perm,
+            nlink: if kind == FileType::Directory { 2 } else { 1 },
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: 4096,
+            flags: 0,
+        }
+    }
+
+    fn repair_in_background(&self, meta: FileMetadata) {
+        let pipeline = self.pipeline.clone();
+        self.handle.spawn(async move {
+            match pipeline.repair_file(&meta).await {
+                Ok(report) if report.chunks_repaired > 0 => tracing::info!(
+                    "Repaired {} chunk(s) of {} on open",
+                    report.chunks_repaired,
+                    hex::encode(meta.file_id)
+                ),
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Repair of {} failed: {e}", hex::encode(meta.file_id)),
+            }
+        });
+    }
+}
+
+impl<B: StorageBackend + 'static> Filesystem for CatalogFs<B> {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        if parent != FUSE_ROOT_ID {
+            return reply.error(libc::ENOENT);
+        }
+        match self
+            .entries()
+            .into_iter()
+            .find(|(entry, _)| name == entry.as_str())
+        {
+            Some((_, meta)) => {
+                let ino = self.inode(meta.file_id);
+                reply.entry(&TTL, &self.file_attr(ino, &meta), 0);
+            }
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        if ino == FUSE_ROOT_ID {
+            return reply.attr(&TTL, &self.root_attr());
+        }
+        match self.file(ino) {
+            Some(meta) => reply.attr(&TTL, &self.file_attr(ino, &meta)),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        if flags & libc::O_ACCMODE != libc::O_RDONLY {
+            return reply.error(libc::EROFS);
+        }
+        let Some(meta) = self.file(ino) else {
+            return reply.error(libc::ENOENT);
+        };
+        if self.repaired.insert(meta.file_id) {
+            self.repair_in_background(meta);
+        }
+        reply.opened(0, 0);
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let Some(meta) = self.file(ino) else {
+            return reply.error(libc::ENOENT);
+        };
+        let Ok(offset) = u64::try_from(offset) else {
+            return reply.error(libc::EINVAL);
+        };
+        let read = self
+            .handle
+            .block_on(self.pipeline.retrieve_range(&meta, offset, size as usize));
+        match read {
+            Ok(data) => reply.data(&data),
+            Err(e) => {
+                tracing::warn!("Read of {} failed: {e}", hex::encode(meta.file_id));
+                reply.error(libc::EIO);
+            }
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        if ino != FUSE_ROOT_ID {
+            return reply.error(libc::ENOTDIR);
+        }
+        let mut listing = vec![
+            (FUSE_ROOT_ID, FileType::Directory, ".".to_string()),
+            (FUSE_ROOT_ID, FileType::Directory, "..".to_string()),
+        ];
+        for (name, meta) in self.entries() {
+            listing.push((self.inode(meta.file_id), FileType::RegularFile, name));
+        }
+
+        // Each entry records the offset of the one after it
+        let skip = usize::try_from(offset).unwrap_or_default();
+        for (index, (ino, kind, name)) in listing.into_iter().enumerate().skip(skip) {
+            if reply.add(ino, index as i64 + 1, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
+
+/// Mount `pipeline`'s catalog read-only at `mountpoint`
+///
+/// Must be called from within a multi-threaded Tokio runtime, which serves
+/// the reads. The filesystem stays mounted until the returned session is
+/// dropped.
+pub fn mount<B: StorageBackend + 'static>(
+    pipeline: Arc<StoragePipeline<B>>,
+    mountpoint: impl AsRef<Path>,
+) -> Result<BackgroundSession> {
+    let mountpoint = mountpoint.as_ref();
+    if !mountpoint.is_dir() {
+        return Err(FuseError::NotADirectory(mountpoint.to_path_buf()));
+    }
+    let handle = Handle::try_current().map_err(|_| FuseError::NoRuntime)?;
+    let options = [
+        MountOption::RO,
+        MountOption::NoSuid,
+        MountOption::NoDev,
+        MountOption::DefaultPermissions,
+        MountOption::FSName("saorsa-fec".to_string()),
+        MountOption::Subtype("saorsa-fec".to_string()),
+    ];
+    let fs = CatalogFs::new(pipeline, handle);
+    Ok(fuser::spawn_mount2(fs, mountpoint, &options)?)
+}
+
+/// Whether `name` can be a directory entry
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 255
+        && name != "."
+        && name != ".."
+        && !name.contains(['/', '\0'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::pipeline::Meta;
+    use crate::storage::MemoryStorage;
+    use std::io::{Read, Seek, SeekFrom};
+
+    async fn pipeline(files: &[([u8; 32], &[u8], Option<&str>)]) -> StoragePipeline<MemoryStorage> {
+        let config = Config::default()
+            .with_fec_params(4, 2)
+            .with_compression(false, 1);
+        let mut pipeline = StoragePipeline::builder(config, MemoryStorage::new())
+            .build()
+            .unwrap();
+        for (file_id, data, filename) in files {
+            let meta = filename.map(|name| Meta::new().with_filename(name));
+            pipeline.process_file(*file_id, data, meta).await.unwrap();
+        }
+        pipeline
+    }
+
+    #[tokio::test]
+    async fn test_catalog_names() {
+        let pipeline = pipeline(&[
+            ([1u8; 32], b"first", Some("notes.txt")),
+            ([2u8; 32], b"second", Some("notes.txt")),
+            ([3u8; 32], b"third", Some("../escape")),
+            ([4u8; 32], b"fourth", None),
+        ])
+        .await;
+        let fs = CatalogFs::new(Arc::new(pipeline), Handle::current());
+
+        let names: Vec<String> = fs.entries().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(
+            names,
+            [
+                "notes.txt".to_string(),
+                hex::encode([2u8; 32]),
+                hex::encode([3u8; 32]),
+                hex::encode([4u8; 32]),
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_mount_serves_catalog_read_only() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let pipeline = pipeline(&[([1u8; 32], &data, Some("photos.tar"))]).await;
+        let dir = tempfile::tempdir().unwrap();
+        let session = match mount(Arc::new(pipeline), dir.path()) {
+            Ok(session) => session,
+            Err(e) => {
+                // Needs /dev/fuse and permission to mount
+                eprintln!("skipping FUSE mount test: {e}");
+                return;
+            }
+        };
+
+        let root = dir.path().to_path_buf();
+        let read = tokio::task::spawn_blocking(move || {
+            let names: Vec<_> = std::fs::read_dir(&root)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name())
+                .collect();
+            assert_eq!(names, ["photos.tar"]);
+
+            let path = root.join("photos.tar");
+            let metadata = std::fs::metadata(&path).unwrap();
+            assert_eq!(metadata.len(), 200_000);
+            assert!(metadata.permissions().readonly());
+            let write = std::fs::OpenOptions::new().write(true).open(&path);
+            assert!(write.is_err());
+
+            let mut file = std::fs::File::open(&path).unwrap();
+            let mut range = vec![0u8; 1000];
+            file.seek(SeekFrom::Start(150_000)).unwrap();
+            file.read_exact(&mut range).unwrap();
+            (range, std::fs::read(&path).unwrap())
+        })
+        .await;
+        drop(session);
+
+        let (range, whole) = read.unwrap();
+        assert_eq!(range, &data[150_000..151_000]);
+        assert_eq!(whole, data);
+    }
+}
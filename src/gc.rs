@@ -3,27 +3,56 @@
 //! This module provides configurable retention policies and safe garbage
 //! collection of unreferenced chunks.
 
-use anyhow::Result;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use thiserror::Error;
+use tokio::sync::{broadcast, watch, Notify};
+use tokio::task::JoinHandle;
 
-use crate::chunk_registry::ChunkRegistry;
+use crate::chunk_registry::{ChunkMetadata, ChunkQuery, ChunkRegistry};
+use crate::config::GcConfig;
 use crate::storage::{Cid, StorageBackend};
 use crate::version::VersionNode;
 
+/// Errors from saving or loading the incremental sweep cursor
+///
+/// Failures to delete individual chunks are counted in the
+/// [`CollectionReport`] rather than failing the run.
+#[derive(Debug, Error)]
+pub enum GcError {
+    #[error("Corrupt GC cursor file {}", .0.display())]
+    CorruptCursor(PathBuf),
+
+    #[error("{}: {error}", path.display())]
+    Io { path: PathBuf, error: io::Error },
+}
+
+pub type Result<T> = std::result::Result<T, GcError>;
+
 /// Retention policy for garbage collection
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub enum RetentionPolicy {
     /// Keep all versions and chunks
+    #[default]
     KeepAll,
     /// Keep only the last N versions
     KeepLastN(usize),
     /// Keep specific tagged versions
     KeepTagged(HashSet<[u8; 32]>),
-    /// Keep versions newer than a certain age (seconds)
+    /// Keep chunks younger than a certain age (seconds); 0 keeps none
     KeepRecent(u64),
+    /// Keep chunks read within a certain time (seconds), evicting the least
+    /// recently read unreferenced chunks first
+    LeastRecentlyRead(u64),
+    /// Delete the oldest unreferenced chunks until the registry holds at
+    /// most this many bytes
+    TargetSize(u64),
     /// Custom policy (not serializable)
     #[serde(skip)]
     Custom(Arc<dyn Fn(&VersionNode) -> bool + Send + Sync>),
@@ -36,144 +65,412 @@ impl std::fmt::Debug for RetentionPolicy {
             Self::KeepLastN(n) => write!(f, "KeepLastN({})", n),
             Self::KeepTagged(tags) => write!(f, "KeepTagged({:?})", tags),
             Self::KeepRecent(secs) => write!(f, "KeepRecent({})", secs),
+            Self::LeastRecentlyRead(secs) => write!(f, "LeastRecentlyRead({})", secs),
+            Self::TargetSize(bytes) => write!(f, "TargetSize({})", bytes),
             Self::Custom(_) => write!(f, "Custom(<function>)"),
         }
     }
 }
 
-impl Default for RetentionPolicy {
-    fn default() -> Self {
-        Self::KeepAll
-    }
-}
-
 /// Garbage collector for managing chunk lifecycle
 pub struct GarbageCollector {
     /// Retention policy to apply
     pub policy: RetentionPolicy,
     /// Reference to chunk registry
-    chunk_registry: Arc<RwLock<ChunkRegistry>>,
+    chunk_registry: Arc<ChunkRegistry>,
     /// Storage backend for chunk deletion
     storage: Arc<dyn StorageBackend>,
+    /// Report collections without deleting anything
+    dry_run: bool,
+    /// Time budget and batch size when runs are incremental
+    incremental: Option<(Duration, usize)>,
+    /// Last chunk ID an incremental sweep examined
+    cursor: Mutex<Option<[u8; 32]>>,
+    /// File the cursor is saved to between runs
+    cursor_path: Option<PathBuf>,
+    /// Counters of the current or last run
+    progress: RwLock<GcProgress>,
+    /// Progress events for subscribers
+    events: broadcast::Sender<GcEvent>,
+    /// Set to stop the run in progress
+    cancelled: AtomicBool,
 }
 
 impl GarbageCollector {
     /// Create a new garbage collector
     pub fn new(
         policy: RetentionPolicy,
-        chunk_registry: Arc<RwLock<ChunkRegistry>>,
+        chunk_registry: Arc<ChunkRegistry>,
         storage: Arc<dyn StorageBackend>,
     ) -> Self {
         Self {
             policy,
             chunk_registry,
             storage,
+            dry_run: false,
+            incremental: None,
+            cursor: Mutex::new(None),
+            cursor_path: None,
+            progress: RwLock::new(GcProgress::default()),
+            events: broadcast::channel(256).0,
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    /// Subscribe to progress events of later runs
+    pub fn subscribe(&self) -> broadcast::Receiver<GcEvent> {
+        self.events.subscribe()
+    }
+
+    /// Counters of the run in progress, or of the last run
+    pub fn progress(&self) -> GcProgress {
+        self.progress.read().clone()
+    }
+
+    /// Stop the run in progress after the chunk it is deleting
+    ///
+    /// The run returns the report of the work done so far with
+    /// `cancelled` set. Has no effect on runs started afterwards.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    fn begin_run(&self) -> Instant {
+        self.cancelled.store(false, Ordering::SeqCst);
+        *self.progress.write() = GcProgress::default();
+        // Send errors only mean nobody is subscribed
+        let _ = self.events.send(GcEvent::Started);
+        Instant::now()
+    }
+
+    fn finish_run(&self, mut report: CollectionReport, started: Instant) -> CollectionReport {
+        report.duration_ms = started.elapsed().as_millis() as u64;
+        let _ = self.events.send(GcEvent::Finished(report.clone()));
+        report
+    }
+
+    /// Apply the run settings of `config`: dry-run and incremental mode
+    ///
+    /// The retention policy is passed to [`Self::new`], see
+    /// [`GcConfig::retention_policy`].
+    pub fn with_config(mut self, config: &GcConfig) -> Self {
+        self = self.with_dry_run(config.dry_run);
+        if let Some(time_budget) = config.time_budget {
+            self = self.with_incremental(time_budget, config.batch_size);
+        }
+        self
+    }
+
+    /// Make [`Self::run`] sweep incrementally, see [`Self::run_incremental`]
+    pub fn with_incremental(mut self, time_budget: Duration, batch_size: usize) -> Self {
+        self.incremental = Some((time_budget, batch_size.max(1)));
+        self
+    }
+
+    /// Save the incremental sweep cursor to `path`
+    ///
+    /// A cursor already saved there is loaded, so a restarted node resumes
+    /// the sweep where it stopped.
+    pub fn with_cursor_file(mut self, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let cursor = match std::fs::read(&path) {
+            Ok(bytes) if bytes.is_empty() => None,
+            Ok(bytes) => Some(
+                <[u8; 32]>::try_from(bytes.as_slice())
+                    .map_err(|_| GcError::CorruptCursor(path.clone()))?,
+            ),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(error) => return Err(GcError::Io { path, error }),
+        };
+        self.cursor = Mutex::new(cursor);
+        self.cursor_path = Some(path);
+        Ok(self)
+    }
+
+    /// Chunk ID the next incremental sweep resumes after, `None` to start
+    /// from the beginning
+    pub fn cursor(&self) -> Option<[u8; 32]> {
+        *self.cursor.lock()
+    }
+
+    fn save_cursor(&self, cursor: Option<[u8; 32]>) -> Result<()> {
+        *self.cursor.lock() = cursor;
+        if let Some(path) = &self.cursor_path {
+            let tmp = path.with_extension("tmp");
+            std::fs::write(&tmp, cursor.as_ref().map_or(&[][..], |c| &c[..]))
+                .and_then(|()| std::fs::rename(&tmp, path))
+                .map_err(|error| GcError::Io {
+                    path: path.clone(),
+                    error,
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Only report what [`Self::run`] would delete
+    ///
+    /// In dry-run mode runs return the report of [`Self::dry_run`] and
+    /// leave storage and the registry untouched.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Whether runs only report what they would delete
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Storage objects holding a chunk: its shards, or the chunk ID itself
+    /// for chunks registered without shards
+    fn storage_cids(chunk_id: &[u8; 32], metadata: &ChunkMetadata) -> Vec<Cid> {
+        if metadata.shard_ids.is_empty() {
+            vec![Cid::new(*chunk_id)]
+        } else {
+            metadata.shard_ids.iter().map(|id| Cid::new(*id)).collect()
         }
     }
 
     /// Mark and sweep to identify chunks for collection
     /// Returns list of chunk IDs that can be safely deleted
     pub fn mark_sweep(&self) -> Vec<[u8; 32]> {
-        let registry = self.chunk_registry.read();
+        let registry = &self.chunk_registry;
 
+        let Some(query) = self.policy_query() else {
+            // Never delete anything
+            return Vec::new();
+        };
         match &self.policy {
-            RetentionPolicy::KeepAll => {
-                // Never delete anything
-                Vec::new()
+            RetentionPolicy::LeastRecentlyRead(_) => {
+                let idle = registry.query(&query);
+                let mut candidates = self.with_metadata(idle);
+                candidates.sort_by_key(|(id, m)| (m.last_accessed_locally, *id));
+                candidates.into_iter().map(|(id, _)| id).collect()
             }
-            _ => {
-                // Get all unreferenced chunks
-                let unreferenced = registry.get_unreferenced();
-
-                // Additional filtering based on policy
-                unreferenced
-                    .into_iter()
-                    .filter(|chunk_id| self.should_collect_chunk(chunk_id))
-                    .collect()
+            RetentionPolicy::TargetSize(target_bytes) => {
+                // Chunks of unknown age sort first, as the oldest
+                let mut candidates = self.with_metadata(registry.query(&query));
+                candidates.sort_by_key(|(id, m)| (m.first_seen_locally, *id));
+                self.until_target(candidates, *target_bytes)
             }
+            // Age-based policies and those handled at version level
+            _ => registry.query(&query),
         }
     }
 
-    /// Collect (delete) specified chunks
-    pub async fn collect(&self, chunk_ids: Vec<[u8; 32]>) -> Result<CollectionReport> {
-        let mut report = CollectionReport::new();
-
-        for chunk_id in chunk_ids {
-            // Double-check that chunk is still unreferenced
-            {
-                let registry = self.chunk_registry.read();
-                if let Some(count) = registry.get_ref_count(&chunk_id) {
-                    if count > 0 {
-                        report.skipped += 1;
-                        continue;
-                    }
-                } else {
-                    // Chunk not in registry anymore
-                    report.skipped += 1;
-                    continue;
-                }
+    /// Chunks the policy may delete, before any ordering; `None` if it
+    /// keeps everything
+    fn policy_query(&self) -> Option<ChunkQuery> {
+        let query = ChunkQuery::new().orphaned().unpinned();
+        match &self.policy {
+            RetentionPolicy::KeepAll => None,
+            RetentionPolicy::KeepRecent(max_age_seconds) => {
+                Some(query.older_than(*max_age_seconds))
+            }
+            RetentionPolicy::LeastRecentlyRead(max_idle_seconds) => {
+                Some(query.idle_longer_than(*max_idle_seconds))
             }
+            _ => Some(query),
+        }
+    }
 
-            // Attempt to delete from storage
-            let cid = Cid::new(chunk_id);
-            match self.storage.delete_shard(&cid).await {
-                Ok(()) => {
-                    // Remove from registry after successful deletion
-                    let mut registry = self.chunk_registry.write();
-                    if let Err(e) = registry.remove_chunk(&chunk_id) {
-                        tracing::warn!("Failed to remove chunk from registry: {}", e);
-                    }
+    /// The leading `candidates` whose removal brings the registry down to
+    /// `target_bytes`
+    fn until_target(
+        &self,
+        candidates: Vec<([u8; 32], ChunkMetadata)>,
+        target_bytes: u64,
+    ) -> Vec<[u8; 32]> {
+        let mut excess = self
+            .chunk_registry
+            .total_size()
+            .saturating_sub(target_bytes);
+        candidates
+            .into_iter()
+            .take_while(|(_, m)| {
+                let needed = excess > 0;
+                excess = excess.saturating_sub(m.size as u64);
+                needed
+            })
+            .map(|(id, _)| id)
+            .collect()
+    }
 
-                    report.collected += 1;
-                    report.bytes_freed += registry.get_chunk_size(&chunk_id).unwrap_or(0) as u64;
-                }
-                Err(e) => {
-                    tracing::error!("Failed to delete chunk {:?}: {}", chunk_id, e);
-                    report.failed += 1;
+    /// Sweep the registry in batches of `batch_size` chunks until
+    /// `time_budget` is spent
+    ///
+    /// The sweep walks chunk IDs in ascending order and keeps a cursor, so
+    /// the next call continues after the last chunk examined and wraps
+    /// around once the end is reached. The budget is checked between
+    /// batches, so a run may overshoot it by one batch. Deletion order
+    /// follows chunk IDs rather than the policy's ordering: a `TargetSize`
+    /// sweep stops as soon as the registry is under target, but not
+    /// necessarily by removing the oldest chunks first.
+    pub async fn run_incremental(
+        &self,
+        time_budget: Duration,
+        batch_size: usize,
+    ) -> Result<CollectionReport> {
+        let started = self.begin_run();
+        let mut report = CollectionReport::new();
+        let Some(query) = self.policy_query() else {
+            return Ok(self.finish_run(report, started));
+        };
+        let batch_size = batch_size.max(1);
+        let mut cursor = self.cursor();
+
+        loop {
+            let page = self
+                .chunk_registry
+                .query_page(&query, cursor.as_ref(), batch_size);
+            let mut done = page.len() < batch_size;
+            let page_end = page.last().copied();
+
+            let batch = match &self.policy {
+                RetentionPolicy::TargetSize(target_bytes) => {
+                    let batch = self.until_target(self.with_metadata(page.clone()), *target_bytes);
+                    // Under target once this batch is gone
+                    done |= batch.len() < page.len();
+                    batch
                 }
+                _ => page,
+            };
+            let examined = self.collect_batch(&batch, &mut report).await;
+            if report.cancelled {
+                cursor = examined.checked_sub(1).map(|i| batch[i]).or(cursor);
+                break;
+            }
+            cursor = page_end;
+
+            if done {
+                cursor = None;
+                break;
+            }
+            if started.elapsed() >= time_budget {
+                break;
             }
         }
 
-        Ok(report)
+        self.save_cursor(cursor)?;
+        report.resume_from = cursor;
+        Ok(self.finish_run(report, started))
     }
 
-    /// Run a full garbage collection cycle
-    pub async fn run(&self) -> Result<CollectionReport> {
-        let chunks_to_collect = self.mark_sweep();
+    /// Pair chunk IDs with their current metadata, dropping unknown chunks
+    fn with_metadata(&self, chunk_ids: Vec<[u8; 32]>) -> Vec<([u8; 32], ChunkMetadata)> {
+        chunk_ids
+            .into_iter()
+            .filter_map(|id| self.chunk_registry.get_metadata(&id).map(|m| (id, m)))
+            .collect()
+    }
 
-        if chunks_to_collect.is_empty() {
-            Ok(CollectionReport::new())
-        } else {
-            self.collect(chunks_to_collect).await
-        }
+    /// Collect (delete) specified chunks
+    ///
+    /// Every shard recorded for a chunk is deleted; chunks registered
+    /// without shards are stored under their own ID.
+    pub async fn collect(&self, chunk_ids: Vec<[u8; 32]>) -> Result<CollectionReport> {
+        let started = self.begin_run();
+        let mut report = CollectionReport::new();
+        self.collect_batch(&chunk_ids, &mut report).await;
+        Ok(self.finish_run(report, started))
     }
 
-    /// Check if a specific chunk should be collected
-    fn should_collect_chunk(&self, chunk_id: &[u8; 32]) -> bool {
-        let registry = self.chunk_registry.read();
+    /// Delete `chunk_ids`, recording the outcome in `report`, until done or
+    /// cancelled
+    ///
+    /// Returns how many of the chunks were examined.
+    async fn collect_batch(&self, chunk_ids: &[[u8; 32]], report: &mut CollectionReport) -> usize {
+        for (examined, &chunk_id) in chunk_ids.iter().enumerate() {
+            if self.cancelled.load(Ordering::SeqCst) {
+                report.cancelled = true;
+                return examined;
+            }
+            self.collect_chunk(chunk_id, report).await;
+
+            let progress = {
+                let mut progress = self.progress.write();
+                progress.examined += 1;
+                progress.deleted = report.collected;
+                progress.failed = report.failed;
+                progress.bytes_reclaimed = report.bytes_freed;
+                progress.clone()
+            };
+            let _ = self.events.send(GcEvent::Progress(progress));
+        }
+        chunk_ids.len()
+    }
 
-        // Get chunk metadata
-        let metadata = match registry.get_metadata(chunk_id) {
-            Some(m) => m,
-            None => return false, // Don't collect if we don't have metadata
+    /// Delete one chunk's shards and then its registry entry
+    ///
+    /// The chunk is tombstoned first, which atomically re-checks that it is
+    /// unreferenced and unpinned and makes new references to it fail until
+    /// it is gone. A chunk whose shards cannot all be deleted keeps its
+    /// tombstone, so a later run finishes the job.
+    async fn collect_chunk(&self, chunk_id: [u8; 32], report: &mut CollectionReport) {
+        let (cids, size) = match self.chunk_registry.tombstone(&chunk_id) {
+            Ok(Some(metadata)) => (Self::storage_cids(&chunk_id, &metadata), metadata.size),
+            // Referenced again, pinned, or not in the registry anymore
+            Ok(None) => {
+                report.skipped += 1;
+                return;
+            }
+            Err(e) => {
+                tracing::error!("Failed to tombstone chunk {:?}: {}", chunk_id, e);
+                report.failed += 1;
+                report.failed_ids.push(chunk_id);
+                return;
+            }
         };
 
-        // Never collect chunks with references
-        if metadata.ref_count > 0 {
-            return false;
+        // Attempt to delete from storage
+        let mut failed = false;
+        for cid in &cids {
+            if let Err(e) = self.storage.delete_shard(cid).await {
+                tracing::error!("Failed to delete chunk {:?}: {}", chunk_id, e);
+                failed = true;
+            }
+        }
+        if failed {
+            report.failed += 1;
+            report.failed_ids.push(chunk_id);
+            return;
         }
 
-        // Apply age-based policies
-        match &self.policy {
-            RetentionPolicy::KeepRecent(max_age_seconds) => {
-                if let Some(age) = metadata.age_seconds() {
-                    age > *max_age_seconds
-                } else {
-                    false // Keep if we can't determine age
-                }
-            }
-            _ => true, // Other policies handled at version level
+        // Remove from registry after successful deletion
+        let registry = &self.chunk_registry;
+        if let Err(e) = registry.remove_chunk(&chunk_id) {
+            tracing::warn!("Failed to remove chunk from registry: {}", e);
+        }
+
+        report.collected += 1;
+        report.bytes_freed += size as u64;
+        report.chunk_ids.push(chunk_id);
+    }
+
+    /// Run a full garbage collection cycle
+    ///
+    /// Collectors set up with [`Self::with_incremental`] run one bounded
+    /// step of the sweep instead. Progress is published to
+    /// [`Self::subscribe`] and the run can be stopped with [`Self::cancel`].
+    #[tracing::instrument(name = "gc_run", skip_all)]
+    pub async fn run(&self) -> Result<CollectionReport> {
+        if self.dry_run {
+            let started = self.begin_run();
+            let dry_run = self.dry_run();
+            let report = CollectionReport {
+                collected: dry_run.chunks_to_delete,
+                bytes_freed: dry_run.bytes_to_free,
+                chunk_ids: dry_run.chunk_ids,
+                dry_run: true,
+                ..CollectionReport::default()
+            };
+            return Ok(self.finish_run(report, started));
         }
+        if let Some((time_budget, batch_size)) = self.incremental {
+            return self.run_incremental(time_budget, batch_size).await;
+        }
+        let chunks_to_collect = self.mark_sweep();
+        self.collect(chunks_to_collect).await
     }
 
     /// Update retention policy
@@ -184,7 +481,7 @@ impl GarbageCollector {
     /// Estimate space that can be reclaimed
     pub fn estimate_reclaimable(&self) -> u64 {
         let chunks_to_collect = self.mark_sweep();
-        let registry = self.chunk_registry.read();
+        let registry = &self.chunk_registry;
 
         chunks_to_collect
             .iter()
@@ -194,20 +491,32 @@ impl GarbageCollector {
     }
 
     /// Perform a dry run without actually deleting
+    ///
+    /// Lists exactly the chunks a run would delete now and the shards it
+    /// would remove from storage for them.
     pub fn dry_run(&self) -> GCDryRun {
-        let chunks_to_collect = self.mark_sweep();
-        let registry = self.chunk_registry.read();
-
-        let total_size: u64 = chunks_to_collect
-            .iter()
-            .filter_map(|id| registry.get_chunk_size(id))
-            .map(|size| size as u64)
-            .sum();
+        let mut chunk_ids = self.mark_sweep();
+        chunk_ids.sort_unstable();
+        let registry = &self.chunk_registry;
+
+        let mut total_size = 0;
+        let mut shard_ids = Vec::new();
+        for chunk_id in &chunk_ids {
+            if let Some(metadata) = registry.get_metadata(chunk_id) {
+                total_size += metadata.size as u64;
+                shard_ids.extend(
+                    Self::storage_cids(chunk_id, &metadata)
+                        .iter()
+                        .map(|cid| *cid.as_bytes()),
+                );
+            }
+        }
 
         GCDryRun {
-            chunks_to_delete: chunks_to_collect.len(),
+            chunks_to_delete: chunk_ids.len(),
             bytes_to_free: total_size,
-            chunk_ids: chunks_to_collect,
+            chunk_ids,
+            shard_ids,
         }
     }
 }
@@ -225,6 +534,18 @@ pub struct CollectionReport {
     pub bytes_freed: u64,
     /// Time taken in milliseconds
     pub duration_ms: u64,
+    /// IDs of the chunks collected
+    pub chunk_ids: Vec<[u8; 32]>,
+    /// Whether nothing was actually deleted, see
+    /// [`GarbageCollector::with_dry_run`]
+    pub dry_run: bool,
+    /// Chunk ID an incremental sweep stopped after when its time budget ran
+    /// out or it was cancelled; `None` once the sweep reached the end
+    pub resume_from: Option<[u8; 32]>,
+    /// IDs of the chunks whose shards could not all be deleted
+    pub failed_ids: Vec<[u8; 32]>,
+    /// Whether the run was stopped by [`GarbageCollector::cancel`]
+    pub cancelled: bool,
 }
 
 impl CollectionReport {
@@ -244,6 +565,30 @@ impl CollectionReport {
     }
 }
 
+/// Running counters of a garbage collection run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GcProgress {
+    /// Candidate chunks examined so far
+    pub examined: usize,
+    /// Chunks deleted so far
+    pub deleted: usize,
+    /// Chunks that failed to delete so far
+    pub failed: usize,
+    /// Bytes reclaimed so far
+    pub bytes_reclaimed: u64,
+}
+
+/// Progress events emitted by the garbage collector
+#[derive(Debug, Clone)]
+pub enum GcEvent {
+    /// A run started
+    Started,
+    /// A candidate chunk was examined; carries the run's counters
+    Progress(GcProgress),
+    /// A run finished or was cancelled
+    Finished(CollectionReport),
+}
+
 /// Dry run results
 #[derive(Debug, Clone)]
 pub struct GCDryRun {
@@ -251,8 +596,10 @@ pub struct GCDryRun {
     pub chunks_to_delete: usize,
     /// Bytes that would be freed
     pub bytes_to_free: u64,
-    /// Actual chunk IDs that would be deleted
+    /// Actual chunk IDs that would be deleted, sorted
     pub chunk_ids: Vec<[u8; 32]>,
+    /// CIDs of the shards that would be removed from storage
+    pub shard_ids: Vec<[u8; 32]>,
 }
 
 /// Garbage collection scheduler
@@ -263,8 +610,12 @@ pub struct GCScheduler {
     min_interval: u64,
     /// Minimum reclaimable space before triggering (bytes)
     min_reclaimable: u64,
+    /// Free space (bytes) below which collection runs regardless of reclaimable size
+    min_free_space: Option<u64>,
     /// Last collection timestamp
     last_run: Option<u64>,
+    /// Source of interval changes while running in the background
+    interval_updates: Option<watch::Receiver<Duration>>,
 }
 
 impl GCScheduler {
@@ -274,31 +625,89 @@ impl GCScheduler {
             gc,
             min_interval,
             min_reclaimable,
+            min_free_space: None,
             last_run: None,
+            interval_updates: None,
+        }
+    }
+
+    /// Follow interval changes sent on `updates` once spawned
+    ///
+    /// Each new value replaces both the minimum interval between runs and
+    /// the time between checks, without waiting for the current check.
+    pub fn follow_interval(mut self, updates: watch::Receiver<Duration>) -> Self {
+        self.interval_updates = Some(updates);
+        self
+    }
+
+    /// Resolves when the followed interval changes; never if none is followed
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn interval_changed(updates: &mut Option<watch::Receiver<Duration>>) {
+        let changed = match updates {
+            Some(updates) => updates.changed().await.is_ok(),
+            None => false,
+        };
+        if !changed {
+            // Sender gone; keep the last interval from here on
+            *updates = None;
+            std::future::pending::<()>().await;
+        }
+    }
+
+    /// Create a scheduler from the GC section of the configuration
+    ///
+    /// Collection runs at most once per `run_interval`: whenever anything is
+    /// reclaimable, or when the storage backend reports less than
+    /// `min_free_space_gb` of free space.
+    pub fn from_config(gc: Arc<GarbageCollector>, config: &GcConfig) -> Self {
+        Self::new(gc, config.run_interval.as_secs(), 1)
+            .with_min_free_space(config.min_free_space_gb as u64 * 1024 * 1024 * 1024)
+    }
+
+    /// Force collection whenever the backend has less than `bytes` free
+    ///
+    /// Backends that do not report free space never trigger this.
+    pub fn with_min_free_space(mut self, bytes: u64) -> Self {
+        self.min_free_space = Some(bytes);
+        self
+    }
+
+    /// Check whether the backend is below the free space threshold
+    pub async fn low_on_space(&self) -> bool {
+        let Some(threshold) = self.min_free_space else {
+            return false;
+        };
+        match self.gc.storage.stats().await {
+            Ok(stats) => stats.free_space.is_some_and(|free| free < threshold),
+            Err(e) => {
+                tracing::warn!("Failed to read storage stats: {}", e);
+                false
+            }
         }
     }
 
     /// Check if garbage collection should run
     pub fn should_run(&self) -> bool {
-        // Check time since last run
+        !self.interval_pending() && self.gc.estimate_reclaimable() >= self.min_reclaimable
+    }
+
+    /// Whether the minimum interval since the last run has not yet elapsed
+    fn interval_pending(&self) -> bool {
         if let Some(last) = self.last_run {
             let now = std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .map(|d| d.as_secs())
                 .unwrap_or(0);
 
-            if now - last < self.min_interval {
-                return false;
-            }
+            return now.saturating_sub(last) < self.min_interval;
         }
-
-        // Check reclaimable space
-        self.gc.estimate_reclaimable() >= self.min_reclaimable
+        false
     }
 
     /// Run garbage collection if needed
     pub async fn run_if_needed(&mut self) -> Result<Option<CollectionReport>> {
-        if !self.should_run() {
+        let needed = self.should_run() || (!self.interval_pending() && self.low_on_space().await);
+        if !needed {
             return Ok(None);
         }
 
@@ -315,18 +724,147 @@ impl GCScheduler {
     }
 }
 
+/// What the background collector has done so far
+#[derive(Debug, Clone, Default)]
+pub struct GcStatus {
+    /// Collections run, scheduled or triggered
+    pub runs: u64,
+    /// When the last collection finished
+    pub last_run_at: Option<SystemTime>,
+    /// Report of the last successful collection
+    pub last_report: Option<CollectionReport>,
+    /// Error of the last collection, cleared by the next success
+    pub last_error: Option<String>,
+}
+
+impl GCScheduler {
+    /// Run the scheduler in a background task on the current tokio runtime
+    ///
+    /// Every `check_interval` the task collects if [`Self::run_if_needed`]
+    /// says so; [`GcHandle::trigger`] collects immediately instead.
+    ///
+    /// Not available on wasm32, which has no Tokio runtime to spawn on.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn(mut self, mut check_interval: std::time::Duration) -> GcHandle {
+        let status = Arc::new(RwLock::new(GcStatus::default()));
+        let trigger = Arc::new(Notify::new());
+        let (shutdown, mut shutdown_rx) = watch::channel(false);
+
+        let gc = self.gc.clone();
+        let task = {
+            let status = status.clone();
+            let trigger = trigger.clone();
+            tokio::spawn(async move {
+                let mut updates = self.interval_updates.take();
+                loop {
+                    if let Some(updates) = &updates {
+                        check_interval = *updates.borrow();
+                        self.min_interval = check_interval.as_secs();
+                    }
+                    let result = tokio::select! {
+                        _ = tokio::time::sleep(check_interval) => self.run_if_needed().await,
+                        _ = trigger.notified() => self.gc.run().await.map(Some),
+                        _ = Self::interval_changed(&mut updates) => continue,
+                        _ = shutdown_rx.changed() => break,
+                    };
+
+                    let mut status = status.write();
+                    match result {
+                        Ok(None) => continue,
+                        Ok(Some(report)) => {
+                            status.last_report = Some(report);
+                            status.last_error = None;
+                        }
+                        Err(e) => {
+                            tracing::warn!("Background garbage collection failed: {}", e);
+                            status.last_error = Some(e.to_string());
+                        }
+                    }
+                    status.runs += 1;
+                    status.last_run_at = Some(SystemTime::now());
+                }
+            })
+        };
+
+        GcHandle {
+            gc,
+            status,
+            trigger,
+            shutdown,
+            task,
+        }
+    }
+
+    /// Spawn the scheduler described by `config`, unless GC is disabled
+    ///
+    /// The task checks every `run_interval`, see [`Self::from_config`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn_from_config(gc: Arc<GarbageCollector>, config: &GcConfig) -> Option<GcHandle> {
+        config
+            .enabled
+            .then(|| Self::from_config(gc, config).spawn(config.run_interval))
+    }
+}
+
+/// Handle to a background garbage collector
+pub struct GcHandle {
+    gc: Arc<GarbageCollector>,
+    status: Arc<RwLock<GcStatus>>,
+    trigger: Arc<Notify>,
+    shutdown: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl GcHandle {
+    /// What the collector has done so far
+    pub fn status(&self) -> GcStatus {
+        self.status.read().clone()
+    }
+
+    /// Collect now, regardless of the schedule and thresholds
+    ///
+    /// Returns at once; the outcome shows up in [`Self::status`].
+    pub fn trigger(&self) {
+        self.trigger.notify_one();
+    }
+
+    /// Subscribe to progress events of the collector's runs
+    pub fn subscribe(&self) -> broadcast::Receiver<GcEvent> {
+        self.gc.subscribe()
+    }
+
+    /// Counters of the run in progress, or of the last run
+    pub fn progress(&self) -> GcProgress {
+        self.gc.progress()
+    }
+
+    /// Stop the run in progress, if any; the schedule carries on
+    pub fn cancel(&self) {
+        self.gc.cancel();
+    }
+
+    /// Stop the collector, waiting for any in-progress collection to finish
+    pub async fn stop(self) {
+        let _ = self.shutdown.send(true);
+        let _ = self.task.await;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::chunk_registry::RegistryError;
     use crate::{
         EncryptionMode, FecError, FileMetadata, GcReport, Shard, ShardHeader, StorageStats,
     };
     use async_trait::async_trait;
+    use std::result::Result;
 
     // Mock storage backend for testing
     struct MockStorage {
         deleted: Arc<RwLock<Vec<[u8; 32]>>>,
         fail_on: HashSet<[u8; 32]>,
+        yield_on_delete: bool,
     }
 
     impl MockStorage {
@@ -334,17 +872,24 @@ mod tests {
             Self {
                 deleted: Arc::new(RwLock::new(Vec::new())),
                 fail_on: HashSet::new(),
+                yield_on_delete: false,
             }
         }
 
-        #[allow(dead_code)]
+        /// Let other tasks run during every deletion
+        fn yielding(mut self) -> Self {
+            self.yield_on_delete = true;
+            self
+        }
+
         fn with_failures(mut self, chunks: Vec<[u8; 32]>) -> Self {
             self.fail_on = chunks.into_iter().collect();
             self
         }
     }
 
-    #[async_trait]
+    #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
     impl StorageBackend for MockStorage {
         async fn put_shard(&self, _cid: &Cid, _shard: &Shard) -> Result<(), FecError> {
             Ok(())
@@ -356,6 +901,9 @@ mod tests {
         }
 
         async fn delete_shard(&self, cid: &Cid) -> Result<(), FecError> {
+            if self.yield_on_delete {
+                tokio::task::yield_now().await;
+            }
             if self.fail_on.contains(cid.as_bytes()) {
                 return Err(FecError::Backend("Mock deletion failure".to_string()));
             }
@@ -393,6 +941,8 @@ mod tests {
                 total_size: 0,
                 metadata_count: 0,
                 unreferenced_shards: 0,
+                capacity: None,
+                free_space: None,
             })
         }
 
@@ -407,12 +957,12 @@ mod tests {
 
     #[tokio::test]
     async fn test_gc_keep_all_policy() {
-        let registry = Arc::new(RwLock::new(ChunkRegistry::new()));
+        let registry = Arc::new(ChunkRegistry::new());
         let storage = Arc::new(MockStorage::new());
 
         // Add unreferenced chunk
         {
-            let mut reg = registry.write();
+            let reg = &registry;
             reg.increment_ref(&[1u8; 32]).unwrap();
             reg.decrement_ref(&[1u8; 32]).unwrap();
         }
@@ -425,12 +975,12 @@ mod tests {
 
     #[tokio::test]
     async fn test_gc_collection() {
-        let registry = Arc::new(RwLock::new(ChunkRegistry::new()));
+        let registry = Arc::new(ChunkRegistry::new());
         let storage = Arc::new(MockStorage::new());
 
         // Add unreferenced chunks
         {
-            let mut reg = registry.write();
+            let reg = &registry;
             for i in 1..=3 {
                 reg.increment_ref(&[i; 32]).unwrap();
                 reg.decrement_ref(&[i; 32]).unwrap();
@@ -452,14 +1002,44 @@ mod tests {
         assert_eq!(deleted.len(), 3);
     }
 
+    #[tokio::test]
+    async fn test_gc_tombstones_chunks_against_new_references() {
+        let registry = Arc::new(ChunkRegistry::new());
+        for i in 1..=2 {
+            registry.increment_ref(&[i; 32]).unwrap();
+            registry.decrement_ref(&[i; 32]).unwrap();
+        }
+
+        // A chunk whose shards could not be deleted stays tombstoned
+        let failing = Arc::new(MockStorage::new().with_failures(vec![[1u8; 32]]));
+        let gc = GarbageCollector::new(RetentionPolicy::KeepAll, registry.clone(), failing);
+        let report = gc.collect(vec![[1u8; 32], [2u8; 32]]).await.unwrap();
+        assert_eq!((report.collected, report.failed), (1, 1));
+        assert!(!registry.contains(&[2u8; 32]));
+        assert!(matches!(
+            registry.increment_ref(&[1u8; 32]),
+            Err(RegistryError::Tombstoned(_))
+        ));
+        assert_eq!(registry.get_ref_count(&[1u8; 32]), Some(0));
+
+        // A later run finishes it
+        let gc = GarbageCollector::new(
+            RetentionPolicy::KeepAll,
+            registry.clone(),
+            Arc::new(MockStorage::new()),
+        );
+        assert_eq!(gc.collect(vec![[1u8; 32]]).await.unwrap().collected, 1);
+        registry.increment_ref(&[1u8; 32]).unwrap();
+    }
+
     #[tokio::test]
     async fn test_gc_dry_run() {
-        let registry = Arc::new(RwLock::new(ChunkRegistry::new()));
+        let registry = Arc::new(ChunkRegistry::new());
         let storage = Arc::new(MockStorage::new());
 
         // Add unreferenced chunks with sizes
         {
-            let mut reg = registry.write();
+            let reg = &registry;
             use crate::metadata::ChunkReference;
 
             let chunks = vec![
@@ -478,9 +1058,204 @@ mod tests {
         assert_eq!(dry_run.bytes_to_free, 3072);
     }
 
+    #[tokio::test]
+    async fn test_gc_keeps_pinned_chunks() {
+        let registry = Arc::new(ChunkRegistry::new());
+        let storage = Arc::new(MockStorage::new());
+        {
+            let reg = &registry;
+            for i in 0..2u8 {
+                reg.increment_ref(&[i; 32]).unwrap();
+                reg.decrement_ref(&[i; 32]).unwrap();
+            }
+            reg.pin(&[[0u8; 32]]).unwrap();
+        }
+
+        let gc = GarbageCollector::new(RetentionPolicy::KeepRecent(0), registry.clone(), storage);
+        assert_eq!(gc.dry_run().chunk_ids, vec![[1u8; 32]]);
+
+        // Pinned chunks are skipped even when named explicitly
+        let report = gc.collect(vec![[0u8; 32], [1u8; 32]]).await.unwrap();
+        assert_eq!((report.collected, report.skipped), (1, 1));
+        assert!(registry.contains(&[0u8; 32]));
+    }
+
+    /// Registry of unreferenced chunks `[i; 32]` of 100 bytes each, first seen
+    /// and last read at the given timestamps
+    fn aged_registry(times: &[(u64, u64)]) -> Arc<ChunkRegistry> {
+        let chunks = times
+            .iter()
+            .enumerate()
+            .map(|(i, &(first_seen, last_read))| {
+                let mut metadata = ChunkMetadata::new(100);
+                metadata.first_seen_locally = Some(first_seen);
+                metadata.last_accessed_locally = Some(last_read);
+                ([i as u8; 32], metadata)
+            })
+            .collect();
+        let registry = Arc::new(ChunkRegistry::new());
+        registry
+            .restore(crate::chunk_registry::RegistrySnapshot::new(chunks))
+            .unwrap();
+        registry
+    }
+
+    #[test]
+    fn test_least_recently_read_policy() {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        // Chunk 0 is old but was read recently; 2 was read longest ago
+        let registry = aged_registry(&[(0, now), (0, now - 500), (now, now - 900)]);
+        let gc = GarbageCollector::new(
+            RetentionPolicy::LeastRecentlyRead(100),
+            registry.clone(),
+            Arc::new(MockStorage::new()),
+        );
+        assert_eq!(gc.mark_sweep(), vec![[2u8; 32], [1u8; 32]]);
+
+        registry.record_read(&[[2u8; 32]]).unwrap();
+        assert_eq!(gc.mark_sweep(), vec![[1u8; 32]]);
+    }
+
+    #[test]
+    fn test_target_size_policy_deletes_oldest_first() {
+        let registry = aged_registry(&[(30, 0), (10, 0), (20, 0), (40, 0)]);
+        let gc = GarbageCollector::new(
+            RetentionPolicy::TargetSize(250),
+            registry.clone(),
+            Arc::new(MockStorage::new()),
+        );
+        // 400 bytes stored, so the two oldest chunks must go
+        assert_eq!(gc.mark_sweep(), vec![[1u8; 32], [2u8; 32]]);
+
+        registry.pin(&[[1u8; 32]]).unwrap();
+        assert_eq!(gc.mark_sweep(), vec![[2u8; 32], [0u8; 32]]);
+
+        let gc = GarbageCollector::new(
+            RetentionPolicy::TargetSize(400),
+            registry,
+            Arc::new(MockStorage::new()),
+        );
+        assert!(gc.mark_sweep().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_incremental_gc_resumes_from_saved_cursor() {
+        let registry = aged_registry(&[(0, 0); 7]);
+        let dir = tempfile::TempDir::new().unwrap();
+        let cursor_file = dir.path().join("gc.cursor");
+        let storage = Arc::new(MockStorage::new());
+
+        let gc = GarbageCollector::new(
+            RetentionPolicy::KeepRecent(0),
+            registry.clone(),
+            storage.clone(),
+        )
+        .with_incremental(Duration::ZERO, 3)
+        .with_cursor_file(&cursor_file)
+        .unwrap();
+        let report = gc.run().await.unwrap();
+        assert_eq!(report.chunk_ids, vec![[0u8; 32], [1u8; 32], [2u8; 32]]);
+        assert_eq!(report.resume_from, Some([2u8; 32]));
+
+        // A fresh collector picks up the saved cursor
+        let gc = GarbageCollector::new(
+            RetentionPolicy::KeepRecent(0),
+            registry.clone(),
+            storage.clone(),
+        )
+        .with_cursor_file(&cursor_file)
+        .unwrap();
+        assert_eq!(gc.cursor(), Some([2u8; 32]));
+        let report = gc
+            .run_incremental(Duration::from_secs(60), 3)
+            .await
+            .unwrap();
+        assert_eq!(report.collected, 4);
+        assert_eq!(report.resume_from, None);
+        assert_eq!(gc.cursor(), None);
+        assert_eq!(registry.stats().total_chunks, 0);
+        assert_eq!(storage.deleted.read().len(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_gc_progress_events_and_cancellation() {
+        let registry = aged_registry(&[(0, 0); 5]);
+        let gc = GarbageCollector::new(
+            RetentionPolicy::KeepRecent(0),
+            registry.clone(),
+            Arc::new(MockStorage::new().yielding()),
+        );
+        let mut events = gc.subscribe();
+
+        // Cancel as soon as the first chunk has been dealt with
+        let watcher = async {
+            let mut seen = Vec::new();
+            while let Ok(event) = events.recv().await {
+                if let GcEvent::Progress(progress) = &event {
+                    gc.cancel();
+                    seen.push(progress.clone());
+                }
+                if matches!(event, GcEvent::Finished(_)) {
+                    break;
+                }
+            }
+            seen
+        };
+        let (report, seen) = tokio::join!(gc.run(), watcher);
+        let report = report.unwrap();
+
+        assert!(report.cancelled);
+        assert!(report.collected > 0 && report.collected < 5);
+        assert_eq!(registry.stats().total_chunks, 5 - report.collected);
+        assert_eq!(
+            seen[0],
+            GcProgress {
+                examined: 1,
+                deleted: 1,
+                failed: 0,
+                bytes_reclaimed: 100,
+            }
+        );
+        assert_eq!(gc.progress().deleted, report.collected);
+
+        // Cancelling only affects the run in progress
+        let report = gc.run().await.unwrap();
+        assert!(!report.cancelled);
+        assert_eq!(registry.stats().total_chunks, 0);
+    }
+
+    #[tokio::test]
+    async fn test_gc_dry_run_mode_deletes_nothing() {
+        let registry = Arc::new(ChunkRegistry::new());
+        let mut chunk = crate::metadata::ChunkReference::new([1u8; 32], 0, 0, 512);
+        chunk.shard_ids = vec![[7u8; 32], [8u8; 32]];
+        registry.increment_refs(&[chunk]).unwrap();
+        registry.decrement_ref(&[1u8; 32]).unwrap();
+        let storage = Arc::new(MockStorage::new());
+
+        let gc = GarbageCollector::new(
+            RetentionPolicy::KeepLastN(0),
+            registry.clone(),
+            storage.clone(),
+        )
+        .with_dry_run(true);
+        let preview = gc.dry_run();
+        assert_eq!(preview.shard_ids, vec![[7u8; 32], [8u8; 32]]);
+
+        let report = gc.run().await.unwrap();
+        assert!(report.dry_run);
+        assert_eq!(report.chunk_ids, vec![[1u8; 32]]);
+        assert_eq!(report.bytes_freed, 512);
+        assert!(storage.deleted.read().is_empty());
+        assert!(registry.contains(&[1u8; 32]));
+    }
+
     #[tokio::test]
     async fn test_gc_scheduler() {
-        let registry = Arc::new(RwLock::new(ChunkRegistry::new()));
+        let registry = Arc::new(ChunkRegistry::new());
         let storage = Arc::new(MockStorage::new());
 
         let gc = Arc::new(GarbageCollector::new(
@@ -497,4 +1272,94 @@ mod tests {
         // Should not run immediately
         assert!(!scheduler.should_run());
     }
+
+    #[tokio::test]
+    async fn test_background_gc_trigger_and_stop() {
+        let registry = Arc::new(ChunkRegistry::new());
+        registry.increment_ref(&[1u8; 32]).unwrap();
+        registry.decrement_ref(&[1u8; 32]).unwrap();
+        let storage = Arc::new(MockStorage::new());
+        let gc = Arc::new(GarbageCollector::new(
+            RetentionPolicy::KeepLastN(0),
+            registry.clone(),
+            storage,
+        ));
+
+        let disabled = GcConfig {
+            enabled: false,
+            ..GcConfig::default()
+        };
+        assert!(GCScheduler::spawn_from_config(gc.clone(), &disabled).is_none());
+
+        let handle = GCScheduler::spawn_from_config(gc, &GcConfig::default()).unwrap();
+        assert_eq!(handle.status().runs, 0);
+        handle.trigger();
+        for _ in 0..100 {
+            if handle.status().runs > 0 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let status = handle.status();
+        assert_eq!(status.runs, 1);
+        assert_eq!(status.last_report.map(|r| r.collected), Some(1));
+        assert!(status.last_run_at.is_some());
+        assert!(!registry.contains(&[1u8; 32]));
+        handle.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_background_gc_follows_interval_changes() {
+        let registry = Arc::new(ChunkRegistry::new());
+        registry.increment_ref(&[1u8; 32]).unwrap();
+        registry.decrement_ref(&[1u8; 32]).unwrap();
+        let gc = Arc::new(GarbageCollector::new(
+            RetentionPolicy::KeepLastN(0),
+            registry.clone(),
+            Arc::new(MockStorage::new()),
+        ));
+
+        let hour = Duration::from_secs(3600);
+        let (interval, updates) = watch::channel(hour);
+        let handle = GCScheduler::new(gc, hour.as_secs(), 0)
+            .follow_interval(updates)
+            .spawn(hour);
+        interval.send_replace(Duration::from_millis(10));
+        for _ in 0..100 {
+            if handle.status().runs > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(handle.status().runs > 0);
+        assert!(!registry.contains(&[1u8; 32]));
+        handle.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_gc_scheduler_free_space_trigger() {
+        let registry = Arc::new(ChunkRegistry::new());
+        let storage = crate::MemoryStorage::with_capacity(4096);
+        let header = ShardHeader::new(EncryptionMode::Convergent, (3, 2), 2048, [0u8; 32]);
+        let shard = Shard::new(header, vec![0u8; 2048]);
+        storage
+            .put_shard(&shard.cid().unwrap(), &shard)
+            .await
+            .unwrap();
+
+        let gc = Arc::new(GarbageCollector::new(
+            RetentionPolicy::KeepLastN(0),
+            registry,
+            Arc::new(storage),
+        ));
+
+        let mut scheduler = GCScheduler::new(gc.clone(), 0, u64::MAX).with_min_free_space(1024);
+        assert!(!scheduler.low_on_space().await);
+        assert!(scheduler.run_if_needed().await.unwrap().is_none());
+
+        // Roughly 1.9 KiB free is below a 2 KiB threshold
+        let mut scheduler = GCScheduler::new(gc, 0, u64::MAX).with_min_free_space(2048);
+        assert!(scheduler.low_on_space().await);
+        assert!(scheduler.run_if_needed().await.unwrap().is_some());
+    }
 }
@@ -6,7 +6,8 @@
 //! This module implements arithmetic operations over GF(2^8) using
 //! the irreducible polynomial x^8 + x^4 + x^3 + x + 1 (0x11b)
 
-use std::ops::{Add, Div, Mul, Sub};
+use alloc::{vec, vec::Vec};
+use core::ops::{Add, Div, Mul, Sub};
 
 /// GF(256) field element
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -206,16 +207,17 @@ pub fn generate_cauchy_matrix(k: usize, m: usize) -> Vec<Vec<Gf256>> {
     // Cauchy matrix for parity rows
     // Use carefully chosen values to avoid xi + yj = 0
     for i in 0..m {
-        for j in 0..k {
+        let row = &mut matrix[k + i];
+        for (j, cell) in row.iter_mut().enumerate().take(k) {
             // Use non-overlapping ranges to ensure xi + yj never equals 0 in GF(256)
             let xi = Gf256::new((i + 1) as u8);
             let yj = Gf256::new((j + 128) as u8); // Offset by 128 to avoid overlap
             let sum = xi + yj;
             if sum.0 == 0 {
                 // This shouldn't happen with our offset, but handle it gracefully
-                matrix[k + i][j] = Gf256::new(1);
+                *cell = Gf256::new(1);
             } else {
-                matrix[k + i][j] = Gf256::ONE / sum;
+                *cell = Gf256::ONE / sum;
             }
         }
     }
@@ -4,11 +4,12 @@
 //! Information Dispersal Algorithm (IDA) implementation
 
 use crate::{FecError, Result};
+use alloc::{string::String, vec::Vec};
 use bytes::{Bytes, BytesMut};
 use serde::{Deserialize, Serialize};
 
 /// IDA configuration for different content sizes
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct IDAConfig {
     pub k: u16,           // Data shares required
     pub n: u16,           // Total shares (k + parity)
@@ -0,0 +1,434 @@
+//! Background integrity scanning for stored files
+//!
+//! This module provides health reports for files written through the
+//! [`StoragePipeline`] and an opt-in background scanner that periodically
+//! verifies every catalogued file and repairs degraded ones.
+
+use parking_lot::RwLock;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+
+use crate::metadata::FileMetadata;
+use crate::pipeline::StoragePipeline;
+use crate::storage::StorageBackend;
+
+/// Overall health of a file or chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    /// Every shard is present and intact
+    Healthy,
+    /// Some shards are missing or corrupted but the data can be reconstructed
+    Degraded,
+    /// Too few intact shards remain to reconstruct the data
+    Unrecoverable,
+}
+
+/// Shard-level health of a single chunk
+#[derive(Debug, Clone)]
+pub struct ChunkHealth {
+    /// Chunk identifier (BLAKE3 of chunk contents)
+    pub chunk_id: [u8; 32],
+    /// Number of shards the chunk was encoded into
+    pub total_shards: usize,
+    /// Number of shards required to reconstruct, if any shard could be read
+    pub required_shards: Option<usize>,
+    /// Share indices whose shards could not be fetched
+    pub missing: Vec<usize>,
+    /// Share indices whose shard contents no longer match their CID
+    pub corrupted: Vec<usize>,
+}
+
+impl ChunkHealth {
+    /// Number of intact shards
+    pub fn available(&self) -> usize {
+        self.total_shards - self.missing.len() - self.corrupted.len()
+    }
+
+    /// Health classification of this chunk
+    pub fn status(&self) -> HealthStatus {
+        match self.required_shards {
+            _ if self.missing.is_empty() && self.corrupted.is_empty() => HealthStatus::Healthy,
+            Some(k) if self.available() >= k => HealthStatus::Degraded,
+            _ => HealthStatus::Unrecoverable,
+        }
+    }
+}
+
+/// Result of verifying a file
+#[derive(Debug, Clone)]
+pub struct FileHealth {
+    /// File identifier
+    pub file_id: [u8; 32],
+    /// Per-chunk health, in chunk order
+    pub chunks: Vec<ChunkHealth>,
+}
+
+impl FileHealth {
+    /// Worst health status across all chunks
+    pub fn status(&self) -> HealthStatus {
+        self.chunks
+            .iter()
+            .map(ChunkHealth::status)
+            .fold(HealthStatus::Healthy, |worst, status| {
+                match (worst, status) {
+                    (HealthStatus::Unrecoverable, _) | (_, HealthStatus::Unrecoverable) => {
+                        HealthStatus::Unrecoverable
+                    }
+                    (HealthStatus::Degraded, _) | (_, HealthStatus::Degraded) => {
+                        HealthStatus::Degraded
+                    }
+                    _ => HealthStatus::Healthy,
+                }
+            })
+    }
+
+    /// Check whether every shard of the file is intact
+    pub fn is_healthy(&self) -> bool {
+        self.status() == HealthStatus::Healthy
+    }
+}
+
+/// Result of repairing a file
+#[derive(Debug, Clone, Default)]
+pub struct RepairReport {
+    /// File identifier
+    pub file_id: [u8; 32],
+    /// Number of shards rewritten to the backend
+    pub shards_restored: usize,
+    /// Number of chunks that were degraded and have been repaired
+    pub chunks_repaired: usize,
+    /// Number of chunks that could not be reconstructed
+    pub chunks_unrecoverable: usize,
+}
+
+/// Configuration for the background integrity scanner
+#[derive(Debug, Clone)]
+pub struct ScanConfig {
+    /// Time to wait between scan passes
+    pub interval: Duration,
+    /// Repair degraded files as they are found
+    pub repair: bool,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(3600),
+            repair: true,
+        }
+    }
+}
+
+/// Cumulative statistics collected by the scanner
+#[derive(Debug, Clone, Default)]
+pub struct ScanStats {
+    /// Number of completed scan passes
+    pub passes: u64,
+    /// Number of files verified
+    pub files_scanned: u64,
+    /// Number of degraded files found
+    pub degraded_files: u64,
+    /// Number of unrecoverable files found
+    pub unrecoverable_files: u64,
+    /// Number of shards restored by repairs
+    pub shards_restored: u64,
+    /// Number of repairs or verifications that failed with an error
+    pub failures: u64,
+    /// Completion time of the last pass
+    pub last_pass_at: Option<SystemTime>,
+}
+
+/// Progress events emitted by the scanner
+#[derive(Debug, Clone)]
+pub enum ScanEvent {
+    /// A scan pass started over the given number of files
+    PassStarted { files: usize },
+    /// A file was verified
+    FileVerified(FileHealth),
+    /// A degraded file was repaired
+    FileRepaired(RepairReport),
+    /// Verifying or repairing a file failed
+    FileFailed { file_id: [u8; 32], error: String },
+    /// A scan pass finished; carries the cumulative statistics
+    PassCompleted(ScanStats),
+}
+
+/// Opt-in background task that verifies and repairs catalogued files
+pub struct IntegrityScanner;
+
+impl IntegrityScanner {
+    /// Spawn the scanner on the current tokio runtime
+    ///
+    /// The first pass starts immediately; subsequent passes run every
+    /// `config.interval` until the returned handle is stopped or dropped.
+    /// The pipeline is read-locked per file rather than per pass, so writers
+    /// are only held up while one file is checked.
+    ///
+    /// Not available on wasm32, which has no Tokio runtime; run
+    /// [`Self::scan_pass`] from the host's own scheduler instead.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn<B: StorageBackend + 'static>(
+        pipeline: Arc<tokio::sync::RwLock<StoragePipeline<B>>>,
+        config: ScanConfig,
+    ) -> ScannerHandle {
+        let stats = Arc::new(RwLock::new(ScanStats::default()));
+        let (events, _) = broadcast::channel(256);
+        let (shutdown, mut shutdown_rx) = watch::channel(false);
+
+        let task = {
+            let stats = stats.clone();
+            let events = events.clone();
+            tokio::spawn(async move {
+                loop {
+                    Self::scan_shared(&pipeline, &config, &stats, &events).await;
+
+                    tokio::select! {
+                        _ = tokio::time::sleep(config.interval) => {}
+                        _ = shutdown_rx.changed() => break,
+                    }
+                }
+            })
+        };
+
+        ScannerHandle {
+            stats,
+            events,
+            shutdown,
+            task,
+        }
+    }
+
+    /// Verify every catalogued file once, repairing degraded files if enabled
+    pub async fn scan_pass<B: StorageBackend + 'static>(
+        pipeline: &StoragePipeline<B>,
+        config: &ScanConfig,
+        stats: &RwLock<ScanStats>,
+        events: &broadcast::Sender<ScanEvent>,
+    ) {
+        let files = pipeline.list_files();
+        // Send errors only mean nobody is subscribed
+        let _ = events.send(ScanEvent::PassStarted { files: files.len() });
+        for meta in files {
+            Self::scan_file(pipeline, &meta, config, stats, events).await;
+        }
+        Self::complete_pass(stats, events);
+    }
+
+    /// [`Self::scan_pass`] over a snapshot of the catalogue, locking the
+    /// pipeline for one file at a time
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn scan_shared<B: StorageBackend + 'static>(
+        pipeline: &tokio::sync::RwLock<StoragePipeline<B>>,
+        config: &ScanConfig,
+        stats: &RwLock<ScanStats>,
+        events: &broadcast::Sender<ScanEvent>,
+    ) {
+        let files = pipeline.read().await.list_files();
+        let _ = events.send(ScanEvent::PassStarted { files: files.len() });
+        for meta in files {
+            Self::scan_file(&*pipeline.read().await, &meta, config, stats, events).await;
+            // Let waiting writers take the lock before the next file
+            tokio::task::yield_now().await;
+        }
+        Self::complete_pass(stats, events);
+    }
+
+    /// Verify one file, repairing it if degraded and repair is enabled
+    async fn scan_file<B: StorageBackend + 'static>(
+        pipeline: &StoragePipeline<B>,
+        meta: &FileMetadata,
+        config: &ScanConfig,
+        stats: &RwLock<ScanStats>,
+        events: &broadcast::Sender<ScanEvent>,
+    ) {
+        let health = match pipeline.verify_file(meta).await {
+            Ok(health) => health,
+            Err(e) => {
+                stats.write().failures += 1;
+                let _ = events.send(ScanEvent::FileFailed {
+                    file_id: meta.file_id,
+                    error: e.to_string(),
+                });
+                return;
+            }
+        };
+
+        let status = health.status();
+        {
+            let mut stats = stats.write();
+            stats.files_scanned += 1;
+            match status {
+                HealthStatus::Healthy => {}
+                HealthStatus::Degraded => stats.degraded_files += 1,
+                HealthStatus::Unrecoverable => stats.unrecoverable_files += 1,
+            }
+        }
+        let _ = events.send(ScanEvent::FileVerified(health));
+
+        if !config.repair || status == HealthStatus::Healthy {
+            return;
+        }
+
+        match pipeline.repair_file(meta).await {
+            Ok(report) => {
+                stats.write().shards_restored += report.shards_restored as u64;
+                let _ = events.send(ScanEvent::FileRepaired(report));
+            }
+            Err(e) => {
+                tracing::warn!("Repair of file {} failed: {}", hex::encode(meta.file_id), e);
+                stats.write().failures += 1;
+                let _ = events.send(ScanEvent::FileFailed {
+                    file_id: meta.file_id,
+                    error: e.to_string(),
+                });
+            }
+        }
+    }
+
+    /// Count a finished pass and report the statistics
+    fn complete_pass(stats: &RwLock<ScanStats>, events: &broadcast::Sender<ScanEvent>) {
+        let snapshot = {
+            let mut stats = stats.write();
+            stats.passes += 1;
+            stats.last_pass_at = Some(SystemTime::now());
+            stats.clone()
+        };
+        let _ = events.send(ScanEvent::PassCompleted(snapshot));
+    }
+}
+
+/// Handle to a running integrity scanner
+pub struct ScannerHandle {
+    stats: Arc<RwLock<ScanStats>>,
+    events: broadcast::Sender<ScanEvent>,
+    shutdown: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl ScannerHandle {
+    /// Snapshot of the cumulative scan statistics
+    pub fn stats(&self) -> ScanStats {
+        self.stats.read().clone()
+    }
+
+    /// Subscribe to scan progress events
+    pub fn subscribe(&self) -> broadcast::Receiver<ScanEvent> {
+        self.events.subscribe()
+    }
+
+    /// Stop the scanner, waiting for any in-progress pass to finish
+    pub async fn stop(self) {
+        let _ = self.shutdown.send(true);
+        let _ = self.task.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::storage::{Cid, MemoryStorage};
+
+    fn health(missing: Vec<usize>, corrupted: Vec<usize>) -> ChunkHealth {
+        ChunkHealth {
+            chunk_id: [0u8; 32],
+            total_shards: 6,
+            required_shards: Some(4),
+            missing,
+            corrupted,
+        }
+    }
+
+    #[test]
+    fn test_chunk_health_status() {
+        assert_eq!(health(vec![], vec![]).status(), HealthStatus::Healthy);
+        assert_eq!(health(vec![1], vec![4]).status(), HealthStatus::Degraded);
+        assert_eq!(
+            health(vec![0, 1], vec![2]).status(),
+            HealthStatus::Unrecoverable
+        );
+    }
+
+    #[test]
+    fn test_file_health_reports_worst_chunk() {
+        let file = FileHealth {
+            file_id: [1u8; 32],
+            chunks: vec![health(vec![], vec![]), health(vec![5], vec![])],
+        };
+        assert_eq!(file.status(), HealthStatus::Degraded);
+        assert!(!file.is_healthy());
+    }
+
+    #[tokio::test]
+    async fn test_scanner_repairs_degraded_file() {
+        let config = Config::default()
+            .with_fec_params(4, 2)
+            .with_compression(false, 1);
+        let mut pipeline = StoragePipeline::new(config, MemoryStorage::new())
+            .await
+            .unwrap();
+
+        let data: Vec<u8> = (0..4000u32).map(|i| (i % 251) as u8).collect();
+        let metadata = pipeline.process_file([9u8; 32], &data, None).await.unwrap();
+
+        // Drop a data shard and a parity shard of the first chunk
+        let shard_ids = metadata.chunks[0].shard_ids.clone();
+        for idx in [0, 5] {
+            pipeline
+                .backend()
+                .delete_shard(&Cid::new(shard_ids[idx]))
+                .await
+                .unwrap();
+        }
+
+        let pipeline = Arc::new(tokio::sync::RwLock::new(pipeline));
+        let scanner = IntegrityScanner::spawn(pipeline.clone(), ScanConfig::default());
+        let mut events = scanner.subscribe();
+
+        let stats = loop {
+            if let Ok(ScanEvent::PassCompleted(stats)) = events.recv().await {
+                break stats;
+            }
+        };
+        scanner.stop().await;
+
+        assert_eq!(stats.files_scanned, 1);
+        assert_eq!(stats.degraded_files, 1);
+        assert_eq!(stats.shards_restored, 2);
+
+        let pipeline = pipeline.read().await;
+        assert!(pipeline.verify_file(&metadata).await.unwrap().is_healthy());
+        assert_eq!(pipeline.retrieve_file(&metadata).await.unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_scanner_lets_writers_in_between_files() {
+        let config = Config::default().with_fec_params(4, 2);
+        let mut pipeline = StoragePipeline::new(config, MemoryStorage::new())
+            .await
+            .unwrap();
+        for i in 0..3u8 {
+            pipeline
+                .process_file([i; 32], &[i; 1000], None)
+                .await
+                .unwrap();
+        }
+
+        let pipeline = Arc::new(tokio::sync::RwLock::new(pipeline));
+        let scanner = IntegrityScanner::spawn(pipeline.clone(), ScanConfig::default());
+        let mut events = scanner.subscribe();
+        while !matches!(events.recv().await, Ok(ScanEvent::FileVerified(_))) {}
+
+        // The write lock is granted before the pass over the rest completes
+        pipeline
+            .write()
+            .await
+            .process_file([9u8; 32], b"written mid-pass", None)
+            .await
+            .unwrap();
+        assert_eq!(scanner.stats().passes, 0);
+        scanner.stop().await;
+    }
+}
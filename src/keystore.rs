@@ -0,0 +1,729 @@
+//! Key storage
+//!
+//! [`Keystore`] is where a pipeline keeps the 32-byte secrets it needs, such
+//! as the convergence secret for ConvergentWithSecret mode. Implementations
+//! range from [`MemoryKeystore`] for tests, through the passphrase-encrypted
+//! [`FileKeystore`], to the OS keychain with the `keychain` feature and a
+//! hardware security module with the `pkcs11` feature.
+//!
+//! RandomKey encryption encapsulates a fresh secret to an ML-KEM public key,
+//! and HPKE mode to an X25519 one. [`KemKeyStore`] keeps the matching private
+//! keys, indexed by the BLAKE3 hash of the public key, so the secret can be
+//! recovered later.
+//!
+//! Both file stores are encrypted with ChaCha20Poly1305 under a key derived
+//! from the passphrase with PBKDF2, and rewritten atomically on every change.
+
+use generic_array::GenericArray;
+use parking_lot::RwLock;
+use rand::RngCore;
+use saorsa_pqc::api::{
+    kdf::helpers::derive_key_from_password,
+    kem::{MlKem, MlKemCiphertext, MlKemSecretKey, MlKemSharedSecret},
+    symmetric::ChaCha20Poly1305,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::quantum_crypto::{hybrid_shared_key, SecurityLevel};
+
+#[cfg(feature = "pkcs11")]
+mod pkcs11;
+#[cfg(feature = "pkcs11")]
+pub use pkcs11::Pkcs11Keystore;
+
+/// Errors from keystores and the secret registry
+#[derive(Debug, Error)]
+pub enum KeystoreError {
+    #[error("Failed to access {}: {error}", path.display())]
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+
+    #[error("{0} is malformed")]
+    Malformed(&'static str),
+
+    #[error("Unsupported keystore version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Wrong passphrase or corrupted keystore")]
+    WrongPassphrase,
+
+    #[error("Key derivation failed: {0}")]
+    KeyDerivation(String),
+
+    #[error("{0} PBKDF2 iterations outside the accepted range {MIN_KDF_ITERATIONS}..={MAX_KDF_ITERATIONS}")]
+    IterationsOutOfRange(u32),
+
+    #[error("Keystore encryption failed: {0}")]
+    Encryption(String),
+
+    #[error("No decapsulation key {0} in keystore")]
+    KeyNotFound(String),
+
+    #[error("Key {0} is not a hybrid key")]
+    NotHybrid(String),
+
+    #[error("KEM operation failed: {0}")]
+    Kem(String),
+
+    #[error("Keychain entry {id}: {message}")]
+    Keychain { id: String, message: String },
+
+    #[error("Cannot load PKCS#11 module: {0}")]
+    Pkcs11Module(String),
+
+    #[error("PKCS#11 {function} failed with {code:#x}")]
+    Pkcs11 { function: &'static str, code: u64 },
+
+    #[error("Unknown convergence secret {0}")]
+    UnknownSecret(String),
+}
+
+pub type Result<T> = std::result::Result<T, KeystoreError>;
+
+/// Wrap an IO failure on the file at `path`
+pub(crate) fn io_error(path: &Path) -> impl FnOnce(std::io::Error) -> KeystoreError + '_ {
+    move |error| KeystoreError::Io {
+        path: path.to_path_buf(),
+        error,
+    }
+}
+
+/// Identifier of a keypair: BLAKE3 hash of its public key
+pub type KemKeyId = [u8; 32];
+
+/// PBKDF2 rounds used for newly created stores
+pub const DEFAULT_KDF_ITERATIONS: u32 = 600_000;
+/// Fewest PBKDF2 rounds a store may be created or opened with
+pub const MIN_KDF_ITERATIONS: u32 = 1_000;
+/// Most PBKDF2 rounds a store may be created or opened with, so a tampered
+/// file cannot stall the process
+pub const MAX_KDF_ITERATIONS: u32 = 10_000_000;
+
+const KEYSTORE_VERSION: u8 = 1;
+
+/// On-disk envelope; only `ciphertext` holds key material
+#[derive(Serialize, Deserialize)]
+struct KeyStoreFile {
+    version: u8,
+    iterations: u32,
+    salt: [u8; 16],
+    nonce: [u8; 12],
+    ciphertext: Vec<u8>,
+}
+
+/// Secret under which a pipeline keeps its convergence secret
+pub const CONVERGENCE_SECRET_ID: &str = "convergence-secret";
+
+/// Storage for named 32-byte secrets
+pub trait Keystore: Send + Sync {
+    /// Look up a secret, returning `None` if it has never been stored
+    fn get_secret(&self, id: &str) -> Result<Option<Zeroizing<[u8; 32]>>>;
+
+    /// Store a secret, replacing any previous value
+    fn put_secret(&self, id: &str, secret: &[u8; 32]) -> Result<()>;
+
+    /// Remove a secret, returning whether it existed
+    fn delete_secret(&self, id: &str) -> Result<bool>;
+
+    /// Look up a secret, generating and storing a random one if missing
+    fn get_or_create_secret(&self, id: &str) -> Result<Zeroizing<[u8; 32]>> {
+        if let Some(secret) = self.get_secret(id)? {
+            return Ok(secret);
+        }
+        let mut secret = Zeroizing::new([0u8; 32]);
+        rand::thread_rng().fill_bytes(&mut secret[..]);
+        self.put_secret(id, &secret)?;
+        Ok(secret)
+    }
+}
+
+/// Lets one keystore back both a pipeline and a crypto engine's master key;
+/// every call is forwarded
+impl<K: Keystore + ?Sized> Keystore for std::sync::Arc<K> {
+    fn get_secret(&self, id: &str) -> Result<Option<Zeroizing<[u8; 32]>>> {
+        (**self).get_secret(id)
+    }
+
+    fn put_secret(&self, id: &str, secret: &[u8; 32]) -> Result<()> {
+        (**self).put_secret(id, secret)
+    }
+
+    fn delete_secret(&self, id: &str) -> Result<bool> {
+        (**self).delete_secret(id)
+    }
+
+    fn get_or_create_secret(&self, id: &str) -> Result<Zeroizing<[u8; 32]>> {
+        (**self).get_or_create_secret(id)
+    }
+}
+
+/// Keystore that forgets everything when dropped
+#[derive(Default)]
+pub struct MemoryKeystore {
+    secrets: RwLock<HashMap<String, Zeroizing<[u8; 32]>>>,
+}
+
+impl MemoryKeystore {
+    /// Create an empty keystore
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Keystore for MemoryKeystore {
+    fn get_secret(&self, id: &str) -> Result<Option<Zeroizing<[u8; 32]>>> {
+        Ok(self.secrets.read().get(id).cloned())
+    }
+
+    fn put_secret(&self, id: &str, secret: &[u8; 32]) -> Result<()> {
+        self.secrets
+            .write()
+            .insert(id.to_string(), Zeroizing::new(*secret));
+        Ok(())
+    }
+
+    fn delete_secret(&self, id: &str) -> Result<bool> {
+        Ok(self.secrets.write().remove(id).is_some())
+    }
+}
+
+/// Keystore kept in a passphrase-encrypted file
+pub struct FileKeystore {
+    vault: Vault,
+    secrets: RwLock<SecretEntries>,
+}
+
+/// Decrypted content of a [`FileKeystore`]
+#[derive(Default, Serialize, Deserialize)]
+struct SecretEntries {
+    secrets: HashMap<String, [u8; 32]>,
+}
+
+impl Drop for SecretEntries {
+    fn drop(&mut self) {
+        self.secrets.values_mut().for_each(Zeroize::zeroize);
+    }
+}
+
+impl FileKeystore {
+    /// Create an empty keystore at `path`, replacing any existing file
+    pub fn create(path: impl Into<PathBuf>, passphrase: &str) -> Result<Self> {
+        Self::create_with_iterations(path, passphrase, DEFAULT_KDF_ITERATIONS)
+    }
+
+    /// Create an empty keystore using `iterations` PBKDF2 rounds
+    pub fn create_with_iterations(
+        path: impl Into<PathBuf>,
+        passphrase: &str,
+        iterations: u32,
+    ) -> Result<Self> {
+        let vault = Vault::create(path.into(), passphrase, iterations)?;
+        let secrets = SecretEntries::default();
+        vault.save(&secrets)?;
+        Ok(Self {
+            vault,
+            secrets: RwLock::new(secrets),
+        })
+    }
+
+    /// Open and decrypt an existing keystore
+    pub fn open(path: impl Into<PathBuf>, passphrase: &str) -> Result<Self> {
+        let (vault, secrets) = Vault::open(path.into(), passphrase)?;
+        Ok(Self {
+            vault,
+            secrets: RwLock::new(secrets),
+        })
+    }
+
+    /// Path of the backing file
+    pub fn path(&self) -> &Path {
+        &self.vault.path
+    }
+}
+
+impl Keystore for FileKeystore {
+    fn get_secret(&self, id: &str) -> Result<Option<Zeroizing<[u8; 32]>>> {
+        Ok(self
+            .secrets
+            .read()
+            .secrets
+            .get(id)
+            .copied()
+            .map(Zeroizing::new))
+    }
+
+    fn put_secret(&self, id: &str, secret: &[u8; 32]) -> Result<()> {
+        let mut entries = self.secrets.write();
+        entries.secrets.insert(id.to_string(), *secret);
+        self.vault.save(&*entries)
+    }
+
+    fn delete_secret(&self, id: &str) -> Result<bool> {
+        let mut entries = self.secrets.write();
+        let removed = entries.secrets.remove(id).is_some();
+        if removed {
+            self.vault.save(&*entries)?;
+        }
+        Ok(removed)
+    }
+}
+
+/// Keystore backed by the platform keychain
+///
+/// Uses the kernel keyring on Linux, Keychain on macOS and the Credential
+/// Manager on Windows. Secrets are stored under `service`, one entry per id.
+#[cfg(feature = "keychain")]
+pub struct KeychainKeystore {
+    service: String,
+}
+
+#[cfg(feature = "keychain")]
+impl KeychainKeystore {
+    /// Use keychain entries belonging to `service`
+    pub fn new(service: impl Into<String>) -> Self {
+        Self {
+            service: service.into(),
+        }
+    }
+
+    fn entry(&self, id: &str) -> Result<keyring::Entry> {
+        keyring::Entry::new(&self.service, id).map_err(|e| keychain_error(id, e))
+    }
+}
+
+#[cfg(feature = "keychain")]
+impl Keystore for KeychainKeystore {
+    fn get_secret(&self, id: &str) -> Result<Option<Zeroizing<[u8; 32]>>> {
+        let bytes = match self.entry(id)?.get_secret() {
+            Ok(bytes) => Zeroizing::new(bytes),
+            Err(keyring::Error::NoEntry) => return Ok(None),
+            Err(e) => return Err(keychain_error(id, e)),
+        };
+        let secret: [u8; 32] =
+            bytes
+                .as_slice()
+                .try_into()
+                .map_err(|_| KeystoreError::Keychain {
+                    id: id.to_string(),
+                    message: "not a 32-byte secret".to_string(),
+                })?;
+        Ok(Some(Zeroizing::new(secret)))
+    }
+
+    fn put_secret(&self, id: &str, secret: &[u8; 32]) -> Result<()> {
+        self.entry(id)?
+            .set_secret(secret)
+            .map_err(|e| keychain_error(id, e))
+    }
+
+    fn delete_secret(&self, id: &str) -> Result<bool> {
+        match self.entry(id)?.delete_credential() {
+            Ok(()) => Ok(true),
+            Err(keyring::Error::NoEntry) => Ok(false),
+            Err(e) => Err(keychain_error(id, e)),
+        }
+    }
+}
+
+#[cfg(feature = "keychain")]
+fn keychain_error(id: &str, e: keyring::Error) -> KeystoreError {
+    KeystoreError::Keychain {
+        id: id.to_string(),
+        message: e.to_string(),
+    }
+}
+
+/// Decrypted content of a [`KemKeyStore`]
+#[derive(Default, Serialize, Deserialize)]
+struct KeyEntries {
+    keys: HashMap<String, StoredKey>,
+}
+
+#[derive(Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+struct StoredKey {
+    public_key: Vec<u8>,
+    secret_key: Vec<u8>,
+    /// Stores written before levels were recorded only hold ML-KEM-768 keys
+    #[serde(default)]
+    #[zeroize(skip)]
+    security_level: SecurityLevel,
+    /// X25519 half of a hybrid keypair, whose public key then follows the
+    /// ML-KEM one in `public_key`
+    #[serde(default)]
+    x25519_secret: Option<[u8; 32]>,
+}
+
+/// Encrypted file of ML-KEM, hybrid and HPKE keypairs
+pub struct KemKeyStore {
+    vault: Vault,
+    entries: KeyEntries,
+}
+
+impl KemKeyStore {
+    /// Create an empty store at `path`, replacing any existing file
+    pub fn create(path: impl Into<PathBuf>, passphrase: &str) -> Result<Self> {
+        Self::create_with_iterations(path, passphrase, DEFAULT_KDF_ITERATIONS)
+    }
+
+    /// Create an empty store using `iterations` PBKDF2 rounds
+    pub fn create_with_iterations(
+        path: impl Into<PathBuf>,
+        passphrase: &str,
+        iterations: u32,
+    ) -> Result<Self> {
+        let store = Self {
+            vault: Vault::create(path.into(), passphrase, iterations)?,
+            entries: KeyEntries::default(),
+        };
+        store.save()?;
+        Ok(store)
+    }
+
+    /// Open and decrypt an existing store
+    pub fn open(path: impl Into<PathBuf>, passphrase: &str) -> Result<Self> {
+        let (vault, entries) = Vault::open(path.into(), passphrase)?;
+        Ok(Self { vault, entries })
+    }
+
+    /// Path of the backing file
+    pub fn path(&self) -> &Path {
+        &self.vault.path
+    }
+
+    /// Generate and persist a new ML-KEM-768 keypair, returning its id
+    pub fn generate(&mut self) -> Result<KemKeyId> {
+        self.generate_with_level(SecurityLevel::default())
+    }
+
+    /// Generate and persist a keypair for `level`, returning its id
+    pub fn generate_with_level(&mut self, level: SecurityLevel) -> Result<KemKeyId> {
+        let (public_key, secret_key) = MlKem::new(level.kem_variant())
+            .generate_keypair()
+            .map_err(|e| KeystoreError::Kem(format!("keypair generation failed: {:?}", e)))?;
+        let public_key = public_key.to_bytes();
+        let key_id = *blake3::hash(&public_key).as_bytes();
+
+        self.entries.keys.insert(
+            hex::encode(key_id),
+            StoredKey {
+                public_key,
+                secret_key: secret_key.to_bytes(),
+                security_level: level,
+                x25519_secret: None,
+            },
+        );
+        self.save()?;
+        Ok(key_id)
+    }
+
+    /// Generate and persist a hybrid X25519+ML-KEM keypair for `level`
+    ///
+    /// Its public key is the ML-KEM public key followed by the 32-byte X25519
+    /// public key.
+    pub fn generate_hybrid(&mut self, level: SecurityLevel) -> Result<KemKeyId> {
+        let (public_key, secret_key) = MlKem::new(level.kem_variant())
+            .generate_keypair()
+            .map_err(|e| KeystoreError::Kem(format!("keypair generation failed: {:?}", e)))?;
+        let x25519_secret = x25519_dalek::StaticSecret::random_from_rng(rand::rngs::OsRng);
+        let mut public_key = public_key.to_bytes();
+        public_key.extend_from_slice(x25519_dalek::PublicKey::from(&x25519_secret).as_bytes());
+        let key_id = *blake3::hash(&public_key).as_bytes();
+
+        self.entries.keys.insert(
+            hex::encode(key_id),
+            StoredKey {
+                public_key,
+                secret_key: secret_key.to_bytes(),
+                security_level: level,
+                x25519_secret: Some(x25519_secret.to_bytes()),
+            },
+        );
+        self.save()?;
+        Ok(key_id)
+    }
+
+    /// Generate and persist an X25519 keypair for RFC 9180 HPKE
+    ///
+    /// Its public key is the 32-byte X25519 public key that
+    /// [`QuantumCryptoEngine::encrypt_hpke`](crate::quantum_crypto::QuantumCryptoEngine::encrypt_hpke)
+    /// encrypts to.
+    pub fn generate_hpke(&mut self) -> Result<KemKeyId> {
+        let x25519_secret = x25519_dalek::StaticSecret::random_from_rng(rand::rngs::OsRng);
+        let public_key = x25519_dalek::PublicKey::from(&x25519_secret)
+            .to_bytes()
+            .to_vec();
+        let key_id = *blake3::hash(&public_key).as_bytes();
+
+        self.entries.keys.insert(
+            hex::encode(key_id),
+            StoredKey {
+                public_key,
+                secret_key: Vec::new(),
+                security_level: SecurityLevel::default(),
+                x25519_secret: Some(x25519_secret.to_bytes()),
+            },
+        );
+        self.save()?;
+        Ok(key_id)
+    }
+
+    /// Public key of a stored keypair
+    pub fn public_key(&self, key_id: &KemKeyId) -> Option<&[u8]> {
+        self.entries
+            .keys
+            .get(&hex::encode(key_id))
+            .map(|key| key.public_key.as_slice())
+    }
+
+    /// Ids of every stored keypair
+    pub fn key_ids(&self) -> Vec<KemKeyId> {
+        self.entries
+            .keys
+            .keys()
+            .filter_map(|id| hex::decode(id).ok()?.try_into().ok())
+            .collect()
+    }
+
+    /// Delete a keypair; anything encrypted to it becomes unrecoverable
+    pub fn remove(&mut self, key_id: &KemKeyId) -> Result<bool> {
+        let removed = self.entries.keys.remove(&hex::encode(key_id)).is_some();
+        if removed {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    /// Recover the shared secret encapsulated to `key_id`
+    pub fn decapsulate(&self, key_id: &KemKeyId, encapsulated: &[u8]) -> Result<MlKemSharedSecret> {
+        Self::decapsulate_kem(self.stored(key_id)?, encapsulated)
+    }
+
+    /// Recover the content key encapsulated to the hybrid keypair `key_id`
+    pub fn decapsulate_hybrid(
+        &self,
+        key_id: &KemKeyId,
+        encapsulated: &[u8],
+    ) -> Result<Zeroizing<[u8; 32]>> {
+        let stored = self.stored(key_id)?;
+        let x25519_secret = stored
+            .x25519_secret
+            .ok_or_else(|| KeystoreError::NotHybrid(hex::encode(key_id)))?;
+        let (kem_ciphertext, ephemeral_public) = encapsulated
+            .split_last_chunk::<32>()
+            .ok_or_else(|| KeystoreError::Kem("hybrid encapsulation too short".to_string()))?;
+
+        let kem_secret = Self::decapsulate_kem(stored, kem_ciphertext)?;
+        let x25519_shared = x25519_dalek::StaticSecret::from(x25519_secret)
+            .diffie_hellman(&x25519_dalek::PublicKey::from(*ephemeral_public));
+        hybrid_shared_key(
+            x25519_shared.as_bytes(),
+            &kem_secret.to_bytes(),
+            encapsulated,
+        )
+        .map_err(|e| KeystoreError::Kem(e.to_string()))
+    }
+
+    fn decapsulate_kem(stored: &StoredKey, encapsulated: &[u8]) -> Result<MlKemSharedSecret> {
+        let variant = stored.security_level.kem_variant();
+        let secret_key = MlKemSecretKey::from_bytes(variant, &stored.secret_key)
+            .map_err(|e| KeystoreError::Kem(format!("stored secret key is invalid: {:?}", e)))?;
+        let ciphertext = MlKemCiphertext::from_bytes(variant, encapsulated)
+            .map_err(|e| KeystoreError::Kem(format!("encapsulated secret is invalid: {:?}", e)))?;
+        MlKem::new(variant)
+            .decapsulate(&secret_key, &ciphertext)
+            .map_err(|e| KeystoreError::Kem(format!("decapsulation failed: {:?}", e)))
+    }
+
+    /// X25519 secret of `key_id`, if it is an HPKE or hybrid keypair
+    pub(crate) fn x25519_secret(&self, key_id: &KemKeyId) -> Result<Option<Zeroizing<[u8; 32]>>> {
+        Ok(self.stored(key_id)?.x25519_secret.map(Zeroizing::new))
+    }
+
+    fn stored(&self, key_id: &KemKeyId) -> Result<&StoredKey> {
+        self.entries
+            .keys
+            .get(&hex::encode(key_id))
+            .ok_or_else(|| KeystoreError::KeyNotFound(hex::encode(key_id)))
+    }
+
+    fn save(&self) -> Result<()> {
+        self.vault.save(&self.entries)
+    }
+}
+
+/// Passphrase-derived key and location of an encrypted store file
+struct Vault {
+    path: PathBuf,
+    key: Zeroizing<[u8; 32]>,
+    iterations: u32,
+    salt: [u8; 16],
+}
+
+impl Vault {
+    fn create(path: PathBuf, passphrase: &str, iterations: u32) -> Result<Self> {
+        check_iterations(iterations)?;
+        let mut salt = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut salt);
+
+        Ok(Self {
+            path,
+            key: derive_store_key(passphrase, &salt, iterations)?,
+            iterations,
+            salt,
+        })
+    }
+
+    fn open<T: serde::de::DeserializeOwned>(path: PathBuf, passphrase: &str) -> Result<(Self, T)> {
+        let bytes = std::fs::read(&path).map_err(io_error(&path))?;
+        let file: KeyStoreFile = serde_json::from_slice(&bytes)
+            .map_err(|_| KeystoreError::Malformed("Keystore file"))?;
+        if file.version != KEYSTORE_VERSION {
+            return Err(KeystoreError::UnsupportedVersion(file.version));
+        }
+        check_iterations(file.iterations)?;
+
+        let key = derive_store_key(passphrase, &file.salt, file.iterations)?;
+        let cipher = ChaCha20Poly1305::new(GenericArray::from_slice(&key[..]));
+        let plaintext = Zeroizing::new(
+            cipher
+                .decrypt(GenericArray::from_slice(&file.nonce), &file.ciphertext)
+                .map_err(|_| KeystoreError::WrongPassphrase)?,
+        );
+        let content = serde_json::from_slice(&plaintext)
+            .map_err(|_| KeystoreError::Malformed("Keystore content"))?;
+
+        let vault = Self {
+            path,
+            key,
+            iterations: file.iterations,
+            salt: file.salt,
+        };
+        Ok((vault, content))
+    }
+
+    /// Encrypt `content` under a fresh nonce and atomically replace the file
+    fn save<T: Serialize>(&self, content: &T) -> Result<()> {
+        let plaintext = Zeroizing::new(
+            serde_json::to_vec(content).map_err(|e| KeystoreError::Encryption(e.to_string()))?,
+        );
+        let mut nonce = [0u8; 12];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let cipher = ChaCha20Poly1305::new(GenericArray::from_slice(&self.key[..]));
+        let ciphertext = cipher
+            .encrypt(GenericArray::from_slice(&nonce), &plaintext)
+            .map_err(|e| KeystoreError::Encryption(format!("{:?}", e)))?;
+
+        let file = KeyStoreFile {
+            version: KEYSTORE_VERSION,
+            iterations: self.iterations,
+            salt: self.salt,
+            nonce,
+            ciphertext,
+        };
+        let temp = self.path.with_extension("tmp");
+        let bytes =
+            serde_json::to_vec(&file).map_err(|e| KeystoreError::Encryption(e.to_string()))?;
+        write_private(&temp, &bytes).map_err(io_error(&temp))?;
+        std::fs::rename(&temp, &self.path).map_err(io_error(&self.path))
+    }
+}
+
+fn check_iterations(iterations: u32) -> Result<()> {
+    if (MIN_KDF_ITERATIONS..=MAX_KDF_ITERATIONS).contains(&iterations) {
+        Ok(())
+    } else {
+        Err(KeystoreError::IterationsOutOfRange(iterations))
+    }
+}
+
+/// Write `bytes` to a new file readable only by its owner, and sync it
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()
+}
+
+fn derive_store_key(passphrase: &str, salt: &[u8], iterations: u32) -> Result<Zeroizing<[u8; 32]>> {
+    derive_key_from_password(passphrase.as_bytes(), salt, iterations)
+        .map_err(|e| KeystoreError::KeyDerivation(format!("{:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_keystore_persists_keys() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("keys.json");
+
+        let mut store = KemKeyStore::create_with_iterations(&path, "hunter2", 1_000)?;
+        let key_id = store.generate()?;
+        assert_eq!(store.key_ids(), vec![key_id]);
+
+        let reopened = KemKeyStore::open(&path, "hunter2")?;
+        assert_eq!(reopened.public_key(&key_id), store.public_key(&key_id));
+        assert!(KemKeyStore::open(&path, "wrong").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_keystore_file_rejects_tampered_iterations() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("secrets.json");
+        FileKeystore::create_with_iterations(&path, "hunter2", 1_000)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path)?.permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        for iterations in [1, u32::MAX] {
+            let mut file: KeyStoreFile = serde_json::from_slice(&std::fs::read(&path)?)?;
+            file.iterations = iterations;
+            std::fs::write(&path, serde_json::to_vec(&file)?)?;
+            assert!(matches!(
+                FileKeystore::open(&path, "hunter2"),
+                Err(KeystoreError::IterationsOutOfRange(n)) if n == iterations
+            ));
+        }
+        assert!(FileKeystore::create_with_iterations(&path, "hunter2", 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_keystore_round_trip() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("secrets.json");
+
+        let store = FileKeystore::create_with_iterations(&path, "hunter2", 1_000)?;
+        assert!(store.get_secret(CONVERGENCE_SECRET_ID)?.is_none());
+        let secret = store.get_or_create_secret(CONVERGENCE_SECRET_ID)?;
+
+        let reopened = FileKeystore::open(&path, "hunter2")?;
+        assert_eq!(
+            reopened.get_or_create_secret(CONVERGENCE_SECRET_ID)?,
+            secret
+        );
+        assert!(reopened.delete_secret(CONVERGENCE_SECRET_ID)?);
+        assert!(FileKeystore::open(&path, "hunter2")?
+            .get_secret(CONVERGENCE_SECRET_ID)?
+            .is_none());
+        Ok(())
+    }
+}
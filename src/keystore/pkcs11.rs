@@ -0,0 +1,663 @@
+//! Keystore on a PKCS#11 token
+//!
+//! [`Pkcs11Keystore`] keeps secrets on a hardware security module or smart
+//! card through the vendor's PKCS#11 module, loaded at runtime. Each secret
+//! is a private token data object with application `saorsa-fec` and the
+//! secret's ID as label, readable only after logging in with the user PIN.
+//!
+//! Only the part of the PKCS#11 2.40 C API the keystore calls is declared
+//! here, following `pkcs11t.h` and `pkcs11f.h`.
+
+use libloading::Library;
+use parking_lot::Mutex;
+use std::ffi::c_void;
+use std::os::raw::c_ulong;
+use std::path::Path;
+use std::ptr;
+use zeroize::Zeroizing;
+
+use super::{Keystore, KeystoreError, Result};
+
+type CkUlong = c_ulong;
+type CkRv = CkUlong;
+type CkSessionHandle = CkUlong;
+type CkObjectHandle = CkUlong;
+
+const CKR_OK: CkRv = 0x000;
+const CKR_USER_ALREADY_LOGGED_IN: CkRv = 0x100;
+const CKR_CRYPTOKI_ALREADY_INITIALIZED: CkRv = 0x191;
+const CK_INVALID_HANDLE: CkUlong = 0;
+const CKF_OS_LOCKING_OK: CkUlong = 0x02;
+const CKF_RW_SESSION: CkUlong = 0x02;
+const CKF_SERIAL_SESSION: CkUlong = 0x04;
+const CKU_USER: CkUlong = 1;
+const CKO_DATA: CkUlong = 0x00;
+const CKA_CLASS: CkUlong = 0x00;
+const CKA_TOKEN: CkUlong = 0x01;
+const CKA_PRIVATE: CkUlong = 0x02;
+const CKA_LABEL: CkUlong = 0x03;
+const CKA_APPLICATION: CkUlong = 0x10;
+const CKA_VALUE: CkUlong = 0x11;
+const CK_TRUE: u8 = 1;
+
+/// `CKA_APPLICATION` of the objects holding secrets
+const APPLICATION: &[u8] = b"saorsa-fec";
+
+/// Objects fetched per `C_FindObjects` call
+const FIND_BATCH: usize = 16;
+
+#[repr(C)]
+#[cfg_attr(windows, repr(packed))]
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+struct CkVersion {
+    major: u8,
+    minor: u8,
+}
+
+#[repr(C)]
+#[cfg_attr(windows, repr(packed))]
+struct CkAttribute {
+    kind: CkUlong,
+    value: *mut c_void,
+    len: CkUlong,
+}
+
+#[repr(C)]
+#[cfg_attr(windows, repr(packed))]
+struct CkInitializeArgs {
+    create_mutex: *const c_void,
+    destroy_mutex: *const c_void,
+    lock_mutex: *const c_void,
+    unlock_mutex: *const c_void,
+    flags: CkUlong,
+    reserved: *mut c_void,
+}
+
+type Initialize = unsafe extern "C" fn(*mut c_void) -> CkRv;
+type OpenSession = unsafe extern "C" fn(
+    CkUlong,
+    CkUlong,
+    *mut c_void,
+    *const c_void,
+    *mut CkSessionHandle,
+) -> CkRv;
+type CloseSession = unsafe extern "C" fn(CkSessionHandle) -> CkRv;
+type Login = unsafe extern "C" fn(CkSessionHandle, CkUlong, *const u8, CkUlong) -> CkRv;
+type CreateObject =
+    unsafe extern "C" fn(CkSessionHandle, *mut CkAttribute, CkUlong, *mut CkObjectHandle) -> CkRv;
+type DestroyObject = unsafe extern "C" fn(CkSessionHandle, CkObjectHandle) -> CkRv;
+type GetAttributeValue =
+    unsafe extern "C" fn(CkSessionHandle, CkObjectHandle, *mut CkAttribute, CkUlong) -> CkRv;
+type FindObjectsInit = unsafe extern "C" fn(CkSessionHandle, *mut CkAttribute, CkUlong) -> CkRv;
+type FindObjects =
+    unsafe extern "C" fn(CkSessionHandle, *mut CkObjectHandle, CkUlong, *mut CkUlong) -> CkRv;
+type GetFunctionList = unsafe extern "C" fn(*mut *const FunctionList) -> CkRv;
+
+/// Leading entries of `CK_FUNCTION_LIST`, up to `C_FindObjectsFinal`
+///
+/// The list only ever arrives by pointer, so the entries after these are
+/// left out. Functions the keystore does not call are untyped.
+#[repr(C)]
+#[cfg_attr(windows, repr(packed))]
+#[allow(dead_code)]
+struct FunctionList {
+    version: CkVersion,
+    initialize: Option<Initialize>,
+    finalize: Option<Initialize>,
+    get_info: *const c_void,
+    get_function_list: *const c_void,
+    get_slot_list: *const c_void,
+    get_slot_info: *const c_void,
+    get_token_info: *const c_void,
+    get_mechanism_list: *const c_void,
+    get_mechanism_info: *const c_void,
+    init_token: *const c_void,
+    init_pin: *const c_void,
+    set_pin: *const c_void,
+    open_session: Option<OpenSession>,
+    close_session: Option<CloseSession>,
+    close_all_sessions: *const c_void,
+    get_session_info: *const c_void,
+    get_operation_state: *const c_void,
+    set_operation_state: *const c_void,
+    login: Option<Login>,
+    logout: *const c_void,
+    create_object: Option<CreateObject>,
+    copy_object: *const c_void,
+    destroy_object: Option<DestroyObject>,
+    get_object_size: *const c_void,
+    get_attribute_value: Option<GetAttributeValue>,
+    set_attribute_value: *const c_void,
+    find_objects_init: Option<FindObjectsInit>,
+    find_objects: Option<FindObjects>,
+    find_objects_final: Option<CloseSession>,
+}
+
+/// Keystore keeping secrets as private data objects on a PKCS#11 token
+pub struct Pkcs11Keystore {
+    functions: *const FunctionList,
+    /// The one session, locked for every call into the module; PKCS#11
+    /// sessions are not safe for concurrent use, and the lock is what makes
+    /// the keystore `Sync`
+    session: Mutex<CkSessionHandle>,
+    /// Whether this keystore initialized the module and must finalize it
+    finalize: bool,
+    /// Keeps the module loaded; dropped after the session is closed
+    _library: Option<Library>,
+}
+
+// SAFETY: the raw pointer is what keeps the keystore from being `Send` and
+// `Sync`. It points at the module's function list, which PKCS#11 requires to
+// stay valid and unchanged while the module is loaded, and `_library` keeps
+// the module loaded until after `Drop` has finished with it. PKCS#11 lets any
+// thread call into a module: `C_Initialize` here passes `CKF_OS_LOCKING_OK`,
+// so the module locks its own state. A module some other component initialized
+// without locking still allows calls from any thread, as long as they are not
+// simultaneous. Construction finishes before the keystore can be shared, every
+// later call holds the `session` mutex and `Drop` has exclusive access, so the
+// keystore's calls never overlap.
+unsafe impl Send for Pkcs11Keystore {}
+unsafe impl Sync for Pkcs11Keystore {}
+
+impl Pkcs11Keystore {
+    /// Load the PKCS#11 module at `module` and log in to the token in
+    /// `slot` with the user `pin`
+    pub fn open(module: impl AsRef<Path>, slot: u64, pin: &str) -> Result<Self> {
+        let module = module.as_ref();
+        let module_error = |e: libloading::Error| {
+            KeystoreError::Pkcs11Module(format!("{}: {}", module.display(), e))
+        };
+        // Loading runs the module's initializers; it is trusted like any
+        // other HSM driver
+        let library = unsafe { Library::new(module) }.map_err(module_error)?;
+        let mut functions = ptr::null();
+        unsafe {
+            let get_function_list = library
+                .get::<GetFunctionList>(b"C_GetFunctionList\0")
+                .map_err(module_error)?;
+            check("C_GetFunctionList", get_function_list(&mut functions))?;
+        }
+        if functions.is_null() {
+            return Err(KeystoreError::Pkcs11Module(format!(
+                "{}: no function list",
+                module.display()
+            )));
+        }
+        unsafe { Self::with_functions(Some(library), functions, slot, pin) }
+    }
+
+    /// # Safety
+    /// `functions` must point to a function list that stays valid, and
+    /// callable from any thread one call at a time, for the life of the
+    /// keystore
+    unsafe fn with_functions(
+        library: Option<Library>,
+        functions: *const FunctionList,
+        slot: u64,
+        pin: &str,
+    ) -> Result<Self> {
+        let list = &*functions;
+        let mut args = CkInitializeArgs {
+            create_mutex: ptr::null(),
+            destroy_mutex: ptr::null(),
+            lock_mutex: ptr::null(),
+            unlock_mutex: ptr::null(),
+            flags: CKF_OS_LOCKING_OK,
+            reserved: ptr::null_mut(),
+        };
+        let finalize =
+            match function("C_Initialize", list.initialize)?(ptr::addr_of_mut!(args).cast()) {
+                CKR_OK => true,
+                CKR_CRYPTOKI_ALREADY_INITIALIZED => false,
+                code => return Err(pkcs11_error("C_Initialize", code)),
+            };
+        // From here on, dropping the keystore closes and finalizes
+        let keystore = Self {
+            functions,
+            session: Mutex::new(CK_INVALID_HANDLE),
+            finalize,
+            _library: library,
+        };
+
+        let mut session = CK_INVALID_HANDLE;
+        check(
+            "C_OpenSession",
+            function("C_OpenSession", list.open_session)?(
+                slot as CkUlong,
+                CKF_SERIAL_SESSION | CKF_RW_SESSION,
+                ptr::null_mut(),
+                ptr::null(),
+                &mut session,
+            ),
+        )?;
+        *keystore.session.lock() = session;
+
+        match function("C_Login", list.login)?(
+            session,
+            CKU_USER,
+            pin.as_ptr(),
+            pin.len() as CkUlong,
+        ) {
+            CKR_OK | CKR_USER_ALREADY_LOGGED_IN => Ok(keystore),
+            code => Err(pkcs11_error("C_Login", code)),
+        }
+    }
+
+    fn list(&self) -> &FunctionList {
+        // Valid for the life of the keystore, see `with_functions`
+        unsafe { &*self.functions }
+    }
+
+    /// Handles of the objects holding secret `id`
+    fn find(&self, session: CkSessionHandle, id: &str) -> Result<Vec<CkObjectHandle>> {
+        let list = self.list();
+        let class = CKO_DATA;
+        let mut template = [
+            ulong_attribute(CKA_CLASS, &class),
+            bytes_attribute(CKA_TOKEN, &[CK_TRUE]),
+            bytes_attribute(CKA_APPLICATION, APPLICATION),
+            bytes_attribute(CKA_LABEL, id.as_bytes()),
+        ];
+        unsafe {
+            check(
+                "C_FindObjectsInit",
+                function("C_FindObjectsInit", list.find_objects_init)?(
+                    session,
+                    template.as_mut_ptr(),
+                    template.len() as CkUlong,
+                ),
+            )?;
+            let found = self.collect_found(session);
+            let finished = check(
+                "C_FindObjectsFinal",
+                function("C_FindObjectsFinal", list.find_objects_final)?(session),
+            );
+            let found = found?;
+            finished.map(|_| found)
+        }
+    }
+
+    /// Drain the results of the search running on `session`
+    unsafe fn collect_found(&self, session: CkSessionHandle) -> Result<Vec<CkObjectHandle>> {
+        let find_objects = function("C_FindObjects", self.list().find_objects)?;
+        let mut found = Vec::new();
+        loop {
+            let mut batch = [CK_INVALID_HANDLE; FIND_BATCH];
+            let mut count: CkUlong = 0;
+            check(
+                "C_FindObjects",
+                find_objects(
+                    session,
+                    batch.as_mut_ptr(),
+                    FIND_BATCH as CkUlong,
+                    &mut count,
+                ),
+            )?;
+            let count = (count as usize).min(FIND_BATCH);
+            if count == 0 {
+                return Ok(found);
+            }
+            found.extend_from_slice(&batch[..count]);
+        }
+    }
+
+    fn destroy(&self, session: CkSessionHandle, objects: &[CkObjectHandle]) -> Result<()> {
+        let destroy_object = function("C_DestroyObject", self.list().destroy_object)?;
+        for &object in objects {
+            check("C_DestroyObject", unsafe {
+                destroy_object(session, object)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl Keystore for Pkcs11Keystore {
+    fn get_secret(&self, id: &str) -> Result<Option<Zeroizing<[u8; 32]>>> {
+        let session = self.session.lock();
+        let Some(&object) = self.find(*session, id)?.first() else {
+            return Ok(None);
+        };
+        let get_attribute_value = function("C_GetAttributeValue", self.list().get_attribute_value)?;
+        let mut secret = Zeroizing::new([0u8; 32]);
+        let mut template = [CkAttribute {
+            kind: CKA_VALUE,
+            value: secret.as_mut_ptr().cast(),
+            len: secret.len() as CkUlong,
+        }];
+        check("C_GetAttributeValue", unsafe {
+            get_attribute_value(*session, object, template.as_mut_ptr(), 1)
+        })?;
+        let len = template[0].len;
+        if len != secret.len() as CkUlong {
+            return Err(KeystoreError::Malformed("PKCS#11 secret object"));
+        }
+        Ok(Some(secret))
+    }
+
+    fn put_secret(&self, id: &str, secret: &[u8; 32]) -> Result<()> {
+        let session = self.session.lock();
+        let existing = self.find(*session, id)?;
+        let create_object = function("C_CreateObject", self.list().create_object)?;
+
+        let class = CKO_DATA;
+        let mut template = [
+            ulong_attribute(CKA_CLASS, &class),
+            bytes_attribute(CKA_TOKEN, &[CK_TRUE]),
+            bytes_attribute(CKA_PRIVATE, &[CK_TRUE]),
+            bytes_attribute(CKA_APPLICATION, APPLICATION),
+            bytes_attribute(CKA_LABEL, id.as_bytes()),
+            bytes_attribute(CKA_VALUE, secret),
+        ];
+        let mut object = CK_INVALID_HANDLE;
+        check("C_CreateObject", unsafe {
+            create_object(
+                *session,
+                template.as_mut_ptr(),
+                template.len() as CkUlong,
+                &mut object,
+            )
+        })?;
+        // Remove the old value only once the new one is stored
+        self.destroy(*session, &existing)
+    }
+
+    fn delete_secret(&self, id: &str) -> Result<bool> {
+        let session = self.session.lock();
+        let existing = self.find(*session, id)?;
+        self.destroy(*session, &existing)?;
+        Ok(!existing.is_empty())
+    }
+}
+
+impl Drop for Pkcs11Keystore {
+    fn drop(&mut self) {
+        let session = *self.session.get_mut();
+        let list = self.list();
+        unsafe {
+            if session != CK_INVALID_HANDLE {
+                if let Some(close_session) = list.close_session {
+                    close_session(session);
+                }
+            }
+            if self.finalize {
+                if let Some(finalize) = list.finalize {
+                    finalize(ptr::null_mut());
+                }
+            }
+        }
+    }
+}
+
+/// An entry of the function list, which a broken module may leave null
+fn function<F>(name: &'static str, entry: Option<F>) -> Result<F> {
+    entry.ok_or(KeystoreError::Pkcs11 {
+        function: name,
+        code: u64::MAX,
+    })
+}
+
+fn check(function: &'static str, code: CkRv) -> Result<()> {
+    match code {
+        CKR_OK => Ok(()),
+        code => Err(pkcs11_error(function, code)),
+    }
+}
+
+// CK_ULONG is 32 bits on Windows
+#[allow(clippy::useless_conversion)]
+fn pkcs11_error(function: &'static str, code: CkRv) -> KeystoreError {
+    KeystoreError::Pkcs11 {
+        function,
+        code: u64::from(code),
+    }
+}
+
+/// Attribute pointing at `value`, which the module only reads
+fn bytes_attribute(kind: CkUlong, value: &[u8]) -> CkAttribute {
+    CkAttribute {
+        kind,
+        value: value.as_ptr().cast_mut().cast(),
+        len: value.len() as CkUlong,
+    }
+}
+
+fn ulong_attribute(kind: CkUlong, value: &CkUlong) -> CkAttribute {
+    CkAttribute {
+        kind,
+        value: ptr::from_ref(value).cast_mut().cast(),
+        len: std::mem::size_of::<CkUlong>() as CkUlong,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CKR_CANT_LOCK: CkRv = 0x0a;
+    const CKR_PIN_INCORRECT: CkRv = 0xa0;
+    const CKR_USER_NOT_LOGGED_IN: CkRv = 0x101;
+    const CKR_BUFFER_TOO_SMALL: CkRv = 0x150;
+
+    /// Objects on the fake token, as attribute lists
+    type Objects = Vec<(CkObjectHandle, Vec<(CkUlong, Vec<u8>)>)>;
+
+    /// In-process stand-in for a token: objects, login state and the
+    /// results of the running search
+    struct FakeToken {
+        objects: Objects,
+        next_handle: CkObjectHandle,
+        logged_in: bool,
+        found: Vec<CkObjectHandle>,
+    }
+
+    static TOKEN: Mutex<FakeToken> = Mutex::new(FakeToken {
+        objects: Vec::new(),
+        next_handle: 1,
+        logged_in: false,
+        found: Vec::new(),
+    });
+
+    unsafe fn attributes(template: *mut CkAttribute, count: CkUlong) -> Vec<(CkUlong, Vec<u8>)> {
+        std::slice::from_raw_parts(template, count as usize)
+            .iter()
+            .map(|a| {
+                let value = std::slice::from_raw_parts(a.value as *const u8, a.len as usize);
+                (a.kind, value.to_vec())
+            })
+            .collect()
+    }
+
+    /// Refuses to start without OS locking, which the keystore relies on
+    unsafe extern "C" fn initialize(args: *mut c_void) -> CkRv {
+        let args = args.cast::<CkInitializeArgs>();
+        if args.is_null() || (*args).flags & CKF_OS_LOCKING_OK == 0 {
+            return CKR_CANT_LOCK;
+        }
+        CKR_OK
+    }
+
+    unsafe extern "C" fn open_session(
+        slot: CkUlong,
+        _: CkUlong,
+        _: *mut c_void,
+        _: *const c_void,
+        session: *mut CkSessionHandle,
+    ) -> CkRv {
+        *session = 10 + slot;
+        CKR_OK
+    }
+
+    unsafe extern "C" fn close_session(_: CkSessionHandle) -> CkRv {
+        TOKEN.lock().logged_in = false;
+        CKR_OK
+    }
+
+    unsafe extern "C" fn login(
+        _: CkSessionHandle,
+        _: CkUlong,
+        pin: *const u8,
+        len: CkUlong,
+    ) -> CkRv {
+        if std::slice::from_raw_parts(pin, len as usize) != b"1234" {
+            return CKR_PIN_INCORRECT;
+        }
+        TOKEN.lock().logged_in = true;
+        CKR_OK
+    }
+
+    unsafe extern "C" fn create_object(
+        _: CkSessionHandle,
+        template: *mut CkAttribute,
+        count: CkUlong,
+        object: *mut CkObjectHandle,
+    ) -> CkRv {
+        let mut token = TOKEN.lock();
+        if !token.logged_in {
+            return CKR_USER_NOT_LOGGED_IN;
+        }
+        let handle = token.next_handle;
+        token.next_handle += 1;
+        token.objects.push((handle, attributes(template, count)));
+        *object = handle;
+        CKR_OK
+    }
+
+    unsafe extern "C" fn destroy_object(_: CkSessionHandle, object: CkObjectHandle) -> CkRv {
+        TOKEN.lock().objects.retain(|(handle, _)| *handle != object);
+        CKR_OK
+    }
+
+    unsafe extern "C" fn get_attribute_value(
+        _: CkSessionHandle,
+        object: CkObjectHandle,
+        template: *mut CkAttribute,
+        count: CkUlong,
+    ) -> CkRv {
+        let token = TOKEN.lock();
+        let Some((_, stored)) = token.objects.iter().find(|(handle, _)| *handle == object) else {
+            return 0x82; // CKR_OBJECT_HANDLE_INVALID
+        };
+        for attribute in std::slice::from_raw_parts_mut(template, count as usize) {
+            let Some((_, value)) = stored.iter().find(|(kind, _)| *kind == attribute.kind) else {
+                return 0x12; // CKR_ATTRIBUTE_TYPE_INVALID
+            };
+            if (attribute.len as usize) < value.len() {
+                return CKR_BUFFER_TOO_SMALL;
+            }
+            ptr::copy_nonoverlapping(value.as_ptr(), attribute.value.cast(), value.len());
+            attribute.len = value.len() as CkUlong;
+        }
+        CKR_OK
+    }
+
+    unsafe extern "C" fn find_objects_init(
+        _: CkSessionHandle,
+        template: *mut CkAttribute,
+        count: CkUlong,
+    ) -> CkRv {
+        let wanted = attributes(template, count);
+        let mut token = TOKEN.lock();
+        // Private objects are hidden until login
+        let logged_in = token.logged_in;
+        token.found = token
+            .objects
+            .iter()
+            .filter(|(_, stored)| logged_in && wanted.iter().all(|w| stored.contains(w)))
+            .map(|(handle, _)| *handle)
+            .collect();
+        CKR_OK
+    }
+
+    unsafe extern "C" fn find_objects(
+        _: CkSessionHandle,
+        objects: *mut CkObjectHandle,
+        max: CkUlong,
+        count: *mut CkUlong,
+    ) -> CkRv {
+        let mut token = TOKEN.lock();
+        let take = token.found.len().min(max as usize);
+        for (i, handle) in token.found.drain(..take).enumerate() {
+            *objects.add(i) = handle;
+        }
+        *count = take as CkUlong;
+        CKR_OK
+    }
+
+    unsafe extern "C" fn find_objects_final(_: CkSessionHandle) -> CkRv {
+        TOKEN.lock().found.clear();
+        CKR_OK
+    }
+
+    fn fake_module() -> FunctionList {
+        FunctionList {
+            version: CkVersion {
+                major: 2,
+                minor: 40,
+            },
+            initialize: Some(initialize),
+            finalize: Some(initialize),
+            get_info: ptr::null(),
+            get_function_list: ptr::null(),
+            get_slot_list: ptr::null(),
+            get_slot_info: ptr::null(),
+            get_token_info: ptr::null(),
+            get_mechanism_list: ptr::null(),
+            get_mechanism_info: ptr::null(),
+            init_token: ptr::null(),
+            init_pin: ptr::null(),
+            set_pin: ptr::null(),
+            open_session: Some(open_session),
+            close_session: Some(close_session),
+            close_all_sessions: ptr::null(),
+            get_session_info: ptr::null(),
+            get_operation_state: ptr::null(),
+            set_operation_state: ptr::null(),
+            login: Some(login),
+            logout: ptr::null(),
+            create_object: Some(create_object),
+            copy_object: ptr::null(),
+            destroy_object: Some(destroy_object),
+            get_object_size: ptr::null(),
+            get_attribute_value: Some(get_attribute_value),
+            set_attribute_value: ptr::null(),
+            find_objects_init: Some(find_objects_init),
+            find_objects: Some(find_objects),
+            find_objects_final: Some(find_objects_final),
+        }
+    }
+
+    #[test]
+    fn test_pkcs11_keystore_keeps_secrets_on_the_token() {
+        let module = fake_module();
+        assert!(matches!(
+            unsafe { Pkcs11Keystore::with_functions(None, &module, 1, "0000") },
+            Err(KeystoreError::Pkcs11 {
+                function: "C_Login",
+                code: 0xa0
+            })
+        ));
+
+        let store = unsafe { Pkcs11Keystore::with_functions(None, &module, 1, "1234") }.unwrap();
+        assert!(store.get_secret("a").unwrap().is_none());
+        store.put_secret("a", &[1u8; 32]).unwrap();
+        store.put_secret("b", &[2u8; 32]).unwrap();
+        store.put_secret("a", &[3u8; 32]).unwrap();
+        assert_eq!(*store.get_secret("a").unwrap().unwrap(), [3u8; 32]);
+        assert_eq!(*store.get_secret("b").unwrap().unwrap(), [2u8; 32]);
+        // Replacing a secret leaves a single object behind
+        assert_eq!(TOKEN.lock().objects.len(), 2);
+
+        assert!(store.delete_secret("a").unwrap());
+        assert!(!store.delete_secret("a").unwrap());
+        assert!(store.get_secret("a").unwrap().is_none());
+        drop(store);
+        assert!(!TOKEN.lock().logged_in);
+
+        assert!(matches!(
+            Pkcs11Keystore::open("/nonexistent/libpkcs11.so", 0, "1234"),
+            Err(KeystoreError::Pkcs11Module(_))
+        ));
+    }
+}
@@ -18,38 +18,156 @@
 //! - **Content Addressing**: Blake3-based deduplication
 //! - **Storage Pipeline**: High-level API with pluggable backends
 //! - **Cross-Platform**: Pure Rust with no C dependencies
+//!
+//! ## `no_std`
+//! With default features off the crate is `no_std` + `alloc` and provides
+//! only the coding core: [`gf256`], [`backends::pure_rust`], [`ida`],
+//! [`types`], [`FecParams`], [`FecCodec`] and the [`FecBackend`] trait.
+//! Everything else needs the default `std` feature.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+// Storage is single-threaded on wasm32, so its `Arc`s are never shared
+#![cfg_attr(target_arch = "wasm32", allow(clippy::arc_with_non_send_sync))]
+
+extern crate alloc;
 
-use std::fmt;
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+use core::fmt;
 use thiserror::Error;
 
+#[cfg(feature = "std")]
+pub mod audit;
 pub mod backends;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "std")]
+pub mod bloom;
+#[cfg(feature = "std")]
+pub mod car;
+#[cfg(feature = "std")]
+pub mod chunk_codec;
+#[cfg(feature = "std")]
 pub mod chunk_registry;
+#[cfg(feature = "std")]
 pub mod config;
+#[cfg(feature = "std")]
 pub mod crypto;
+#[cfg(feature = "std")]
+pub mod dedup;
+#[cfg(feature = "std")]
+pub mod error;
+#[cfg(feature = "std")]
 pub mod fec;
+#[cfg(feature = "std")]
+pub mod fec_policy;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+pub mod fuse;
+#[cfg(feature = "std")]
 pub mod gc;
 pub mod gf256;
 pub mod ida;
+#[cfg(feature = "std")]
+pub mod integrity;
+#[cfg(feature = "std")]
+pub mod keystore;
+#[cfg(feature = "std")]
+pub mod locator;
+#[cfg(feature = "std")]
 pub mod metadata;
+#[cfg(feature = "std")]
+pub mod network;
+#[cfg(feature = "node")]
+pub mod node;
+#[cfg(feature = "std")]
+pub mod patch;
+#[cfg(feature = "std")]
 pub mod pipeline;
+#[cfg(feature = "std")]
 pub mod quantum_crypto;
+#[cfg(feature = "std")]
+pub mod reencrypt;
+#[cfg(feature = "std")]
+pub mod secret_registry;
+#[cfg(feature = "std")]
+pub mod secure_memory;
+#[cfg(feature = "std")]
 pub mod storage;
+#[cfg(feature = "std")]
+pub mod stream;
+#[cfg(feature = "otel")]
+pub mod telemetry;
 pub mod traits;
 pub mod types;
+#[cfg(feature = "std")]
 pub mod version;
-
+#[cfg(feature = "std")]
+pub mod wire;
+
+#[cfg(feature = "std")]
+pub use audit::{AuditEntry, AuditEvent, AuditLog, AuditOperation};
+#[cfg(feature = "std")]
+pub use chunk_codec::{ChunkCodec, EncodedChunk};
+#[cfg(feature = "std")]
+pub use dedup::{DedupClient, DedupOracle, DedupQuery, DedupResponse, StorageDedupOracle};
+#[cfg(feature = "std")]
+pub use error::{Error, ErrorKind};
+#[cfg(feature = "std")]
+pub use fec_policy::{AdaptivePolicy, FecChoice, FecInputs, FecPolicy, StaticPolicy};
 pub use ida::{IDAConfig, IDADescriptor, ShareMetadata};
-pub use traits::{Fec, FecBackend};
+#[cfg(feature = "std")]
+pub use traits::Fec;
+pub use traits::FecBackend;
+#[cfg(feature = "std")]
+pub use wire::WireFormat;
 
 // v0.3 API exports
-pub use config::{Config, EncryptionMode};
-pub use pipeline::{Meta, PipelineStats, StoragePipeline};
-pub use quantum_crypto::{QuantumCryptoEngine, QuantumEncryptionMetadata};
+#[cfg(feature = "std")]
+pub use config::{Config, CryptoPolicy, EncryptionMode, ProfileRegistry};
+#[cfg(feature = "std")]
+pub use integrity::{
+    FileHealth, HealthStatus, IntegrityScanner, RepairReport, ScanConfig, ScanEvent, ScanStats,
+    ScannerHandle,
+};
+#[cfg(feature = "keychain")]
+pub use keystore::KeychainKeystore;
+#[cfg(feature = "std")]
+pub use keystore::{FileKeystore, KemKeyStore, Keystore, MemoryKeystore};
+#[cfg(feature = "std")]
+pub use locator::{MemoryLocator, ShardLocator};
+#[cfg(feature = "std")]
+pub use patch::{ChunkOp, VersionPatch};
+#[cfg(feature = "std")]
+pub use pipeline::{
+    Chunker, CryptoProvider, FixedSizeChunker, Meta, PipelineStats, StoragePipeline,
+    StoragePipelineBuilder,
+};
+#[cfg(feature = "std")]
+pub use quantum_crypto::{
+    ChunkPosition, ConvergenceSecret, KeyShare, KeySplit, PassphraseKdf, QuantumCryptoEngine,
+    QuantumEncryptionMetadata, WrappedKey,
+};
+#[cfg(feature = "std")]
+pub use reencrypt::{ReencryptionConfig, ReencryptionHandle, ReencryptionProgress, Reencryptor};
+#[cfg(feature = "std")]
+pub use secret_registry::{SecretInfo, SecretRegistry};
+#[cfg(feature = "redb")]
+pub use storage::KvStorage;
+#[cfg(feature = "sqlite")]
+pub use storage::SqliteStorage;
+#[cfg(feature = "std")]
 pub use storage::{
-    ChunkMeta, Cid, FileMetadata, GcReport, LocalStorage, MemoryStorage, MultiStorage,
-    MultiStorageStrategy, NetworkStorage, NodeEndpoint, Shard, ShardHeader, StorageBackend,
-    StorageStats,
+    BackendFactory, CacheStats, CachedStorage, ChunkMeta, Cid, CompressedStorage, DurabilityPolicy,
+    FileMetadata, GcReport, HealthPolicy, MemoryObjectStore, MemoryStorage, MultiStorage,
+    MultiStorageStrategy, NetworkStorage, NodeEndpoint, NodeHealth, ObjectStore,
+    ObjectStoreStorage, PendingDeletion, RebalanceReport, Shard, ShardHeader, ShardPage,
+    ShardReader, StorageBackend, StorageStats, SyncPolicy, ThrottledStorage, Transaction,
 };
+#[cfg(feature = "http")]
+pub use storage::{HttpStorage, HttpStorageConfig};
+#[cfg(feature = "native")]
+pub use storage::{LocalStorage, StorageRepairHooks};
 
 /// Errors that can occur during FEC operations
 #[derive(Debug, Error)]
@@ -72,14 +190,132 @@ pub enum FecError {
     #[error("Backend error: {0}")]
     Backend(String),
 
+    #[error("Storage capacity exceeded: need {needed} bytes, {available} available")]
+    CapacityExceeded { needed: u64, available: u64 },
+
+    #[error("Shard {cid} is corrupted: content hashes to {actual}")]
+    CorruptShard { cid: String, actual: String },
+
+    #[error("Shard not found: {cid}")]
+    ShardNotFound { cid: String },
+
+    #[error("Metadata not found: {file_id}")]
+    MetadataNotFound { file_id: String },
+
+    #[cfg(feature = "std")]
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
 
-pub type Result<T> = std::result::Result<T, FecError>;
+pub type Result<T> = core::result::Result<T, FecError>;
+
+/// Wire form of [`FecError`], so errors can travel in messages between
+/// nodes. An `Io` error keeps only its message.
+#[derive(serde::Serialize, serde::Deserialize)]
+enum FecErrorRepr {
+    InvalidParameters { k: usize, n: usize },
+    InsufficientShares { have: usize, need: usize },
+    InvalidShareIndex { index: usize, max: usize },
+    SizeMismatch { expected: usize, actual: usize },
+    SingularMatrix,
+    Backend(String),
+    CapacityExceeded { needed: u64, available: u64 },
+    CorruptShard { cid: String, actual: String },
+    Io(String),
+    ShardNotFound { cid: String },
+    MetadataNotFound { file_id: String },
+}
+
+impl From<&FecError> for FecErrorRepr {
+    fn from(error: &FecError) -> Self {
+        match error {
+            FecError::InvalidParameters { k, n } => Self::InvalidParameters { k: *k, n: *n },
+            FecError::InsufficientShares { have, need } => Self::InsufficientShares {
+                have: *have,
+                need: *need,
+            },
+            FecError::InvalidShareIndex { index, max } => Self::InvalidShareIndex {
+                index: *index,
+                max: *max,
+            },
+            FecError::SizeMismatch { expected, actual } => Self::SizeMismatch {
+                expected: *expected,
+                actual: *actual,
+            },
+            FecError::SingularMatrix => Self::SingularMatrix,
+            FecError::Backend(message) => Self::Backend(message.clone()),
+            FecError::CapacityExceeded { needed, available } => Self::CapacityExceeded {
+                needed: *needed,
+                available: *available,
+            },
+            FecError::CorruptShard { cid, actual } => Self::CorruptShard {
+                cid: cid.clone(),
+                actual: actual.clone(),
+            },
+            FecError::ShardNotFound { cid } => Self::ShardNotFound { cid: cid.clone() },
+            FecError::MetadataNotFound { file_id } => Self::MetadataNotFound {
+                file_id: file_id.clone(),
+            },
+            #[cfg(feature = "std")]
+            FecError::Io(e) => Self::Io(e.to_string()),
+        }
+    }
+}
+
+impl From<FecErrorRepr> for FecError {
+    fn from(repr: FecErrorRepr) -> Self {
+        match repr {
+            FecErrorRepr::InvalidParameters { k, n } => Self::InvalidParameters { k, n },
+            FecErrorRepr::InsufficientShares { have, need } => {
+                Self::InsufficientShares { have, need }
+            }
+            FecErrorRepr::InvalidShareIndex { index, max } => {
+                Self::InvalidShareIndex { index, max }
+            }
+            FecErrorRepr::SizeMismatch { expected, actual } => {
+                Self::SizeMismatch { expected, actual }
+            }
+            FecErrorRepr::SingularMatrix => Self::SingularMatrix,
+            FecErrorRepr::Backend(message) => Self::Backend(message),
+            FecErrorRepr::CapacityExceeded { needed, available } => {
+                Self::CapacityExceeded { needed, available }
+            }
+            FecErrorRepr::CorruptShard { cid, actual } => Self::CorruptShard { cid, actual },
+            FecErrorRepr::ShardNotFound { cid } => Self::ShardNotFound { cid },
+            FecErrorRepr::MetadataNotFound { file_id } => Self::MetadataNotFound { file_id },
+            #[cfg(feature = "std")]
+            FecErrorRepr::Io(message) => Self::Io(std::io::Error::other(message)),
+            #[cfg(not(feature = "std"))]
+            FecErrorRepr::Io(message) => Self::Backend(alloc::format!("IO error: {}", message)),
+        }
+    }
+}
+
+impl serde::Serialize for FecError {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> core::result::Result<S::Ok, S::Error> {
+        FecErrorRepr::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for FecError {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> core::result::Result<Self, D::Error> {
+        FecErrorRepr::deserialize(deserializer).map(Self::from)
+    }
+}
 
 /// FEC parameters for encoding/decoding
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///
+/// This is the one parameter type for every codec in the crate, including
+/// the shard layer in [`fec`]. Deserialization applies the same checks as
+/// [`new`](Self::new) and [`with_symbol_size`](Self::with_symbol_size), so
+/// parameters received from another node are valid once read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "FecParamsRepr")]
 pub struct FecParams {
     /// Number of data shares (k)
     pub data_shares: u16,
@@ -89,6 +325,26 @@ pub struct FecParams {
     pub symbol_size: u32,
 }
 
+/// Unchecked [`FecParams`] as read. The aliases read manifests written when
+/// the shard layer had its own `k`/`m`/`shard_size` type.
+#[derive(serde::Deserialize)]
+struct FecParamsRepr {
+    #[serde(alias = "k")]
+    data_shares: u16,
+    #[serde(alias = "m")]
+    parity_shares: u16,
+    #[serde(alias = "shard_size")]
+    symbol_size: u32,
+}
+
+impl TryFrom<FecParamsRepr> for FecParams {
+    type Error = FecError;
+
+    fn try_from(repr: FecParamsRepr) -> Result<Self> {
+        Self::new(repr.data_shares, repr.parity_shares)?.with_symbol_size(repr.symbol_size)
+    }
+}
+
 impl FecParams {
     /// Create new FEC parameters
     pub fn new(data_shares: u16, parity_shares: u16) -> Result<Self> {
@@ -114,11 +370,54 @@ impl FecParams {
         })
     }
 
+    /// Same parameters with `symbol_size`-byte symbols
+    pub fn with_symbol_size(self, symbol_size: u32) -> Result<Self> {
+        if symbol_size == 0 {
+            return Err(FecError::InvalidParameters {
+                k: self.data_shares as usize,
+                n: self.total_shares() as usize,
+            });
+        }
+        Ok(Self {
+            symbol_size,
+            ..self
+        })
+    }
+
     /// Get total number of shares (n)
     pub fn total_shares(&self) -> u16 {
         self.data_shares + self.parity_shares
     }
 
+    /// Old name of [`total_shares`](Self::total_shares) from the shard layer
+    #[deprecated(since = "0.4.12", note = "use `total_shares`")]
+    pub fn total_shards(&self) -> u16 {
+        self.total_shares()
+    }
+
+    /// Old name of [`data_shares`](Self::data_shares) from the shard layer
+    #[deprecated(since = "0.4.12", note = "use `data_shares`")]
+    pub fn k(&self) -> u16 {
+        self.data_shares
+    }
+
+    /// Old name of [`parity_shares`](Self::parity_shares) from the shard layer
+    #[deprecated(since = "0.4.12", note = "use `parity_shares`")]
+    pub fn m(&self) -> u16 {
+        self.parity_shares
+    }
+
+    /// Old name of [`symbol_size`](Self::symbol_size) from the shard layer
+    #[deprecated(since = "0.4.12", note = "use `symbol_size`")]
+    pub fn shard_size(&self) -> usize {
+        self.symbol_size as usize
+    }
+
+    /// Stored bytes per byte of data
+    pub fn overhead_ratio(&self) -> f64 {
+        self.total_shares() as f64 / self.data_shares as f64
+    }
+
     /// Calculate parameters based on content size
     pub fn from_content_size(size: usize) -> Self {
         match size {
@@ -256,4 +555,48 @@ mod tests {
         assert_eq!(large.data_shares, 20);
         assert_eq!(large.parity_shares, 5);
     }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_fec_params_deserialization_is_checked() {
+        let params = FecParams::new(10, 4).unwrap();
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(serde_json::from_str::<FecParams>(&json).unwrap(), params);
+        let bytes = bincode::serialize(&params).unwrap();
+        assert_eq!(bincode::deserialize::<FecParams>(&bytes).unwrap(), params);
+
+        for invalid in [
+            r#"{"data_shares": 0, "parity_shares": 4, "symbol_size": 1024}"#,
+            r#"{"data_shares": 200, "parity_shares": 100, "symbol_size": 1024}"#,
+            r#"{"k": 3, "m": 2, "shard_size": 0}"#,
+        ] {
+            assert!(serde_json::from_str::<FecParams>(invalid).is_err());
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_fec_error_roundtrip() {
+        let errors = [
+            FecError::InsufficientShares { have: 3, need: 8 },
+            FecError::Backend("node unreachable".to_string()),
+            FecError::CorruptShard {
+                cid: "ab".to_string(),
+                actual: "cd".to_string(),
+            },
+            FecError::Io(std::io::Error::other("disk full")),
+        ];
+        for error in errors {
+            let json = serde_json::to_string(&error).unwrap();
+            let from_json: FecError = serde_json::from_str(&json).unwrap();
+            let bytes = bincode::serialize(&error).unwrap();
+            let from_bincode: FecError = bincode::deserialize(&bytes).unwrap();
+            assert_eq!(from_json.to_string(), error.to_string());
+            assert_eq!(from_bincode.to_string(), error.to_string());
+        }
+        assert!(matches!(
+            serde_json::from_str(r#"{"InsufficientShares":{"have":3,"need":8}}"#),
+            Ok(FecError::InsufficientShares { have: 3, need: 8 })
+        ));
+    }
 }
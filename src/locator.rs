@@ -0,0 +1,137 @@
+//! Shard placement lookup
+//!
+//! A [`ShardLocator`] answers "which nodes hold shard X of object Y",
+//! independently of the [`StorageBackend`](crate::storage::StorageBackend)
+//! the shards are written to. In the wider Saorsa stack it is backed by the
+//! DHT; [`MemoryLocator`] keeps the records in process.
+//!
+//! A pipeline given a locator with
+//! [`shard_locator`](crate::StoragePipelineBuilder::shard_locator) publishes
+//! itself as holder of every shard it stores or repairs. With a
+//! [`NodeTransport`](crate::network::NodeTransport) as well, shards missing
+//! from its backend are fetched from the nodes the locator names before
+//! they are counted as lost.
+
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+
+use crate::storage::{Cid, NodeEndpoint};
+use crate::FecError;
+
+/// Publishes and resolves the nodes holding each shard of an object
+#[async_trait]
+pub trait ShardLocator: Send + Sync {
+    /// Record `node` as a holder of the shards `cids` of `object`
+    async fn publish(
+        &self,
+        object: &[u8; 32],
+        cids: &[Cid],
+        node: &NodeEndpoint,
+    ) -> Result<(), FecError>;
+
+    /// Nodes known to hold shard `cid` of `object`
+    async fn resolve(&self, object: &[u8; 32], cid: &Cid) -> Result<Vec<NodeEndpoint>, FecError>;
+
+    /// Remove `node` as a holder of the shards `cids` of `object`
+    async fn withdraw(
+        &self,
+        object: &[u8; 32],
+        cids: &[Cid],
+        node: &NodeEndpoint,
+    ) -> Result<(), FecError>;
+}
+
+/// Object ID and shard CID
+type ShardKey = ([u8; 32], Cid);
+
+/// In-process [`ShardLocator`]
+#[derive(Default)]
+pub struct MemoryLocator {
+    holders: RwLock<HashMap<ShardKey, Vec<NodeEndpoint>>>,
+}
+
+impl MemoryLocator {
+    /// Create an empty locator
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ShardLocator for MemoryLocator {
+    async fn publish(
+        &self,
+        object: &[u8; 32],
+        cids: &[Cid],
+        node: &NodeEndpoint,
+    ) -> Result<(), FecError> {
+        let mut holders = self.holders.write();
+        for cid in cids {
+            let nodes = holders.entry((*object, *cid)).or_default();
+            if !nodes.contains(node) {
+                nodes.push(node.clone());
+            }
+        }
+        Ok(())
+    }
+
+    async fn resolve(&self, object: &[u8; 32], cid: &Cid) -> Result<Vec<NodeEndpoint>, FecError> {
+        Ok(self
+            .holders
+            .read()
+            .get(&(*object, *cid))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn withdraw(
+        &self,
+        object: &[u8; 32],
+        cids: &[Cid],
+        node: &NodeEndpoint,
+    ) -> Result<(), FecError> {
+        let mut holders = self.holders.write();
+        for cid in cids {
+            let key = (*object, *cid);
+            if let Some(nodes) = holders.get_mut(&key) {
+                nodes.retain(|n| n != node);
+                if nodes.is_empty() {
+                    holders.remove(&key);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_locator_publish_resolve_withdraw() {
+        let locator = MemoryLocator::new();
+        let object = [1u8; 32];
+        let cids = [Cid::new([2u8; 32]), Cid::new([3u8; 32])];
+        let a: NodeEndpoint = "10.0.0.1:9000".parse().unwrap();
+        let b: NodeEndpoint = "10.0.0.2:9000".parse().unwrap();
+
+        locator.publish(&object, &cids, &a).await.unwrap();
+        locator.publish(&object, &cids[..1], &b).await.unwrap();
+        locator.publish(&object, &cids[..1], &a).await.unwrap();
+        assert_eq!(
+            locator.resolve(&object, &cids[0]).await.unwrap(),
+            vec![a.clone(), b.clone()]
+        );
+        assert!(locator
+            .resolve(&[9u8; 32], &cids[0])
+            .await
+            .unwrap()
+            .is_empty());
+
+        locator.withdraw(&object, &cids, &a).await.unwrap();
+        assert_eq!(locator.resolve(&object, &cids[0]).await.unwrap(), vec![b]);
+        assert!(locator.resolve(&object, &cids[1]).await.unwrap().is_empty());
+    }
+}
@@ -3,15 +3,62 @@
 //! This module provides deterministic metadata structures that enable
 //! content-addressed storage with perfect deduplication.
 
-use anyhow::{Context, Result};
 use blake3::Hasher;
+use saorsa_pqc::api::sig::{
+    ml_dsa_65, MlDsaPublicKey, MlDsaSecretKey, MlDsaSignature, MlDsaVariant,
+};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use zeroize::Zeroizing;
 
+use crate::config::{CryptoPolicy, EncryptionMode};
 use crate::crypto::EncryptionMetadata;
 use crate::quantum_crypto::QuantumEncryptionMetadata;
 
+/// Errors from signing, validating or persisting metadata
+#[derive(Debug, Error)]
+pub enum MetadataError {
+    #[error("Metadata is not signed")]
+    NotSigned,
+
+    /// The signature is well formed but does not cover these contents
+    #[error("Signature does not match the signed contents")]
+    InvalidSignature,
+
+    #[error("Malformed signature: {0}")]
+    MalformedSignature(String),
+
+    #[error("Invalid ML-DSA key: {0}")]
+    InvalidKey(String),
+
+    #[error("Signing failed: {0}")]
+    Signing(String),
+
+    #[error("Duplicate chunk index: stripe={stripe}, shard={shard}")]
+    DuplicateChunk { stripe: u32, shard: u16 },
+
+    #[error("Metadata not found: {0}")]
+    NotFound(String),
+
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] bincode::Error),
+
+    #[error("{}: {error}", path.display())]
+    Io { path: PathBuf, error: io::Error },
+}
+
+pub type Result<T> = std::result::Result<T, MetadataError>;
+
+fn io_error(path: &Path) -> impl FnOnce(io::Error) -> MetadataError + '_ {
+    |error| MetadataError::Io {
+        path: path.to_path_buf(),
+        error,
+    }
+}
+
 /// File metadata containing all deterministic information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileMetadata {
@@ -28,8 +75,41 @@ pub struct FileMetadata {
     /// Parent version hash for version tracking
     pub parent_version: Option<[u8; 32]>,
     /// Optional local-only metadata (never affects hashing)
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub local_metadata: Option<LocalMetadata>,
+    /// Creator's ML-DSA signature over the metadata
+    #[serde(default)]
+    pub signature: Option<MetadataSignature>,
+    /// Crypto policy the file was encrypted under
+    #[serde(default = "standard_policy")]
+    pub crypto_policy: CryptoPolicy,
+    /// Settings the file was processed with, absent for files written
+    /// before they were recorded
+    #[serde(default)]
+    pub params: Option<FileParams>,
+}
+
+/// Effective settings a file was processed with
+///
+/// These reflect per-file overrides as well as the pipeline configuration
+/// in force at the time, so retrieval does not depend on either.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileParams {
+    /// Encryption mode
+    pub encryption_mode: EncryptionMode,
+    /// FEC data shares per chunk
+    pub data_shards: u16,
+    /// FEC parity shares per chunk
+    pub parity_shards: u16,
+    /// Size chunks were cut to, or `None` if a custom chunker cut them
+    pub chunk_size: Option<usize>,
+    /// Compression level, or `None` if the file was not compressed
+    pub compression_level: Option<u8>,
+}
+
+/// Files written before crypto policies were recorded
+fn standard_policy() -> CryptoPolicy {
+    CryptoPolicy::Standard
 }
 
 impl FileMetadata {
@@ -48,6 +128,9 @@ impl FileMetadata {
             chunks,
             parent_version: None,
             local_metadata: None,
+            signature: None,
+            crypto_policy: CryptoPolicy::Standard,
+            params: None,
         }
     }
 
@@ -66,6 +149,9 @@ impl FileMetadata {
             chunks,
             parent_version: None,
             local_metadata: None,
+            signature: None,
+            crypto_policy: CryptoPolicy::Standard,
+            params: None,
         }
     }
 
@@ -112,6 +198,45 @@ impl FileMetadata {
         self
     }
 
+    /// Sign the metadata with the creator's key
+    ///
+    /// The signature covers every field except the local metadata and the
+    /// signature itself, so sign after the metadata is final.
+    pub fn sign(&mut self, signer: &MetadataSigner) -> Result<()> {
+        let digest = self.signing_digest()?;
+        self.signature = Some(MetadataSignature {
+            public_key: signer.public_key(),
+            signature: signer.sign_digest(&digest, METADATA_SIGNATURE_CONTEXT)?,
+        });
+        Ok(())
+    }
+
+    /// Verify the signature and return the signer's public key
+    ///
+    /// Fails if the metadata is unsigned or was modified after signing.
+    /// Anyone can sign, so callers must still check the returned key
+    /// belongs to a creator they trust.
+    pub fn verify_signature(&self) -> Result<&[u8]> {
+        let signed = self.signature.as_ref().ok_or(MetadataError::NotSigned)?;
+        let digest = self.signing_digest()?;
+        verify_digest(
+            &signed.public_key,
+            &signed.signature,
+            &digest,
+            METADATA_SIGNATURE_CONTEXT,
+        )?;
+        Ok(&signed.public_key)
+    }
+
+    /// BLAKE3 digest of everything the signature covers
+    fn signing_digest(&self) -> Result<[u8; 32]> {
+        let mut unsigned = self.clone();
+        unsigned.signature = None;
+        unsigned.local_metadata = None;
+        let serialized = bincode::serialize(&unsigned)?;
+        Ok(*blake3::hash(&serialized).as_bytes())
+    }
+
     /// Get total size of all chunks
     pub fn total_chunk_size(&self) -> u64 {
         self.chunks.iter().map(|c| c.size as u64).sum()
@@ -123,11 +248,10 @@ impl FileMetadata {
         let mut seen_indices = HashSet::new();
         for chunk in &self.chunks {
             if !seen_indices.insert((chunk.stripe_index, chunk.shard_index)) {
-                anyhow::bail!(
-                    "Duplicate chunk index: stripe={}, shard={}",
-                    chunk.stripe_index,
-                    chunk.shard_index
-                );
+                return Err(MetadataError::DuplicateChunk {
+                    stripe: chunk.stripe_index,
+                    shard: chunk.shard_index,
+                });
             }
         }
 
@@ -135,6 +259,86 @@ impl FileMetadata {
     }
 }
 
+/// Domain separation for metadata signatures
+const METADATA_SIGNATURE_CONTEXT: &[u8] = b"saorsa-fec-metadata-v1";
+
+/// ML-DSA-65 signature over [`FileMetadata`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataSignature {
+    /// Signer's ML-DSA-65 public key
+    pub public_key: Vec<u8>,
+    /// Signature bytes
+    pub signature: Vec<u8>,
+}
+
+/// ML-DSA-65 keypair a creator signs metadata with
+#[derive(Clone)]
+pub struct MetadataSigner {
+    public_key: MlDsaPublicKey,
+    secret_key: MlDsaSecretKey,
+}
+
+impl MetadataSigner {
+    /// Generate a new signing keypair
+    pub fn generate() -> Result<Self> {
+        let (public_key, secret_key) = ml_dsa_65()
+            .generate_keypair()
+            .map_err(|e| MetadataError::Signing(format!("{:?}", e)))?;
+        Ok(Self {
+            public_key,
+            secret_key,
+        })
+    }
+
+    /// Load a keypair from its serialized public and secret keys
+    pub fn from_bytes(public_key: &[u8], secret_key: &[u8]) -> Result<Self> {
+        Ok(Self {
+            public_key: MlDsaPublicKey::from_bytes(MlDsaVariant::MlDsa65, public_key)
+                .map_err(|e| MetadataError::InvalidKey(format!("public key: {:?}", e)))?,
+            secret_key: MlDsaSecretKey::from_bytes(MlDsaVariant::MlDsa65, secret_key)
+                .map_err(|e| MetadataError::InvalidKey(format!("secret key: {:?}", e)))?,
+        })
+    }
+
+    /// Serialized public key, as recorded in signed metadata
+    pub fn public_key(&self) -> Vec<u8> {
+        self.public_key.to_bytes()
+    }
+
+    /// Serialized secret key
+    pub fn secret_key(&self) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new(self.secret_key.to_bytes())
+    }
+
+    /// Sign `digest` under the domain separation string `context`
+    pub(crate) fn sign_digest(&self, digest: &[u8; 32], context: &[u8]) -> Result<Vec<u8>> {
+        let signature = ml_dsa_65()
+            .sign_with_context(&self.secret_key, digest, context)
+            .map_err(|e| MetadataError::Signing(format!("{:?}", e)))?;
+        Ok(signature.to_bytes())
+    }
+}
+
+/// Check an ML-DSA-65 signature made with [`MetadataSigner::sign_digest`]
+pub(crate) fn verify_digest(
+    public_key: &[u8],
+    signature: &[u8],
+    digest: &[u8; 32],
+    context: &[u8],
+) -> Result<()> {
+    let public_key = MlDsaPublicKey::from_bytes(MlDsaVariant::MlDsa65, public_key)
+        .map_err(|e| MetadataError::InvalidKey(format!("signer public key: {:?}", e)))?;
+    let signature = MlDsaSignature::from_bytes(MlDsaVariant::MlDsa65, signature)
+        .map_err(|e| MetadataError::MalformedSignature(format!("{:?}", e)))?;
+    let valid = ml_dsa_65()
+        .verify_with_context(&public_key, digest, &signature, context)
+        .map_err(|e| MetadataError::MalformedSignature(format!("{:?}", e)))?;
+    if !valid {
+        return Err(MetadataError::InvalidSignature);
+    }
+    Ok(())
+}
+
 /// Reference to a chunk with its location information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChunkReference {
@@ -149,6 +353,9 @@ pub struct ChunkReference {
     /// Storage locations for this chunk
     #[serde(default)]
     pub storage_locations: Vec<StorageLocation>,
+    /// CIDs of the FEC shards for this chunk, ordered by share index
+    #[serde(default)]
+    pub shard_ids: Vec<[u8; 32]>,
 }
 
 impl ChunkReference {
@@ -160,9 +367,16 @@ impl ChunkReference {
             shard_index,
             size,
             storage_locations: Vec::new(),
+            shard_ids: Vec::new(),
         }
     }
 
+    /// Set the shard CIDs produced by FEC encoding
+    pub fn with_shards(mut self, shard_ids: Vec<[u8; 32]>) -> Self {
+        self.shard_ids = shard_ids;
+        self
+    }
+
     /// Add a storage location
     pub fn add_location(&mut self, location: StorageLocation) {
         if !self.storage_locations.iter().any(|l| l == &location) {
@@ -268,7 +482,7 @@ pub struct MetadataStore {
 impl MetadataStore {
     /// Create a new metadata store
     pub fn new(base_path: PathBuf) -> Result<Self> {
-        std::fs::create_dir_all(&base_path).context("Failed to create metadata directory")?;
+        std::fs::create_dir_all(&base_path).map_err(io_error(&base_path))?;
         Ok(Self { base_path })
     }
 
@@ -277,9 +491,9 @@ impl MetadataStore {
         let id = metadata.compute_id();
         let path = self.metadata_path(&id);
 
-        let data = bincode::serialize(metadata).context("Failed to serialize metadata")?;
+        let data = bincode::serialize(metadata)?;
 
-        std::fs::write(path, data).context("Failed to write metadata")?;
+        std::fs::write(&path, data).map_err(io_error(&path))?;
 
         Ok(())
     }
@@ -288,9 +502,12 @@ impl MetadataStore {
     pub fn load(&self, id: &[u8; 32]) -> Result<FileMetadata> {
         let path = self.metadata_path(id);
 
-        let data = std::fs::read(path).context("Failed to read metadata")?;
+        let data = std::fs::read(&path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => MetadataError::NotFound(hex::encode(id)),
+            _ => io_error(&path)(e),
+        })?;
 
-        let metadata = bincode::deserialize(&data).context("Failed to deserialize metadata")?;
+        let metadata = bincode::deserialize(&data)?;
 
         Ok(metadata)
     }
@@ -304,7 +521,7 @@ impl MetadataStore {
     pub fn delete(&self, id: &[u8; 32]) -> Result<()> {
         let path = self.metadata_path(id);
         if path.exists() {
-            std::fs::remove_file(path).context("Failed to delete metadata")?;
+            std::fs::remove_file(&path).map_err(io_error(&path))?;
         }
         Ok(())
     }
@@ -313,8 +530,8 @@ impl MetadataStore {
     pub fn list_ids(&self) -> Result<Vec<[u8; 32]>> {
         let mut ids = Vec::new();
 
-        for entry in std::fs::read_dir(&self.base_path)? {
-            let entry = entry?;
+        for entry in std::fs::read_dir(&self.base_path).map_err(io_error(&self.base_path))? {
+            let entry = entry.map_err(io_error(&self.base_path))?;
             if let Some(name) = entry.file_name().to_str() {
                 if name.ends_with(".meta") && name.len() == 68 {
                     // 64 hex chars + ".meta"
@@ -434,6 +651,34 @@ mod tests {
         // Delete and verify
         store.delete(&id).unwrap();
         assert!(!store.exists(&id));
+        assert!(matches!(store.load(&id), Err(MetadataError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_metadata_signature() {
+        let signer = MetadataSigner::generate().unwrap();
+        let mut metadata = FileMetadata::new(
+            [42u8; 32],
+            1024,
+            None,
+            vec![ChunkReference::new([1u8; 32], 0, 0, 1024).with_shards(vec![[7u8; 32]])],
+        );
+        assert!(matches!(
+            metadata.verify_signature(),
+            Err(MetadataError::NotSigned)
+        ));
+
+        metadata.sign(&signer).unwrap();
+        assert_eq!(metadata.verify_signature().unwrap(), signer.public_key());
+
+        // Local metadata is not signed, shard locations are
+        let mut relabelled = metadata.clone().with_local_metadata(LocalMetadata::new());
+        assert!(relabelled.verify_signature().is_ok());
+        relabelled.chunks[0].shard_ids[0] = [8u8; 32];
+        assert!(matches!(
+            relabelled.verify_signature(),
+            Err(MetadataError::InvalidSignature)
+        ));
     }
 
     #[test]
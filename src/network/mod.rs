@@ -0,0 +1,357 @@
+//! Wire protocol for networked shard storage
+//!
+//! Nodes exchange bincode-encoded [`Request`]/[`Response`] pairs, one pair per
+//! stream. Every request is wrapped in an [`Envelope`] carrying the protocol
+//! version and a BLAKE3 MAC keyed with a pre-shared token, so a node only
+//! serves peers that hold the same token.
+//!
+//! The MAC also covers a per-connection sequence number and a value bound to
+//! the connection, such as a TLS exporter secret. A node accepts each
+//! sequence number once per connection through a [`ReplayWindow`], so a
+//! captured request can neither be replayed on its connection nor on another.
+//!
+//! The transport is abstracted behind [`NodeTransport`]; the QUIC
+//! implementation lives in [`quic`] and is enabled with the `quic` feature.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use crate::storage::{Cid, FileMetadata, GcReport, NodeEndpoint, StorageBackend, StorageStats};
+use crate::FecError;
+
+#[cfg(feature = "quic")]
+pub mod quic;
+
+/// Current wire protocol version
+pub const PROTOCOL_VERSION: u8 = 2;
+
+/// Largest message accepted on the wire (64 MiB)
+pub const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Sequence numbers a [`ReplayWindow`] tracks above its floor, bounding how
+/// far concurrent requests on one connection may arrive out of order
+pub const REPLAY_WINDOW: usize = 4096;
+
+/// Storage RPCs understood by a node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Request {
+    /// Store a serialized shard
+    PutShard { cid: Cid, shard: Vec<u8> },
+    /// Fetch a serialized shard
+    GetShard { cid: Cid },
+    /// Check whether a shard exists
+    HasShard { cid: Cid },
+    /// Delete a shard
+    DeleteShard { cid: Cid },
+    /// List every shard CID held by the node
+    ListShards,
+    /// Store file metadata
+    PutMetadata { metadata: FileMetadata },
+    /// Fetch file metadata
+    GetMetadata { file_id: [u8; 32] },
+    /// Delete file metadata
+    DeleteMetadata { file_id: [u8; 32] },
+    /// List all file metadata held by the node
+    ListMetadata,
+    /// Report storage statistics
+    Stats,
+    /// Run garbage collection on the node
+    GarbageCollect,
+    /// Liveness probe; answered with [`Response::Ok`]
+    Ping,
+}
+
+impl Request {
+    /// Whether running the request twice has the effect of running it once
+    ///
+    /// Shards are content-addressed, so storing one again rewrites the same
+    /// bytes. A repeated delete would report the first one's success as not
+    /// found, and a repeated collection does a second pass of work.
+    pub fn is_idempotent(&self) -> bool {
+        !matches!(
+            self,
+            Self::DeleteShard { .. } | Self::DeleteMetadata { .. } | Self::GarbageCollect
+        )
+    }
+}
+
+/// Replies sent by a node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Response {
+    /// The operation succeeded with no payload
+    Ok,
+    /// A serialized shard
+    Shard(Vec<u8>),
+    /// Result of an existence check
+    Exists(bool),
+    /// Shard CIDs
+    Shards(Vec<Cid>),
+    /// File metadata
+    Metadata(FileMetadata),
+    /// A list of file metadata
+    MetadataList(Vec<FileMetadata>),
+    /// Storage statistics
+    Stats(StorageStats),
+    /// Garbage collection report
+    GcReport(GcReport),
+    /// The operation failed on the node
+    Error(String),
+}
+
+/// Authenticated request wrapper
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    /// Protocol version of the sender
+    pub version: u8,
+    /// Position of the request on its connection, starting at 1
+    pub seq: u64,
+    /// BLAKE3 MAC of the sequence number, channel binding and `body`, keyed
+    /// with the shared token
+    pub mac: [u8; 32],
+    /// bincode-encoded [`Request`]
+    pub body: Vec<u8>,
+}
+
+impl Envelope {
+    /// Seal request number `seq` of the connection identified by `channel`
+    /// with the shared token
+    pub fn seal(
+        request: &Request,
+        token: &[u8; 32],
+        channel: &[u8; 32],
+        seq: u64,
+    ) -> Result<Self, FecError> {
+        let body = encode(request)?;
+        Ok(Self {
+            version: PROTOCOL_VERSION,
+            seq,
+            mac: mac(token, channel, seq, &body),
+            body,
+        })
+    }
+
+    /// Verify the version and MAC and decode the request
+    ///
+    /// Sequence numbers are only authenticated here; pass them through the
+    /// connection's [`ReplayWindow`] to reject replays.
+    pub fn open(&self, token: &[u8; 32], channel: &[u8; 32]) -> Result<Request, FecError> {
+        if self.version != PROTOCOL_VERSION {
+            return Err(FecError::Backend(format!(
+                "Unsupported protocol version {}",
+                self.version
+            )));
+        }
+        // blake3::Hash comparison is constant-time
+        let expected = blake3::Hash::from(mac(token, channel, self.seq, &self.body));
+        if expected != blake3::Hash::from(self.mac) {
+            return Err(FecError::Backend(
+                "Request authentication failed".to_string(),
+            ));
+        }
+        decode(&self.body)
+    }
+}
+
+fn mac(token: &[u8; 32], channel: &[u8; 32], seq: u64, body: &[u8]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new_keyed(token);
+    hasher.update(channel);
+    hasher.update(&seq.to_le_bytes());
+    hasher.update(body);
+    *hasher.finalize().as_bytes()
+}
+
+/// Sequence numbers already accepted on one connection
+///
+/// Numbers may arrive out of order, as each request has its own stream, but
+/// at most [`REPLAY_WINDOW`] apart; older ones are rejected.
+#[derive(Debug, Default)]
+pub struct ReplayWindow {
+    /// Every number up to and including this one counts as seen
+    floor: u64,
+    seen: BTreeSet<u64>,
+}
+
+impl ReplayWindow {
+    /// Record `seq`, returning `false` if it was seen before or is too old
+    pub fn accept(&mut self, seq: u64) -> bool {
+        if seq <= self.floor || !self.seen.insert(seq) {
+            return false;
+        }
+        while self.seen.len() > REPLAY_WINDOW {
+            if let Some(oldest) = self.seen.pop_first() {
+                self.floor = oldest;
+            }
+        }
+        true
+    }
+}
+
+/// Encode a protocol message
+pub fn encode<T: Serialize>(message: &T) -> Result<Vec<u8>, FecError> {
+    bincode::serialize(message).map_err(|e| FecError::Backend(format!("Encode failed: {}", e)))
+}
+
+/// Decode a protocol message
+pub fn decode<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T, FecError> {
+    if bytes.len() > MAX_MESSAGE_SIZE {
+        return Err(FecError::Backend(format!(
+            "Message of {} bytes exceeds limit",
+            bytes.len()
+        )));
+    }
+    bincode::deserialize(bytes).map_err(|e| FecError::Backend(format!("Decode failed: {}", e)))
+}
+
+/// Serve a single request from a local backend
+pub async fn handle_request<B: StorageBackend + ?Sized>(backend: &B, request: Request) -> Response {
+    let result = match request {
+        Request::PutShard { cid, shard } => {
+            match crate::storage::Shard::from_bytes(&shard).and_then(|shard| {
+                // Content addressing only holds if peers cannot pick the CID
+                shard.verify(&cid)?;
+                Ok(shard)
+            }) {
+                Ok(shard) => backend.put_shard(&cid, &shard).await.map(|_| Response::Ok),
+                Err(e) => Err(e),
+            }
+        }
+        Request::GetShard { cid } => match backend.get_shard(&cid).await {
+            Ok(shard) => shard.to_bytes().map(Response::Shard),
+            Err(e) => Err(e),
+        },
+        Request::HasShard { cid } => backend.has_shard(&cid).await.map(Response::Exists),
+        Request::DeleteShard { cid } => backend.delete_shard(&cid).await.map(|_| Response::Ok),
+        Request::ListShards => backend.list_shards().await.map(Response::Shards),
+        Request::PutMetadata { metadata } => {
+            backend.put_metadata(&metadata).await.map(|_| Response::Ok)
+        }
+        Request::GetMetadata { file_id } => {
+            backend.get_metadata(&file_id).await.map(Response::Metadata)
+        }
+        Request::DeleteMetadata { file_id } => backend
+            .delete_metadata(&file_id)
+            .await
+            .map(|_| Response::Ok),
+        Request::ListMetadata => backend.list_metadata().await.map(Response::MetadataList),
+        Request::Stats => backend.stats().await.map(Response::Stats),
+        Request::GarbageCollect => backend.garbage_collect().await.map(Response::GcReport),
+        Request::Ping => Ok(Response::Ok),
+    };
+
+    result.unwrap_or_else(|e| Response::Error(e.to_string()))
+}
+
+/// Transport used by [`NetworkStorage`](crate::storage::NetworkStorage) to reach nodes
+#[async_trait]
+pub trait NodeTransport: Send + Sync {
+    /// Send a request to a node and wait for its response
+    async fn call(&self, node: &NodeEndpoint, request: Request) -> Result<Response, FecError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{MemoryStorage, Shard, ShardHeader};
+    use crate::EncryptionMode;
+
+    #[test]
+    fn test_envelope_roundtrip() {
+        let token = [7u8; 32];
+        let request = Request::HasShard {
+            cid: Cid::new([1u8; 32]),
+        };
+
+        let envelope = Envelope::seal(&request, &token, &[0u8; 32], 1).unwrap();
+        assert!(matches!(
+            envelope.open(&token, &[0u8; 32]).unwrap(),
+            Request::HasShard { .. }
+        ));
+    }
+
+    #[test]
+    fn test_only_deletes_and_collection_are_not_idempotent() {
+        let cid = Cid::new([1u8; 32]);
+        assert!(Request::GetShard { cid }.is_idempotent());
+        assert!(Request::PutShard {
+            cid,
+            shard: vec![1, 2, 3]
+        }
+        .is_idempotent());
+        assert!(!Request::DeleteShard { cid }.is_idempotent());
+        assert!(!Request::DeleteMetadata { file_id: [2u8; 32] }.is_idempotent());
+        assert!(!Request::GarbageCollect.is_idempotent());
+    }
+
+    #[test]
+    fn test_envelope_rejects_wrong_token() {
+        let channel = [3u8; 32];
+        let envelope = Envelope::seal(&Request::ListShards, &[1u8; 32], &channel, 1).unwrap();
+        assert!(envelope.open(&[2u8; 32], &channel).is_err());
+
+        let mut tampered = envelope.clone();
+        tampered.version = PROTOCOL_VERSION + 1;
+        assert!(tampered.open(&[1u8; 32], &channel).is_err());
+
+        // The sequence number and connection are covered by the MAC
+        let mut renumbered = envelope.clone();
+        renumbered.seq = 2;
+        assert!(renumbered.open(&[1u8; 32], &channel).is_err());
+        assert!(envelope.open(&[1u8; 32], &[4u8; 32]).is_err());
+    }
+
+    #[test]
+    fn test_replay_window_accepts_each_number_once() {
+        let mut window = ReplayWindow::default();
+        assert!(!window.accept(0));
+        assert!(window.accept(2));
+        assert!(window.accept(1));
+        assert!(!window.accept(2));
+        for seq in 3..(REPLAY_WINDOW as u64 + 10) {
+            assert!(window.accept(seq));
+        }
+        // Numbers that fell out of the window count as seen
+        assert!(!window.accept(5));
+        assert!(window.accept(REPLAY_WINDOW as u64 + 20));
+    }
+
+    #[tokio::test]
+    async fn test_handle_request_against_backend() {
+        let backend = MemoryStorage::new();
+        let header = ShardHeader::new(EncryptionMode::Convergent, (4, 2), 8, [0u8; 32]);
+        let shard = Shard::new(header, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        let cid = shard.cid().unwrap();
+
+        let put = Request::PutShard {
+            cid,
+            shard: shard.to_bytes().unwrap(),
+        };
+        assert!(matches!(handle_request(&backend, put).await, Response::Ok));
+
+        match handle_request(&backend, Request::GetShard { cid }).await {
+            Response::Shard(bytes) => {
+                assert_eq!(Shard::from_bytes(&bytes).unwrap().data, shard.data)
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        let missing = Request::GetShard {
+            cid: Cid::new([9u8; 32]),
+        };
+        assert!(matches!(
+            handle_request(&backend, missing).await,
+            Response::Error(_)
+        ));
+
+        // A shard stored under another CID is refused
+        let mislabelled = Request::PutShard {
+            cid: Cid::new([9u8; 32]),
+            shard: shard.to_bytes().unwrap(),
+        };
+        assert!(matches!(
+            handle_request(&backend, mislabelled).await,
+            Response::Error(_)
+        ));
+        assert!(!backend.has_shard(&Cid::new([9u8; 32])).await.unwrap());
+    }
+}
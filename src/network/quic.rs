@@ -0,0 +1,462 @@
+//! QUIC transport and server for networked shard storage
+//!
+//! [`QuicServer`] exposes any [`StorageBackend`] to the network and
+//! [`QuicTransport`] lets [`NetworkStorage`](crate::storage::NetworkStorage)
+//! talk to such servers. Connections are pooled per node and each request
+//! runs on its own bidirectional stream, so concurrent calls to the same
+//! node share a single connection.
+//!
+//! Both sides bind request MACs to the connection through a TLS exporter
+//! secret, and the server accepts each request sequence number once per
+//! connection.
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use quinn::{ClientConfig, Connection, Endpoint, ServerConfig};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+use tokio::task::JoinHandle;
+
+pub use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+use super::{
+    decode, encode, handle_request, Envelope, NodeTransport, ReplayWindow, Request, Response,
+    MAX_MESSAGE_SIZE,
+};
+use crate::storage::{NodeEndpoint, StorageBackend};
+use crate::FecError;
+
+/// TLS exporter label for the channel binding covered by request MACs
+const CHANNEL_LABEL: &[u8] = b"saorsa-fec request mac";
+
+/// Secret unique to a connection, known to both of its ends
+fn channel_binding(connection: &Connection) -> Result<[u8; 32], FecError> {
+    let mut channel = [0u8; 32];
+    connection
+        .export_keying_material(&mut channel, CHANNEL_LABEL, &[])
+        .map_err(|_| FecError::Backend("Channel binding unavailable".to_string()))?;
+    Ok(channel)
+}
+
+/// Generate a self-signed certificate and key for the given host names
+pub fn generate_self_signed(
+    names: Vec<String>,
+) -> Result<(CertificateDer<'static>, PrivateKeyDer<'static>), FecError> {
+    let certified = rcgen::generate_simple_self_signed(names)
+        .map_err(|e| FecError::Backend(format!("Certificate generation failed: {}", e)))?;
+    let key = PrivateKeyDer::try_from(certified.key_pair.serialize_der())
+        .map_err(|e| FecError::Backend(format!("Invalid private key: {}", e)))?;
+    Ok((certified.cert.der().clone(), key))
+}
+
+/// Configuration for a [`QuicServer`]
+pub struct QuicServerConfig {
+    /// Address to listen on
+    pub bind_addr: SocketAddr,
+    /// TLS certificate chain presented to clients
+    pub cert_chain: Vec<CertificateDer<'static>>,
+    /// Private key for the leaf certificate
+    pub private_key: PrivateKeyDer<'static>,
+    /// Pre-shared token clients must authenticate with
+    pub auth_token: [u8; 32],
+}
+
+/// Server exposing a storage backend over QUIC
+pub struct QuicServer {
+    endpoint: Endpoint,
+    task: JoinHandle<()>,
+}
+
+impl QuicServer {
+    /// Bind the server and start accepting connections on the current runtime
+    pub fn bind<B: StorageBackend + 'static>(
+        config: QuicServerConfig,
+        backend: Arc<B>,
+    ) -> Result<Self, FecError> {
+        let server_config =
+            ServerConfig::with_single_cert(config.cert_chain, config.private_key)
+                .map_err(|e| FecError::Backend(format!("Invalid server certificate: {}", e)))?;
+        let endpoint = Endpoint::server(server_config, config.bind_addr)?;
+
+        let token = config.auth_token;
+        let accept_endpoint = endpoint.clone();
+        let task = tokio::spawn(async move {
+            while let Some(incoming) = accept_endpoint.accept().await {
+                let backend = backend.clone();
+                tokio::spawn(async move {
+                    match incoming.await {
+                        Ok(connection) => serve_connection(connection, backend, token).await,
+                        Err(e) => tracing::debug!("Incoming connection failed: {}", e),
+                    }
+                });
+            }
+        });
+
+        Ok(Self { endpoint, task })
+    }
+
+    /// Address the server is listening on
+    pub fn local_addr(&self) -> Result<SocketAddr, FecError> {
+        Ok(self.endpoint.local_addr()?)
+    }
+
+    /// Close all connections and stop accepting new ones
+    pub async fn shutdown(self) {
+        self.endpoint.close(0u32.into(), b"shutdown");
+        self.task.abort();
+        self.endpoint.wait_idle().await;
+    }
+}
+
+/// Serve every request stream opened on a connection
+async fn serve_connection<B: StorageBackend + 'static>(
+    connection: Connection,
+    backend: Arc<B>,
+    token: [u8; 32],
+) {
+    let channel = match channel_binding(&connection) {
+        Ok(channel) => channel,
+        Err(e) => {
+            tracing::debug!("Dropping connection: {}", e);
+            connection.close(1u32.into(), b"no channel binding");
+            return;
+        }
+    };
+    let window = Arc::new(Mutex::new(ReplayWindow::default()));
+    while let Ok((mut send, mut recv)) = connection.accept_bi().await {
+        let backend = backend.clone();
+        let window = window.clone();
+        tokio::spawn(async move {
+            let result = async {
+                let bytes = recv
+                    .read_to_end(MAX_MESSAGE_SIZE)
+                    .await
+                    .map_err(|e| FecError::Backend(format!("Read failed: {}", e)))?;
+                let envelope: Envelope = decode(&bytes)?;
+                let response = match envelope.open(&token, &channel) {
+                    Ok(_) if !window.lock().accept(envelope.seq) => {
+                        Response::Error("Replayed request".to_string())
+                    }
+                    Ok(request) => handle_request(backend.as_ref(), request).await,
+                    Err(e) => Response::Error(e.to_string()),
+                };
+                send.write_all(&encode(&response)?)
+                    .await
+                    .map_err(|e| FecError::Backend(format!("Write failed: {}", e)))?;
+                send.finish()
+                    .map_err(|e| FecError::Backend(format!("Finish failed: {}", e)))
+            }
+            .await;
+
+            if let Err(e) = result {
+                tracing::debug!("Request stream failed: {}", e);
+            }
+        });
+    }
+}
+
+/// Configuration for a [`QuicTransport`]
+pub struct QuicClientConfig {
+    /// Certificates trusted as server identities
+    pub trusted_certs: Vec<CertificateDer<'static>>,
+    /// Pre-shared token used to authenticate requests
+    pub auth_token: [u8; 32],
+    /// TLS server name to verify; defaults to each node's address
+    pub server_name: Option<String>,
+}
+
+/// Pooled connection to one node
+struct Session {
+    connection: Connection,
+    channel: [u8; 32],
+    next_seq: AtomicU64,
+}
+
+impl Session {
+    /// Seal the next request on this connection
+    fn seal(&self, request: &Request, token: &[u8; 32]) -> Result<Vec<u8>, FecError> {
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        encode(&Envelope::seal(request, token, &self.channel, seq)?)
+    }
+}
+
+/// Connection slot for a node; concurrent callers wait on the same connect
+type Slot = Arc<OnceCell<Arc<Session>>>;
+
+/// QUIC client transport with per-node connection pooling
+pub struct QuicTransport {
+    endpoint: Endpoint,
+    auth_token: [u8; 32],
+    server_name: Option<String>,
+    connections: Mutex<HashMap<(String, u16), Slot>>,
+}
+
+impl QuicTransport {
+    /// Create a client endpoint on the current runtime
+    pub fn new(config: QuicClientConfig) -> Result<Self, FecError> {
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in config.trusted_certs {
+            roots
+                .add(cert)
+                .map_err(|e| FecError::Backend(format!("Invalid trusted certificate: {}", e)))?;
+        }
+        let client_config = ClientConfig::with_root_certificates(Arc::new(roots))
+            .map_err(|e| FecError::Backend(format!("Invalid client configuration: {}", e)))?;
+
+        let mut endpoint = Endpoint::client(SocketAddr::from(([0, 0, 0, 0], 0)))?;
+        endpoint.set_default_client_config(client_config);
+
+        Ok(Self {
+            endpoint,
+            auth_token: config.auth_token,
+            server_name: config.server_name,
+            connections: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Get a pooled connection to a node, connecting if needed
+    ///
+    /// The pool lock is only held to find the node's slot, so resolving and
+    /// connecting to one node never holds up calls to the others.
+    async fn session(&self, node: &NodeEndpoint) -> Result<Arc<Session>, FecError> {
+        let session = self.pooled(node).await?;
+        if session.connection.close_reason().is_none() {
+            return Ok(session);
+        }
+        self.evict(node, &session);
+        self.pooled(node).await
+    }
+
+    async fn pooled(&self, node: &NodeEndpoint) -> Result<Arc<Session>, FecError> {
+        let slot = self
+            .connections
+            .lock()
+            .entry((node.address.clone(), node.port))
+            .or_default()
+            .clone();
+        slot.get_or_try_init(|| self.connect(node)).await.cloned()
+    }
+
+    /// Drop `session` from the pool unless it was already replaced
+    fn evict(&self, node: &NodeEndpoint, session: &Arc<Session>) {
+        let key = (node.address.clone(), node.port);
+        let mut connections = self.connections.lock();
+        let current = connections
+            .get(&key)
+            .and_then(|slot| slot.get())
+            .is_some_and(|pooled| Arc::ptr_eq(pooled, session));
+        if current {
+            connections.remove(&key);
+        }
+    }
+
+    async fn connect(&self, node: &NodeEndpoint) -> Result<Arc<Session>, FecError> {
+        let addr = tokio::net::lookup_host((node.address.as_str(), node.port))
+            .await?
+            .next()
+            .ok_or_else(|| {
+                FecError::Backend(format!("Cannot resolve {}:{}", node.address, node.port))
+            })?;
+        let server_name = self.server_name.as_deref().unwrap_or(&node.address);
+        let connection = self
+            .endpoint
+            .connect(addr, server_name)
+            .map_err(|e| FecError::Backend(format!("Connect failed: {}", e)))?
+            .await
+            .map_err(|e| FecError::Backend(format!("Connect failed: {}", e)))?;
+
+        Ok(Arc::new(Session {
+            channel: channel_binding(&connection)?,
+            connection,
+            next_seq: AtomicU64::new(1),
+        }))
+    }
+
+    /// Run one request/response exchange on a fresh stream
+    async fn exchange(connection: &Connection, envelope: &[u8]) -> Result<Vec<u8>, ExchangeError> {
+        let (mut send, mut recv) = connection.open_bi().await.map_err(|e| ExchangeError {
+            error: FecError::Backend(format!("Open stream failed: {}", e)),
+            sent: false,
+        })?;
+        let sent = |error| ExchangeError { error, sent: true };
+        send.write_all(envelope)
+            .await
+            .map_err(|e| sent(FecError::Backend(format!("Write failed: {}", e))))?;
+        send.finish()
+            .map_err(|e| sent(FecError::Backend(format!("Finish failed: {}", e))))?;
+        recv.read_to_end(MAX_MESSAGE_SIZE)
+            .await
+            .map_err(|e| sent(FecError::Backend(format!("Read failed: {}", e))))
+    }
+}
+
+/// Failed exchange, and whether the request may have reached the node
+#[derive(Debug)]
+struct ExchangeError {
+    error: FecError,
+    /// Set once any of the request was written to a stream
+    sent: bool,
+}
+
+#[async_trait]
+impl NodeTransport for QuicTransport {
+    async fn call(&self, node: &NodeEndpoint, request: Request) -> Result<Response, FecError> {
+        let session = self.session(node).await?;
+        let envelope = session.seal(&request, &self.auth_token)?;
+        let reply = match Self::exchange(&session.connection, &envelope).await {
+            Ok(reply) => reply,
+            // The node may have run the request, so only repeat it if that
+            // is harmless
+            Err(e) if e.sent && !request.is_idempotent() => return Err(e.error),
+            Err(e) => {
+                // The pooled connection may have gone stale; reconnect once
+                tracing::debug!(
+                    "Retrying {}:{} after error: {}",
+                    node.address,
+                    node.port,
+                    e.error
+                );
+                self.evict(node, &session);
+                let session = self.session(node).await?;
+                let envelope = session.seal(&request, &self.auth_token)?;
+                Self::exchange(&session.connection, &envelope)
+                    .await
+                    .map_err(|e| e.error)?
+            }
+        };
+
+        decode(&reply)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{Cid, MemoryStorage, NetworkStorage, Shard, ShardHeader};
+    use crate::EncryptionMode;
+
+    fn start_server(token: [u8; 32]) -> (QuicServer, CertificateDer<'static>, Arc<MemoryStorage>) {
+        let (cert, key) = generate_self_signed(vec!["localhost".to_string()]).unwrap();
+        let backend = Arc::new(MemoryStorage::new());
+        let server = QuicServer::bind(
+            QuicServerConfig {
+                bind_addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+                cert_chain: vec![cert.clone()],
+                private_key: key,
+                auth_token: token,
+            },
+            backend.clone(),
+        )
+        .unwrap();
+        (server, cert, backend)
+    }
+
+    fn client(
+        server: &QuicServer,
+        cert: CertificateDer<'static>,
+        token: [u8; 32],
+    ) -> NetworkStorage {
+        let transport = QuicTransport::new(QuicClientConfig {
+            trusted_certs: vec![cert],
+            auth_token: token,
+            server_name: Some("localhost".to_string()),
+        })
+        .unwrap();
+        let node = NodeEndpoint {
+            address: "127.0.0.1".to_string(),
+            port: server.local_addr().unwrap().port(),
+            node_id: None,
+        };
+        NetworkStorage::new(vec![node], 1).with_transport(Arc::new(transport))
+    }
+
+    #[tokio::test]
+    async fn test_quic_roundtrip() {
+        let token = [5u8; 32];
+        let (server, cert, backend) = start_server(token);
+        let storage = client(&server, cert, token);
+
+        let header = ShardHeader::new(EncryptionMode::Convergent, (4, 2), 16, [0u8; 32]);
+        let shard = Shard::new(header, vec![3u8; 16]);
+        let cid = shard.cid().unwrap();
+
+        storage.put_shard(&cid, &shard).await.unwrap();
+        assert_eq!(backend.shard_count(), 1);
+        assert!(storage.has_shard(&cid).await.unwrap());
+        assert_eq!(storage.get_shard(&cid).await.unwrap().data, shard.data);
+        assert_eq!(storage.list_shards().await.unwrap(), vec![cid]);
+
+        storage.delete_shard(&cid).await.unwrap();
+        assert!(!storage.has_shard(&Cid::new(*cid.as_bytes())).await.unwrap());
+
+        server.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_quic_rejects_wrong_token() {
+        let (server, cert, backend) = start_server([1u8; 32]);
+        let storage = client(&server, cert, [2u8; 32]);
+
+        let header = ShardHeader::new(EncryptionMode::Convergent, (4, 2), 4, [0u8; 32]);
+        let shard = Shard::new(header, vec![1u8; 4]);
+        let cid = shard.cid().unwrap();
+
+        let err = storage.put_shard(&cid, &shard).await.unwrap_err();
+        assert!(err.to_string().contains("authentication"));
+        assert_eq!(backend.shard_count(), 0);
+
+        server.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_quic_rejects_replayed_requests() {
+        let token = [6u8; 32];
+        let (server, cert, backend) = start_server(token);
+        let transport = QuicTransport::new(QuicClientConfig {
+            trusted_certs: vec![cert],
+            auth_token: token,
+            server_name: Some("localhost".to_string()),
+        })
+        .unwrap();
+        let node = NodeEndpoint {
+            address: "127.0.0.1".to_string(),
+            port: server.local_addr().unwrap().port(),
+            node_id: None,
+        };
+
+        let header = ShardHeader::new(EncryptionMode::Convergent, (4, 2), 4, [0u8; 32]);
+        let shard = Shard::new(header, vec![2u8; 4]);
+        let cid = shard.cid().unwrap();
+        let request = Request::PutShard {
+            cid,
+            shard: shard.to_bytes().unwrap(),
+        };
+
+        let session = transport.session(&node).await.unwrap();
+        let envelope = session.seal(&request, &token).unwrap();
+        let reply = QuicTransport::exchange(&session.connection, &envelope).await;
+        assert!(matches!(decode(&reply.unwrap()).unwrap(), Response::Ok));
+        backend.delete_shard(&cid).await.unwrap();
+
+        // The same envelope is refused on its own connection and on another
+        let reply = QuicTransport::exchange(&session.connection, &envelope).await;
+        assert!(matches!(
+            decode(&reply.unwrap()).unwrap(),
+            Response::Error(_)
+        ));
+        transport.evict(&node, &session);
+        let fresh = transport.session(&node).await.unwrap();
+        assert!(!Arc::ptr_eq(&fresh, &session));
+        let reply = QuicTransport::exchange(&fresh.connection, &envelope).await;
+        assert!(matches!(
+            decode(&reply.unwrap()).unwrap(),
+            Response::Error(_)
+        ));
+        assert_eq!(backend.shard_count(), 0);
+
+        server.shutdown().await;
+    }
+}
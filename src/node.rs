@@ -0,0 +1,191 @@
+// Copyright 2024 Saorsa Labs
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Node.js bindings
+//!
+//! With the `node` feature the crate builds as an N-API addon, so Electron
+//! and other desktop JavaScript applications can code shares and run a
+//! [`StoragePipeline`](crate::StoragePipeline) in process:
+//!
+//! ```text
+//! cargo rustc --release --lib --features node --crate-type cdylib
+//! cp target/release/libsaorsa_fec.so saorsa_fec.node
+//! ```
+//!
+//! ```js
+//! const fec = require('./saorsa_fec.node');
+//! const shares = fec.encode(data, 8, 2);
+//! const pipeline = await fec.StoragePipeline.open({ path: './shards' });
+//! const metadata = await pipeline.store(data);
+//! const copy = await pipeline.retrieve(metadata);
+//! ```
+//!
+//! File metadata is handed to JavaScript as JSON, which the application
+//! keeps to retrieve the file later.
+
+use std::fmt::Display;
+use std::sync::Arc;
+
+use napi::bindgen_prelude::Buffer;
+use napi_derive::napi;
+use tokio::sync::Mutex;
+
+use crate::metadata::FileMetadata;
+use crate::storage::{LocalStorage, MemoryStorage, StorageBackend};
+use crate::{Config, FecCodec, FecParams, StoragePipeline};
+
+fn js_error(e: impl Display) -> napi::Error {
+    napi::Error::from_reason(e.to_string())
+}
+
+fn codec(data_shares: u16, parity_shares: u16) -> crate::Result<FecCodec> {
+    FecParams::new(data_shares, parity_shares).and_then(FecCodec::new)
+}
+
+fn encode_shares(data: &[u8], data_shares: u16, parity_shares: u16) -> crate::Result<Vec<Vec<u8>>> {
+    let codec = codec(data_shares, parity_shares)?;
+    // The coder needs shares of even length; decode trims the padding
+    let mut data = data.to_vec();
+    data.resize(data.len().next_multiple_of(2 * data_shares as usize), 0);
+    codec.encode(&data)
+}
+
+fn decode_shares(
+    shares: &[Option<Vec<u8>>],
+    data_shares: u16,
+    parity_shares: u16,
+    length: usize,
+) -> crate::Result<Vec<u8>> {
+    let mut data = codec(data_shares, parity_shares)?.decode(shares)?;
+    if length > data.len() {
+        return Err(crate::FecError::SizeMismatch {
+            expected: length,
+            actual: data.len(),
+        });
+    }
+    data.truncate(length);
+    Ok(data)
+}
+
+/// Split `data` into `dataShares` data shares followed by `parityShares`
+/// parity shares
+#[napi]
+pub fn encode(data: Buffer, data_shares: u16, parity_shares: u16) -> napi::Result<Vec<Buffer>> {
+    let shares = encode_shares(&data, data_shares, parity_shares).map_err(js_error)?;
+    Ok(shares.into_iter().map(Buffer::from).collect())
+}
+
+/// Recover `length` bytes from shares in encoding order, with `null` for
+/// each missing share
+#[napi]
+pub fn decode(
+    shares: Vec<Option<Buffer>>,
+    data_shares: u16,
+    parity_shares: u16,
+    length: i64,
+) -> napi::Result<Buffer> {
+    let shares: Vec<Option<Vec<u8>>> = shares
+        .into_iter()
+        .map(|share| share.map(|share| share.to_vec()))
+        .collect();
+    let length = usize::try_from(length).map_err(js_error)?;
+    let data = decode_shares(&shares, data_shares, parity_shares, length).map_err(js_error)?;
+    Ok(data.into())
+}
+
+/// Where and how a [`NodePipeline`] stores files
+#[napi(object)]
+pub struct PipelineOptions {
+    /// Directory to keep shards in; they are held in memory when absent
+    pub path: Option<String>,
+    /// Named configuration profile, see [`Config::profile`]
+    pub profile: Option<String>,
+}
+
+/// A storage pipeline, exposed to JavaScript as `StoragePipeline`
+#[napi(js_name = "StoragePipeline")]
+pub struct NodePipeline {
+    inner: Arc<Mutex<StoragePipeline<Arc<dyn StorageBackend>>>>,
+}
+
+#[napi]
+impl NodePipeline {
+    /// Open a pipeline over a local directory or memory
+    #[napi(factory)]
+    pub async fn open(options: Option<PipelineOptions>) -> napi::Result<Self> {
+        let (path, profile) = options.map_or((None, None), |o| (o.path, o.profile));
+        let config = match profile {
+            Some(name) => Config::profile(&name)
+                .ok_or_else(|| js_error(format!("Unknown profile: {}", name)))?,
+            None => Config::default(),
+        };
+        let backend: Arc<dyn StorageBackend> = match path {
+            Some(path) => Arc::new(LocalStorage::new(path.into()).await.map_err(js_error)?),
+            None => Arc::new(MemoryStorage::new()),
+        };
+        let pipeline = StoragePipeline::builder(config, backend)
+            .build()
+            .map_err(js_error)?;
+        Ok(Self {
+            inner: Arc::new(Mutex::new(pipeline)),
+        })
+    }
+
+    /// Store `data`, returning its metadata as JSON
+    ///
+    /// The file ID defaults to the BLAKE3 hash of `data`.
+    #[napi]
+    pub async fn store(&self, data: Buffer, file_id: Option<Buffer>) -> napi::Result<String> {
+        let file_id = match file_id {
+            Some(id) => <[u8; 32]>::try_from(id.as_ref())
+                .map_err(|_| js_error("File ID must be 32 bytes"))?,
+            None => *blake3::hash(&data).as_bytes(),
+        };
+        let metadata = self
+            .inner
+            .lock()
+            .await
+            .process_file(file_id, &data, None)
+            .await
+            .map_err(js_error)?;
+        serde_json::to_string(&metadata).map_err(js_error)
+    }
+
+    /// Retrieve a file from the metadata JSON returned by `store`
+    #[napi]
+    pub async fn retrieve(&self, metadata: String) -> napi::Result<Buffer> {
+        let metadata: FileMetadata = serde_json::from_str(&metadata).map_err(js_error)?;
+        let data = self
+            .inner
+            .lock()
+            .await
+            .retrieve_file(&metadata)
+            .await
+            .map_err(js_error)?;
+        Ok(data.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The N-API wrappers only link inside a Node.js process, so the tests
+    // exercise the functions behind them
+    #[test]
+    fn test_node_encode_decode_odd_length() {
+        let data: Vec<u8> = (0..=100).collect();
+        let mut shares: Vec<Option<Vec<u8>>> = encode_shares(&data, 4, 2)
+            .unwrap()
+            .into_iter()
+            .map(Some)
+            .collect();
+        assert_eq!(shares.len(), 6);
+        shares[0] = None;
+        shares[3] = None;
+
+        assert_eq!(decode_shares(&shares, 4, 2, data.len()).unwrap(), data);
+        assert!(decode_shares(&shares, 4, 2, 1000).is_err());
+        assert!(decode_shares(&[None, None, None, None, None, None], 4, 2, 10).is_err());
+    }
+}
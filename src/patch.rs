@@ -0,0 +1,174 @@
+//! Patches carrying a file update between nodes
+//!
+//! A [`VersionPatch`] turns one version of a file into another. Chunks the
+//! new version shares with the base are referenced by position rather than
+//! repeated, and only the shards of chunks the base lacks are included, so
+//! a small edit to a large file produces a small patch. Export patches with
+//! [`StoragePipeline::export_patch`](crate::StoragePipeline::export_patch)
+//! and apply them with
+//! [`StoragePipeline::apply_patch`](crate::StoragePipeline::apply_patch).
+
+use serde::{Deserialize, Serialize};
+
+use crate::metadata::{ChunkReference, FileMetadata};
+use crate::version::{decode_delta, encode_delta, Result, VersionError};
+
+/// How to produce one chunk reference of the new version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ChunkOp {
+    /// Reuse the base chunk at `base_index`, moved to `shard_index`
+    Copy {
+        /// Position of the chunk in the base version
+        base_index: u32,
+        /// Shard index of the chunk in the new version
+        shard_index: u16,
+    },
+    /// A chunk the base version does not have
+    Insert(ChunkReference),
+}
+
+/// Delta from one version of a file to another
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionPatch {
+    /// Metadata hash of the version the patch applies to
+    pub base: [u8; 32],
+    /// Metadata hash of the version the patch produces
+    pub target: [u8; 32],
+    /// Metadata of the new version without its chunks or local metadata
+    pub header: FileMetadata,
+    /// Chunk map of the new version, in order
+    pub ops: Vec<ChunkOp>,
+    /// Serialized shards of inserted chunks
+    pub shards: Vec<Vec<u8>>,
+}
+
+impl VersionPatch {
+    /// Patch turning `base` into `target`, without shard data
+    pub fn diff(base: &FileMetadata, target: &FileMetadata) -> Result<Self> {
+        if base.file_id != target.file_id {
+            return Err(VersionError::WrongFile);
+        }
+
+        let base_keys = base
+            .chunks
+            .iter()
+            .map(reuse_key)
+            .collect::<Result<Vec<_>>>()?;
+        let mut ops = Vec::with_capacity(target.chunks.len());
+        for chunk in &target.chunks {
+            let key = reuse_key(chunk)?;
+            ops.push(match base_keys.iter().position(|k| *k == key) {
+                Some(i) => ChunkOp::Copy {
+                    base_index: i as u32,
+                    shard_index: chunk.shard_index,
+                },
+                None => ChunkOp::Insert(chunk.clone()),
+            });
+        }
+
+        let mut header = target.clone();
+        header.chunks = Vec::new();
+        header.local_metadata = None;
+        Ok(Self {
+            base: base.compute_id(),
+            target: target.compute_id(),
+            header,
+            ops,
+            shards: Vec::new(),
+        })
+    }
+
+    /// Metadata of the new version, built on top of `base`
+    ///
+    /// The local metadata of `base` carries over to the new version.
+    pub fn apply_to(&self, base: &FileMetadata) -> Result<FileMetadata> {
+        if base.compute_id() != self.base {
+            return Err(VersionError::InvalidPatch(
+                "Patch does not apply to this version",
+            ));
+        }
+
+        let mut metadata = self.header.clone();
+        metadata.local_metadata = base.local_metadata.clone();
+        metadata.chunks = self
+            .ops
+            .iter()
+            .map(|op| match op {
+                ChunkOp::Copy {
+                    base_index,
+                    shard_index,
+                } => {
+                    let mut chunk = base.chunks.get(*base_index as usize).cloned().ok_or(
+                        VersionError::InvalidPatch(
+                            "Patch references a chunk outside the base version",
+                        ),
+                    )?;
+                    chunk.shard_index = *shard_index;
+                    Ok(chunk)
+                }
+                ChunkOp::Insert(chunk) => Ok(chunk.clone()),
+            })
+            .collect::<Result<_>>()?;
+
+        if metadata.compute_id() != self.target {
+            return Err(VersionError::InvalidPatch(
+                "Patched metadata does not match the target version",
+            ));
+        }
+        Ok(metadata)
+    }
+
+    /// Chunk references the base version does not have
+    pub fn inserted_chunks(&self) -> impl Iterator<Item = &ChunkReference> {
+        self.ops.iter().filter_map(|op| match op {
+            ChunkOp::Insert(chunk) => Some(chunk),
+            ChunkOp::Copy { .. } => None,
+        })
+    }
+
+    /// Serialize for transfer, gzipped if `compress` is set
+    pub fn to_bytes(&self, compress: bool) -> Result<Vec<u8>> {
+        encode_delta(self, compress)
+    }
+
+    /// Deserialize a patch received from another node
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        decode_delta(bytes)
+    }
+}
+
+/// Everything about a chunk reference except its position
+fn reuse_key(chunk: &ChunkReference) -> Result<Vec<u8>> {
+    let mut chunk = chunk.clone();
+    chunk.shard_index = 0;
+    Ok(bincode::serialize(&chunk)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(chunk_ids: &[u8]) -> FileMetadata {
+        let chunks = chunk_ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| ChunkReference::new([*id; 32], 0, i as u16, 1024))
+            .collect();
+        FileMetadata::new([1u8; 32], 1024 * chunk_ids.len() as u64, None, chunks)
+    }
+
+    #[test]
+    fn test_patch_reuses_shifted_chunks() -> Result<()> {
+        let base = metadata(&[1, 2, 3]);
+        let target = metadata(&[9, 1, 3]).with_parent(base.compute_id());
+
+        let patch = VersionPatch::diff(&base, &target)?;
+        assert_eq!(patch.inserted_chunks().count(), 1);
+
+        let patch = VersionPatch::from_bytes(&patch.to_bytes(true)?)?;
+        let applied = patch.apply_to(&base)?;
+        assert_eq!(applied.compute_id(), target.compute_id());
+        assert!(patch.apply_to(&target).is_err());
+        Ok(())
+    }
+}
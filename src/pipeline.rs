@@ -3,23 +3,103 @@
 //! This module provides the main orchestration layer that combines
 //! encryption, FEC encoding, metadata management, and storage.
 //! Implements the v0.3 StoragePipeline API specification.
+//!
+//! The chunker, crypto provider, FEC backend and FEC parameter policy used
+//! by [`StoragePipeline`] are pluggable through [`StoragePipeline::builder`].
+//!
+//! Shard coding itself lives in the sans-IO [`chunk_codec`] module; this
+//! layer only moves shards to and from a [`StorageBackend`]. It awaits
+//! backend futures without spawning tasks, so it runs on any executor, or
+//! synchronously under a `block_on`. Only [`StoragePipeline::spawn_gc`]
+//! needs a tokio runtime.
 
-use anyhow::{Context, Result};
+use futures_util::future::join_all;
 use parking_lot::RwLock;
+use std::ops::Range;
 use std::sync::Arc;
-
-use crate::chunk_registry::{ChunkInfo, ChunkRegistry};
-use crate::config::{Config, EncryptionMode};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::sync::{broadcast, watch};
+use tracing::Instrument;
+use zeroize::Zeroizing;
+
+use crate::audit::{AuditEvent, AuditLog, AuditOperation};
+use crate::chunk_codec::{self, ChunkCodec};
+use crate::chunk_registry::{ChunkInfo, ChunkRegistry, RegistrySnapshot};
+use crate::config::{Config, ConfigError, ConfigUpdate, CryptoPolicy, EncryptionMode};
 use crate::crypto::{
-    derive_convergent_key, generate_random_key, CryptoEngine, EncryptionKey, EncryptionMetadata,
+    self, derive_convergent_key, derive_convergent_nonce, generate_random_key, CryptoEngine,
+    CryptoError, EncryptionAlgorithm, EncryptionKey, EncryptionMetadata,
 };
-use crate::gc::GarbageCollector;
+use crate::dedup::DedupClient;
+use crate::error::Error;
+use crate::fec_policy::{
+    AdaptivePolicy, FecChoice, FecInputs, FecPolicy, ShardLossTracker, StaticPolicy,
+};
+use crate::gc::{CollectionReport, GarbageCollector, GcEvent};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::gc::{GCScheduler, GcHandle};
 use crate::ida::IDAConfig;
-use crate::metadata::{ChunkReference, FileMetadata, LocalMetadata};
-use crate::quantum_crypto::QuantumCryptoEngine;
-use crate::storage::StorageBackend;
+use crate::integrity::{ChunkHealth, FileHealth, HealthStatus, RepairReport};
+use crate::keystore::{Keystore, CONVERGENCE_SECRET_ID};
+use crate::locator::ShardLocator;
+use crate::metadata::{ChunkReference, FileMetadata, FileParams, LocalMetadata, MetadataSigner};
+use crate::network::{NodeTransport, Request, Response};
+use crate::patch::VersionPatch;
+use crate::quantum_crypto::{
+    ConvergenceSecret, QuantumCryptoEngine, QuantumEncryptionMetadata, QuantumKeyDerivation,
+    SecurityLevel,
+};
+use crate::secret_registry::SecretRegistry;
+use crate::storage::{Cid, NodeEndpoint, Shard, StorageBackend};
+use crate::stream;
 use crate::types::{ChunkId, DataId, ShareId};
-use crate::version::VersionManager;
+use crate::version::{VersionError, VersionManager};
+use crate::{FecBackend, FecParams};
+
+/// Name of the incremental GC cursor in a persistent registry directory
+const GC_CURSOR_FILE: &str = "gc.cursor";
+
+/// Failures of the pipeline itself, as opposed to the modules it drives
+#[derive(Debug, Error)]
+pub enum PipelineError {
+    #[error("No shards available for chunk {0}")]
+    NoShards(String),
+
+    /// The decoded chunk does not hash to its recorded ID
+    #[error("Chunk hash mismatch for {0}")]
+    ChunkCorrupted(String),
+
+    #[error("Rebuilt shard {0} does not match its recorded CID")]
+    RebuiltShardMismatch(String),
+
+    #[error("One or more chunks are empty, cannot reconstruct data")]
+    EmptyChunk,
+
+    #[error("Patch carries unexpected shard {0}")]
+    UnexpectedShard(String),
+
+    #[error("Metadata signed by untrusted key")]
+    UntrustedSigner,
+
+    #[error("Crypto provider violated the configured policy: {0}")]
+    PolicyViolation(ConfigError),
+
+    #[error("FEC policy chose an empty chunk size")]
+    EmptyChunkSize,
+
+    #[error("System time before Unix epoch")]
+    ClockBeforeEpoch,
+
+    #[error("Compression failed: {0}")]
+    Compression(std::io::Error),
+
+    /// ConvergentWithSecret mode was chosen without a source for the secret
+    #[error("ConvergentWithSecret mode needs a keystore or secret registry")]
+    MissingKeystore,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
 
 /// Meta information for file processing
 /// Optional metadata that can be passed during file processing
@@ -35,6 +115,17 @@ pub struct Meta {
     pub mime_type: Option<String>,
     /// Custom tags
     pub tags: Vec<String>,
+    /// How long the stored shards live before reads skip them and GC reaps them
+    pub ttl: Option<Duration>,
+    /// FEC data and parity shards to use instead of the pipeline's
+    pub fec_params: Option<(u8, u8)>,
+    /// Encryption mode to use instead of the configured one
+    pub encryption_mode: Option<EncryptionMode>,
+    /// Whether to compress, and at which level, instead of the configured
+    /// compression settings
+    pub compression: Option<(bool, u8)>,
+    /// Chunk size to use instead of the pipeline's, even with a custom chunker
+    pub chunk_size: Option<usize>,
 }
 
 impl Meta {
@@ -46,6 +137,11 @@ impl Meta {
             description: None,
             mime_type: None,
             tags: Vec::new(),
+            ttl: None,
+            fec_params: None,
+            encryption_mode: None,
+            compression: None,
+            chunk_size: None,
         }
     }
 
@@ -61,6 +157,36 @@ impl Meta {
         self
     }
 
+    /// Expire the stored shards after the given duration
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Encode this file with the given FEC parameters
+    pub fn with_fec_params(mut self, data_shards: u8, parity_shards: u8) -> Self {
+        self.fec_params = Some((data_shards, parity_shards));
+        self
+    }
+
+    /// Encrypt this file with the given mode
+    pub fn with_encryption_mode(mut self, mode: EncryptionMode) -> Self {
+        self.encryption_mode = Some(mode);
+        self
+    }
+
+    /// Compress this file, or not, at the given level
+    pub fn with_compression(mut self, on: bool, level: u8) -> Self {
+        self.compression = Some((on, level));
+        self
+    }
+
+    /// Cut this file into chunks of the given size
+    pub fn with_chunk_size(mut self, bytes: usize) -> Self {
+        self.chunk_size = Some(bytes);
+        self
+    }
+
     /// Add tag
     pub fn add_tag(&mut self, tag: impl Into<String>) {
         self.tags.push(tag.into());
@@ -73,92 +199,678 @@ impl Default for Meta {
     }
 }
 
+/// Strategy for splitting encrypted data into chunks before FEC encoding
+pub trait Chunker: Send + Sync {
+    /// Split data into consecutive, non-overlapping chunks
+    fn chunk<'a>(&self, data: &'a [u8]) -> Vec<&'a [u8]>;
+}
+
+/// Chunker that splits data into fixed-size pieces (the default)
+#[derive(Debug, Clone, Copy)]
+pub struct FixedSizeChunker {
+    chunk_size: usize,
+}
+
+impl FixedSizeChunker {
+    /// Create a chunker producing chunks of at most `chunk_size` bytes
+    pub fn new(chunk_size: usize) -> Self {
+        Self {
+            chunk_size: chunk_size.max(1),
+        }
+    }
+
+    /// Get the configured chunk size
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+}
+
+impl Chunker for FixedSizeChunker {
+    fn chunk<'a>(&self, data: &'a [u8]) -> Vec<&'a [u8]> {
+        data.chunks(self.chunk_size).collect()
+    }
+}
+
+/// Encryption provider used by the pipeline
+///
+/// [`QuantumCryptoEngine`] (the default) and the classical AES-256-GCM
+/// [`CryptoEngine`] are built in. Implement this trait to plug in other
+/// primitives, or an engine whose keys never leave a KMS.
+pub trait CryptoProvider: Send + Sync {
+    /// Encrypt data using the specified encryption mode
+    fn encrypt(
+        &mut self,
+        data: &[u8],
+        mode: EncryptionMode,
+        convergence_secret: Option<&ConvergenceSecret>,
+    ) -> crypto::Result<(Vec<u8>, QuantumEncryptionMetadata)>;
+
+    /// Decrypt data previously produced by [`CryptoProvider::encrypt`]
+    fn decrypt(
+        &self,
+        encrypted_data: &[u8],
+        metadata: &QuantumEncryptionMetadata,
+        convergence_secret: Option<&ConvergenceSecret>,
+        original_data: Option<&[u8]>,
+    ) -> crypto::Result<Vec<u8>>;
+
+    /// Derive the key [`CryptoProvider::encrypt`] would use for `data`
+    ///
+    /// Returns `None` in modes whose key does not follow from the content.
+    fn derive_key(
+        &self,
+        data: &[u8],
+        mode: EncryptionMode,
+        convergence_secret: Option<&ConvergenceSecret>,
+    ) -> crypto::Result<Option<EncryptionKey>>;
+
+    /// Key the data described by `metadata` was sealed under
+    ///
+    /// Lets [`StoragePipeline::retrieve_range`] open streamed files a segment
+    /// at a time. `original_data` is the plaintext convergent keys derive
+    /// from, when the caller holds it. The default returns `None`, which
+    /// makes ranged reads decrypt the whole file.
+    fn content_key(
+        &self,
+        _metadata: &QuantumEncryptionMetadata,
+        _convergence_secret: Option<&ConvergenceSecret>,
+        _original_data: Option<&[u8]>,
+    ) -> crypto::Result<Option<Zeroizing<[u8; 32]>>> {
+        Ok(None)
+    }
+}
+
+/// Convergence secret required by `mode`, if any
+fn required_secret(
+    mode: EncryptionMode,
+    convergence_secret: Option<&ConvergenceSecret>,
+) -> crypto::Result<Option<&ConvergenceSecret>> {
+    match mode {
+        EncryptionMode::ConvergentWithSecret => convergence_secret.map(Some).ok_or(
+            CryptoError::Missing("Convergence secret required for ConvergentWithSecret mode"),
+        ),
+        _ => Ok(None),
+    }
+}
+
+impl CryptoProvider for QuantumCryptoEngine {
+    fn encrypt(
+        &mut self,
+        data: &[u8],
+        mode: EncryptionMode,
+        convergence_secret: Option<&ConvergenceSecret>,
+    ) -> crypto::Result<(Vec<u8>, QuantumEncryptionMetadata)> {
+        QuantumCryptoEngine::encrypt(self, data, mode, convergence_secret)
+    }
+
+    fn decrypt(
+        &self,
+        encrypted_data: &[u8],
+        metadata: &QuantumEncryptionMetadata,
+        convergence_secret: Option<&ConvergenceSecret>,
+        original_data: Option<&[u8]>,
+    ) -> crypto::Result<Vec<u8>> {
+        QuantumCryptoEngine::decrypt(
+            self,
+            encrypted_data,
+            metadata,
+            convergence_secret,
+            original_data,
+        )
+    }
+
+    fn derive_key(
+        &self,
+        data: &[u8],
+        mode: EncryptionMode,
+        convergence_secret: Option<&ConvergenceSecret>,
+    ) -> crypto::Result<Option<EncryptionKey>> {
+        if mode == EncryptionMode::RandomKey {
+            return Ok(None);
+        }
+        let secret = required_secret(mode, convergence_secret)?;
+        let key = self.derive_convergent_key(data, secret)?;
+        Ok(Some(EncryptionKey::new(key)))
+    }
+
+    fn content_key(
+        &self,
+        metadata: &QuantumEncryptionMetadata,
+        convergence_secret: Option<&ConvergenceSecret>,
+        original_data: Option<&[u8]>,
+    ) -> crypto::Result<Option<Zeroizing<[u8; 32]>>> {
+        QuantumCryptoEngine::content_key(self, metadata, convergence_secret, original_data)
+    }
+}
+
+/// Classical provider: AES-256-GCM under SHA-256 HKDF convergent keys
+///
+/// Supports the convergent modes only, since it has nowhere to keep a random
+/// key. Ciphertexts carry the nonce as a 12-byte prefix, except for data
+/// larger than one segment, which is sealed as a [STREAM](crate::stream).
+impl CryptoProvider for CryptoEngine {
+    fn encrypt(
+        &mut self,
+        data: &[u8],
+        mode: EncryptionMode,
+        convergence_secret: Option<&ConvergenceSecret>,
+    ) -> crypto::Result<(Vec<u8>, QuantumEncryptionMetadata)> {
+        let secret = required_secret(mode, convergence_secret)?;
+        let key = CryptoProvider::derive_key(self, data, mode, secret)?.ok_or_else(|| {
+            CryptoError::Unsupported(
+                "CryptoEngine cannot store RandomKey keys; use QuantumCryptoEngine".into(),
+            )
+        })?;
+        let nonce = derive_convergent_nonce(&key)?;
+        let (encrypted, stream_segment_size) = if data.len() > stream::DEFAULT_SEGMENT_SIZE {
+            let mut prefix = [0u8; stream::PREFIX_LEN];
+            prefix.copy_from_slice(&nonce[..stream::PREFIX_LEN]);
+            let sealed = stream::seal_stream_with_prefix(
+                EncryptionAlgorithm::Aes256Gcm,
+                key.as_bytes(),
+                stream::DEFAULT_SEGMENT_SIZE,
+                prefix,
+                data,
+            )?;
+            (sealed, Some(stream::DEFAULT_SEGMENT_SIZE as u32))
+        } else {
+            (self.encrypt_with_nonce(data, &key, nonce)?, None)
+        };
+
+        let metadata = QuantumEncryptionMetadata {
+            security_level: SecurityLevel::default(),
+            encapsulated_secret: Vec::new(),
+            algorithm: EncryptionAlgorithm::Aes256Gcm,
+            nonce,
+            key_derivation: QuantumKeyDerivation::Sha256Convergent,
+            convergence_secret_id: secret.map(ConvergenceSecret::id),
+            kem_key_id: None,
+            wrapped_key: None,
+            key_split: None,
+            secret_kdf: secret.and_then(|s| s.kdf().cloned()),
+            // A stream carries its prefix instead of the whole nonce
+            detached_nonce: stream_segment_size.is_some(),
+            stream_segment_size,
+        };
+        Ok((encrypted, metadata))
+    }
+
+    fn decrypt(
+        &self,
+        encrypted_data: &[u8],
+        metadata: &QuantumEncryptionMetadata,
+        convergence_secret: Option<&ConvergenceSecret>,
+        original_data: Option<&[u8]>,
+    ) -> crypto::Result<Vec<u8>> {
+        if !matches!(
+            metadata.key_derivation,
+            QuantumKeyDerivation::Sha256Convergent
+        ) {
+            return Err(CryptoError::Unsupported(format!(
+                "CryptoEngine cannot decrypt {:?} data",
+                metadata.key_derivation
+            )));
+        }
+        let data = original_data.ok_or(CryptoError::Missing(
+            "Original data required for convergent decryption",
+        ))?;
+        let secret = convergence_secret
+            .filter(|_| metadata.convergence_secret_id.is_some())
+            .map(ConvergenceSecret::as_bytes);
+        let key = derive_convergent_key(data, secret)?;
+        match metadata.stream_segment_size {
+            Some(segment_size) => Ok(stream::open_stream(
+                EncryptionAlgorithm::Aes256Gcm,
+                key.as_bytes(),
+                segment_size as usize,
+                encrypted_data,
+            )?),
+            None => CryptoEngine::decrypt(self, encrypted_data, &key),
+        }
+    }
+
+    fn derive_key(
+        &self,
+        data: &[u8],
+        mode: EncryptionMode,
+        convergence_secret: Option<&ConvergenceSecret>,
+    ) -> crypto::Result<Option<EncryptionKey>> {
+        if mode == EncryptionMode::RandomKey {
+            return Ok(None);
+        }
+        let secret = required_secret(mode, convergence_secret)?;
+        derive_convergent_key(data, secret.map(ConvergenceSecret::as_bytes)).map(Some)
+    }
+
+    fn content_key(
+        &self,
+        metadata: &QuantumEncryptionMetadata,
+        convergence_secret: Option<&ConvergenceSecret>,
+        original_data: Option<&[u8]>,
+    ) -> crypto::Result<Option<Zeroizing<[u8; 32]>>> {
+        let (QuantumKeyDerivation::Sha256Convergent, Some(data)) =
+            (&metadata.key_derivation, original_data)
+        else {
+            return Ok(None);
+        };
+        let secret = convergence_secret
+            .filter(|_| metadata.convergence_secret_id.is_some())
+            .map(ConvergenceSecret::as_bytes);
+        let key = derive_convergent_key(data, secret)?;
+        Ok(Some(Zeroizing::new(*key.as_bytes())))
+    }
+}
+
+/// Built-in crypto provider for `config`
+///
+/// Under the FIPS policy convergent modes use the AES-256-GCM [`CryptoEngine`],
+/// and RandomKey mode ML-KEM with AES-256-GCM.
+fn default_crypto(config: &Config) -> Box<dyn CryptoProvider> {
+    match (config.crypto_policy, config.encryption_mode) {
+        (CryptoPolicy::Standard, _) => Box::new(QuantumCryptoEngine::new()),
+        (CryptoPolicy::Fips, EncryptionMode::RandomKey) => {
+            Box::new(QuantumCryptoEngine::new().with_algorithm(EncryptionAlgorithm::Aes256Gcm))
+        }
+        (CryptoPolicy::Fips, _) => Box::new(CryptoEngine::new()),
+    }
+}
+
+/// Builder for [`StoragePipeline`] with pluggable components
+///
+/// Any component that is not supplied falls back to the built-in default:
+/// a [`FixedSizeChunker`] using the chunk size the FEC policy picks, a
+/// [`QuantumCryptoEngine`], the best available [`FecBackend`] for the
+/// platform, and an [`AdaptivePolicy`] if `fec.auto_params` is set or a
+/// [`StaticPolicy`] otherwise.
+///
+/// There is no default keystore: a convergence secret kept only in memory
+/// would make ConvergentWithSecret files unreadable after a restart, so
+/// [`build`](Self::build) fails for that mode unless a
+/// [`keystore`](Self::keystore) or [`secret_registry`](Self::secret_registry)
+/// is supplied.
+pub struct StoragePipelineBuilder<B: StorageBackend> {
+    config: Config,
+    backend: B,
+    chunker: Option<Box<dyn Chunker>>,
+    crypto: Option<Box<dyn CryptoProvider>>,
+    fec_backend: Option<Box<dyn FecBackend>>,
+    fec_policy: Option<Box<dyn FecPolicy>>,
+    keystore: Option<Arc<dyn Keystore>>,
+    signer: Option<MetadataSigner>,
+    trusted_signers: Vec<Vec<u8>>,
+    secret_registry: Option<Arc<SecretRegistry>>,
+    audit_log: Option<Arc<AuditLog>>,
+    dedup: Option<DedupClient>,
+    registry_dir: Option<std::path::PathBuf>,
+    locator: Option<(Arc<dyn ShardLocator>, NodeEndpoint)>,
+    node_transport: Option<Arc<dyn NodeTransport>>,
+}
+
+impl<B: StorageBackend + 'static> StoragePipelineBuilder<B> {
+    /// Create a builder for the given configuration and storage backend
+    pub fn new(config: Config, backend: B) -> Self {
+        Self {
+            config,
+            backend,
+            chunker: None,
+            crypto: None,
+            fec_backend: None,
+            fec_policy: None,
+            keystore: None,
+            signer: None,
+            trusted_signers: Vec::new(),
+            secret_registry: None,
+            audit_log: None,
+            dedup: None,
+            registry_dir: None,
+            locator: None,
+            node_transport: None,
+        }
+    }
+
+    /// Use a custom chunker
+    pub fn chunker(mut self, chunker: impl Chunker + 'static) -> Self {
+        self.chunker = Some(Box::new(chunker));
+        self
+    }
+
+    /// Use a custom crypto provider
+    pub fn crypto_provider(mut self, crypto: impl CryptoProvider + 'static) -> Self {
+        self.crypto = Some(Box::new(crypto));
+        self
+    }
+
+    /// Use a custom FEC codec backend
+    pub fn fec_backend(mut self, backend: Box<dyn FecBackend>) -> Self {
+        self.fec_backend = Some(backend);
+        self
+    }
+
+    /// Choose FEC parameters for each file with `policy`
+    pub fn fec_policy(mut self, policy: impl FecPolicy + 'static) -> Self {
+        self.fec_policy = Some(Box::new(policy));
+        self
+    }
+
+    /// Keep the convergence secret in `keystore`
+    pub fn keystore(mut self, keystore: impl Keystore + 'static) -> Self {
+        self.keystore = Some(Arc::new(keystore));
+        self
+    }
+
+    /// Take convergence secrets from `registry` instead of the keystore
+    ///
+    /// New files use the registry's active secret, and files are decrypted
+    /// with whichever secret their metadata names, including rotated ones.
+    pub fn secret_registry(mut self, registry: Arc<SecretRegistry>) -> Self {
+        self.secret_registry = Some(registry);
+        self
+    }
+
+    /// Record every encryption and decryption in `audit_log`
+    pub fn audit_log(mut self, audit_log: Arc<AuditLog>) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// Skip writing shards that `dedup` reports the server already holds
+    ///
+    /// The check reveals only a CID prefix to the server, see
+    /// [`crate::dedup`].
+    pub fn oblivious_dedup(mut self, dedup: DedupClient) -> Self {
+        self.dedup = Some(dedup);
+        self
+    }
+
+    /// Keep chunk reference counts in `dir` so they survive restarts
+    ///
+    /// The registry in `dir` is loaded when the pipeline is built, see
+    /// [`ChunkRegistry::open`]. Without it, counts live only in memory.
+    /// The cursor of incremental GC sweeps is kept in the same directory.
+    pub fn persistent_registry(mut self, dir: impl Into<std::path::PathBuf>) -> Self {
+        self.registry_dir = Some(dir.into());
+        self
+    }
+
+    /// Publish `local_node` to `locator` as holder of every shard stored
+    ///
+    /// Repairs then rebuild only shards no node holds, see
+    /// [`node_transport`](Self::node_transport).
+    pub fn shard_locator(
+        mut self,
+        locator: Arc<dyn ShardLocator>,
+        local_node: NodeEndpoint,
+    ) -> Self {
+        self.locator = Some((locator, local_node));
+        self
+    }
+
+    /// Fetch shards missing from the backend from the nodes the
+    /// [`shard_locator`](Self::shard_locator) names, over `transport`
+    pub fn node_transport(mut self, transport: Arc<dyn NodeTransport>) -> Self {
+        self.node_transport = Some(transport);
+        self
+    }
+
+    /// Sign the metadata of every processed file with `signer`
+    pub fn signer(mut self, signer: MetadataSigner) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Only retrieve files whose metadata is signed by one of the trusted keys
+    ///
+    /// Call once per trusted ML-DSA public key. Without trusted signers, any
+    /// signature present is still checked but unsigned metadata is accepted.
+    pub fn trusted_signer(mut self, public_key: Vec<u8>) -> Self {
+        self.trusted_signers.push(public_key);
+        self
+    }
+
+    /// Build the pipeline
+    pub fn build(self) -> Result<StoragePipeline<B>> {
+        let cfg = self.config;
+        cfg.validate()?;
+        for warning in cfg.check().warnings {
+            tracing::warn!("Configuration {}", warning);
+        }
+
+        let fec_policy = self.fec_policy.unwrap_or_else(|| {
+            if cfg.fec.auto_params {
+                Box::new(AdaptivePolicy::new())
+            } else {
+                Box::new(StaticPolicy)
+            }
+        });
+        let crypto = self.crypto.unwrap_or_else(|| default_crypto(&cfg));
+        let codec = match self.fec_backend {
+            Some(backend) => ChunkCodec::with_backend(backend),
+            None => ChunkCodec::new()?,
+        };
+        if cfg.encryption_mode == EncryptionMode::ConvergentWithSecret
+            && self.keystore.is_none()
+            && self.secret_registry.is_none()
+        {
+            return Err(PipelineError::MissingKeystore.into());
+        }
+
+        let backend = Arc::new(self.backend);
+        let chunk_registry = match &self.registry_dir {
+            Some(dir) => ChunkRegistry::open(dir)?,
+            None => ChunkRegistry::new(),
+        };
+        let chunk_registry = Arc::new(chunk_registry);
+        let mut version_manager = VersionManager::new(chunk_registry.clone())
+            .with_auto_tag_interval(cfg.version.auto_tag_interval);
+        if let Some(signer) = &self.signer {
+            version_manager = version_manager.with_signer(signer.clone());
+        }
+        let version_manager = Arc::new(RwLock::new(version_manager));
+
+        let retention_policy = cfg.gc.retention_policy();
+        let storage_for_gc: Arc<dyn StorageBackend> = backend.clone();
+        let mut gc =
+            GarbageCollector::new(retention_policy, chunk_registry.clone(), storage_for_gc)
+                .with_config(&cfg.gc);
+        if let Some(dir) = &self.registry_dir {
+            gc = gc.with_cursor_file(dir.join(GC_CURSOR_FILE))?;
+        }
+        let gc = Arc::new(gc);
+
+        Ok(StoragePipeline {
+            gc_interval: watch::channel(cfg.gc.run_interval).0,
+            config: RwLock::new(Arc::new(cfg)),
+            backend,
+            chunker: self.chunker,
+            crypto,
+            codec,
+            fec_policy,
+            shard_loss: ShardLossTracker::new(),
+            keystore: self.keystore,
+            signer: self.signer,
+            trusted_signers: self.trusted_signers,
+            secret_registry: self.secret_registry,
+            audit_log: self.audit_log,
+            dedup: self.dedup,
+            placement: self.locator.map(|(locator, local_node)| Placement {
+                locator,
+                local_node,
+                transport: self.node_transport,
+            }),
+            chunk_registry,
+            version_manager,
+            gc,
+            catalog: Arc::new(RwLock::new(std::collections::HashMap::new())),
+            version_metadata: Arc::new(RwLock::new(std::collections::HashMap::new())),
+            original_data_storage: Arc::new(RwLock::new(std::collections::HashMap::new())),
+        })
+    }
+}
+
 /// Storage pipeline implementing v0.3 specification API
 /// Generic over storage backend type B
 pub struct StoragePipeline<B: StorageBackend> {
-    /// Configuration
-    config: Config,
+    /// Configuration, replaced as a whole by `reconfigure`
+    config: RwLock<Arc<Config>>,
+    /// Current GC interval, followed by background collectors
+    gc_interval: watch::Sender<Duration>,
     /// Storage backend
-    #[allow(dead_code)]
-    backend: B,
+    backend: Arc<B>,
+    /// Custom chunker; without one chunks follow the FEC policy's size
+    chunker: Option<Box<dyn Chunker>>,
+    /// Encryption provider
+    crypto: Box<dyn CryptoProvider>,
+    /// Codes chunks to and from shards
+    codec: ChunkCodec,
+    /// Chooses FEC parameters for each file
+    fec_policy: Box<dyn FecPolicy>,
+    /// Shard losses seen on reads, fed to the FEC policy
+    shard_loss: ShardLossTracker,
+    /// Source of the convergence secret
+    keystore: Option<Arc<dyn Keystore>>,
+    /// Key signing the metadata of processed files
+    signer: Option<MetadataSigner>,
+    /// Public keys whose metadata signatures are accepted on retrieval
+    trusted_signers: Vec<Vec<u8>>,
+    /// Convergence secrets by ID, if lifecycle management is on
+    secret_registry: Option<Arc<SecretRegistry>>,
+    /// Key usage log, if auditing is on
+    audit_log: Option<Arc<AuditLog>>,
+    /// Oblivious check for shards the server already holds
+    dedup: Option<DedupClient>,
+    /// Where shards are published and other nodes' copies fetched from
+    placement: Option<Placement>,
     /// Chunk registry
-    chunk_registry: Arc<RwLock<ChunkRegistry>>,
+    chunk_registry: Arc<ChunkRegistry>,
     /// Version manager
     version_manager: Arc<RwLock<VersionManager>>,
     /// Garbage collector
     gc: Arc<GarbageCollector>,
-    /// In-memory storage for chunks (for testing)
-    chunk_storage: Arc<RwLock<std::collections::HashMap<String, Vec<u8>>>>,
-    /// Store original data for key recovery (for testing)
+    /// Metadata of every file processed by this pipeline, keyed by file ID
+    catalog: Arc<RwLock<std::collections::HashMap<[u8; 32], FileMetadata>>>,
+    /// Metadata of every retained version, keyed by metadata hash
+    version_metadata: Arc<RwLock<std::collections::HashMap<[u8; 32], FileMetadata>>>,
+    /// Store original data for key recovery (for testing), keyed by ciphertext ID
     original_data_storage: Arc<RwLock<std::collections::HashMap<[u8; 32], Vec<u8>>>>,
 }
 
-impl<B: StorageBackend> StoragePipeline<B> {
+impl<B: StorageBackend + 'static> StoragePipeline<B> {
     /// Create a new storage pipeline with the given configuration and backend
     /// Required by v0.3 specification
     pub async fn new(cfg: Config, backend: B) -> Result<Self> {
-        cfg.validate().context("Invalid configuration")?;
-
-        let chunk_registry = Arc::new(RwLock::new(ChunkRegistry::new()));
-        let version_manager = Arc::new(RwLock::new(VersionManager::new(chunk_registry.clone())));
-
-        use crate::gc::RetentionPolicy;
-        let retention_policy =
-            RetentionPolicy::KeepRecent(cfg.gc.retention_days as u64 * 24 * 3600);
-
-        // Create a dummy Arc<dyn StorageBackend> for GC - this will need to be addressed in a future refactor
-        let storage_for_gc: Arc<dyn StorageBackend> =
-            Arc::new(crate::storage::LocalStorage::new(std::path::PathBuf::from("/tmp")).await?);
-        let gc = Arc::new(GarbageCollector::new(
-            retention_policy,
-            chunk_registry.clone(),
-            storage_for_gc,
-        ));
+        Self::builder(cfg, backend).build()
+    }
 
-        Ok(Self {
-            config: cfg,
-            backend,
-            chunk_registry,
-            version_manager,
-            gc,
-            chunk_storage: Arc::new(RwLock::new(std::collections::HashMap::new())),
-            original_data_storage: Arc::new(RwLock::new(std::collections::HashMap::new())),
-        })
+    /// Start building a pipeline with custom components
+    pub fn builder(cfg: Config, backend: B) -> StoragePipelineBuilder<B> {
+        StoragePipelineBuilder::new(cfg, backend)
+    }
+
+    /// Get the storage backend
+    pub fn backend(&self) -> &Arc<B> {
+        &self.backend
+    }
+
+    /// Fraction of shards found missing or corrupted on reads so far
+    ///
+    /// `None` until enough shards have been read to estimate it.
+    pub fn shard_loss_rate(&self) -> Option<f64> {
+        self.shard_loss.rate()
+    }
+
+    /// Current configuration, including changes made with
+    /// [`reconfigure`](Self::reconfigure)
+    pub fn config(&self) -> Arc<Config> {
+        self.config.read().clone()
+    }
+
+    /// Change runtime-tunable settings of the running pipeline
+    ///
+    /// The changed configuration is validated first and rejected as a
+    /// whole if invalid. Operations already in progress finish with the
+    /// settings they started with; background collectors started with
+    /// [`spawn_gc`](Self::spawn_gc) pick up a new interval at once.
+    pub fn reconfigure(&self, update: &ConfigUpdate) -> Result<()> {
+        let mut config = self.config.write();
+        let mut updated = Config::clone(&config);
+        updated.apply(update);
+        updated.validate()?;
+
+        let run_interval = updated.gc.run_interval;
+        *config = Arc::new(updated);
+        drop(config);
+        self.gc_interval.send_replace(run_interval);
+        Ok(())
+    }
+
+    /// Version history of the files processed, for tagging and branching
+    pub fn version_manager(&self) -> &Arc<RwLock<VersionManager>> {
+        &self.version_manager
+    }
+
+    /// Keep the chunks of a file version through garbage collection
+    pub fn pin_file(&self, metadata: &FileMetadata) -> Result<()> {
+        Ok(self.chunk_registry.pin_file(metadata)?)
+    }
+
+    /// Let garbage collection reclaim a file version pinned with
+    /// [`Self::pin_file`] once it is unreferenced
+    pub fn unpin_file(&self, metadata: &FileMetadata) -> Result<()> {
+        Ok(self.chunk_registry.unpin_file(metadata)?)
+    }
+
+    /// Serialized snapshot of the chunk registry, for backup or migration
+    pub fn export_registry(&self) -> Result<Vec<u8>> {
+        Ok(self.chunk_registry.export()?)
+    }
+
+    /// Replace the chunk registry with a snapshot from [`Self::export_registry`]
+    pub fn import_registry(&self, bytes: &[u8]) -> Result<()> {
+        let snapshot = RegistrySnapshot::from_bytes(bytes)?;
+        Ok(self.chunk_registry.restore(snapshot)?)
     }
 
     /// Process a file: encrypt, chunk, and store with FEC encoding
     /// Required by v0.3 specification
+    ///
+    /// FEC parameters, encryption mode, compression and chunk size set on
+    /// `meta` apply to this file only. The settings used are recorded in
+    /// [`FileMetadata::params`].
+    #[tracing::instrument(
+        name = "process_file",
+        skip_all,
+        fields(file_id = %hex::encode(file_id), size = data.len(), chunks)
+    )]
     pub async fn process_file(
         &mut self,
         file_id: [u8; 32],
         data: &[u8],
         meta: Option<Meta>,
     ) -> Result<FileMetadata> {
-        // Create quantum crypto engine
-        let mut crypto = QuantumCryptoEngine::new();
+        let config = self.file_config(meta.as_ref())?;
 
         // Process data with optional compression
-        let processed_data = if self.config.compression_enabled {
-            self.compress(data)?
+        let processed_data = if config.compression_enabled {
+            chunk_codec::compress(data, config.compression_level)?
         } else {
             data.to_vec()
         };
 
-        // Encrypt using quantum engine
+        // Encrypt using the configured provider
         let (encrypted_data, quantum_encryption_metadata) = {
-            let secret = match self.config.encryption_mode {
-                EncryptionMode::ConvergentWithSecret => {
-                    let secret_bytes = self.get_user_secret()?;
-                    Some(crate::quantum_crypto::ConvergenceSecret::new(secret_bytes))
-                }
+            let secret = match config.encryption_mode {
+                EncryptionMode::ConvergentWithSecret => Some(self.active_secret()?),
                 _ => None,
             };
 
-            let (encrypted, quantum_meta) = crypto.encrypt(
-                &processed_data,
-                self.config.encryption_mode,
-                secret.as_ref(),
-            )?;
+            let (encrypted, quantum_meta) =
+                self.crypto
+                    .encrypt(&processed_data, config.encryption_mode, secret.as_ref())?;
+            config
+                .crypto_policy
+                .check_metadata(&quantum_meta)
+                .map_err(PipelineError::PolicyViolation)?;
+            self.audit(AuditOperation::Encrypt, &quantum_meta, file_id)?;
 
             (encrypted, Some(quantum_meta))
         };
@@ -172,11 +884,42 @@ impl<B: StorageBackend> StoragePipeline<B> {
         // Store original data for key recovery (for testing)
         {
             let mut orig_storage = self.original_data_storage.write();
-            orig_storage.insert(file_id, processed_data.clone());
+            orig_storage.insert(*data_id.as_bytes(), processed_data.clone());
         }
 
         // Process chunks with FEC encoding
-        let chunk_refs = self.process_chunks(&encrypted_data, &data_id).await?;
+        let expires_at = match meta.as_ref().and_then(|m| m.ttl) {
+            Some(ttl) => Some(
+                (SystemTime::now() + ttl)
+                    .duration_since(UNIX_EPOCH)
+                    .map_err(|_| PipelineError::ClockBeforeEpoch)?
+                    .as_secs(),
+            ),
+            None => None,
+        };
+        let mut choice = self.fec_choice(&config, encrypted_data.len())?;
+        let overrides = meta.as_ref();
+        if let Some((data_shards, parity_shards)) = overrides.and_then(|m| m.fec_params) {
+            choice.params = FecParams::new(data_shards as u16, parity_shards as u16)?;
+        }
+        // A custom chunker cuts the file unless the caller fixed a size
+        let fixed_size = overrides.and_then(|m| m.chunk_size);
+        let custom_chunker = self.chunker.as_ref().filter(|_| fixed_size.is_none());
+        let chunk_size = fixed_size.unwrap_or(choice.chunk_size);
+        let chunks = match custom_chunker {
+            Some(chunker) => chunker.chunk(&encrypted_data),
+            None => FixedSizeChunker::new(chunk_size).chunk(&encrypted_data),
+        };
+        tracing::Span::current().record("chunks", chunks.len());
+        let chunk_refs = self
+            .process_chunks(
+                &file_id,
+                chunks,
+                choice.params,
+                config.encryption_mode,
+                expires_at,
+            )
+            .await?;
 
         // Create file metadata with quantum encryption
         let mut file_metadata = FileMetadata::with_quantum_encryption(
@@ -185,6 +928,16 @@ impl<B: StorageBackend> StoragePipeline<B> {
             quantum_encryption_metadata,
             chunk_refs,
         );
+        file_metadata.crypto_policy = config.crypto_policy;
+        file_metadata.params = Some(FileParams {
+            encryption_mode: config.encryption_mode,
+            data_shards: choice.params.data_shares,
+            parity_shards: choice.params.parity_shares,
+            chunk_size: custom_chunker.is_none().then_some(chunk_size),
+            compression_level: config
+                .compression_enabled
+                .then_some(config.compression_level),
+        });
 
         // Add local metadata if provided
         if let Some(meta) = meta {
@@ -201,136 +954,697 @@ impl<B: StorageBackend> StoragePipeline<B> {
             file_metadata = file_metadata.with_local_metadata(local_meta);
         }
 
-        // Register version
-        {
-            let mut version_mgr = self.version_manager.write();
-            version_mgr.create_version(&file_metadata)?;
+        if let Some(signer) = &self.signer {
+            file_metadata.sign(signer)?;
         }
 
+        self.register_version(&file_metadata)?;
+        let chunk_ids: Vec<[u8; 32]> = file_metadata.chunks.iter().map(|c| c.chunk_id).collect();
+        self.chunk_registry.add_data_ref(&chunk_ids, &data_id)?;
+
         Ok(file_metadata)
     }
 
+    /// Make an earlier version of a file its latest version again
+    ///
+    /// The new head reuses the chunks of `version_hash`, so nothing is
+    /// re-encoded or stored, and the versions in between stay in history.
+    pub fn rollback(&self, file_id: &[u8; 32], version_hash: &[u8; 32]) -> Result<FileMetadata> {
+        let target = self
+            .version_metadata
+            .read()
+            .get(version_hash)
+            .cloned()
+            .ok_or_else(|| VersionError::VersionNotFound(hex::encode(version_hash)))?;
+        if target.file_id != *file_id {
+            return Err(VersionError::WrongFile.into());
+        }
+        let head = self
+            .version_manager
+            .read()
+            .find_previous_version(file_id)
+            .map(|node| node.metadata_hash)
+            .ok_or_else(|| VersionError::NoVersions(hex::encode(file_id)))?;
+        if head == *version_hash {
+            return Ok(target);
+        }
+
+        let mut metadata = target.with_parent(head);
+        metadata.signature = None;
+        if let Some(local) = &mut metadata.local_metadata {
+            local.modified_at = Some(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_err(|_| PipelineError::ClockBeforeEpoch)?
+                    .as_secs(),
+            );
+        }
+        if let Some(signer) = &self.signer {
+            metadata.sign(signer)?;
+        }
+
+        self.register_version(&metadata)?;
+        Ok(metadata)
+    }
+
+    /// Metadata of the version of a file named by a branch or tag
+    pub fn resolve_version(&self, file_id: &[u8; 32], name: &str) -> Result<FileMetadata> {
+        let hash = self
+            .version_manager
+            .read()
+            .resolve(file_id, name)
+            .map(|node| node.metadata_hash)
+            .ok_or_else(|| VersionError::UnknownName(name.to_string()))?;
+        let metadata = self.version_metadata.read().get(&hash).cloned();
+        Ok(metadata.ok_or_else(|| VersionError::VersionNotFound(hex::encode(hash)))?)
+    }
+
+    /// Patch turning version `base` of a file into version `target`
+    ///
+    /// Includes the shards of every chunk `target` has that `base` lacks;
+    /// shards missing from the backend are left out and must be recovered
+    /// by FEC on the receiving side.
+    pub async fn export_patch(
+        &self,
+        base: &FileMetadata,
+        target: &FileMetadata,
+    ) -> Result<VersionPatch> {
+        let mut patch = VersionPatch::diff(base, target)?;
+
+        let held: std::collections::HashSet<&[u8; 32]> =
+            base.chunks.iter().flat_map(|c| &c.shard_ids).collect();
+        let cids: Vec<Cid> = patch
+            .inserted_chunks()
+            .flat_map(|c| &c.shard_ids)
+            .filter(|id| !held.contains(id))
+            .map(|id| Cid::new(*id))
+            .collect();
+        for (cid, shard) in cids.iter().zip(self.backend.get_shards(&cids).await?) {
+            match shard {
+                Some(shard) => patch.shards.push(shard.to_bytes()?),
+                None => tracing::warn!("Shard {} unavailable for patch", cid.to_hex()),
+            }
+        }
+        Ok(patch)
+    }
+
+    /// [`export_patch`](Self::export_patch) encoded for transfer
+    ///
+    /// The patch is compressed if the version config enables
+    /// `diff_compression`.
+    pub async fn export_patch_bytes(
+        &self,
+        base: &FileMetadata,
+        target: &FileMetadata,
+    ) -> Result<Vec<u8>> {
+        Ok(self
+            .export_patch(base, target)
+            .await?
+            .to_bytes(self.config().version.diff_compression)?)
+    }
+
+    /// Store the shards of `patch` and record the version it produces from `base`
+    pub async fn apply_patch(
+        &self,
+        base: &FileMetadata,
+        patch: &VersionPatch,
+    ) -> Result<FileMetadata> {
+        let metadata = patch.apply_to(base)?;
+        self.check_signature(&metadata)?;
+
+        let expected: std::collections::HashSet<&[u8; 32]> =
+            patch.inserted_chunks().flat_map(|c| &c.shard_ids).collect();
+        let mut shards = Vec::with_capacity(patch.shards.len());
+        for bytes in &patch.shards {
+            let shard = Shard::from_bytes(bytes)?;
+            let cid = shard.cid()?;
+            if !expected.contains(cid.as_bytes()) {
+                return Err(PipelineError::UnexpectedShard(cid.to_hex()).into());
+            }
+            shards.push((cid, shard));
+        }
+        self.backend
+            .put_shards(&shards)
+            .instrument(tracing::debug_span!("put_shards", shards = shards.len()))
+            .await?;
+        if let Some(placement) = &self.placement {
+            let cids: Vec<Cid> = shards.iter().map(|(cid, _)| *cid).collect();
+            placement.publish(&metadata.file_id, &cids).await?;
+        }
+
+        self.register_version(&metadata)?;
+        Ok(metadata)
+    }
+
+    /// Remove a version of a file from its history
+    ///
+    /// Chunks no other version uses are released and their shards deleted by
+    /// the next [`run_gc`](Self::run_gc), subject to the GC retention policy.
+    /// Removing the latest version makes its parent the latest.
+    pub fn remove_version(&self, hash: &[u8; 32]) -> Result<()> {
+        let mut version_mgr = self.version_manager.write();
+        version_mgr.remove_version(hash)?;
+
+        let mut versions = self.version_metadata.write();
+        let removed = versions.remove(hash);
+        let mut catalog = self.catalog.write();
+        if let Some(removed) = removed {
+            match version_mgr.find_previous_version(&removed.file_id) {
+                Some(head) => {
+                    if let Some(meta) = versions.get(&head.metadata_hash) {
+                        catalog.insert(removed.file_id, meta.clone());
+                    }
+                }
+                None => {
+                    catalog.remove(&removed.file_id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Record a new latest version of a file, pruning old versions
+    fn register_version(&self, metadata: &FileMetadata) -> Result<()> {
+        let pruned = {
+            let mut version_mgr = self.version_manager.write();
+            version_mgr.create_version(metadata)?;
+            version_mgr.prune(&metadata.file_id, &self.config().version)?
+        };
+
+        let mut versions = self.version_metadata.write();
+        for hash in &pruned {
+            versions.remove(hash);
+        }
+        versions.insert(metadata.compute_id(), metadata.clone());
+        self.catalog
+            .write()
+            .insert(metadata.file_id, metadata.clone());
+        Ok(())
+    }
+
     /// Retrieve and decrypt a file
     /// Required by v0.3 specification
+    #[tracing::instrument(
+        name = "retrieve_file",
+        skip_all,
+        fields(file_id = %hex::encode(meta.file_id), size = meta.file_size, chunks = meta.chunks.len())
+    )]
     pub async fn retrieve_file(&self, meta: &FileMetadata) -> Result<Vec<u8>> {
+        self.check_signature(meta)?;
+
         let mut chunks = Vec::new();
 
         // Retrieve all chunks
         for chunk_ref in &meta.chunks {
-            let chunk_data = self.retrieve_chunk(&chunk_ref.chunk_id).await?;
+            let chunk_data = self.retrieve_chunk(&meta.file_id, chunk_ref).await?;
             chunks.push(chunk_data);
         }
+        let chunk_ids: Vec<[u8; 32]> = meta.chunks.iter().map(|c| c.chunk_id).collect();
+        self.chunk_registry.record_read(&chunk_ids)?;
 
         // Combine chunks (reconstruct with FEC if needed)
         let encrypted_data = self.reconstruct_data(&chunks, meta).await?;
 
-        // Decrypt using quantum engine
-        let decrypted = if let Some(quantum_meta) = &meta.quantum_encryption_metadata {
-            let crypto = QuantumCryptoEngine::new();
+        let data_id = DataId::from_data(&encrypted_data);
 
-            // Get convergence secret if needed
-            let secret = if quantum_meta.convergence_secret_id.is_some() {
-                let secret_bytes = self.get_user_secret()?;
-                Some(crate::quantum_crypto::ConvergenceSecret::new(secret_bytes))
-            } else {
-                None
+        // Decrypt using the configured provider
+        let decrypted = if let Some(quantum_meta) = &meta.quantum_encryption_metadata {
+            // Get the convergence secret the file was encrypted with
+            let secret = match &quantum_meta.convergence_secret_id {
+                Some(id) => Some(self.resolve_secret(id)?),
+                None => None,
             };
 
             // Get original data for convergent decryption
             let orig_storage = self.original_data_storage.read();
-            let original_data = orig_storage.get(&meta.file_id);
+            let original_data = orig_storage.get(data_id.as_bytes());
 
-            crypto.decrypt(
+            let decrypted = self.crypto.decrypt(
                 &encrypted_data,
                 quantum_meta,
                 secret.as_ref(),
                 original_data.map(|v| v.as_slice()),
-            )?
+            )?;
+            self.audit(AuditOperation::Decrypt, quantum_meta, meta.file_id)?;
+            decrypted
         } else if let Some(enc_meta) = &meta.encryption_metadata {
             // Legacy fallback
             let crypto = CryptoEngine::new();
-            let key = self.recover_key(enc_meta, &meta.file_id)?;
+            let key = self.recover_key(enc_meta, &data_id)?;
             crypto.decrypt(&encrypted_data, &key)?
         } else {
             encrypted_data
         };
 
         // Optionally decompress
-        if self.config.compression_enabled {
-            self.decompress(&decrypted)
+        let compressed = match &meta.params {
+            Some(params) => params.compression_level.is_some(),
+            None => self.config().compression_enabled,
+        };
+        if compressed {
+            chunk_codec::decompress(&decrypted)
         } else {
             Ok(decrypted)
         }
     }
 
+    /// Retrieve `len` bytes of a file starting at `offset`
+    ///
+    /// Reads past the end of the file are truncated. For files sealed as a
+    /// [STREAM](crate::stream), only the chunks holding the segments that
+    /// cover the range are fetched, decoded and decrypted. Compressed files,
+    /// files sealed as one AEAD message and keys the crypto provider cannot
+    /// supply through [`CryptoProvider::content_key`] fall back to reading
+    /// the whole file.
+    pub async fn retrieve_range(
+        &self,
+        meta: &FileMetadata,
+        offset: u64,
+        len: usize,
+    ) -> Result<Vec<u8>> {
+        if offset >= meta.file_size || len == 0 {
+            return Ok(Vec::new());
+        }
+        let end = offset.saturating_add(len as u64).min(meta.file_size);
+        if let Some(range) = self.retrieve_segments(meta, offset..end).await? {
+            return Ok(range);
+        }
+        let data = self.retrieve_file(meta).await?;
+        let start = (offset as usize).min(data.len());
+        let end = (end as usize).min(data.len());
+        Ok(data[start..end].to_vec())
+    }
+
+    /// Serve `range` from the STREAM segments covering it, or `None` if the
+    /// file cannot be read a segment at a time
+    async fn retrieve_segments(
+        &self,
+        meta: &FileMetadata,
+        range: Range<u64>,
+    ) -> Result<Option<Vec<u8>>> {
+        let compressed = match &meta.params {
+            Some(params) => params.compression_level.is_some(),
+            None => self.config().compression_enabled,
+        };
+        let Some(quantum) = meta
+            .quantum_encryption_metadata
+            .as_ref()
+            .filter(|_| !compressed)
+        else {
+            return Ok(None);
+        };
+        let Some(segment_size) = quantum.stream_segment_size else {
+            return Ok(None);
+        };
+        self.check_signature(meta)?;
+        let Some(key) = self.range_key(meta, quantum)? else {
+            return Ok(None);
+        };
+
+        let segment_size = segment_size as usize;
+        let stream_len: usize = meta.chunks.iter().map(|c| c.size as usize).sum();
+        let first = (range.start / segment_size as u64) as u32;
+        let last = ((range.end - 1) / segment_size as u64) as u32;
+        let segment_at = |index| {
+            stream::segment_range(segment_size, stream_len, index).map_err(CryptoError::from)
+        };
+        let sealed_range = segment_at(first)?.0.start..segment_at(last)?.0.end;
+
+        // Fetch only the chunks overlapping the sealed segments
+        let mut sealed = Vec::with_capacity(sealed_range.len());
+        let mut chunk_ids = Vec::new();
+        let mut chunk_start = 0;
+        for chunk_ref in &meta.chunks {
+            let chunk_end = chunk_start + chunk_ref.size as usize;
+            if chunk_end > sealed_range.start && chunk_start < sealed_range.end {
+                let chunk = self.retrieve_chunk(&meta.file_id, chunk_ref).await?;
+                let from = sealed_range.start.saturating_sub(chunk_start);
+                let to = sealed_range.end.min(chunk_end) - chunk_start;
+                let bytes = chunk.get(from..to).ok_or_else(|| {
+                    PipelineError::ChunkCorrupted(hex::encode(chunk_ref.chunk_id))
+                })?;
+                sealed.extend_from_slice(bytes);
+                chunk_ids.push(chunk_ref.chunk_id);
+            }
+            chunk_start = chunk_end;
+        }
+        self.chunk_registry.record_read(&chunk_ids)?;
+
+        let mut prefix = [0u8; stream::PREFIX_LEN];
+        prefix.copy_from_slice(&quantum.nonce[..stream::PREFIX_LEN]);
+        let mut plaintext = Vec::with_capacity((last - first + 1) as usize * segment_size);
+        for index in first..=last {
+            let (segment, is_last) = segment_at(index)?;
+            let bytes = sealed
+                .get(segment.start - sealed_range.start..segment.end - sealed_range.start)
+                .ok_or(CryptoError::TooShort("Encrypted data"))?;
+            plaintext.extend(
+                stream::open_sealed_segment(
+                    quantum.algorithm,
+                    &key,
+                    &prefix,
+                    index,
+                    is_last,
+                    bytes,
+                )
+                .map_err(CryptoError::from)?,
+            );
+        }
+        self.audit(AuditOperation::Decrypt, quantum, meta.file_id)?;
+
+        let skip = (range.start - u64::from(first) * segment_size as u64) as usize;
+        let end = (skip + (range.end - range.start) as usize).min(plaintext.len());
+        Ok(Some(plaintext[skip.min(end)..end].to_vec()))
+    }
+
+    /// Content key for reading `meta` a segment at a time
+    ///
+    /// Convergent keys need the plaintext, which is only at hand for data
+    /// this pipeline stored itself.
+    fn range_key(
+        &self,
+        meta: &FileMetadata,
+        quantum: &QuantumEncryptionMetadata,
+    ) -> Result<Option<Zeroizing<[u8; 32]>>> {
+        let secret = match &quantum.convergence_secret_id {
+            Some(id) => Some(self.resolve_secret(id)?),
+            None => None,
+        };
+        let data_ids = meta
+            .chunks
+            .first()
+            .and_then(|chunk| self.chunk_registry.get_metadata(&chunk.chunk_id))
+            .map(|chunk| chunk.data_ids)
+            .unwrap_or_default();
+        let orig_storage = self.original_data_storage.read();
+        let mut originals = data_ids.iter().filter_map(|id| orig_storage.get(id));
+        // A chunk shared with other data leaves the plaintext ambiguous
+        let original = match (originals.next(), originals.next()) {
+            (Some(data), None) => Some(data.as_slice()),
+            _ => None,
+        };
+        Ok(self
+            .crypto
+            .content_key(quantum, secret.as_ref(), original)?)
+    }
+
     /// Process chunks with FEC encoding
-    async fn process_chunks(&self, data: &[u8], data_id: &DataId) -> Result<Vec<ChunkReference>> {
+    ///
+    /// Chunks enter the chunk registry, keyed by their content hash, once the
+    /// version using them is created. That fails with
+    /// [`RegistryError::Tombstoned`](crate::chunk_registry::RegistryError::Tombstoned)
+    /// for a chunk garbage collection is deleting, leaving the file to be
+    /// stored again once the collection is done.
+    async fn process_chunks(
+        &self,
+        file_id: &[u8; 32],
+        chunks: Vec<&[u8]>,
+        params: FecParams,
+        encryption_mode: EncryptionMode,
+        expires_at: Option<u64>,
+    ) -> Result<Vec<ChunkReference>> {
         let mut chunk_refs = Vec::new();
-        let chunk_size = self.config.chunk_size;
 
-        // Split into chunks
-        for (index, chunk_data) in data.chunks(chunk_size).enumerate() {
-            let chunk_id = ChunkId::new(data_id, index);
+        for (index, chunk_data) in chunks.into_iter().enumerate() {
+            let encoded = self.codec.encode(
+                index as u16,
+                chunk_data,
+                params,
+                encryption_mode,
+                expires_at,
+            )?;
+            let cids: Vec<Cid> = encoded.shards.iter().map(|(cid, _)| *cid).collect();
+            let mut batch = encoded.shards;
+            if let Some(dedup) = &self.dedup {
+                let mut new_shards = Vec::with_capacity(batch.len());
+                for (cid, shard) in batch {
+                    if !dedup.contains(cid.as_bytes()).await? {
+                        new_shards.push((cid, shard));
+                    }
+                }
+                batch = new_shards;
+            }
+            // One batch per stripe lets backends amortize round trips
+            self.backend
+                .put_shards(&batch)
+                .instrument(tracing::debug_span!(
+                    "put_shards",
+                    chunk = index,
+                    shards = batch.len()
+                ))
+                .await?;
+            if let Some(placement) = &self.placement {
+                placement.publish(file_id, &cids).await?;
+            }
+            chunk_refs.push(encoded.reference);
+        }
 
-            // Store chunk data in memory for testing
-            let chunk_hash = blake3::hash(chunk_data);
-            let chunk_ref_id = hex::encode(chunk_hash.as_bytes());
-            {
-                let mut storage = self.chunk_storage.write();
-                storage.insert(chunk_ref_id, chunk_data.to_vec());
+        Ok(chunk_refs)
+    }
+
+    /// Retrieve a chunk by fetching its shards and decoding them
+    async fn retrieve_chunk(
+        &self,
+        file_id: &[u8; 32],
+        chunk_ref: &ChunkReference,
+    ) -> Result<Vec<u8>> {
+        let (shards, _) = self.fetch_shards(file_id, chunk_ref).await;
+        self.codec.decode(chunk_ref, &shards)
+    }
+
+    /// Fetch every shard of a chunk, treating shards that fail CID verification as missing
+    ///
+    /// Shards the backend lacks or holds corrupted are fetched from other
+    /// nodes when the pipeline has a locator and transport, so the health
+    /// returned covers every node holding the file.
+    async fn fetch_shards(
+        &self,
+        file_id: &[u8; 32],
+        chunk_ref: &ChunkReference,
+    ) -> (Vec<Option<Shard>>, ChunkHealth) {
+        let cids: Vec<Cid> = chunk_ref.shard_ids.iter().map(|id| Cid::new(*id)).collect();
+        let mut fetched = self.get_shards_parallel(&cids).await;
+        if let Some(placement) = &self.placement {
+            fetched.resize(cids.len(), None);
+            for (cid, slot) in cids.iter().zip(fetched.iter_mut()) {
+                let intact = slot
+                    .as_ref()
+                    .is_some_and(|s| s.cid().is_ok_and(|c| c == *cid));
+                if !intact {
+                    if let Some(shard) = placement.fetch(file_id, cid).await {
+                        *slot = Some(shard);
+                    }
+                }
             }
+        }
+        let (shards, health) = ChunkCodec::verify_shards(chunk_ref, fetched);
 
-            let share_ids = vec![ShareId::new(&chunk_id, 0)];
+        self.shard_loss.record(
+            health.total_shards,
+            health.missing.len() + health.corrupted.len(),
+        );
+        (shards, health)
+    }
 
-            // Register chunk
-            let chunk_info = ChunkInfo {
-                id: chunk_id,
-                data_id: *data_id,
-                size: chunk_data.len(),
-                encrypted_size: chunk_data.len(),
-                share_ids,
-                encryption_key_hash: [0u8; 32], // Would store actual key hash
-                created_at: std::time::SystemTime::now(),
-            };
+    /// Fetch shards in up to `storage.parallel_operations` concurrent batches
+    ///
+    /// The batches run as futures on the caller's task rather than spawned
+    /// tasks, so any executor can drive the pipeline. Shards of a batch that
+    /// fails are reported as unavailable.
+    async fn get_shards_parallel(&self, cids: &[Cid]) -> Vec<Option<Shard>> {
+        let parallel = self.config().storage.parallel_operations.max(1);
+        let batch_size = cids.len().div_ceil(parallel).max(1);
+
+        let batches = cids.chunks(batch_size).map(|batch| {
+            async move {
+                self.backend.get_shards(batch).await.unwrap_or_else(|e| {
+                    tracing::debug!("Shard batch unavailable: {}", e);
+                    vec![None; batch.len()]
+                })
+            }
+            .instrument(tracing::debug_span!("get_shards", shards = batch.len()))
+        });
+        join_all(batches).await.into_iter().flatten().collect()
+    }
 
-            {
-                let mut registry = self.chunk_registry.write();
-                registry.register_chunk(chunk_info);
+    /// Check every shard of a file without decoding it
+    #[tracing::instrument(
+        name = "verify_file",
+        skip_all,
+        fields(file_id = %hex::encode(meta.file_id), chunks = meta.chunks.len())
+    )]
+    pub async fn verify_file(&self, meta: &FileMetadata) -> Result<FileHealth> {
+        let mut chunks = Vec::with_capacity(meta.chunks.len());
+        for chunk_ref in &meta.chunks {
+            let (_, health) = self.fetch_shards(&meta.file_id, chunk_ref).await;
+            chunks.push(health);
+        }
+
+        Ok(FileHealth {
+            file_id: meta.file_id,
+            chunks,
+        })
+    }
+
+    /// Rebuild and rewrite any missing or corrupted shards of a file
+    ///
+    /// With a [`ShardLocator`], shards held by another node count as
+    /// available, and this node is published as holder of those it rebuilds.
+    #[tracing::instrument(
+        name = "repair_file",
+        skip_all,
+        fields(file_id = %hex::encode(meta.file_id), chunks = meta.chunks.len())
+    )]
+    pub async fn repair_file(&self, meta: &FileMetadata) -> Result<RepairReport> {
+        let mut report = RepairReport {
+            file_id: meta.file_id,
+            ..Default::default()
+        };
+
+        for chunk_ref in &meta.chunks {
+            let (shards, health) = self.fetch_shards(&meta.file_id, chunk_ref).await;
+            match health.status() {
+                HealthStatus::Healthy => continue,
+                HealthStatus::Degraded
+                    if health.missing.len() + health.corrupted.len()
+                        < self.config().fec.repair_threshold =>
+                {
+                    continue
+                }
+                HealthStatus::Unrecoverable => {
+                    tracing::warn!(
+                        "Chunk {} cannot be repaired: {} of {} shards available",
+                        hex::encode(chunk_ref.chunk_id),
+                        health.available(),
+                        health.total_shards
+                    );
+                    report.chunks_unrecoverable += 1;
+                    continue;
+                }
+                HealthStatus::Degraded => {}
             }
 
-            // Create chunk reference
-            let chunk_ref = ChunkReference::new(
-                blake3::hash(chunk_data).into(),
-                0,            // stripe_index
-                index as u16, // shard_index
-                chunk_data.len() as u32,
-            );
-            chunk_refs.push(chunk_ref);
+            let mut restored = Vec::new();
+            for (cid, shard) in self.codec.rebuild(chunk_ref, &shards, &health)? {
+                self.backend
+                    .put_shard(&cid, &shard)
+                    .instrument(tracing::debug_span!("put_shard", cid = %cid.to_hex()))
+                    .await?;
+                report.shards_restored += 1;
+                restored.push(cid);
+            }
+            if let Some(placement) = &self.placement {
+                placement.publish(&meta.file_id, &restored).await?;
+            }
+            report.chunks_repaired += 1;
         }
 
-        Ok(chunk_refs)
+        Ok(report)
     }
 
-    /// Retrieve a chunk from storage
-    async fn retrieve_chunk(&self, chunk_id: &[u8; 32]) -> Result<Vec<u8>> {
-        let storage = self.chunk_storage.read();
+    /// Metadata of every file processed by this pipeline
+    pub fn list_files(&self) -> Vec<FileMetadata> {
+        self.catalog.read().values().cloned().collect()
+    }
 
-        // The chunk_id is actually the blake3 hash of the chunk data
-        let chunk_key = hex::encode(chunk_id);
+    /// Metadata of the latest version of a file processed by this pipeline
+    pub fn file_metadata(&self, file_id: &[u8; 32]) -> Option<FileMetadata> {
+        self.catalog.read().get(file_id).cloned()
+    }
 
-        // Look up chunk by exact hash match
-        if let Some(data) = storage.get(&chunk_key) {
-            return Ok(data.clone());
+    /// Files encrypted under a convergence secret that is no longer active
+    ///
+    /// Always empty without a secret registry, as the keystore then holds a
+    /// single secret that never rotates.
+    pub fn files_pending_reencryption(&self) -> Vec<FileMetadata> {
+        let Some(active) = self.secret_registry.as_ref().and_then(|r| r.active_id()) else {
+            return Vec::new();
+        };
+        self.catalog
+            .read()
+            .values()
+            .filter(|meta| {
+                meta.quantum_encryption_metadata
+                    .as_ref()
+                    .and_then(|q| q.convergence_secret_id)
+                    .is_some_and(|id| id != active)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Rewrite a file under the active convergence secret
+    ///
+    /// The file keeps its ID, local metadata and recorded processing
+    /// settings and becomes a new version; chunks of the old version are
+    /// left to garbage collection. Shard expiry is not carried over.
+    pub async fn reencrypt_file(&mut self, meta: &FileMetadata) -> Result<FileMetadata> {
+        let data = self.retrieve_file(meta).await?;
+        let local = meta.local_metadata.clone().unwrap_or_default();
+        let mut meta_in = Meta {
+            filename: local.filename,
+            author: local.author,
+            description: local.description,
+            mime_type: local.mime_type,
+            tags: local.tags,
+            ..Meta::new()
+        };
+        if let Some(params) = meta.params {
+            meta_in.fec_params = Some((params.data_shards as u8, params.parity_shards as u8));
+            meta_in.encryption_mode = Some(params.encryption_mode);
+            meta_in.compression = Some(match params.compression_level {
+                Some(level) => (true, level),
+                None => (false, self.config().compression_level),
+            });
+            meta_in.chunk_size = params.chunk_size;
+        }
+        self.process_file(meta.file_id, &data, Some(meta_in)).await
+    }
+
+    /// FEC parameters derived from the configuration
+    /// Pipeline configuration with the per-file overrides in `meta` applied
+    fn file_config(&self, meta: Option<&Meta>) -> Result<Config> {
+        let mut config = Config::clone(&self.config());
+        let Some(meta) = meta else {
+            return Ok(config);
+        };
+        if let Some((data_shards, parity_shards)) = meta.fec_params {
+            config = config.with_fec_params(data_shards, parity_shards);
+        }
+        if let Some(mode) = meta.encryption_mode {
+            config = config.with_encryption_mode(mode);
+        }
+        if let Some((on, level)) = meta.compression {
+            config = config.with_compression(on, level);
+        }
+        if let Some(bytes) = meta.chunk_size {
+            config = config.with_chunk_size(bytes);
         }
+        config.validate()?;
+        Ok(config)
+    }
 
-        anyhow::bail!("Chunk not found: {}", chunk_key)
+    /// FEC parameters and chunk size for `content_size` bytes of data
+    fn fec_choice(&self, config: &Config, content_size: usize) -> Result<FecChoice> {
+        let inputs = FecInputs {
+            content_size: content_size as u64,
+            shard_loss_rate: self.shard_loss.rate(),
+            target_durability: config.fec.target_durability,
+            data_shards: config.data_shards as u16,
+            parity_shards: config.parity_shards as u16,
+            chunk_size: config.chunk_size,
+        };
+        let choice = self.fec_policy.choose(&inputs)?;
+        if choice.chunk_size == 0 {
+            return Err(PipelineError::EmptyChunkSize.into());
+        }
+        Ok(choice)
     }
 
     /// Reconstruct data from chunks (with FEC if needed)
     async fn reconstruct_data(&self, chunks: &[Vec<u8>], _meta: &FileMetadata) -> Result<Vec<u8>> {
-        // Simple concatenation for now - FEC reconstruction would be more complex
         if chunks.iter().any(|chunk| chunk.is_empty()) {
-            anyhow::bail!("One or more chunks are empty, cannot reconstruct data");
+            return Err(PipelineError::EmptyChunk.into());
         }
         Ok(chunks.concat())
     }
@@ -345,69 +1659,122 @@ impl<B: StorageBackend> StoragePipeline<B> {
     fn recover_key(
         &self,
         metadata: &EncryptionMetadata,
-        file_id: &[u8; 32],
+        data_id: &DataId,
     ) -> Result<EncryptionKey> {
         match metadata.key_derivation {
             crate::crypto::KeyDerivation::Blake3Convergent => {
                 // Get original data from storage
                 let orig_storage = self.original_data_storage.read();
                 let original_data = orig_storage
-                    .get(file_id)
-                    .ok_or_else(|| anyhow::anyhow!("Original data not found for file"))?;
+                    .get(data_id.as_bytes())
+                    .ok_or(CryptoError::Missing("Original data not found for file"))?;
 
                 let secret = if metadata.convergence_secret_id.is_some() {
                     Some(self.get_user_secret()?)
                 } else {
                     None
                 };
-                derive_convergent_key(original_data, secret.as_ref())
-            }
-            crate::crypto::KeyDerivation::Random => {
-                anyhow::bail!("Random keys cannot be reconstructed without external storage")
+                Ok(derive_convergent_key(original_data, secret.as_ref())?)
             }
+            crate::crypto::KeyDerivation::Random => Err(CryptoError::KeyNotRecoverable.into()),
         }
     }
 
-    /// Get user secret for convergent encryption
+    /// Get user secret for convergent encryption, creating it on first use
     fn get_user_secret(&self) -> Result<[u8; 32]> {
-        // Simplified - would retrieve from secure storage
-        Ok([0u8; 32])
+        let keystore = self
+            .keystore
+            .as_ref()
+            .ok_or(PipelineError::MissingKeystore)?;
+        Ok(*keystore.get_or_create_secret(CONVERGENCE_SECRET_ID)?)
     }
 
-    /// Compress data
-    fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
-        use flate2::write::GzEncoder;
-        use flate2::Compression;
-        use std::io::Write;
+    /// Secret new ConvergentWithSecret files are encrypted with
+    fn active_secret(&self) -> Result<ConvergenceSecret> {
+        match &self.secret_registry {
+            Some(registry) => Ok(registry.active()?),
+            None => Ok(ConvergenceSecret::new(self.get_user_secret()?)),
+        }
+    }
 
-        let level = Compression::new(self.config.compression_level as u32);
-        let mut encoder = GzEncoder::new(Vec::new(), level);
-        encoder.write_all(data).context("Compression failed")?;
-        encoder.finish().context("Failed to finish compression")
+    /// Secret with the ID recorded in a file's metadata
+    fn resolve_secret(&self, id: &[u8; 32]) -> Result<ConvergenceSecret> {
+        match &self.secret_registry {
+            Some(registry) => Ok(registry.resolve(id)?),
+            None => Ok(ConvergenceSecret::new(self.get_user_secret()?)),
+        }
     }
 
-    /// Decompress data
-    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
-        use flate2::read::GzDecoder;
-        use std::io::Read;
+    /// Record a key usage if auditing is on
+    fn audit(
+        &self,
+        operation: AuditOperation,
+        metadata: &QuantumEncryptionMetadata,
+        file_id: [u8; 32],
+    ) -> Result<()> {
+        if let Some(log) = &self.audit_log {
+            log.record(AuditEvent::new(
+                operation,
+                AuditEvent::key_id_of(metadata),
+                file_id,
+            ))?;
+        }
+        Ok(())
+    }
 
-        let mut decoder = GzDecoder::new(data);
-        let mut decompressed = Vec::new();
-        decoder
-            .read_to_end(&mut decompressed)
-            .context("Decompression failed")?;
-        Ok(decompressed)
+    /// Reject forged or tampered metadata before touching any shards
+    fn check_signature(&self, meta: &FileMetadata) -> Result<()> {
+        if meta.signature.is_none() && self.trusted_signers.is_empty() {
+            return Ok(());
+        }
+        let signer = meta.verify_signature()?;
+        if !self.trusted_signers.is_empty()
+            && !self
+                .trusted_signers
+                .iter()
+                .any(|key| key.as_slice() == signer)
+        {
+            return Err(PipelineError::UntrustedSigner.into());
+        }
+        Ok(())
     }
 
     /// Run garbage collection
-    pub async fn run_gc(&self) -> Result<()> {
-        let _report = self.gc.run().await?;
-        Ok(())
+    ///
+    /// The report lists every chunk collected or failed, and whether the
+    /// run was cancelled with [`cancel_gc`](Self::cancel_gc).
+    pub async fn run_gc(&self) -> Result<CollectionReport> {
+        Ok(self.gc.run().await?)
+    }
+
+    /// Subscribe to progress events of garbage collection runs
+    pub fn subscribe_gc(&self) -> broadcast::Receiver<GcEvent> {
+        self.gc.subscribe()
+    }
+
+    /// Stop the garbage collection run in progress, if any
+    pub fn cancel_gc(&self) {
+        self.gc.cancel();
+    }
+
+    /// Start collecting garbage in the background as configured in
+    /// `Config::gc`
+    ///
+    /// Returns `None` when GC is disabled. Must be called within a tokio
+    /// runtime.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn_gc(&self) -> Option<GcHandle> {
+        let config = self.config();
+        config.gc.enabled.then(|| {
+            GCScheduler::from_config(self.gc.clone(), &config.gc)
+                .follow_interval(self.gc_interval.subscribe())
+                .spawn(config.gc.run_interval)
+        })
     }
 
     /// Get pipeline statistics
     pub fn stats(&self) -> PipelineStats {
-        let registry = self.chunk_registry.read();
+        let registry = &self.chunk_registry;
         let registry_stats = registry.stats();
 
         PipelineStats {
@@ -415,15 +1782,70 @@ impl<B: StorageBackend> StoragePipeline<B> {
             total_size: registry_stats.total_size,
             referenced_size: registry_stats.referenced_size,
             unreferenced_size: registry_stats.unreferenced_size,
-            encryption_mode: self.config.encryption_mode,
+            encryption_mode: self.config().encryption_mode,
             fec_params: (
-                self.config.data_shards as u16,
-                self.config.parity_shards as u16,
+                self.config().data_shards as u16,
+                self.config().parity_shards as u16,
             ),
         }
     }
 }
 
+/// Shard placement shared with other nodes through a [`ShardLocator`]
+struct Placement {
+    locator: Arc<dyn ShardLocator>,
+    /// This node, as published to the locator
+    local_node: NodeEndpoint,
+    /// Reaches other nodes; without it, located copies are not fetched
+    transport: Option<Arc<dyn NodeTransport>>,
+}
+
+impl Placement {
+    /// Record this node as holder of `cids`
+    async fn publish(&self, object: &[u8; 32], cids: &[Cid]) -> Result<()> {
+        Ok(self
+            .locator
+            .publish(object, cids, &self.local_node)
+            .instrument(tracing::debug_span!("publish_shards", shards = cids.len()))
+            .await?)
+    }
+
+    /// An intact copy of shard `cid` held by another node, if any
+    async fn fetch(&self, object: &[u8; 32], cid: &Cid) -> Option<Shard> {
+        let transport = self.transport.as_ref()?;
+        let nodes = match self.locator.resolve(object, cid).await {
+            Ok(nodes) => nodes,
+            Err(e) => {
+                tracing::debug!("Cannot locate shard {}: {}", cid.to_hex(), e);
+                return None;
+            }
+        };
+        for node in nodes.iter().filter(|n| **n != self.local_node) {
+            let request = Request::GetShard { cid: *cid };
+            match transport.call(node, request).await {
+                Ok(Response::Shard(bytes)) => match Shard::from_bytes(&bytes) {
+                    Ok(shard) if shard.cid().is_ok_and(|c| c == *cid) => return Some(shard),
+                    _ => tracing::warn!(
+                        "Node {}:{} returned a corrupted copy of shard {}",
+                        node.address,
+                        node.port,
+                        cid.to_hex()
+                    ),
+                },
+                Ok(response) => tracing::debug!(
+                    "Node {}:{} did not return shard {}: {:?}",
+                    node.address,
+                    node.port,
+                    cid.to_hex(),
+                    response
+                ),
+                Err(e) => tracing::debug!("Node {}:{} unreachable: {}", node.address, node.port, e),
+            }
+        }
+        None
+    }
+}
+
 /// Main pipeline for processing files (legacy compatibility)
 pub struct Pipeline {
     /// Configuration
@@ -434,17 +1856,19 @@ pub struct Pipeline {
     #[allow(dead_code)]
     storage: Arc<dyn StorageBackend>,
     /// Chunk registry
-    chunk_registry: Arc<RwLock<ChunkRegistry>>,
+    chunk_registry: Arc<ChunkRegistry>,
     /// Version manager
     version_manager: Arc<RwLock<VersionManager>>,
     /// Garbage collector
     gc: Arc<GarbageCollector>,
+    /// Source of the convergence secret
+    keystore: Option<Arc<dyn Keystore>>,
 }
 
 impl Pipeline {
     /// Create a new pipeline with the given configuration
     pub async fn new(config: Config, storage: Arc<dyn StorageBackend>) -> Result<Self> {
-        config.validate().context("Invalid configuration")?;
+        config.validate()?;
 
         let encryption = CryptoEngine::new();
 
@@ -454,18 +1878,18 @@ impl Pipeline {
             stripe_size: config.fec.stripe_size as u32,
         };
 
-        let chunk_registry = Arc::new(RwLock::new(ChunkRegistry::new()));
-        let version_manager = Arc::new(RwLock::new(VersionManager::new(chunk_registry.clone())));
-
-        use crate::gc::RetentionPolicy;
-        let retention_policy =
-            RetentionPolicy::KeepRecent(config.gc.retention_days as u64 * 24 * 3600);
-        let gc = Arc::new(GarbageCollector::new(
-            retention_policy,
-            chunk_registry.clone(),
-            storage.clone(),
+        let chunk_registry = Arc::new(ChunkRegistry::new());
+        let version_manager = Arc::new(RwLock::new(
+            VersionManager::new(chunk_registry.clone())
+                .with_auto_tag_interval(config.version.auto_tag_interval),
         ));
 
+        let retention_policy = config.gc.retention_policy();
+        let gc = Arc::new(
+            GarbageCollector::new(retention_policy, chunk_registry.clone(), storage.clone())
+                .with_config(&config.gc),
+        );
+
         Ok(Self {
             config,
             encryption,
@@ -473,9 +1897,16 @@ impl Pipeline {
             chunk_registry,
             version_manager,
             gc,
+            keystore: None,
         })
     }
 
+    /// Keep the convergence secret in `keystore`
+    pub fn with_keystore(mut self, keystore: Arc<dyn Keystore>) -> Self {
+        self.keystore = Some(keystore);
+        self
+    }
+
     /// Process a file: encrypt and encode (legacy compatibility)
     pub async fn process_file(
         &mut self,
@@ -485,7 +1916,7 @@ impl Pipeline {
     ) -> Result<FileMetadata> {
         // Optionally compress
         let processed_data = if self.config.encryption.compress_before_encrypt {
-            self.compress(data)?
+            chunk_codec::compress(data, self.config.encryption.compression_level as u8)?
         } else {
             data.to_vec()
         };
@@ -529,6 +1960,7 @@ impl Pipeline {
         {
             let mut version_mgr = self.version_manager.write();
             version_mgr.create_version(&metadata)?;
+            version_mgr.prune(&file_id, &self.config.version)?;
         }
 
         Ok(metadata)
@@ -543,6 +1975,8 @@ impl Pipeline {
             let chunk_data = self.retrieve_chunk(&chunk_ref.chunk_id).await?;
             chunks.push(chunk_data);
         }
+        let chunk_ids: Vec<[u8; 32]> = metadata.chunks.iter().map(|c| c.chunk_id).collect();
+        self.chunk_registry.record_read(&chunk_ids)?;
 
         // Combine chunks
         let encrypted_data = chunks.concat();
@@ -553,7 +1987,7 @@ impl Pipeline {
 
         // Optionally decompress
         if self.config.encryption.compress_before_encrypt {
-            self.decompress(&decrypted)
+            chunk_codec::decompress(&decrypted)
         } else {
             Ok(decrypted)
         }
@@ -592,8 +2026,8 @@ impl Pipeline {
             };
 
             {
-                let mut registry = self.chunk_registry.write();
-                registry.register_chunk(chunk_info);
+                let registry = &self.chunk_registry;
+                registry.register_chunk(chunk_info)?;
             }
 
             // Create chunk reference
@@ -642,46 +2076,23 @@ impl Pipeline {
         Ok(generate_random_key())
     }
 
-    /// Get user secret for convergent encryption
+    /// Get user secret for convergent encryption, creating it on first use
     fn get_user_secret(&self) -> Result<[u8; 32]> {
-        // Simplified - would retrieve from secure storage
-        Ok([0u8; 32])
-    }
-
-    /// Compress data
-    fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
-        use flate2::write::GzEncoder;
-        use flate2::Compression;
-        use std::io::Write;
-
-        let level = Compression::new(self.config.encryption.compression_level);
-        let mut encoder = GzEncoder::new(Vec::new(), level);
-        encoder.write_all(data).context("Compression failed")?;
-        encoder.finish().context("Failed to finish compression")
-    }
-
-    /// Decompress data
-    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
-        use flate2::read::GzDecoder;
-        use std::io::Read;
-
-        let mut decoder = GzDecoder::new(data);
-        let mut decompressed = Vec::new();
-        decoder
-            .read_to_end(&mut decompressed)
-            .context("Decompression failed")?;
-        Ok(decompressed)
+        let keystore = self
+            .keystore
+            .as_ref()
+            .ok_or(PipelineError::MissingKeystore)?;
+        Ok(*keystore.get_or_create_secret(CONVERGENCE_SECRET_ID)?)
     }
 
     /// Run garbage collection
-    pub async fn run_gc(&self) -> Result<()> {
-        let _report = self.gc.run().await?;
-        Ok(())
+    pub async fn run_gc(&self) -> Result<CollectionReport> {
+        Ok(self.gc.run().await?)
     }
 
     /// Get pipeline statistics
     pub fn stats(&self) -> PipelineStats {
-        let registry = self.chunk_registry.read();
+        let registry = &self.chunk_registry;
         let registry_stats = registry.stats();
 
         PipelineStats {
@@ -715,7 +2126,9 @@ pub struct PipelineStats {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::keystore::MemoryKeystore;
     use crate::storage::LocalStorage;
+    use anyhow::Result;
     use tempfile::TempDir;
 
     #[tokio::test]
@@ -746,6 +2159,280 @@ mod tests {
         // Test retrieval
         let retrieved = pipeline.retrieve_file(&metadata).await.unwrap();
         assert_eq!(retrieved, data);
+
+        // Ranged reads clamp to the end of the file
+        let range = pipeline.retrieve_range(&metadata, 7, 5).await.unwrap();
+        assert_eq!(range, b"World");
+        let tail = pipeline.retrieve_range(&metadata, 130, 100).await.unwrap();
+        assert_eq!(tail, &data[130..]);
+        let past_end = pipeline.retrieve_range(&metadata, 1000, 10).await.unwrap();
+        assert!(past_end.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_storage_pipeline_reconfigure() {
+        let temp_dir = TempDir::new().unwrap();
+        let backend = LocalStorage::new(temp_dir.path().to_path_buf())
+            .await
+            .unwrap();
+        let mut pipeline = StoragePipeline::new(Config::default(), backend)
+            .await
+            .unwrap();
+        let data = vec![7u8; 10_000];
+        let before = pipeline.process_file([2u8; 32], &data, None).await.unwrap();
+
+        pipeline
+            .reconfigure(
+                &ConfigUpdate::new()
+                    .compression_level(9)
+                    .repair_threshold(2)
+                    .parallel_operations(1),
+            )
+            .unwrap();
+        let config = pipeline.config();
+        assert_eq!(config.compression_level, 9);
+        assert_eq!(config.fec.repair_threshold, 2);
+        assert_eq!(config.storage.parallel_operations, 1);
+
+        // Invalid updates leave the running configuration untouched
+        assert!(pipeline
+            .reconfigure(
+                &ConfigUpdate::new()
+                    .parallel_operations(0)
+                    .compression_level(1)
+            )
+            .is_err());
+        assert_eq!(pipeline.config().compression_level, 9);
+
+        let after = pipeline.process_file([3u8; 32], &data, None).await.unwrap();
+        assert_eq!(pipeline.retrieve_file(&before).await.unwrap(), data);
+        assert_eq!(pipeline.retrieve_file(&after).await.unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_storage_pipeline_fec_policy() {
+        struct Fixed;
+        impl FecPolicy for Fixed {
+            fn choose(&self, _inputs: &FecInputs) -> crate::Result<FecChoice> {
+                Ok(FecChoice {
+                    params: FecParams::new(3, 2)?,
+                    chunk_size: 1000,
+                })
+            }
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let data = vec![9u8; 2500];
+
+        // Default configs choose parameters adaptively; fixed ones do not
+        let backend = LocalStorage::new(temp_dir.path().join("auto"))
+            .await
+            .unwrap();
+        let mut auto = StoragePipeline::new(Config::default(), backend)
+            .await
+            .unwrap();
+        let meta = auto.process_file([1u8; 32], &data, None).await.unwrap();
+        let shards = meta.chunks[0].shard_ids.len();
+        assert!(shards > 8 && shards != 20, "{} shards", shards);
+
+        let backend = LocalStorage::new(temp_dir.path().join("fixed"))
+            .await
+            .unwrap();
+        let mut fixed = StoragePipeline::new(Config::default().with_fec_params(16, 4), backend)
+            .await
+            .unwrap();
+        let meta = fixed.process_file([1u8; 32], &data, None).await.unwrap();
+        assert_eq!(meta.chunks[0].shard_ids.len(), 20);
+
+        let backend = LocalStorage::new(temp_dir.path().join("custom"))
+            .await
+            .unwrap();
+        let mut custom =
+            StoragePipeline::builder(Config::default().with_compression(false, 1), backend)
+                .fec_policy(Fixed)
+                .build()
+                .unwrap();
+        let meta = custom.process_file([1u8; 32], &data, None).await.unwrap();
+        assert!(meta.chunks.len() >= 3);
+        assert!(meta.chunks.iter().all(|c| c.shard_ids.len() == 5));
+        assert_eq!(custom.retrieve_file(&meta).await.unwrap(), data);
+        assert_eq!(custom.shard_loss_rate(), None);
+    }
+
+    #[tokio::test]
+    async fn test_storage_pipeline_per_file_overrides() {
+        let temp_dir = TempDir::new().unwrap();
+        let backend = LocalStorage::new(temp_dir.path().to_path_buf())
+            .await
+            .unwrap();
+        let mut pipeline =
+            StoragePipeline::builder(Config::default().with_fec_params(16, 4), backend)
+                .keystore(MemoryKeystore::new())
+                .build()
+                .unwrap();
+        let data: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+
+        let meta = Meta::new()
+            .with_fec_params(4, 2)
+            .with_encryption_mode(EncryptionMode::ConvergentWithSecret)
+            .with_compression(false, 1)
+            .with_chunk_size(1000);
+        let custom = pipeline
+            .process_file([1u8; 32], &data, Some(meta))
+            .await
+            .unwrap();
+        assert_eq!(
+            custom.params,
+            Some(FileParams {
+                encryption_mode: EncryptionMode::ConvergentWithSecret,
+                data_shards: 4,
+                parity_shards: 2,
+                chunk_size: Some(1000),
+                compression_level: None,
+            })
+        );
+        assert!(custom.chunks.len() > 1);
+        assert!(custom.chunks.iter().all(|c| c.shard_ids.len() == 6));
+
+        let default = pipeline.process_file([2u8; 32], &data, None).await.unwrap();
+        let params = default.params.unwrap();
+        assert_eq!((params.data_shards, params.parity_shards), (16, 4));
+        assert_eq!(params.compression_level, Some(6));
+
+        // Retrieval follows the recorded settings, not the current config
+        pipeline
+            .reconfigure(&ConfigUpdate::new().compression_level(9))
+            .unwrap();
+        assert_eq!(pipeline.retrieve_file(&custom).await.unwrap(), data);
+        assert_eq!(pipeline.retrieve_file(&default).await.unwrap(), data);
+
+        let invalid = Meta::new().with_fec_params(0, 2);
+        assert!(pipeline
+            .process_file([3u8; 32], &data, Some(invalid))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_storage_pipeline_convergence_secret_from_keystore() {
+        use crate::keystore::FileKeystore;
+
+        let temp_dir = TempDir::new().unwrap();
+        let keystore_path = temp_dir.path().join("secrets.json");
+        let backend = LocalStorage::new(temp_dir.path().join("shards"))
+            .await
+            .unwrap();
+        let config = Config::default().with_encryption_mode(EncryptionMode::ConvergentWithSecret);
+        let keystore = FileKeystore::create_with_iterations(&keystore_path, "pw", 1_000).unwrap();
+
+        let mut pipeline = StoragePipeline::builder(config, backend)
+            .keystore(keystore)
+            .build()
+            .unwrap();
+        let data = b"deduplicated only among holders of the secret";
+        let metadata = pipeline.process_file([3u8; 32], data, None).await.unwrap();
+        assert_eq!(pipeline.retrieve_file(&metadata).await.unwrap(), data);
+
+        // The secret was generated on first use and persisted
+        let reopened = FileKeystore::open(&keystore_path, "pw").unwrap();
+        assert!(reopened
+            .get_secret(CONVERGENCE_SECRET_ID)
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_convergent_with_secret_requires_keystore() {
+        let config = Config::default().with_encryption_mode(EncryptionMode::ConvergentWithSecret);
+        let built = StoragePipeline::builder(config, crate::storage::MemoryStorage::new()).build();
+        assert!(matches!(
+            built,
+            Err(Error::Pipeline(PipelineError::MissingKeystore))
+        ));
+
+        // Choosing the mode per file needs a keystore too
+        let mut pipeline =
+            StoragePipeline::new(Config::default(), crate::storage::MemoryStorage::new())
+                .await
+                .unwrap();
+        let meta = Meta::new().with_encryption_mode(EncryptionMode::ConvergentWithSecret);
+        assert!(matches!(
+            pipeline.process_file([1u8; 32], b"data", Some(meta)).await,
+            Err(Error::Pipeline(PipelineError::MissingKeystore))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_storage_pipeline_resolves_rotated_secrets() {
+        let temp_dir = TempDir::new().unwrap();
+        let backend = LocalStorage::new(temp_dir.path().to_path_buf())
+            .await
+            .unwrap();
+        let registry = Arc::new(SecretRegistry::in_memory(Arc::new(MemoryKeystore::new())));
+        let config = Config::default().with_encryption_mode(EncryptionMode::ConvergentWithSecret);
+        let mut pipeline = StoragePipeline::builder(config, backend)
+            .secret_registry(registry.clone())
+            .build()
+            .unwrap();
+
+        let before = pipeline
+            .process_file([5u8; 32], b"old", None)
+            .await
+            .unwrap();
+        let rotated = registry.rotate("rotated").unwrap();
+        let after = pipeline
+            .process_file([6u8; 32], b"new", None)
+            .await
+            .unwrap();
+
+        let secret_id = |m: &FileMetadata| {
+            m.quantum_encryption_metadata
+                .as_ref()
+                .unwrap()
+                .convergence_secret_id
+        };
+        assert_eq!(secret_id(&after), Some(rotated));
+        assert_ne!(secret_id(&before), Some(rotated));
+        assert_eq!(pipeline.retrieve_file(&before).await.unwrap(), b"old");
+        assert_eq!(pipeline.retrieve_file(&after).await.unwrap(), b"new");
+    }
+
+    #[tokio::test]
+    async fn test_storage_pipeline_verifies_metadata_signatures() {
+        let temp_dir = TempDir::new().unwrap();
+        let backend = LocalStorage::new(temp_dir.path().to_path_buf())
+            .await
+            .unwrap();
+        let creator = MetadataSigner::generate().unwrap();
+        let mut pipeline = StoragePipeline::builder(Config::default(), backend)
+            .signer(creator.clone())
+            .trusted_signer(creator.public_key())
+            .build()
+            .unwrap();
+
+        let data = b"signed by its creator";
+        let metadata = pipeline.process_file([4u8; 32], data, None).await.unwrap();
+        assert_eq!(pipeline.retrieve_file(&metadata).await.unwrap(), data);
+
+        let mut tampered = metadata.clone();
+        tampered.file_size += 1;
+        let err = pipeline.retrieve_file(&tampered).await.unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::Corruption);
+
+        // A valid signature from someone else is still rejected
+        let mut forged = metadata;
+        forged.sign(&MetadataSigner::generate().unwrap()).unwrap();
+        assert!(matches!(
+            pipeline.retrieve_file(&forged).await,
+            Err(Error::Pipeline(PipelineError::UntrustedSigner))
+        ));
+
+        // The version history is signed by the same key
+        pipeline
+            .version_manager
+            .read()
+            .verify_history(&[4u8; 32], &[creator.public_key()])
+            .unwrap();
     }
 
     #[tokio::test]
@@ -784,6 +2471,483 @@ mod tests {
         assert_eq!(stats.total_size, 0);
     }
 
+    /// Chunker that always splits data into two halves
+    struct HalvingChunker;
+
+    impl Chunker for HalvingChunker {
+        fn chunk<'a>(&self, data: &'a [u8]) -> Vec<&'a [u8]> {
+            let (a, b) = data.split_at(data.len() / 2);
+            vec![a, b]
+        }
+    }
+
+    /// Crypto provider that counts calls to the wrapped engine
+    struct CountingProvider {
+        inner: QuantumCryptoEngine,
+        encryptions: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl CryptoProvider for CountingProvider {
+        fn encrypt(
+            &mut self,
+            data: &[u8],
+            mode: EncryptionMode,
+            convergence_secret: Option<&ConvergenceSecret>,
+        ) -> crypto::Result<(Vec<u8>, QuantumEncryptionMetadata)> {
+            self.encryptions
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.inner.encrypt(data, mode, convergence_secret)
+        }
+
+        fn decrypt(
+            &self,
+            encrypted_data: &[u8],
+            metadata: &QuantumEncryptionMetadata,
+            convergence_secret: Option<&ConvergenceSecret>,
+            original_data: Option<&[u8]>,
+        ) -> crypto::Result<Vec<u8>> {
+            self.inner
+                .decrypt(encrypted_data, metadata, convergence_secret, original_data)
+        }
+
+        fn derive_key(
+            &self,
+            data: &[u8],
+            mode: EncryptionMode,
+            convergence_secret: Option<&ConvergenceSecret>,
+        ) -> crypto::Result<Option<EncryptionKey>> {
+            self.inner.derive_key(data, mode, convergence_secret)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_storage_pipeline_builder_custom_components() {
+        let temp_dir = TempDir::new().unwrap();
+        let backend = LocalStorage::new(temp_dir.path().to_path_buf())
+            .await
+            .unwrap();
+
+        let encryptions = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let config = Config::default().with_fec_params(4, 2);
+        let mut pipeline = StoragePipeline::builder(config, backend)
+            .chunker(HalvingChunker)
+            .crypto_provider(CountingProvider {
+                inner: QuantumCryptoEngine::new(),
+                encryptions: encryptions.clone(),
+            })
+            .fec_backend(Box::new(crate::backends::pure_rust::PureRustBackend::new()))
+            .build()
+            .unwrap();
+
+        let data = vec![7u8; 5000];
+        let metadata = pipeline.process_file([3u8; 32], &data, None).await.unwrap();
+
+        assert_eq!(metadata.chunks.len(), 2);
+        assert_eq!(encryptions.load(std::sync::atomic::Ordering::SeqCst), 1);
+        for chunk in &metadata.chunks {
+            assert_eq!(chunk.shard_ids.len(), 6);
+        }
+
+        let retrieved = pipeline.retrieve_file(&metadata).await.unwrap();
+        assert_eq!(retrieved, data);
+    }
+
+    #[tokio::test]
+    async fn test_classical_crypto_provider() -> Result<()> {
+        let config = Config::default()
+            .with_fec_params(4, 2)
+            .with_compression(false, 1);
+        let mut pipeline = StoragePipeline::builder(config, crate::storage::MemoryStorage::new())
+            .crypto_provider(CryptoEngine::new())
+            .build()?;
+
+        let data = vec![9u8; 3000];
+        let metadata = pipeline.process_file([4u8; 32], &data, None).await?;
+        assert_eq!(pipeline.retrieve_file(&metadata).await?, data);
+
+        // Deterministic, keyed as derive_key says, and readable by the
+        // quantum engine
+        let mut engine = CryptoEngine::new();
+        let (first, meta) =
+            CryptoProvider::encrypt(&mut engine, &data, EncryptionMode::Convergent, None)?;
+        let (second, _) =
+            CryptoProvider::encrypt(&mut engine, &data, EncryptionMode::Convergent, None)?;
+        assert_eq!(first, second);
+        let key =
+            CryptoProvider::derive_key(&engine, &data, EncryptionMode::Convergent, None)?.unwrap();
+        assert_eq!(engine.decrypt(&first, &key)?, data);
+        let quantum = QuantumCryptoEngine::new();
+        assert_eq!(quantum.decrypt(&first, &meta, None, Some(&data))?, data);
+
+        assert!(
+            CryptoProvider::encrypt(&mut engine, &data, EncryptionMode::RandomKey, None).is_err()
+        );
+
+        // Data past one segment is streamed, and still deterministic
+        let large = vec![7u8; stream::DEFAULT_SEGMENT_SIZE + 1];
+        let (first, meta) =
+            CryptoProvider::encrypt(&mut engine, &large, EncryptionMode::Convergent, None)?;
+        let (second, _) =
+            CryptoProvider::encrypt(&mut engine, &large, EncryptionMode::Convergent, None)?;
+        assert_eq!(first, second);
+        assert!(meta.stream_segment_size.is_some());
+        assert_eq!(
+            CryptoProvider::decrypt(&engine, &first, &meta, None, Some(&large))?,
+            large
+        );
+        assert_eq!(quantum.decrypt(&first, &meta, None, Some(&large))?, large);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_storage_pipeline_streams_large_files() -> Result<()> {
+        let config = Config::default()
+            .with_fec_params(4, 2)
+            .with_chunk_size(32 * 1024)
+            .with_compression(false, 1);
+        let mut pipeline =
+            StoragePipeline::builder(config, crate::storage::MemoryStorage::new()).build()?;
+
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let metadata = pipeline.process_file([8u8; 32], &data, None).await?;
+        let quantum = metadata.quantum_encryption_metadata.as_ref().unwrap();
+        assert_eq!(
+            quantum.stream_segment_size,
+            Some(stream::DEFAULT_SEGMENT_SIZE as u32)
+        );
+        assert_eq!(pipeline.retrieve_file(&metadata).await?, data);
+
+        // Ranges across a segment boundary and up to the end of the file
+        let boundary = stream::DEFAULT_SEGMENT_SIZE as u64 - 10;
+        let range = pipeline.retrieve_range(&metadata, boundary, 20).await?;
+        assert_eq!(range, &data[boundary as usize..boundary as usize + 20]);
+        let tail = pipeline.retrieve_range(&metadata, 199_990, 100).await?;
+        assert_eq!(tail, &data[199_990..]);
+
+        // Ranged reads only fetch the chunks covering their segments
+        for id in &metadata.chunks[0].shard_ids {
+            pipeline.backend().delete_shard(&Cid::new(*id)).await?;
+        }
+        assert!(pipeline.retrieve_file(&metadata).await.is_err());
+        let range = pipeline.retrieve_range(&metadata, 150_000, 1000).await?;
+        assert_eq!(range, &data[150_000..151_000]);
+        assert!(pipeline.retrieve_range(&metadata, 0, 10).await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_storage_pipeline_runs_without_tokio() -> Result<()> {
+        futures_executor::block_on(async {
+            let config = Config::default().with_fec_params(4, 2);
+            let mut pipeline =
+                StoragePipeline::new(config, crate::storage::MemoryStorage::new()).await?;
+
+            let data: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+            let metadata = pipeline.process_file([8u8; 32], &data, None).await?;
+            assert_eq!(pipeline.retrieve_file(&metadata).await?, data);
+
+            let health = pipeline.verify_file(&metadata).await?;
+            assert_eq!(health.status(), HealthStatus::Healthy);
+            Ok(())
+        })
+    }
+
+    /// Serves one node's backend, as if reached over the network
+    struct LoopbackTransport(Arc<crate::storage::MemoryStorage>);
+
+    #[async_trait::async_trait]
+    impl NodeTransport for LoopbackTransport {
+        async fn call(
+            &self,
+            _node: &NodeEndpoint,
+            request: Request,
+        ) -> std::result::Result<Response, crate::FecError> {
+            Ok(crate::network::handle_request(&*self.0, request).await)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shard_locator_spans_nodes() -> Result<()> {
+        let locator: Arc<dyn ShardLocator> = Arc::new(crate::locator::MemoryLocator::new());
+        let node_a: NodeEndpoint = "10.0.0.1:9000".parse()?;
+        let node_b: NodeEndpoint = "10.0.0.2:9000".parse()?;
+        let config = Config::default().with_fec_params(4, 2);
+
+        let store_a = Arc::new(crate::storage::MemoryStorage::new());
+        let mut pipeline_a = StoragePipeline::builder(config.clone(), store_a.clone())
+            .shard_locator(locator.clone(), node_a.clone())
+            .build()?;
+        let metadata = pipeline_a
+            .process_file([30u8; 32], &[4u8; 3000], None)
+            .await?;
+        let lost = Cid::new(metadata.chunks[0].shard_ids[1]);
+        assert_eq!(
+            locator.resolve(&metadata.file_id, &lost).await?,
+            vec![node_a.clone()]
+        );
+
+        // Node B holds nothing itself but finds every shard on node A
+        let pipeline_b = StoragePipeline::builder(config, crate::storage::MemoryStorage::new())
+            .shard_locator(locator.clone(), node_b.clone())
+            .node_transport(Arc::new(LoopbackTransport(store_a.clone())))
+            .build()?;
+        assert!(pipeline_b.verify_file(&metadata).await?.is_healthy());
+
+        // A shard lost everywhere is rebuilt on node B and published
+        store_a.delete_shard(&lost).await?;
+        let report = pipeline_b.repair_file(&metadata).await?;
+        assert_eq!(report.shards_restored, 1);
+        assert!(pipeline_b.backend().get_shard(&lost).await.is_ok());
+        assert_eq!(
+            locator.resolve(&metadata.file_id, &lost).await?,
+            vec![node_a, node_b]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_storage_pipeline_audits_key_usage() -> Result<()> {
+        let audit_log = Arc::new(AuditLog::in_memory());
+        let config = Config::default().with_fec_params(4, 2);
+        let mut pipeline = StoragePipeline::builder(config, crate::storage::MemoryStorage::new())
+            .audit_log(audit_log.clone())
+            .build()?;
+
+        let metadata = pipeline.process_file([5u8; 32], &[1u8; 500], None).await?;
+        pipeline.retrieve_file(&metadata).await?;
+
+        let entries = audit_log.export();
+        AuditLog::verify(&entries)?;
+        let operations: Vec<_> = entries.iter().map(|e| e.event.operation).collect();
+        assert_eq!(
+            operations,
+            [AuditOperation::Encrypt, AuditOperation::Decrypt]
+        );
+        assert!(entries.iter().all(|e| e.event.file_id == [5u8; 32]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rollback_reuses_old_chunks() -> Result<()> {
+        let config = Config::default().with_fec_params(4, 2);
+        let mut pipeline =
+            StoragePipeline::new(config, crate::storage::MemoryStorage::new()).await?;
+        let file_id = [21u8; 32];
+
+        let original = vec![1u8; 5000];
+        let v1 = pipeline.process_file(file_id, &original, None).await?;
+        let v2 = pipeline.process_file(file_id, &[2u8; 5000], None).await?;
+        let shards = pipeline.backend().list_shards().await?.len();
+
+        let rolled_back = pipeline.rollback(&file_id, &v1.compute_id())?;
+        assert_eq!(rolled_back.parent_version, Some(v2.compute_id()));
+        let chunk_ids = |m: &FileMetadata| m.chunks.iter().map(|c| c.chunk_id).collect::<Vec<_>>();
+        assert_eq!(chunk_ids(&rolled_back), chunk_ids(&v1));
+        assert_eq!(pipeline.backend().list_shards().await?.len(), shards);
+        assert_eq!(pipeline.retrieve_file(&rolled_back).await?, original);
+
+        let history = pipeline.version_manager.read().get_history(&file_id);
+        assert_eq!(history.len(), 3);
+        assert!(pipeline.rollback(&[0u8; 32], &v1.compute_id()).is_err());
+
+        // Versions can be addressed by tag for retrieval
+        pipeline
+            .version_manager()
+            .write()
+            .tag_version(&v1.compute_id(), "first")?;
+        let tagged = pipeline.resolve_version(&file_id, "first")?;
+        assert_eq!(pipeline.retrieve_file(&tagged).await?, original);
+        assert!(pipeline.resolve_version(&file_id, "missing").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_patch_syncs_update_between_nodes() -> Result<()> {
+        let config = Config::default().with_fec_params(4, 2);
+        let mut sender =
+            StoragePipeline::new(config.clone(), crate::storage::MemoryStorage::new()).await?;
+        let receiver = StoragePipeline::new(config, crate::storage::MemoryStorage::new()).await?;
+        let file_id = [22u8; 32];
+
+        let v1 = sender.process_file(file_id, &[1u8; 5000], None).await?;
+        let v2 = sender.process_file(file_id, &[2u8; 5000], None).await?;
+
+        // The receiver starts from v1 with all of its shards
+        let full = sender.export_patch(&v1, &v1).await?;
+        assert!(full.shards.is_empty());
+        let v1_shards: Vec<Cid> = v1
+            .chunks
+            .iter()
+            .flat_map(|c| c.shard_ids.iter().map(|id| Cid::new(*id)))
+            .collect();
+        for cid in &v1_shards {
+            let shard = sender.backend().get_shard(cid).await?;
+            receiver.backend().put_shard(cid, &shard).await?;
+        }
+        receiver.register_version(&v1)?;
+
+        let patch = VersionPatch::from_bytes(&sender.export_patch_bytes(&v1, &v2).await?)?;
+        let applied = receiver.apply_patch(&v1, &patch).await?;
+        assert_eq!(applied.compute_id(), v2.compute_id());
+        assert!(receiver.verify_file(&applied).await?.is_healthy());
+        assert!(receiver.apply_patch(&v2, &patch).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_persistent_registry_loads_at_startup() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let config = Config::default().with_fec_params(4, 2);
+        let mut pipeline =
+            StoragePipeline::builder(config.clone(), crate::storage::MemoryStorage::new())
+                .persistent_registry(dir.path())
+                .build()?;
+        let meta = pipeline
+            .process_file([24u8; 32], &[5u8; 5000], None)
+            .await?;
+        drop(pipeline);
+
+        let pipeline = StoragePipeline::builder(config, crate::storage::MemoryStorage::new())
+            .persistent_registry(dir.path())
+            .build()?;
+        let registry = &pipeline.chunk_registry;
+        for chunk in &meta.chunks {
+            assert_eq!(registry.get_ref_count(&chunk.chunk_id), Some(1));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pruned_versions_are_garbage_collected() -> Result<()> {
+        let mut config = Config::default().with_fec_params(4, 2);
+        config.version.max_versions = 2;
+        config.version.auto_tag_interval = 0;
+        config.gc.retention_days = 0;
+        let mut pipeline =
+            StoragePipeline::new(config, crate::storage::MemoryStorage::new()).await?;
+        let file_id = [23u8; 32];
+
+        let v1 = pipeline.process_file(file_id, &[1u8; 5000], None).await?;
+        let v2 = pipeline.process_file(file_id, &[2u8; 5000], None).await?;
+        let shard_count =
+            |meta: &FileMetadata| -> usize { meta.chunks.iter().map(|c| c.shard_ids.len()).sum() };
+        let before = pipeline.backend().list_shards().await?.len();
+        assert_eq!(before, shard_count(&v1) + shard_count(&v2));
+
+        // The third version pushes the first out of the history
+        let v3 = pipeline.process_file(file_id, &[3u8; 5000], None).await?;
+        assert_eq!(
+            pipeline.version_manager.read().get_history(&file_id).len(),
+            2
+        );
+        let report = pipeline.run_gc().await?;
+        assert_eq!(report.collected, v1.chunks.len());
+        assert_eq!(
+            pipeline.backend().list_shards().await?.len(),
+            shard_count(&v2) + shard_count(&v3)
+        );
+
+        // Removing a version by hand frees its shards too
+        pipeline.remove_version(&v2.compute_id())?;
+        pipeline.run_gc().await?;
+        assert_eq!(
+            pipeline.backend().list_shards().await?.len(),
+            shard_count(&v3)
+        );
+        assert_eq!(pipeline.retrieve_file(&v3).await?, vec![3u8; 5000]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fips_policy_pipeline() -> Result<()> {
+        let config = Config::default()
+            .with_fec_params(4, 2)
+            .with_encryption_mode(EncryptionMode::ConvergentWithSecret)
+            .with_crypto_policy(CryptoPolicy::Fips);
+        let mut pipeline =
+            StoragePipeline::builder(config.clone(), crate::storage::MemoryStorage::new())
+                .keystore(MemoryKeystore::new())
+                .build()?;
+        let data = vec![3u8; 1500];
+        let metadata = pipeline.process_file([6u8; 32], &data, None).await?;
+        assert_eq!(metadata.crypto_policy, CryptoPolicy::Fips);
+        let quantum = metadata.quantum_encryption_metadata.as_ref().unwrap();
+        assert_eq!(quantum.algorithm, EncryptionAlgorithm::Aes256Gcm);
+        assert_eq!(pipeline.retrieve_file(&metadata).await?, data);
+
+        // Non-approved primitives from a custom provider are refused
+        let mut pipeline = StoragePipeline::builder(config, crate::storage::MemoryStorage::new())
+            .crypto_provider(QuantumCryptoEngine::new())
+            .keystore(MemoryKeystore::new())
+            .build()?;
+        assert!(pipeline.process_file([6u8; 32], &data, None).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_oblivious_dedup_skips_held_shards() -> Result<()> {
+        let config = Config::default().with_fec_params(4, 2);
+        let data = vec![8u8; 3000];
+        let mut first =
+            StoragePipeline::new(config.clone(), crate::storage::MemoryStorage::new()).await?;
+        first.process_file([7u8; 32], &data, None).await?;
+
+        // Shards the first backend holds are not written again
+        let oracle = crate::dedup::StorageDedupOracle::new(first.backend().clone());
+        let mut second = StoragePipeline::builder(config, crate::storage::MemoryStorage::new())
+            .oblivious_dedup(DedupClient::new(Arc::new(oracle)))
+            .build()?;
+        second.process_file([7u8; 32], &data, None).await?;
+        assert!(second.backend().list_shards().await?.is_empty());
+
+        second.process_file([8u8; 32], &[9u8; 100], None).await?;
+        assert_eq!(second.backend().list_shards().await?.len(), 6);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_storage_pipeline_stores_shards_in_backend() {
+        let config = Config::default()
+            .with_fec_params(4, 2)
+            .with_chunk_size(1024)
+            .with_compression(false, 1);
+        let mut pipeline = StoragePipeline::new(config, crate::storage::MemoryStorage::new())
+            .await
+            .unwrap();
+
+        let data = vec![42u8; 3000];
+        let metadata = pipeline.process_file([4u8; 32], &data, None).await.unwrap();
+
+        // Identical plaintext chunks share shards, so count distinct CIDs
+        let expected: std::collections::HashSet<_> = metadata
+            .chunks
+            .iter()
+            .flat_map(|c| c.shard_ids.iter().copied())
+            .collect();
+        assert_eq!(pipeline.backend().shard_count(), expected.len());
+    }
+
+    #[tokio::test]
+    async fn test_storage_pipeline_reports_lost_shards() -> Result<()> {
+        let config = Config::default().with_fec_params(4, 2);
+        let mut pipeline =
+            StoragePipeline::new(config, crate::storage::MemoryStorage::new()).await?;
+        let metadata = pipeline.process_file([6u8; 32], &[7u8; 2000], None).await?;
+
+        // Three of six shards gone leaves too few to decode
+        for id in &metadata.chunks[0].shard_ids[..3] {
+            pipeline.backend().delete_shard(&Cid::new(*id)).await?;
+        }
+        let err = pipeline.retrieve_file(&metadata).await.unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::InsufficientShares);
+
+        let mut unknown = metadata.clone();
+        unknown.chunks[0].shard_ids = vec![[0xEE; 32]; 6];
+        let err = pipeline.retrieve_file(&unknown).await.unwrap_err();
+        assert!(matches!(err, Error::Pipeline(PipelineError::NoShards(_))));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_pipeline_basic() {
         let temp_dir = TempDir::new().unwrap();
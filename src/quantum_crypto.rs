@@ -4,22 +4,35 @@
 //! for key encapsulation and AES-256-GCM for data encryption. It replaces
 //! the previous crypto module with quantum-safe alternatives.
 
-use anyhow::{Context, Result};
+use aes_gcm::Aes256Gcm;
+use aes_gcm_siv::aead::{Aead, KeyInit};
+use aes_gcm_siv::Aes256GcmSiv;
 use blake3::Hasher;
 use generic_array::GenericArray;
 use hkdf::Hkdf;
+use hpke::aead::ExportOnlyAead;
+use hpke::kdf::HkdfSha256;
+use hpke::kem::X25519HkdfSha256;
+use hpke::{Deserializable, Kem, OpModeR, OpModeS, Serializable};
+use parking_lot::RwLock;
 use saorsa_pqc::api::{
-    kem::ml_kem_768,
+    kem::{MlKem, MlKemPublicKey, MlKemVariant},
     symmetric::{generate_nonce, ChaCha20Poly1305},
 };
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
-use zeroize::{Zeroize, ZeroizeOnDrop};
+use sharks::{Share, Sharks};
+use std::sync::Arc;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 use crate::config::EncryptionMode;
+use crate::crypto::{CryptoError, EncryptionAlgorithm, Result};
+use crate::keystore::{KemKeyId, KemKeyStore, Keystore};
+use crate::secure_memory::SecretBytes;
+use crate::stream::{self, DEFAULT_SEGMENT_SIZE, PREFIX_LEN};
 
 /// Security levels for post-quantum cryptography
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub enum SecurityLevel {
     /// NIST Level 1 (128-bit security)
     Level1,
@@ -30,18 +43,117 @@ pub enum SecurityLevel {
     Level5,
 }
 
+impl SecurityLevel {
+    /// ML-KEM parameter set providing this level
+    pub fn kem_variant(self) -> MlKemVariant {
+        match self {
+            SecurityLevel::Level1 => MlKemVariant::MlKem512,
+            SecurityLevel::Level3 => MlKemVariant::MlKem768,
+            SecurityLevel::Level5 => MlKemVariant::MlKem1024,
+        }
+    }
+
+    /// Level provided by an ML-KEM parameter set
+    pub fn from_kem_variant(variant: MlKemVariant) -> Self {
+        match variant {
+            MlKemVariant::MlKem512 => SecurityLevel::Level1,
+            MlKemVariant::MlKem768 => SecurityLevel::Level3,
+            MlKemVariant::MlKem1024 => SecurityLevel::Level5,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QuantumEncryptionMetadata {
     /// Security level used
     pub security_level: SecurityLevel,
     /// Encapsulated shared secret (from ML-KEM)
     pub encapsulated_secret: Vec<u8>,
-    /// Nonce used for ChaCha20Poly1305
+    /// AEAD the data was encrypted with
+    #[serde(default = "legacy_algorithm")]
+    pub algorithm: EncryptionAlgorithm,
+    /// Nonce used for the AEAD
     pub nonce: [u8; 12],
     /// Key derivation method for convergent encryption
     pub key_derivation: QuantumKeyDerivation,
     /// Optional convergence secret identifier
     pub convergence_secret_id: Option<[u8; 32]>,
+    /// Keystore id of the ML-KEM keypair the secret was encapsulated to
+    #[serde(default)]
+    pub kem_key_id: Option<KemKeyId>,
+    /// Content key encrypted under a master key, if envelope encryption is on
+    #[serde(default)]
+    pub wrapped_key: Option<WrappedKey>,
+    /// Threshold shares of the content key, if key splitting is on
+    #[serde(default)]
+    pub key_split: Option<KeySplit>,
+    /// Argon2id parameters of a passphrase-derived convergence secret
+    #[serde(default)]
+    pub secret_kdf: Option<PassphraseKdf>,
+    /// Whether the nonce lives only here; older ciphertexts repeat it as a
+    /// 12-byte prefix
+    #[serde(default)]
+    pub detached_nonce: bool,
+    /// Plaintext bytes per segment when the data was sealed as a
+    /// [STREAM](crate::stream) under the prefix `nonce[..7]`, rather than as
+    /// one AEAD message
+    #[serde(default)]
+    pub stream_segment_size: Option<u32>,
+}
+
+/// Shamir t-of-n split of a content key
+///
+/// Hand the shares to different custodians with
+/// [`take_shares`](Self::take_shares), and put any `threshold` of them back
+/// before decrypting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeySplit {
+    /// Number of shares needed to reconstruct the key
+    pub threshold: u8,
+    /// Shares currently held in the metadata
+    pub shares: Vec<KeyShare>,
+}
+
+impl KeySplit {
+    /// Remove the shares for distribution, keeping only the threshold
+    pub fn take_shares(&mut self) -> Vec<KeyShare> {
+        std::mem::take(&mut self.shares)
+    }
+
+    /// Whether enough shares are present to reconstruct the key
+    pub fn is_recoverable(&self) -> bool {
+        self.shares.len() >= self.threshold as usize
+    }
+
+    fn recover(&self) -> Result<Zeroizing<[u8; 32]>> {
+        let shares = self
+            .shares
+            .iter()
+            .map(|share| Share::try_from(share.0.as_slice()))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| CryptoError::KeySplit(format!("invalid key share: {}", e)))?;
+        let secret =
+            Zeroizing::new(Sharks(self.threshold).recover(&shares).map_err(|e| {
+                CryptoError::KeySplit(format!("failed to recover content key: {}", e))
+            })?);
+        let content_key: [u8; 32] = secret.as_slice().try_into().map_err(|_| {
+            CryptoError::KeySplit("recovered content key has the wrong length".to_string())
+        })?;
+        Ok(Zeroizing::new(content_key))
+    }
+}
+
+/// One share of a split content key, including its x coordinate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyShare(pub Vec<u8>);
+
+/// Content key encrypted under a master key held in a [`Keystore`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WrappedKey {
+    /// Keystore id of the master key
+    pub master_key_id: String,
+    /// Nonce followed by the ChaCha20-Poly1305 encrypted content key
+    pub ciphertext: Vec<u8>,
 }
 
 /// Quantum-safe key derivation methods
@@ -51,21 +163,142 @@ pub enum QuantumKeyDerivation {
     Blake3Convergent,
     /// Random key generation using ML-KEM
     QuantumRandom,
+    /// RFC 9180 HPKE in base mode with DHKEM(X25519, HKDF-SHA256),
+    /// HKDF-SHA256 and the export-only AEAD, the data key being exported
+    /// from the context
+    ///
+    /// Data is sealed as a [STREAM](crate::stream) of `chunk_size` segments.
+    Hpke {
+        /// Plaintext bytes per chunk
+        chunk_size: u32,
+    },
+    /// SHA-256 HKDF convergent key of the classical
+    /// [`CryptoEngine`](crate::crypto::CryptoEngine)
+    Sha256Convergent,
+    /// Random key from X25519 and ML-KEM secrets combined with HKDF-SHA256,
+    /// secure while either primitive holds
+    HybridX25519,
 }
 
+/// Where a chunk sits within a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkPosition {
+    /// File the chunk belongs to
+    pub file_id: [u8; 32],
+    /// Index of the chunk within the file
+    pub chunk_index: u32,
+}
+
+/// KEM of the RFC 9180 suite HPKE mode uses
+type HpkeKem = X25519HkdfSha256;
+
+/// Key schedule `info` binding contexts to this crate's file format
+const HPKE_INFO: &[u8] = b"saorsa-fec hpke v1";
+
+/// Exporter context of the key data is streamed under
+const HPKE_STREAM_CONTEXT: &[u8] = b"saorsa-fec stream";
+
+/// Plaintext bytes per STREAM segment in HPKE mode
+pub const DEFAULT_HPKE_CHUNK_SIZE: u32 = 64 * 1024;
+
 /// Convergence secret for controlled deduplication
 #[derive(Zeroize, ZeroizeOnDrop)]
-pub struct ConvergenceSecret([u8; 32]);
+pub struct ConvergenceSecret {
+    secret: SecretBytes<32>,
+    /// How the secret was derived from a passphrase, if it was
+    #[zeroize(skip)]
+    kdf: Option<PassphraseKdf>,
+}
 
 impl ConvergenceSecret {
     /// Create a new convergence secret
     pub fn new(secret: [u8; 32]) -> Self {
-        Self(secret)
+        Self {
+            secret: SecretBytes::new(secret),
+            kdf: None,
+        }
+    }
+
+    /// Derive a convergence secret from a passphrase with Argon2id
+    ///
+    /// Use the same `kdf` (salt included) everywhere the secret should
+    /// match, e.g. the one recorded in
+    /// [`QuantumEncryptionMetadata::secret_kdf`] of an earlier file.
+    pub fn from_passphrase(passphrase: &str, kdf: &PassphraseKdf) -> Result<Self> {
+        let params = argon2::Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+            .map_err(|e| CryptoError::KeyDerivation(format!("invalid Argon2 parameters: {}", e)))?;
+        let argon2 =
+            argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+
+        let mut secret = [0u8; 32];
+        argon2
+            .hash_password_into(passphrase.as_bytes(), &kdf.salt, &mut secret)
+            .map_err(|e| CryptoError::KeyDerivation(e.to_string()))?;
+        Ok(Self {
+            secret: SecretBytes::new(secret),
+            kdf: Some(kdf.clone()),
+        })
     }
 
     /// Get the secret as bytes
     pub fn as_bytes(&self) -> &[u8; 32] {
-        &self.0
+        self.secret.as_bytes()
+    }
+
+    /// Whether the secret is locked into RAM (see the `mlock` feature)
+    pub fn is_locked(&self) -> bool {
+        self.secret.is_locked()
+    }
+
+    /// Identifier recorded in metadata, which does not reveal the secret
+    pub fn id(&self) -> [u8; 32] {
+        let mut hasher = Hasher::new();
+        hasher.update(b"secret-id");
+        hasher.update(self.secret.as_bytes());
+        *hasher.finalize().as_bytes()
+    }
+
+    /// Parameters the secret was derived with, if it came from a passphrase
+    pub fn kdf(&self) -> Option<&PassphraseKdf> {
+        self.kdf.as_ref()
+    }
+}
+
+/// Argon2id parameters for deriving a convergence secret from a passphrase
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PassphraseKdf {
+    /// Salt mixed into the derivation
+    pub salt: [u8; 16],
+    /// Memory cost in KiB
+    pub memory_kib: u32,
+    /// Number of passes
+    pub iterations: u32,
+    /// Degree of parallelism
+    pub parallelism: u32,
+}
+
+impl PassphraseKdf {
+    /// Default parameters (19 MiB, 2 passes, 1 lane) with a random salt
+    pub fn new() -> Self {
+        Self::with_params(19 * 1024, 2, 1)
+    }
+
+    /// Custom cost parameters with a random salt
+    pub fn with_params(memory_kib: u32, iterations: u32, parallelism: u32) -> Self {
+        let mut salt = [0u8; 16];
+        rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut salt);
+        Self {
+            salt,
+            memory_kib,
+            iterations,
+            parallelism,
+        }
+    }
+}
+
+impl Default for PassphraseKdf {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -75,6 +308,18 @@ pub struct QuantumCryptoEngine {
     security_level: SecurityLevel,
     /// Last nonce used (for metadata)
     last_nonce: Option<[u8; 12]>,
+    /// AEAD used for new encryptions, chosen per mode when unset
+    algorithm: Option<EncryptionAlgorithm>,
+    /// Keystore holding decapsulation keys for RandomKey mode
+    keystore: Option<Arc<RwLock<KemKeyStore>>>,
+    /// Long-term public key RandomKey secrets are encapsulated to
+    recipient: Option<MlKemPublicKey>,
+    /// Keystore and id of the master key content keys are wrapped under
+    master_key: Option<(Arc<dyn Keystore>, String)>,
+    /// Threshold and share count content keys are split into
+    key_split: Option<(u8, u8)>,
+    /// Whether RandomKey mode combines ML-KEM with X25519
+    hybrid: bool,
 }
 
 impl Default for QuantumCryptoEngine {
@@ -89,6 +334,12 @@ impl QuantumCryptoEngine {
         Self {
             security_level: SecurityLevel::default(),
             last_nonce: None,
+            algorithm: None,
+            keystore: None,
+            recipient: None,
+            master_key: None,
+            key_split: None,
+            hybrid: false,
         }
     }
 
@@ -97,9 +348,164 @@ impl QuantumCryptoEngine {
         Self {
             security_level: level,
             last_nonce: None,
+            algorithm: None,
+            keystore: None,
+            recipient: None,
+            master_key: None,
+            key_split: None,
+            hybrid: false,
         }
     }
 
+    /// Encrypt every mode with `algorithm`
+    ///
+    /// By default RandomKey mode picks the fastest AEAD for this CPU (see
+    /// [`EncryptionAlgorithm::detect`]), while convergent modes stay on
+    /// ChaCha20-Poly1305 so identical content encrypts identically on every
+    /// machine and still deduplicates. The algorithm is recorded in the
+    /// metadata, so decryption works whatever the decrypting engine is
+    /// configured with. [`EncryptionAlgorithm::Aes256GcmSiv`] keeps convergent
+    /// encryption safe should its deterministic nonces ever repeat under the
+    /// same key.
+    pub fn with_algorithm(mut self, algorithm: EncryptionAlgorithm) -> Self {
+        self.algorithm = Some(algorithm);
+        self
+    }
+
+    /// Keep RandomKey decapsulation keys in `keystore`
+    ///
+    /// Without a keystore RandomKey encryption uses a throwaway keypair and
+    /// the data cannot be decrypted again.
+    pub fn with_keystore(mut self, keystore: Arc<RwLock<KemKeyStore>>) -> Self {
+        self.keystore = Some(keystore);
+        self
+    }
+
+    /// Encapsulate RandomKey secrets to a recipient's ML-KEM public key
+    ///
+    /// The key must match the engine's security level. Only the holder of the matching decapsulation key, typically kept in
+    /// their [`KemKeyStore`], can decrypt. Takes precedence over the keystore
+    /// when encrypting.
+    pub fn with_recipient(mut self, public_key: &[u8]) -> Result<Self> {
+        self.recipient = Some(parse_public_key(self.security_level, public_key)?);
+        Ok(self)
+    }
+
+    /// Encapsulate RandomKey secrets with both X25519 and ML-KEM
+    ///
+    /// Keys are generated with [`KemKeyStore::generate_hybrid`]. A plain
+    /// ML-KEM [`with_recipient`](Self::with_recipient) key cannot be used;
+    /// encrypt to hybrid keys with
+    /// [`encrypt_for_hybrid_recipient`](Self::encrypt_for_hybrid_recipient).
+    pub fn with_hybrid_kem(mut self) -> Self {
+        self.hybrid = true;
+        self
+    }
+
+    /// Wrap every content key under the master key `master_key_id`
+    ///
+    /// The wrapped key is stored in the metadata, so files can be decrypted
+    /// with just the master key: RandomKey data needs no decapsulation key and
+    /// convergent data no copy of the plaintext. The master key is generated
+    /// in `keystore` on first use. Rotating it only rewrites metadata, see
+    /// [`rewrap_key`](Self::rewrap_key).
+    pub fn with_master_key(
+        mut self,
+        keystore: Arc<dyn Keystore>,
+        master_key_id: impl Into<String>,
+    ) -> Self {
+        self.master_key = Some((keystore, master_key_id.into()));
+        self
+    }
+
+    /// Split every content key into `shares` Shamir shares, any `threshold`
+    /// of which reconstruct it
+    ///
+    /// The shares are recorded in [`QuantumEncryptionMetadata::key_split`].
+    /// Distributing them, e.g. one per FEC shard or custodian node, means no
+    /// single holder can decrypt.
+    pub fn with_key_split(mut self, threshold: u8, shares: u8) -> Result<Self> {
+        if threshold == 0 || threshold > shares {
+            return Err(CryptoError::KeySplit(format!(
+                "threshold {} of {} shares",
+                threshold, shares
+            )));
+        }
+        self.key_split = Some((threshold, shares));
+        Ok(self)
+    }
+
+    /// Re-wrap the content key of `metadata` under this engine's master key
+    ///
+    /// The old master key must still be in the keystore. The encrypted data
+    /// itself is untouched.
+    pub fn rewrap_key(&self, metadata: &mut QuantumEncryptionMetadata) -> Result<()> {
+        let wrapped = metadata
+            .wrapped_key
+            .as_ref()
+            .ok_or(CryptoError::Missing("Metadata has no wrapped content key"))?;
+        let content_key = self.unwrap_content_key(wrapped)?;
+        metadata.wrapped_key = self.wrap_content_key(&content_key)?;
+        Ok(())
+    }
+
+    /// Encrypt for the X25519 `recipient_public_key` with RFC 9180 HPKE
+    ///
+    /// The data key is exported from a base-mode context of the
+    /// DHKEM(X25519, HKDF-SHA256), HKDF-SHA256, export-only suite, so any
+    /// RFC 9180 implementation holding the recipient's key can derive it.
+    /// Data is sealed as a [STREAM](crate::stream) of
+    /// [`DEFAULT_HPKE_CHUNK_SIZE`] segments, so reordered, dropped or
+    /// truncated chunks fail to decrypt. X25519 is not post-quantum; use
+    /// [`encrypt_for_recipient`](Self::encrypt_for_recipient) where that
+    /// matters. The recipient decrypts with the key generated by
+    /// [`KemKeyStore::generate_hpke`].
+    pub fn encrypt_hpke(
+        &mut self,
+        data: &[u8],
+        recipient_public_key: &[u8],
+    ) -> Result<(Vec<u8>, QuantumEncryptionMetadata)> {
+        let (encapsulated, key) = hpke_sender_key(recipient_public_key)?;
+
+        let nonce_generic = generate_nonce();
+        let mut nonce = [0u8; 12];
+        nonce.copy_from_slice(&nonce_generic[..12]);
+        self.last_nonce = Some(nonce);
+
+        let algorithm = self.algorithm.unwrap_or_else(EncryptionAlgorithm::detect);
+        let chunk_size = DEFAULT_HPKE_CHUNK_SIZE;
+        let mut prefix = [0u8; PREFIX_LEN];
+        prefix.copy_from_slice(&nonce[..PREFIX_LEN]);
+        let encrypted =
+            stream::seal_stream_with_prefix(algorithm, &key, chunk_size as usize, prefix, data)?;
+
+        let metadata = QuantumEncryptionMetadata {
+            security_level: self.security_level,
+            encapsulated_secret: encapsulated,
+            algorithm,
+            nonce,
+            key_derivation: QuantumKeyDerivation::Hpke { chunk_size },
+            convergence_secret_id: None,
+            kem_key_id: Some(*blake3::hash(recipient_public_key).as_bytes()),
+            wrapped_key: None,
+            key_split: None,
+            secret_kdf: None,
+            detached_nonce: true,
+            stream_segment_size: Some(chunk_size),
+        };
+        Ok((encrypted, metadata))
+    }
+
+    /// Encrypt with a random key encapsulated to `recipient_public_key`
+    pub fn encrypt_for_recipient(
+        &mut self,
+        data: &[u8],
+        recipient_public_key: &[u8],
+    ) -> Result<(Vec<u8>, QuantumEncryptionMetadata)> {
+        let public_key = parse_public_key(self.security_level, recipient_public_key)?;
+        self.encrypt_to_key(data, &public_key)
+    }
+
     /// Encrypt data using the specified encryption mode
     pub fn encrypt(
         &mut self,
@@ -108,11 +514,12 @@ impl QuantumCryptoEngine {
         convergence_secret: Option<&ConvergenceSecret>,
     ) -> Result<(Vec<u8>, QuantumEncryptionMetadata)> {
         match mode {
-            EncryptionMode::Convergent => self.encrypt_convergent(data, None),
+            EncryptionMode::Convergent => self.encrypt_convergent(data, None, None),
             EncryptionMode::ConvergentWithSecret => {
-                let secret = convergence_secret
-                    .context("Convergence secret required for ConvergentWithSecret mode")?;
-                self.encrypt_convergent(data, Some(secret))
+                let secret = convergence_secret.ok_or(CryptoError::Missing(
+                    "Convergence secret required for ConvergentWithSecret mode",
+                ))?;
+                self.encrypt_convergent(data, Some(secret), None)
             }
             EncryptionMode::RandomKey => self.encrypt_random_key(data),
         }
@@ -126,12 +533,78 @@ impl QuantumCryptoEngine {
         convergence_secret: Option<&ConvergenceSecret>,
         original_data: Option<&[u8]>,
     ) -> Result<Vec<u8>> {
+        let key = self
+            .content_key(metadata, convergence_secret, original_data)?
+            .ok_or(CryptoError::Missing(
+                "Original data required for convergent decryption",
+            ))?;
+        self.open_data(metadata, encrypted_data, &key)
+    }
+
+    /// Key the data described by `metadata` was sealed under
+    ///
+    /// With the key, streamed data can be opened a segment at a time through
+    /// [`stream::open_sealed_segment`]. Returns `None` for convergent keys
+    /// when `original_data` is not supplied.
+    pub fn content_key(
+        &self,
+        metadata: &QuantumEncryptionMetadata,
+        convergence_secret: Option<&ConvergenceSecret>,
+        original_data: Option<&[u8]>,
+    ) -> Result<Option<Zeroizing<[u8; 32]>>> {
+        if let Some(wrapped) = &metadata.wrapped_key {
+            return self.unwrap_content_key(wrapped).map(Some);
+        }
+        if let Some(split) = metadata.key_split.as_ref().filter(|s| s.is_recoverable()) {
+            return split.recover().map(Some);
+        }
+
+        let secret = convergence_secret.filter(|_| metadata.convergence_secret_id.is_some());
         match metadata.key_derivation {
-            QuantumKeyDerivation::Blake3Convergent => {
-                self.decrypt_convergent(encrypted_data, metadata, convergence_secret, original_data)
-            }
+            QuantumKeyDerivation::Blake3Convergent => original_data
+                .map(|data| Ok(Zeroizing::new(self.derive_convergent_key(data, secret)?)))
+                .transpose(),
+            QuantumKeyDerivation::Sha256Convergent => original_data
+                .map(|data| {
+                    let key = crate::crypto::derive_convergent_key(
+                        data,
+                        secret.map(ConvergenceSecret::as_bytes),
+                    )?;
+                    Ok(Zeroizing::new(*key.as_bytes()))
+                })
+                .transpose(),
             QuantumKeyDerivation::QuantumRandom => {
-                self.decrypt_random_key(encrypted_data, metadata)
+                let key_id = metadata.kem_key_id.ok_or(CryptoError::Missing(
+                    "Random key decryption requires stored decapsulation key",
+                ))?;
+                let shared_secret = self
+                    .keystore("Random key decryption requires a keystore")?
+                    .read()
+                    .decapsulate(&key_id, &metadata.encapsulated_secret)?;
+                Ok(Some(Zeroizing::new(*shared_secret.as_bytes())))
+            }
+            QuantumKeyDerivation::HybridX25519 => {
+                let key_id = metadata.kem_key_id.ok_or(CryptoError::Missing(
+                    "Hybrid decryption requires stored decapsulation keys",
+                ))?;
+                let key = self
+                    .keystore("Hybrid decryption requires a keystore")?
+                    .read()
+                    .decapsulate_hybrid(&key_id, &metadata.encapsulated_secret)?;
+                Ok(Some(key))
+            }
+            QuantumKeyDerivation::Hpke { .. } => {
+                let key_id = metadata.kem_key_id.ok_or(CryptoError::Missing(
+                    "HPKE decryption requires the recipient key id",
+                ))?;
+                let secret = self
+                    .keystore("HPKE decryption requires a keystore")?
+                    .read()
+                    .x25519_secret(&key_id)?
+                    .ok_or(CryptoError::Missing(
+                        "HPKE decryption requires an X25519 recipient key",
+                    ))?;
+                hpke_recipient_key(&secret, &metadata.encapsulated_secret).map(Some)
             }
         }
     }
@@ -141,46 +614,202 @@ impl QuantumCryptoEngine {
         self.last_nonce.unwrap_or([0u8; 12])
     }
 
+    /// Encrypt one chunk of a file
+    ///
+    /// Convergent nonces are derived from the chunk's position as well as its
+    /// content, so chunks with identical plaintext never share a nonce.
+    /// Decryption works through [`decrypt`](Self::decrypt) as usual.
+    pub fn encrypt_chunk(
+        &mut self,
+        data: &[u8],
+        mode: EncryptionMode,
+        convergence_secret: Option<&ConvergenceSecret>,
+        position: &ChunkPosition,
+    ) -> Result<(Vec<u8>, QuantumEncryptionMetadata)> {
+        match mode {
+            EncryptionMode::Convergent => self.encrypt_convergent(data, None, Some(position)),
+            EncryptionMode::ConvergentWithSecret => {
+                let secret = convergence_secret.ok_or(CryptoError::Missing(
+                    "Convergence secret required for ConvergentWithSecret mode",
+                ))?;
+                self.encrypt_convergent(data, Some(secret), Some(position))
+            }
+            EncryptionMode::RandomKey => self.encrypt_random_key(data),
+        }
+    }
+
     fn encrypt_convergent(
         &mut self,
         data: &[u8],
         secret: Option<&ConvergenceSecret>,
+        position: Option<&ChunkPosition>,
     ) -> Result<(Vec<u8>, QuantumEncryptionMetadata)> {
         // Derive deterministic key from content
         let key_bytes = self.derive_convergent_key(data, secret)?;
 
         // Generate deterministic nonce for convergent encryption
-        let nonce = self.generate_deterministic_nonce(data, secret.map(|s| s.as_bytes()))?;
+        let nonce =
+            self.generate_deterministic_nonce(data, secret.map(|s| s.as_bytes()), position)?;
         self.last_nonce = Some(nonce);
 
-        // Encrypt data with ChaCha20Poly1305
-        let ciphertext = self.chacha20_encrypt(data, &key_bytes, &nonce)?;
+        // Encrypt data with the configured AEAD
+        let algorithm = self.algorithm.unwrap_or_else(legacy_algorithm);
+        let (ciphertext, stream_segment_size) =
+            self.seal_payload(algorithm, data, &key_bytes, &nonce)?;
 
         // Create metadata
         let metadata = QuantumEncryptionMetadata {
             security_level: self.security_level,
             encapsulated_secret: Vec::new(), // No encapsulation for convergent
+            algorithm,
             nonce,
             key_derivation: QuantumKeyDerivation::Blake3Convergent,
-            convergence_secret_id: secret.map(|s| self.compute_secret_id(s.as_bytes())),
+            convergence_secret_id: secret.map(ConvergenceSecret::id),
+            kem_key_id: None,
+            wrapped_key: self.wrap_content_key(&key_bytes)?,
+            key_split: self.split_content_key(&key_bytes),
+            secret_kdf: secret.and_then(|s| s.kdf().cloned()),
+            detached_nonce: true,
+            stream_segment_size,
         };
 
         Ok((ciphertext, metadata))
     }
 
+    /// Encrypt with a random key encapsulated to a hybrid X25519+ML-KEM key
+    ///
+    /// `recipient_public_key` is the ML-KEM public key followed by the 32-byte
+    /// X25519 public key, as returned for keys from
+    /// [`KemKeyStore::generate_hybrid`].
+    pub fn encrypt_for_hybrid_recipient(
+        &mut self,
+        data: &[u8],
+        recipient_public_key: &[u8],
+    ) -> Result<(Vec<u8>, QuantumEncryptionMetadata)> {
+        let (kem_public_key, x25519_public_key) = recipient_public_key
+            .split_last_chunk::<32>()
+            .ok_or(CryptoError::TooShort("Hybrid public key"))?;
+        let kem_public_key = parse_public_key(self.security_level, kem_public_key)?;
+
+        let (kem_secret, kem_ciphertext) = MlKem::new(kem_public_key.variant())
+            .encapsulate(&kem_public_key)
+            .map_err(|e| CryptoError::Kem(format!("encapsulation failed: {:?}", e)))?;
+        let ephemeral = x25519_dalek::EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+        let ephemeral_public = x25519_dalek::PublicKey::from(&ephemeral);
+        let x25519_secret =
+            ephemeral.diffie_hellman(&x25519_dalek::PublicKey::from(*x25519_public_key));
+
+        let mut encapsulated = kem_ciphertext.to_bytes();
+        encapsulated.extend_from_slice(ephemeral_public.as_bytes());
+        let key = hybrid_shared_key(
+            x25519_secret.as_bytes(),
+            &kem_secret.to_bytes(),
+            &encapsulated,
+        )?;
+
+        let nonce_generic = generate_nonce();
+        let mut nonce = [0u8; 12];
+        nonce.copy_from_slice(&nonce_generic[..12]);
+        self.last_nonce = Some(nonce);
+
+        let algorithm = self.algorithm.unwrap_or_else(EncryptionAlgorithm::detect);
+        let (encrypted, stream_segment_size) = self.seal_payload(algorithm, data, &key, &nonce)?;
+
+        let metadata = QuantumEncryptionMetadata {
+            security_level: SecurityLevel::from_kem_variant(kem_public_key.variant()),
+            encapsulated_secret: encapsulated,
+            algorithm,
+            nonce,
+            key_derivation: QuantumKeyDerivation::HybridX25519,
+            convergence_secret_id: None,
+            kem_key_id: Some(*blake3::hash(recipient_public_key).as_bytes()),
+            wrapped_key: self.wrap_content_key(&key)?,
+            key_split: self.split_content_key(&key),
+            secret_kdf: None,
+            detached_nonce: true,
+            stream_segment_size,
+        };
+        Ok((encrypted, metadata))
+    }
+
     fn encrypt_random_key(&mut self, data: &[u8]) -> Result<(Vec<u8>, QuantumEncryptionMetadata)> {
-        // Create ML-KEM instance
-        let kem = ml_kem_768();
+        if self.hybrid {
+            return self.encrypt_random_hybrid(data);
+        }
+
+        // Use the recipient's key, or a new keypair whose decapsulation key
+        // is kept if there is a keystore
+        let public_key = match (&self.recipient, &self.keystore) {
+            (Some(recipient), _) => recipient.clone(),
+            (None, Some(keystore)) => {
+                let mut keystore = keystore.write();
+                let key_id = keystore.generate_with_level(self.security_level)?;
+                let bytes = keystore
+                    .public_key(&key_id)
+                    .ok_or(CryptoError::Missing("Generated key missing from keystore"))?;
+                parse_public_key(self.security_level, bytes)?
+            }
+            (None, None) => {
+                let (public_key, _secret_key) = MlKem::new(self.security_level.kem_variant())
+                    .generate_keypair()
+                    .map_err(|e| CryptoError::Kem(format!("keypair generation failed: {:?}", e)))?;
+                public_key
+            }
+        };
+        let (encrypted, mut metadata) = self.encrypt_to_key(data, &public_key)?;
 
-        // Generate keypair
-        let (public_key, _secret_key) = kem
-            .generate_keypair()
-            .map_err(|e| anyhow::anyhow!("KEM keypair generation failed: {:?}", e))?;
+        // A throwaway keypair cannot be looked up again
+        if self.recipient.is_none() && self.keystore.is_none() {
+            metadata.kem_key_id = None;
+        }
+        Ok((encrypted, metadata))
+    }
 
-        // Encapsulate to get shared secret
-        let (shared_secret, ciphertext) = kem
-            .encapsulate(&public_key)
-            .map_err(|e| anyhow::anyhow!("KEM encapsulation failed: {:?}", e))?;
+    fn encrypt_random_hybrid(
+        &mut self,
+        data: &[u8],
+    ) -> Result<(Vec<u8>, QuantumEncryptionMetadata)> {
+        if self.recipient.is_some() {
+            return Err(CryptoError::InvalidKey(
+                "hybrid mode cannot encrypt to an ML-KEM-only recipient".to_string(),
+            ));
+        }
+        let Some(keystore) = self.keystore.clone() else {
+            // Throwaway keypair: the data can only be decrypted through a
+            // wrapped or split key
+            let (public_key, _secret_key) = MlKem::new(self.security_level.kem_variant())
+                .generate_keypair()
+                .map_err(|e| CryptoError::Kem(format!("keypair generation failed: {:?}", e)))?;
+            let x25519_secret = x25519_dalek::StaticSecret::random_from_rng(rand::rngs::OsRng);
+            let mut hybrid_public_key = public_key.to_bytes();
+            hybrid_public_key
+                .extend_from_slice(x25519_dalek::PublicKey::from(&x25519_secret).as_bytes());
+            let (encrypted, mut metadata) =
+                self.encrypt_for_hybrid_recipient(data, &hybrid_public_key)?;
+            metadata.kem_key_id = None;
+            return Ok((encrypted, metadata));
+        };
+
+        let public_key = {
+            let mut keystore = keystore.write();
+            let key_id = keystore.generate_hybrid(self.security_level)?;
+            keystore
+                .public_key(&key_id)
+                .ok_or(CryptoError::Missing("Generated key missing from keystore"))?
+                .to_vec()
+        };
+        self.encrypt_for_hybrid_recipient(data, &public_key)
+    }
+
+    fn encrypt_to_key(
+        &mut self,
+        data: &[u8],
+        public_key: &MlKemPublicKey,
+    ) -> Result<(Vec<u8>, QuantumEncryptionMetadata)> {
+        // Encapsulate to get shared secret, at the level of the key
+        let (shared_secret, ciphertext) = MlKem::new(public_key.variant())
+            .encapsulate(public_key)
+            .map_err(|e| CryptoError::Kem(format!("encapsulation failed: {:?}", e)))?;
 
         // Derive ChaCha20 key from shared secret - need to convert to [u8; 32]
         let shared_bytes = shared_secret.to_bytes();
@@ -193,54 +822,88 @@ impl QuantumCryptoEngine {
         nonce.copy_from_slice(&nonce_generic[..12]);
         self.last_nonce = Some(nonce);
 
-        // Encrypt data with ChaCha20Poly1305
-        let encrypted = self.chacha20_encrypt(data, &key_bytes, &nonce)?;
+        // Encrypt data with the configured AEAD, or the fastest one here
+        let algorithm = self.algorithm.unwrap_or_else(EncryptionAlgorithm::detect);
+        let (encrypted, stream_segment_size) =
+            self.seal_payload(algorithm, data, &key_bytes, &nonce)?;
 
         // Create metadata
         let metadata = QuantumEncryptionMetadata {
-            security_level: self.security_level,
+            security_level: SecurityLevel::from_kem_variant(public_key.variant()),
             encapsulated_secret: ciphertext.to_bytes(),
+            algorithm,
             nonce,
             key_derivation: QuantumKeyDerivation::QuantumRandom,
             convergence_secret_id: None,
+            kem_key_id: Some(*blake3::hash(&public_key.to_bytes()).as_bytes()),
+            wrapped_key: self.wrap_content_key(&key_bytes)?,
+            key_split: self.split_content_key(&key_bytes),
+            secret_kdf: None,
+            detached_nonce: true,
+            stream_segment_size,
         };
 
         Ok((encrypted, metadata))
     }
 
-    fn decrypt_convergent(
-        &self,
-        encrypted_data: &[u8],
-        metadata: &QuantumEncryptionMetadata,
-        convergence_secret: Option<&ConvergenceSecret>,
-        original_data: Option<&[u8]>,
-    ) -> Result<Vec<u8>> {
-        // For convergent encryption, we need the original data to derive the key
-        let data = original_data.context("Original data required for convergent decryption")?;
+    fn keystore(&self, missing: &'static str) -> Result<&Arc<RwLock<KemKeyStore>>> {
+        self.keystore.as_ref().ok_or(CryptoError::Missing(missing))
+    }
 
-        let secret = if metadata.convergence_secret_id.is_some() {
-            convergence_secret
-        } else {
-            None
+    /// Encrypt `content_key` under the master key, if one is configured
+    fn wrap_content_key(&self, content_key: &[u8; 32]) -> Result<Option<WrappedKey>> {
+        let Some((keystore, master_key_id)) = &self.master_key else {
+            return Ok(None);
         };
+        let master_key = keystore.get_or_create_secret(master_key_id)?;
+        let nonce_generic = generate_nonce();
+        let mut nonce = [0u8; 12];
+        nonce.copy_from_slice(&nonce_generic[..12]);
 
-        // Derive the same key used for encryption
-        let key_bytes = self.derive_convergent_key(data, secret)?;
+        let mut ciphertext = nonce.to_vec();
+        ciphertext.extend(self.seal(legacy_algorithm(), content_key, &master_key, &nonce)?);
+        Ok(Some(WrappedKey {
+            master_key_id: master_key_id.clone(),
+            ciphertext,
+        }))
+    }
 
-        // Decrypt with ChaCha20Poly1305
-        self.chacha20_decrypt(encrypted_data, &key_bytes, &metadata.nonce)
+    fn split_content_key(&self, content_key: &[u8; 32]) -> Option<KeySplit> {
+        let (threshold, shares) = self.key_split?;
+        let shares = Sharks(threshold)
+            .dealer(content_key)
+            .take(shares as usize)
+            .map(|share| KeyShare(Vec::from(&share)))
+            .collect();
+        Some(KeySplit { threshold, shares })
     }
 
-    /// Decrypt random key encryption using ML-KEM
-    fn decrypt_random_key(
-        &self,
-        _encrypted_data: &[u8],
-        _metadata: &QuantumEncryptionMetadata,
-    ) -> Result<Vec<u8>> {
-        anyhow::bail!("Random key decryption requires stored decapsulation key")
+    fn unwrap_content_key(&self, wrapped: &WrappedKey) -> Result<Zeroizing<[u8; 32]>> {
+        let (keystore, _) = self.master_key.as_ref().ok_or(CryptoError::Missing(
+            "Wrapped content key requires a master keystore",
+        ))?;
+        let master_key = keystore
+            .get_secret(&wrapped.master_key_id)?
+            .ok_or_else(|| CryptoError::MasterKeyNotFound(wrapped.master_key_id.clone()))?;
+        let nonce: [u8; 12] = wrapped
+            .ciphertext
+            .get(..12)
+            .and_then(|n| n.try_into().ok())
+            .ok_or(CryptoError::TooShort("Wrapped content key"))?;
+
+        let plaintext = Zeroizing::new(self.open(
+            legacy_algorithm(),
+            &wrapped.ciphertext[12..],
+            &master_key,
+            &nonce,
+        )?);
+        let content_key: [u8; 32] = plaintext.as_slice().try_into().map_err(|_| {
+            CryptoError::InvalidKey("wrapped content key has the wrong length".to_string())
+        })?;
+        Ok(Zeroizing::new(content_key))
     }
 
-    fn derive_convergent_key(
+    pub(crate) fn derive_convergent_key(
         &self,
         content: &[u8],
         secret: Option<&ConvergenceSecret>,
@@ -268,60 +931,115 @@ impl QuantumCryptoEngine {
         let hkdf = Hkdf::<Sha256>::new(Some(salt.as_bytes()), content_hash.as_bytes());
         let mut key_bytes = [0u8; 32];
         hkdf.expand(b"saorsa-fec:quantum-chacha20:v1", &mut key_bytes)
-            .map_err(|e| anyhow::anyhow!("HKDF expansion failed: {}", e))?;
+            .map_err(|e| CryptoError::KeyDerivation(e.to_string()))?;
 
         Ok(key_bytes)
     }
 
-    fn chacha20_encrypt(&self, data: &[u8], key: &[u8; 32], nonce: &[u8; 12]) -> Result<Vec<u8>> {
-        // Convert [u8; 32] to GenericArray for ChaCha20Poly1305
-        let key_array = GenericArray::from_slice(key);
-        let cipher = ChaCha20Poly1305::new(key_array);
+    /// Seal `data` as one AEAD message, or as a STREAM if it spans more
+    /// than one segment
+    ///
+    /// Returns the segment size for the metadata when streamed. The stream's
+    /// nonce prefix is taken from `nonce`, so convergent encryption stays
+    /// deterministic.
+    fn seal_payload(
+        &self,
+        algorithm: EncryptionAlgorithm,
+        data: &[u8],
+        key: &[u8; 32],
+        nonce: &[u8; 12],
+    ) -> Result<(Vec<u8>, Option<u32>)> {
+        if data.len() <= DEFAULT_SEGMENT_SIZE {
+            return Ok((self.seal(algorithm, data, key, nonce)?, None));
+        }
+        let mut prefix = [0u8; PREFIX_LEN];
+        prefix.copy_from_slice(&nonce[..PREFIX_LEN]);
+        let sealed =
+            stream::seal_stream_with_prefix(algorithm, key, DEFAULT_SEGMENT_SIZE, prefix, data)?;
+        Ok((sealed, Some(DEFAULT_SEGMENT_SIZE as u32)))
+    }
 
-        // Convert [u8; 12] to GenericArray for nonce
+    fn seal(
+        &self,
+        algorithm: EncryptionAlgorithm,
+        data: &[u8],
+        key: &[u8; 32],
+        nonce: &[u8; 12],
+    ) -> Result<Vec<u8>> {
+        // Convert [u8; 32] and [u8; 12] to GenericArray for the cipher
+        let key_array = GenericArray::from_slice(key);
         let nonce_array = GenericArray::from_slice(nonce);
 
-        let ciphertext = cipher
-            .encrypt(nonce_array, data)
-            .map_err(|e| anyhow::anyhow!("ChaCha20Poly1305 encryption failed: {:?}", e))?;
-
-        // Prepend nonce to ciphertext for storage
-        let mut result = Vec::with_capacity(12 + ciphertext.len());
-        result.extend_from_slice(nonce);
-        result.extend_from_slice(&ciphertext);
+        let ciphertext = match algorithm {
+            EncryptionAlgorithm::ChaCha20Poly1305 => ChaCha20Poly1305::new(key_array)
+                .encrypt(nonce_array, data)
+                .map_err(|_| ()),
+            EncryptionAlgorithm::Aes256Gcm => Aes256Gcm::new(key_array)
+                .encrypt(nonce_array, data)
+                .map_err(|_| ()),
+            EncryptionAlgorithm::Aes256GcmSiv => Aes256GcmSiv::new(key_array)
+                .encrypt(nonce_array, data)
+                .map_err(|_| ()),
+        };
 
-        Ok(result)
+        ciphertext.map_err(|()| CryptoError::Encryption(algorithm.name()))
     }
 
-    fn chacha20_decrypt(
+    /// Decrypt data described by `metadata`, whether streamed or one
+    /// message, accepting the legacy format that prefixes the ciphertext
+    /// with its nonce
+    fn open_data(
         &self,
+        metadata: &QuantumEncryptionMetadata,
         encrypted_data: &[u8],
         key: &[u8; 32],
-        nonce: &[u8; 12],
     ) -> Result<Vec<u8>> {
-        if encrypted_data.len() < 12 {
-            anyhow::bail!("Encrypted data too short to contain nonce");
-        }
-
-        let (data_nonce, ciphertext) = encrypted_data.split_at(12);
-
-        // Verify nonce matches
-        if data_nonce != nonce {
-            anyhow::bail!("Nonce mismatch in encrypted data");
+        if let Some(segment_size) = metadata.stream_segment_size {
+            return Ok(stream::open_stream(
+                metadata.algorithm,
+                key,
+                segment_size as usize,
+                encrypted_data,
+            )?);
         }
+        let ciphertext = if metadata.detached_nonce {
+            encrypted_data
+        } else {
+            let (data_nonce, ciphertext) = encrypted_data
+                .split_at_checked(12)
+                .ok_or(CryptoError::TooShort("Encrypted data"))?;
+            if data_nonce != metadata.nonce {
+                return Err(CryptoError::NonceMismatch);
+            }
+            ciphertext
+        };
+        self.open(metadata.algorithm, ciphertext, key, &metadata.nonce)
+    }
 
-        // Convert [u8; 32] to GenericArray for ChaCha20Poly1305
+    fn open(
+        &self,
+        algorithm: EncryptionAlgorithm,
+        ciphertext: &[u8],
+        key: &[u8; 32],
+        nonce: &[u8; 12],
+    ) -> Result<Vec<u8>> {
+        // Convert [u8; 32] and [u8; 12] to GenericArray for the cipher
         let key_array = GenericArray::from_slice(key);
-        let cipher = ChaCha20Poly1305::new(key_array);
-
-        // Convert [u8; 12] to GenericArray for nonce
         let nonce_array = GenericArray::from_slice(nonce);
 
-        let plaintext = cipher
-            .decrypt(nonce_array, ciphertext)
-            .map_err(|e| anyhow::anyhow!("ChaCha20Poly1305 decryption failed: {:?}", e))?;
+        let plaintext = match algorithm {
+            EncryptionAlgorithm::ChaCha20Poly1305 => ChaCha20Poly1305::new(key_array)
+                .decrypt(nonce_array, ciphertext)
+                .map_err(|_| ()),
+            EncryptionAlgorithm::Aes256Gcm => Aes256Gcm::new(key_array)
+                .decrypt(nonce_array, ciphertext)
+                .map_err(|_| ()),
+            EncryptionAlgorithm::Aes256GcmSiv => Aes256GcmSiv::new(key_array)
+                .decrypt(nonce_array, ciphertext)
+                .map_err(|_| ()),
+        };
 
-        Ok(plaintext)
+        plaintext.map_err(|()| CryptoError::Decryption(algorithm.name()))
     }
 
     /// Generate deterministic nonce for convergent encryption
@@ -329,9 +1047,19 @@ impl QuantumCryptoEngine {
         &self,
         content: &[u8],
         secret: Option<&[u8; 32]>,
+        position: Option<&ChunkPosition>,
     ) -> Result<[u8; 12]> {
         let mut hasher = Hasher::new();
-        hasher.update(b"nonce-derivation");
+        match position {
+            Some(position) => {
+                hasher.update(b"chunk-nonce-derivation");
+                hasher.update(&position.file_id);
+                hasher.update(&position.chunk_index.to_le_bytes());
+            }
+            None => {
+                hasher.update(b"nonce-derivation");
+            }
+        }
         hasher.update(content);
 
         if let Some(s) = secret {
@@ -343,20 +1071,84 @@ impl QuantumCryptoEngine {
         nonce.copy_from_slice(&hash.as_bytes()[..12]);
         Ok(nonce)
     }
+}
 
-    /// Compute secret identifier
-    fn compute_secret_id(&self, secret: &[u8; 32]) -> [u8; 32] {
-        let mut hasher = Hasher::new();
-        hasher.update(b"secret-id");
-        hasher.update(secret);
-        let hash = hasher.finalize();
-        *hash.as_bytes()
-    }
+/// Algorithm of metadata written before it was recorded
+fn legacy_algorithm() -> EncryptionAlgorithm {
+    EncryptionAlgorithm::ChaCha20Poly1305
+}
+
+fn parse_public_key(level: SecurityLevel, bytes: &[u8]) -> Result<MlKemPublicKey> {
+    let variant = level.kem_variant();
+    MlKemPublicKey::from_bytes(variant, bytes)
+        .map_err(|e| CryptoError::InvalidKey(format!("{:?} public key: {:?}", variant, e)))
+}
+
+/// Content key from the X25519 and ML-KEM shared secrets
+///
+/// Both secrets feed one HKDF-SHA256 extraction, bound to the encapsulation
+/// they came from, so the key stays secret unless both primitives fall.
+pub(crate) fn hybrid_shared_key(
+    x25519_secret: &[u8; 32],
+    kem_secret: &[u8],
+    encapsulated: &[u8],
+) -> Result<Zeroizing<[u8; 32]>> {
+    let mut ikm = Zeroizing::new(Vec::with_capacity(32 + kem_secret.len()));
+    ikm.extend_from_slice(x25519_secret);
+    ikm.extend_from_slice(kem_secret);
+
+    let hkdf = Hkdf::<Sha256>::new(Some(b"saorsa-fec-hybrid-kem-v1"), &ikm);
+    let mut key = Zeroizing::new([0u8; 32]);
+    hkdf.expand(encapsulated, key.as_mut())
+        .map_err(|e| CryptoError::KeyDerivation(e.to_string()))?;
+    Ok(key)
+}
+
+/// Data key exported from a new sender context for `recipient_public_key`,
+/// along with the encapsulated key the recipient needs
+fn hpke_sender_key(recipient_public_key: &[u8]) -> Result<(Vec<u8>, Zeroizing<[u8; 32]>)> {
+    let public_key = <HpkeKem as Kem>::PublicKey::from_bytes(recipient_public_key)
+        .map_err(|e| CryptoError::Hpke(format!("invalid recipient public key: {:?}", e)))?;
+    let (encapsulated, context) = hpke::setup_sender::<ExportOnlyAead, HkdfSha256, HpkeKem, _>(
+        &OpModeS::Base,
+        &public_key,
+        HPKE_INFO,
+        &mut rand::rngs::OsRng,
+    )
+    .map_err(|e| CryptoError::Hpke(format!("setup failed: {:?}", e)))?;
+
+    let mut key = Zeroizing::new([0u8; 32]);
+    context
+        .export(HPKE_STREAM_CONTEXT, key.as_mut())
+        .map_err(|e| CryptoError::Hpke(format!("export failed: {:?}", e)))?;
+    Ok((encapsulated.to_bytes().to_vec(), key))
+}
+
+/// Data key exported from the recipient context for `encapsulated`
+fn hpke_recipient_key(secret: &[u8; 32], encapsulated: &[u8]) -> Result<Zeroizing<[u8; 32]>> {
+    let secret = <HpkeKem as Kem>::PrivateKey::from_bytes(secret)
+        .map_err(|e| CryptoError::Hpke(format!("invalid recipient key: {:?}", e)))?;
+    let encapsulated = <HpkeKem as Kem>::EncappedKey::from_bytes(encapsulated)
+        .map_err(|e| CryptoError::Hpke(format!("invalid encapsulated key: {:?}", e)))?;
+    let context = hpke::setup_receiver::<ExportOnlyAead, HkdfSha256, HpkeKem>(
+        &OpModeR::Base,
+        &secret,
+        &encapsulated,
+        HPKE_INFO,
+    )
+    .map_err(|e| CryptoError::Hpke(format!("setup failed: {:?}", e)))?;
+
+    let mut key = Zeroizing::new([0u8; 32]);
+    context
+        .export(HPKE_STREAM_CONTEXT, key.as_mut())
+        .map_err(|e| CryptoError::Hpke(format!("export failed: {:?}", e)))?;
+    Ok(key)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use anyhow::Result;
 
     #[test]
     fn test_quantum_crypto_convergent() -> Result<()> {
@@ -446,6 +1238,395 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_random_key_decrypts_with_keystore() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let path = temp_dir.path().join("keys.json");
+        let keystore = KemKeyStore::create_with_iterations(&path, "passphrase", 1_000)?;
+        let mut engine = QuantumCryptoEngine::new().with_keystore(Arc::new(RwLock::new(keystore)));
+        let data = b"test data for recoverable random key encryption";
+
+        let (encrypted, metadata) = engine.encrypt(data, EncryptionMode::RandomKey, None)?;
+        assert!(metadata.kem_key_id.is_some());
+
+        // A fresh engine over the reopened keystore can still decrypt
+        let keystore = KemKeyStore::open(&path, "passphrase")?;
+        let engine = QuantumCryptoEngine::new().with_keystore(Arc::new(RwLock::new(keystore)));
+        assert_eq!(engine.decrypt(&encrypted, &metadata, None, None)?, data);
+        assert!(QuantumCryptoEngine::new()
+            .decrypt(&encrypted, &metadata, None, None)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_encrypt_for_recipient() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let mut recipient_store =
+            KemKeyStore::create_with_iterations(temp_dir.path().join("keys.json"), "pw", 1_000)?;
+        let identity = recipient_store.generate()?;
+        let public_key = recipient_store.public_key(&identity).unwrap().to_vec();
+        let data = b"stored on behalf of someone else";
+
+        // The sender has no keystore of its own
+        let mut sender = QuantumCryptoEngine::new();
+        let (encrypted, metadata) = sender.encrypt_for_recipient(data, &public_key)?;
+        assert_eq!(metadata.kem_key_id, Some(identity));
+        let mut sender = QuantumCryptoEngine::new().with_recipient(&public_key)?;
+        let (encrypted2, metadata2) = sender.encrypt(data, EncryptionMode::RandomKey, None)?;
+        assert_eq!(metadata2.kem_key_id, Some(identity));
+
+        let recipient =
+            QuantumCryptoEngine::new().with_keystore(Arc::new(RwLock::new(recipient_store)));
+        assert_eq!(recipient.decrypt(&encrypted, &metadata, None, None)?, data);
+        assert_eq!(
+            recipient.decrypt(&encrypted2, &metadata2, None, None)?,
+            data
+        );
+        assert!(QuantumCryptoEngine::new().with_recipient(b"short").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_gcm_siv_algorithm_recorded() -> Result<()> {
+        let data = b"convergent data under a misuse-resistant AEAD";
+        let mut engine =
+            QuantumCryptoEngine::new().with_algorithm(EncryptionAlgorithm::Aes256GcmSiv);
+        let (encrypted, metadata) = engine.encrypt(data, EncryptionMode::Convergent, None)?;
+        assert_eq!(metadata.algorithm, EncryptionAlgorithm::Aes256GcmSiv);
+
+        // A default engine follows the metadata rather than its own setting
+        let (chacha, _) =
+            QuantumCryptoEngine::new().encrypt(data, EncryptionMode::Convergent, None)?;
+        assert_ne!(encrypted, chacha);
+        let decrypted =
+            QuantumCryptoEngine::new().decrypt(&encrypted, &metadata, None, Some(data))?;
+        assert_eq!(decrypted, data);
+
+        // Metadata written before the algorithm was recorded means ChaCha20-Poly1305
+        let mut json = serde_json::to_value(&metadata)?;
+        json.as_object_mut().unwrap().remove("algorithm");
+        let legacy: QuantumEncryptionMetadata = serde_json::from_value(json)?;
+        assert_eq!(legacy.algorithm, EncryptionAlgorithm::ChaCha20Poly1305);
+        Ok(())
+    }
+
+    #[test]
+    fn test_random_key_auto_selects_algorithm() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let keystore =
+            KemKeyStore::create_with_iterations(temp_dir.path().join("keys.json"), "pw", 1_000)?;
+        let keystore = Arc::new(RwLock::new(keystore));
+        let data = b"random key data";
+
+        let mut engine = QuantumCryptoEngine::new().with_keystore(keystore.clone());
+        let (encrypted, metadata) = engine.encrypt(data, EncryptionMode::RandomKey, None)?;
+        assert_eq!(metadata.algorithm, EncryptionAlgorithm::detect());
+
+        // Convergent modes keep a machine-independent algorithm for dedup
+        let (_, convergent) = engine.encrypt(data, EncryptionMode::Convergent, None)?;
+        assert_eq!(convergent.algorithm, EncryptionAlgorithm::ChaCha20Poly1305);
+
+        // Decryption honours the metadata, not the decrypting engine's choice
+        let decryptor = QuantumCryptoEngine::new()
+            .with_algorithm(EncryptionAlgorithm::Aes256GcmSiv)
+            .with_keystore(keystore);
+        assert_eq!(decryptor.decrypt(&encrypted, &metadata, None, None)?, data);
+        Ok(())
+    }
+
+    #[test]
+    fn test_envelope_encryption_and_rotation() -> Result<()> {
+        use crate::keystore::MemoryKeystore;
+
+        let masters: Arc<dyn Keystore> = Arc::new(MemoryKeystore::new());
+        let data = b"recoverable without the plaintext or a KEM keystore";
+
+        let mut engine = QuantumCryptoEngine::new().with_master_key(masters.clone(), "master-1");
+        let (random, mut random_meta) = engine.encrypt(data, EncryptionMode::RandomKey, None)?;
+        let (convergent, convergent_meta) =
+            engine.encrypt(data, EncryptionMode::Convergent, None)?;
+        assert_eq!(engine.decrypt(&random, &random_meta, None, None)?, data);
+        assert_eq!(
+            engine.decrypt(&convergent, &convergent_meta, None, None)?,
+            data
+        );
+
+        // Rotate to a new master key and retire the old one
+        let rotated = QuantumCryptoEngine::new().with_master_key(masters.clone(), "master-2");
+        rotated.rewrap_key(&mut random_meta)?;
+        assert_eq!(
+            random_meta.wrapped_key.as_ref().unwrap().master_key_id,
+            "master-2"
+        );
+        masters.delete_secret("master-1")?;
+        assert_eq!(rotated.decrypt(&random, &random_meta, None, None)?, data);
+        assert!(rotated
+            .decrypt(&convergent, &convergent_meta, None, None)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_split_threshold() -> Result<()> {
+        let mut engine = QuantumCryptoEngine::new().with_key_split(3, 5)?;
+        let data = b"any three custodians together";
+        let (encrypted, mut metadata) = engine.encrypt(data, EncryptionMode::RandomKey, None)?;
+
+        let shares = metadata.key_split.as_mut().unwrap().take_shares();
+        assert_eq!(shares.len(), 5);
+        assert!(engine.decrypt(&encrypted, &metadata, None, None).is_err());
+
+        // Two custodians are not enough, any three are
+        let split = metadata.key_split.as_mut().unwrap();
+        split.shares = vec![shares[1].clone(), shares[4].clone()];
+        assert!(engine.decrypt(&encrypted, &metadata, None, None).is_err());
+        metadata
+            .key_split
+            .as_mut()
+            .unwrap()
+            .shares
+            .push(shares[2].clone());
+        assert_eq!(engine.decrypt(&encrypted, &metadata, None, None)?, data);
+
+        assert!(QuantumCryptoEngine::new().with_key_split(4, 3).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_passphrase_convergence_secret() -> Result<()> {
+        let kdf = PassphraseKdf::with_params(1024, 1, 1);
+        let secret = ConvergenceSecret::from_passphrase("correct horse", &kdf)?;
+        let data = b"shared among users of one passphrase";
+
+        let mut engine = QuantumCryptoEngine::new();
+        let (encrypted, metadata) =
+            engine.encrypt(data, EncryptionMode::ConvergentWithSecret, Some(&secret))?;
+        assert_eq!(metadata.secret_kdf.as_ref(), Some(&kdf));
+
+        // The recorded parameters re-derive the same secret
+        let recorded = metadata.secret_kdf.as_ref().unwrap();
+        let rederived = ConvergenceSecret::from_passphrase("correct horse", recorded)?;
+        assert_eq!(rederived.as_bytes(), secret.as_bytes());
+        assert_eq!(
+            engine.decrypt(&encrypted, &metadata, Some(&rederived), Some(data))?,
+            data
+        );
+
+        let wrong = ConvergenceSecret::from_passphrase("wrong horse", recorded)?;
+        assert_ne!(wrong.as_bytes(), secret.as_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hpke_round_trip() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let mut store = KemKeyStore::create_with_iterations(dir.path().join("keys"), "pw", 1_000)?;
+        let key_id = store.generate_hpke()?;
+        let other_id = store.generate_hpke()?;
+        let public_key = store.public_key(&key_id).unwrap().to_vec();
+        assert_eq!(public_key.len(), 32);
+        let store = Arc::new(RwLock::new(store));
+        let mut engine = QuantumCryptoEngine::new().with_keystore(store.clone());
+
+        // Spans several STREAM segments
+        let chunk_size = DEFAULT_HPKE_CHUNK_SIZE as usize;
+        let data: Vec<u8> = (0..chunk_size * 2 + 100).map(|i| i as u8).collect();
+        let (encrypted, metadata) = engine.encrypt_hpke(&data, &public_key)?;
+        assert_eq!(encrypted.len(), PREFIX_LEN + data.len() + 3 * 16);
+        assert_eq!(metadata.encapsulated_secret.len(), 32);
+        assert_eq!(metadata.kem_key_id, Some(key_id));
+        assert_eq!(engine.decrypt(&encrypted, &metadata, None, None)?, data);
+
+        // Any RFC 9180 receiver with the recipient key exports the same key
+        let secret = store.read().x25519_secret(&key_id)?.unwrap();
+        let hpke_error = |e: hpke::HpkeError| anyhow::anyhow!("{e:?}");
+        let context = hpke::setup_receiver::<ExportOnlyAead, HkdfSha256, X25519HkdfSha256>(
+            &OpModeR::Base,
+            &<X25519HkdfSha256 as Kem>::PrivateKey::from_bytes(secret.as_ref())
+                .map_err(hpke_error)?,
+            &<X25519HkdfSha256 as Kem>::EncappedKey::from_bytes(&metadata.encapsulated_secret)
+                .map_err(hpke_error)?,
+            b"saorsa-fec hpke v1",
+        )
+        .map_err(hpke_error)?;
+        let mut key = [0u8; 32];
+        context
+            .export(b"saorsa-fec stream", &mut key)
+            .map_err(hpke_error)?;
+        assert_eq!(
+            stream::open_stream(metadata.algorithm, &key, chunk_size, &encrypted)?,
+            data
+        );
+
+        // Dropping whole trailing chunks does not yield a shorter plaintext
+        for kept in [1, 2] {
+            let truncated = &encrypted[..PREFIX_LEN + kept * (chunk_size + 16)];
+            assert!(engine.decrypt(truncated, &metadata, None, None).is_err());
+        }
+        let mut wrong_recipient = metadata.clone();
+        wrong_recipient.kem_key_id = Some(other_id);
+        assert!(engine
+            .decrypt(&encrypted, &wrong_recipient, None, None)
+            .is_err());
+        assert!(QuantumCryptoEngine::new()
+            .decrypt(&encrypted, &metadata, None, None)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hybrid_kem_round_trip() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let store = KemKeyStore::create_with_iterations(dir.path().join("keys"), "pw", 1_000)?;
+        let store = Arc::new(RwLock::new(store));
+        let mut engine = QuantumCryptoEngine::new()
+            .with_keystore(store.clone())
+            .with_hybrid_kem();
+
+        let data = b"secure while either KEM holds";
+        let (encrypted, metadata) = engine.encrypt(data, EncryptionMode::RandomKey, None)?;
+        assert!(matches!(
+            metadata.key_derivation,
+            QuantumKeyDerivation::HybridX25519
+        ));
+        assert_eq!(metadata.encapsulated_secret.len(), 1088 + 32);
+        assert_eq!(engine.decrypt(&encrypted, &metadata, None, None)?, data);
+
+        // Both halves of the encapsulation feed the key
+        for idx in [0, metadata.encapsulated_secret.len() - 1] {
+            let mut tampered = metadata.clone();
+            tampered.encapsulated_secret[idx] ^= 1;
+            assert!(engine.decrypt(&encrypted, &tampered, None, None).is_err());
+        }
+
+        // Keys from the store round-trip through a reopen
+        let key_id = metadata.kem_key_id.unwrap();
+        let reopened = KemKeyStore::open(dir.path().join("keys"), "pw")?;
+        assert_eq!(reopened.public_key(&key_id).unwrap().len(), 1184 + 32);
+        Ok(())
+    }
+
+    #[test]
+    fn test_security_level_selects_kem() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let store = KemKeyStore::create_with_iterations(dir.path().join("keys"), "pw", 1_000)?;
+        let store = Arc::new(RwLock::new(store));
+        let data = b"sized to the level";
+
+        for (level, encapsulated_len) in [
+            (SecurityLevel::Level1, 768),
+            (SecurityLevel::Level3, 1088),
+            (SecurityLevel::Level5, 1568),
+        ] {
+            let mut engine =
+                QuantumCryptoEngine::with_security_level(level).with_keystore(store.clone());
+            let (encrypted, metadata) = engine.encrypt(data, EncryptionMode::RandomKey, None)?;
+            assert_eq!(metadata.security_level, level);
+            assert_eq!(metadata.encapsulated_secret.len(), encapsulated_len);
+
+            // Decryption follows the metadata, not the engine's level
+            let reader = QuantumCryptoEngine::new().with_keystore(store.clone());
+            assert_eq!(reader.decrypt(&encrypted, &metadata, None, None)?, data);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunk_nonces_bound_to_position() -> Result<()> {
+        let mut engine = QuantumCryptoEngine::new();
+        let data = b"the same plaintext in two chunks";
+        let at = |file_id: u8, chunk_index| ChunkPosition {
+            file_id: [file_id; 32],
+            chunk_index,
+        };
+
+        let (first, first_meta) =
+            engine.encrypt_chunk(data, EncryptionMode::Convergent, None, &at(1, 0))?;
+        let (second, second_meta) =
+            engine.encrypt_chunk(data, EncryptionMode::Convergent, None, &at(1, 1))?;
+        let (other, other_meta) =
+            engine.encrypt_chunk(data, EncryptionMode::Convergent, None, &at(2, 0))?;
+        assert_ne!(first_meta.nonce, second_meta.nonce);
+        assert_ne!(first_meta.nonce, other_meta.nonce);
+        assert_ne!(first, second);
+
+        // Re-encrypting the same chunk stays deterministic
+        let (again, _) = engine.encrypt_chunk(data, EncryptionMode::Convergent, None, &at(1, 0))?;
+        assert_eq!(first, again);
+
+        for (encrypted, metadata) in [(first, first_meta), (other, other_meta)] {
+            assert_eq!(
+                engine.decrypt(&encrypted, &metadata, None, Some(data))?,
+                data
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_nonce_stored_once_with_legacy_read() -> Result<()> {
+        let mut engine = QuantumCryptoEngine::new();
+        let data = b"nonce only in the metadata";
+        let (encrypted, mut metadata) = engine.encrypt(data, EncryptionMode::Convergent, None)?;
+        assert!(metadata.detached_nonce);
+        assert_eq!(encrypted.len(), data.len() + 16);
+
+        // Ciphertexts from older versions repeat the nonce up front
+        let mut legacy = metadata.nonce.to_vec();
+        legacy.extend_from_slice(&encrypted);
+        metadata.detached_nonce = false;
+        assert_eq!(engine.decrypt(&legacy, &metadata, None, Some(data))?, data);
+
+        legacy[0] ^= 1;
+        assert!(engine
+            .decrypt(&legacy, &metadata, None, Some(data))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_large_payloads_streamed() -> Result<()> {
+        let data: Vec<u8> = (0..DEFAULT_SEGMENT_SIZE * 2 + 100)
+            .map(|i| i as u8)
+            .collect();
+        let mut engine = QuantumCryptoEngine::new();
+        let (encrypted, metadata) = engine.encrypt(&data, EncryptionMode::Convergent, None)?;
+        assert_eq!(
+            metadata.stream_segment_size,
+            Some(DEFAULT_SEGMENT_SIZE as u32)
+        );
+        assert_eq!(encrypted.len(), PREFIX_LEN + data.len() + 3 * 16);
+        assert_eq!(&encrypted[..PREFIX_LEN], &metadata.nonce[..PREFIX_LEN]);
+        assert_eq!(
+            engine.decrypt(&encrypted, &metadata, None, Some(&data))?,
+            data
+        );
+
+        // Convergent streams stay deterministic
+        let (again, _) =
+            QuantumCryptoEngine::new().encrypt(&data, EncryptionMode::Convergent, None)?;
+        assert_eq!(again, encrypted);
+
+        // Dropping the final segment is detected
+        let truncated = &encrypted[..PREFIX_LEN + 2 * (DEFAULT_SEGMENT_SIZE + 16)];
+        assert!(engine
+            .decrypt(truncated, &metadata, None, Some(&data))
+            .is_err());
+
+        let dir = tempfile::TempDir::new()?;
+        let store = KemKeyStore::create_with_iterations(dir.path().join("keys"), "pw", 1_000)?;
+        let mut engine = QuantumCryptoEngine::new().with_keystore(Arc::new(RwLock::new(store)));
+        let (encrypted, metadata) = engine.encrypt(&data, EncryptionMode::RandomKey, None)?;
+        assert!(metadata.stream_segment_size.is_some());
+        assert_eq!(engine.decrypt(&encrypted, &metadata, None, None)?, data);
+
+        // Small payloads remain a single AEAD message
+        let (_, small) = engine.encrypt(b"small", EncryptionMode::RandomKey, None)?;
+        assert_eq!(small.stream_segment_size, None);
+        Ok(())
+    }
+
     #[test]
     fn test_security_levels() {
         let engine1 = QuantumCryptoEngine::with_security_level(SecurityLevel::Level1);
@@ -0,0 +1,204 @@
+//! Lazy re-encryption after convergence secret rotation
+//!
+//! Rotating a convergence secret in a [`SecretRegistry`](crate::SecretRegistry)
+//! only affects new files; existing files stay readable under the retired
+//! secret. The [`Reencryptor`] walks the files still encrypted under a
+//! retired secret and rewrites them a batch at a time, holding the pipeline
+//! lock for one file at a time, so the migration finishes eventually without
+//! stalling normal reads and writes.
+
+use parking_lot::RwLock;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::pipeline::StoragePipeline;
+use crate::storage::StorageBackend;
+
+/// Configuration for the background re-encryption task
+#[derive(Debug, Clone)]
+pub struct ReencryptionConfig {
+    /// Time to wait between batches
+    pub interval: Duration,
+    /// Maximum number of files rewritten per batch
+    pub batch_size: usize,
+}
+
+impl Default for ReencryptionConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60),
+            batch_size: 16,
+        }
+    }
+}
+
+/// Progress of the re-encryption task
+#[derive(Debug, Clone, Default)]
+pub struct ReencryptionProgress {
+    /// Files still under a retired secret at the start of the last batch
+    pub pending: usize,
+    /// Number of files rewritten under the active secret
+    pub reencrypted: u64,
+    /// Number of rewrites that failed with an error
+    pub failures: u64,
+    /// Number of completed batches
+    pub batches: u64,
+}
+
+impl ReencryptionProgress {
+    /// Whether the last batch found every file under the active secret
+    pub fn is_complete(&self) -> bool {
+        self.batches > 0 && self.pending == 0
+    }
+}
+
+/// Opt-in background task that re-encrypts files after secret rotation
+pub struct Reencryptor;
+
+impl Reencryptor {
+    /// Spawn the task on the current tokio runtime
+    ///
+    /// The first batch starts immediately; further batches run every
+    /// `config.interval` until the returned handle is stopped or dropped.
+    /// The task keeps running after completion to pick up later rotations.
+    ///
+    /// Not available on wasm32, which has no Tokio runtime; run
+    /// [`Self::run_batch`] from the host's own scheduler instead.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn<B: StorageBackend + 'static>(
+        pipeline: Arc<tokio::sync::RwLock<StoragePipeline<B>>>,
+        config: ReencryptionConfig,
+    ) -> ReencryptionHandle {
+        let progress = Arc::new(RwLock::new(ReencryptionProgress::default()));
+        let (shutdown, mut shutdown_rx) = watch::channel(false);
+
+        let task = {
+            let progress = progress.clone();
+            tokio::spawn(async move {
+                loop {
+                    Self::run_batch(&pipeline, config.batch_size, &progress).await;
+
+                    tokio::select! {
+                        _ = tokio::time::sleep(config.interval) => {}
+                        _ = shutdown_rx.changed() => break,
+                    }
+                }
+            })
+        };
+
+        ReencryptionHandle {
+            progress,
+            shutdown,
+            task,
+        }
+    }
+
+    /// Rewrite up to `batch_size` files still under a retired secret
+    pub async fn run_batch<B: StorageBackend + 'static>(
+        pipeline: &tokio::sync::RwLock<StoragePipeline<B>>,
+        batch_size: usize,
+        progress: &RwLock<ReencryptionProgress>,
+    ) {
+        let pending = pipeline.read().await.files_pending_reencryption();
+        progress.write().pending = pending.len();
+
+        for meta in pending.into_iter().take(batch_size) {
+            let result = pipeline.write().await.reencrypt_file(&meta).await;
+            let mut progress = progress.write();
+            match result {
+                Ok(_) => {
+                    progress.reencrypted += 1;
+                    progress.pending -= 1;
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "Re-encryption of file {} failed: {}",
+                        hex::encode(meta.file_id),
+                        e
+                    );
+                    progress.failures += 1;
+                }
+            }
+        }
+        progress.write().batches += 1;
+    }
+}
+
+/// Handle to a running re-encryption task
+pub struct ReencryptionHandle {
+    progress: Arc<RwLock<ReencryptionProgress>>,
+    shutdown: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl ReencryptionHandle {
+    /// Snapshot of the re-encryption progress
+    pub fn progress(&self) -> ReencryptionProgress {
+        self.progress.read().clone()
+    }
+
+    /// Stop the task, waiting for any in-progress batch to finish
+    pub async fn stop(self) {
+        let _ = self.shutdown.send(true);
+        let _ = self.task.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, EncryptionMode};
+    use crate::keystore::{Keystore, MemoryKeystore};
+    use crate::secret_registry::SecretRegistry;
+    use crate::storage::MemoryStorage;
+
+    #[tokio::test]
+    async fn test_rotation_reencrypts_in_batches() -> anyhow::Result<()> {
+        let keystore: Arc<dyn Keystore> = Arc::new(MemoryKeystore::new());
+        let registry = Arc::new(SecretRegistry::in_memory(keystore));
+        let config = Config::default()
+            .with_fec_params(4, 2)
+            .with_encryption_mode(EncryptionMode::ConvergentWithSecret);
+        let mut pipeline = StoragePipeline::builder(config, MemoryStorage::new())
+            .secret_registry(registry.clone())
+            .build()?;
+
+        let files: Vec<Vec<u8>> = (0..3u8).map(|i| vec![i; 2000]).collect();
+        for (i, data) in files.iter().enumerate() {
+            pipeline.process_file([i as u8; 32], data, None).await?;
+        }
+        assert!(pipeline.files_pending_reencryption().is_empty());
+
+        let active = registry.rotate("rotated")?;
+        assert_eq!(pipeline.files_pending_reencryption().len(), 3);
+
+        let pipeline = Arc::new(tokio::sync::RwLock::new(pipeline));
+        let progress = RwLock::new(ReencryptionProgress::default());
+        Reencryptor::run_batch(&pipeline, 2, &progress).await;
+        assert_eq!(progress.read().pending, 1);
+        assert!(!progress.read().is_complete());
+
+        let handle = Reencryptor::spawn(
+            pipeline.clone(),
+            ReencryptionConfig {
+                interval: Duration::from_millis(10),
+                batch_size: 2,
+            },
+        );
+        while !handle.progress().is_complete() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        handle.stop().await;
+
+        let pipeline = pipeline.read().await;
+        for meta in pipeline.list_files() {
+            let quantum = meta.quantum_encryption_metadata.as_ref().unwrap();
+            assert_eq!(quantum.convergence_secret_id, Some(active));
+            let data = pipeline.retrieve_file(&meta).await?;
+            assert_eq!(data, files[meta.file_id[0] as usize]);
+        }
+        Ok(())
+    }
+}
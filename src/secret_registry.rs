@@ -0,0 +1,211 @@
+//! Convergence secret lifecycle
+//!
+//! ConvergentWithSecret mode deduplicates only among holders of the same
+//! secret, and every file records the ID of the secret it was encrypted
+//! with in `convergence_secret_id`. [`SecretRegistry`] generates, labels and
+//! rotates those secrets, keeping the secrets themselves in a [`Keystore`]
+//! and their IDs and labels in a local registry file. Retired secrets stay
+//! resolvable, so files encrypted before a rotation can still be decrypted.
+
+use parking_lot::RwLock;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::keystore::{io_error, Keystore, KeystoreError, Result, CONVERGENCE_SECRET_ID};
+use crate::quantum_crypto::ConvergenceSecret;
+
+/// Registry entry describing one convergence secret
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecretInfo {
+    /// Secret ID, as recorded in encryption metadata
+    pub id: [u8; 32],
+    /// Human-readable label
+    pub label: String,
+    /// Unix timestamp of generation
+    pub created_at: u64,
+    /// Unix timestamp the secret was rotated out, if it was
+    pub retired_at: Option<u64>,
+}
+
+/// Persisted registry content
+#[derive(Default, Serialize, Deserialize)]
+struct RegistryFile {
+    active: Option<[u8; 32]>,
+    secrets: Vec<SecretInfo>,
+}
+
+/// Registry of convergence secrets backed by a keystore
+pub struct SecretRegistry {
+    keystore: Arc<dyn Keystore>,
+    path: Option<PathBuf>,
+    content: RwLock<RegistryFile>,
+}
+
+impl SecretRegistry {
+    /// Registry that is not persisted, for tests and short-lived pipelines
+    pub fn in_memory(keystore: Arc<dyn Keystore>) -> Self {
+        Self {
+            keystore,
+            path: None,
+            content: RwLock::new(RegistryFile::default()),
+        }
+    }
+
+    /// Open the registry file at `path`, starting empty if it does not exist
+    pub fn open(keystore: Arc<dyn Keystore>, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let content = if path.exists() {
+            let bytes = std::fs::read(&path).map_err(io_error(&path))?;
+            serde_json::from_slice(&bytes)
+                .map_err(|_| KeystoreError::Malformed("Secret registry"))?
+        } else {
+            RegistryFile::default()
+        };
+        Ok(Self {
+            keystore,
+            path: Some(path),
+            content: RwLock::new(content),
+        })
+    }
+
+    /// Generate and store a new secret, returning its ID
+    ///
+    /// The first secret generated becomes the active one.
+    pub fn generate(&self, label: impl Into<String>) -> Result<[u8; 32]> {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let secret = ConvergenceSecret::new(bytes);
+        bytes.fill(0);
+
+        let id = secret.id();
+        self.keystore
+            .put_secret(&keystore_name(&id), secret.as_bytes())?;
+
+        let mut content = self.content.write();
+        content.secrets.push(SecretInfo {
+            id,
+            label: label.into(),
+            created_at: now(),
+            retired_at: None,
+        });
+        content.active.get_or_insert(id);
+        self.save(&content)?;
+        Ok(id)
+    }
+
+    /// Generate a new active secret and retire the current one
+    ///
+    /// New files are encrypted under the new secret, so they no longer
+    /// deduplicate against files encrypted before the rotation.
+    pub fn rotate(&self, label: impl Into<String>) -> Result<[u8; 32]> {
+        let id = self.generate(label)?;
+        let mut content = self.content.write();
+        let retired = content.active.replace(id);
+        if let Some(info) = content
+            .secrets
+            .iter_mut()
+            .find(|info| Some(info.id) == retired && info.id != id)
+        {
+            info.retired_at = Some(now());
+        }
+        self.save(&content)?;
+        Ok(id)
+    }
+
+    /// Change the label of a secret
+    pub fn set_label(&self, id: &[u8; 32], label: impl Into<String>) -> Result<()> {
+        let mut content = self.content.write();
+        let info = content
+            .secrets
+            .iter_mut()
+            .find(|info| info.id == *id)
+            .ok_or_else(|| KeystoreError::UnknownSecret(hex::encode(id)))?;
+        info.label = label.into();
+        self.save(&content)
+    }
+
+    /// All registered secrets, oldest first
+    pub fn list(&self) -> Vec<SecretInfo> {
+        self.content.read().secrets.clone()
+    }
+
+    /// ID of the secret new files are encrypted with
+    pub fn active_id(&self) -> Option<[u8; 32]> {
+        self.content.read().active
+    }
+
+    /// Secret new files are encrypted with, generating one on first use
+    pub fn active(&self) -> Result<ConvergenceSecret> {
+        let id = match self.active_id() {
+            Some(id) => id,
+            None => self.generate("default")?,
+        };
+        self.resolve(&id)
+    }
+
+    /// Look up the secret a file's metadata refers to
+    pub fn resolve(&self, id: &[u8; 32]) -> Result<ConvergenceSecret> {
+        let secret = self
+            .keystore
+            .get_secret(&keystore_name(id))?
+            .ok_or_else(|| KeystoreError::UnknownSecret(hex::encode(id)))?;
+        Ok(ConvergenceSecret::new(*secret))
+    }
+
+    fn save(&self, content: &RegistryFile) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let temp = path.with_extension("tmp");
+        let bytes = serde_json::to_vec_pretty(content)
+            .map_err(|_| KeystoreError::Malformed("Secret registry"))?;
+        std::fs::write(&temp, bytes).map_err(io_error(&temp))?;
+        std::fs::rename(&temp, path).map_err(io_error(path))
+    }
+}
+
+fn keystore_name(id: &[u8; 32]) -> String {
+    format!("{}/{}", CONVERGENCE_SECRET_ID, hex::encode(id))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keystore::MemoryKeystore;
+    use anyhow::Result;
+
+    #[test]
+    fn test_rotation_keeps_old_secrets_resolvable() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let keystore: Arc<dyn Keystore> = Arc::new(MemoryKeystore::new());
+        let path = dir.path().join("secrets.json");
+
+        let registry = SecretRegistry::open(keystore.clone(), &path)?;
+        let first = registry.active()?.id();
+        let second = registry.rotate("2026")?;
+        registry.set_label(&first, "legacy")?;
+        assert_ne!(first, second);
+
+        // Reopening restores IDs, labels and the active secret
+        let registry = SecretRegistry::open(keystore, &path)?;
+        assert_eq!(registry.active_id(), Some(second));
+        let list = registry.list();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].label, "legacy");
+        assert!(list[0].retired_at.is_some());
+        assert!(list[1].retired_at.is_none());
+        assert_eq!(registry.resolve(&first)?.id(), first);
+        assert!(registry.resolve(&[0u8; 32]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+//! Heap storage for key material
+//!
+//! [`SecretBytes`] keeps a fixed-size secret in its own heap allocation and
+//! zeroizes it on drop. With the `mlock` feature the pages holding it are
+//! also locked into RAM (`mlock` on Unix, `VirtualLock` on Windows) so the
+//! secret is never written to swap.
+//!
+//! Locking is best effort: if the OS refuses, e.g. because `RLIMIT_MEMLOCK`
+//! is exhausted, a warning is logged and the secret stays usable. Check
+//! [`SecretBytes::is_locked`] where locked memory is mandatory.
+
+use std::fmt;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Fixed-size secret in zeroized, optionally locked, heap memory
+pub struct SecretBytes<const N: usize> {
+    bytes: Box<[u8; N]>,
+    locked: bool,
+}
+
+impl<const N: usize> SecretBytes<N> {
+    /// Move `bytes` into secure storage, zeroizing the original
+    pub fn new(mut bytes: [u8; N]) -> Self {
+        let mut boxed = Box::new([0u8; N]);
+        boxed.copy_from_slice(&bytes);
+        bytes.zeroize();
+
+        let locked = page_lock::lock(boxed.as_ptr(), N);
+        Self {
+            bytes: boxed,
+            locked,
+        }
+    }
+
+    /// The secret bytes
+    pub fn as_bytes(&self) -> &[u8; N] {
+        &self.bytes
+    }
+
+    /// Whether the secret's pages are locked into RAM
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+}
+
+impl<const N: usize> Clone for SecretBytes<N> {
+    fn clone(&self) -> Self {
+        Self::new(*self.bytes)
+    }
+}
+
+impl<const N: usize> fmt::Debug for SecretBytes<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretBytes([REDACTED])")
+    }
+}
+
+impl<const N: usize> Zeroize for SecretBytes<N> {
+    fn zeroize(&mut self) {
+        self.bytes.zeroize();
+    }
+}
+
+impl<const N: usize> Drop for SecretBytes<N> {
+    fn drop(&mut self) {
+        self.bytes.zeroize();
+        if self.locked {
+            page_lock::unlock(self.bytes.as_ptr(), N);
+        }
+    }
+}
+
+impl<const N: usize> ZeroizeOnDrop for SecretBytes<N> {}
+
+#[cfg(feature = "mlock")]
+mod page_lock {
+    //! Reference-counted page locks
+    //!
+    //! The OS tracks locks per page, not per allocation, so unlocking one
+    //! secret would unlock any other secret sharing its page. Counting the
+    //! secrets on each page unlocks it only when the last one goes.
+
+    use parking_lot::Mutex;
+    use std::collections::HashMap;
+    use std::sync::OnceLock;
+
+    fn counts() -> &'static Mutex<HashMap<usize, usize>> {
+        static COUNTS: OnceLock<Mutex<HashMap<usize, usize>>> = OnceLock::new();
+        COUNTS.get_or_init(Default::default)
+    }
+
+    fn pages(ptr: *const u8, len: usize) -> impl Iterator<Item = usize> {
+        let page_size = region::page::size();
+        let start = ptr as usize / page_size * page_size;
+        let end = ptr as usize + len.max(1);
+        (start..end).step_by(page_size)
+    }
+
+    pub(super) fn lock(ptr: *const u8, len: usize) -> bool {
+        let mut counts = counts().lock();
+        let pages: Vec<usize> = pages(ptr, len).collect();
+        for (i, &page) in pages.iter().enumerate() {
+            if !counts.contains_key(&page) {
+                // The count, not the guard, decides when to unlock
+                if let Err(e) = region::lock(page as *const u8, 1).map(std::mem::forget) {
+                    tracing::warn!("Failed to lock key material into memory: {}", e);
+                    for &page in &pages[..i] {
+                        release(&mut counts, page);
+                    }
+                    return false;
+                }
+            }
+            *counts.entry(page).or_insert(0) += 1;
+        }
+        true
+    }
+
+    pub(super) fn unlock(ptr: *const u8, len: usize) {
+        let mut counts = counts().lock();
+        for page in pages(ptr, len) {
+            release(&mut counts, page);
+        }
+    }
+
+    fn release(counts: &mut HashMap<usize, usize>, page: usize) {
+        if let Some(count) = counts.get_mut(&page) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&page);
+                let _ = region::unlock(page as *const u8, 1);
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "mlock"))]
+mod page_lock {
+    pub(super) fn lock(_ptr: *const u8, _len: usize) -> bool {
+        false
+    }
+
+    pub(super) fn unlock(_ptr: *const u8, _len: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_bytes_round_trip() {
+        let secret = SecretBytes::new([7u8; 32]);
+        assert_eq!(secret.as_bytes(), &[7u8; 32]);
+        assert_eq!(secret.clone().as_bytes(), &[7u8; 32]);
+        assert_eq!(format!("{:?}", secret), "SecretBytes([REDACTED])");
+        if !cfg!(feature = "mlock") {
+            assert!(!secret.is_locked());
+        }
+    }
+}
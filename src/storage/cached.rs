@@ -0,0 +1,453 @@
+//! Read-through LRU cache in front of a storage backend
+//!
+//! [`CachedStorage`] keeps recently read shards in memory, bounded by a byte
+//! budget (normally [`StorageConfig::cache_size`](crate::config::StorageConfig)).
+//! Reads are served from the cache when possible, unless the cached shard
+//! has expired; writes and deletes go straight to the wrapped backend and
+//! keep the cache coherent.
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::AsyncRead;
+
+use super::{
+    Cid, FileMetadata, GcReport, Shard, ShardHeader, ShardPage, ShardReader, StorageBackend,
+    StorageStats,
+};
+use crate::config::StorageConfig;
+use crate::FecError;
+
+/// Cache hit/miss statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Reads served from the cache
+    pub hits: u64,
+    /// Reads that went to the backend
+    pub misses: u64,
+    /// Entries dropped to stay within the byte budget
+    pub evictions: u64,
+    /// Shards currently cached
+    pub entries: u64,
+    /// Bytes currently cached (header + data)
+    pub bytes: u64,
+    /// Byte budget of the cache
+    pub capacity: u64,
+}
+
+impl CacheStats {
+    /// Fraction of reads served from the cache
+    pub fn hit_ratio(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+struct CacheEntry {
+    shard: Shard,
+    size: u64,
+    tick: u64,
+}
+
+/// Byte-bounded LRU keyed by CID
+///
+/// Recency is tracked with a monotonically increasing tick; the `order` map
+/// holds the oldest tick first so eviction is a `pop_first`.
+#[derive(Default)]
+struct Lru {
+    entries: HashMap<Cid, CacheEntry>,
+    order: BTreeMap<u64, Cid>,
+    bytes: u64,
+    next_tick: u64,
+}
+
+impl Lru {
+    fn tick(&mut self) -> u64 {
+        self.next_tick += 1;
+        self.next_tick
+    }
+
+    /// Cached shard under `cid`; an expired shard is dropped and missed
+    fn get(&mut self, cid: &Cid) -> Option<Shard> {
+        if self.entries.get(cid)?.shard.header.is_expired() {
+            self.remove(cid);
+            return None;
+        }
+        let tick = self.tick();
+        let entry = self.entries.get_mut(cid)?;
+        self.order.remove(&entry.tick);
+        entry.tick = tick;
+        self.order.insert(tick, *cid);
+        Some(entry.shard.clone())
+    }
+
+    fn remove(&mut self, cid: &Cid) {
+        if let Some(entry) = self.entries.remove(cid) {
+            self.order.remove(&entry.tick);
+            self.bytes -= entry.size;
+        }
+    }
+
+    /// Insert a shard, returning how many entries were evicted
+    fn insert(&mut self, cid: Cid, shard: Shard, capacity: u64) -> u64 {
+        self.remove(&cid);
+        let size = shard.data.len() as u64 + ShardHeader::SIZE as u64;
+        if size > capacity {
+            return 0;
+        }
+
+        let mut evicted = 0;
+        while self.bytes + size > capacity {
+            match self.order.pop_first() {
+                Some((_, oldest)) => {
+                    if let Some(entry) = self.entries.remove(&oldest) {
+                        self.bytes -= entry.size;
+                    }
+                    evicted += 1;
+                }
+                None => break,
+            }
+        }
+
+        let tick = self.tick();
+        self.order.insert(tick, cid);
+        self.entries.insert(cid, CacheEntry { shard, size, tick });
+        self.bytes += size;
+        evicted
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.bytes = 0;
+    }
+}
+
+/// Storage backend wrapper caching hot shards in memory
+pub struct CachedStorage<B> {
+    inner: B,
+    capacity: u64,
+    lru: Mutex<Lru>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl<B: StorageBackend> CachedStorage<B> {
+    /// Wrap a backend with a cache holding at most `capacity` bytes of shards
+    pub fn new(inner: B, capacity: u64) -> Self {
+        Self {
+            inner,
+            capacity,
+            lru: Mutex::new(Lru::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// Wrap a backend using the cache size from the storage configuration
+    pub fn from_config(inner: B, config: &StorageConfig) -> Self {
+        Self::new(inner, config.cache_size as u64)
+    }
+
+    /// The wrapped backend
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    /// Current cache statistics
+    pub fn cache_stats(&self) -> CacheStats {
+        let lru = self.lru.lock();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            entries: lru.entries.len() as u64,
+            bytes: lru.bytes,
+            capacity: self.capacity,
+        }
+    }
+
+    /// Drop every cached shard; statistics are kept
+    pub fn clear_cache(&self) {
+        self.lru.lock().clear();
+    }
+
+    fn cache(&self, cid: Cid, shard: Shard) {
+        let evicted = self.lru.lock().insert(cid, shard, self.capacity);
+        if evicted > 0 {
+            self.evictions.fetch_add(evicted, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl<B: StorageBackend> StorageBackend for CachedStorage<B> {
+    async fn put_shard(&self, cid: &Cid, shard: &Shard) -> Result<(), FecError> {
+        // Invalidate first so a failed write never leaves a stale entry behind
+        self.lru.lock().remove(cid);
+        self.inner.put_shard(cid, shard).await
+    }
+
+    async fn get_shard(&self, cid: &Cid) -> Result<Shard, FecError> {
+        if let Some(shard) = self.lru.lock().get(cid) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(shard);
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let shard = self.inner.get_shard(cid).await?;
+        self.cache(*cid, shard.clone());
+        Ok(shard)
+    }
+
+    async fn get_shard_verified(&self, cid: &Cid) -> Result<Shard, FecError> {
+        if let Some(shard) = self.lru.lock().get(cid) {
+            if shard.verify(cid).is_ok() {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(shard);
+            }
+        }
+
+        // Let the wrapped backend fall back to, and heal from, a good copy
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let shard = self.inner.get_shard_verified(cid).await?;
+        self.cache(*cid, shard.clone());
+        Ok(shard)
+    }
+
+    async fn delete_shard(&self, cid: &Cid) -> Result<(), FecError> {
+        self.lru.lock().remove(cid);
+        self.inner.delete_shard(cid).await
+    }
+
+    async fn has_shard(&self, cid: &Cid) -> Result<bool, FecError> {
+        if self.lru.lock().entries.contains_key(cid) {
+            return Ok(true);
+        }
+        self.inner.has_shard(cid).await
+    }
+
+    async fn list_shards(&self) -> Result<Vec<Cid>, FecError> {
+        self.inner.list_shards().await
+    }
+
+    async fn list_shards_paged(
+        &self,
+        after: Option<&Cid>,
+        limit: usize,
+    ) -> Result<ShardPage, FecError> {
+        self.inner.list_shards_paged(after, limit).await
+    }
+
+    async fn put_metadata(&self, metadata: &FileMetadata) -> Result<(), FecError> {
+        self.inner.put_metadata(metadata).await
+    }
+
+    async fn get_metadata(&self, file_id: &[u8; 32]) -> Result<FileMetadata, FecError> {
+        self.inner.get_metadata(file_id).await
+    }
+
+    async fn delete_metadata(&self, file_id: &[u8; 32]) -> Result<(), FecError> {
+        self.inner.delete_metadata(file_id).await
+    }
+
+    async fn list_metadata(&self) -> Result<Vec<FileMetadata>, FecError> {
+        self.inner.list_metadata().await
+    }
+
+    async fn stats(&self) -> Result<StorageStats, FecError> {
+        self.inner.stats().await
+    }
+
+    async fn garbage_collect(&self) -> Result<GcReport, FecError> {
+        // GC may remove any shard, so the cache cannot be trusted afterwards
+        let report = self.inner.garbage_collect().await;
+        self.clear_cache();
+        report
+    }
+
+    async fn put_shards(&self, shards: &[(Cid, Shard)]) -> Result<(), FecError> {
+        {
+            let mut lru = self.lru.lock();
+            for (cid, _) in shards {
+                lru.remove(cid);
+            }
+        }
+        self.inner.put_shards(shards).await
+    }
+
+    async fn commit_transaction(
+        &self,
+        shards: &[(Cid, Shard)],
+        metadata: &[FileMetadata],
+    ) -> Result<(), FecError> {
+        {
+            let mut lru = self.lru.lock();
+            for (cid, _) in shards {
+                lru.remove(cid);
+            }
+        }
+        self.inner.commit_transaction(shards, metadata).await
+    }
+
+    async fn put_shard_stream(
+        &self,
+        cid: &Cid,
+        header: &ShardHeader,
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        len: u64,
+    ) -> Result<(), FecError> {
+        self.lru.lock().remove(cid);
+        self.inner.put_shard_stream(cid, header, reader, len).await
+    }
+
+    async fn get_shard_stream(&self, cid: &Cid) -> Result<(ShardHeader, ShardReader), FecError> {
+        if let Some(shard) = self.lru.lock().get(cid) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok((shard.header, Box::new(std::io::Cursor::new(shard.data))));
+        }
+
+        // Streamed shards are typically too large to be worth caching
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        self.inner.get_shard_stream(cid).await
+    }
+
+    async fn get_shards(&self, cids: &[Cid]) -> Result<Vec<Option<Shard>>, FecError> {
+        let mut shards: Vec<Option<Shard>> = {
+            let mut lru = self.lru.lock();
+            cids.iter().map(|cid| lru.get(cid)).collect()
+        };
+
+        // Fetch every miss from the backend in one batch
+        let missing: Vec<Cid> = cids
+            .iter()
+            .zip(&shards)
+            .filter(|(_, shard)| shard.is_none())
+            .map(|(cid, _)| *cid)
+            .collect();
+        let hits = (cids.len() - missing.len()) as u64;
+        self.hits.fetch_add(hits, Ordering::Relaxed);
+        if missing.is_empty() {
+            return Ok(shards);
+        }
+        self.misses
+            .fetch_add(missing.len() as u64, Ordering::Relaxed);
+
+        let mut fetched = self.inner.get_shards(&missing).await?.into_iter();
+        for (cid, slot) in cids.iter().zip(shards.iter_mut()) {
+            if slot.is_none() {
+                *slot = fetched.next().flatten();
+                if let Some(shard) = slot {
+                    self.cache(*cid, shard.clone());
+                }
+            }
+        }
+        Ok(shards)
+    }
+
+    async fn has_shards(&self, cids: &[Cid]) -> Result<Vec<bool>, FecError> {
+        self.inner.has_shards(cids).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use crate::EncryptionMode;
+
+    fn shard(byte: u8, len: usize) -> (Cid, Shard) {
+        let header = ShardHeader::new(EncryptionMode::Convergent, (4, 2), len as u32, [0u8; 32]);
+        let shard = Shard::new(header, vec![byte; len]);
+        (shard.cid().unwrap(), shard)
+    }
+
+    #[tokio::test]
+    async fn test_cache_hits_and_misses() {
+        let backend = MemoryStorage::new();
+        let storage = CachedStorage::new(backend.clone(), 1024 * 1024);
+        let (cid, shard) = shard(1, 64);
+        storage.put_shard(&cid, &shard).await.unwrap();
+
+        assert_eq!(storage.get_shard(&cid).await.unwrap().data, shard.data);
+        assert_eq!(storage.get_shard(&cid).await.unwrap().data, shard.data);
+
+        let stats = storage.cache_stats();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+        assert_eq!(stats.entries, 1);
+        assert_eq!(stats.hit_ratio(), 0.5);
+
+        // Listing and batch existence checks come from the wrapped backend
+        let page = storage.list_shards_paged(None, 10).await.unwrap();
+        assert_eq!(page.cids, vec![cid]);
+        assert_eq!(storage.has_shards(&[cid]).await.unwrap(), vec![true]);
+
+        // Deleting through the wrapper must not leave a stale entry
+        storage.delete_shard(&cid).await.unwrap();
+        assert!(storage.get_shard(&cid).await.is_err());
+        assert_eq!(storage.cache_stats().entries, 0);
+    }
+
+    #[tokio::test]
+    async fn test_cache_evicts_least_recently_used() {
+        let entry_size = 100 + ShardHeader::SIZE as u64;
+        let storage = CachedStorage::new(MemoryStorage::new(), entry_size * 2);
+        let (a, shard_a) = shard(1, 100);
+        let (b, shard_b) = shard(2, 100);
+        let (c, shard_c) = shard(3, 100);
+        for (cid, shard) in [(&a, &shard_a), (&b, &shard_b), (&c, &shard_c)] {
+            storage.put_shard(cid, shard).await.unwrap();
+        }
+
+        storage.get_shard(&a).await.unwrap();
+        storage.get_shard(&b).await.unwrap();
+        // Touch `a` so `b` becomes the eviction candidate
+        storage.get_shard(&a).await.unwrap();
+        storage.get_shard(&c).await.unwrap();
+
+        let stats = storage.cache_stats();
+        assert_eq!(stats.evictions, 1);
+        assert!(stats.bytes <= stats.capacity);
+
+        storage.get_shard(&a).await.unwrap();
+        storage.get_shard(&b).await.unwrap();
+        let stats = storage.cache_stats();
+        assert_eq!((stats.hits, stats.misses), (2, 4));
+    }
+
+    #[tokio::test]
+    async fn test_cache_drops_expired_shards() {
+        let storage = CachedStorage::new(MemoryStorage::new(), 1024 * 1024);
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let mut cids = Vec::new();
+        for byte in 0..2 {
+            let header = ShardHeader::new(EncryptionMode::Convergent, (4, 2), 8, [byte; 32])
+                .with_expiry(now + 1);
+            let shard = Shard::new(header, vec![byte; 8]);
+            let cid = shard.cid().unwrap();
+            storage.put_shard(&cid, &shard).await.unwrap();
+            storage.get_shard(&cid).await.unwrap();
+            cids.push(cid);
+        }
+        assert_eq!(storage.cache_stats().entries, 2);
+
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+
+        // Both reads miss and reach the backend, which refuses expired shards
+        assert!(storage.get_shard(&cids[0]).await.is_err());
+        assert!(storage.get_shards(&cids[1..]).await.unwrap()[0].is_none());
+        let stats = storage.cache_stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (0, 4, 0));
+    }
+}
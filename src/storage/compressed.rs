@@ -0,0 +1,292 @@
+//! Transparent compression at rest
+//!
+//! [`CompressedStorage`] deflates shard payloads before handing them to the
+//! wrapped backend and inflates them on read. This is independent of the
+//! pipeline's pre-encryption compression and only pays off for backends that
+//! hold plaintext FEC shards; encrypted payloads do not compress, so each
+//! payload is stored raw whenever deflate would not make it smaller.
+//!
+//! Stored payloads carry a one-byte tag, so the wrapped backend must only be
+//! written through the wrapper. Shards keep their original header and CID.
+
+use async_trait::async_trait;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::io::{Read, Write};
+
+use super::{
+    Cid, FileMetadata, GcReport, Shard, ShardHeader, ShardPage, StorageBackend, StorageStats,
+};
+use crate::FecError;
+
+/// Payload stored as-is
+const TAG_RAW: u8 = 0;
+/// Payload stored deflate-compressed
+const TAG_DEFLATE: u8 = 1;
+
+/// Storage backend wrapper compressing shard payloads at rest
+pub struct CompressedStorage<B> {
+    inner: B,
+    level: Compression,
+}
+
+impl<B: StorageBackend> CompressedStorage<B> {
+    /// Wrap a backend using the default compression level
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            level: Compression::default(),
+        }
+    }
+
+    /// Set the deflate level (0-9)
+    pub fn with_level(mut self, level: u32) -> Self {
+        self.level = Compression::new(level.min(9));
+        self
+    }
+
+    /// The wrapped backend
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, FecError> {
+        let mut encoder = DeflateEncoder::new(vec![TAG_DEFLATE], self.level);
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
+
+        if compressed.len() < data.len() + 1 {
+            return Ok(compressed);
+        }
+        let mut raw = Vec::with_capacity(data.len() + 1);
+        raw.push(TAG_RAW);
+        raw.extend_from_slice(data);
+        Ok(raw)
+    }
+
+    fn compress_all(&self, shards: &[(Cid, Shard)]) -> Result<Vec<(Cid, Shard)>, FecError> {
+        shards
+            .iter()
+            .map(|(cid, shard)| {
+                let data = self.compress(&shard.data)?;
+                Ok((*cid, Shard::new(shard.header.clone(), data)))
+            })
+            .collect()
+    }
+
+    /// Restore a stored payload, inflating at most the `data_size` the
+    /// header records so a corrupt or hostile payload cannot balloon
+    fn decompress(cid: &Cid, header: &ShardHeader, stored: &[u8]) -> Result<Vec<u8>, FecError> {
+        let limit = header.data_size as usize;
+        match stored.split_first() {
+            Some((&TAG_RAW, payload)) => Ok(payload.to_vec()),
+            Some((&TAG_DEFLATE, payload)) => {
+                let mut data = Vec::with_capacity(limit);
+                DeflateDecoder::new(payload)
+                    .take(limit as u64 + 1)
+                    .read_to_end(&mut data)?;
+                if data.len() > limit {
+                    return Err(FecError::Backend(format!(
+                        "Shard {} inflates past its {} byte payload",
+                        cid.to_hex(),
+                        limit
+                    )));
+                }
+                Ok(data)
+            }
+            Some((tag, _)) => Err(FecError::Backend(format!(
+                "Unknown compression tag {} for shard {}",
+                tag,
+                cid.to_hex()
+            ))),
+            None => Err(FecError::Backend(format!(
+                "Empty compressed payload for shard {}",
+                cid.to_hex()
+            ))),
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl<B: StorageBackend> StorageBackend for CompressedStorage<B> {
+    async fn put_shard(&self, cid: &Cid, shard: &Shard) -> Result<(), FecError> {
+        let stored = Shard::new(shard.header.clone(), self.compress(&shard.data)?);
+        self.inner.put_shard(cid, &stored).await
+    }
+
+    async fn get_shard(&self, cid: &Cid) -> Result<Shard, FecError> {
+        let stored = self.inner.get_shard(cid).await?;
+        let data = Self::decompress(cid, &stored.header, &stored.data)?;
+        Ok(Shard::new(stored.header, data))
+    }
+
+    /// Stored payloads do not hash to their CID, so the wrapped backend
+    /// cannot check them; a payload that no longer inflates counts as corrupt
+    async fn get_shard_verified(&self, cid: &Cid) -> Result<Shard, FecError> {
+        let stored = self.inner.get_shard(cid).await?;
+        let data = match Self::decompress(cid, &stored.header, &stored.data) {
+            Ok(data) => data,
+            Err(_) => {
+                return Err(FecError::CorruptShard {
+                    cid: cid.to_hex(),
+                    actual: stored.cid()?.to_hex(),
+                })
+            }
+        };
+        let shard = Shard::new(stored.header, data);
+        shard.verify(cid)?;
+        Ok(shard)
+    }
+
+    async fn delete_shard(&self, cid: &Cid) -> Result<(), FecError> {
+        self.inner.delete_shard(cid).await
+    }
+
+    async fn has_shard(&self, cid: &Cid) -> Result<bool, FecError> {
+        self.inner.has_shard(cid).await
+    }
+
+    async fn list_shards(&self) -> Result<Vec<Cid>, FecError> {
+        self.inner.list_shards().await
+    }
+
+    async fn list_shards_paged(
+        &self,
+        after: Option<&Cid>,
+        limit: usize,
+    ) -> Result<ShardPage, FecError> {
+        self.inner.list_shards_paged(after, limit).await
+    }
+
+    async fn put_metadata(&self, metadata: &FileMetadata) -> Result<(), FecError> {
+        self.inner.put_metadata(metadata).await
+    }
+
+    async fn get_metadata(&self, file_id: &[u8; 32]) -> Result<FileMetadata, FecError> {
+        self.inner.get_metadata(file_id).await
+    }
+
+    async fn delete_metadata(&self, file_id: &[u8; 32]) -> Result<(), FecError> {
+        self.inner.delete_metadata(file_id).await
+    }
+
+    async fn list_metadata(&self) -> Result<Vec<FileMetadata>, FecError> {
+        self.inner.list_metadata().await
+    }
+
+    async fn stats(&self) -> Result<StorageStats, FecError> {
+        self.inner.stats().await
+    }
+
+    async fn garbage_collect(&self) -> Result<GcReport, FecError> {
+        self.inner.garbage_collect().await
+    }
+
+    async fn put_shards(&self, shards: &[(Cid, Shard)]) -> Result<(), FecError> {
+        self.inner.put_shards(&self.compress_all(shards)?).await
+    }
+
+    async fn commit_transaction(
+        &self,
+        shards: &[(Cid, Shard)],
+        metadata: &[FileMetadata],
+    ) -> Result<(), FecError> {
+        self.inner
+            .commit_transaction(&self.compress_all(shards)?, metadata)
+            .await
+    }
+
+    async fn get_shards(&self, cids: &[Cid]) -> Result<Vec<Option<Shard>>, FecError> {
+        let stored = self.inner.get_shards(cids).await?;
+        Ok(cids
+            .iter()
+            .zip(stored)
+            .map(|(cid, shard)| {
+                let shard = shard?;
+                match Self::decompress(cid, &shard.header, &shard.data) {
+                    Ok(data) => Some(Shard::new(shard.header, data)),
+                    Err(e) => {
+                        tracing::warn!("{}", e);
+                        None
+                    }
+                }
+            })
+            .collect())
+    }
+
+    async fn has_shards(&self, cids: &[Cid]) -> Result<Vec<bool>, FecError> {
+        self.inner.has_shards(cids).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{MemoryStorage, ShardHeader};
+    use crate::EncryptionMode;
+
+    #[tokio::test]
+    async fn test_compressible_shard_roundtrip() {
+        let backend = MemoryStorage::new();
+        let storage = CompressedStorage::new(backend.clone());
+
+        let header = ShardHeader::new(EncryptionMode::Convergent, (4, 2), 4096, [0u8; 32]);
+        let shard = Shard::new(header, vec![7u8; 4096]);
+        let cid = shard.cid().unwrap();
+        storage.put_shard(&cid, &shard).await.unwrap();
+
+        let stored = backend.get_shard(&cid).await.unwrap();
+        assert_eq!(stored.data[0], TAG_DEFLATE);
+        assert!(stored.data.len() < shard.data.len());
+
+        let restored = storage.get_shard(&cid).await.unwrap();
+        assert_eq!(restored.data, shard.data);
+        assert_eq!(restored.cid().unwrap(), cid);
+    }
+
+    #[tokio::test]
+    async fn test_incompressible_shard_stored_raw() {
+        let backend = MemoryStorage::new();
+        let storage = CompressedStorage::new(backend.clone()).with_level(9);
+
+        let data: Vec<u8> = (0..1024u32)
+            .map(|i| blake3::hash(&i.to_le_bytes()).as_bytes()[0])
+            .collect();
+        let header = ShardHeader::new(EncryptionMode::Convergent, (4, 2), 1024, [0u8; 32]);
+        let shard = Shard::new(header, data);
+        let cid = shard.cid().unwrap();
+        storage.put_shard(&cid, &shard).await.unwrap();
+
+        let stored = backend.get_shard(&cid).await.unwrap();
+        assert_eq!(stored.data[0], TAG_RAW);
+        assert_eq!(stored.data.len(), shard.data.len() + 1);
+        assert_eq!(storage.get_shard(&cid).await.unwrap().data, shard.data);
+    }
+
+    #[tokio::test]
+    async fn test_verified_read_bounds_inflated_size() {
+        let backend = MemoryStorage::new();
+        let storage = CompressedStorage::new(backend.clone());
+
+        let header = ShardHeader::new(EncryptionMode::Convergent, (4, 2), 4096, [0u8; 32]);
+        let shard = Shard::new(header, vec![7u8; 4096]);
+        let cid = shard.cid().unwrap();
+        storage.put_shard(&cid, &shard).await.unwrap();
+        assert_eq!(
+            storage.get_shard_verified(&cid).await.unwrap().data,
+            shard.data
+        );
+        let page = storage.list_shards_paged(None, 10).await.unwrap();
+        assert_eq!(page.cids, vec![cid]);
+
+        // A payload inflating past the header's size is rejected as corrupt
+        let mut bomb = Shard::new(shard.header.clone(), vec![0u8; 1 << 20]);
+        bomb.data = storage.compress(&bomb.data).unwrap();
+        backend.put_shard(&cid, &bomb).await.unwrap();
+        assert!(storage.get_shard(&cid).await.is_err());
+        let err = storage.get_shard_verified(&cid).await.unwrap_err();
+        assert!(matches!(err, FecError::CorruptShard { .. }));
+    }
+}
@@ -0,0 +1,221 @@
+//! Write durability settings for [`LocalStorage`](super::LocalStorage)
+//!
+//! Every file is written to a temporary path and renamed into place, so a
+//! crash never leaves a partially written shard visible. What this module
+//! controls is how soon the data reaches stable storage: fsync on every write
+//! gives the strongest guarantee, batching or skipping it trades the most
+//! recent writes for ingest throughput.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+#[cfg(feature = "native")]
+use std::path::PathBuf;
+
+#[cfg(feature = "native")]
+use crate::FecError;
+
+/// Alignment required by direct I/O on common Linux filesystems
+#[cfg(all(feature = "native", target_os = "linux"))]
+const DIRECT_IO_ALIGN: usize = 4096;
+
+/// When written files are flushed to stable storage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyncPolicy {
+    /// fsync every file before it is renamed into place
+    Always,
+    /// fsync outstanding files once `max_pending` writes have accumulated,
+    /// or when [`LocalStorage::flush`](super::LocalStorage::flush) is called
+    Batched {
+        /// Writes allowed to stay unsynced
+        max_pending: usize,
+    },
+    /// Leave flushing to the operating system
+    Never,
+}
+
+/// Durability settings for local filesystem writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DurabilityPolicy {
+    /// When file contents are fsynced
+    pub sync: SyncPolicy,
+    /// fsync the parent directory after a rename so the new name survives a crash
+    pub sync_dir: bool,
+    /// Write shards with `O_DIRECT`, bypassing the page cache
+    ///
+    /// Only honoured on Linux; filesystems that reject direct I/O fall back
+    /// to buffered writes.
+    pub direct_io: bool,
+}
+
+impl DurabilityPolicy {
+    /// fsync files and directories on every write
+    pub fn max_durability() -> Self {
+        Self {
+            sync: SyncPolicy::Always,
+            sync_dir: true,
+            direct_io: false,
+        }
+    }
+
+    /// Never fsync; a crash may lose recently written shards
+    pub fn max_throughput() -> Self {
+        Self {
+            sync: SyncPolicy::Never,
+            sync_dir: false,
+            direct_io: false,
+        }
+    }
+}
+
+impl Default for DurabilityPolicy {
+    fn default() -> Self {
+        Self {
+            sync: SyncPolicy::Always,
+            sync_dir: false,
+            direct_io: false,
+        }
+    }
+}
+
+/// Overwrite the contents of `path` with random data and fsync it
+///
+/// Used before unlinking when secure delete is enabled. On copy-on-write or
+/// flash-translated storage the old blocks may survive elsewhere, so this is
+/// a best effort against casual recovery rather than a guarantee.
+#[cfg(feature = "native")]
+pub(super) async fn erase_file(path: &Path) -> Result<(), FecError> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        use rand::RngCore;
+        use std::io::Write;
+
+        let mut file = std::fs::OpenOptions::new().write(true).open(&path)?;
+        let mut remaining = file.metadata()?.len();
+        let mut rng = rand::thread_rng();
+        let mut buf = vec![0u8; 64 * 1024];
+        while remaining > 0 {
+            let n = remaining.min(buf.len() as u64) as usize;
+            rng.fill_bytes(&mut buf[..n]);
+            file.write_all(&buf[..n])?;
+            remaining -= n as u64;
+        }
+        file.sync_all().map_err(FecError::Io)
+    })
+    .await
+    .map_err(|e| FecError::Backend(format!("Erase task failed: {}", e)))?
+}
+
+/// Write `bytes` to a new file at `path`, fsyncing it if `sync` is set
+#[cfg(feature = "native")]
+pub(super) async fn write_file(
+    path: &Path,
+    bytes: &[u8],
+    sync: bool,
+    direct_io: bool,
+) -> Result<(), FecError> {
+    #[cfg(target_os = "linux")]
+    if direct_io {
+        let path = path.to_path_buf();
+        let bytes = bytes.to_vec();
+        return tokio::task::spawn_blocking(move || write_direct(&path, &bytes, sync))
+            .await
+            .map_err(|e| FecError::Backend(format!("Write task failed: {}", e)))?;
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = direct_io;
+
+    let mut file = tokio::fs::File::create(path).await?;
+    tokio::io::AsyncWriteExt::write_all(&mut file, bytes).await?;
+    if sync {
+        file.sync_all().await?;
+    }
+    Ok(())
+}
+
+/// Write through `O_DIRECT`, falling back to a buffered write if the
+/// filesystem refuses it
+#[cfg(all(feature = "native", target_os = "linux"))]
+fn write_direct(path: &Path, bytes: &[u8], sync: bool) -> Result<(), FecError> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let opened = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .custom_flags(libc::O_DIRECT)
+        .open(path);
+    let mut file = match opened {
+        Ok(file) => file,
+        Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {
+            let mut file = std::fs::File::create(path)?;
+            file.write_all(bytes)?;
+            if sync {
+                file.sync_all()?;
+            }
+            return Ok(());
+        }
+        Err(e) => return Err(FecError::Io(e)),
+    };
+
+    // Direct I/O needs an aligned buffer and length; the padding is cut off
+    // again with set_len
+    let padded = bytes.len().div_ceil(DIRECT_IO_ALIGN) * DIRECT_IO_ALIGN;
+    let mut buf = vec![0u8; padded + DIRECT_IO_ALIGN];
+    let offset = buf.as_ptr().align_offset(DIRECT_IO_ALIGN);
+    let aligned = &mut buf[offset..offset + padded];
+    aligned[..bytes.len()].copy_from_slice(bytes);
+
+    file.write_all(aligned)?;
+    file.set_len(bytes.len() as u64)?;
+    if sync {
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+/// fsync a directory so renames within it are durable
+#[cfg(feature = "native")]
+pub(super) async fn sync_dir(dir: &Path) -> Result<(), FecError> {
+    let dir = dir.to_path_buf();
+    tokio::task::spawn_blocking(move || sync_dir_blocking(&dir))
+        .await
+        .map_err(|e| FecError::Backend(format!("Sync task failed: {}", e)))??;
+    Ok(())
+}
+
+/// [`sync_dir`] for synchronous callers
+pub(crate) fn sync_dir_blocking(dir: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    std::fs::File::open(dir)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+/// fsync files written under [`SyncPolicy::Batched`], and their directories
+/// when `sync_dirs` is set
+#[cfg(feature = "native")]
+pub(super) async fn sync_files(paths: Vec<PathBuf>, sync_dirs: bool) -> Result<(), FecError> {
+    let mut dirs = Vec::new();
+    for path in paths {
+        // A file deleted since it was written has nothing left to sync
+        match tokio::fs::File::open(&path).await {
+            Ok(file) => file.sync_all().await?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(FecError::Io(e)),
+        }
+        if let Some(parent) = path.parent() {
+            dirs.push(parent.to_path_buf());
+        }
+    }
+
+    if sync_dirs {
+        dirs.sort();
+        dirs.dedup();
+        for dir in dirs {
+            sync_dir(&dir).await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,164 @@
+//! Build storage backends from configuration
+//!
+//! [`BackendFactory`] turns a [`config::StorageBackend`](crate::config::StorageBackend)
+//! description into a ready backend, so an application can be wired entirely
+//! from a configuration file. Network backends need a transport, which cannot
+//! be described in configuration and is supplied to the factory instead.
+
+use std::sync::Arc;
+
+#[cfg(feature = "native")]
+use super::LocalStorage;
+use super::{
+    CachedStorage, MultiStorage, NetworkStorage, NodeEndpoint, StorageBackend, ThrottledStorage,
+};
+use crate::config;
+use crate::network::NodeTransport;
+use crate::FecError;
+
+/// Constructs storage backends from configuration
+#[derive(Clone, Default)]
+pub struct BackendFactory {
+    transport: Option<Arc<dyn NodeTransport>>,
+    secure_delete: bool,
+}
+
+impl BackendFactory {
+    /// Create a factory without a network transport
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the transport given to every network backend
+    pub fn with_transport(mut self, transport: Arc<dyn NodeTransport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Make local backends overwrite files before deleting them
+    pub fn with_secure_delete(mut self, secure_delete: bool) -> Self {
+        self.secure_delete = secure_delete;
+        self
+    }
+
+    /// Build the backend described by `config`, recursing into multi-backends
+    pub async fn build(
+        &self,
+        config: &config::StorageBackend,
+    ) -> Result<Arc<dyn StorageBackend>, FecError> {
+        match config {
+            #[cfg(feature = "native")]
+            config::StorageBackend::Local { path } => {
+                let storage = LocalStorage::new(path.into()).await?;
+                Ok(Arc::new(storage.with_secure_delete(self.secure_delete)))
+            }
+            #[cfg(not(feature = "native"))]
+            config::StorageBackend::Local { .. } => Err(FecError::Backend(
+                "Local backend requires the `native` feature".to_string(),
+            )),
+            config::StorageBackend::Network { nodes, replication } => {
+                let nodes = nodes
+                    .iter()
+                    .map(|node| node.parse())
+                    .collect::<Result<Vec<NodeEndpoint>, FecError>>()?;
+                if *replication == 0 || *replication > nodes.len() {
+                    return Err(FecError::Backend(format!(
+                        "Replication factor {} needs between 1 and {} nodes",
+                        replication,
+                        nodes.len()
+                    )));
+                }
+                let transport = self.transport.clone().ok_or_else(|| {
+                    FecError::Backend("Network backend requires a transport".to_string())
+                })?;
+                Ok(Arc::new(
+                    NetworkStorage::new(nodes, *replication).with_transport(transport),
+                ))
+            }
+            config::StorageBackend::Multi { backends } => {
+                if backends.is_empty() {
+                    return Err(FecError::Backend(
+                        "Multi backend needs at least one backend".to_string(),
+                    ));
+                }
+                let mut built = Vec::with_capacity(backends.len());
+                for backend in backends {
+                    built.push(Box::pin(self.build(backend)).await?);
+                }
+                Ok(Arc::new(MultiStorage::new(built)))
+            }
+        }
+    }
+
+    /// Build the configured backend behind a read cache of `cache_size` bytes,
+    /// with writes throttled to `max_in_flight_bytes`
+    pub async fn build_storage(
+        &self,
+        config: &config::StorageConfig,
+    ) -> Result<Arc<dyn StorageBackend>, FecError> {
+        let factory = self
+            .clone()
+            .with_secure_delete(self.secure_delete || config.secure_delete);
+        let backend = factory.build(&config.backend).await?;
+        let throttled = ThrottledStorage::from_config(backend, config);
+        Ok(Arc::new(CachedStorage::from_config(throttled, config)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{Shard, ShardHeader};
+    use crate::EncryptionMode;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_build_nested_multi_backend() {
+        let temp_dir = TempDir::new().unwrap();
+        let local = |name: &str| config::StorageBackend::Local {
+            path: temp_dir.path().join(name).to_string_lossy().into_owned(),
+        };
+        let config = config::StorageConfig {
+            backend: config::StorageBackend::Multi {
+                backends: vec![
+                    local("a"),
+                    config::StorageBackend::Multi {
+                        backends: vec![local("b")],
+                    },
+                ],
+            },
+            cache_size: 1024 * 1024,
+            parallel_operations: 1,
+            secure_delete: true,
+            max_in_flight_bytes: 1024 * 1024,
+        };
+
+        let storage = BackendFactory::new().build_storage(&config).await.unwrap();
+        let header = ShardHeader::new(EncryptionMode::Convergent, (2, 1), 4, [0u8; 32]);
+        let shard = Shard::new(header, b"data".to_vec());
+        let cid = shard.cid().unwrap();
+        storage.put_shard(&cid, &shard).await.unwrap();
+
+        // Both leaves received the shard
+        for name in ["a", "b"] {
+            let leaf = LocalStorage::new(temp_dir.path().join(name)).await.unwrap();
+            assert!(leaf.has_shard(&cid).await.unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_network_backend_needs_transport() {
+        let config = config::StorageBackend::Network {
+            nodes: vec!["10.0.0.1:9000".to_string(), "[::1]:9001".to_string()],
+            replication: 2,
+        };
+        let err = BackendFactory::new().build(&config).await.err().unwrap();
+        assert!(err.to_string().contains("transport"));
+
+        let bad = config::StorageBackend::Network {
+            nodes: vec!["no-port".to_string()],
+            replication: 1,
+        };
+        assert!(BackendFactory::new().build(&bad).await.is_err());
+    }
+}
@@ -0,0 +1,214 @@
+//! Per-node health tracking for [`NetworkStorage`](super::NetworkStorage)
+//!
+//! Every request outcome updates a node's latency, error rate and last-seen
+//! time. A node that fails `failure_threshold` requests in a row is marked
+//! unhealthy and skipped for writes until `retry_after` has passed, at which
+//! point the next request probes it again.
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::NodeEndpoint;
+
+/// Weight given to the newest sample in the moving averages
+const SMOOTHING: f64 = 0.2;
+
+/// When a node is considered unhealthy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthPolicy {
+    /// Consecutive failures after which a node is marked unhealthy
+    pub failure_threshold: u32,
+    /// How long an unhealthy node is skipped before it is tried again
+    pub retry_after: Duration,
+}
+
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 3,
+            retry_after: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Snapshot of one node's health
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeHealth {
+    /// The node this entry describes
+    pub node: NodeEndpoint,
+    /// Whether the node is currently used for writes
+    pub healthy: bool,
+    /// Moving average of request round-trip time
+    pub latency: Option<Duration>,
+    /// Moving average of the request failure rate (0.0 - 1.0)
+    pub error_rate: f64,
+    /// Failures since the last successful request
+    pub consecutive_failures: u32,
+    /// Total successful requests
+    pub successes: u64,
+    /// Total failed requests
+    pub failures: u64,
+    /// Time of the last successful request
+    pub last_seen: Option<SystemTime>,
+    /// Most recent transport error
+    pub last_error: Option<String>,
+}
+
+impl NodeHealth {
+    fn new(node: &NodeEndpoint) -> Self {
+        Self {
+            node: node.clone(),
+            healthy: true,
+            latency: None,
+            error_rate: 0.0,
+            consecutive_failures: 0,
+            successes: 0,
+            failures: 0,
+            last_seen: None,
+            last_error: None,
+        }
+    }
+}
+
+struct Entry {
+    health: NodeHealth,
+    last_failure: Option<Instant>,
+}
+
+/// Thread-safe health table keyed by node address
+pub(super) struct HealthTracker {
+    policy: HealthPolicy,
+    entries: RwLock<HashMap<(String, u16), Entry>>,
+}
+
+impl HealthTracker {
+    pub(super) fn new(policy: HealthPolicy) -> Self {
+        Self {
+            policy,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub(super) fn record_success(&self, node: &NodeEndpoint, latency: Duration) {
+        self.update(node, |entry| {
+            let health = &mut entry.health;
+            health.successes += 1;
+            health.consecutive_failures = 0;
+            health.error_rate *= 1.0 - SMOOTHING;
+            health.latency = Some(match health.latency {
+                Some(avg) => avg.mul_f64(1.0 - SMOOTHING) + latency.mul_f64(SMOOTHING),
+                None => latency,
+            });
+            health.last_seen = Some(SystemTime::now());
+        });
+    }
+
+    pub(super) fn record_failure(&self, node: &NodeEndpoint, error: &str) {
+        self.update(node, |entry| {
+            let health = &mut entry.health;
+            health.failures += 1;
+            health.consecutive_failures += 1;
+            health.error_rate = health.error_rate * (1.0 - SMOOTHING) + SMOOTHING;
+            health.last_error = Some(error.to_string());
+            entry.last_failure = Some(Instant::now());
+        });
+    }
+
+    /// Whether writes should be attempted on this node right now
+    pub(super) fn is_available(&self, node: &NodeEndpoint) -> bool {
+        match self.entries.read().get(&key(node)) {
+            Some(entry) => self.available(entry),
+            None => true,
+        }
+    }
+
+    /// Health of every given node, including ones never contacted
+    pub(super) fn snapshot<'a>(
+        &self,
+        nodes: impl IntoIterator<Item = &'a NodeEndpoint>,
+    ) -> Vec<NodeHealth> {
+        let entries = self.entries.read();
+        nodes
+            .into_iter()
+            .map(|node| match entries.get(&key(node)) {
+                Some(entry) => NodeHealth {
+                    healthy: self.available(entry),
+                    ..entry.health.clone()
+                },
+                None => NodeHealth::new(node),
+            })
+            .collect()
+    }
+
+    fn available(&self, entry: &Entry) -> bool {
+        entry.health.consecutive_failures < self.policy.failure_threshold
+            || entry
+                .last_failure
+                .is_some_and(|at| at.elapsed() >= self.policy.retry_after)
+    }
+
+    fn update(&self, node: &NodeEndpoint, f: impl FnOnce(&mut Entry)) {
+        let mut entries = self.entries.write();
+        let entry = entries.entry(key(node)).or_insert_with(|| Entry {
+            health: NodeHealth::new(node),
+            last_failure: None,
+        });
+        f(entry);
+    }
+}
+
+fn key(node: &NodeEndpoint) -> (String, u16) {
+    (node.address.clone(), node.port)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node() -> NodeEndpoint {
+        NodeEndpoint {
+            address: "10.0.0.1".to_string(),
+            port: 9000,
+            node_id: None,
+        }
+    }
+
+    #[test]
+    fn test_node_marked_unhealthy_after_threshold() {
+        let tracker = HealthTracker::new(HealthPolicy {
+            failure_threshold: 2,
+            retry_after: Duration::from_secs(3600),
+        });
+        let node = node();
+
+        tracker.record_success(&node, Duration::from_millis(10));
+        tracker.record_failure(&node, "timeout");
+        assert!(tracker.is_available(&node));
+        tracker.record_failure(&node, "timeout");
+        assert!(!tracker.is_available(&node));
+
+        let health = &tracker.snapshot([&node])[0];
+        assert!(!health.healthy);
+        assert_eq!((health.successes, health.failures), (1, 2));
+        assert_eq!(health.latency, Some(Duration::from_millis(10)));
+        assert_eq!(health.last_error.as_deref(), Some("timeout"));
+        assert!(health.last_seen.is_some());
+
+        // A single success clears the failure streak
+        tracker.record_success(&node, Duration::from_millis(20));
+        assert!(tracker.is_available(&node));
+    }
+
+    #[test]
+    fn test_unhealthy_node_retried_after_cooldown() {
+        let tracker = HealthTracker::new(HealthPolicy {
+            failure_threshold: 1,
+            retry_after: Duration::ZERO,
+        });
+        let node = node();
+        tracker.record_failure(&node, "refused");
+        assert!(tracker.is_available(&node));
+    }
+}
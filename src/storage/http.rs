@@ -0,0 +1,534 @@
+//! HTTP and WebDAV storage backend
+//!
+//! Shards and metadata are stored as plain files under a base URL using the
+//! same naming as [`LocalStorage`](super::LocalStorage):
+//! `shards/<cid>.shard` and `metadata/<file_id>.meta`. Any server that accepts
+//! `PUT`, `GET`, `HEAD` and `DELETE` works; listing requires either WebDAV
+//! (`PROPFIND`) or an index page for the two directories (e.g. nginx
+//! `autoindex`).
+
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use reqwest::{Method, StatusCode};
+use std::collections::HashSet;
+use std::time::Duration;
+
+use super::{Cid, FileMetadata, GcReport, Shard, StorageBackend, StorageStats};
+use crate::FecError;
+
+/// Configuration for [`HttpStorage`]
+#[derive(Debug, Clone)]
+pub struct HttpStorageConfig {
+    /// Base URL under which `shards/` and `metadata/` live
+    pub base_url: String,
+    /// Extra headers sent with every request (e.g. authentication)
+    pub headers: Vec<(String, String)>,
+    /// Use WebDAV: create collections with `MKCOL` and list with `PROPFIND`
+    pub webdav: bool,
+    /// Per-request timeout
+    pub timeout: Duration,
+}
+
+impl HttpStorageConfig {
+    /// Create a configuration for the given base URL
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            headers: Vec::new(),
+            webdav: false,
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Add a header sent with every request
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Authenticate with a bearer token
+    pub fn with_bearer_token(self, token: impl AsRef<str>) -> Self {
+        self.with_header(AUTHORIZATION.as_str(), format!("Bearer {}", token.as_ref()))
+    }
+
+    /// Enable or disable WebDAV mode
+    pub fn with_webdav(mut self, webdav: bool) -> Self {
+        self.webdav = webdav;
+        self
+    }
+
+    /// Set the per-request timeout
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// Storage backend speaking plain HTTP or WebDAV
+pub struct HttpStorage {
+    client: reqwest::Client,
+    base_url: String,
+    webdav: bool,
+}
+
+impl HttpStorage {
+    /// Create a new HTTP storage backend
+    ///
+    /// In WebDAV mode the `shards/` and `metadata/` collections are created if
+    /// they do not already exist.
+    pub async fn new(config: HttpStorageConfig) -> Result<Self, FecError> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| FecError::Backend(format!("Invalid header name {}: {}", name, e)))?;
+            let mut value = HeaderValue::from_str(value)
+                .map_err(|e| FecError::Backend(format!("Invalid header value: {}", e)))?;
+            value.set_sensitive(name == AUTHORIZATION);
+            headers.insert(name, value);
+        }
+
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(config.timeout)
+            .build()
+            .map_err(http_error)?;
+
+        let storage = Self {
+            client,
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            webdav: config.webdav,
+        };
+
+        if storage.webdav {
+            for collection in ["shards/", "metadata/"] {
+                let response = storage
+                    .client
+                    .request(webdav_method("MKCOL")?, storage.url(collection))
+                    .send()
+                    .await
+                    .map_err(http_error)?;
+                // 405 means the collection already exists
+                if !response.status().is_success()
+                    && response.status() != StatusCode::METHOD_NOT_ALLOWED
+                {
+                    return Err(status_error(response.status(), collection));
+                }
+            }
+        }
+
+        Ok(storage)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path)
+    }
+
+    fn shard_path(cid: &Cid) -> String {
+        format!("shards/{}.shard", cid.to_hex())
+    }
+
+    fn metadata_path(file_id: &[u8; 32]) -> String {
+        format!("metadata/{}.meta", hex::encode(file_id))
+    }
+
+    async fn put(&self, path: &str, body: Vec<u8>) -> Result<(), FecError> {
+        let response = self
+            .client
+            .put(self.url(path))
+            .body(body)
+            .send()
+            .await
+            .map_err(http_error)?;
+        if !response.status().is_success() {
+            return Err(status_error(response.status(), path));
+        }
+        Ok(())
+    }
+
+    /// Body of `path`, or `None` if the server has nothing there
+    async fn get(&self, path: &str) -> Result<Option<Vec<u8>>, FecError> {
+        let response = self
+            .client
+            .get(self.url(path))
+            .send()
+            .await
+            .map_err(http_error)?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(status_error(response.status(), path));
+        }
+        Ok(Some(response.bytes().await.map_err(http_error)?.to_vec()))
+    }
+
+    async fn delete(&self, path: &str) -> Result<(), FecError> {
+        let response = self
+            .client
+            .delete(self.url(path))
+            .send()
+            .await
+            .map_err(http_error)?;
+        // Deleting something that is already gone is not an error
+        if !response.status().is_success() && response.status() != StatusCode::NOT_FOUND {
+            return Err(status_error(response.status(), path));
+        }
+        Ok(())
+    }
+
+    async fn exists(&self, path: &str) -> Result<bool, FecError> {
+        let response = self
+            .client
+            .head(self.url(path))
+            .send()
+            .await
+            .map_err(http_error)?;
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            status => Err(status_error(status, path)),
+        }
+    }
+
+    /// List hex names with the given extension inside a directory
+    async fn list(&self, dir: &str, extension: &str) -> Result<Vec<[u8; 32]>, FecError> {
+        let request = if self.webdav {
+            self.client
+                .request(webdav_method("PROPFIND")?, self.url(dir))
+                .header("Depth", "1")
+        } else {
+            self.client.get(self.url(dir))
+        };
+
+        let response = request.send().await.map_err(http_error)?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+        if !response.status().is_success() {
+            return Err(status_error(response.status(), dir));
+        }
+
+        let body = response.text().await.map_err(http_error)?;
+        Ok(extract_names(&body, extension))
+    }
+}
+
+/// Find every `<64 hex chars><extension>` name in a directory listing
+///
+/// This works for WebDAV multistatus XML as well as HTML index pages.
+fn extract_names(body: &str, extension: &str) -> Vec<[u8; 32]> {
+    let mut seen = HashSet::new();
+    let mut names = Vec::new();
+
+    for (pos, _) in body.match_indices(extension) {
+        let Some(start) = pos.checked_sub(64) else {
+            continue;
+        };
+        let Some(candidate) = body.get(start..pos) else {
+            continue;
+        };
+        let mut bytes = [0u8; 32];
+        if hex::decode_to_slice(candidate, &mut bytes).is_ok() && seen.insert(bytes) {
+            names.push(bytes);
+        }
+    }
+
+    names
+}
+
+/// Build an extension method such as `MKCOL` or `PROPFIND`
+fn webdav_method(name: &str) -> Result<Method, FecError> {
+    Method::from_bytes(name.as_bytes())
+        .map_err(|e| FecError::Backend(format!("Invalid HTTP method {}: {}", name, e)))
+}
+
+fn http_error(e: reqwest::Error) -> FecError {
+    FecError::Backend(format!("HTTP request failed: {}", e))
+}
+
+fn status_error(status: StatusCode, path: &str) -> FecError {
+    FecError::Backend(format!("HTTP {} for {}", status, path))
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl StorageBackend for HttpStorage {
+    async fn put_shard(&self, cid: &Cid, shard: &Shard) -> Result<(), FecError> {
+        self.put(&Self::shard_path(cid), shard.to_bytes()?).await
+    }
+
+    async fn get_shard(&self, cid: &Cid) -> Result<Shard, FecError> {
+        let bytes = self
+            .get(&Self::shard_path(cid))
+            .await?
+            .ok_or_else(|| FecError::ShardNotFound { cid: cid.to_hex() })?;
+        super::reject_expired(cid, Shard::from_bytes(&bytes)?)
+    }
+
+    async fn delete_shard(&self, cid: &Cid) -> Result<(), FecError> {
+        self.delete(&Self::shard_path(cid)).await
+    }
+
+    async fn has_shard(&self, cid: &Cid) -> Result<bool, FecError> {
+        self.exists(&Self::shard_path(cid)).await
+    }
+
+    async fn list_shards(&self) -> Result<Vec<Cid>, FecError> {
+        Ok(self
+            .list("shards/", ".shard")
+            .await?
+            .into_iter()
+            .map(Cid::new)
+            .collect())
+    }
+
+    async fn put_metadata(&self, metadata: &FileMetadata) -> Result<(), FecError> {
+        let serialized = bincode::serialize(metadata)
+            .map_err(|e| FecError::Backend(format!("Failed to serialize metadata: {}", e)))?;
+        self.put(&Self::metadata_path(&metadata.file_id), serialized)
+            .await
+    }
+
+    async fn get_metadata(&self, file_id: &[u8; 32]) -> Result<FileMetadata, FecError> {
+        let data = self
+            .get(&Self::metadata_path(file_id))
+            .await?
+            .ok_or_else(|| FecError::MetadataNotFound {
+                file_id: hex::encode(file_id),
+            })?;
+        bincode::deserialize(&data)
+            .map_err(|e| FecError::Backend(format!("Failed to deserialize metadata: {}", e)))
+    }
+
+    async fn delete_metadata(&self, file_id: &[u8; 32]) -> Result<(), FecError> {
+        self.delete(&Self::metadata_path(file_id)).await
+    }
+
+    async fn list_metadata(&self) -> Result<Vec<FileMetadata>, FecError> {
+        let mut metadata_list = Vec::new();
+        for file_id in self.list("metadata/", ".meta").await? {
+            if let Ok(metadata) = self.get_metadata(&file_id).await {
+                metadata_list.push(metadata);
+            }
+        }
+        Ok(metadata_list)
+    }
+
+    async fn stats(&self) -> Result<StorageStats, FecError> {
+        let shards = self.list_shards().await?;
+        let metadata = self.list_metadata().await?;
+
+        let mut total_size = 0u64;
+        for cid in &shards {
+            if let Ok(Some(bytes)) = self.get(&Self::shard_path(cid)).await {
+                total_size += bytes.len() as u64;
+            }
+        }
+
+        let referenced_cids = super::referenced_cids(&metadata);
+        let unreferenced_shards = shards
+            .iter()
+            .filter(|cid| !referenced_cids.contains(cid))
+            .count() as u64;
+
+        Ok(StorageStats {
+            total_shards: shards.len() as u64,
+            total_size,
+            metadata_count: metadata.len() as u64,
+            unreferenced_shards,
+            capacity: None,
+            free_space: None,
+        })
+    }
+
+    async fn garbage_collect(&self) -> Result<GcReport, FecError> {
+        let start_time = std::time::Instant::now();
+        let mut shards_deleted = 0u64;
+        let mut bytes_freed = 0u64;
+
+        let shards = self.list_shards().await?;
+        let referenced_cids = super::referenced_cids(&self.list_metadata().await?);
+
+        for cid in shards {
+            if referenced_cids.contains(&cid) {
+                continue;
+            }
+            if let Ok(Some(bytes)) = self.get(&Self::shard_path(&cid)).await {
+                bytes_freed += bytes.len() as u64;
+            }
+            self.delete_shard(&cid).await?;
+            shards_deleted += 1;
+        }
+
+        Ok(GcReport {
+            shards_deleted,
+            bytes_freed,
+            duration_ms: start_time.elapsed().as_millis() as u64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::ShardHeader;
+    use crate::EncryptionMode;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    type Files = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    /// Minimal in-memory HTTP/1.1 file server requiring a bearer token
+    async fn start_server(token: &'static str) -> (String, Files) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let files: Files = Arc::new(Mutex::new(HashMap::new()));
+
+        let server_files = files.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let files = server_files.clone();
+                tokio::spawn(async move {
+                    let (read, mut write) = stream.into_split();
+                    let mut reader = BufReader::new(read);
+                    loop {
+                        let mut request_line = String::new();
+                        if reader.read_line(&mut request_line).await.unwrap_or(0) == 0 {
+                            break;
+                        }
+                        let mut parts = request_line.split_whitespace();
+                        let method = parts.next().unwrap_or_default().to_string();
+                        let path = parts.next().unwrap_or_default().to_string();
+
+                        let mut content_length = 0;
+                        let mut authorized = false;
+                        loop {
+                            let mut line = String::new();
+                            reader.read_line(&mut line).await.unwrap();
+                            let line = line.trim_end();
+                            if line.is_empty() {
+                                break;
+                            }
+                            let (name, value) = line.split_once(':').unwrap();
+                            match name.to_ascii_lowercase().as_str() {
+                                "content-length" => content_length = value.trim().parse().unwrap(),
+                                "authorization" => {
+                                    authorized = value.trim() == format!("Bearer {}", token)
+                                }
+                                _ => {}
+                            }
+                        }
+                        let mut body = vec![0u8; content_length];
+                        reader.read_exact(&mut body).await.unwrap();
+
+                        let (status, payload) = if !authorized {
+                            ("401 Unauthorized", Vec::new())
+                        } else {
+                            let mut files = files.lock().unwrap();
+                            match method.as_str() {
+                                "PUT" => {
+                                    files.insert(path, body);
+                                    ("201 Created", Vec::new())
+                                }
+                                "GET" | "HEAD" if path.ends_with('/') => {
+                                    let listing: String = files
+                                        .keys()
+                                        .filter_map(|k| k.strip_prefix(&path))
+                                        .map(|name| format!("<a href=\"{}\">{}</a>\n", name, name))
+                                        .collect();
+                                    ("200 OK", listing.into_bytes())
+                                }
+                                "GET" | "HEAD" => match files.get(&path) {
+                                    Some(data) => ("200 OK", data.clone()),
+                                    None => ("404 Not Found", Vec::new()),
+                                },
+                                "DELETE" => match files.remove(&path) {
+                                    Some(_) => ("204 No Content", Vec::new()),
+                                    None => ("404 Not Found", Vec::new()),
+                                },
+                                _ => ("405 Method Not Allowed", Vec::new()),
+                            }
+                        };
+
+                        let mut response = format!(
+                            "HTTP/1.1 {}\r\ncontent-length: {}\r\n\r\n",
+                            status,
+                            payload.len()
+                        )
+                        .into_bytes();
+                        if method != "HEAD" {
+                            response.extend_from_slice(&payload);
+                        }
+                        if write.write_all(&response).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+
+        (format!("http://{}", addr), files)
+    }
+
+    fn test_shard(fill: u8) -> Shard {
+        let header = ShardHeader::new(EncryptionMode::Convergent, (4, 2), 32, [0u8; 32]);
+        Shard::new(header, vec![fill; 32])
+    }
+
+    #[test]
+    fn test_extract_names() {
+        let a = "ab".repeat(32);
+        let b = "cd".repeat(32);
+        let body = format!(
+            "<D:href>/shards/{a}.shard</D:href><a href=\"{b}.shard\">{b}.shard</a><a href=\"x.shard\">"
+        );
+        let names = extract_names(&body, ".shard");
+        assert_eq!(names, vec![[0xab; 32], [0xcd; 32]]);
+    }
+
+    #[tokio::test]
+    async fn test_http_storage_roundtrip() {
+        let (url, files) = start_server("secret").await;
+        let storage = HttpStorage::new(HttpStorageConfig::new(url).with_bearer_token("secret"))
+            .await
+            .unwrap();
+
+        let shard = test_shard(7);
+        let cid = shard.cid().unwrap();
+        storage.put_shard(&cid, &shard).await.unwrap();
+        assert!(files
+            .lock()
+            .unwrap()
+            .contains_key(&format!("/shards/{}.shard", cid.to_hex())));
+
+        assert!(storage.has_shard(&cid).await.unwrap());
+        assert_eq!(storage.get_shard(&cid).await.unwrap().data, shard.data);
+        assert_eq!(storage.list_shards().await.unwrap(), vec![cid]);
+
+        let metadata = FileMetadata::new([1u8; 32], 32, Vec::new());
+        storage.put_metadata(&metadata).await.unwrap();
+        assert_eq!(storage.list_metadata().await.unwrap().len(), 1);
+
+        storage.delete_shard(&cid).await.unwrap();
+        assert!(!storage.has_shard(&cid).await.unwrap());
+        // Deleting twice is fine
+        storage.delete_shard(&cid).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_http_storage_rejects_bad_credentials() {
+        let (url, _files) = start_server("secret").await;
+        let storage = HttpStorage::new(HttpStorageConfig::new(url).with_bearer_token("wrong"))
+            .await
+            .unwrap();
+
+        let shard = test_shard(1);
+        let err = storage
+            .put_shard(&shard.cid().unwrap(), &shard)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("401"));
+    }
+}
@@ -0,0 +1,119 @@
+//! Browser storage backend over IndexedDB
+//!
+//! Needs the `indexeddb` feature and a wasm32 target. [`IndexedDbStore`] is an
+//! [`ObjectStore`] over one IndexedDB object store, keyed by the 32 raw key
+//! bytes with values stored as `Uint8Array`s. [`IndexedDbStore::open`] opens a
+//! database holding the `shards` and `metadata` stores an
+//! [`ObjectStoreStorage`] needs.
+
+use async_trait::async_trait;
+use indexed_db_futures::prelude::*;
+use js_sys::{Array, Uint8Array};
+use std::rc::Rc;
+use wasm_bindgen::JsValue;
+use web_sys::DomException;
+
+use super::{ObjectStore, ObjectStoreStorage};
+use crate::FecError;
+
+const SHARDS: &str = "shards";
+const METADATA: &str = "metadata";
+
+/// Object store backed by one IndexedDB object store
+///
+/// Holds a JavaScript database handle, so it is neither `Send` nor `Sync`;
+/// on wasm32 [`ObjectStore`] does not require either.
+#[derive(Debug, Clone)]
+pub struct IndexedDbStore {
+    db: Rc<IdbDatabase>,
+    store: &'static str,
+}
+
+impl IndexedDbStore {
+    /// Open or create the database `name` with its shard and metadata stores
+    pub async fn open(name: &str) -> Result<ObjectStoreStorage<Self>, FecError> {
+        let mut request = IdbDatabase::open_u32(name, 1).map_err(idb_error)?;
+        request.set_on_upgrade_needed(Some(|event: &IdbVersionChangeEvent| {
+            let existing: Vec<String> = event.db().object_store_names().collect();
+            for store in [SHARDS, METADATA] {
+                if !existing.iter().any(|name| name == store) {
+                    event.db().create_object_store(store)?;
+                }
+            }
+            Ok(())
+        }));
+        let db = Rc::new(request.await.map_err(idb_error)?);
+
+        Ok(ObjectStoreStorage::new(
+            Self {
+                db: db.clone(),
+                store: SHARDS,
+            },
+            Self {
+                db,
+                store: METADATA,
+            },
+        ))
+    }
+
+    /// Run `op` on the store in a read-write transaction and wait for commit
+    async fn write<F>(&self, op: F) -> Result<(), FecError>
+    where
+        F: FnOnce(&IdbObjectStore<'_>) -> Result<(), DomException>,
+    {
+        let tx = self
+            .db
+            .transaction_on_one_with_mode(self.store, IdbTransactionMode::Readwrite)
+            .map_err(idb_error)?;
+        op(&tx.object_store(self.store).map_err(idb_error)?).map_err(idb_error)?;
+        tx.await.into_result().map_err(idb_error)
+    }
+}
+
+#[async_trait(?Send)]
+impl ObjectStore for IndexedDbStore {
+    async fn get(&self, key: &[u8; 32]) -> Result<Option<Vec<u8>>, FecError> {
+        let tx = self.db.transaction_on_one(self.store).map_err(idb_error)?;
+        let store = tx.object_store(self.store).map_err(idb_error)?;
+        let value = store
+            .get_owned(Uint8Array::from(&key[..]))
+            .map_err(idb_error)?
+            .await
+            .map_err(idb_error)?;
+        Ok(value.map(|value| Uint8Array::new(&value).to_vec()))
+    }
+
+    async fn put(&self, key: &[u8; 32], value: Vec<u8>) -> Result<(), FecError> {
+        self.write(|store| {
+            store
+                .put_key_val_owned(Uint8Array::from(&key[..]), &Uint8Array::from(&value[..]))
+                .map(drop)
+        })
+        .await
+    }
+
+    async fn delete(&self, key: &[u8; 32]) -> Result<(), FecError> {
+        self.write(|store| store.delete_owned(Uint8Array::from(&key[..])).map(drop))
+            .await
+    }
+
+    async fn keys(&self) -> Result<Vec<[u8; 32]>, FecError> {
+        let tx = self.db.transaction_on_one(self.store).map_err(idb_error)?;
+        let store = tx.object_store(self.store).map_err(idb_error)?;
+        let keys: Array = store
+            .get_all_keys()
+            .map_err(idb_error)?
+            .await
+            .map_err(idb_error)?;
+        // Binary keys come back as ArrayBuffers; skip any another writer left
+        Ok(keys
+            .iter()
+            .filter_map(|key| Uint8Array::new(&key).to_vec().try_into().ok())
+            .collect())
+    }
+}
+
+fn idb_error(e: impl Into<JsValue>) -> FecError {
+    let e: JsValue = e.into();
+    FecError::Backend(format!("IndexedDB error: {:?}", e))
+}
@@ -0,0 +1,487 @@
+//! Embedded key-value storage backend
+//!
+//! Stores shards and metadata as records in a single [redb] database file,
+//! which avoids the per-file overhead of [`LocalStorage`](super::LocalStorage)
+//! when holding millions of small shards. Writes are transactional, batches
+//! of shards commit in one transaction, and the file can be compacted to
+//! reclaim space freed by deletions.
+
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use redb::{Database, ReadableTable, ReadableTableMetadata, TableDefinition};
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{
+    Cid, FileMetadata, GcReport, Shard, ShardHeader, ShardPage, StorageBackend, StorageStats,
+};
+use crate::FecError;
+
+const SHARDS: TableDefinition<&[u8; 32], &[u8]> = TableDefinition::new("shards");
+const METADATA: TableDefinition<&[u8; 32], &[u8]> = TableDefinition::new("metadata");
+
+/// Storage backend keeping all shards in a single embedded database file
+pub struct KvStorage {
+    /// Compaction needs exclusive access; everything else shares the database
+    db: Arc<RwLock<Database>>,
+}
+
+impl KvStorage {
+    /// Open or create a database at the given path
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, FecError> {
+        let path = path.as_ref().to_path_buf();
+        let db = tokio::task::spawn_blocking(move || -> Result<Database, FecError> {
+            let db = Database::create(path).map_err(kv_error)?;
+            // Create both tables up front so read transactions never miss them
+            let txn = db.begin_write().map_err(kv_error)?;
+            txn.open_table(SHARDS).map_err(kv_error)?;
+            txn.open_table(METADATA).map_err(kv_error)?;
+            txn.commit().map_err(kv_error)?;
+            Ok(db)
+        })
+        .await
+        .map_err(join_error)??;
+
+        Ok(Self {
+            db: Arc::new(RwLock::new(db)),
+        })
+    }
+
+    /// Run a blocking database operation off the async runtime
+    async fn with_db<T, F>(&self, f: F) -> Result<T, FecError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Database) -> Result<T, FecError> + Send + 'static,
+    {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || f(&db.read()))
+            .await
+            .map_err(join_error)?
+    }
+
+    /// Compact the database file, returning whether any space was reclaimed
+    pub async fn compact(&self) -> Result<bool, FecError> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || db.write().compact().map_err(kv_error))
+            .await
+            .map_err(join_error)?
+    }
+
+    /// Insert a record into a table
+    async fn insert(
+        &self,
+        table: TableDefinition<'static, &'static [u8; 32], &'static [u8]>,
+        key: [u8; 32],
+        value: Vec<u8>,
+    ) -> Result<(), FecError> {
+        self.with_db(move |db| {
+            let txn = db.begin_write().map_err(kv_error)?;
+            txn.open_table(table)
+                .map_err(kv_error)?
+                .insert(&key, value.as_slice())
+                .map_err(kv_error)?;
+            txn.commit().map_err(kv_error)
+        })
+        .await
+    }
+
+    /// Fetch a record from a table
+    async fn get(
+        &self,
+        table: TableDefinition<'static, &'static [u8; 32], &'static [u8]>,
+        key: [u8; 32],
+    ) -> Result<Option<Vec<u8>>, FecError> {
+        self.with_db(move |db| {
+            let txn = db.begin_read().map_err(kv_error)?;
+            let table = txn.open_table(table).map_err(kv_error)?;
+            let value = table.get(&key).map_err(kv_error)?;
+            Ok(value.map(|v| v.value().to_vec()))
+        })
+        .await
+    }
+
+    /// Remove a record from a table
+    async fn remove(
+        &self,
+        table: TableDefinition<'static, &'static [u8; 32], &'static [u8]>,
+        key: [u8; 32],
+    ) -> Result<(), FecError> {
+        self.with_db(move |db| {
+            let txn = db.begin_write().map_err(kv_error)?;
+            txn.open_table(table)
+                .map_err(kv_error)?
+                .remove(&key)
+                .map_err(kv_error)?;
+            txn.commit().map_err(kv_error)
+        })
+        .await
+    }
+}
+
+fn kv_error(e: impl Into<redb::Error>) -> FecError {
+    FecError::Backend(format!("Database error: {}", e.into()))
+}
+
+fn join_error(e: tokio::task::JoinError) -> FecError {
+    FecError::Backend(format!("Database task failed: {}", e))
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl StorageBackend for KvStorage {
+    async fn put_shard(&self, cid: &Cid, shard: &Shard) -> Result<(), FecError> {
+        self.insert(SHARDS, *cid.as_bytes(), shard.to_bytes()?)
+            .await
+    }
+
+    async fn get_shard(&self, cid: &Cid) -> Result<Shard, FecError> {
+        let bytes = self
+            .get(SHARDS, *cid.as_bytes())
+            .await?
+            .ok_or_else(|| FecError::ShardNotFound { cid: cid.to_hex() })?;
+        super::reject_expired(cid, Shard::from_bytes(&bytes)?)
+    }
+
+    async fn delete_shard(&self, cid: &Cid) -> Result<(), FecError> {
+        self.remove(SHARDS, *cid.as_bytes()).await
+    }
+
+    async fn has_shard(&self, cid: &Cid) -> Result<bool, FecError> {
+        Ok(self.get(SHARDS, *cid.as_bytes()).await?.is_some())
+    }
+
+    async fn list_shards(&self) -> Result<Vec<Cid>, FecError> {
+        self.with_db(|db| {
+            let txn = db.begin_read().map_err(kv_error)?;
+            let table = txn.open_table(SHARDS).map_err(kv_error)?;
+            let mut cids = Vec::with_capacity(table.len().map_err(kv_error)? as usize);
+            for entry in table.iter().map_err(kv_error)? {
+                let (key, _) = entry.map_err(kv_error)?;
+                cids.push(Cid::new(*key.value()));
+            }
+            Ok(cids)
+        })
+        .await
+    }
+
+    async fn list_shards_paged(
+        &self,
+        after: Option<&Cid>,
+        limit: usize,
+    ) -> Result<ShardPage, FecError> {
+        let after = after.map(|cid| *cid.as_bytes());
+        self.with_db(move |db| {
+            let txn = db.begin_read().map_err(kv_error)?;
+            let table = txn.open_table(SHARDS).map_err(kv_error)?;
+            let range = match &after {
+                Some(after) => table.range::<&[u8; 32]>((Bound::Excluded(after), Bound::Unbounded)),
+                None => table.range::<&[u8; 32]>(..),
+            }
+            .map_err(kv_error)?;
+
+            let mut cids = Vec::new();
+            for entry in range.take(limit + 1) {
+                let (key, _) = entry.map_err(kv_error)?;
+                cids.push(Cid::new(*key.value()));
+            }
+            Ok(ShardPage::from_candidates(cids, limit))
+        })
+        .await
+    }
+
+    async fn put_metadata(&self, metadata: &FileMetadata) -> Result<(), FecError> {
+        let serialized = bincode::serialize(metadata)
+            .map_err(|e| FecError::Backend(format!("Failed to serialize metadata: {}", e)))?;
+        self.insert(METADATA, metadata.file_id, serialized).await
+    }
+
+    async fn get_metadata(&self, file_id: &[u8; 32]) -> Result<FileMetadata, FecError> {
+        let data =
+            self.get(METADATA, *file_id)
+                .await?
+                .ok_or_else(|| FecError::MetadataNotFound {
+                    file_id: hex::encode(file_id),
+                })?;
+        bincode::deserialize(&data)
+            .map_err(|e| FecError::Backend(format!("Failed to deserialize metadata: {}", e)))
+    }
+
+    async fn delete_metadata(&self, file_id: &[u8; 32]) -> Result<(), FecError> {
+        self.remove(METADATA, *file_id).await
+    }
+
+    async fn list_metadata(&self) -> Result<Vec<FileMetadata>, FecError> {
+        self.with_db(|db| {
+            let txn = db.begin_read().map_err(kv_error)?;
+            let table = txn.open_table(METADATA).map_err(kv_error)?;
+            let mut metadata_list = Vec::new();
+            for entry in table.iter().map_err(kv_error)? {
+                let (_, value) = entry.map_err(kv_error)?;
+                if let Ok(metadata) = bincode::deserialize::<FileMetadata>(value.value()) {
+                    metadata_list.push(metadata);
+                }
+            }
+            Ok(metadata_list)
+        })
+        .await
+    }
+
+    async fn stats(&self) -> Result<StorageStats, FecError> {
+        let referenced_cids = super::referenced_cids(&self.list_metadata().await?);
+
+        self.with_db(move |db| {
+            let txn = db.begin_read().map_err(kv_error)?;
+            let shards = txn.open_table(SHARDS).map_err(kv_error)?;
+            let metadata = txn.open_table(METADATA).map_err(kv_error)?;
+
+            let mut total_size = 0u64;
+            let mut unreferenced_shards = 0u64;
+            for entry in shards.iter().map_err(kv_error)? {
+                let (key, value) = entry.map_err(kv_error)?;
+                total_size += value.value().len() as u64;
+                if !referenced_cids.contains(&Cid::new(*key.value())) {
+                    unreferenced_shards += 1;
+                }
+            }
+
+            Ok(StorageStats {
+                total_shards: shards.len().map_err(kv_error)?,
+                total_size,
+                metadata_count: metadata.len().map_err(kv_error)?,
+                unreferenced_shards,
+                capacity: None,
+                free_space: None,
+            })
+        })
+        .await
+    }
+
+    async fn garbage_collect(&self) -> Result<GcReport, FecError> {
+        let start_time = std::time::Instant::now();
+        let referenced_cids = super::referenced_cids(&self.list_metadata().await?);
+
+        // Remove every unreferenced or expired shard in one transaction
+        let (shards_deleted, bytes_freed) = self
+            .with_db(move |db| {
+                let txn = db.begin_write().map_err(kv_error)?;
+                let mut shards_deleted = 0u64;
+                let mut bytes_freed = 0u64;
+                {
+                    let mut table = txn.open_table(SHARDS).map_err(kv_error)?;
+                    table
+                        .retain(|cid, bytes| {
+                            let keep = referenced_cids.contains(&Cid::new(*cid))
+                                && !bytes
+                                    .get(..ShardHeader::SIZE)
+                                    .and_then(|h| ShardHeader::from_bytes(h).ok())
+                                    .is_some_and(|h| h.is_expired());
+                            if !keep {
+                                shards_deleted += 1;
+                                bytes_freed += bytes.len() as u64;
+                            }
+                            keep
+                        })
+                        .map_err(kv_error)?;
+                }
+                txn.commit().map_err(kv_error)?;
+                Ok((shards_deleted, bytes_freed))
+            })
+            .await?;
+
+        Ok(GcReport {
+            shards_deleted,
+            bytes_freed,
+            duration_ms: start_time.elapsed().as_millis() as u64,
+        })
+    }
+
+    async fn put_shards(&self, shards: &[(Cid, Shard)]) -> Result<(), FecError> {
+        let records = shards
+            .iter()
+            .map(|(cid, shard)| Ok((*cid.as_bytes(), shard.to_bytes()?)))
+            .collect::<Result<Vec<_>, FecError>>()?;
+
+        self.with_db(move |db| {
+            let txn = db.begin_write().map_err(kv_error)?;
+            {
+                let mut table = txn.open_table(SHARDS).map_err(kv_error)?;
+                for (cid, bytes) in &records {
+                    table.insert(cid, bytes.as_slice()).map_err(kv_error)?;
+                }
+            }
+            txn.commit().map_err(kv_error)
+        })
+        .await
+    }
+
+    async fn commit_transaction(
+        &self,
+        shards: &[(Cid, Shard)],
+        metadata: &[FileMetadata],
+    ) -> Result<(), FecError> {
+        let shard_records = shards
+            .iter()
+            .map(|(cid, shard)| Ok((*cid.as_bytes(), shard.to_bytes()?)))
+            .collect::<Result<Vec<_>, FecError>>()?;
+        let metadata_records = metadata
+            .iter()
+            .map(|meta| {
+                let serialized = bincode::serialize(meta).map_err(|e| {
+                    FecError::Backend(format!("Failed to serialize metadata: {}", e))
+                })?;
+                Ok((meta.file_id, serialized))
+            })
+            .collect::<Result<Vec<_>, FecError>>()?;
+
+        self.with_db(move |db| {
+            let txn = db.begin_write().map_err(kv_error)?;
+            {
+                let mut table = txn.open_table(SHARDS).map_err(kv_error)?;
+                for (cid, bytes) in &shard_records {
+                    table.insert(cid, bytes.as_slice()).map_err(kv_error)?;
+                }
+                let mut table = txn.open_table(METADATA).map_err(kv_error)?;
+                for (file_id, bytes) in &metadata_records {
+                    table.insert(file_id, bytes.as_slice()).map_err(kv_error)?;
+                }
+            }
+            txn.commit().map_err(kv_error)
+        })
+        .await
+    }
+
+    async fn get_shards(&self, cids: &[Cid]) -> Result<Vec<Option<Shard>>, FecError> {
+        let keys: Vec<[u8; 32]> = cids.iter().map(|cid| *cid.as_bytes()).collect();
+        self.with_db(move |db| {
+            let txn = db.begin_read().map_err(kv_error)?;
+            let table = txn.open_table(SHARDS).map_err(kv_error)?;
+            let mut shards = Vec::with_capacity(keys.len());
+            for key in &keys {
+                let value = table.get(key).map_err(kv_error)?;
+                shards.push(
+                    value
+                        .and_then(|v| Shard::from_bytes(v.value()).ok())
+                        .filter(|s| !s.header.is_expired()),
+                );
+            }
+            Ok(shards)
+        })
+        .await
+    }
+
+    async fn has_shards(&self, cids: &[Cid]) -> Result<Vec<bool>, FecError> {
+        let keys: Vec<[u8; 32]> = cids.iter().map(|cid| *cid.as_bytes()).collect();
+        self.with_db(move |db| {
+            let txn = db.begin_read().map_err(kv_error)?;
+            let table = txn.open_table(SHARDS).map_err(kv_error)?;
+            keys.iter()
+                .map(|key| Ok(table.get(key).map_err(kv_error)?.is_some()))
+                .collect()
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::ChunkMeta;
+    use crate::EncryptionMode;
+    use tempfile::TempDir;
+
+    fn test_shard(fill: u8) -> Shard {
+        let header = ShardHeader::new(EncryptionMode::Convergent, (4, 2), 64, [0u8; 32]);
+        Shard::new(header, vec![fill; 64])
+    }
+
+    #[tokio::test]
+    async fn test_kv_storage_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("shards.redb");
+        let shard = test_shard(3);
+        let cid = shard.cid().unwrap();
+
+        {
+            let storage = KvStorage::open(&path).await.unwrap();
+            storage.put_shard(&cid, &shard).await.unwrap();
+            storage
+                .put_metadata(&FileMetadata::new([1u8; 32], 64, Vec::new()))
+                .await
+                .unwrap();
+        }
+
+        // Data survives reopening the database
+        let storage = KvStorage::open(&path).await.unwrap();
+        assert!(storage.has_shard(&cid).await.unwrap());
+        assert_eq!(storage.get_shard(&cid).await.unwrap().data, shard.data);
+        assert_eq!(storage.list_shards().await.unwrap(), vec![cid]);
+        assert_eq!(
+            storage.get_metadata(&[1u8; 32]).await.unwrap().file_size,
+            64
+        );
+
+        storage.delete_shard(&cid).await.unwrap();
+        assert!(!storage.has_shard(&cid).await.unwrap());
+        assert!(storage.get_shard(&cid).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_kv_storage_batch_gc_and_compact() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = KvStorage::open(temp_dir.path().join("shards.redb"))
+            .await
+            .unwrap();
+
+        let shards: Vec<(Cid, Shard)> = (0..10u8)
+            .map(|i| {
+                let shard = test_shard(i);
+                (shard.cid().unwrap(), shard)
+            })
+            .collect();
+        storage.put_shards(&shards).await.unwrap();
+
+        // Reference only the first shard
+        let chunk = ChunkMeta::new(
+            (4, 2),
+            EncryptionMode::Convergent,
+            vec![shards[0].0.to_hex()],
+        );
+        storage
+            .put_metadata(&FileMetadata::new([2u8; 32], 64, vec![chunk]))
+            .await
+            .unwrap();
+
+        let stats = storage.stats().await.unwrap();
+        assert_eq!(stats.total_shards, 10);
+        assert_eq!(stats.unreferenced_shards, 9);
+
+        let first = storage.list_shards_paged(None, 4).await.unwrap();
+        let rest = storage
+            .list_shards_paged(first.next.as_ref(), 100)
+            .await
+            .unwrap();
+        let mut all = storage.list_shards().await.unwrap();
+        all.sort();
+        assert_eq!([first.cids, rest.cids].concat(), all);
+        assert!(rest.next.is_none());
+
+        let report = storage.garbage_collect().await.unwrap();
+        assert_eq!(report.shards_deleted, 9);
+        assert_eq!(storage.list_shards().await.unwrap(), vec![shards[0].0]);
+
+        storage.compact().await.unwrap();
+        assert!(storage.has_shard(&shards[0].0).await.unwrap());
+
+        // A transaction lands its shard and metadata together
+        let shard = test_shard(42);
+        let cid = shard.cid().unwrap();
+        let chunk = ChunkMeta::new((4, 2), EncryptionMode::Convergent, vec![cid.to_hex()]);
+        let mut txn = storage.begin();
+        txn.put_shard(cid, shard)
+            .put_metadata(FileMetadata::new([3u8; 32], 64, vec![chunk]));
+        txn.commit().await.unwrap();
+        assert!(storage.has_shard(&cid).await.unwrap());
+        assert!(storage.get_metadata(&[3u8; 32]).await.is_ok());
+    }
+}
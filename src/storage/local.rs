@@ -0,0 +1,807 @@
+//! Local filesystem storage backend
+//!
+//! Needs the `native` feature; wasm32 builds have no filesystem to write to.
+
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::fs;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+
+use super::durability::{self, DurabilityPolicy, SyncPolicy};
+use super::{
+    reject_expired, Cid, FileMetadata, GcReport, Shard, ShardHeader, ShardPage, ShardReader,
+    StorageBackend, StorageStats,
+};
+use crate::FecError;
+
+/// Local filesystem storage implementation
+/// Stores shards and metadata on local filesystem with CID-based addressing
+pub struct LocalStorage {
+    /// Base directory for shard storage
+    base_path: PathBuf,
+    /// Directory for metadata storage
+    metadata_path: PathBuf,
+    /// Number of directory levels for sharding
+    shard_levels: usize,
+    /// When writes are flushed to stable storage
+    durability: DurabilityPolicy,
+    /// Files written but not yet synced under [`SyncPolicy::Batched`]
+    pub(super) pending_sync: Mutex<Vec<PathBuf>>,
+    /// Overwrite files with random data before deleting them
+    secure_delete: bool,
+}
+
+impl LocalStorage {
+    /// Directory levels used when a store has no recorded layout
+    const DEFAULT_SHARD_LEVELS: usize = 2;
+
+    /// Deepest supported fan-out (each level consumes two hex characters)
+    const MAX_SHARD_LEVELS: usize = 8;
+
+    /// Create a new local storage backend
+    ///
+    /// An existing store is opened with the fan-out it was written with.
+    pub async fn new(base_path: PathBuf) -> Result<Self, FecError> {
+        let metadata_path = base_path.join("metadata");
+
+        fs::create_dir_all(&base_path).await.map_err(FecError::Io)?;
+        fs::create_dir_all(&metadata_path)
+            .await
+            .map_err(FecError::Io)?;
+
+        let shard_levels = match fs::read_to_string(base_path.join("layout")).await {
+            Ok(layout) => layout.trim().parse().map_err(|_| {
+                FecError::Backend(format!("Invalid storage layout: {:?}", layout.trim()))
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::DEFAULT_SHARD_LEVELS,
+            Err(e) => return Err(FecError::Io(e)),
+        };
+
+        let storage = Self {
+            base_path,
+            metadata_path,
+            shard_levels,
+            durability: DurabilityPolicy::default(),
+            pending_sync: Mutex::new(Vec::new()),
+            secure_delete: false,
+        };
+        storage.recover_journal().await?;
+        Ok(storage)
+    }
+
+    /// Set when writes are flushed to stable storage
+    pub fn with_durability(mut self, durability: DurabilityPolicy) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// The durability policy applied to writes
+    pub fn durability(&self) -> DurabilityPolicy {
+        self.durability
+    }
+
+    /// Overwrite shard and metadata files with random data before deleting them
+    pub fn with_secure_delete(mut self, secure_delete: bool) -> Self {
+        self.secure_delete = secure_delete;
+        self
+    }
+
+    /// Remove a file, erasing its contents first if secure delete is enabled
+    async fn remove(&self, path: &Path) -> Result<(), FecError> {
+        if !path.exists() {
+            return Ok(());
+        }
+        if self.secure_delete {
+            durability::erase_file(path).await?;
+        }
+        fs::remove_file(path).await.map_err(FecError::Io)
+    }
+
+    /// Sync every write still pending under [`SyncPolicy::Batched`]
+    pub async fn flush(&self) -> Result<(), FecError> {
+        let pending = {
+            let mut pending = match self.pending_sync.lock() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            std::mem::take(&mut *pending)
+        };
+        durability::sync_files(pending, self.durability.sync_dir).await
+    }
+
+    /// Directory holding staged transaction files and commit records
+    pub(super) fn journal_dir(&self) -> PathBuf {
+        self.base_path.join("journal")
+    }
+
+    /// Finish transactions that committed before a crash and discard the rest
+    ///
+    /// A transaction is committed once its `.commit` record exists; staging
+    /// directories without one belong to transactions that never got there.
+    async fn recover_journal(&self) -> Result<(), FecError> {
+        let journal_dir = self.journal_dir();
+        if !journal_dir.exists() {
+            return Ok(());
+        }
+
+        let mut records = Vec::new();
+        let mut entries = fs::read_dir(&journal_dir).await.map_err(FecError::Io)?;
+        while let Some(entry) = entries.next_entry().await.map_err(FecError::Io)? {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "commit") {
+                records.push(path);
+            }
+        }
+        for record in records {
+            tracing::info!("Replaying committed transaction {:?}", record);
+            self.apply_journal(&record).await?;
+        }
+
+        // Whatever is left was never committed
+        let mut entries = fs::read_dir(&journal_dir).await.map_err(FecError::Io)?;
+        while let Some(entry) = entries.next_entry().await.map_err(FecError::Io)? {
+            let path = entry.path();
+            if path.is_dir() {
+                fs::remove_dir_all(&path).await.map_err(FecError::Io)?;
+            } else {
+                fs::remove_file(&path).await.map_err(FecError::Io)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Move every staged file named in a commit record into place
+    ///
+    /// Safe to repeat: files already moved are skipped.
+    async fn apply_journal(&self, record: &Path) -> Result<(), FecError> {
+        let data = fs::read(record).await.map_err(FecError::Io)?;
+        let moves: Vec<(String, String)> = serde_json::from_slice(&data)
+            .map_err(|e| FecError::Backend(format!("Invalid journal record: {}", e)))?;
+
+        for (staged, target) in &moves {
+            let (staged, target) = (self.base_path.join(staged), self.base_path.join(target));
+            if staged.exists() {
+                self.ensure_parent(&target).await?;
+                self.commit(&staged, &target).await?;
+            }
+        }
+
+        fs::remove_file(record).await.map_err(FecError::Io)?;
+        let _ = fs::remove_dir_all(record.with_extension("")).await;
+        Ok(())
+    }
+
+    /// Open a local storage backend with the given directory fan-out
+    ///
+    /// Shards already stored under a different fan-out are migrated first.
+    pub async fn with_shard_levels(
+        base_path: PathBuf,
+        shard_levels: usize,
+    ) -> Result<Self, FecError> {
+        let mut storage = Self::new(base_path).await?;
+        if storage.shard_levels != shard_levels {
+            storage.migrate_layout(shard_levels).await?;
+        }
+        Ok(storage)
+    }
+
+    /// Number of directory levels shards are spread over
+    pub fn shard_levels(&self) -> usize {
+        self.shard_levels
+    }
+
+    /// Move every shard file to its location under a new directory fan-out
+    ///
+    /// Each file is moved with a rename, so shards are never copied or lost.
+    /// An interrupted migration is completed by calling this again with the
+    /// same `shard_levels`. Returns the number of shard files moved.
+    pub async fn migrate_layout(&mut self, shard_levels: usize) -> Result<u64, FecError> {
+        if shard_levels > Self::MAX_SHARD_LEVELS {
+            return Err(FecError::Backend(format!(
+                "Shard levels must be at most {}, got {}",
+                Self::MAX_SHARD_LEVELS,
+                shard_levels
+            )));
+        }
+
+        self.shard_levels = shard_levels;
+        let mut moved = 0;
+        for (cid, path) in self.shard_files().await? {
+            let target = self.shard_path(&cid);
+            if path != target {
+                self.ensure_parent(&target).await?;
+                fs::rename(&path, &target).await.map_err(FecError::Io)?;
+                moved += 1;
+            }
+        }
+        self.prune_empty_dirs().await?;
+
+        // Record the layout last so a reopened store never points at
+        // directories that have not been populated yet
+        let layout_path = self.base_path.join("layout");
+        let temp_path = layout_path.with_extension("tmp");
+        fs::write(&temp_path, format!("{}\n", shard_levels))
+            .await
+            .map_err(FecError::Io)?;
+        fs::rename(temp_path, layout_path)
+            .await
+            .map_err(FecError::Io)?;
+
+        Ok(moved)
+    }
+
+    /// Every shard file under the shards directory, whatever its depth
+    async fn shard_files(&self) -> Result<Vec<(Cid, PathBuf)>, FecError> {
+        let mut shards = Vec::new();
+        let shards_dir = self.base_path.join("shards");
+
+        // Walk directory tree
+        let mut stack = vec![shards_dir];
+
+        while let Some(dir) = stack.pop() {
+            if !dir.exists() {
+                continue;
+            }
+
+            let mut entries = fs::read_dir(&dir).await.map_err(|e| {
+                FecError::Backend(format!("Failed to read directory {:?}: {}", dir, e))
+            })?;
+
+            while let Some(entry) = entries.next_entry().await.map_err(FecError::Io)? {
+                let path = entry.path();
+
+                if path.is_dir() {
+                    stack.push(path);
+                } else if let Some(name) = path.file_name() {
+                    if let Some(name_str) = name.to_str() {
+                        if name_str.ends_with(".shard") {
+                            // Extract hex CID from filename
+                            let hex = name_str.trim_end_matches(".shard");
+                            if let Ok(cid_bytes) = hex::decode(hex) {
+                                if cid_bytes.len() == 32 {
+                                    let mut cid_array = [0u8; 32];
+                                    cid_array.copy_from_slice(&cid_bytes);
+                                    shards.push((Cid::new(cid_array), path));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(shards)
+    }
+
+    /// Remove fan-out directories left empty, deepest first
+    async fn prune_empty_dirs(&self) -> Result<(), FecError> {
+        let shards_dir = self.base_path.join("shards");
+        let mut dirs = Vec::new();
+        let mut stack = vec![shards_dir];
+        while let Some(dir) = stack.pop() {
+            if !dir.exists() {
+                continue;
+            }
+            let mut entries = fs::read_dir(&dir).await.map_err(FecError::Io)?;
+            while let Some(entry) = entries.next_entry().await.map_err(FecError::Io)? {
+                let path = entry.path();
+                if path.is_dir() {
+                    dirs.push(path.clone());
+                    stack.push(path);
+                }
+            }
+        }
+
+        dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+        for dir in dirs {
+            // Fails harmlessly on directories that still hold shards
+            let _ = fs::remove_dir(dir).await;
+        }
+        Ok(())
+    }
+
+    /// Get the path for a shard based on its CID
+    pub(super) fn shard_path(&self, cid: &Cid) -> PathBuf {
+        let hex = cid.to_hex();
+
+        // Create sharded path (e.g., ab/cd/abcdef...)
+        let mut path = self.base_path.join("shards");
+
+        for level in 0..self.shard_levels {
+            if hex.len() > level * 2 + 2 {
+                path = path.join(&hex[level * 2..level * 2 + 2]);
+            }
+        }
+
+        path.join(format!("{}.shard", hex))
+    }
+
+    /// Get the path for file metadata
+    fn metadata_file_path(&self, file_id: &[u8; 32]) -> PathBuf {
+        let hex = hex::encode(file_id);
+        self.metadata_path.join(format!("{}.meta", hex))
+    }
+
+    /// Read a shard file, whether or not it has expired
+    async fn read_shard(&self, cid: &Cid) -> Result<Shard, FecError> {
+        let path = self.shard_path(cid);
+
+        let mut file = fs::File::open(&path).await.map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => FecError::ShardNotFound { cid: cid.to_hex() },
+            _ => FecError::Backend(format!("Failed to open shard file {:?}: {}", path, e)),
+        })?;
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).await.map_err(FecError::Io)?;
+
+        Shard::from_bytes(&data)
+    }
+
+    /// Read only the header of a shard file
+    async fn read_header(&self, cid: &Cid) -> Result<ShardHeader, FecError> {
+        let mut file = fs::File::open(self.shard_path(cid))
+            .await
+            .map_err(FecError::Io)?;
+        let mut header_bytes = [0u8; ShardHeader::SIZE];
+        file.read_exact(&mut header_bytes)
+            .await
+            .map_err(FecError::Io)?;
+        ShardHeader::from_bytes(&header_bytes)
+    }
+
+    /// Write a file via a temporary path, honouring the durability policy
+    async fn write_atomic(
+        &self,
+        path: &Path,
+        bytes: &[u8],
+        direct_io: bool,
+    ) -> Result<(), FecError> {
+        let temp_path = path.with_extension("tmp");
+        let sync = self.durability.sync == SyncPolicy::Always;
+        let direct_io = direct_io && self.durability.direct_io;
+        if let Err(e) = durability::write_file(&temp_path, bytes, sync, direct_io).await {
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(e);
+        }
+        self.commit(&temp_path, path).await
+    }
+
+    /// Rename a written temporary file into place and sync per the policy
+    async fn commit(&self, temp_path: &Path, path: &Path) -> Result<(), FecError> {
+        fs::rename(temp_path, path).await.map_err(FecError::Io)?;
+
+        match self.durability.sync {
+            SyncPolicy::Always => {
+                if let (true, Some(parent)) = (self.durability.sync_dir, path.parent()) {
+                    durability::sync_dir(parent).await?;
+                }
+            }
+            SyncPolicy::Batched { max_pending } => {
+                let ready = {
+                    let mut pending = match self.pending_sync.lock() {
+                        Ok(guard) => guard,
+                        Err(poisoned) => poisoned.into_inner(),
+                    };
+                    pending.push(path.to_path_buf());
+                    (pending.len() >= max_pending).then(|| std::mem::take(&mut *pending))
+                };
+                if let Some(paths) = ready {
+                    durability::sync_files(paths, self.durability.sync_dir).await?;
+                }
+            }
+            SyncPolicy::Never => {}
+        }
+        Ok(())
+    }
+
+    /// Ensure parent directory exists
+    async fn ensure_parent(&self, path: &Path) -> Result<(), FecError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await.map_err(FecError::Io)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl StorageBackend for LocalStorage {
+    async fn put_shard(&self, cid: &Cid, shard: &Shard) -> Result<(), FecError> {
+        let path = self.shard_path(cid);
+
+        // Ensure parent directory exists
+        self.ensure_parent(&path).await?;
+
+        // Serialize shard to bytes
+        let shard_bytes = shard.to_bytes()?;
+
+        // Write shard atomically using temp file
+        self.write_atomic(&path, &shard_bytes, true).await
+    }
+
+    async fn get_shard(&self, cid: &Cid) -> Result<Shard, FecError> {
+        reject_expired(cid, self.read_shard(cid).await?)
+    }
+
+    async fn delete_shard(&self, cid: &Cid) -> Result<(), FecError> {
+        self.remove(&self.shard_path(cid)).await
+    }
+
+    async fn has_shard(&self, cid: &Cid) -> Result<bool, FecError> {
+        let path = self.shard_path(cid);
+        Ok(path.exists())
+    }
+
+    async fn list_shards(&self) -> Result<Vec<Cid>, FecError> {
+        Ok(self
+            .shard_files()
+            .await?
+            .into_iter()
+            .map(|(cid, _)| cid)
+            .collect())
+    }
+
+    async fn put_metadata(&self, metadata: &FileMetadata) -> Result<(), FecError> {
+        let path = self.metadata_file_path(&metadata.file_id);
+
+        let serialized = bincode::serialize(metadata)
+            .map_err(|e| FecError::Backend(format!("Failed to serialize metadata: {}", e)))?;
+
+        // Write metadata atomically using temp file
+        self.write_atomic(&path, &serialized, false).await
+    }
+
+    async fn get_metadata(&self, file_id: &[u8; 32]) -> Result<FileMetadata, FecError> {
+        let path = self.metadata_file_path(file_id);
+
+        let data = fs::read(&path).await.map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => FecError::MetadataNotFound {
+                file_id: hex::encode(file_id),
+            },
+            _ => FecError::Backend(format!("Failed to read metadata file {:?}: {}", path, e)),
+        })?;
+
+        bincode::deserialize(&data)
+            .map_err(|e| FecError::Backend(format!("Failed to deserialize metadata: {}", e)))
+    }
+
+    async fn delete_metadata(&self, file_id: &[u8; 32]) -> Result<(), FecError> {
+        self.remove(&self.metadata_file_path(file_id)).await
+    }
+
+    async fn list_metadata(&self) -> Result<Vec<FileMetadata>, FecError> {
+        let mut metadata_list = Vec::new();
+
+        let mut entries = fs::read_dir(&self.metadata_path)
+            .await
+            .map_err(FecError::Io)?;
+
+        while let Some(entry) = entries.next_entry().await.map_err(FecError::Io)? {
+            let path = entry.path();
+            if let Some(name) = path.file_name() {
+                if let Some(name_str) = name.to_str() {
+                    if name_str.ends_with(".meta") {
+                        let data = fs::read(&path).await.map_err(FecError::Io)?;
+                        if let Ok(metadata) = bincode::deserialize::<FileMetadata>(&data) {
+                            metadata_list.push(metadata);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(metadata_list)
+    }
+    async fn stats(&self) -> Result<StorageStats, FecError> {
+        let shards = self.list_shards().await?;
+        let metadata = self.list_metadata().await?;
+
+        // Calculate total size by reading all shards
+        let mut total_size = 0u64;
+        for cid in &shards {
+            if let Ok(shard) = self.read_shard(cid).await {
+                total_size += shard.data.len() as u64 + ShardHeader::SIZE as u64;
+            }
+        }
+
+        // Count unreferenced shards (shards not referenced in any metadata)
+        let mut referenced_cids = std::collections::HashSet::new();
+        for meta in &metadata {
+            for chunk in &meta.chunks {
+                for shard_id in &chunk.shard_ids {
+                    if let Ok(cid_bytes) = hex::decode(shard_id) {
+                        if cid_bytes.len() == 32 {
+                            let mut cid_array = [0u8; 32];
+                            cid_array.copy_from_slice(&cid_bytes);
+                            referenced_cids.insert(Cid::new(cid_array));
+                        }
+                    }
+                }
+            }
+        }
+
+        let unreferenced_shards = shards
+            .iter()
+            .filter(|cid| !referenced_cids.contains(cid))
+            .count() as u64;
+
+        // Capacity comes from the filesystem holding the store
+        let base_path = self.base_path.clone();
+        let fs_stats = tokio::task::spawn_blocking(move || fs4::statvfs(base_path))
+            .await
+            .map_err(|e| FecError::Backend(format!("Filesystem stats task failed: {}", e)))?;
+        let (capacity, free_space) = match fs_stats {
+            Ok(fs_stats) => (
+                Some(fs_stats.total_space()),
+                Some(fs_stats.available_space()),
+            ),
+            Err(e) => {
+                tracing::debug!("Filesystem stats unavailable: {}", e);
+                (None, None)
+            }
+        };
+
+        Ok(StorageStats {
+            total_shards: shards.len() as u64,
+            total_size,
+            metadata_count: metadata.len() as u64,
+            unreferenced_shards,
+            capacity,
+            free_space,
+        })
+    }
+
+    async fn garbage_collect(&self) -> Result<GcReport, FecError> {
+        let start_time = std::time::Instant::now();
+        let mut shards_deleted = 0u64;
+        let mut bytes_freed = 0u64;
+
+        // Get all shards and metadata
+        let shards = self.list_shards().await?;
+        let metadata = self.list_metadata().await?;
+
+        // Build set of referenced shards
+        let mut referenced_cids = std::collections::HashSet::new();
+        for meta in &metadata {
+            for chunk in &meta.chunks {
+                for shard_id in &chunk.shard_ids {
+                    if let Ok(cid_bytes) = hex::decode(shard_id) {
+                        if cid_bytes.len() == 32 {
+                            let mut cid_array = [0u8; 32];
+                            cid_array.copy_from_slice(&cid_bytes);
+                            referenced_cids.insert(Cid::new(cid_array));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Delete unreferenced and expired shards
+        for cid in shards {
+            if referenced_cids.contains(&cid)
+                && !self.read_header(&cid).await.is_ok_and(|h| h.is_expired())
+            {
+                continue;
+            }
+            if let Ok(shard) = self.read_shard(&cid).await {
+                let shard_size = shard.data.len() as u64 + ShardHeader::SIZE as u64;
+                if self.delete_shard(&cid).await.is_ok() {
+                    shards_deleted += 1;
+                    bytes_freed += shard_size;
+                }
+            }
+        }
+
+        let duration_ms = start_time.elapsed().as_millis() as u64;
+
+        Ok(GcReport {
+            shards_deleted,
+            bytes_freed,
+            duration_ms,
+        })
+    }
+
+    async fn list_shards_paged(
+        &self,
+        after: Option<&Cid>,
+        limit: usize,
+    ) -> Result<ShardPage, FecError> {
+        let after = after.map(Cid::to_hex).unwrap_or_default();
+        let mut cids = Vec::new();
+
+        // The fan-out directories are named by CID prefix, so visiting them in
+        // sorted order yields CIDs in ascending order and lets whole
+        // directories before the cursor be skipped.
+        let mut stack = vec![(self.base_path.join("shards"), String::new())];
+        while let Some((dir, prefix)) = stack.pop() {
+            if !dir.exists() {
+                continue;
+            }
+
+            let mut entries = Vec::new();
+            let mut read_dir = fs::read_dir(&dir).await.map_err(|e| {
+                FecError::Backend(format!("Failed to read directory {:?}: {}", dir, e))
+            })?;
+            while let Some(entry) = read_dir.next_entry().await.map_err(FecError::Io)? {
+                if let Ok(name) = entry.file_name().into_string() {
+                    entries.push((name, entry.path()));
+                }
+            }
+            entries.sort();
+
+            let mut subdirs = Vec::new();
+            for (name, path) in entries {
+                if path.is_dir() {
+                    let child = format!("{}{}", prefix, name);
+                    let bound = &after[..child.len().min(after.len())];
+                    if child.as_str() >= bound {
+                        subdirs.push((path, child));
+                    }
+                } else if let Some(hex) = name.strip_suffix(".shard") {
+                    let mut cid = [0u8; 32];
+                    if hex > after.as_str() && hex::decode_to_slice(hex, &mut cid).is_ok() {
+                        cids.push(Cid::new(cid));
+                        if cids.len() > limit {
+                            return Ok(ShardPage::from_candidates(cids, limit));
+                        }
+                    }
+                }
+            }
+            stack.extend(subdirs.into_iter().rev());
+        }
+
+        Ok(ShardPage::from_candidates(cids, limit))
+    }
+
+    // Files are staged in the journal, then a commit record is written; only
+    // once that record exists are they renamed into place, and recovery on
+    // open replays any record a crash interrupted.
+    async fn commit_transaction(
+        &self,
+        shards: &[(Cid, Shard)],
+        metadata: &[FileMetadata],
+    ) -> Result<(), FecError> {
+        let txid = format!("{:016x}", rand::random::<u64>());
+        let staging = self.journal_dir().join(&txid);
+        fs::create_dir_all(&staging).await.map_err(FecError::Io)?;
+
+        let sync = self.durability.sync != SyncPolicy::Never;
+        let relative = |path: &Path| -> String {
+            path.strip_prefix(&self.base_path)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .into_owned()
+        };
+
+        let record = self.journal_dir().join(format!("{}.commit", txid));
+        let staged = async {
+            let mut moves = Vec::with_capacity(shards.len() + metadata.len());
+            for (index, (cid, shard)) in shards.iter().enumerate() {
+                let staged = staging.join(format!("{}.shard", index));
+                durability::write_file(
+                    &staged,
+                    &shard.to_bytes()?,
+                    sync,
+                    self.durability.direct_io,
+                )
+                .await?;
+                moves.push((relative(&staged), relative(&self.shard_path(cid))));
+            }
+            for (index, meta) in metadata.iter().enumerate() {
+                let serialized = bincode::serialize(meta).map_err(|e| {
+                    FecError::Backend(format!("Failed to serialize metadata: {}", e))
+                })?;
+                let staged = staging.join(format!("{}.meta", index));
+                durability::write_file(&staged, &serialized, sync, false).await?;
+                moves.push((
+                    relative(&staged),
+                    relative(&self.metadata_file_path(&meta.file_id)),
+                ));
+            }
+
+            // Writing the commit record is the commit point
+            let manifest = serde_json::to_vec(&moves)
+                .map_err(|e| FecError::Backend(format!("Failed to write journal: {}", e)))?;
+            let temp_record = record.with_extension("tmp");
+            durability::write_file(&temp_record, &manifest, sync, false).await?;
+            fs::rename(&temp_record, &record)
+                .await
+                .map_err(FecError::Io)?;
+            if sync {
+                durability::sync_dir(&self.journal_dir()).await?;
+            }
+            Ok(())
+        }
+        .await;
+
+        if let Err(e) = staged {
+            let _ = fs::remove_dir_all(&staging).await;
+            let _ = fs::remove_file(record.with_extension("tmp")).await;
+            return Err(e);
+        }
+        self.apply_journal(&record).await
+    }
+
+    async fn put_shard_stream(
+        &self,
+        cid: &Cid,
+        header: &ShardHeader,
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        len: u64,
+    ) -> Result<(), FecError> {
+        let path = self.shard_path(cid);
+        self.ensure_parent(&path).await?;
+
+        let temp_path = path.with_extension("tmp");
+        let result = async {
+            let header_bytes = header.to_bytes()?;
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(&header_bytes);
+
+            let mut file = fs::File::create(&temp_path).await.map_err(FecError::Io)?;
+            file.write_all(&header_bytes).await.map_err(FecError::Io)?;
+
+            // Copy the payload in bounded pieces, hashing as we go
+            let mut reader = reader.take(len);
+            let mut buf = vec![0u8; 64 * 1024];
+            let mut written = 0u64;
+            loop {
+                let n = reader.read(&mut buf).await.map_err(FecError::Io)?;
+                if n == 0 {
+                    break;
+                }
+                hasher.update(&buf[..n]);
+                file.write_all(&buf[..n]).await.map_err(FecError::Io)?;
+                written += n as u64;
+            }
+
+            if written != len {
+                return Err(FecError::SizeMismatch {
+                    expected: len as usize,
+                    actual: written as usize,
+                });
+            }
+            if Cid::from(hasher.finalize()) != *cid {
+                return Err(FecError::Backend(format!(
+                    "Streamed shard does not match CID {}",
+                    cid.to_hex()
+                )));
+            }
+            if self.durability.sync == SyncPolicy::Always {
+                file.sync_all().await.map_err(FecError::Io)?;
+            }
+            Ok(())
+        }
+        .await;
+
+        match result {
+            Ok(()) => self.commit(&temp_path, &path).await,
+            Err(e) => {
+                let _ = fs::remove_file(&temp_path).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn get_shard_stream(&self, cid: &Cid) -> Result<(ShardHeader, ShardReader), FecError> {
+        let path = self.shard_path(cid);
+        let mut file = fs::File::open(&path).await.map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => FecError::ShardNotFound { cid: cid.to_hex() },
+            _ => FecError::Backend(format!("Failed to open shard file {:?}: {}", path, e)),
+        })?;
+
+        let mut header_bytes = [0u8; ShardHeader::SIZE];
+        file.read_exact(&mut header_bytes)
+            .await
+            .map_err(FecError::Io)?;
+        let header = ShardHeader::from_bytes(&header_bytes)?;
+        if header.is_expired() {
+            return Err(FecError::Backend(format!(
+                "Shard expired: {}",
+                cid.to_hex()
+            )));
+        }
+
+        Ok((header, Box::new(tokio::io::BufReader::new(file))))
+    }
+}
@@ -0,0 +1,3300 @@
+//! Storage backend abstraction for shard storage
+//!
+//! This module provides a trait for different storage implementations
+//! (local filesystem, memory, network, multi-backend) that work with
+//! the v0.3 shard format with 96-byte headers and CID-based addressing.
+
+use crate::config::EncryptionMode;
+use crate::network::{NodeTransport, Request, Response};
+use crate::FecError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+mod cached;
+pub use cached::{CacheStats, CachedStorage};
+mod compressed;
+pub use compressed::CompressedStorage;
+mod durability;
+pub(crate) use durability::sync_dir_blocking;
+pub use durability::{DurabilityPolicy, SyncPolicy};
+mod factory;
+pub use factory::BackendFactory;
+mod health;
+use health::HealthTracker;
+pub use health::{HealthPolicy, NodeHealth};
+#[cfg(feature = "http")]
+mod http;
+#[cfg(feature = "http")]
+pub use http::{HttpStorage, HttpStorageConfig};
+#[cfg(all(feature = "indexeddb", target_arch = "wasm32"))]
+mod indexed_db;
+#[cfg(all(feature = "indexeddb", target_arch = "wasm32"))]
+pub use indexed_db::IndexedDbStore;
+#[cfg(feature = "native")]
+mod local;
+#[cfg(feature = "native")]
+pub use local::LocalStorage;
+mod object_store;
+pub use object_store::{MemoryObjectStore, ObjectStore, ObjectStoreStorage};
+#[cfg(feature = "redb")]
+mod kv;
+#[cfg(feature = "redb")]
+pub use kv::KvStorage;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStorage;
+#[cfg(feature = "native")]
+mod repair;
+#[cfg(feature = "native")]
+pub use repair::StorageRepairHooks;
+mod throttled;
+pub use throttled::ThrottledStorage;
+mod transaction;
+pub use transaction::Transaction;
+
+/// Content Identifier (CID) for addressing shards
+/// Uses BLAKE3 hash for content-addressable storage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Cid([u8; 32]);
+
+impl Cid {
+    /// Create CID from raw bytes
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Create CID from data using BLAKE3
+    pub fn from_data(data: &[u8]) -> Self {
+        let hash = blake3::hash(data);
+        Self(*hash.as_bytes())
+    }
+
+    /// Get raw bytes
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Convert to hex string
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0)
+    }
+}
+
+impl From<[u8; 32]> for Cid {
+    fn from(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<blake3::Hash> for Cid {
+    fn from(hash: blake3::Hash) -> Self {
+        Self(*hash.as_bytes())
+    }
+}
+
+/// Shard header (106 bytes after bincode serialization) for v0.3
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShardHeader {
+    /// Shard format version
+    pub version: u8,
+    /// Encryption mode used
+    pub encryption_mode: EncryptionMode,
+    /// FEC parameters (k, n-k)
+    pub nspec: (u8, u8),
+    /// Encrypted data size
+    pub data_size: u32,
+    /// Nonce for encryption (32 bytes)
+    pub nonce: [u8; 32],
+    /// Reserved bytes for future use
+    #[serde(with = "serde_bytes")]
+    pub reserved: Vec<u8>,
+}
+
+impl ShardHeader {
+    const SIZE: usize = 106; // Actual bincode serialization size
+
+    /// Flag in `reserved[0]` marking that `reserved[1..9]` holds an expiry
+    const FLAG_EXPIRY: u8 = 0x01;
+    /// Flag in `reserved[0]` marking an unencrypted payload
+    const FLAG_PLAINTEXT: u8 = 0x02;
+
+    /// Create new shard header
+    pub fn new(
+        encryption_mode: EncryptionMode,
+        nspec: (u8, u8),
+        data_size: u32,
+        nonce: [u8; 32],
+    ) -> Self {
+        Self {
+            version: 1,
+            encryption_mode,
+            nspec,
+            data_size,
+            nonce,
+            reserved: vec![0u8; 55],
+        }
+    }
+
+    /// Set the time (seconds since the Unix epoch) after which the shard expires
+    ///
+    /// The expiry lives in the reserved bytes, so it is covered by the CID.
+    pub fn with_expiry(mut self, expires_at: u64) -> Self {
+        self.reserved.resize(55, 0);
+        self.reserved[0] |= Self::FLAG_EXPIRY;
+        self.reserved[1..9].copy_from_slice(&expires_at.to_le_bytes());
+        self
+    }
+
+    /// Expiry time in seconds since the Unix epoch, if one is set
+    pub fn expires_at(&self) -> Option<u64> {
+        if self.reserved.len() < 9 || self.reserved[0] & Self::FLAG_EXPIRY == 0 {
+            return None;
+        }
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&self.reserved[1..9]);
+        Some(u64::from_le_bytes(bytes))
+    }
+
+    /// Mark the payload as unencrypted, overriding the encryption mode
+    ///
+    /// Like the expiry, the flag is covered by the CID.
+    pub fn with_plaintext(mut self) -> Self {
+        self.reserved.resize(55, 0);
+        self.reserved[0] |= Self::FLAG_PLAINTEXT;
+        self
+    }
+
+    /// Whether the payload is unencrypted data
+    pub fn is_plaintext(&self) -> bool {
+        self.reserved
+            .first()
+            .is_some_and(|flags| flags & Self::FLAG_PLAINTEXT != 0)
+    }
+
+    /// Whether the shard's expiry time has passed
+    pub fn is_expired(&self) -> bool {
+        self.expires_at().is_some_and(|expires_at| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|now| now.as_secs() >= expires_at)
+                .unwrap_or(false)
+        })
+    }
+
+    /// Serialize to bytes
+    pub fn to_bytes(&self) -> Result<[u8; Self::SIZE], FecError> {
+        bincode::serialize(self)
+            .map_err(|e| FecError::Backend(format!("Failed to serialize header: {}", e)))
+            .and_then(|bytes| {
+                if bytes.len() == Self::SIZE {
+                    let mut result = [0u8; Self::SIZE];
+                    result.copy_from_slice(&bytes);
+                    Ok(result)
+                } else {
+                    Err(FecError::Backend(format!(
+                        "Header size mismatch: expected {}, got {}",
+                        Self::SIZE,
+                        bytes.len()
+                    )))
+                }
+            })
+    }
+
+    /// Deserialize from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FecError> {
+        if bytes.len() != Self::SIZE {
+            return Err(FecError::Backend(format!(
+                "Invalid header size: expected {}, got {}",
+                Self::SIZE,
+                bytes.len()
+            )));
+        }
+        bincode::deserialize(bytes)
+            .map_err(|e| FecError::Backend(format!("Failed to deserialize header: {}", e)))
+    }
+}
+
+/// Complete shard with header and encrypted data
+#[derive(Debug, Clone)]
+pub struct Shard {
+    /// 96-byte header
+    pub header: ShardHeader,
+    /// Encrypted data payload
+    pub data: Vec<u8>,
+}
+
+impl Shard {
+    /// Create new shard
+    pub fn new(header: ShardHeader, data: Vec<u8>) -> Self {
+        Self { header, data }
+    }
+
+    /// Get CID for this shard (computed over header + data)
+    pub fn cid(&self) -> Result<Cid, FecError> {
+        let header_bytes = self.header.to_bytes()?;
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&header_bytes);
+        hasher.update(&self.data);
+        Ok(Cid::from(hasher.finalize()))
+    }
+
+    /// Check that this shard's content hashes to `cid`
+    pub fn verify(&self, cid: &Cid) -> Result<(), FecError> {
+        let actual = self.cid()?;
+        if actual == *cid {
+            Ok(())
+        } else {
+            Err(FecError::CorruptShard {
+                cid: cid.to_hex(),
+                actual: actual.to_hex(),
+            })
+        }
+    }
+
+    /// Serialize shard to bytes (header + data)
+    pub fn to_bytes(&self) -> Result<Vec<u8>, FecError> {
+        let header_bytes = self.header.to_bytes()?;
+        let mut result = Vec::with_capacity(ShardHeader::SIZE + self.data.len());
+        result.extend_from_slice(&header_bytes);
+        result.extend_from_slice(&self.data);
+        Ok(result)
+    }
+
+    /// Deserialize shard from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FecError> {
+        if bytes.len() < ShardHeader::SIZE {
+            return Err(FecError::Backend(
+                "Insufficient data for shard header".to_string(),
+            ));
+        }
+
+        let header = ShardHeader::from_bytes(&bytes[..ShardHeader::SIZE])?;
+        let data = bytes[ShardHeader::SIZE..].to_vec();
+
+        Ok(Self { header, data })
+    }
+}
+
+/// Treat an expired shard as absent
+fn reject_expired(cid: &Cid, shard: Shard) -> Result<Shard, FecError> {
+    if shard.header.is_expired() {
+        return Err(FecError::Backend(format!(
+            "Shard expired: {}",
+            cid.to_hex()
+        )));
+    }
+    Ok(shard)
+}
+
+/// Chunk metadata as specified in v0.3
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkMeta {
+    /// FEC parameters (k, n-k)
+    pub nspec: (u8, u8),
+    /// Encryption mode used
+    pub mode: EncryptionMode,
+    /// CIDs of all shards for this chunk
+    pub shard_ids: Vec<String>,
+}
+
+impl ChunkMeta {
+    /// Create new chunk metadata
+    pub fn new(nspec: (u8, u8), mode: EncryptionMode, shard_ids: Vec<String>) -> Self {
+        Self {
+            nspec,
+            mode,
+            shard_ids,
+        }
+    }
+}
+
+/// File metadata as specified in v0.3
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileMetadata {
+    /// File identifier
+    pub file_id: [u8; 32],
+    /// Original file size
+    pub file_size: u64,
+    /// Chunks comprising this file
+    pub chunks: Vec<ChunkMeta>,
+    /// Creation timestamp
+    pub created_at: u64,
+    /// Version number
+    pub version: u8,
+}
+
+impl FileMetadata {
+    /// Create new file metadata
+    pub fn new(file_id: [u8; 32], file_size: u64, chunks: Vec<ChunkMeta>) -> Self {
+        let created_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Self {
+            file_id,
+            file_size,
+            chunks,
+            created_at,
+            version: 1,
+        }
+    }
+}
+
+/// `Send + Sync`, except on wasm32 where storage runs on one thread
+///
+/// Backends and object stores require it, so native backends can be shared
+/// across tasks while browser backends can hold JavaScript handles.
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSendSync: Send + Sync {}
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send + Sync + ?Sized> MaybeSendSync for T {}
+
+/// `Send + Sync`, except on wasm32 where storage runs on one thread
+///
+/// Backends and object stores require it, so native backends can be shared
+/// across tasks while browser backends can hold JavaScript handles.
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSendSync {}
+#[cfg(target_arch = "wasm32")]
+impl<T: ?Sized> MaybeSendSync for T {}
+
+/// Boxed future of a backend write, `Send` like the backend's own futures
+#[cfg(not(target_arch = "wasm32"))]
+type BackendFuture<'a> =
+    std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), FecError>> + Send + 'a>>;
+#[cfg(target_arch = "wasm32")]
+type BackendFuture<'a> =
+    std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), FecError>> + 'a>>;
+
+/// Abstract storage backend interface for v0.3 specification
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait StorageBackend: MaybeSendSync {
+    /// Store a shard with the given CID
+    async fn put_shard(&self, cid: &Cid, shard: &Shard) -> Result<(), FecError>;
+
+    /// Retrieve a shard by CID
+    async fn get_shard(&self, cid: &Cid) -> Result<Shard, FecError>;
+
+    /// Delete a shard by CID
+    async fn delete_shard(&self, cid: &Cid) -> Result<(), FecError>;
+
+    /// Check if a shard exists
+    async fn has_shard(&self, cid: &Cid) -> Result<bool, FecError>;
+
+    /// List all shard CIDs in storage
+    async fn list_shards(&self) -> Result<Vec<Cid>, FecError>;
+
+    /// Store file metadata
+    async fn put_metadata(&self, metadata: &FileMetadata) -> Result<(), FecError>;
+
+    /// Retrieve file metadata
+    async fn get_metadata(&self, file_id: &[u8; 32]) -> Result<FileMetadata, FecError>;
+
+    /// Delete file metadata
+    async fn delete_metadata(&self, file_id: &[u8; 32]) -> Result<(), FecError>;
+
+    /// List all file metadata
+    async fn list_metadata(&self) -> Result<Vec<FileMetadata>, FecError>;
+
+    /// Get storage statistics
+    async fn stats(&self) -> Result<StorageStats, FecError>;
+
+    /// Run garbage collection
+    async fn garbage_collect(&self) -> Result<GcReport, FecError>;
+
+    /// List shard CIDs in ascending order, one page at a time
+    ///
+    /// Returns at most `limit` CIDs greater than `after`; pass the page's
+    /// `next` cursor back as `after` to continue. The default sorts the full
+    /// [`list_shards`](Self::list_shards) result, so backends holding many
+    /// shards override it to avoid materializing every CID.
+    async fn list_shards_paged(
+        &self,
+        after: Option<&Cid>,
+        limit: usize,
+    ) -> Result<ShardPage, FecError> {
+        let mut cids = self.list_shards().await?;
+        cids.sort();
+        let cids = cids
+            .into_iter()
+            .filter(|cid| after.is_none_or(|after| cid > after));
+        Ok(ShardPage::from_candidates(cids, limit))
+    }
+
+    /// Retrieve a shard and check that its content hashes to `cid`
+    ///
+    /// Returns [`FecError::CorruptShard`] on a mismatch. Backends holding
+    /// several copies override this to fall back to, and heal from, a good one.
+    async fn get_shard_verified(&self, cid: &Cid) -> Result<Shard, FecError> {
+        let shard = self.get_shard(cid).await?;
+        shard.verify(cid)?;
+        Ok(shard)
+    }
+
+    /// Store several shards
+    ///
+    /// The default stores each shard in turn. Backends with native batching
+    /// override this to write the whole batch in one round trip.
+    async fn put_shards(&self, shards: &[(Cid, Shard)]) -> Result<(), FecError> {
+        for (cid, shard) in shards {
+            self.put_shard(cid, shard).await?;
+        }
+        Ok(())
+    }
+
+    /// Retrieve several shards, with `None` for any shard that cannot be read
+    ///
+    /// Results are returned in the order of `cids`.
+    async fn get_shards(&self, cids: &[Cid]) -> Result<Vec<Option<Shard>>, FecError> {
+        let mut shards = Vec::with_capacity(cids.len());
+        for cid in cids {
+            shards.push(self.get_shard(cid).await.ok());
+        }
+        Ok(shards)
+    }
+
+    /// Check which of several shards exist, in the order of `cids`
+    async fn has_shards(&self, cids: &[Cid]) -> Result<Vec<bool>, FecError> {
+        let mut present = Vec::with_capacity(cids.len());
+        for cid in cids {
+            present.push(self.has_shard(cid).await?);
+        }
+        Ok(present)
+    }
+
+    /// Start a transaction whose writes are applied together on commit
+    fn begin(&self) -> Transaction<'_>
+    where
+        Self: Sized,
+    {
+        Transaction::new(self)
+    }
+
+    /// Write shards and metadata as one unit
+    ///
+    /// The default writes the shards, then the metadata, and on failure
+    /// removes whatever this call created and restores metadata it
+    /// overwrote. That is not crash-safe, so backends with native
+    /// transactions or a journal override it.
+    async fn commit_transaction(
+        &self,
+        shards: &[(Cid, Shard)],
+        metadata: &[FileMetadata],
+    ) -> Result<(), FecError> {
+        let cids: Vec<Cid> = shards.iter().map(|(cid, _)| *cid).collect();
+        let existed = self.has_shards(&cids).await?;
+        // Metadata as it was before each write, `None` where there was none
+        let mut previous_metadata = Vec::new();
+
+        let result = async {
+            self.put_shards(shards).await?;
+            for meta in metadata {
+                let previous = self.get_metadata(&meta.file_id).await.ok();
+                previous_metadata.push((meta.file_id, previous));
+                self.put_metadata(meta).await?;
+            }
+            Ok(())
+        }
+        .await;
+
+        if result.is_err() {
+            // Newest first, so a file written twice ends at its original
+            for (file_id, previous) in previous_metadata.iter().rev() {
+                let _ = match previous {
+                    Some(previous) => self.put_metadata(previous).await,
+                    None => self.delete_metadata(file_id).await,
+                };
+            }
+            for (cid, existed) in cids.iter().zip(existed) {
+                if !existed {
+                    let _ = self.delete_shard(cid).await;
+                }
+            }
+        }
+        result
+    }
+
+    /// Store a shard whose `len`-byte payload is read from `reader`
+    ///
+    /// The default buffers the payload and calls [`put_shard`](Self::put_shard);
+    /// backends that can write incrementally override this so large shards
+    /// are never held in memory.
+    async fn put_shard_stream(
+        &self,
+        cid: &Cid,
+        header: &ShardHeader,
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        len: u64,
+    ) -> Result<(), FecError> {
+        let mut data = Vec::new();
+        reader.take(len).read_to_end(&mut data).await?;
+        if data.len() as u64 != len {
+            return Err(FecError::SizeMismatch {
+                expected: len as usize,
+                actual: data.len(),
+            });
+        }
+        self.put_shard(cid, &Shard::new(header.clone(), data)).await
+    }
+
+    /// Open a shard for reading, returning its header and a reader over the payload
+    ///
+    /// The default loads the whole shard with [`get_shard`](Self::get_shard).
+    async fn get_shard_stream(&self, cid: &Cid) -> Result<(ShardHeader, ShardReader), FecError> {
+        let shard = self.get_shard(cid).await?;
+        Ok((shard.header, Box::new(std::io::Cursor::new(shard.data))))
+    }
+}
+
+/// Shared backends, such as those held by [`MultiStorage`], can be wrapped
+/// like any other; every call is forwarded so overrides are kept.
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl<T: StorageBackend + ?Sized> StorageBackend for Arc<T> {
+    async fn put_shard(&self, cid: &Cid, shard: &Shard) -> Result<(), FecError> {
+        (**self).put_shard(cid, shard).await
+    }
+
+    async fn get_shard(&self, cid: &Cid) -> Result<Shard, FecError> {
+        (**self).get_shard(cid).await
+    }
+
+    async fn delete_shard(&self, cid: &Cid) -> Result<(), FecError> {
+        (**self).delete_shard(cid).await
+    }
+
+    async fn has_shard(&self, cid: &Cid) -> Result<bool, FecError> {
+        (**self).has_shard(cid).await
+    }
+
+    async fn list_shards(&self) -> Result<Vec<Cid>, FecError> {
+        (**self).list_shards().await
+    }
+
+    async fn put_metadata(&self, metadata: &FileMetadata) -> Result<(), FecError> {
+        (**self).put_metadata(metadata).await
+    }
+
+    async fn get_metadata(&self, file_id: &[u8; 32]) -> Result<FileMetadata, FecError> {
+        (**self).get_metadata(file_id).await
+    }
+
+    async fn delete_metadata(&self, file_id: &[u8; 32]) -> Result<(), FecError> {
+        (**self).delete_metadata(file_id).await
+    }
+
+    async fn list_metadata(&self) -> Result<Vec<FileMetadata>, FecError> {
+        (**self).list_metadata().await
+    }
+
+    async fn stats(&self) -> Result<StorageStats, FecError> {
+        (**self).stats().await
+    }
+
+    async fn garbage_collect(&self) -> Result<GcReport, FecError> {
+        (**self).garbage_collect().await
+    }
+
+    async fn list_shards_paged(
+        &self,
+        after: Option<&Cid>,
+        limit: usize,
+    ) -> Result<ShardPage, FecError> {
+        (**self).list_shards_paged(after, limit).await
+    }
+
+    async fn get_shard_verified(&self, cid: &Cid) -> Result<Shard, FecError> {
+        (**self).get_shard_verified(cid).await
+    }
+
+    async fn put_shards(&self, shards: &[(Cid, Shard)]) -> Result<(), FecError> {
+        (**self).put_shards(shards).await
+    }
+
+    async fn get_shards(&self, cids: &[Cid]) -> Result<Vec<Option<Shard>>, FecError> {
+        (**self).get_shards(cids).await
+    }
+
+    async fn has_shards(&self, cids: &[Cid]) -> Result<Vec<bool>, FecError> {
+        (**self).has_shards(cids).await
+    }
+
+    async fn commit_transaction(
+        &self,
+        shards: &[(Cid, Shard)],
+        metadata: &[FileMetadata],
+    ) -> Result<(), FecError> {
+        (**self).commit_transaction(shards, metadata).await
+    }
+
+    async fn put_shard_stream(
+        &self,
+        cid: &Cid,
+        header: &ShardHeader,
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        len: u64,
+    ) -> Result<(), FecError> {
+        (**self).put_shard_stream(cid, header, reader, len).await
+    }
+
+    async fn get_shard_stream(&self, cid: &Cid) -> Result<(ShardHeader, ShardReader), FecError> {
+        (**self).get_shard_stream(cid).await
+    }
+}
+
+/// One page of a [`StorageBackend::list_shards_paged`] listing
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShardPage {
+    /// CIDs in ascending order
+    pub cids: Vec<Cid>,
+    /// Cursor for the next page, or `None` once the listing is complete
+    pub next: Option<Cid>,
+}
+
+impl ShardPage {
+    /// Build a page from ascending candidates, reading at most one past `limit`
+    fn from_candidates(candidates: impl IntoIterator<Item = Cid>, limit: usize) -> Self {
+        let mut cids: Vec<Cid> = candidates.into_iter().take(limit + 1).collect();
+        let more = cids.len() > limit;
+        cids.truncate(limit);
+        let next = if more { cids.last().copied() } else { None };
+        Self { cids, next }
+    }
+}
+
+/// Reader over a shard payload returned by [`StorageBackend::get_shard_stream`]
+pub type ShardReader = Box<dyn AsyncRead + Send + Unpin>;
+
+/// Storage statistics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageStats {
+    /// Total number of shards
+    pub total_shards: u64,
+    /// Total storage size in bytes
+    pub total_size: u64,
+    /// Number of file metadata entries
+    pub metadata_count: u64,
+    /// Number of unreferenced shards
+    pub unreferenced_shards: u64,
+    /// Total capacity in bytes, where the backend knows it
+    #[serde(default)]
+    pub capacity: Option<u64>,
+    /// Bytes still available for new shards, where the backend knows it
+    #[serde(default)]
+    pub free_space: Option<u64>,
+}
+
+impl StorageStats {
+    /// Fraction of capacity in use, if capacity and free space are known
+    pub fn usage_ratio(&self) -> Option<f64> {
+        match (self.capacity, self.free_space) {
+            (Some(capacity), Some(free)) if capacity > 0 => {
+                Some(capacity.saturating_sub(free) as f64 / capacity as f64)
+            }
+            _ => None,
+        }
+    }
+
+    /// Add up the statistics of several backends
+    ///
+    /// Capacity and free space are only reported if every backend knows them.
+    fn sum(stats: impl IntoIterator<Item = StorageStats>) -> Self {
+        let mut stats = stats.into_iter();
+        let Some(mut total) = stats.next() else {
+            return Self::default();
+        };
+        for other in stats {
+            total.total_shards += other.total_shards;
+            total.total_size += other.total_size;
+            total.metadata_count += other.metadata_count;
+            total.unreferenced_shards += other.unreferenced_shards;
+            total.capacity = total.capacity.zip(other.capacity).map(|(a, b)| a + b);
+            total.free_space = total.free_space.zip(other.free_space).map(|(a, b)| a + b);
+        }
+        total
+    }
+}
+
+/// Garbage collection report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GcReport {
+    /// Number of shards deleted
+    pub shards_deleted: u64,
+    /// Bytes freed
+    pub bytes_freed: u64,
+    /// Duration of GC run
+    pub duration_ms: u64,
+}
+
+/// Collect the CIDs of every shard referenced by the given metadata
+fn referenced_cids(metadata: &[FileMetadata]) -> std::collections::HashSet<Cid> {
+    metadata
+        .iter()
+        .flat_map(|meta| &meta.chunks)
+        .flat_map(|chunk| &chunk.shard_ids)
+        .filter_map(|shard_id| {
+            let mut cid = [0u8; 32];
+            hex::decode_to_slice(shard_id, &mut cid).ok()?;
+            Some(Cid::new(cid))
+        })
+        .collect()
+}
+
+/// In-memory storage implementation for testing and caching
+/// Stores shards and metadata in HashMap structures
+///
+/// Clones share the same underlying maps, so a clone handed to a pipeline can
+/// still be inspected by the caller.
+#[derive(Clone)]
+pub struct MemoryStorage {
+    /// In-memory shard storage
+    shards: Arc<RwLock<HashMap<Cid, Shard>>>,
+    /// In-memory metadata storage
+    metadata: Arc<RwLock<HashMap<[u8; 32], FileMetadata>>>,
+    /// Bytes currently held by shards (header + data)
+    used_bytes: Arc<AtomicU64>,
+    /// Optional upper bound on `used_bytes`
+    capacity: Option<u64>,
+}
+
+impl MemoryStorage {
+    /// Create a new memory storage backend
+    pub fn new() -> Self {
+        Self {
+            shards: Arc::new(RwLock::new(HashMap::new())),
+            metadata: Arc::new(RwLock::new(HashMap::new())),
+            used_bytes: Arc::new(AtomicU64::new(0)),
+            capacity: None,
+        }
+    }
+
+    /// Create a memory storage backend that holds at most `capacity` bytes of shards
+    ///
+    /// Writes that would exceed the limit fail with [`FecError::CapacityExceeded`].
+    pub fn with_capacity(capacity: u64) -> Self {
+        Self {
+            capacity: Some(capacity),
+            ..Self::new()
+        }
+    }
+
+    /// Configured capacity limit in bytes, if any
+    pub fn capacity(&self) -> Option<u64> {
+        self.capacity
+    }
+
+    /// Bytes currently used by stored shards
+    pub fn used_bytes(&self) -> u64 {
+        self.used_bytes.load(Ordering::SeqCst)
+    }
+
+    /// Size accounted against the capacity for a shard
+    fn shard_size(shard: &Shard) -> u64 {
+        shard.data.len() as u64 + ShardHeader::SIZE as u64
+    }
+
+    /// Clear all stored data
+    pub fn clear(&self) {
+        // Handle poisoned locks by recovering the data
+        match self.shards.write() {
+            Ok(mut guard) => guard.clear(),
+            Err(poisoned) => poisoned.into_inner().clear(),
+        }
+        self.used_bytes.store(0, Ordering::SeqCst);
+        match self.metadata.write() {
+            Ok(mut guard) => guard.clear(),
+            Err(poisoned) => poisoned.into_inner().clear(),
+        }
+    }
+
+    /// Get the number of stored shards
+    pub fn shard_count(&self) -> usize {
+        match self.shards.read() {
+            Ok(guard) => guard.len(),
+            Err(poisoned) => poisoned.into_inner().len(),
+        }
+    }
+
+    /// Get the number of stored metadata entries
+    pub fn metadata_count(&self) -> usize {
+        match self.metadata.read() {
+            Ok(guard) => guard.len(),
+            Err(poisoned) => poisoned.into_inner().len(),
+        }
+    }
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl StorageBackend for MemoryStorage {
+    async fn put_shard(&self, cid: &Cid, shard: &Shard) -> Result<(), FecError> {
+        let mut shards = match self.shards.write() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        // Replacing a shard only needs room for the size difference
+        let new_size = Self::shard_size(shard);
+        let old_size = shards.get(cid).map(Self::shard_size).unwrap_or(0);
+        let used = self.used_bytes.load(Ordering::SeqCst) - old_size;
+        if let Some(capacity) = self.capacity {
+            if used + new_size > capacity {
+                return Err(FecError::CapacityExceeded {
+                    needed: new_size,
+                    available: capacity.saturating_sub(used),
+                });
+            }
+        }
+
+        shards.insert(*cid, shard.clone());
+        self.used_bytes.store(used + new_size, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn get_shard(&self, cid: &Cid) -> Result<Shard, FecError> {
+        let shards = match self.shards.read() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let shard = shards
+            .get(cid)
+            .cloned()
+            .ok_or_else(|| FecError::ShardNotFound { cid: cid.to_hex() })?;
+        reject_expired(cid, shard)
+    }
+
+    async fn delete_shard(&self, cid: &Cid) -> Result<(), FecError> {
+        let mut shards = match self.shards.write() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Some(shard) = shards.remove(cid) {
+            self.used_bytes
+                .fetch_sub(Self::shard_size(&shard), Ordering::SeqCst);
+        }
+        Ok(())
+    }
+
+    async fn has_shard(&self, cid: &Cid) -> Result<bool, FecError> {
+        let shards = match self.shards.read() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        Ok(shards.contains_key(cid))
+    }
+
+    async fn list_shards(&self) -> Result<Vec<Cid>, FecError> {
+        let shards = match self.shards.read() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        Ok(shards.keys().copied().collect())
+    }
+
+    async fn put_metadata(&self, metadata: &FileMetadata) -> Result<(), FecError> {
+        let mut metadata_store = match self.metadata.write() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        metadata_store.insert(metadata.file_id, metadata.clone());
+        Ok(())
+    }
+
+    async fn get_metadata(&self, file_id: &[u8; 32]) -> Result<FileMetadata, FecError> {
+        let metadata_store = match self.metadata.read() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        metadata_store
+            .get(file_id)
+            .cloned()
+            .ok_or_else(|| FecError::MetadataNotFound {
+                file_id: hex::encode(file_id),
+            })
+    }
+
+    async fn delete_metadata(&self, file_id: &[u8; 32]) -> Result<(), FecError> {
+        let mut metadata_store = match self.metadata.write() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        metadata_store.remove(file_id);
+        Ok(())
+    }
+
+    async fn list_metadata(&self) -> Result<Vec<FileMetadata>, FecError> {
+        let metadata_store = match self.metadata.read() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        Ok(metadata_store.values().cloned().collect())
+    }
+
+    async fn stats(&self) -> Result<StorageStats, FecError> {
+        let shards = match self.shards.read() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let metadata = match self.metadata.read() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let total_size: u64 = shards
+            .values()
+            .map(|shard| shard.data.len() as u64 + ShardHeader::SIZE as u64)
+            .sum();
+
+        // Count unreferenced shards
+        let mut referenced_cids = std::collections::HashSet::new();
+        for meta in metadata.values() {
+            for chunk in &meta.chunks {
+                for shard_id in &chunk.shard_ids {
+                    if let Ok(cid_bytes) = hex::decode(shard_id) {
+                        if cid_bytes.len() == 32 {
+                            let mut cid_array = [0u8; 32];
+                            cid_array.copy_from_slice(&cid_bytes);
+                            referenced_cids.insert(Cid::new(cid_array));
+                        }
+                    }
+                }
+            }
+        }
+
+        let unreferenced_shards = shards
+            .keys()
+            .filter(|cid| !referenced_cids.contains(cid))
+            .count() as u64;
+
+        Ok(StorageStats {
+            total_shards: shards.len() as u64,
+            total_size,
+            metadata_count: metadata.len() as u64,
+            unreferenced_shards,
+            capacity: self.capacity,
+            free_space: self
+                .capacity
+                .map(|capacity| capacity.saturating_sub(self.used_bytes())),
+        })
+    }
+
+    async fn garbage_collect(&self) -> Result<GcReport, FecError> {
+        let start_time = std::time::Instant::now();
+        let mut shards_deleted = 0u64;
+        let mut bytes_freed = 0u64;
+
+        // Get snapshot of current state
+        let shards = match self.shards.read() {
+            Ok(guard) => guard.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        };
+        let metadata = match self.metadata.read() {
+            Ok(guard) => guard.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        };
+
+        // Build set of referenced shards
+        let mut referenced_cids = std::collections::HashSet::new();
+        for meta in metadata.values() {
+            for chunk in &meta.chunks {
+                for shard_id in &chunk.shard_ids {
+                    if let Ok(cid_bytes) = hex::decode(shard_id) {
+                        if cid_bytes.len() == 32 {
+                            let mut cid_array = [0u8; 32];
+                            cid_array.copy_from_slice(&cid_bytes);
+                            referenced_cids.insert(Cid::new(cid_array));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Delete unreferenced and expired shards
+        let mut shards_write = match self.shards.write() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        for (cid, shard) in shards {
+            if !referenced_cids.contains(&cid) || shard.header.is_expired() {
+                let shard_size = shard.data.len() as u64 + ShardHeader::SIZE as u64;
+                if shards_write.remove(&cid).is_some() {
+                    self.used_bytes.fetch_sub(shard_size, Ordering::SeqCst);
+                }
+                shards_deleted += 1;
+                bytes_freed += shard_size;
+            }
+        }
+        drop(shards_write);
+
+        let duration_ms = start_time.elapsed().as_millis() as u64;
+
+        Ok(GcReport {
+            shards_deleted,
+            bytes_freed,
+            duration_ms,
+        })
+    }
+
+    // Both maps stay locked for the whole commit, so readers never see a
+    // partial transaction
+    async fn commit_transaction(
+        &self,
+        shards: &[(Cid, Shard)],
+        metadata: &[FileMetadata],
+    ) -> Result<(), FecError> {
+        let mut shard_store = match self.shards.write() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut metadata_store = match self.metadata.write() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        // Check capacity for the whole batch before changing anything
+        let mut used = self.used_bytes.load(Ordering::SeqCst);
+        let mut sizes: HashMap<Cid, u64> = HashMap::new();
+        for (cid, shard) in shards {
+            let old_size = match sizes.get(cid) {
+                Some(size) => *size,
+                None => shard_store.get(cid).map(Self::shard_size).unwrap_or(0),
+            };
+            let new_size = Self::shard_size(shard);
+            used = used - old_size + new_size;
+            sizes.insert(*cid, new_size);
+        }
+        if let Some(capacity) = self.capacity {
+            if used > capacity {
+                let current = self.used_bytes.load(Ordering::SeqCst);
+                return Err(FecError::CapacityExceeded {
+                    needed: used.saturating_sub(current),
+                    available: capacity.saturating_sub(current),
+                });
+            }
+        }
+
+        for (cid, shard) in shards {
+            shard_store.insert(*cid, shard.clone());
+        }
+        for meta in metadata {
+            metadata_store.insert(meta.file_id, meta.clone());
+        }
+        self.used_bytes.store(used, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn get_shards(&self, cids: &[Cid]) -> Result<Vec<Option<Shard>>, FecError> {
+        let shards = match self.shards.read() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        Ok(cids
+            .iter()
+            .map(|cid| shards.get(cid).filter(|s| !s.header.is_expired()).cloned())
+            .collect())
+    }
+
+    async fn has_shards(&self, cids: &[Cid]) -> Result<Vec<bool>, FecError> {
+        let shards = match self.shards.read() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        Ok(cids.iter().map(|cid| shards.contains_key(cid)).collect())
+    }
+}
+
+/// Network storage node endpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeEndpoint {
+    /// Node address (IP or hostname)
+    pub address: String,
+    /// Node port
+    pub port: u16,
+    /// Optional node ID
+    pub node_id: Option<[u8; 32]>,
+}
+
+/// Parses `host:port`, with IPv6 addresses in brackets (`[::1]:9000`)
+impl std::str::FromStr for NodeEndpoint {
+    type Err = FecError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || FecError::Backend(format!("Invalid node address: {:?}", s));
+        let (host, port) = s.rsplit_once(':').ok_or_else(invalid)?;
+        let host = host
+            .strip_prefix('[')
+            .and_then(|h| h.strip_suffix(']'))
+            .unwrap_or(host);
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            address: host.to_string(),
+            port: port.parse().map_err(|_| invalid())?,
+            node_id: None,
+        })
+    }
+}
+
+impl NodeEndpoint {
+    /// Rendezvous hashing weight of this node for a key
+    fn rendezvous_score(&self, key: &[u8; 32]) -> u64 {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(key);
+        match &self.node_id {
+            Some(id) => hasher.update(id),
+            None => hasher.update(format!("{}:{}", self.address, self.port).as_bytes()),
+        };
+        let hash = hasher.finalize();
+        let mut score = [0u8; 8];
+        score.copy_from_slice(&hash.as_bytes()[..8]);
+        u64::from_le_bytes(score)
+    }
+}
+
+/// Outcome of a [`NetworkStorage::rebalance`] pass
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RebalanceReport {
+    /// Shard replicas written to newly responsible nodes
+    pub shards_copied: u64,
+    /// Shard replicas deleted from nodes no longer responsible
+    pub shards_removed: u64,
+    /// Metadata replicas written to newly responsible nodes
+    pub metadata_copied: u64,
+    /// Metadata replicas deleted from nodes no longer responsible
+    pub metadata_removed: u64,
+    /// Copies or deletions that failed and will be retried next pass
+    pub failures: u64,
+}
+
+/// Network-based storage implementation
+///
+/// Shards are replicated to `replication` nodes chosen from the shard CID by
+/// rendezvous hashing. Requests are carried by a [`NodeTransport`], such as the QUIC
+/// transport enabled by the `quic` feature.
+///
+/// Writes go to every selected node and succeed once `write_quorum` of them
+/// acknowledge. Reads try the selected nodes in turn until `read_quorum` valid
+/// copies are found, and push the shard back to any replica found missing or
+/// corrupted on the way (read-repair).
+pub struct NetworkStorage {
+    /// List of storage nodes
+    nodes: Vec<NodeEndpoint>,
+    /// Replication factor
+    replication: usize,
+    /// Acknowledgements required for a write to succeed
+    write_quorum: usize,
+    /// Valid copies required for a read to succeed
+    read_quorum: usize,
+    /// Transport used to reach nodes
+    transport: Option<Arc<dyn NodeTransport>>,
+    /// Removed nodes still holding data to be drained by a rebalance
+    retired: Vec<NodeEndpoint>,
+    /// Latency and failure history of every contacted node
+    health: HealthTracker,
+    /// Replicas restored by read-repair
+    read_repairs: AtomicU64,
+}
+
+impl NetworkStorage {
+    /// Create a new network storage backend
+    ///
+    /// The write quorum defaults to a majority of the replicas and the read
+    /// quorum to one. A transport must be attached with
+    /// [`with_transport`](Self::with_transport) before any request can reach
+    /// a node.
+    pub fn new(nodes: Vec<NodeEndpoint>, replication: usize) -> Self {
+        Self {
+            nodes,
+            replication,
+            write_quorum: replication / 2 + 1,
+            read_quorum: 1,
+            transport: None,
+            retired: Vec::new(),
+            health: HealthTracker::new(HealthPolicy::default()),
+            read_repairs: AtomicU64::new(0),
+        }
+    }
+
+    /// Attach the transport used to reach nodes
+    pub fn with_transport(mut self, transport: Arc<dyn NodeTransport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Set the write (W) and read (R) quorums
+    ///
+    /// Both are clamped to at least one; quorums larger than the number of
+    /// replicas can never be met and make every request fail.
+    pub fn with_quorum(mut self, write: usize, read: usize) -> Self {
+        self.write_quorum = write.max(1);
+        self.read_quorum = read.max(1);
+        self
+    }
+
+    /// Number of replicas restored by read-repair so far
+    pub fn read_repairs(&self) -> u64 {
+        self.read_repairs.load(Ordering::Relaxed)
+    }
+
+    /// Set when nodes are considered unhealthy; resets recorded health
+    pub fn with_health_policy(mut self, policy: HealthPolicy) -> Self {
+        self.health = HealthTracker::new(policy);
+        self
+    }
+
+    /// Health of every node in the placement ring
+    pub fn health(&self) -> Vec<NodeHealth> {
+        self.health.snapshot(&self.nodes)
+    }
+
+    /// Health of the replicas responsible for a shard
+    ///
+    /// Lets a repair layer tell which of a shard's locations are reachable
+    /// before deciding whether to rebuild it.
+    pub fn shard_locations(&self, cid: &Cid) -> Vec<NodeHealth> {
+        self.health.snapshot(self.select_nodes(cid.as_bytes()))
+    }
+
+    /// Probe every node and return the updated health snapshot
+    pub async fn check_health(&self) -> Vec<NodeHealth> {
+        for node in &self.nodes {
+            if let Err(e) = self.call(node, Request::Ping).await {
+                tracing::debug!(
+                    "Health probe of {}:{} failed: {}",
+                    node.address,
+                    node.port,
+                    e
+                );
+            }
+        }
+        self.health()
+    }
+
+    /// Send a request to every node in `nodes`, failing unless `write_quorum` succeed
+    async fn write_quorum(
+        &self,
+        nodes: &[&NodeEndpoint],
+        what: &str,
+        request: Request,
+    ) -> Result<(), FecError> {
+        if nodes.is_empty() {
+            return Err(FecError::Backend(
+                "No nodes available for storage".to_string(),
+            ));
+        }
+
+        let mut acks = 0;
+        let mut last_error = None;
+        for node in nodes {
+            if !self.health.is_available(node) {
+                tracing::debug!("Skipping unhealthy node {}:{}", node.address, node.port);
+                last_error = Some(FecError::Backend(format!(
+                    "{}:{} is marked unhealthy",
+                    node.address, node.port
+                )));
+                continue;
+            }
+            match self.call(node, request.clone()).await {
+                Ok(_) => acks += 1,
+                Err(e) => {
+                    tracing::warn!("Storing {} failed: {}", what, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        if acks < self.write_quorum {
+            return Err(FecError::Backend(format!(
+                "Write quorum not reached for {}: {} of {} acknowledgements{}",
+                what,
+                acks,
+                self.write_quorum,
+                last_error
+                    .map(|e| format!(" (last error: {})", e))
+                    .unwrap_or_default()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Best-effort write of `request` to replicas found stale during a read
+    async fn read_repair(&self, stale: &[&NodeEndpoint], what: &str, request: Request) {
+        for node in stale {
+            match self.call(node, request.clone()).await {
+                Ok(_) => {
+                    self.read_repairs.fetch_add(1, Ordering::Relaxed);
+                    tracing::debug!("Repaired {} on {}:{}", what, node.address, node.port);
+                }
+                Err(e) => tracing::warn!("Read-repair of {} failed: {}", what, e),
+            }
+        }
+    }
+
+    /// Select the replicas responsible for a key
+    ///
+    /// Uses rendezvous (highest random weight) hashing: every node is scored
+    /// by hashing it together with the key and the best-scoring nodes win.
+    /// Adding or removing a node therefore only moves the keys whose top
+    /// scores involve that node, roughly `1 / nodes` of the total.
+    fn select_nodes(&self, shard_id: &[u8; 32]) -> Vec<&NodeEndpoint> {
+        let mut scored: Vec<(u64, &NodeEndpoint)> = self
+            .nodes
+            .iter()
+            .map(|node| (node.rendezvous_score(shard_id), node))
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored
+            .into_iter()
+            .take(self.replication)
+            .map(|(_, node)| node)
+            .collect()
+    }
+
+    /// Add a node to the placement ring
+    ///
+    /// Existing data stays where it is until [`rebalance`](Self::rebalance)
+    /// migrates it.
+    pub fn add_node(&mut self, node: NodeEndpoint) {
+        if !self.nodes.contains(&node) {
+            self.nodes.push(node);
+        }
+    }
+
+    /// Remove a node from the placement ring
+    ///
+    /// The node is kept as a drain source, so the next
+    /// [`rebalance`](Self::rebalance) moves its data to the remaining nodes.
+    pub fn remove_node(&mut self, node: &NodeEndpoint) -> bool {
+        match self.nodes.iter().position(|n| n == node) {
+            Some(index) => {
+                self.retired.push(self.nodes.remove(index));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move shards and metadata onto the nodes now responsible for them
+    ///
+    /// Every current and retired node is listed; items missing from their
+    /// selected replicas are copied there and copies on nodes no longer
+    /// responsible are deleted once the new replicas hold them. Retired
+    /// nodes are forgotten after a pass without failures.
+    pub async fn rebalance(&mut self) -> Result<RebalanceReport, FecError> {
+        let mut report = RebalanceReport::default();
+        let sources: Vec<NodeEndpoint> = self.nodes.iter().chain(&self.retired).cloned().collect();
+
+        let mut shard_holders: HashMap<Cid, Vec<&NodeEndpoint>> = HashMap::new();
+        let mut metadata_holders: HashMap<[u8; 32], (FileMetadata, Vec<&NodeEndpoint>)> =
+            HashMap::new();
+        for node in &sources {
+            match self.call(node, Request::ListShards).await? {
+                Response::Shards(cids) => {
+                    for cid in cids {
+                        shard_holders.entry(cid).or_default().push(node);
+                    }
+                }
+                other => return Err(unexpected_response(other)),
+            }
+            match self.call(node, Request::ListMetadata).await? {
+                Response::MetadataList(list) => {
+                    for metadata in list {
+                        metadata_holders
+                            .entry(metadata.file_id)
+                            .or_insert_with(|| (metadata, Vec::new()))
+                            .1
+                            .push(node);
+                    }
+                }
+                other => return Err(unexpected_response(other)),
+            }
+        }
+
+        for (cid, holders) in shard_holders {
+            let desired = self.select_nodes(cid.as_bytes());
+            let missing: Vec<&NodeEndpoint> = desired
+                .iter()
+                .copied()
+                .filter(|node| !holders.contains(node))
+                .collect();
+
+            let mut placed = true;
+            if !missing.is_empty() {
+                let bytes = match self.call(holders[0], Request::GetShard { cid }).await {
+                    Ok(Response::Shard(bytes)) => bytes,
+                    Ok(other) => return Err(unexpected_response(other)),
+                    Err(e) => {
+                        tracing::warn!("Rebalance read of shard {} failed: {}", cid.to_hex(), e);
+                        report.failures += 1;
+                        continue;
+                    }
+                };
+                for node in missing {
+                    let request = Request::PutShard {
+                        cid,
+                        shard: bytes.clone(),
+                    };
+                    match self.call(node, request).await {
+                        Ok(_) => report.shards_copied += 1,
+                        Err(e) => {
+                            tracing::warn!("Rebalance of shard {} failed: {}", cid.to_hex(), e);
+                            report.failures += 1;
+                            placed = false;
+                        }
+                    }
+                }
+            }
+
+            if placed {
+                for node in holders.into_iter().filter(|n| !desired.contains(n)) {
+                    match self.call(node, Request::DeleteShard { cid }).await {
+                        Ok(_) => report.shards_removed += 1,
+                        Err(e) => {
+                            tracing::warn!("Removing moved shard {} failed: {}", cid.to_hex(), e);
+                            report.failures += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        for (file_id, (metadata, holders)) in metadata_holders {
+            let desired = self.select_nodes(&file_id);
+            let mut placed = true;
+            for node in desired.iter().filter(|node| !holders.contains(node)) {
+                let request = Request::PutMetadata {
+                    metadata: metadata.clone(),
+                };
+                match self.call(node, request).await {
+                    Ok(_) => report.metadata_copied += 1,
+                    Err(e) => {
+                        tracing::warn!("Rebalance of metadata failed: {}", e);
+                        report.failures += 1;
+                        placed = false;
+                    }
+                }
+            }
+
+            if placed {
+                for node in holders.into_iter().filter(|n| !desired.contains(n)) {
+                    match self.call(node, Request::DeleteMetadata { file_id }).await {
+                        Ok(_) => report.metadata_removed += 1,
+                        Err(e) => {
+                            tracing::warn!("Removing moved metadata failed: {}", e);
+                            report.failures += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        if report.failures == 0 {
+            self.retired.clear();
+        }
+        Ok(report)
+    }
+
+    /// Send a request to a single node
+    async fn call(&self, node: &NodeEndpoint, request: Request) -> Result<Response, FecError> {
+        let transport = self
+            .transport
+            .as_ref()
+            .ok_or_else(|| FecError::Backend("No network transport configured".to_string()))?;
+        let started = std::time::Instant::now();
+        let response = match transport.call(node, request).await {
+            Ok(response) => {
+                // An error reply still proves the node is reachable
+                self.health.record_success(node, started.elapsed());
+                response
+            }
+            Err(e) => {
+                self.health.record_failure(node, &e.to_string());
+                return Err(e);
+            }
+        };
+        match response {
+            Response::Error(e) => Err(FecError::Backend(format!(
+                "{}:{}: {}",
+                node.address, node.port, e
+            ))),
+            response => Ok(response),
+        }
+    }
+
+    /// Send a request to every node, keeping the successful responses
+    async fn call_all(&self, request: Request) -> Vec<Response> {
+        let mut responses = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            match self.call(node, request.clone()).await {
+                Ok(response) => responses.push(response),
+                Err(e) => tracing::warn!("Node {}:{} failed: {}", node.address, node.port, e),
+            }
+        }
+        responses
+    }
+}
+
+/// Build an error for a response variant that does not match the request
+fn unexpected_response(response: Response) -> FecError {
+    FecError::Backend(format!("Unexpected response from node: {:?}", response))
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl StorageBackend for NetworkStorage {
+    async fn put_shard(&self, cid: &Cid, shard: &Shard) -> Result<(), FecError> {
+        let request = Request::PutShard {
+            cid: *cid,
+            shard: shard.to_bytes()?,
+        };
+        let what = format!("shard {}", cid.to_hex());
+        self.write_quorum(&self.select_nodes(cid.as_bytes()), &what, request)
+            .await
+    }
+
+    async fn get_shard(&self, cid: &Cid) -> Result<Shard, FecError> {
+        let mut found = None;
+        let mut copies = 0;
+        let mut stale = Vec::new();
+
+        for node in self.select_nodes(cid.as_bytes()) {
+            if copies >= self.read_quorum {
+                break;
+            }
+            match self.call(node, Request::GetShard { cid: *cid }).await {
+                Ok(Response::Shard(bytes)) => match Shard::from_bytes(&bytes) {
+                    Ok(shard) if shard.verify(cid).is_ok() => {
+                        copies += 1;
+                        found.get_or_insert(shard);
+                    }
+                    _ => {
+                        tracing::warn!("Replica of shard {} is corrupted", cid.to_hex());
+                        stale.push(node);
+                    }
+                },
+                Ok(other) => return Err(unexpected_response(other)),
+                Err(e) => {
+                    tracing::debug!("Retrieving shard {} failed: {}", cid.to_hex(), e);
+                    stale.push(node);
+                }
+            }
+        }
+
+        let shard = found.ok_or_else(|| FecError::ShardNotFound { cid: cid.to_hex() })?;
+        if !stale.is_empty() {
+            let request = Request::PutShard {
+                cid: *cid,
+                shard: shard.to_bytes()?,
+            };
+            let what = format!("shard {}", cid.to_hex());
+            self.read_repair(&stale, &what, request).await;
+        }
+
+        if copies < self.read_quorum {
+            return Err(FecError::Backend(format!(
+                "Read quorum not reached for shard {}: {} of {} copies",
+                cid.to_hex(),
+                copies,
+                self.read_quorum
+            )));
+        }
+        Ok(shard)
+    }
+
+    async fn delete_shard(&self, cid: &Cid) -> Result<(), FecError> {
+        for node in self.select_nodes(cid.as_bytes()) {
+            self.call(node, Request::DeleteShard { cid: *cid }).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn has_shard(&self, cid: &Cid) -> Result<bool, FecError> {
+        for node in self.select_nodes(cid.as_bytes()) {
+            match self.call(node, Request::HasShard { cid: *cid }).await {
+                Ok(Response::Exists(true)) => return Ok(true),
+                Ok(Response::Exists(false)) => {}
+                Ok(other) => return Err(unexpected_response(other)),
+                Err(e) => tracing::debug!("Checking shard {} failed: {}", cid.to_hex(), e),
+            }
+        }
+
+        Ok(false)
+    }
+
+    async fn list_shards(&self) -> Result<Vec<Cid>, FecError> {
+        let mut cids = Vec::new();
+        for response in self.call_all(Request::ListShards).await {
+            match response {
+                Response::Shards(shards) => cids.extend(shards),
+                other => return Err(unexpected_response(other)),
+            }
+        }
+        cids.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+        cids.dedup();
+        Ok(cids)
+    }
+
+    async fn put_metadata(&self, metadata: &FileMetadata) -> Result<(), FecError> {
+        let request = Request::PutMetadata {
+            metadata: metadata.clone(),
+        };
+        let what = format!("metadata {}", hex::encode(metadata.file_id));
+        self.write_quorum(&self.select_nodes(&metadata.file_id), &what, request)
+            .await
+    }
+
+    async fn get_metadata(&self, file_id: &[u8; 32]) -> Result<FileMetadata, FecError> {
+        let mut missing = Vec::new();
+        for node in self.select_nodes(file_id) {
+            match self
+                .call(node, Request::GetMetadata { file_id: *file_id })
+                .await
+            {
+                Ok(Response::Metadata(metadata)) => {
+                    if !missing.is_empty() {
+                        let request = Request::PutMetadata {
+                            metadata: metadata.clone(),
+                        };
+                        let what = format!("metadata {}", hex::encode(file_id));
+                        self.read_repair(&missing, &what, request).await;
+                    }
+                    return Ok(metadata);
+                }
+                Ok(other) => return Err(unexpected_response(other)),
+                Err(e) => {
+                    tracing::debug!("Retrieving metadata failed: {}", e);
+                    missing.push(node);
+                }
+            }
+        }
+
+        Err(FecError::MetadataNotFound {
+            file_id: hex::encode(file_id),
+        })
+    }
+
+    async fn delete_metadata(&self, file_id: &[u8; 32]) -> Result<(), FecError> {
+        for node in self.select_nodes(file_id) {
+            self.call(node, Request::DeleteMetadata { file_id: *file_id })
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn list_metadata(&self) -> Result<Vec<FileMetadata>, FecError> {
+        let mut by_id = HashMap::new();
+        for response in self.call_all(Request::ListMetadata).await {
+            match response {
+                Response::MetadataList(list) => {
+                    for metadata in list {
+                        by_id.insert(metadata.file_id, metadata);
+                    }
+                }
+                other => return Err(unexpected_response(other)),
+            }
+        }
+        Ok(by_id.into_values().collect())
+    }
+
+    async fn stats(&self) -> Result<StorageStats, FecError> {
+        // Replicas are counted once per node that holds them
+        let mut per_node = Vec::new();
+        for response in self.call_all(Request::Stats).await {
+            match response {
+                Response::Stats(stats) => per_node.push(stats),
+                other => return Err(unexpected_response(other)),
+            }
+        }
+
+        Ok(StorageStats::sum(per_node))
+    }
+
+    async fn garbage_collect(&self) -> Result<GcReport, FecError> {
+        let mut total = GcReport {
+            shards_deleted: 0,
+            bytes_freed: 0,
+            duration_ms: 0,
+        };
+
+        for response in self.call_all(Request::GarbageCollect).await {
+            match response {
+                Response::GcReport(report) => {
+                    total.shards_deleted += report.shards_deleted;
+                    total.bytes_freed += report.bytes_freed;
+                    total.duration_ms = total.duration_ms.max(report.duration_ms);
+                }
+                other => return Err(unexpected_response(other)),
+            }
+        }
+
+        Ok(total)
+    }
+}
+
+/// Multi-backend storage that combines multiple backends for redundancy and load balancing
+/// Implements failover capabilities and load distribution
+pub struct MultiStorage {
+    /// Ordered list of storage backends (priority order)
+    backends: Vec<Arc<dyn StorageBackend>>,
+    /// Strategy for backend selection
+    strategy: MultiStorageStrategy,
+    /// Corrupted copies overwritten by verified reads
+    shards_healed: AtomicU64,
+    /// Deleted shards still held by some backends, with the error each
+    /// of those backends last returned
+    pending_deletions: Mutex<HashMap<Cid, DeletionFailures>>,
+}
+
+/// Error last returned by each backend that failed to delete a shard
+type DeletionFailures = std::collections::BTreeMap<usize, String>;
+
+/// A shard deletion that has not yet reached every backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingDeletion {
+    /// The shard being deleted
+    pub cid: Cid,
+    /// Indices of the backends that failed to delete it, with their errors
+    pub failures: Vec<(usize, String)>,
+}
+
+/// Strategy for multi-backend operations
+///
+/// Reads always try backends in order and return the first copy found; the
+/// strategy decides where writes go and how many must succeed.
+#[derive(Debug, Clone)]
+pub enum MultiStorageStrategy {
+    /// Write to all backends, succeeding if any one accepts the write
+    Redundant,
+    /// Stripe writes across backends, one backend per key
+    LoadBalance,
+    /// Use primary backend with failover to secondary
+    Failover,
+    /// Write to all backends, succeeding once this many accept the write
+    Quorum(usize),
+}
+
+impl MultiStorage {
+    /// Create a new multi-backend storage with redundant strategy
+    pub fn new(backends: Vec<Arc<dyn StorageBackend>>) -> Self {
+        Self::with_strategy(backends, MultiStorageStrategy::Redundant)
+    }
+
+    /// Create with specific strategy
+    pub fn with_strategy(
+        backends: Vec<Arc<dyn StorageBackend>>,
+        strategy: MultiStorageStrategy,
+    ) -> Self {
+        Self {
+            backends,
+            strategy,
+            shards_healed: AtomicU64::new(0),
+            pending_deletions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Add a backend
+    pub fn add_backend(&mut self, backend: Arc<dyn StorageBackend>) {
+        self.backends.push(backend);
+    }
+
+    /// Remove a backend
+    ///
+    /// Pending deletions on the removed backend are dropped.
+    pub fn remove_backend(&mut self, index: usize) -> Option<Arc<dyn StorageBackend>> {
+        if index >= self.backends.len() {
+            return None;
+        }
+        let pending = match self.pending_deletions.get_mut() {
+            Ok(pending) => pending,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        pending.retain(|_, failures| {
+            *failures = std::mem::take(failures)
+                .into_iter()
+                .filter(|(i, _)| *i != index)
+                .map(|(i, e)| (if i > index { i - 1 } else { i }, e))
+                .collect();
+            !failures.is_empty()
+        });
+        Some(self.backends.remove(index))
+    }
+
+    /// Get number of backends
+    pub fn backend_count(&self) -> usize {
+        self.backends.len()
+    }
+
+    /// Apply a write to the backends chosen by the strategy
+    ///
+    /// `key` picks the backend when striping. If too few backends accept the
+    /// write, the error lists every backend's failure.
+    async fn write<'a, F>(&'a self, what: &str, key: u8, op: F) -> Result<(), FecError>
+    where
+        F: Fn(&'a Arc<dyn StorageBackend>) -> BackendFuture<'a>,
+    {
+        if self.backends.is_empty() {
+            return Err(FecError::Backend("No backends available".to_string()));
+        }
+
+        let (targets, required, stop_early): (Vec<usize>, usize, bool) = match self.strategy {
+            MultiStorageStrategy::Redundant => ((0..self.backends.len()).collect(), 1, false),
+            MultiStorageStrategy::LoadBalance => {
+                (vec![key as usize % self.backends.len()], 1, true)
+            }
+            MultiStorageStrategy::Failover => ((0..self.backends.len()).collect(), 1, true),
+            MultiStorageStrategy::Quorum(quorum) => (
+                (0..self.backends.len()).collect(),
+                quorum.clamp(1, self.backends.len()),
+                false,
+            ),
+        };
+
+        let mut succeeded = 0;
+        let mut failures = Vec::new();
+        for index in targets {
+            match op(&self.backends[index]).await {
+                Ok(()) => {
+                    succeeded += 1;
+                    if stop_early {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("{} write failed on backend {}: {}", what, index, e);
+                    failures.push(format!("backend {}: {}", index, e));
+                }
+            }
+        }
+
+        if succeeded >= required {
+            Ok(())
+        } else {
+            Err(FecError::Backend(format!(
+                "{} write succeeded on {} of {} required backends ({})",
+                what,
+                succeeded,
+                required,
+                failures.join("; ")
+            )))
+        }
+    }
+
+    /// Number of corrupted copies replaced by [`StorageBackend::get_shard_verified`]
+    pub fn shards_healed(&self) -> u64 {
+        self.shards_healed.load(Ordering::Relaxed)
+    }
+
+    fn pending(&self) -> std::sync::MutexGuard<'_, HashMap<Cid, DeletionFailures>> {
+        match self.pending_deletions.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Shard deletions that failed on some backends, see
+    /// [`Self::retry_deletions`]
+    pub fn pending_deletions(&self) -> Vec<PendingDeletion> {
+        let mut pending: Vec<PendingDeletion> = self
+            .pending()
+            .iter()
+            .map(|(cid, failures)| PendingDeletion {
+                cid: *cid,
+                failures: failures.iter().map(|(i, e)| (*i, e.clone())).collect(),
+            })
+            .collect();
+        pending.sort_by_key(|deletion| deletion.cid);
+        pending
+    }
+
+    /// Delete `cid` from the backends at `targets`, recording each failure
+    ///
+    /// The shard stays pending until every backend has deleted it.
+    async fn delete_from(&self, cid: &Cid, targets: &[usize]) -> Result<(), FecError> {
+        let mut failures = Vec::new();
+        for &index in targets {
+            match self.backends[index].delete_shard(cid).await {
+                Ok(()) => {
+                    let mut pending = self.pending();
+                    if let Some(backends) = pending.get_mut(cid) {
+                        backends.remove(&index);
+                        if backends.is_empty() {
+                            pending.remove(cid);
+                        }
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to delete shard from backend {}: {}", index, e);
+                    failures.push(format!("backend {}: {}", index, e));
+                    self.pending()
+                        .entry(*cid)
+                        .or_default()
+                        .insert(index, e.to_string());
+                }
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(FecError::Backend(format!(
+                "Shard {} still stored on {} backends ({})",
+                cid.to_hex(),
+                failures.len(),
+                failures.join("; ")
+            )))
+        }
+    }
+
+    /// Shards referenced by the metadata on any backend
+    ///
+    /// Fails if any backend cannot list its metadata, since a shard it
+    /// needs could otherwise be deleted.
+    async fn referenced_anywhere(&self) -> Result<std::collections::HashSet<Cid>, FecError> {
+        let mut referenced = std::collections::HashSet::new();
+        for (index, backend) in self.backends.iter().enumerate() {
+            let metadata = backend.list_metadata().await.map_err(|e| {
+                FecError::Backend(format!("Cannot list metadata on backend {}: {}", index, e))
+            })?;
+            referenced.extend(referenced_cids(&metadata));
+        }
+        Ok(referenced)
+    }
+
+    /// Retry pending deletions on the backends that failed them
+    ///
+    /// A shard that metadata on some backend references again is no longer
+    /// deleted; its remaining copies are kept. Returns how many shards are
+    /// now gone from every backend.
+    pub async fn retry_deletions(&self) -> Result<usize, FecError> {
+        let pending: Vec<(Cid, Vec<usize>)> = self
+            .pending()
+            .iter()
+            .map(|(cid, failures)| (*cid, failures.keys().copied().collect()))
+            .collect();
+        if pending.is_empty() {
+            return Ok(0);
+        }
+
+        let referenced = self.referenced_anywhere().await?;
+        let mut completed = 0;
+        for (cid, targets) in pending {
+            if referenced.contains(&cid) {
+                self.pending().remove(&cid);
+                continue;
+            }
+            if self.delete_from(&cid, &targets).await.is_ok() {
+                completed += 1;
+            }
+        }
+        Ok(completed)
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl StorageBackend for MultiStorage {
+    async fn put_shard(&self, cid: &Cid, shard: &Shard) -> Result<(), FecError> {
+        self.write("Shard", cid.as_bytes()[0], |backend| {
+            backend.put_shard(cid, shard)
+        })
+        .await?;
+        // Needed again, so leave any copies a failed deletion left behind
+        self.pending().remove(cid);
+        Ok(())
+    }
+
+    async fn get_shard(&self, cid: &Cid) -> Result<Shard, FecError> {
+        // Try each backend in order until we find the shard
+        for backend in &self.backends {
+            match backend.get_shard(cid).await {
+                Ok(shard) => return Ok(shard),
+                Err(e) => {
+                    tracing::debug!("Backend failed to get shard: {}", e);
+                }
+            }
+        }
+
+        Err(FecError::ShardNotFound { cid: cid.to_hex() })
+    }
+
+    async fn get_shard_verified(&self, cid: &Cid) -> Result<Shard, FecError> {
+        let mut corrupted = Vec::new();
+        let mut corruption = None;
+
+        for backend in &self.backends {
+            let shard = match backend.get_shard(cid).await {
+                Ok(shard) => shard,
+                Err(e) => {
+                    tracing::debug!("Backend failed to get shard: {}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = shard.verify(cid) {
+                tracing::warn!("{}", e);
+                corrupted.push(backend);
+                corruption = Some(e);
+                continue;
+            }
+
+            // Overwrite every bad copy seen so far with the good one
+            for bad in corrupted {
+                match bad.put_shard(cid, &shard).await {
+                    Ok(()) => {
+                        self.shards_healed.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(e) => tracing::warn!("Failed to heal shard {}: {}", cid.to_hex(), e),
+                }
+            }
+            return Ok(shard);
+        }
+
+        Err(corruption.unwrap_or_else(|| FecError::ShardNotFound { cid: cid.to_hex() }))
+    }
+
+    /// Delete the shard from every backend
+    ///
+    /// Fails if any backend keeps its copy; those backends are tracked in
+    /// [`MultiStorage::pending_deletions`] until a retry reaches them.
+    async fn delete_shard(&self, cid: &Cid) -> Result<(), FecError> {
+        let all: Vec<usize> = (0..self.backends.len()).collect();
+        self.delete_from(cid, &all).await
+    }
+
+    async fn has_shard(&self, cid: &Cid) -> Result<bool, FecError> {
+        // Check if any backend has the shard
+        for backend in &self.backends {
+            if backend.has_shard(cid).await? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    async fn list_shards(&self) -> Result<Vec<Cid>, FecError> {
+        let mut all_shards = std::collections::HashSet::new();
+
+        // Collect from all backends
+        for backend in &self.backends {
+            if let Ok(shards) = backend.list_shards().await {
+                all_shards.extend(shards);
+            }
+        }
+
+        Ok(all_shards.into_iter().collect())
+    }
+
+    async fn put_metadata(&self, metadata: &FileMetadata) -> Result<(), FecError> {
+        self.write("Metadata", metadata.file_id[0], |backend| {
+            backend.put_metadata(metadata)
+        })
+        .await
+    }
+
+    async fn get_metadata(&self, file_id: &[u8; 32]) -> Result<FileMetadata, FecError> {
+        // Try each backend in order
+        for backend in &self.backends {
+            match backend.get_metadata(file_id).await {
+                Ok(metadata) => return Ok(metadata),
+                Err(e) => {
+                    tracing::debug!("Backend failed to get metadata: {}", e);
+                }
+            }
+        }
+
+        Err(FecError::MetadataNotFound {
+            file_id: hex::encode(file_id),
+        })
+    }
+
+    async fn delete_metadata(&self, file_id: &[u8; 32]) -> Result<(), FecError> {
+        // Delete from all backends
+        for backend in &self.backends {
+            if let Err(e) = backend.delete_metadata(file_id).await {
+                tracing::warn!("Failed to delete metadata from backend: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn list_metadata(&self) -> Result<Vec<FileMetadata>, FecError> {
+        let mut all_metadata = std::collections::HashMap::new();
+
+        // Collect from all backends, deduplicating by file_id
+        for backend in &self.backends {
+            if let Ok(metadata_list) = backend.list_metadata().await {
+                for metadata in metadata_list {
+                    all_metadata.insert(metadata.file_id, metadata);
+                }
+            }
+        }
+
+        Ok(all_metadata.into_values().collect())
+    }
+
+    async fn stats(&self) -> Result<StorageStats, FecError> {
+        // Aggregate stats from all backends
+        let mut per_backend = Vec::with_capacity(self.backends.len());
+        for backend in &self.backends {
+            if let Ok(stats) = backend.stats().await {
+                per_backend.push(stats);
+            }
+        }
+
+        Ok(StorageStats::sum(per_backend))
+    }
+
+    /// Delete shards that no backend's metadata references, or that have
+    /// expired, from every backend holding them
+    ///
+    /// Backends are not collected one by one: a shard on one backend may be
+    /// referenced only by metadata stored on another. Pending deletions are
+    /// retried first.
+    async fn garbage_collect(&self) -> Result<GcReport, FecError> {
+        let start_time = std::time::Instant::now();
+        self.retry_deletions().await?;
+        let referenced = self.referenced_anywhere().await?;
+
+        // Which backends hold each shard
+        let mut holders: HashMap<Cid, Vec<usize>> = HashMap::new();
+        for (index, backend) in self.backends.iter().enumerate() {
+            let shards = backend.list_shards().await.map_err(|e| {
+                FecError::Backend(format!("Cannot list shards on backend {}: {}", index, e))
+            })?;
+            for cid in shards {
+                holders.entry(cid).or_default().push(index);
+            }
+        }
+
+        let mut shards_deleted = 0u64;
+        let mut bytes_freed = 0u64;
+        for (cid, targets) in holders {
+            let shard = self.backends[targets[0]].get_shard(&cid).await.ok();
+            let expired = shard.as_ref().is_some_and(|s| s.header.is_expired());
+            if referenced.contains(&cid) && !expired {
+                continue;
+            }
+            let shard_size = shard.map_or(0, |s| s.data.len() as u64 + ShardHeader::SIZE as u64);
+            let failed = match self.delete_from(&cid, &targets).await {
+                Ok(()) => 0,
+                Err(_) => self.pending().get(&cid).map_or(0, |f| f.len()),
+            };
+            let deleted = (targets.len() - failed) as u64;
+            shards_deleted += deleted;
+            bytes_freed += deleted * shard_size;
+        }
+
+        Ok(GcReport {
+            shards_deleted,
+            bytes_freed,
+            duration_ms: start_time.elapsed().as_millis() as u64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_local_storage_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = LocalStorage::new(temp_dir.path().to_path_buf())
+            .await
+            .unwrap();
+
+        let header = ShardHeader::new(EncryptionMode::Convergent, (16, 4), 13, [1u8; 32]);
+        let shard = Shard::new(header, b"Hello, World!".to_vec());
+        let cid = shard.cid().unwrap();
+
+        // Store shard
+        storage.put_shard(&cid, &shard).await.unwrap();
+
+        // Verify it exists
+        assert!(storage.has_shard(&cid).await.unwrap());
+
+        // Retrieve shard
+        let retrieved = storage.get_shard(&cid).await.unwrap();
+        assert_eq!(retrieved.data, shard.data);
+
+        // Delete shard
+        storage.delete_shard(&cid).await.unwrap();
+        assert!(!storage.has_shard(&cid).await.unwrap());
+
+        // Capacity and free space come from the underlying filesystem
+        let stats = storage.stats().await.unwrap();
+        let capacity = stats.capacity.unwrap();
+        assert!(capacity > 0);
+        assert!(stats.free_space.unwrap() <= capacity);
+        assert!(stats.usage_ratio().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_batch_operations() {
+        let temp_dir = TempDir::new().unwrap();
+        let local = LocalStorage::new(temp_dir.path().to_path_buf())
+            .await
+            .unwrap();
+        let memory = MemoryStorage::new();
+        let backends: [&dyn StorageBackend; 2] = [&local, &memory];
+
+        let batch: Vec<(Cid, Shard)> = (1..=3u8)
+            .map(|i| {
+                let header = ShardHeader::new(EncryptionMode::Convergent, (2, 1), 4, [i; 32]);
+                let shard = Shard::new(header, vec![i; 4]);
+                (shard.cid().unwrap(), shard)
+            })
+            .collect();
+        let missing = Cid::new([0xEE; 32]);
+        let cids = [batch[0].0, missing, batch[2].0];
+
+        for storage in backends {
+            storage.put_shards(&batch).await.unwrap();
+            assert_eq!(
+                storage.has_shards(&cids).await.unwrap(),
+                vec![true, false, true]
+            );
+
+            let fetched = storage.get_shards(&cids).await.unwrap();
+            assert_eq!(fetched[0].as_ref().unwrap().data, vec![1u8; 4]);
+            assert!(fetched[1].is_none());
+            assert_eq!(fetched[2].as_ref().unwrap().data, vec![3u8; 4]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shard_streaming() {
+        let temp_dir = TempDir::new().unwrap();
+        let local = LocalStorage::new(temp_dir.path().to_path_buf())
+            .await
+            .unwrap();
+        let memory = MemoryStorage::new();
+        let backends: [&dyn StorageBackend; 2] = [&local, &memory];
+
+        let payload: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let header = ShardHeader::new(
+            EncryptionMode::Convergent,
+            (4, 2),
+            payload.len() as u32,
+            [0u8; 32],
+        );
+        let cid = Shard::new(header.clone(), payload.clone()).cid().unwrap();
+
+        for storage in backends {
+            let mut reader = payload.as_slice();
+            storage
+                .put_shard_stream(&cid, &header, &mut reader, payload.len() as u64)
+                .await
+                .unwrap();
+
+            let (read_header, mut reader) = storage.get_shard_stream(&cid).await.unwrap();
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).await.unwrap();
+            assert_eq!(read_header.data_size, header.data_size);
+            assert_eq!(data, payload);
+        }
+
+        // A truncated stream must not leave a shard behind
+        let other = Cid::new([0xAB; 32]);
+        let mut short = &payload[..10];
+        assert!(local
+            .put_shard_stream(&other, &header, &mut short, payload.len() as u64)
+            .await
+            .is_err());
+        assert!(!local.has_shard(&other).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_paged_listing() {
+        let temp_dir = TempDir::new().unwrap();
+        let local = LocalStorage::new(temp_dir.path().to_path_buf())
+            .await
+            .unwrap();
+        let memory = MemoryStorage::new();
+        let backends: [&dyn StorageBackend; 2] = [&local, &memory];
+
+        for storage in backends {
+            for i in 0..25u8 {
+                let header = ShardHeader::new(EncryptionMode::Convergent, (2, 1), 1, [i; 32]);
+                let shard = Shard::new(header, vec![i]);
+                storage
+                    .put_shard(&shard.cid().unwrap(), &shard)
+                    .await
+                    .unwrap();
+            }
+
+            let mut listed = Vec::new();
+            let mut cursor = None;
+            loop {
+                let page = storage
+                    .list_shards_paged(cursor.as_ref(), 10)
+                    .await
+                    .unwrap();
+                assert!(page.cids.len() <= 10);
+                listed.extend(page.cids);
+                match page.next {
+                    Some(next) => cursor = Some(next),
+                    None => break,
+                }
+            }
+
+            let mut expected = storage.list_shards().await.unwrap();
+            expected.sort();
+            assert_eq!(listed, expected);
+            assert_eq!(listed.len(), 25);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_local_storage_list() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = LocalStorage::new(temp_dir.path().to_path_buf())
+            .await
+            .unwrap();
+
+        // Store multiple shards
+        let mut shards = Vec::new();
+        let mut cids = Vec::new();
+
+        for i in 1..=3 {
+            let header = ShardHeader::new(EncryptionMode::Convergent, (16, 4), 4, [i; 32]);
+            let shard = Shard::new(header, b"data".to_vec());
+            let cid = shard.cid().unwrap();
+            storage.put_shard(&cid, &shard).await.unwrap();
+            shards.push(shard);
+            cids.push(cid);
+        }
+
+        // List shards
+        let listed = storage.list_shards().await.unwrap();
+        assert_eq!(listed.len(), 3);
+
+        for cid in cids {
+            assert!(listed.contains(&cid));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_local_storage_durability_policies() {
+        let policies = [
+            DurabilityPolicy::max_durability(),
+            DurabilityPolicy::max_throughput(),
+            DurabilityPolicy {
+                sync: SyncPolicy::Batched { max_pending: 3 },
+                sync_dir: true,
+                direct_io: true,
+            },
+        ];
+
+        for policy in policies {
+            let temp_dir = TempDir::new().unwrap();
+            let storage = LocalStorage::new(temp_dir.path().to_path_buf())
+                .await
+                .unwrap()
+                .with_durability(policy);
+            assert_eq!(storage.durability(), policy);
+
+            for i in 0..4u8 {
+                // Sizes straddling the direct I/O alignment
+                let data = vec![i; 4000 + i as usize * 100];
+                let header = ShardHeader::new(
+                    EncryptionMode::Convergent,
+                    (16, 4),
+                    data.len() as u32,
+                    [i; 32],
+                );
+                let shard = Shard::new(header, data);
+                let cid = shard.cid().unwrap();
+                storage.put_shard(&cid, &shard).await.unwrap();
+                assert_eq!(storage.get_shard(&cid).await.unwrap().data, shard.data);
+            }
+
+            let pending = storage.pending_sync.lock().unwrap().len();
+            match policy.sync {
+                SyncPolicy::Batched { .. } => assert_eq!(pending, 1),
+                _ => assert_eq!(pending, 0),
+            }
+            storage.flush().await.unwrap();
+            assert!(storage.pending_sync.lock().unwrap().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_local_storage_journal_recovery() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path().to_path_buf();
+        let storage = LocalStorage::new(base.clone()).await.unwrap();
+
+        let shards: Vec<(Cid, Shard)> = (0..3u8)
+            .map(|i| {
+                let header = ShardHeader::new(EncryptionMode::Convergent, (2, 1), 1, [i; 32]);
+                let shard = Shard::new(header, vec![i]);
+                (shard.cid().unwrap(), shard)
+            })
+            .collect();
+        let metadata = FileMetadata::new(
+            [9u8; 32],
+            2,
+            vec![ChunkMeta::new(
+                (2, 1),
+                EncryptionMode::Convergent,
+                shards[..2].iter().map(|(cid, _)| cid.to_hex()).collect(),
+            )],
+        );
+
+        let mut txn = storage.begin();
+        txn.put_shard(shards[0].0, shards[0].1.clone())
+            .put_shard(shards[1].0, shards[1].1.clone())
+            .put_metadata(metadata.clone());
+        txn.commit().await.unwrap();
+        assert!(storage.has_shard(&shards[1].0).await.unwrap());
+        assert!(storage.get_metadata(&[9u8; 32]).await.is_ok());
+        assert_eq!(std::fs::read_dir(storage.journal_dir()).unwrap().count(), 0);
+        drop(storage);
+
+        // Simulate a crash after the commit record was written but before
+        // the staged shard was moved, and another before any record existed
+        let journal = base.join("journal");
+        std::fs::create_dir_all(journal.join("committed")).unwrap();
+        std::fs::write(
+            journal.join("committed").join("0.shard"),
+            shards[2].1.to_bytes().unwrap(),
+        )
+        .unwrap();
+        let hex = shards[2].0.to_hex();
+        let target = format!("shards/{}/{}/{}.shard", &hex[0..2], &hex[2..4], hex);
+        let record = serde_json::to_vec(&vec![("journal/committed/0.shard", target)]).unwrap();
+        std::fs::write(journal.join("committed.commit"), record).unwrap();
+        std::fs::create_dir_all(journal.join("aborted")).unwrap();
+        std::fs::write(journal.join("aborted").join("0.shard"), b"partial").unwrap();
+
+        let storage = LocalStorage::new(base).await.unwrap();
+        assert_eq!(storage.get_shard(&shards[2].0).await.unwrap().data, vec![2]);
+        assert_eq!(storage.list_shards().await.unwrap().len(), 3);
+        assert_eq!(std::fs::read_dir(journal).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_local_storage_secure_delete() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = LocalStorage::new(temp_dir.path().to_path_buf())
+            .await
+            .unwrap()
+            .with_secure_delete(true);
+
+        let header = ShardHeader::new(EncryptionMode::Convergent, (16, 4), 1024, [1u8; 32]);
+        let shard = Shard::new(header, vec![0xAB; 1024]);
+        let cid = shard.cid().unwrap();
+        storage.put_shard(&cid, &shard).await.unwrap();
+
+        // A second link keeps the inode alive so the erased bytes can be seen
+        let witness = temp_dir.path().join("witness");
+        std::fs::hard_link(storage.shard_path(&cid), &witness).unwrap();
+        let original = std::fs::read(&witness).unwrap();
+
+        storage.delete_shard(&cid).await.unwrap();
+        assert!(!storage.has_shard(&cid).await.unwrap());
+        let erased = std::fs::read(&witness).unwrap();
+        assert_eq!(erased.len(), original.len());
+        assert_ne!(erased, original);
+    }
+
+    #[tokio::test]
+    async fn test_local_storage_layout_migration() {
+        let temp_dir = TempDir::new().unwrap();
+        let base = temp_dir.path().to_path_buf();
+        let storage = LocalStorage::new(base.clone()).await.unwrap();
+        assert_eq!(storage.shard_levels(), 2);
+
+        let mut shards = Vec::new();
+        for i in 0..5u8 {
+            let header = ShardHeader::new(EncryptionMode::Convergent, (16, 4), 1, [i; 32]);
+            let shard = Shard::new(header, vec![i]);
+            let cid = shard.cid().unwrap();
+            storage.put_shard(&cid, &shard).await.unwrap();
+            shards.push((cid, shard));
+        }
+        drop(storage);
+
+        let storage = LocalStorage::with_shard_levels(base.clone(), 3)
+            .await
+            .unwrap();
+        for (cid, shard) in &shards {
+            let hex = cid.to_hex();
+            let expected = base
+                .join("shards")
+                .join(&hex[0..2])
+                .join(&hex[2..4])
+                .join(&hex[4..6])
+                .join(format!("{}.shard", hex));
+            assert!(expected.exists());
+            assert_eq!(storage.get_shard(cid).await.unwrap().data, shard.data);
+        }
+        drop(storage);
+
+        // The layout is recorded, so a plain reopen uses it
+        let mut storage = LocalStorage::new(base.clone()).await.unwrap();
+        assert_eq!(storage.shard_levels(), 3);
+        assert_eq!(storage.migrate_layout(0).await.unwrap(), 5);
+        assert_eq!(storage.migrate_layout(0).await.unwrap(), 0);
+        assert_eq!(storage.list_shards().await.unwrap().len(), 5);
+        for (cid, _) in &shards {
+            assert!(storage.has_shard(cid).await.unwrap());
+        }
+
+        // Old fan-out directories are cleaned up
+        let mut entries = std::fs::read_dir(base.join("shards")).unwrap();
+        assert!(entries.all(|e| e.unwrap().path().is_file()));
+        assert!(storage.migrate_layout(9).await.is_err());
+    }
+
+    #[test]
+    fn test_network_storage_node_selection() {
+        let nodes = vec![
+            NodeEndpoint {
+                address: "node1".to_string(),
+                port: 8080,
+                node_id: None,
+            },
+            NodeEndpoint {
+                address: "node2".to_string(),
+                port: 8080,
+                node_id: None,
+            },
+            NodeEndpoint {
+                address: "node3".to_string(),
+                port: 8080,
+                node_id: None,
+            },
+        ];
+
+        let storage = NetworkStorage::new(nodes, 2);
+
+        let shard_id = [42u8; 32];
+        let selected = storage.select_nodes(&shard_id);
+
+        assert_eq!(selected.len(), 2);
+
+        // Should select same nodes for same shard ID
+        let selected2 = storage.select_nodes(&shard_id);
+        assert_eq!(selected, selected2);
+
+        // Different shard should select different nodes (probably)
+        let shard_id2 = [99u8; 32];
+        let selected3 = storage.select_nodes(&shard_id2);
+        // May or may not be different, but should be deterministic
+        assert_eq!(selected3.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_network_storage_requires_transport() {
+        let nodes = vec![NodeEndpoint {
+            address: "node1".to_string(),
+            port: 8080,
+            node_id: None,
+        }];
+        let storage = NetworkStorage::new(nodes, 1);
+
+        let header = ShardHeader::new(EncryptionMode::Convergent, (4, 2), 4, [0u8; 32]);
+        let shard = Shard::new(header, vec![0u8; 4]);
+        let cid = shard.cid().unwrap();
+
+        let err = storage.put_shard(&cid, &shard).await.unwrap_err();
+        assert!(err.to_string().contains("transport"));
+        assert!(storage.get_shard(&cid).await.is_err());
+    }
+
+    /// Transport routing requests to in-memory nodes keyed by port; nodes
+    /// listed in `down` refuse every request
+    struct LoopbackTransport {
+        nodes: HashMap<u16, MemoryStorage>,
+        down: RwLock<Vec<u16>>,
+    }
+
+    #[async_trait]
+    impl NodeTransport for LoopbackTransport {
+        async fn call(&self, node: &NodeEndpoint, request: Request) -> Result<Response, FecError> {
+            if self.down.read().unwrap().contains(&node.port) {
+                return Err(FecError::Backend("node unreachable".to_string()));
+            }
+            let backend = &self.nodes[&node.port];
+            Ok(crate::network::handle_request(backend, request).await)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_network_storage_quorum_and_read_repair() {
+        let ports = [1u16, 2, 3];
+        let transport = Arc::new(LoopbackTransport {
+            nodes: ports.iter().map(|&p| (p, MemoryStorage::new())).collect(),
+            down: RwLock::new(vec![3]),
+        });
+        let endpoints = endpoints(ports);
+
+        let header = ShardHeader::new(EncryptionMode::Convergent, (4, 2), 8, [0u8; 32]);
+        let shard = Shard::new(header, vec![9u8; 8]);
+        let cid = shard.cid().unwrap();
+
+        // W=3 cannot be met with one node down, W=2 can
+        let strict = NetworkStorage::new(endpoints.clone(), 3)
+            .with_quorum(3, 1)
+            .with_transport(transport.clone());
+        let err = strict.put_shard(&cid, &shard).await.unwrap_err();
+        assert!(err.to_string().contains("Write quorum"));
+
+        let storage = NetworkStorage::new(endpoints, 3)
+            .with_quorum(2, 3)
+            .with_transport(transport.clone());
+        storage.put_shard(&cid, &shard).await.unwrap();
+
+        // The node that missed the write comes back; a full-quorum read repairs it
+        transport.down.write().unwrap().clear();
+        assert!(!transport.nodes[&3].has_shard(&cid).await.unwrap());
+        let err = storage.get_shard(&cid).await.unwrap_err();
+        assert!(err.to_string().contains("Read quorum"));
+        assert_eq!(storage.read_repairs(), 1);
+        assert!(transport.nodes[&3].has_shard(&cid).await.unwrap());
+        assert_eq!(storage.get_shard(&cid).await.unwrap().data, shard.data);
+    }
+
+    fn endpoints(ports: impl IntoIterator<Item = u16>) -> Vec<NodeEndpoint> {
+        ports
+            .into_iter()
+            .map(|port| NodeEndpoint {
+                address: "127.0.0.1".to_string(),
+                port,
+                node_id: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rendezvous_selection_moves_few_keys() {
+        let before = NetworkStorage::new(endpoints(1..=10), 2);
+        let mut after = NetworkStorage::new(endpoints(1..=10), 2);
+        after.add_node(endpoints([11])[0].clone());
+
+        let mut moved = 0;
+        for i in 0..1000u32 {
+            let key = *blake3::hash(&i.to_le_bytes()).as_bytes();
+            let old: Vec<_> = before.select_nodes(&key).into_iter().cloned().collect();
+            let new = after.select_nodes(&key);
+            assert_eq!(new.len(), 2);
+            moved += new.iter().filter(|node| !old.contains(node)).count();
+        }
+
+        // Ideal is 2000 / 11 ≈ 182 replicas; a modulo scheme moves almost all
+        assert!(moved < 300, "moved {} replicas", moved);
+    }
+
+    #[tokio::test]
+    async fn test_network_storage_rebalance() {
+        let transport = Arc::new(LoopbackTransport {
+            nodes: (1..=4).map(|p| (p, MemoryStorage::new())).collect(),
+            down: RwLock::new(Vec::new()),
+        });
+        let mut storage =
+            NetworkStorage::new(endpoints(1..=3), 1).with_transport(transport.clone());
+
+        let mut cids = Vec::new();
+        for i in 0..40u8 {
+            let header = ShardHeader::new(EncryptionMode::Convergent, (4, 2), 4, [i; 32]);
+            let shard = Shard::new(header, vec![i; 4]);
+            let cid = shard.cid().unwrap();
+            storage.put_shard(&cid, &shard).await.unwrap();
+            cids.push(cid);
+        }
+
+        // Grow to four nodes, then retire node 1
+        storage.add_node(endpoints([4])[0].clone());
+        let report = storage.rebalance().await.unwrap();
+        assert!(report.shards_copied > 0);
+        assert_eq!(report.shards_copied, report.shards_removed);
+        assert!(storage.remove_node(&endpoints([1])[0]));
+        storage.rebalance().await.unwrap();
+
+        assert_eq!(transport.nodes[&1].shard_count(), 0);
+        for cid in &cids {
+            let owner = storage.select_nodes(cid.as_bytes())[0].port;
+            assert!(transport.nodes[&owner].has_shard(cid).await.unwrap());
+            assert!(storage.get_shard(cid).await.is_ok());
+        }
+        let total: usize = (2..=4).map(|p| transport.nodes[&p].shard_count()).sum();
+        assert_eq!(total, cids.len());
+    }
+
+    #[tokio::test]
+    async fn test_network_storage_skips_unhealthy_nodes() {
+        let transport = Arc::new(LoopbackTransport {
+            nodes: (1..=2).map(|p| (p, MemoryStorage::new())).collect(),
+            down: RwLock::new(vec![2]),
+        });
+        let storage = NetworkStorage::new(endpoints(1..=2), 2)
+            .with_quorum(1, 1)
+            .with_health_policy(HealthPolicy {
+                failure_threshold: 1,
+                retry_after: std::time::Duration::from_secs(3600),
+            })
+            .with_transport(transport.clone());
+
+        let health = storage.check_health().await;
+        assert!(health[0].healthy && health[0].latency.is_some());
+        assert!(!health[1].healthy);
+        assert!(health[1]
+            .last_error
+            .as_ref()
+            .unwrap()
+            .contains("node unreachable"));
+
+        // Node 2 recovers but stays skipped for writes until the cooldown ends
+        transport.down.write().unwrap().clear();
+        let header = ShardHeader::new(EncryptionMode::Convergent, (4, 2), 4, [0u8; 32]);
+        let shard = Shard::new(header, vec![1u8; 4]);
+        let cid = shard.cid().unwrap();
+        storage.put_shard(&cid, &shard).await.unwrap();
+        assert!(!transport.nodes[&2].has_shard(&cid).await.unwrap());
+
+        let locations = storage.shard_locations(&cid);
+        assert_eq!(locations.len(), 2);
+        assert_eq!(locations.iter().filter(|h| h.healthy).count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_multi_storage() {
+        let temp_dir1 = TempDir::new().unwrap();
+        let temp_dir2 = TempDir::new().unwrap();
+
+        let backend1 = Arc::new(
+            LocalStorage::new(temp_dir1.path().to_path_buf())
+                .await
+                .unwrap(),
+        );
+        let backend2 = Arc::new(
+            LocalStorage::new(temp_dir2.path().to_path_buf())
+                .await
+                .unwrap(),
+        );
+
+        let multi = MultiStorage::new(vec![backend1.clone(), backend2.clone()]);
+
+        let header = ShardHeader::new(EncryptionMode::Convergent, (16, 4), 9, [42u8; 32]);
+        let shard = Shard::new(header, b"Test data".to_vec());
+        let cid = shard.cid().unwrap();
+
+        // Store through multi-backend
+        multi.put_shard(&cid, &shard).await.unwrap();
+
+        // Verify both backends have the shard
+        assert!(backend1.has_shard(&cid).await.unwrap());
+        assert!(backend2.has_shard(&cid).await.unwrap());
+
+        // Delete from first backend
+        backend1.delete_shard(&cid).await.unwrap();
+
+        // Multi-backend should still find it in second backend
+        let retrieved = multi.get_shard(&cid).await.unwrap();
+        assert_eq!(retrieved.data, shard.data);
+    }
+
+    #[tokio::test]
+    async fn test_verified_read_heals_corrupted_copy() {
+        let primary = Arc::new(MemoryStorage::new());
+        let replica = Arc::new(MemoryStorage::new());
+        let multi = MultiStorage::new(vec![primary.clone(), replica.clone()]);
+
+        let header = ShardHeader::new(EncryptionMode::Convergent, (16, 4), 9, [7u8; 32]);
+        let shard = Shard::new(header.clone(), b"Good data".to_vec());
+        let cid = shard.cid().unwrap();
+        multi.put_shard(&cid, &shard).await.unwrap();
+
+        // Bit rot on the primary copy
+        let rotten = Shard::new(header, b"Bad! data".to_vec());
+        primary.put_shard(&cid, &rotten).await.unwrap();
+        let err = primary.get_shard_verified(&cid).await.unwrap_err();
+        assert!(matches!(err, FecError::CorruptShard { .. }));
+
+        let retrieved = multi.get_shard_verified(&cid).await.unwrap();
+        assert_eq!(retrieved.data, shard.data);
+        assert_eq!(multi.shards_healed(), 1);
+        assert_eq!(primary.get_shard(&cid).await.unwrap().data, shard.data);
+
+        // With no good copy left the corruption is reported
+        primary.put_shard(&cid, &rotten).await.unwrap();
+        replica.put_shard(&cid, &rotten).await.unwrap();
+        let err = multi.get_shard_verified(&cid).await.unwrap_err();
+        assert!(matches!(err, FecError::CorruptShard { .. }));
+    }
+
+    /// Memory backend whose deletions fail while `fail_deletes` is set
+    #[derive(Default)]
+    struct StubbornStorage {
+        inner: MemoryStorage,
+        fail_deletes: std::sync::atomic::AtomicBool,
+    }
+
+    #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+    impl StorageBackend for StubbornStorage {
+        async fn put_shard(&self, cid: &Cid, shard: &Shard) -> Result<(), FecError> {
+            self.inner.put_shard(cid, shard).await
+        }
+        async fn get_shard(&self, cid: &Cid) -> Result<Shard, FecError> {
+            self.inner.get_shard(cid).await
+        }
+        async fn delete_shard(&self, cid: &Cid) -> Result<(), FecError> {
+            if self.fail_deletes.load(Ordering::SeqCst) {
+                return Err(FecError::Backend("Backend unavailable".to_string()));
+            }
+            self.inner.delete_shard(cid).await
+        }
+        async fn has_shard(&self, cid: &Cid) -> Result<bool, FecError> {
+            self.inner.has_shard(cid).await
+        }
+        async fn list_shards(&self) -> Result<Vec<Cid>, FecError> {
+            self.inner.list_shards().await
+        }
+        async fn put_metadata(&self, metadata: &FileMetadata) -> Result<(), FecError> {
+            self.inner.put_metadata(metadata).await
+        }
+        async fn get_metadata(&self, file_id: &[u8; 32]) -> Result<FileMetadata, FecError> {
+            self.inner.get_metadata(file_id).await
+        }
+        async fn delete_metadata(&self, file_id: &[u8; 32]) -> Result<(), FecError> {
+            self.inner.delete_metadata(file_id).await
+        }
+        async fn list_metadata(&self) -> Result<Vec<FileMetadata>, FecError> {
+            self.inner.list_metadata().await
+        }
+        async fn stats(&self) -> Result<StorageStats, FecError> {
+            self.inner.stats().await
+        }
+        async fn garbage_collect(&self) -> Result<GcReport, FecError> {
+            self.inner.garbage_collect().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_multi_gc_keeps_shards_referenced_on_other_backends() {
+        let first = Arc::new(MemoryStorage::new());
+        let second = Arc::new(MemoryStorage::new());
+        let multi = MultiStorage::new(vec![first.clone(), second.clone()]);
+
+        let header = ShardHeader::new(EncryptionMode::Convergent, (16, 4), 10, [1u8; 32]);
+        let orphan = Shard::new(header, b"Unreferenced".to_vec());
+        let orphan_cid = orphan.cid().unwrap();
+        multi.put_shard(&orphan_cid, &orphan).await.unwrap();
+
+        // The shard lives on one backend, the metadata needing it on the other
+        let header = ShardHeader::new(EncryptionMode::Convergent, (16, 4), 10, [2u8; 32]);
+        let needed = Shard::new(header, b"Referenced".to_vec());
+        let needed_cid = needed.cid().unwrap();
+        second.put_shard(&needed_cid, &needed).await.unwrap();
+        let metadata = FileMetadata::new(
+            [1u8; 32],
+            1024,
+            vec![ChunkMeta::new(
+                (16, 4),
+                EncryptionMode::Convergent,
+                vec![needed_cid.to_hex()],
+            )],
+        );
+        first.put_metadata(&metadata).await.unwrap();
+
+        let report = multi.garbage_collect().await.unwrap();
+        assert_eq!(report.shards_deleted, 2);
+        assert!(second.has_shard(&needed_cid).await.unwrap());
+        assert!(!multi.has_shard(&orphan_cid).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_multi_tracks_and_retries_partial_deletions() {
+        let healthy = Arc::new(MemoryStorage::new());
+        let stubborn = Arc::new(StubbornStorage::default());
+        let multi = MultiStorage::new(vec![healthy.clone(), stubborn.clone()]);
+
+        let header = ShardHeader::new(EncryptionMode::Convergent, (16, 4), 4, [3u8; 32]);
+        let shard = Shard::new(header, b"Data".to_vec());
+        let cid = shard.cid().unwrap();
+        multi.put_shard(&cid, &shard).await.unwrap();
+
+        stubborn.fail_deletes.store(true, Ordering::SeqCst);
+        assert!(multi.delete_shard(&cid).await.is_err());
+        let pending = multi.pending_deletions();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].cid, cid);
+        assert_eq!(pending[0].failures.len(), 1);
+        assert_eq!(pending[0].failures[0].0, 1);
+        assert!(!healthy.has_shard(&cid).await.unwrap());
+
+        // Retries reach only the backend that still holds the shard
+        assert_eq!(multi.retry_deletions().await.unwrap(), 0);
+        stubborn.fail_deletes.store(false, Ordering::SeqCst);
+        assert_eq!(multi.retry_deletions().await.unwrap(), 1);
+        assert!(multi.pending_deletions().is_empty());
+        assert!(!stubborn.has_shard(&cid).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_multi_cancels_pending_deletion_of_reused_shard() {
+        let healthy = Arc::new(MemoryStorage::new());
+        let stubborn = Arc::new(StubbornStorage::default());
+        let multi = MultiStorage::new(vec![healthy.clone(), stubborn.clone()]);
+
+        let header = ShardHeader::new(EncryptionMode::Convergent, (16, 4), 4, [4u8; 32]);
+        let shard = Shard::new(header, b"Data".to_vec());
+        let cid = shard.cid().unwrap();
+        multi.put_shard(&cid, &shard).await.unwrap();
+        stubborn.fail_deletes.store(true, Ordering::SeqCst);
+        assert!(multi.delete_shard(&cid).await.is_err());
+        stubborn.fail_deletes.store(false, Ordering::SeqCst);
+
+        // New metadata needs the surviving copy before the retry
+        let metadata = FileMetadata::new(
+            [4u8; 32],
+            4,
+            vec![ChunkMeta::new(
+                (16, 4),
+                EncryptionMode::Convergent,
+                vec![cid.to_hex()],
+            )],
+        );
+        healthy.put_metadata(&metadata).await.unwrap();
+        assert_eq!(multi.retry_deletions().await.unwrap(), 0);
+        assert!(multi.pending_deletions().is_empty());
+        assert!(multi.get_shard(&cid).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_memory_storage() {
+        let storage = MemoryStorage::new();
+
+        let header = ShardHeader::new(EncryptionMode::Convergent, (16, 4), 11, [1u8; 32]);
+        let shard = Shard::new(header, b"Memory test".to_vec());
+        let cid = shard.cid().unwrap();
+
+        // Store shard
+        storage.put_shard(&cid, &shard).await.unwrap();
+
+        // Verify it exists
+        assert!(storage.has_shard(&cid).await.unwrap());
+        assert_eq!(storage.shard_count(), 1);
+
+        // Retrieve shard
+        let retrieved = storage.get_shard(&cid).await.unwrap();
+        assert_eq!(retrieved.data, shard.data);
+
+        // Test metadata
+        let metadata = FileMetadata::new(
+            [1u8; 32],
+            1024,
+            vec![ChunkMeta::new(
+                (16, 4),
+                EncryptionMode::Convergent,
+                vec![cid.to_hex()],
+            )],
+        );
+
+        storage.put_metadata(&metadata).await.unwrap();
+        assert_eq!(storage.metadata_count(), 1);
+
+        let retrieved_meta = storage.get_metadata(&metadata.file_id).await.unwrap();
+        assert_eq!(retrieved_meta.file_id, metadata.file_id);
+
+        // Clear storage
+        storage.clear();
+        assert_eq!(storage.shard_count(), 0);
+        assert_eq!(storage.metadata_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_memory_storage_capacity_limit() {
+        let shard_size = ShardHeader::SIZE as u64 + 100;
+        let storage = MemoryStorage::with_capacity(2 * shard_size);
+        let shards: Vec<Shard> = (0..3u8)
+            .map(|i| {
+                let header = ShardHeader::new(EncryptionMode::Convergent, (4, 2), 100, [i; 32]);
+                Shard::new(header, vec![i; 100])
+            })
+            .collect();
+
+        for shard in &shards[..2] {
+            storage
+                .put_shard(&shard.cid().unwrap(), shard)
+                .await
+                .unwrap();
+        }
+        assert_eq!(storage.used_bytes(), 2 * shard_size);
+
+        // Rewriting an existing shard does not need extra room
+        storage
+            .put_shard(&shards[0].cid().unwrap(), &shards[0])
+            .await
+            .unwrap();
+
+        let third = shards[2].cid().unwrap();
+        let err = storage.put_shard(&third, &shards[2]).await.unwrap_err();
+        assert!(matches!(
+            err,
+            FecError::CapacityExceeded { available: 0, .. }
+        ));
+
+        // Deleting frees space, and clones share the same contents
+        let clone = storage.clone();
+        clone.delete_shard(&shards[0].cid().unwrap()).await.unwrap();
+        storage.put_shard(&third, &shards[2]).await.unwrap();
+        assert_eq!(clone.shard_count(), 2);
+        assert_eq!(storage.used_bytes(), 2 * shard_size);
+    }
+
+    #[tokio::test]
+    async fn test_garbage_collection() {
+        let storage = MemoryStorage::new();
+
+        // Create unreferenced shard
+        let header1 = ShardHeader::new(EncryptionMode::Convergent, (16, 4), 10, [1u8; 32]);
+        let shard1 = Shard::new(header1, b"Unreferenced".to_vec());
+        let cid1 = shard1.cid().unwrap();
+        storage.put_shard(&cid1, &shard1).await.unwrap();
+
+        // Create referenced shard
+        let header2 = ShardHeader::new(EncryptionMode::Convergent, (16, 4), 10, [2u8; 32]);
+        let shard2 = Shard::new(header2, b"Referenced".to_vec());
+        let cid2 = shard2.cid().unwrap();
+        storage.put_shard(&cid2, &shard2).await.unwrap();
+
+        // Create metadata referencing only shard2
+        let metadata = FileMetadata::new(
+            [1u8; 32],
+            1024,
+            vec![ChunkMeta::new(
+                (16, 4),
+                EncryptionMode::Convergent,
+                vec![cid2.to_hex()],
+            )],
+        );
+        storage.put_metadata(&metadata).await.unwrap();
+
+        // Run garbage collection
+        let gc_report = storage.garbage_collect().await.unwrap();
+
+        assert_eq!(gc_report.shards_deleted, 1);
+        assert!(gc_report.bytes_freed > 0);
+        assert!(!storage.has_shard(&cid1).await.unwrap()); // Unreferenced shard deleted
+        assert!(storage.has_shard(&cid2).await.unwrap()); // Referenced shard kept
+    }
+
+    #[tokio::test]
+    async fn test_expired_shards_skipped_and_reaped() {
+        let temp_dir = TempDir::new().unwrap();
+        let local = LocalStorage::new(temp_dir.path().to_path_buf())
+            .await
+            .unwrap();
+        let memory = MemoryStorage::new();
+        let backends: [&dyn StorageBackend; 2] = [&local, &memory];
+
+        for storage in backends {
+            let expired_header =
+                ShardHeader::new(EncryptionMode::Convergent, (16, 4), 7, [1u8; 32]).with_expiry(1);
+            assert_eq!(expired_header.expires_at(), Some(1));
+            let expired = Shard::new(expired_header, b"expired".to_vec());
+            let expired_cid = expired.cid().unwrap();
+
+            let live_header = ShardHeader::new(EncryptionMode::Convergent, (16, 4), 4, [2u8; 32])
+                .with_expiry(u64::MAX);
+            let live = Shard::new(live_header, b"live".to_vec());
+            let live_cid = live.cid().unwrap();
+
+            // The expiry survives serialization and changes the CID
+            let bytes = expired.to_bytes().unwrap();
+            assert_eq!(
+                Shard::from_bytes(&bytes).unwrap().header.expires_at(),
+                Some(1)
+            );
+            assert_ne!(
+                expired_cid,
+                Shard::new(
+                    ShardHeader::new(EncryptionMode::Convergent, (16, 4), 7, [1u8; 32]),
+                    b"expired".to_vec()
+                )
+                .cid()
+                .unwrap()
+            );
+
+            storage.put_shard(&expired_cid, &expired).await.unwrap();
+            storage.put_shard(&live_cid, &live).await.unwrap();
+            let metadata = FileMetadata::new(
+                [3u8; 32],
+                11,
+                vec![ChunkMeta::new(
+                    (16, 4),
+                    EncryptionMode::Convergent,
+                    vec![expired_cid.to_hex(), live_cid.to_hex()],
+                )],
+            );
+            storage.put_metadata(&metadata).await.unwrap();
+
+            assert!(storage.get_shard(&expired_cid).await.is_err());
+            assert_eq!(storage.get_shard(&live_cid).await.unwrap().data, live.data);
+            let fetched = storage.get_shards(&[expired_cid, live_cid]).await.unwrap();
+            assert!(fetched[0].is_none() && fetched[1].is_some());
+
+            // Referenced or not, an expired shard is reaped
+            let report = storage.garbage_collect().await.unwrap();
+            assert_eq!(report.shards_deleted, 1);
+            assert!(!storage.has_shard(&expired_cid).await.unwrap());
+            assert!(storage.has_shard(&live_cid).await.unwrap());
+        }
+    }
+
+    #[test]
+    fn test_shard_header_serialization() {
+        let header = ShardHeader::new(
+            EncryptionMode::ConvergentWithSecret,
+            (20, 5),
+            2048,
+            [42u8; 32],
+        );
+
+        let bytes = header.to_bytes().unwrap();
+        assert_eq!(bytes.len(), ShardHeader::SIZE);
+
+        let deserialized = ShardHeader::from_bytes(&bytes).unwrap();
+        assert_eq!(deserialized.version, header.version);
+        assert_eq!(deserialized.encryption_mode, header.encryption_mode);
+        assert_eq!(deserialized.nspec, header.nspec);
+        assert_eq!(deserialized.data_size, header.data_size);
+        assert_eq!(deserialized.nonce, header.nonce);
+    }
+
+    #[test]
+    fn test_shard_cid_calculation() {
+        let header = ShardHeader::new(EncryptionMode::RandomKey, (16, 4), 1024, [0u8; 32]);
+        let shard = Shard::new(header, vec![1, 2, 3, 4, 5]);
+
+        let cid1 = shard.cid().unwrap();
+        let cid2 = shard.cid().unwrap();
+
+        // CID should be deterministic
+        assert_eq!(cid1, cid2);
+
+        // Different data should produce different CID
+        let shard2 = Shard::new(shard.header.clone(), vec![1, 2, 3, 4, 6]);
+        let cid3 = shard2.cid().unwrap();
+        assert_ne!(cid1, cid3);
+    }
+
+    #[test]
+    fn test_multi_storage_strategies() {
+        let backend1 = Arc::new(MemoryStorage::new());
+        let backend2 = Arc::new(MemoryStorage::new());
+
+        // Test different strategies
+        let redundant = MultiStorage::with_strategy(
+            vec![backend1.clone(), backend2.clone()],
+            MultiStorageStrategy::Redundant,
+        );
+
+        let load_balance = MultiStorage::with_strategy(
+            vec![backend1.clone(), backend2.clone()],
+            MultiStorageStrategy::LoadBalance,
+        );
+
+        let failover =
+            MultiStorage::with_strategy(vec![backend1, backend2], MultiStorageStrategy::Failover);
+
+        assert_eq!(redundant.backend_count(), 2);
+        assert_eq!(load_balance.backend_count(), 2);
+        assert_eq!(failover.backend_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_multi_storage_write_policies() {
+        let full = Arc::new(MemoryStorage::with_capacity(0));
+        let first = Arc::new(MemoryStorage::new());
+        let second = Arc::new(MemoryStorage::new());
+        let backends: Vec<Arc<dyn StorageBackend>> =
+            vec![full.clone(), first.clone(), second.clone()];
+
+        let shard = Shard::new(
+            ShardHeader::new(EncryptionMode::Convergent, (2, 1), 4, [5u8; 32]),
+            b"data".to_vec(),
+        );
+        let cid = shard.cid().unwrap();
+
+        // Failover stops at the first backend that accepts the write
+        let failover =
+            MultiStorage::with_strategy(backends.clone(), MultiStorageStrategy::Failover);
+        failover.put_shard(&cid, &shard).await.unwrap();
+        assert!(first.has_shard(&cid).await.unwrap());
+        assert!(!second.has_shard(&cid).await.unwrap());
+
+        // Two of three backends accept, so a quorum of two is met
+        let quorum = MultiStorage::with_strategy(backends.clone(), MultiStorageStrategy::Quorum(2));
+        quorum.put_shard(&cid, &shard).await.unwrap();
+        assert!(second.has_shard(&cid).await.unwrap());
+
+        // A quorum of three is not, and the error names the failing backend
+        let all = MultiStorage::with_strategy(backends.clone(), MultiStorageStrategy::Quorum(3));
+        let err = all.put_shard(&cid, &shard).await.unwrap_err().to_string();
+        assert!(err.contains("2 of 3"));
+        assert!(err.contains("backend 0: Storage capacity exceeded"));
+
+        // Striping sends each key to exactly one backend
+        let striped = MultiStorage::with_strategy(
+            vec![first.clone(), second.clone()],
+            MultiStorageStrategy::LoadBalance,
+        );
+        let other = Shard::new(
+            ShardHeader::new(EncryptionMode::Convergent, (2, 1), 4, [6u8; 32]),
+            b"more".to_vec(),
+        );
+        let other_cid = other.cid().unwrap();
+        striped.put_shard(&other_cid, &other).await.unwrap();
+        let copies = first.has_shard(&other_cid).await.unwrap() as u8
+            + second.has_shard(&other_cid).await.unwrap() as u8;
+        assert_eq!(copies, 1);
+        assert_eq!(
+            striped.get_shard(&other_cid).await.unwrap().data,
+            other.data
+        );
+    }
+
+    #[test]
+    fn test_cid_operations() {
+        let data = b"test data";
+        let cid1 = Cid::from_data(data);
+        let cid2 = Cid::from_data(data);
+
+        // Same data should produce same CID
+        assert_eq!(cid1, cid2);
+
+        // Different data should produce different CID
+        let cid3 = Cid::from_data(b"different data");
+        assert_ne!(cid1, cid3);
+
+        // Test hex representation
+        let hex = cid1.to_hex();
+        assert_eq!(hex.len(), 64); // 32 bytes * 2 hex chars per byte
+
+        // Test round-trip
+        let bytes = cid1.as_bytes();
+        let cid4 = Cid::new(*bytes);
+        assert_eq!(cid1, cid4);
+    }
+}
@@ -0,0 +1,267 @@
+//! Storage over an asynchronous object store
+//!
+//! Browser storage such as IndexedDB offers named object stores that map keys
+//! to byte values, with every access asynchronous. [`ObjectStore`] captures
+//! that interface and [`ObjectStoreStorage`] keeps shards and metadata in two
+//! such stores, so the pipeline can run without a filesystem, as in wasm32
+//! builds. [`MemoryObjectStore`] is an in-memory store for tests and as a
+//! reference implementation.
+
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+
+use super::{
+    Cid, FileMetadata, GcReport, MaybeSendSync, Shard, ShardHeader, StorageBackend, StorageStats,
+};
+use crate::FecError;
+
+/// An asynchronous map from 32-byte keys to byte values
+///
+/// Shards are keyed by CID and metadata by file ID, so keys are always 32
+/// bytes. On wasm32 neither the store nor its futures need be `Send`, so
+/// implementations can hold JavaScript handles, as `IndexedDbStore` does.
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait ObjectStore: MaybeSendSync {
+    /// Value stored under `key`, if any
+    async fn get(&self, key: &[u8; 32]) -> Result<Option<Vec<u8>>, FecError>;
+
+    /// Store `value` under `key`, replacing any previous value
+    async fn put(&self, key: &[u8; 32], value: Vec<u8>) -> Result<(), FecError>;
+
+    /// Remove the value under `key`; removing a missing key is not an error
+    async fn delete(&self, key: &[u8; 32]) -> Result<(), FecError>;
+
+    /// Every key in the store
+    async fn keys(&self) -> Result<Vec<[u8; 32]>, FecError>;
+}
+
+/// Object store held in memory
+#[derive(Debug, Default)]
+pub struct MemoryObjectStore {
+    entries: RwLock<BTreeMap<[u8; 32], Vec<u8>>>,
+}
+
+impl MemoryObjectStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl ObjectStore for MemoryObjectStore {
+    async fn get(&self, key: &[u8; 32]) -> Result<Option<Vec<u8>>, FecError> {
+        Ok(self.entries.read().get(key).cloned())
+    }
+
+    async fn put(&self, key: &[u8; 32], value: Vec<u8>) -> Result<(), FecError> {
+        self.entries.write().insert(*key, value);
+        Ok(())
+    }
+
+    async fn delete(&self, key: &[u8; 32]) -> Result<(), FecError> {
+        self.entries.write().remove(key);
+        Ok(())
+    }
+
+    async fn keys(&self) -> Result<Vec<[u8; 32]>, FecError> {
+        Ok(self.entries.read().keys().copied().collect())
+    }
+}
+
+/// Storage backend keeping shards and metadata in two object stores
+pub struct ObjectStoreStorage<S> {
+    shards: S,
+    metadata: S,
+}
+
+impl<S: ObjectStore> ObjectStoreStorage<S> {
+    /// Use `shards` for shard records and `metadata` for file metadata
+    pub fn new(shards: S, metadata: S) -> Self {
+        Self { shards, metadata }
+    }
+}
+
+impl ObjectStoreStorage<MemoryObjectStore> {
+    /// Backend over two empty in-memory stores
+    pub fn in_memory() -> Self {
+        Self::new(MemoryObjectStore::new(), MemoryObjectStore::new())
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl<S: ObjectStore> StorageBackend for ObjectStoreStorage<S> {
+    async fn put_shard(&self, cid: &Cid, shard: &Shard) -> Result<(), FecError> {
+        self.shards.put(cid.as_bytes(), shard.to_bytes()?).await
+    }
+
+    async fn get_shard(&self, cid: &Cid) -> Result<Shard, FecError> {
+        let bytes = self
+            .shards
+            .get(cid.as_bytes())
+            .await?
+            .ok_or_else(|| FecError::ShardNotFound { cid: cid.to_hex() })?;
+        super::reject_expired(cid, Shard::from_bytes(&bytes)?)
+    }
+
+    async fn delete_shard(&self, cid: &Cid) -> Result<(), FecError> {
+        self.shards.delete(cid.as_bytes()).await
+    }
+
+    async fn has_shard(&self, cid: &Cid) -> Result<bool, FecError> {
+        Ok(self.shards.get(cid.as_bytes()).await?.is_some())
+    }
+
+    async fn list_shards(&self) -> Result<Vec<Cid>, FecError> {
+        Ok(self
+            .shards
+            .keys()
+            .await?
+            .into_iter()
+            .map(Cid::new)
+            .collect())
+    }
+
+    async fn put_metadata(&self, metadata: &FileMetadata) -> Result<(), FecError> {
+        let serialized = bincode::serialize(metadata)
+            .map_err(|e| FecError::Backend(format!("Failed to serialize metadata: {}", e)))?;
+        self.metadata.put(&metadata.file_id, serialized).await
+    }
+
+    async fn get_metadata(&self, file_id: &[u8; 32]) -> Result<FileMetadata, FecError> {
+        let data = self
+            .metadata
+            .get(file_id)
+            .await?
+            .ok_or_else(|| FecError::MetadataNotFound {
+                file_id: hex::encode(file_id),
+            })?;
+        bincode::deserialize(&data)
+            .map_err(|e| FecError::Backend(format!("Failed to deserialize metadata: {}", e)))
+    }
+
+    async fn delete_metadata(&self, file_id: &[u8; 32]) -> Result<(), FecError> {
+        self.metadata.delete(file_id).await
+    }
+
+    async fn list_metadata(&self) -> Result<Vec<FileMetadata>, FecError> {
+        let mut metadata_list = Vec::new();
+        for key in self.metadata.keys().await? {
+            if let Some(data) = self.metadata.get(&key).await? {
+                if let Ok(metadata) = bincode::deserialize::<FileMetadata>(&data) {
+                    metadata_list.push(metadata);
+                }
+            }
+        }
+        Ok(metadata_list)
+    }
+
+    async fn stats(&self) -> Result<StorageStats, FecError> {
+        let metadata = self.list_metadata().await?;
+        let referenced_cids = super::referenced_cids(&metadata);
+
+        let mut total_shards = 0u64;
+        let mut total_size = 0u64;
+        let mut unreferenced_shards = 0u64;
+        for key in self.shards.keys().await? {
+            if let Some(bytes) = self.shards.get(&key).await? {
+                total_shards += 1;
+                total_size += bytes.len() as u64;
+                if !referenced_cids.contains(&Cid::new(key)) {
+                    unreferenced_shards += 1;
+                }
+            }
+        }
+
+        Ok(StorageStats {
+            total_shards,
+            total_size,
+            metadata_count: metadata.len() as u64,
+            unreferenced_shards,
+            capacity: None,
+            free_space: None,
+        })
+    }
+
+    async fn garbage_collect(&self) -> Result<GcReport, FecError> {
+        let start_time = std::time::Instant::now();
+        let referenced_cids = super::referenced_cids(&self.list_metadata().await?);
+
+        let mut shards_deleted = 0u64;
+        let mut bytes_freed = 0u64;
+        for key in self.shards.keys().await? {
+            let Some(bytes) = self.shards.get(&key).await? else {
+                continue;
+            };
+            let expired = bytes
+                .get(..ShardHeader::SIZE)
+                .and_then(|h| ShardHeader::from_bytes(h).ok())
+                .is_some_and(|h| h.is_expired());
+            if expired || !referenced_cids.contains(&Cid::new(key)) {
+                self.shards.delete(&key).await?;
+                shards_deleted += 1;
+                bytes_freed += bytes.len() as u64;
+            }
+        }
+
+        Ok(GcReport {
+            shards_deleted,
+            bytes_freed,
+            duration_ms: start_time.elapsed().as_millis() as u64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::ChunkMeta;
+    use crate::EncryptionMode;
+
+    fn test_shard(fill: u8) -> Shard {
+        let header = ShardHeader::new(EncryptionMode::Convergent, (4, 2), 64, [0u8; 32]);
+        Shard::new(header, vec![fill; 64])
+    }
+
+    #[tokio::test]
+    async fn test_object_store_storage_roundtrip_and_gc() {
+        let storage = ObjectStoreStorage::in_memory();
+        let shards: Vec<(Cid, Shard)> = (0..4u8)
+            .map(|i| {
+                let shard = test_shard(i);
+                (shard.cid().unwrap(), shard)
+            })
+            .collect();
+        storage.put_shards(&shards).await.unwrap();
+
+        let (cid, shard) = &shards[0];
+        assert!(storage.has_shard(cid).await.unwrap());
+        assert_eq!(storage.get_shard(cid).await.unwrap().data, shard.data);
+
+        // Reference only the first shard
+        let chunk = ChunkMeta::new((4, 2), EncryptionMode::Convergent, vec![cid.to_hex()]);
+        storage
+            .put_metadata(&FileMetadata::new([7u8; 32], 64, vec![chunk]))
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.get_metadata(&[7u8; 32]).await.unwrap().file_size,
+            64
+        );
+
+        let stats = storage.stats().await.unwrap();
+        assert_eq!(stats.total_shards, 4);
+        assert_eq!(stats.unreferenced_shards, 3);
+
+        let report = storage.garbage_collect().await.unwrap();
+        assert_eq!(report.shards_deleted, 3);
+        assert_eq!(storage.list_shards().await.unwrap(), vec![*cid]);
+
+        storage.delete_metadata(&[7u8; 32]).await.unwrap();
+        assert!(storage.get_metadata(&[7u8; 32]).await.is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! [`RepairHooks`] on top of a storage backend
+//!
+//! [`fec::maintain`](crate::fec::maintain) drives repair through the
+//! synchronous [`RepairHooks`] trait. [`StorageRepairHooks`] implements it for
+//! any [`StorageBackend`], tracking where each shard of one object lives in a
+//! [`ShardManifest`].
+//!
+//! Shards are stored content-addressed, so reseeding records each shard's CID
+//! in the manifest's `shard_keys`. Persist [`StorageRepairHooks::manifest`]
+//! after a reseed to find the shards again later.
+
+use parking_lot::RwLock;
+use std::future::Future;
+use tokio::runtime::Handle;
+
+use super::{Cid, Shard, ShardHeader, StorageBackend};
+use crate::config::EncryptionMode;
+use crate::fec::{self, Key, RepairHooks, ShardError, ShardManifest};
+
+/// Repair hooks reading and reseeding one object's shards in a storage backend
+pub struct StorageRepairHooks<B> {
+    backend: B,
+    manifest: RwLock<ShardManifest>,
+    handle: Handle,
+}
+
+impl<B: StorageBackend> StorageRepairHooks<B> {
+    /// Create hooks for the object described by `manifest`
+    ///
+    /// Must be called from within a Tokio runtime, which is then used to
+    /// drive the backend. Use [`with_handle`](Self::with_handle) otherwise.
+    pub fn new(backend: B, manifest: ShardManifest) -> Self {
+        Self::with_handle(backend, manifest, Handle::current())
+    }
+
+    /// Create hooks driving the backend on the given runtime
+    pub fn with_handle(backend: B, manifest: ShardManifest, handle: Handle) -> Self {
+        Self {
+            backend,
+            manifest: RwLock::new(manifest),
+            handle,
+        }
+    }
+
+    /// The wrapped backend
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Current manifest, including the CIDs of reseeded shards
+    pub fn manifest(&self) -> ShardManifest {
+        self.manifest.read().clone()
+    }
+
+    /// Run a backend future to completion from synchronous code
+    ///
+    /// Inside a multi-threaded runtime the worker is handed off with
+    /// `block_in_place` first; a current-thread runtime cannot be blocked on.
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        match Handle::try_current() {
+            Ok(_) => tokio::task::block_in_place(|| self.handle.block_on(future)),
+            Err(_) => self.handle.block_on(future),
+        }
+    }
+
+    fn check_key(&self, key: &Key) -> fec::Result<()> {
+        if *key != self.manifest.read().object_id {
+            return Err(ShardError::UnknownObject(hex::encode(key)));
+        }
+        Ok(())
+    }
+}
+
+impl<B: StorageBackend> RepairHooks for StorageRepairHooks<B> {
+    fn fetch_shards(&self, key: Key, need: usize) -> fec::Result<Vec<fec::Shard>> {
+        self.check_key(&key)?;
+        let shard_keys = self.manifest.read().shard_keys.clone();
+
+        self.block_on(async {
+            let mut shards = Vec::with_capacity(need);
+            for (idx, shard_key) in shard_keys.iter().enumerate() {
+                if shards.len() >= need {
+                    break;
+                }
+                let Ok(bytes) = <[u8; 32]>::try_from(shard_key.as_slice()) else {
+                    continue;
+                };
+                // Missing or corrupted shards count as lost and get reseeded
+                match self.backend.get_shard_verified(&Cid::new(bytes)).await {
+                    Ok(shard) => shards.push(fec::Shard::new(idx as u16, shard.data)),
+                    Err(e) => {
+                        tracing::debug!("Shard {} of {} unavailable: {}", idx, hex::encode(&key), e)
+                    }
+                }
+            }
+            Ok(shards)
+        })
+    }
+
+    fn reseed(&self, key: Key, shards: Vec<fec::Shard>) -> fec::Result<()> {
+        self.check_key(&key)?;
+        let params = self.manifest.read().params;
+
+        let mut stored = Vec::with_capacity(shards.len());
+        for shard in &shards {
+            if shard.idx >= params.total_shares() {
+                return Err(ShardError::IndexOutOfRange(shard.idx));
+            }
+            // The per-object storage key as nonce keeps identical shard data
+            // from different objects under distinct CIDs
+            let mut nonce = [0u8; 32];
+            nonce.copy_from_slice(&shard.storage_key(&key));
+            let header = ShardHeader::new(
+                EncryptionMode::Convergent,
+                (params.data_shares as u8, params.parity_shares as u8),
+                shard.data.len() as u32,
+                nonce,
+            );
+            let stored_shard = Shard::new(header, shard.data.clone());
+            stored.push((stored_shard.cid()?, stored_shard));
+        }
+
+        self.block_on(self.backend.put_shards(&stored))?;
+
+        let mut manifest = self.manifest.write();
+        for (shard, (cid, _)) in shards.iter().zip(&stored) {
+            manifest.shard_keys[shard.idx as usize] = cid.as_bytes().to_vec();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use crate::FecParams;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_maintain_restores_lost_shards() {
+        let params = FecParams::new(3, 2)
+            .and_then(|p| p.with_symbol_size(64))
+            .unwrap();
+        let object_id = b"object".to_vec();
+        let data: Vec<u8> = (0..192).map(|i| i as u8).collect();
+        let manifest = ShardManifest::new(object_id.clone(), params, data.len());
+        let hooks = StorageRepairHooks::new(MemoryStorage::new(), manifest);
+
+        hooks
+            .reseed(object_id.clone(), fec::encode(&data, params).unwrap())
+            .unwrap();
+        let manifest = hooks.manifest();
+        let cid = |idx: usize| Cid::new(manifest.shard_keys[idx].clone().try_into().unwrap());
+
+        // Lose a data and a parity shard, then let maintenance rebuild them
+        hooks.backend().delete_shard(&cid(0)).await.unwrap();
+        hooks.backend().delete_shard(&cid(4)).await.unwrap();
+        let survivors = hooks.fetch_shards(object_id.clone(), 5).unwrap();
+        assert_eq!(survivors.len(), 3);
+        assert_eq!(fec::decode(&survivors, params).unwrap(), data);
+
+        fec::maintain(object_id.clone(), params, &hooks).unwrap();
+        let shards = hooks.fetch_shards(object_id.clone(), 5).unwrap();
+        assert_eq!(shards.len(), 5);
+        assert_eq!(fec::decode(&shards, params).unwrap(), data);
+        // Rebuilt shards are identical to the lost ones
+        let restored = hooks.manifest();
+        assert_eq!(restored.shard_keys[0], manifest.shard_keys[0]);
+        assert_eq!(restored.shard_keys[4], manifest.shard_keys[4]);
+        assert!(hooks.fetch_shards(b"other".to_vec(), 5).is_err());
+    }
+}
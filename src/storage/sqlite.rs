@@ -0,0 +1,572 @@
+//! SQLite storage backend
+//!
+//! Keeps shards and metadata in a single SQLite database running in WAL mode.
+//! Besides the serialized records, each row carries a few plain columns (FEC
+//! parameters, sizes, timestamps) so the store can be inspected with the
+//! standard `sqlite3` tool.
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{
+    Cid, FileMetadata, GcReport, Shard, ShardHeader, ShardPage, StorageBackend, StorageStats,
+};
+use crate::FecError;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS shards (
+        cid TEXT PRIMARY KEY,
+        data_shards INTEGER NOT NULL,
+        parity_shards INTEGER NOT NULL,
+        size INTEGER NOT NULL,
+        shard BLOB NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS metadata (
+        file_id TEXT PRIMARY KEY,
+        file_size INTEGER NOT NULL,
+        created_at INTEGER NOT NULL,
+        metadata BLOB NOT NULL
+    );
+";
+
+/// Storage backend keeping shards and metadata in a SQLite database
+pub struct SqliteStorage {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStorage {
+    /// Open or create a database at the given path
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, FecError> {
+        let path = path.as_ref().to_path_buf();
+        let conn = tokio::task::spawn_blocking(move || -> Result<Connection, FecError> {
+            let conn = Connection::open(path).map_err(sql_error)?;
+            conn.pragma_update(None, "journal_mode", "WAL")
+                .map_err(sql_error)?;
+            // NORMAL is durable across application crashes in WAL mode
+            conn.pragma_update(None, "synchronous", "NORMAL")
+                .map_err(sql_error)?;
+            conn.execute_batch(SCHEMA).map_err(sql_error)?;
+            Ok(conn)
+        })
+        .await
+        .map_err(join_error)??;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Run a blocking database operation off the async runtime
+    async fn with_conn<T, F>(&self, f: F) -> Result<T, FecError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, FecError> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || f(&mut conn.lock()))
+            .await
+            .map_err(join_error)?
+    }
+}
+
+/// A shard ready to be written to the `shards` table
+struct ShardRow {
+    cid: String,
+    nspec: (u8, u8),
+    size: u32,
+    bytes: Vec<u8>,
+}
+
+impl ShardRow {
+    fn new(cid: &Cid, shard: &Shard) -> Result<Self, FecError> {
+        Ok(Self {
+            cid: cid.to_hex(),
+            nspec: shard.header.nspec,
+            size: shard.header.data_size,
+            bytes: shard.to_bytes()?,
+        })
+    }
+
+    fn insert(&self, conn: &Connection) -> Result<(), FecError> {
+        conn.execute(
+            "INSERT OR REPLACE INTO shards (cid, data_shards, parity_shards, size, shard)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![self.cid, self.nspec.0, self.nspec.1, self.size, self.bytes],
+        )
+        .map_err(sql_error)?;
+        Ok(())
+    }
+}
+
+/// A metadata record with its inspectable columns
+struct MetadataRow {
+    file_id: String,
+    file_size: u64,
+    created_at: u64,
+    bytes: Vec<u8>,
+}
+
+impl MetadataRow {
+    fn new(metadata: &FileMetadata) -> Result<Self, FecError> {
+        Ok(Self {
+            file_id: hex::encode(metadata.file_id),
+            file_size: metadata.file_size,
+            created_at: metadata.created_at,
+            bytes: bincode::serialize(metadata)
+                .map_err(|e| FecError::Backend(format!("Failed to serialize metadata: {}", e)))?,
+        })
+    }
+
+    fn insert(&self, conn: &Connection) -> Result<(), FecError> {
+        conn.execute(
+            "INSERT OR REPLACE INTO metadata (file_id, file_size, created_at, metadata)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                self.file_id,
+                self.file_size as i64,
+                self.created_at as i64,
+                self.bytes
+            ],
+        )
+        .map_err(sql_error)?;
+        Ok(())
+    }
+}
+
+fn sql_error(e: rusqlite::Error) -> FecError {
+    FecError::Backend(format!("SQLite error: {}", e))
+}
+
+fn join_error(e: tokio::task::JoinError) -> FecError {
+    FecError::Backend(format!("Database task failed: {}", e))
+}
+
+fn parse_cid(hex_cid: &str) -> Option<Cid> {
+    let mut cid = [0u8; 32];
+    hex::decode_to_slice(hex_cid, &mut cid).ok()?;
+    Some(Cid::new(cid))
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl StorageBackend for SqliteStorage {
+    async fn put_shard(&self, cid: &Cid, shard: &Shard) -> Result<(), FecError> {
+        let row = ShardRow::new(cid, shard)?;
+        self.with_conn(move |conn| row.insert(conn)).await
+    }
+
+    async fn get_shard(&self, cid: &Cid) -> Result<Shard, FecError> {
+        let key = cid.to_hex();
+        let bytes: Option<Vec<u8>> = self
+            .with_conn(move |conn| {
+                conn.query_row(
+                    "SELECT shard FROM shards WHERE cid = ?1",
+                    params![key],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(sql_error)
+            })
+            .await?;
+
+        let bytes = bytes.ok_or_else(|| FecError::ShardNotFound { cid: cid.to_hex() })?;
+        super::reject_expired(cid, Shard::from_bytes(&bytes)?)
+    }
+
+    async fn delete_shard(&self, cid: &Cid) -> Result<(), FecError> {
+        let key = cid.to_hex();
+        self.with_conn(move |conn| {
+            conn.execute("DELETE FROM shards WHERE cid = ?1", params![key])
+                .map_err(sql_error)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn has_shard(&self, cid: &Cid) -> Result<bool, FecError> {
+        let key = cid.to_hex();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM shards WHERE cid = ?1)",
+                params![key],
+                |row| row.get(0),
+            )
+            .map_err(sql_error)
+        })
+        .await
+    }
+
+    async fn list_shards(&self) -> Result<Vec<Cid>, FecError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare("SELECT cid FROM shards ORDER BY cid")
+                .map_err(sql_error)?;
+            let rows = stmt
+                .query_map([], |row| row.get::<_, String>(0))
+                .map_err(sql_error)?;
+
+            let mut cids = Vec::new();
+            for row in rows {
+                if let Some(cid) = parse_cid(&row.map_err(sql_error)?) {
+                    cids.push(cid);
+                }
+            }
+            Ok(cids)
+        })
+        .await
+    }
+
+    async fn list_shards_paged(
+        &self,
+        after: Option<&Cid>,
+        limit: usize,
+    ) -> Result<ShardPage, FecError> {
+        let after = after.map(Cid::to_hex).unwrap_or_default();
+        self.with_conn(move |conn| {
+            let mut stmt = conn
+                .prepare_cached("SELECT cid FROM shards WHERE cid > ?1 ORDER BY cid LIMIT ?2")
+                .map_err(sql_error)?;
+            let rows = stmt
+                .query_map(params![after, limit as i64 + 1], |row| {
+                    row.get::<_, String>(0)
+                })
+                .map_err(sql_error)?;
+
+            let mut cids = Vec::new();
+            for row in rows {
+                if let Some(cid) = parse_cid(&row.map_err(sql_error)?) {
+                    cids.push(cid);
+                }
+            }
+            Ok(ShardPage::from_candidates(cids, limit))
+        })
+        .await
+    }
+
+    async fn put_metadata(&self, metadata: &FileMetadata) -> Result<(), FecError> {
+        let row = MetadataRow::new(metadata)?;
+        self.with_conn(move |conn| row.insert(conn)).await
+    }
+
+    async fn get_metadata(&self, file_id: &[u8; 32]) -> Result<FileMetadata, FecError> {
+        let key = hex::encode(file_id);
+        let data: Option<Vec<u8>> = self
+            .with_conn(move |conn| {
+                conn.query_row(
+                    "SELECT metadata FROM metadata WHERE file_id = ?1",
+                    params![key],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(sql_error)
+            })
+            .await?;
+
+        let data = data.ok_or_else(|| FecError::MetadataNotFound {
+            file_id: hex::encode(file_id),
+        })?;
+        bincode::deserialize(&data)
+            .map_err(|e| FecError::Backend(format!("Failed to deserialize metadata: {}", e)))
+    }
+
+    async fn delete_metadata(&self, file_id: &[u8; 32]) -> Result<(), FecError> {
+        let key = hex::encode(file_id);
+        self.with_conn(move |conn| {
+            conn.execute("DELETE FROM metadata WHERE file_id = ?1", params![key])
+                .map_err(sql_error)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list_metadata(&self) -> Result<Vec<FileMetadata>, FecError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare("SELECT metadata FROM metadata")
+                .map_err(sql_error)?;
+            let rows = stmt
+                .query_map([], |row| row.get::<_, Vec<u8>>(0))
+                .map_err(sql_error)?;
+
+            let mut metadata_list = Vec::new();
+            for row in rows {
+                if let Ok(metadata) = bincode::deserialize::<FileMetadata>(&row.map_err(sql_error)?)
+                {
+                    metadata_list.push(metadata);
+                }
+            }
+            Ok(metadata_list)
+        })
+        .await
+    }
+
+    async fn stats(&self) -> Result<StorageStats, FecError> {
+        let referenced_cids = super::referenced_cids(&self.list_metadata().await?);
+        let shards = self.list_shards().await?;
+        let unreferenced_shards = shards
+            .iter()
+            .filter(|cid| !referenced_cids.contains(cid))
+            .count() as u64;
+
+        let (total_size, metadata_count) = self
+            .with_conn(|conn| {
+                conn.query_row(
+                    "SELECT (SELECT COALESCE(SUM(LENGTH(shard)), 0) FROM shards),
+                            (SELECT COUNT(*) FROM metadata)",
+                    [],
+                    |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)),
+                )
+                .map_err(sql_error)
+            })
+            .await?;
+
+        Ok(StorageStats {
+            total_shards: shards.len() as u64,
+            total_size: total_size as u64,
+            metadata_count: metadata_count as u64,
+            unreferenced_shards,
+            capacity: None,
+            free_space: None,
+        })
+    }
+
+    async fn garbage_collect(&self) -> Result<GcReport, FecError> {
+        let start_time = std::time::Instant::now();
+        let referenced_cids = super::referenced_cids(&self.list_metadata().await?);
+
+        let (shards_deleted, bytes_freed) = self
+            .with_conn(move |conn| {
+                let txn = conn.transaction().map_err(sql_error)?;
+                let unreferenced = {
+                    // Only the header is needed to check expiry
+                    let mut stmt = txn
+                        .prepare("SELECT cid, LENGTH(shard), substr(shard, 1, ?1) FROM shards")
+                        .map_err(sql_error)?;
+                    let rows = stmt
+                        .query_map(params![ShardHeader::SIZE as i64], |row| {
+                            Ok((
+                                row.get::<_, String>(0)?,
+                                row.get::<_, i64>(1)?,
+                                row.get::<_, Vec<u8>>(2)?,
+                            ))
+                        })
+                        .map_err(sql_error)?;
+
+                    let mut unreferenced = Vec::new();
+                    for row in rows {
+                        let (cid, size, header) = row.map_err(sql_error)?;
+                        let expired =
+                            ShardHeader::from_bytes(&header).is_ok_and(|h| h.is_expired());
+                        if expired || !parse_cid(&cid).is_some_and(|c| referenced_cids.contains(&c))
+                        {
+                            unreferenced.push((cid, size as u64));
+                        }
+                    }
+                    unreferenced
+                };
+
+                for (cid, _) in &unreferenced {
+                    txn.execute("DELETE FROM shards WHERE cid = ?1", params![cid])
+                        .map_err(sql_error)?;
+                }
+                txn.commit().map_err(sql_error)?;
+
+                let bytes_freed = unreferenced.iter().map(|(_, size)| size).sum();
+                Ok((unreferenced.len() as u64, bytes_freed))
+            })
+            .await?;
+
+        Ok(GcReport {
+            shards_deleted,
+            bytes_freed,
+            duration_ms: start_time.elapsed().as_millis() as u64,
+        })
+    }
+
+    // Either every shard in the batch is written or none are
+    async fn put_shards(&self, shards: &[(Cid, Shard)]) -> Result<(), FecError> {
+        let rows = shards
+            .iter()
+            .map(|(cid, shard)| ShardRow::new(cid, shard))
+            .collect::<Result<Vec<_>, FecError>>()?;
+
+        self.with_conn(move |conn| {
+            let txn = conn.transaction().map_err(sql_error)?;
+            for row in &rows {
+                row.insert(&txn)?;
+            }
+            txn.commit().map_err(sql_error)
+        })
+        .await
+    }
+
+    async fn commit_transaction(
+        &self,
+        shards: &[(Cid, Shard)],
+        metadata: &[FileMetadata],
+    ) -> Result<(), FecError> {
+        let shard_rows = shards
+            .iter()
+            .map(|(cid, shard)| ShardRow::new(cid, shard))
+            .collect::<Result<Vec<_>, FecError>>()?;
+        let metadata_rows = metadata
+            .iter()
+            .map(MetadataRow::new)
+            .collect::<Result<Vec<_>, FecError>>()?;
+
+        self.with_conn(move |conn| {
+            let txn = conn.transaction().map_err(sql_error)?;
+            for row in &shard_rows {
+                row.insert(&txn)?;
+            }
+            for row in &metadata_rows {
+                row.insert(&txn)?;
+            }
+            txn.commit().map_err(sql_error)
+        })
+        .await
+    }
+
+    async fn get_shards(&self, cids: &[Cid]) -> Result<Vec<Option<Shard>>, FecError> {
+        let keys: Vec<String> = cids.iter().map(Cid::to_hex).collect();
+        self.with_conn(move |conn| {
+            let mut stmt = conn
+                .prepare_cached("SELECT shard FROM shards WHERE cid = ?1")
+                .map_err(sql_error)?;
+            let mut shards = Vec::with_capacity(keys.len());
+            for key in &keys {
+                let bytes: Option<Vec<u8>> = stmt
+                    .query_row(params![key], |row| row.get(0))
+                    .optional()
+                    .map_err(sql_error)?;
+                shards.push(
+                    bytes
+                        .and_then(|bytes| Shard::from_bytes(&bytes).ok())
+                        .filter(|s| !s.header.is_expired()),
+                );
+            }
+            Ok(shards)
+        })
+        .await
+    }
+
+    async fn has_shards(&self, cids: &[Cid]) -> Result<Vec<bool>, FecError> {
+        let keys: Vec<String> = cids.iter().map(Cid::to_hex).collect();
+        self.with_conn(move |conn| {
+            let mut stmt = conn
+                .prepare_cached("SELECT EXISTS(SELECT 1 FROM shards WHERE cid = ?1)")
+                .map_err(sql_error)?;
+            keys.iter()
+                .map(|key| {
+                    stmt.query_row(params![key], |row| row.get(0))
+                        .map_err(sql_error)
+                })
+                .collect()
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::ChunkMeta;
+    use crate::EncryptionMode;
+    use tempfile::TempDir;
+
+    fn test_shard(fill: u8) -> Shard {
+        let header = ShardHeader::new(EncryptionMode::Convergent, (4, 2), 48, [0u8; 32]);
+        Shard::new(header, vec![fill; 48])
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_storage_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("store.db");
+        let shard = test_shard(5);
+        let cid = shard.cid().unwrap();
+
+        {
+            let storage = SqliteStorage::open(&path).await.unwrap();
+            storage.put_shard(&cid, &shard).await.unwrap();
+            storage
+                .put_metadata(&FileMetadata::new([1u8; 32], 48, Vec::new()))
+                .await
+                .unwrap();
+        }
+
+        let storage = SqliteStorage::open(&path).await.unwrap();
+        assert!(storage.has_shard(&cid).await.unwrap());
+        assert_eq!(storage.get_shard(&cid).await.unwrap().data, shard.data);
+        assert_eq!(storage.list_shards().await.unwrap(), vec![cid]);
+        assert_eq!(storage.list_metadata().await.unwrap().len(), 1);
+
+        storage.delete_shard(&cid).await.unwrap();
+        assert!(!storage.has_shard(&cid).await.unwrap());
+
+        // Rows are inspectable with plain SQL
+        let conn = Connection::open(&path).unwrap();
+        let mode: String = conn
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_storage_batch_and_gc() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = SqliteStorage::open(temp_dir.path().join("store.db"))
+            .await
+            .unwrap();
+
+        let shards: Vec<(Cid, Shard)> = (0..6u8)
+            .map(|i| {
+                let shard = test_shard(i);
+                (shard.cid().unwrap(), shard)
+            })
+            .collect();
+        storage.put_shards(&shards).await.unwrap();
+
+        let chunk = ChunkMeta::new(
+            (4, 2),
+            EncryptionMode::Convergent,
+            shards[..2].iter().map(|(cid, _)| cid.to_hex()).collect(),
+        );
+        storage
+            .put_metadata(&FileMetadata::new([2u8; 32], 96, vec![chunk]))
+            .await
+            .unwrap();
+
+        let stats = storage.stats().await.unwrap();
+        assert_eq!(stats.total_shards, 6);
+        assert_eq!(stats.unreferenced_shards, 4);
+
+        let first = storage.list_shards_paged(None, 4).await.unwrap();
+        let rest = storage
+            .list_shards_paged(first.next.as_ref(), 100)
+            .await
+            .unwrap();
+        let mut all = storage.list_shards().await.unwrap();
+        all.sort();
+        assert_eq!([first.cids, rest.cids].concat(), all);
+        assert!(rest.next.is_none());
+
+        let report = storage.garbage_collect().await.unwrap();
+        assert_eq!(report.shards_deleted, 4);
+        assert_eq!(storage.list_shards().await.unwrap().len(), 2);
+
+        // A transaction lands its shard and metadata together
+        let shard = test_shard(42);
+        let cid = shard.cid().unwrap();
+        let chunk = ChunkMeta::new((4, 2), EncryptionMode::Convergent, vec![cid.to_hex()]);
+        let mut txn = storage.begin();
+        txn.put_shard(cid, shard)
+            .put_metadata(FileMetadata::new([3u8; 32], 64, vec![chunk]));
+        txn.commit().await.unwrap();
+        assert!(storage.has_shard(&cid).await.unwrap());
+        assert!(storage.get_metadata(&[3u8; 32]).await.is_ok());
+    }
+}
@@ -0,0 +1,255 @@
+//! Backpressure for writes into slow backends
+//!
+//! [`ThrottledStorage`] bounds the number of shard bytes being written at
+//! once. A producer whose write would exceed the budget waits until earlier
+//! writes finish, so a slow backend throttles the pipeline instead of letting
+//! pending shards pile up in memory.
+
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio::io::AsyncRead;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use super::{
+    Cid, FileMetadata, GcReport, Shard, ShardHeader, ShardPage, ShardReader, StorageBackend,
+    StorageStats,
+};
+use crate::config::StorageConfig;
+use crate::FecError;
+
+/// Storage backend wrapper limiting the bytes of in-flight writes
+pub struct ThrottledStorage<B> {
+    inner: B,
+    budget: u32,
+    permits: Arc<Semaphore>,
+}
+
+impl<B: StorageBackend> ThrottledStorage<B> {
+    /// Wrap a backend allowing at most `max_in_flight_bytes` of concurrent writes
+    ///
+    /// The budget is capped at 4 GiB. A single write larger than the budget
+    /// is still accepted, but only once nothing else is in flight.
+    pub fn new(inner: B, max_in_flight_bytes: u64) -> Self {
+        let budget = max_in_flight_bytes.clamp(1, u32::MAX as u64) as u32;
+        Self {
+            inner,
+            budget,
+            permits: Arc::new(Semaphore::new(budget as usize)),
+        }
+    }
+
+    /// Wrap a backend using the in-flight limit from the storage configuration
+    pub fn from_config(inner: B, config: &StorageConfig) -> Self {
+        Self::new(inner, config.max_in_flight_bytes as u64)
+    }
+
+    /// The wrapped backend
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    /// Bytes of writes currently in flight
+    pub fn in_flight_bytes(&self) -> u64 {
+        (self.budget as usize - self.permits.available_permits()) as u64
+    }
+
+    /// Wait until `bytes` more fit in the budget
+    async fn reserve(&self, bytes: u64) -> Result<SemaphorePermit<'_>, FecError> {
+        let permits = bytes.clamp(1, self.budget as u64) as u32;
+        self.permits
+            .acquire_many(permits)
+            .await
+            .map_err(|e| FecError::Backend(format!("Write budget closed: {}", e)))
+    }
+}
+
+fn shard_bytes(shard: &Shard) -> u64 {
+    shard.data.len() as u64 + ShardHeader::SIZE as u64
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl<B: StorageBackend> StorageBackend for ThrottledStorage<B> {
+    async fn put_shard(&self, cid: &Cid, shard: &Shard) -> Result<(), FecError> {
+        let _permit = self.reserve(shard_bytes(shard)).await?;
+        self.inner.put_shard(cid, shard).await
+    }
+
+    async fn get_shard(&self, cid: &Cid) -> Result<Shard, FecError> {
+        self.inner.get_shard(cid).await
+    }
+
+    async fn delete_shard(&self, cid: &Cid) -> Result<(), FecError> {
+        self.inner.delete_shard(cid).await
+    }
+
+    async fn has_shard(&self, cid: &Cid) -> Result<bool, FecError> {
+        self.inner.has_shard(cid).await
+    }
+
+    async fn list_shards(&self) -> Result<Vec<Cid>, FecError> {
+        self.inner.list_shards().await
+    }
+
+    async fn put_metadata(&self, metadata: &FileMetadata) -> Result<(), FecError> {
+        self.inner.put_metadata(metadata).await
+    }
+
+    async fn get_metadata(&self, file_id: &[u8; 32]) -> Result<FileMetadata, FecError> {
+        self.inner.get_metadata(file_id).await
+    }
+
+    async fn delete_metadata(&self, file_id: &[u8; 32]) -> Result<(), FecError> {
+        self.inner.delete_metadata(file_id).await
+    }
+
+    async fn list_metadata(&self) -> Result<Vec<FileMetadata>, FecError> {
+        self.inner.list_metadata().await
+    }
+
+    async fn stats(&self) -> Result<StorageStats, FecError> {
+        self.inner.stats().await
+    }
+
+    async fn garbage_collect(&self) -> Result<GcReport, FecError> {
+        self.inner.garbage_collect().await
+    }
+
+    async fn list_shards_paged(
+        &self,
+        after: Option<&Cid>,
+        limit: usize,
+    ) -> Result<ShardPage, FecError> {
+        self.inner.list_shards_paged(after, limit).await
+    }
+
+    async fn get_shard_verified(&self, cid: &Cid) -> Result<Shard, FecError> {
+        self.inner.get_shard_verified(cid).await
+    }
+
+    async fn put_shards(&self, shards: &[(Cid, Shard)]) -> Result<(), FecError> {
+        let bytes = shards.iter().map(|(_, shard)| shard_bytes(shard)).sum();
+        let _permit = self.reserve(bytes).await?;
+        self.inner.put_shards(shards).await
+    }
+
+    async fn get_shards(&self, cids: &[Cid]) -> Result<Vec<Option<Shard>>, FecError> {
+        self.inner.get_shards(cids).await
+    }
+
+    async fn has_shards(&self, cids: &[Cid]) -> Result<Vec<bool>, FecError> {
+        self.inner.has_shards(cids).await
+    }
+
+    async fn commit_transaction(
+        &self,
+        shards: &[(Cid, Shard)],
+        metadata: &[FileMetadata],
+    ) -> Result<(), FecError> {
+        let bytes = shards.iter().map(|(_, shard)| shard_bytes(shard)).sum();
+        let _permit = self.reserve(bytes).await?;
+        self.inner.commit_transaction(shards, metadata).await
+    }
+
+    async fn put_shard_stream(
+        &self,
+        cid: &Cid,
+        header: &ShardHeader,
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        len: u64,
+    ) -> Result<(), FecError> {
+        let _permit = self.reserve(len + ShardHeader::SIZE as u64).await?;
+        self.inner.put_shard_stream(cid, header, reader, len).await
+    }
+
+    async fn get_shard_stream(&self, cid: &Cid) -> Result<(ShardHeader, ShardReader), FecError> {
+        self.inner.get_shard_stream(cid).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use crate::EncryptionMode;
+    use std::time::Duration;
+
+    /// Backend whose writes take a while to complete
+    struct SlowStorage(MemoryStorage);
+
+    #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+    impl StorageBackend for SlowStorage {
+        async fn put_shard(&self, cid: &Cid, shard: &Shard) -> Result<(), FecError> {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            self.0.put_shard(cid, shard).await
+        }
+        async fn get_shard(&self, cid: &Cid) -> Result<Shard, FecError> {
+            self.0.get_shard(cid).await
+        }
+        async fn delete_shard(&self, cid: &Cid) -> Result<(), FecError> {
+            self.0.delete_shard(cid).await
+        }
+        async fn has_shard(&self, cid: &Cid) -> Result<bool, FecError> {
+            self.0.has_shard(cid).await
+        }
+        async fn list_shards(&self) -> Result<Vec<Cid>, FecError> {
+            self.0.list_shards().await
+        }
+        async fn put_metadata(&self, metadata: &FileMetadata) -> Result<(), FecError> {
+            self.0.put_metadata(metadata).await
+        }
+        async fn get_metadata(&self, file_id: &[u8; 32]) -> Result<FileMetadata, FecError> {
+            self.0.get_metadata(file_id).await
+        }
+        async fn delete_metadata(&self, file_id: &[u8; 32]) -> Result<(), FecError> {
+            self.0.delete_metadata(file_id).await
+        }
+        async fn list_metadata(&self) -> Result<Vec<FileMetadata>, FecError> {
+            self.0.list_metadata().await
+        }
+        async fn stats(&self) -> Result<StorageStats, FecError> {
+            self.0.stats().await
+        }
+        async fn garbage_collect(&self) -> Result<GcReport, FecError> {
+            self.0.garbage_collect().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_writes_wait_for_budget() {
+        let shard = |i: u8| {
+            let header = ShardHeader::new(EncryptionMode::Convergent, (2, 1), 1000, [i; 32]);
+            Shard::new(header, vec![i; 1000])
+        };
+        let per_shard = shard_bytes(&shard(0));
+
+        // Room for exactly one shard at a time
+        let storage = Arc::new(ThrottledStorage::new(
+            SlowStorage(MemoryStorage::new()),
+            per_shard,
+        ));
+        let first = {
+            let storage = storage.clone();
+            tokio::spawn(async move {
+                let shard = shard(1);
+                storage.put_shard(&shard.cid().unwrap(), &shard).await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(storage.in_flight_bytes(), per_shard);
+
+        let started = std::time::Instant::now();
+        let second = shard(2);
+        storage
+            .put_shard(&second.cid().unwrap(), &second)
+            .await
+            .unwrap();
+        first.await.unwrap().unwrap();
+
+        // The second write could only start once the first released its bytes
+        assert!(started.elapsed() >= Duration::from_millis(80));
+        assert_eq!(storage.in_flight_bytes(), 0);
+        assert_eq!(storage.inner().0.list_shards().await.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,186 @@
+//! Buffered multi-shard transactions
+//!
+//! A [`Transaction`] collects shard and metadata writes and hands them to
+//! [`StorageBackend::commit_transaction`] in one call, so a whole stripe and
+//! the metadata referencing it become visible together. Nothing touches the
+//! backend before [`Transaction::commit`]; rolling back just drops the buffer.
+
+use super::{Cid, FileMetadata, Shard, StorageBackend};
+use crate::FecError;
+
+/// Pending writes against a storage backend
+pub struct Transaction<'a> {
+    backend: &'a dyn StorageBackend,
+    shards: Vec<(Cid, Shard)>,
+    metadata: Vec<FileMetadata>,
+}
+
+impl<'a> Transaction<'a> {
+    /// Start an empty transaction against `backend`
+    pub fn new(backend: &'a dyn StorageBackend) -> Self {
+        Self {
+            backend,
+            shards: Vec::new(),
+            metadata: Vec::new(),
+        }
+    }
+
+    /// Queue a shard write
+    pub fn put_shard(&mut self, cid: Cid, shard: Shard) -> &mut Self {
+        self.shards.push((cid, shard));
+        self
+    }
+
+    /// Queue a metadata write
+    pub fn put_metadata(&mut self, metadata: FileMetadata) -> &mut Self {
+        self.metadata.push(metadata);
+        self
+    }
+
+    /// Number of queued writes
+    pub fn len(&self) -> usize {
+        self.shards.len() + self.metadata.len()
+    }
+
+    /// Whether no writes are queued
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Apply every queued write atomically
+    pub async fn commit(self) -> Result<(), FecError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        self.backend
+            .commit_transaction(&self.shards, &self.metadata)
+            .await
+    }
+
+    /// Discard every queued write
+    pub fn rollback(self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{ChunkMeta, GcReport, MemoryStorage, ShardHeader, StorageStats};
+    use crate::EncryptionMode;
+    use async_trait::async_trait;
+
+    /// Memory storage that refuses metadata for one file ID, and so relies
+    /// on the default `commit_transaction` to undo earlier writes
+    struct RefusingStorage {
+        inner: MemoryStorage,
+        refused: [u8; 32],
+    }
+
+    #[async_trait]
+    impl StorageBackend for RefusingStorage {
+        async fn put_shard(&self, cid: &Cid, shard: &Shard) -> Result<(), FecError> {
+            self.inner.put_shard(cid, shard).await
+        }
+
+        async fn get_shard(&self, cid: &Cid) -> Result<Shard, FecError> {
+            self.inner.get_shard(cid).await
+        }
+
+        async fn delete_shard(&self, cid: &Cid) -> Result<(), FecError> {
+            self.inner.delete_shard(cid).await
+        }
+
+        async fn has_shard(&self, cid: &Cid) -> Result<bool, FecError> {
+            self.inner.has_shard(cid).await
+        }
+
+        async fn list_shards(&self) -> Result<Vec<Cid>, FecError> {
+            self.inner.list_shards().await
+        }
+
+        async fn put_metadata(&self, metadata: &FileMetadata) -> Result<(), FecError> {
+            if metadata.file_id == self.refused {
+                return Err(FecError::Backend("metadata refused".to_string()));
+            }
+            self.inner.put_metadata(metadata).await
+        }
+
+        async fn get_metadata(&self, file_id: &[u8; 32]) -> Result<FileMetadata, FecError> {
+            self.inner.get_metadata(file_id).await
+        }
+
+        async fn delete_metadata(&self, file_id: &[u8; 32]) -> Result<(), FecError> {
+            self.inner.delete_metadata(file_id).await
+        }
+
+        async fn list_metadata(&self) -> Result<Vec<FileMetadata>, FecError> {
+            self.inner.list_metadata().await
+        }
+
+        async fn stats(&self) -> Result<StorageStats, FecError> {
+            self.inner.stats().await
+        }
+
+        async fn garbage_collect(&self) -> Result<GcReport, FecError> {
+            self.inner.garbage_collect().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_commit_and_rollback() {
+        let storage = MemoryStorage::new();
+        let shard = Shard::new(
+            ShardHeader::new(EncryptionMode::Convergent, (2, 1), 4, [0u8; 32]),
+            b"data".to_vec(),
+        );
+        let cid = shard.cid().unwrap();
+        let metadata = FileMetadata::new(
+            [1u8; 32],
+            4,
+            vec![ChunkMeta::new(
+                (2, 1),
+                EncryptionMode::Convergent,
+                vec![cid.to_hex()],
+            )],
+        );
+
+        let mut txn = storage.begin();
+        txn.put_shard(cid, shard.clone())
+            .put_metadata(metadata.clone());
+        assert_eq!(txn.len(), 2);
+        txn.rollback();
+        assert!(!storage.has_shard(&cid).await.unwrap());
+
+        let mut txn = storage.begin();
+        txn.put_shard(cid, shard).put_metadata(metadata);
+        txn.commit().await.unwrap();
+        assert!(storage.has_shard(&cid).await.unwrap());
+        assert!(storage.get_metadata(&[1u8; 32]).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_failed_commit_restores_overwritten_metadata() {
+        let storage = RefusingStorage {
+            inner: MemoryStorage::new(),
+            refused: [9u8; 32],
+        };
+        let original = FileMetadata::new([1u8; 32], 4, Vec::new());
+        storage.put_metadata(&original).await.unwrap();
+
+        let shard = Shard::new(
+            ShardHeader::new(EncryptionMode::Convergent, (2, 1), 4, [0u8; 32]),
+            b"data".to_vec(),
+        );
+        let cid = shard.cid().unwrap();
+        let mut txn = storage.begin();
+        txn.put_shard(cid, shard)
+            .put_metadata(FileMetadata::new([1u8; 32], 8, Vec::new()))
+            .put_metadata(FileMetadata::new([2u8; 32], 4, Vec::new()))
+            .put_metadata(FileMetadata::new([9u8; 32], 4, Vec::new()));
+        assert!(txn.commit().await.is_err());
+
+        // The overwrite is undone, and everything the commit created is gone
+        assert_eq!(storage.get_metadata(&[1u8; 32]).await.unwrap().file_size, 4);
+        assert!(storage.get_metadata(&[2u8; 32]).await.is_err());
+        assert!(!storage.has_shard(&cid).await.unwrap());
+    }
+}
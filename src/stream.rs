@@ -0,0 +1,493 @@
+//! Streaming AEAD using the STREAM construction
+//!
+//! Sealing a whole file as one AEAD message needs the file in memory, and a
+//! single flipped bit makes all of it undecryptable. STREAM (Hoang et al.,
+//! "Online Authenticated-Encryption and its Nonce-Reuse Misuse-Resistance")
+//! splits the plaintext into fixed-size segments, each sealed with its own
+//! tag under the nonce
+//!
+//! ```text
+//! prefix (7 bytes) || segment counter (4 bytes, BE) || last flag (1 byte)
+//! ```
+//!
+//! Reordering, dropping or truncating segments fails authentication, while
+//! corruption only affects the segment it hits: [`open_segment`] decrypts
+//! any intact segment on its own.
+//!
+//! The stream starts with the nonce prefix, random unless the caller fixes
+//! it, followed by the sealed segments. Every segment but the last holds
+//! exactly `segment_size` plaintext bytes.
+
+use aes_gcm::Aes256Gcm;
+use aes_gcm_siv::aead::{Aead, KeyInit};
+use aes_gcm_siv::Aes256GcmSiv;
+use generic_array::GenericArray;
+use rand::RngCore;
+use saorsa_pqc::api::symmetric::ChaCha20Poly1305;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use thiserror::Error;
+
+use crate::crypto::EncryptionAlgorithm;
+
+/// Errors from sealing or opening a STREAM
+#[derive(Debug, Error)]
+pub enum StreamError {
+    #[error("Segment size must be non-zero")]
+    ZeroSegmentSize,
+
+    #[error("Stream too short for its nonce prefix")]
+    Truncated,
+
+    /// The segment failed authentication, or the stream was cut short
+    #[error("Segment {0} is corrupted or out of place")]
+    CorruptSegment(u32),
+
+    #[error("Segment {index} out of range ({segments} segments)")]
+    SegmentOutOfRange { index: u32, segments: usize },
+
+    #[error("Stream exceeds the maximum number of segments")]
+    TooManySegments,
+
+    #[error("Segment encryption failed")]
+    Encryption,
+
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+}
+
+impl StreamError {
+    /// Unwrap a stream error that passed through the `Read` or `Write` impls
+    fn from_io(e: io::Error) -> Self {
+        if !e.get_ref().is_some_and(|inner| inner.is::<Self>()) {
+            return Self::Io(e);
+        }
+        let kind = e.kind();
+        match e.into_inner().map(|inner| inner.downcast::<Self>()) {
+            Some(Ok(inner)) => *inner,
+            _ => Self::Io(kind.into()),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, StreamError>;
+
+/// Length of the random nonce prefix at the start of a stream
+pub const PREFIX_LEN: usize = 7;
+
+/// Authentication tag added to every segment
+pub const TAG_LEN: usize = 16;
+
+/// Plaintext bytes per segment unless configured otherwise
+pub const DEFAULT_SEGMENT_SIZE: usize = 64 * 1024;
+
+/// AEAD instance shared by all segments of a stream
+enum SegmentCipher {
+    Aes256Gcm(Box<Aes256Gcm>),
+    ChaCha20Poly1305(Box<ChaCha20Poly1305>),
+    Aes256GcmSiv(Box<Aes256GcmSiv>),
+}
+
+impl SegmentCipher {
+    fn new(algorithm: EncryptionAlgorithm, key: &[u8; 32]) -> Self {
+        let key = GenericArray::from_slice(key);
+        match algorithm {
+            EncryptionAlgorithm::Aes256Gcm => Self::Aes256Gcm(Box::new(Aes256Gcm::new(key))),
+            EncryptionAlgorithm::ChaCha20Poly1305 => {
+                Self::ChaCha20Poly1305(Box::new(ChaCha20Poly1305::new(key)))
+            }
+            EncryptionAlgorithm::Aes256GcmSiv => {
+                Self::Aes256GcmSiv(Box::new(Aes256GcmSiv::new(key)))
+            }
+        }
+    }
+
+    fn seal(&self, nonce: &[u8; 12], plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = GenericArray::from_slice(nonce);
+        let sealed = match self {
+            Self::Aes256Gcm(cipher) => cipher.encrypt(nonce, plaintext).ok(),
+            Self::ChaCha20Poly1305(cipher) => cipher.encrypt(nonce, plaintext).ok(),
+            Self::Aes256GcmSiv(cipher) => cipher.encrypt(nonce, plaintext).ok(),
+        };
+        sealed.ok_or(StreamError::Encryption)
+    }
+
+    fn open(&self, nonce: &[u8; 12], ciphertext: &[u8]) -> Option<Vec<u8>> {
+        let nonce = GenericArray::from_slice(nonce);
+        match self {
+            Self::Aes256Gcm(cipher) => cipher.decrypt(nonce, ciphertext).ok(),
+            Self::ChaCha20Poly1305(cipher) => cipher.decrypt(nonce, ciphertext).ok(),
+            Self::Aes256GcmSiv(cipher) => cipher.decrypt(nonce, ciphertext).ok(),
+        }
+    }
+}
+
+fn segment_nonce(prefix: &[u8; PREFIX_LEN], index: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..PREFIX_LEN].copy_from_slice(prefix);
+    nonce[PREFIX_LEN..11].copy_from_slice(&index.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+/// Writer encrypting everything written to it into a STREAM
+///
+/// Call [`finish`](Self::finish) once done; dropping the encryptor instead
+/// leaves a stream without its final segment, which fails to decrypt.
+pub struct StreamEncryptor<W: Write> {
+    inner: W,
+    cipher: SegmentCipher,
+    prefix: [u8; PREFIX_LEN],
+    segment_size: usize,
+    buffer: Vec<u8>,
+    index: u32,
+}
+
+impl<W: Write> StreamEncryptor<W> {
+    /// Start a stream with [`DEFAULT_SEGMENT_SIZE`] segments
+    pub fn new(inner: W, algorithm: EncryptionAlgorithm, key: &[u8; 32]) -> Result<Self> {
+        Self::with_segment_size(inner, algorithm, key, DEFAULT_SEGMENT_SIZE)
+    }
+
+    /// Start a stream with custom segment size, writing the nonce prefix
+    pub fn with_segment_size(
+        inner: W,
+        algorithm: EncryptionAlgorithm,
+        key: &[u8; 32],
+        segment_size: usize,
+    ) -> Result<Self> {
+        let mut prefix = [0u8; PREFIX_LEN];
+        rand::thread_rng().fill_bytes(&mut prefix);
+        Self::with_prefix(inner, algorithm, key, segment_size, prefix)
+    }
+
+    /// Start a stream under a caller-chosen nonce prefix
+    ///
+    /// The prefix must never repeat under the same key with different
+    /// plaintext. Convergent encryption derives it from the content, so
+    /// identical files still produce identical streams.
+    pub fn with_prefix(
+        mut inner: W,
+        algorithm: EncryptionAlgorithm,
+        key: &[u8; 32],
+        segment_size: usize,
+        prefix: [u8; PREFIX_LEN],
+    ) -> Result<Self> {
+        if segment_size == 0 {
+            return Err(StreamError::ZeroSegmentSize);
+        }
+        inner.write_all(&prefix)?;
+
+        Ok(Self {
+            inner,
+            cipher: SegmentCipher::new(algorithm, key),
+            prefix,
+            segment_size,
+            buffer: Vec::with_capacity(segment_size),
+            index: 0,
+        })
+    }
+
+    /// Seal the final segment and return the inner writer
+    pub fn finish(mut self) -> Result<W> {
+        let buffer = std::mem::take(&mut self.buffer);
+        self.write_segment(&buffer, true)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn write_segment(&mut self, plaintext: &[u8], last: bool) -> Result<()> {
+        let nonce = segment_nonce(&self.prefix, self.index, last);
+        let sealed = self.cipher.seal(&nonce, plaintext)?;
+        self.inner.write_all(&sealed)?;
+        self.index = self
+            .index
+            .checked_add(1)
+            .ok_or(StreamError::TooManySegments)?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for StreamEncryptor<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        // Hold back a full segment: it is only known not to be the last one
+        // once more data arrives
+        if self.buffer.len() == self.segment_size && !data.is_empty() {
+            let segment = std::mem::take(&mut self.buffer);
+            self.write_segment(&segment, false)
+                .map_err(io::Error::other)?;
+        }
+        let take = data.len().min(self.segment_size - self.buffer.len());
+        self.buffer.extend_from_slice(&data[..take]);
+        Ok(take)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reader decrypting a STREAM produced by [`StreamEncryptor`]
+///
+/// Each segment is authenticated before any of its plaintext is returned.
+/// A truncated stream fails once the missing final segment is noticed.
+pub struct StreamDecryptor<R: Read> {
+    inner: R,
+    cipher: SegmentCipher,
+    prefix: [u8; PREFIX_LEN],
+    segment_size: usize,
+    index: u32,
+    /// Sealed bytes read ahead to tell whether a segment is the last one
+    pending: Vec<u8>,
+    plaintext: Vec<u8>,
+    position: usize,
+    finished: bool,
+}
+
+impl<R: Read> StreamDecryptor<R> {
+    /// Decrypt a stream with [`DEFAULT_SEGMENT_SIZE`] segments
+    pub fn new(inner: R, algorithm: EncryptionAlgorithm, key: &[u8; 32]) -> Result<Self> {
+        Self::with_segment_size(inner, algorithm, key, DEFAULT_SEGMENT_SIZE)
+    }
+
+    /// Decrypt a stream with custom segment size, reading the nonce prefix
+    pub fn with_segment_size(
+        mut inner: R,
+        algorithm: EncryptionAlgorithm,
+        key: &[u8; 32],
+        segment_size: usize,
+    ) -> Result<Self> {
+        if segment_size == 0 {
+            return Err(StreamError::ZeroSegmentSize);
+        }
+        let mut prefix = [0u8; PREFIX_LEN];
+        inner.read_exact(&mut prefix).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => StreamError::Truncated,
+            _ => StreamError::Io(e),
+        })?;
+
+        Ok(Self {
+            inner,
+            cipher: SegmentCipher::new(algorithm, key),
+            prefix,
+            segment_size,
+            index: 0,
+            pending: Vec::new(),
+            plaintext: Vec::new(),
+            position: 0,
+            finished: false,
+        })
+    }
+
+    /// Decrypt the next segment into the plaintext buffer
+    fn next_segment(&mut self) -> Result<()> {
+        // A full sealed segment plus one byte proves it is not the last
+        let sealed_size = self.segment_size + TAG_LEN;
+        let wanted = sealed_size + 1;
+        while self.pending.len() < wanted {
+            let mut chunk = vec![0u8; wanted - self.pending.len()];
+            let read = self.inner.read(&mut chunk)?;
+            if read == 0 {
+                break;
+            }
+            self.pending.extend_from_slice(&chunk[..read]);
+        }
+
+        let last = self.pending.len() <= sealed_size;
+        let segment: Vec<u8> = if last {
+            std::mem::take(&mut self.pending)
+        } else {
+            self.pending.drain(..sealed_size).collect()
+        };
+        let nonce = segment_nonce(&self.prefix, self.index, last);
+        self.plaintext = self
+            .cipher
+            .open(&nonce, &segment)
+            .ok_or(StreamError::CorruptSegment(self.index))?;
+        self.position = 0;
+        self.index = self.index.wrapping_add(1);
+        self.finished = last;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for StreamDecryptor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.plaintext.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            self.next_segment()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        }
+        let n = buf.len().min(self.plaintext.len() - self.position);
+        buf[..n].copy_from_slice(&self.plaintext[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+/// Encrypt `plaintext` into a STREAM in one call
+pub fn seal_stream(
+    algorithm: EncryptionAlgorithm,
+    key: &[u8; 32],
+    segment_size: usize,
+    plaintext: &[u8],
+) -> Result<Vec<u8>> {
+    let mut prefix = [0u8; PREFIX_LEN];
+    rand::thread_rng().fill_bytes(&mut prefix);
+    seal_stream_with_prefix(algorithm, key, segment_size, prefix, plaintext)
+}
+
+/// Encrypt `plaintext` into a STREAM under a caller-chosen nonce prefix
+///
+/// See [`StreamEncryptor::with_prefix`] for when a fixed prefix is safe.
+pub fn seal_stream_with_prefix(
+    algorithm: EncryptionAlgorithm,
+    key: &[u8; 32],
+    segment_size: usize,
+    prefix: [u8; PREFIX_LEN],
+    plaintext: &[u8],
+) -> Result<Vec<u8>> {
+    let segments = plaintext.len().div_ceil(segment_size.max(1)).max(1);
+    let capacity = PREFIX_LEN + plaintext.len() + segments * TAG_LEN;
+    let mut encryptor = StreamEncryptor::with_prefix(
+        Vec::with_capacity(capacity),
+        algorithm,
+        key,
+        segment_size,
+        prefix,
+    )?;
+    encryptor
+        .write_all(plaintext)
+        .map_err(StreamError::from_io)?;
+    encryptor.finish()
+}
+
+/// Decrypt a whole STREAM in one call
+pub fn open_stream(
+    algorithm: EncryptionAlgorithm,
+    key: &[u8; 32],
+    segment_size: usize,
+    stream: &[u8],
+) -> Result<Vec<u8>> {
+    let mut decryptor = StreamDecryptor::with_segment_size(stream, algorithm, key, segment_size)?;
+    let mut plaintext = Vec::with_capacity(stream.len());
+    decryptor
+        .read_to_end(&mut plaintext)
+        .map_err(StreamError::from_io)?;
+    Ok(plaintext)
+}
+
+/// Decrypt segment `index` of a complete STREAM on its own
+///
+/// Lets intact segments be recovered around a corrupted one, and serves
+/// range reads without decrypting from the start.
+pub fn open_segment(
+    algorithm: EncryptionAlgorithm,
+    key: &[u8; 32],
+    segment_size: usize,
+    stream: &[u8],
+    index: u32,
+) -> Result<Vec<u8>> {
+    let prefix: [u8; PREFIX_LEN] = stream
+        .get(..PREFIX_LEN)
+        .and_then(|p| p.try_into().ok())
+        .ok_or(StreamError::Truncated)?;
+    let (range, last) = segment_range(segment_size, stream.len(), index)?;
+    open_sealed_segment(algorithm, key, &prefix, index, last, &stream[range])
+}
+
+/// Where segment `index` sits in a stream of `stream_len` bytes
+///
+/// Returns its byte range, prefix included in the offsets, and whether it
+/// is the last segment. Callers holding only part of a stream use this to
+/// find the bytes to fetch for [`open_sealed_segment`].
+pub fn segment_range(
+    segment_size: usize,
+    stream_len: usize,
+    index: u32,
+) -> Result<(Range<usize>, bool)> {
+    if segment_size == 0 {
+        return Err(StreamError::ZeroSegmentSize);
+    }
+    let body_len = stream_len
+        .checked_sub(PREFIX_LEN)
+        .ok_or(StreamError::Truncated)?;
+    let sealed_size = segment_size + TAG_LEN;
+    let segments = body_len.div_ceil(sealed_size).max(1);
+    if index as usize >= segments {
+        return Err(StreamError::SegmentOutOfRange { index, segments });
+    }
+
+    let start = PREFIX_LEN + index as usize * sealed_size;
+    let end = (start + sealed_size).min(stream_len);
+    Ok((start..end, index as usize == segments - 1))
+}
+
+/// Decrypt one sealed segment given the stream's nonce prefix
+pub fn open_sealed_segment(
+    algorithm: EncryptionAlgorithm,
+    key: &[u8; 32],
+    prefix: &[u8; PREFIX_LEN],
+    index: u32,
+    last: bool,
+    sealed: &[u8],
+) -> Result<Vec<u8>> {
+    SegmentCipher::new(algorithm, key)
+        .open(&segment_nonce(prefix, index, last), sealed)
+        .ok_or(StreamError::CorruptSegment(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [9u8; 32];
+
+    #[test]
+    fn test_stream_round_trip() -> Result<()> {
+        for len in [0usize, 1, 64, 65, 640] {
+            let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let sealed = seal_stream(EncryptionAlgorithm::ChaCha20Poly1305, &KEY, 64, &data)?;
+            let segments = len.div_ceil(64).max(1);
+            assert_eq!(sealed.len(), PREFIX_LEN + len + segments * TAG_LEN);
+            assert_eq!(
+                open_stream(EncryptionAlgorithm::ChaCha20Poly1305, &KEY, 64, &sealed)?,
+                data
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_detects_truncation_and_localizes_corruption() -> Result<()> {
+        let algorithm = EncryptionAlgorithm::Aes256Gcm;
+        let data: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        let sealed = seal_stream(algorithm, &KEY, 100, &data)?;
+
+        // Dropping the final segment leaves a non-final one at the end
+        let truncated = &sealed[..PREFIX_LEN + 2 * (100 + TAG_LEN)];
+        assert!(matches!(
+            open_stream(algorithm, &KEY, 100, truncated),
+            Err(StreamError::CorruptSegment(1))
+        ));
+
+        let mut corrupted = sealed.clone();
+        corrupted[PREFIX_LEN + 100 + TAG_LEN + 5] ^= 1;
+        assert!(matches!(
+            open_stream(algorithm, &KEY, 100, &corrupted),
+            Err(StreamError::CorruptSegment(1))
+        ));
+        assert!(open_segment(algorithm, &KEY, 100, &corrupted, 1).is_err());
+        assert_eq!(
+            open_segment(algorithm, &KEY, 100, &corrupted, 0)?,
+            &data[..100]
+        );
+        assert_eq!(
+            open_segment(algorithm, &KEY, 100, &corrupted, 2)?,
+            &data[200..]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+//! OTLP export of tracing spans
+//!
+//! The pipeline emits [`tracing`] spans for every store, retrieval,
+//! verification and repair, carrying the file ID and chunk count, with child
+//! spans for chunk coding and each storage call. Any subscriber sees them.
+//! With the `otel` feature this module forwards them to an OpenTelemetry
+//! collector over OTLP/HTTP, so a slow store can be followed end-to-end
+//! alongside the spans of the surrounding application:
+//!
+//! ```no_run
+//! let _guard = saorsa_fec::telemetry::init_otlp("archiver", "http://localhost:4318/v1/traces")?;
+//! // ... use the pipeline; spans are flushed when the guard drops
+//! # Ok::<(), saorsa_fec::telemetry::TelemetryError>(())
+//! ```
+//!
+//! Applications with their own subscriber add [`otlp_layer`] to it instead.
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{ExporterBuildError, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use thiserror::Error;
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::{SubscriberInitExt, TryInitError};
+
+/// Errors setting up span export
+#[derive(Debug, Error)]
+pub enum TelemetryError {
+    #[error("Failed to build the OTLP exporter: {0}")]
+    Exporter(#[from] ExporterBuildError),
+
+    #[error("Failed to install the tracing subscriber: {0}")]
+    Subscriber(#[from] TryInitError),
+}
+
+pub type Result<T> = std::result::Result<T, TelemetryError>;
+
+/// Keeps span export running; flushes and stops it when dropped
+#[must_use = "spans stop being exported when the guard is dropped"]
+pub struct OtlpGuard {
+    provider: SdkTracerProvider,
+}
+
+impl OtlpGuard {
+    /// Export the spans finished so far without waiting for the next batch
+    pub fn flush(&self) {
+        if let Err(e) = self.provider.force_flush() {
+            tracing::warn!("Failed to flush spans: {}", e);
+        }
+    }
+}
+
+impl Drop for OtlpGuard {
+    fn drop(&mut self) {
+        // The subscriber may already be gone, so report nothing here
+        let _ = self.provider.shutdown();
+    }
+}
+
+/// Tracing layer exporting spans to the OTLP/HTTP `endpoint`, such as
+/// `http://localhost:4318/v1/traces`, under `service_name`
+pub fn otlp_layer<S>(
+    service_name: &str,
+    endpoint: &str,
+) -> Result<(OpenTelemetryLayer<S, SdkTracer>, OtlpGuard)>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(service_name.to_string())
+                .build(),
+        )
+        .build();
+    Ok(layer_for(provider))
+}
+
+/// Install a global subscriber exporting spans to the OTLP/HTTP `endpoint`
+pub fn init_otlp(service_name: &str, endpoint: &str) -> Result<OtlpGuard> {
+    let (layer, guard) = otlp_layer(service_name, endpoint)?;
+    tracing_subscriber::registry().with(layer).try_init()?;
+    Ok(guard)
+}
+
+fn layer_for<S>(provider: SdkTracerProvider) -> (OpenTelemetryLayer<S, SdkTracer>, OtlpGuard)
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+    (
+        tracing_opentelemetry::layer().with_tracer(tracer),
+        OtlpGuard { provider },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+    use crate::{Config, StoragePipeline};
+    use opentelemetry_sdk::trace::InMemorySpanExporter;
+
+    #[tokio::test]
+    async fn test_pipeline_spans_reach_the_exporter() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let (layer, guard) = layer_for(provider);
+        let _default = tracing_subscriber::registry().with(layer).set_default();
+
+        let config = Config::default().with_fec_params(4, 2);
+        let mut pipeline = StoragePipeline::new(config, MemoryStorage::new())
+            .await
+            .unwrap();
+        let metadata = pipeline
+            .process_file([6u8; 32], &[1u8; 3000], None)
+            .await
+            .unwrap();
+        pipeline.retrieve_file(&metadata).await.unwrap();
+        guard.flush();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let store = spans.iter().find(|s| s.name == "process_file").unwrap();
+        let attribute = |key: &str| {
+            store
+                .attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.to_string())
+        };
+        assert_eq!(attribute("file_id"), Some(hex::encode([6u8; 32])));
+        assert_eq!(attribute("chunks").as_deref(), Some("1"));
+        assert!(spans.iter().any(|s| s.name == "retrieve_file"));
+        assert!(spans
+            .iter()
+            .any(|s| s.name == "put_shards" && s.parent_span_id == store.span_context.span_id()));
+    }
+}
@@ -4,11 +4,15 @@
 //! Core traits for FEC operations
 
 use crate::{FecParams, Result};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
 use async_trait::async_trait;
+#[cfg(feature = "std")]
 use bytes::Bytes;
-use std::fmt;
+use core::fmt;
 
 /// Core FEC trait for encoding and decoding operations
+#[cfg(feature = "std")]
 #[async_trait]
 pub trait Fec: Send + Sync {
     /// Encode data into shares using systematic encoding
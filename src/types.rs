@@ -1,7 +1,7 @@
 //! Common types used throughout the Saorsa FEC system
 
+use core::fmt;
 use serde::{Deserialize, Serialize};
-use std::fmt;
 
 /// Unique identifier for data
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
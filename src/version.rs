@@ -3,14 +3,87 @@
 //! This module provides a version tree structure for tracking file versions,
 //! enabling efficient diff computation and chunk deduplication.
 
-use anyhow::{Context, Result};
-use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::sync::Arc;
+use thiserror::Error;
 
-use crate::chunk_registry::ChunkRegistry;
-use crate::metadata::FileMetadata;
+use crate::chunk_registry::{ChunkRegistry, RefUpdate, RegistryError};
+use crate::config::VersionConfig;
+use crate::metadata::{verify_digest, FileMetadata, MetadataError, MetadataSigner};
+
+/// Errors from version management and version patches
+#[derive(Debug, Error)]
+pub enum VersionError {
+    #[error("Version not found: {0}")]
+    VersionNotFound(String),
+
+    #[error("File {0} has no versions")]
+    NoVersions(String),
+
+    #[error("Branch {0} not found")]
+    BranchNotFound(String),
+
+    #[error("Tag {0} not found")]
+    TagNotFound(String),
+
+    #[error("No branch or tag named {0}")]
+    UnknownName(String),
+
+    #[error("Name {0} is already a branch or tag of this file")]
+    NameTaken(String),
+
+    #[error("Version belongs to a different file")]
+    WrongFile,
+
+    #[error("Version is not signed")]
+    NotSigned,
+
+    #[error("Version {version} failed verification: {error}")]
+    Unverified {
+        version: String,
+        error: MetadataError,
+    },
+
+    #[error("Version {0} signed by untrusted key")]
+    UntrustedSigner(String),
+
+    /// A patch that does not fit the version it is applied to
+    #[error("{0}")]
+    InvalidPatch(&'static str),
+
+    #[error("Empty version delta")]
+    EmptyDelta,
+
+    #[error("Unknown version delta format {0}")]
+    UnknownDeltaFormat(u8),
+
+    #[error("Compression failed: {0}")]
+    Compression(std::io::Error),
+
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] bincode::Error),
+
+    #[error(transparent)]
+    Metadata(#[from] MetadataError),
+
+    #[error(transparent)]
+    Registry(#[from] RegistryError),
+}
+
+pub type Result<T> = std::result::Result<T, VersionError>;
+
+fn version_not_found(hash: &[u8; 32]) -> VersionError {
+    VersionError::VersionNotFound(hex::encode(hash))
+}
+
+/// Domain separation for version node signatures
+const VERSION_SIGNATURE_CONTEXT: &[u8] = b"saorsa-fec-version-v1";
+
+/// Leading byte of an encoded delta stored as plain bincode
+const DELTA_RAW: u8 = 0;
+/// Leading byte of an encoded delta stored as gzipped bincode
+const DELTA_GZIP: u8 = 1;
 
 /// Type alias for chunk diff result
 type ChunkDiff = (Vec<[u8; 32]>, Vec<[u8; 32]>);
@@ -20,15 +93,32 @@ type ChunkDiff = (Vec<[u8; 32]>, Vec<[u8; 32]>);
 pub struct VersionNode {
     /// Hash of the FileMetadata for this version
     pub metadata_hash: [u8; 32],
-    /// Parent version if this is not the first version
-    pub parent: Option<Box<VersionNode>>,
+    /// Metadata hash of the parent version if this is not the first version
+    pub parent: Option<[u8; 32]>,
+    /// Metadata hash of the branch merged into this version, if any
+    #[serde(default)]
+    pub merge_parent: Option<[u8; 32]>,
     /// Chunks added in this version
     pub chunks_added: Vec<[u8; 32]>,
     /// Chunks removed in this version
     pub chunks_removed: Vec<[u8; 32]>,
     /// Optional local version information
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub local_info: Option<LocalVersionInfo>,
+    /// Creator's signature over the version's place in history
+    #[serde(default)]
+    pub signature: Option<VersionSignature>,
+}
+
+/// ML-DSA-65 signature over a version's hash, parents and creation time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionSignature {
+    /// Unix timestamp the version was signed at
+    pub timestamp: u64,
+    /// Signer's ML-DSA-65 public key
+    pub public_key: Vec<u8>,
+    /// Signature bytes
+    pub signature: Vec<u8>,
 }
 
 impl VersionNode {
@@ -37,15 +127,17 @@ impl VersionNode {
         Self {
             metadata_hash,
             parent: None,
+            merge_parent: None,
             chunks_added: Vec::new(),
             chunks_removed: Vec::new(),
             local_info: None,
+            signature: None,
         }
     }
 
     /// Set parent version
-    pub fn with_parent(mut self, parent: VersionNode) -> Self {
-        self.parent = Some(Box::new(parent));
+    pub fn with_parent(mut self, parent_hash: [u8; 32]) -> Self {
+        self.parent = Some(parent_hash);
         self
     }
 
@@ -61,25 +153,60 @@ impl VersionNode {
         self
     }
 
-    /// Get depth of this node in version tree
-    pub fn depth(&self) -> usize {
-        match &self.parent {
-            Some(parent) => parent.depth() + 1,
-            None => 0,
-        }
+    /// Encode for persistence, gzipping the chunk lists and local info if
+    /// `compress` is set (see [`VersionConfig::diff_compression`])
+    pub fn to_bytes(&self, compress: bool) -> Result<Vec<u8>> {
+        encode_delta(self, compress)
+    }
+
+    /// Decode a node written by [`to_bytes`](Self::to_bytes), compressed or not
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        decode_delta(bytes)
     }
 
-    /// Get all ancestor metadata hashes
-    pub fn ancestors(&self) -> Vec<[u8; 32]> {
-        let mut result = Vec::new();
-        let mut current = self.parent.as_deref();
+    /// Sign the metadata hash, parents and the current time
+    ///
+    /// Changing the parent or merge parent afterwards invalidates the
+    /// signature, so a storage provider cannot splice the version into a
+    /// different history.
+    pub fn sign(&mut self, signer: &MetadataSigner) -> Result<()> {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let digest = self.signing_digest(timestamp);
+        self.signature = Some(VersionSignature {
+            timestamp,
+            public_key: signer.public_key(),
+            signature: signer.sign_digest(&digest, VERSION_SIGNATURE_CONTEXT)?,
+        });
+        Ok(())
+    }
 
-        while let Some(node) = current {
-            result.push(node.metadata_hash);
-            current = node.parent.as_deref();
-        }
+    /// Verify the signature and return the signer's public key
+    pub fn verify_signature(&self) -> Result<&[u8]> {
+        let signed = self.signature.as_ref().ok_or(VersionError::NotSigned)?;
+        let digest = self.signing_digest(signed.timestamp);
+        verify_digest(
+            &signed.public_key,
+            &signed.signature,
+            &digest,
+            VERSION_SIGNATURE_CONTEXT,
+        )?;
+        Ok(&signed.public_key)
+    }
 
-        result
+    fn signing_digest(&self, timestamp: u64) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&self.metadata_hash);
+        for parent in [&self.parent, &self.merge_parent] {
+            match parent {
+                Some(hash) => hasher.update(&[1]).update(hash),
+                None => hasher.update(&[0]),
+            };
+        }
+        hasher.update(&timestamp.to_le_bytes());
+        *hasher.finalize().as_bytes()
     }
 }
 
@@ -144,24 +271,163 @@ pub struct VersionDiff {
     pub size_delta: i64,
 }
 
+/// Chunks two versions have in common
+#[derive(Debug, Clone, Default)]
+pub struct ChunkOverlap {
+    /// Chunks present in both versions
+    pub shared: Vec<[u8; 32]>,
+    /// Bytes stored once instead of twice thanks to the shared chunks
+    pub bytes_saved: u64,
+}
+
+/// Chunk sharing across every version the manager tracks
+#[derive(Debug, Clone, Default)]
+pub struct OverlapReport {
+    /// Number of versions examined
+    pub versions: usize,
+    /// Number of distinct chunks
+    pub unique_chunks: usize,
+    /// Bytes the versions would take if nothing were shared
+    pub logical_bytes: u64,
+    /// Bytes actually taken by the distinct chunks
+    pub stored_bytes: u64,
+    /// Chunks used by more than one version, most bytes saved first
+    pub shared: Vec<SharedChunk>,
+}
+
+impl OverlapReport {
+    /// Bytes saved by deduplication
+    pub fn bytes_saved(&self) -> u64 {
+        self.logical_bytes - self.stored_bytes
+    }
+}
+
+/// A chunk used by several versions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedChunk {
+    /// Chunk identifier
+    pub chunk_id: [u8; 32],
+    /// Chunk size in bytes
+    pub size: u32,
+    /// Number of versions using the chunk
+    pub versions: usize,
+}
+
+impl SharedChunk {
+    /// Bytes saved by storing this chunk once
+    pub fn bytes_saved(&self) -> u64 {
+        self.size as u64 * (self.versions as u64 - 1)
+    }
+}
+
+/// Result of merging two versions
+#[derive(Debug, Clone)]
+pub struct MergeResult {
+    /// Nearest common ancestor, if the versions share history
+    pub base: Option<[u8; 32]>,
+    /// Chunks of the merged version
+    pub chunks: Vec<[u8; 32]>,
+    /// Places where both sides changed the same content
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl MergeResult {
+    /// Whether the merge needs no manual resolution
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// A base chunk that both sides of a merge replaced
+///
+/// Both replacements are kept in [`MergeResult::chunks`]; drop one of them
+/// to resolve the conflict.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeConflict {
+    /// Chunk of the common ancestor removed on both sides
+    pub base_chunk: [u8; 32],
+    /// Chunks added by the first version
+    pub ours: Vec<[u8; 32]>,
+    /// Chunks added by the second version
+    pub theirs: Vec<[u8; 32]>,
+}
+
+/// Child hash with its chunks added and removed relative to a new parent
+type Rebase = ([u8; 32], Vec<[u8; 32]>, Vec<[u8; 32]>);
+
 /// Version manager for tracking file history
 pub struct VersionManager {
     /// All versions indexed by metadata hash
     versions: HashMap<[u8; 32], VersionNode>,
     /// Reference to chunk registry for tracking
-    chunk_registry: Arc<RwLock<ChunkRegistry>>,
+    chunk_registry: Arc<ChunkRegistry>,
     /// File ID to latest version mapping
     file_versions: HashMap<[u8; 32], [u8; 32]>,
+    /// Named branch heads per file ID
+    branches: HashMap<[u8; 32], BTreeMap<String, [u8; 32]>>,
+    /// Named tags per file ID
+    tags: HashMap<[u8; 32], BTreeMap<String, [u8; 32]>>,
+    /// File ID of each version
+    version_files: HashMap<[u8; 32], [u8; 32]>,
+    /// Tag every Nth version of a file automatically (0 = disabled)
+    auto_tag_interval: usize,
+    /// Number of versions ever created per file ID
+    version_counts: HashMap<[u8; 32], usize>,
+    /// Key signing new versions, if any
+    signer: Option<MetadataSigner>,
 }
 
 impl VersionManager {
     /// Create a new version manager
-    pub fn new(chunk_registry: Arc<RwLock<ChunkRegistry>>) -> Self {
+    pub fn new(chunk_registry: Arc<ChunkRegistry>) -> Self {
         Self {
             versions: HashMap::new(),
             chunk_registry,
             file_versions: HashMap::new(),
+            branches: HashMap::new(),
+            tags: HashMap::new(),
+            version_files: HashMap::new(),
+            auto_tag_interval: 0,
+            version_counts: HashMap::new(),
+            signer: None,
+        }
+    }
+
+    /// Tag every `interval`th version of each file as `auto-<n>`
+    ///
+    /// `n` counts every version created for the file, including pruned
+    /// ones, so tag names are never reused. 0 disables auto-tagging.
+    pub fn with_auto_tag_interval(mut self, interval: usize) -> Self {
+        self.auto_tag_interval = interval;
+        self
+    }
+
+    /// Sign every version created from now on with `signer`
+    pub fn with_signer(mut self, signer: MetadataSigner) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Check that every version in a file's history is validly signed
+    ///
+    /// With a non-empty `trusted` list, each signer's public key must also
+    /// be on it.
+    pub fn verify_history(&self, file_id: &[u8; 32], trusted: &[Vec<u8>]) -> Result<()> {
+        for version in self.history(file_id) {
+            let signer = version.verify_signature().map_err(|e| match e {
+                VersionError::Metadata(error) => VersionError::Unverified {
+                    version: hex::encode(version.metadata_hash),
+                    error,
+                },
+                e => e,
+            })?;
+            if !trusted.is_empty() && !trusted.iter().any(|key| key.as_slice() == signer) {
+                return Err(VersionError::UntrustedSigner(hex::encode(
+                    version.metadata_hash,
+                )));
+            }
         }
+        Ok(())
     }
 
     /// Create a new version from metadata
@@ -174,7 +440,7 @@ impl VersionManager {
                 self.versions
                     .get(&parent_hash)
                     .cloned()
-                    .context("Parent version not found")?,
+                    .ok_or_else(|| version_not_found(&parent_hash))?,
             )
         } else {
             // Check if this is an update to an existing file
@@ -196,21 +462,54 @@ impl VersionManager {
             .with_removed_chunks(removed.clone());
 
         if let Some(parent) = parent_node {
-            node = node.with_parent(parent);
+            node = node.with_parent(parent.metadata_hash);
         }
 
-        // Update chunk registry
+        let sequence = self
+            .version_counts
+            .get(&metadata.file_id)
+            .copied()
+            .unwrap_or(0)
+            + 1;
+        let mut info = LocalVersionInfo::new();
+        let auto_tag = format!("auto-{}", sequence);
+        let auto_tag = (self.auto_tag_interval > 0
+            && sequence % self.auto_tag_interval == 0
+            && !self.name_taken(&metadata.file_id, &auto_tag))
+        .then_some(auto_tag);
+        if let Some(tag) = &auto_tag {
+            info = info.with_tag(tag.clone());
+        }
+        node.local_info = Some(info);
+
+        // Every version holds one reference to each of its chunks
         {
-            let mut registry = self.chunk_registry.write();
-            registry.increment_refs(&metadata.chunks)?;
-            if !removed.is_empty() {
-                registry.decrement_refs(&removed)?;
-            }
+            let mut seen = HashSet::new();
+            let unique: Vec<_> = metadata
+                .chunks
+                .iter()
+                .filter(|c| seen.insert(c.chunk_id))
+                .cloned()
+                .collect();
+            self.chunk_registry
+                .apply_refs(&RefUpdate::new().increment_all(unique))?;
+        }
+
+        if let Some(signer) = &self.signer {
+            node.sign(signer)?;
         }
 
         // Store version
+        self.version_counts.insert(metadata.file_id, sequence);
         self.versions.insert(metadata_hash, node.clone());
+        self.version_files.insert(metadata_hash, metadata.file_id);
         self.file_versions.insert(metadata.file_id, metadata_hash);
+        if let Some(tag) = auto_tag {
+            self.tags
+                .entry(metadata.file_id)
+                .or_default()
+                .insert(tag, metadata_hash);
+        }
 
         Ok(node)
     }
@@ -224,21 +523,213 @@ impl VersionManager {
 
     /// Get version history for a file
     pub fn get_history(&self, file_id: &[u8; 32]) -> Vec<VersionNode> {
-        let mut history = Vec::new();
+        let mut history: Vec<VersionNode> = self.history(file_id).cloned().collect();
+
+        history.reverse(); // Oldest first
+        history
+    }
+
+    /// Walk the history of a file from the latest version back, lazily
+    ///
+    /// Unlike [`get_history`](Self::get_history) nothing is cloned or
+    /// collected, so stopping early after a few versions stays cheap however
+    /// long the history is.
+    pub fn history<'a>(&'a self, file_id: &[u8; 32]) -> impl Iterator<Item = &'a VersionNode> {
+        self.find_previous_version(file_id)
+            .into_iter()
+            .flat_map(move |latest| self.lineage(latest))
+    }
+
+    /// Version of a file that was current at Unix time `timestamp`
+    ///
+    /// This is the newest version in the file's history created at or
+    /// before `timestamp`, or `None` if the file did not exist yet. Versions
+    /// without a creation time are skipped.
+    pub fn get_version_at(&self, file_id: &[u8; 32], timestamp: u64) -> Option<&VersionNode> {
+        self.history(file_id).find(|version| {
+            version
+                .local_info
+                .as_ref()
+                .is_some_and(|info| info.created_at <= timestamp)
+        })
+    }
+
+    /// Versions whose parent or merge parent is `hash`
+    pub fn children(&self, hash: &[u8; 32]) -> Vec<&VersionNode> {
+        self.versions
+            .values()
+            .filter(|v| v.parent.as_ref() == Some(hash) || v.merge_parent.as_ref() == Some(hash))
+            .collect()
+    }
+
+    /// Start a branch of a file named `name` at version `at`
+    pub fn create_branch(
+        &mut self,
+        file_id: &[u8; 32],
+        name: impl Into<String>,
+        at: &[u8; 32],
+    ) -> Result<()> {
+        self.check_file(file_id, at)?;
+        let name = name.into();
+        if self.name_taken(file_id, &name) {
+            return Err(VersionError::NameTaken(name));
+        }
+        self.branches.entry(*file_id).or_default().insert(name, *at);
+        Ok(())
+    }
+
+    /// Delete a branch, leaving its versions in place
+    pub fn delete_branch(&mut self, file_id: &[u8; 32], name: &str) -> Result<()> {
+        self.branches
+            .get_mut(file_id)
+            .and_then(|b| b.remove(name))
+            .ok_or_else(|| VersionError::BranchNotFound(name.to_string()))?;
+        Ok(())
+    }
+
+    /// Head version of a branch
+    pub fn branch_head(&self, file_id: &[u8; 32], name: &str) -> Option<&VersionNode> {
+        self.branches
+            .get(file_id)?
+            .get(name)
+            .and_then(|hash| self.versions.get(hash))
+    }
 
-        if let Some(latest_hash) = self.file_versions.get(file_id) {
-            if let Some(mut node) = self.versions.get(latest_hash).cloned() {
-                history.push(node.clone());
+    /// Branches of a file with their head hashes, by name
+    pub fn branches(&self, file_id: &[u8; 32]) -> Vec<(String, [u8; 32])> {
+        self.branches
+            .get(file_id)
+            .map(|b| b.iter().map(|(name, head)| (name.clone(), *head)).collect())
+            .unwrap_or_default()
+    }
 
-                while let Some(parent) = node.parent {
-                    history.push(parent.as_ref().clone());
-                    node = parent.as_ref().clone();
-                }
+    /// Create a version on top of a branch and advance the branch to it
+    pub fn commit_to_branch(&mut self, name: &str, metadata: &FileMetadata) -> Result<VersionNode> {
+        let head = *self
+            .branches
+            .get(&metadata.file_id)
+            .and_then(|b| b.get(name))
+            .ok_or_else(|| VersionError::BranchNotFound(name.to_string()))?;
+        let node = self.create_version(&metadata.clone().with_parent(head))?;
+        if let Some(branches) = self.branches.get_mut(&metadata.file_id) {
+            branches.insert(name.to_string(), node.metadata_hash);
+        }
+        Ok(node)
+    }
+
+    /// Three-way merge of the chunk sets of versions `a` and `b`
+    ///
+    /// Changes made on either side since the common ancestor are combined.
+    /// A base chunk that both sides removed while adding different chunks is
+    /// reported as a conflict.
+    pub fn merge(&self, a: &[u8; 32], b: &[u8; 32]) -> Result<MergeResult> {
+        let node_a = self.versions.get(a).ok_or_else(|| version_not_found(a))?;
+        let node_b = self.versions.get(b).ok_or_else(|| version_not_found(b))?;
+
+        let base = self.common_ancestor(a, b);
+        let base_chunks: HashSet<_> = match &base {
+            Some(hash) => self.get_version_chunks(&self.versions[hash])?,
+            None => Vec::new(),
+        }
+        .into_iter()
+        .collect();
+        let ours: HashSet<_> = self.get_version_chunks(node_a)?.into_iter().collect();
+        let theirs: HashSet<_> = self.get_version_chunks(node_b)?.into_iter().collect();
+
+        let ours_added: Vec<_> = ours.difference(&base_chunks).copied().collect();
+        let theirs_added: Vec<_> = theirs.difference(&base_chunks).copied().collect();
+        let removed_by_both = base_chunks
+            .iter()
+            .filter(|c| !ours.contains(*c) && !theirs.contains(*c));
+
+        let mut conflicts = Vec::new();
+        if !ours_added.is_empty() && !theirs_added.is_empty() && ours_added != theirs_added {
+            for chunk in removed_by_both {
+                conflicts.push(MergeConflict {
+                    base_chunk: *chunk,
+                    ours: ours_added.clone(),
+                    theirs: theirs_added.clone(),
+                });
             }
         }
 
-        history.reverse(); // Oldest first
-        history
+        // Keep what both kept, plus everything either side added
+        let mut chunks: HashSet<_> = ours.intersection(&theirs).copied().collect();
+        chunks.extend(ours_added);
+        chunks.extend(theirs_added);
+
+        Ok(MergeResult {
+            base,
+            chunks: chunks.into_iter().collect(),
+            conflicts,
+        })
+    }
+
+    /// Record `metadata`, whose parent is one side of a merge, as merging in `other`
+    pub fn create_merge_version(
+        &mut self,
+        metadata: &FileMetadata,
+        other: &[u8; 32],
+    ) -> Result<VersionNode> {
+        if !self.versions.contains_key(other) {
+            return Err(version_not_found(other));
+        }
+        let mut node = self.create_version(metadata)?;
+        node.merge_parent = Some(*other);
+        if let Some(signer) = &self.signer {
+            node.sign(signer)?;
+        }
+        self.versions.insert(node.metadata_hash, node.clone());
+        Ok(node)
+    }
+
+    /// Nearest version that both `a` and `b` descend from
+    fn common_ancestor(&self, a: &[u8; 32], b: &[u8; 32]) -> Option<[u8; 32]> {
+        let ours = self.reachable(a);
+        self.reachable_in_order(b)
+            .into_iter()
+            .find(|hash| ours.contains(hash))
+    }
+
+    fn reachable(&self, from: &[u8; 32]) -> HashSet<[u8; 32]> {
+        self.reachable_in_order(from).into_iter().collect()
+    }
+
+    /// `from` and every version it descends from, nearest first
+    fn reachable_in_order(&self, from: &[u8; 32]) -> Vec<[u8; 32]> {
+        let mut seen = HashSet::new();
+        let mut order = Vec::new();
+        let mut queue = VecDeque::from([*from]);
+        while let Some(hash) = queue.pop_front() {
+            if !seen.insert(hash) {
+                continue;
+            }
+            order.push(hash);
+            if let Some(node) = self.versions.get(&hash) {
+                queue.extend(node.parent.iter().chain(node.merge_parent.iter()));
+            }
+        }
+        order
+    }
+
+    /// Get depth of a version in the version tree
+    pub fn depth(&self, version: &VersionNode) -> usize {
+        self.lineage(version).count() - 1
+    }
+
+    /// Get all ancestor metadata hashes, nearest first
+    pub fn ancestors(&self, version: &VersionNode) -> Vec<[u8; 32]> {
+        self.lineage(version)
+            .skip(1)
+            .map(|node| node.metadata_hash)
+            .collect()
+    }
+
+    /// `version` followed by each of its ancestors that is still known
+    fn lineage<'a>(&'a self, version: &'a VersionNode) -> impl Iterator<Item = &'a VersionNode> {
+        std::iter::successors(Some(version), |node| {
+            node.parent.and_then(|hash| self.versions.get(&hash))
+        })
     }
 
     /// Compute diff between two versions
@@ -255,7 +746,7 @@ impl VersionManager {
         let unchanged: Vec<_> = set1.intersection(&set2).copied().collect();
 
         // Calculate size delta
-        let registry = self.chunk_registry.read();
+        let registry = &self.chunk_registry;
         let size_added: i64 = added
             .iter()
             .filter_map(|id| registry.get_chunk_size(id))
@@ -275,60 +766,339 @@ impl VersionManager {
         })
     }
 
+    /// Chunks shared by the latest versions of two files
+    pub fn shared_chunks(&self, file_a: &[u8; 32], file_b: &[u8; 32]) -> Result<ChunkOverlap> {
+        let a = self
+            .find_previous_version(file_a)
+            .ok_or_else(|| VersionError::NoVersions(hex::encode(file_a)))?;
+        let b = self
+            .find_previous_version(file_b)
+            .ok_or_else(|| VersionError::NoVersions(hex::encode(file_b)))?;
+        self.shared_version_chunks(a, b)
+    }
+
+    /// Chunks shared by two versions, of the same file or different ones
+    pub fn shared_version_chunks(&self, a: &VersionNode, b: &VersionNode) -> Result<ChunkOverlap> {
+        let chunks_a: HashSet<_> = self.get_version_chunks(a)?.into_iter().collect();
+        let shared: Vec<_> = self
+            .get_version_chunks(b)?
+            .into_iter()
+            .filter(|chunk| chunks_a.contains(chunk))
+            .collect();
+
+        let registry = &self.chunk_registry;
+        let bytes_saved = shared
+            .iter()
+            .filter_map(|id| registry.get_chunk_size(id))
+            .map(u64::from)
+            .sum();
+        Ok(ChunkOverlap {
+            shared,
+            bytes_saved,
+        })
+    }
+
+    /// Report chunk sharing across every tracked version of every file
+    pub fn overlap_report(&self) -> Result<OverlapReport> {
+        let mut usage: HashMap<[u8; 32], usize> = HashMap::new();
+        for version in self.versions.values() {
+            for chunk in self.get_version_chunks(version)? {
+                *usage.entry(chunk).or_insert(0) += 1;
+            }
+        }
+
+        let registry = &self.chunk_registry;
+        let mut report = OverlapReport {
+            versions: self.versions.len(),
+            unique_chunks: usage.len(),
+            ..OverlapReport::default()
+        };
+        for (chunk_id, versions) in usage {
+            let size = registry.get_chunk_size(&chunk_id).unwrap_or(0);
+            report.logical_bytes += size as u64 * versions as u64;
+            report.stored_bytes += size as u64;
+            if versions > 1 {
+                report.shared.push(SharedChunk {
+                    chunk_id,
+                    size,
+                    versions,
+                });
+            }
+        }
+        report.shared.sort_by(|a, b| {
+            b.bytes_saved()
+                .cmp(&a.bytes_saved())
+                .then(a.chunk_id.cmp(&b.chunk_id))
+        });
+        Ok(report)
+    }
+
     /// Get specific version by hash
     pub fn get_version(&self, hash: &[u8; 32]) -> Option<&VersionNode> {
         self.versions.get(hash)
     }
 
-    /// Remove a version (careful - this affects chunk references)
+    /// Remove a version, releasing its chunk references
+    ///
+    /// Children of the version are re-parented onto its parent with their
+    /// chunk diffs recomputed, and file and branch heads pointing at it move
+    /// to the parent. Re-parented versions are re-signed with this manager's
+    /// signer, or lose their signature without one.
     pub fn remove_version(&mut self, hash: &[u8; 32]) -> Result<()> {
-        let node = self.versions.remove(hash).context("Version not found")?;
+        let node = self
+            .versions
+            .get(hash)
+            .ok_or_else(|| version_not_found(hash))?;
+        let chunks = self.get_version_chunks(node)?;
+        let rebased = self.rebase_children(hash)?;
+
+        // Release references first so a failure leaves the tree untouched
+        self.chunk_registry
+            .apply_refs(&RefUpdate::new().decrement_all(chunks))?;
+        self.detach_version(hash, rebased)
+    }
 
-        // Update chunk references
-        let mut registry = self.chunk_registry.write();
-        if !node.chunks_removed.is_empty() {
-            // Re-increment refs for chunks that were marked as removed
-            // (since we're removing the version that removed them)
-            for chunk_id in &node.chunks_removed {
-                registry.increment_ref(chunk_id)?;
+    /// New parent links and chunk diffs of the children of `hash` once it
+    /// is removed
+    fn rebase_children(&self, hash: &[u8; 32]) -> Result<Vec<Rebase>> {
+        let node = self
+            .versions
+            .get(hash)
+            .ok_or_else(|| version_not_found(hash))?;
+        let parent = node.parent;
+        let parent_chunks: HashSet<_> = match parent.and_then(|p| self.versions.get(&p)) {
+            Some(parent_node) => self.get_version_chunks(parent_node)?.into_iter().collect(),
+            None => HashSet::new(),
+        };
+
+        let mut rebased = Vec::new();
+        for child in self.versions.values().filter(|v| v.parent == Some(*hash)) {
+            let child_chunks: HashSet<_> = self.get_version_chunks(child)?.into_iter().collect();
+            rebased.push((
+                child.metadata_hash,
+                child_chunks.difference(&parent_chunks).copied().collect(),
+                parent_chunks.difference(&child_chunks).copied().collect(),
+            ));
+        }
+        Ok(rebased)
+    }
+
+    /// Take a version whose references are already released out of the tree
+    fn detach_version(&mut self, hash: &[u8; 32], rebased: Vec<Rebase>) -> Result<()> {
+        let parent = self
+            .versions
+            .remove(hash)
+            .ok_or_else(|| version_not_found(hash))?
+            .parent;
+        for (child, added, removed) in rebased {
+            if let Some(child) = self.versions.get_mut(&child) {
+                child.parent = parent;
+                child.chunks_added = added;
+                child.chunks_removed = removed;
+                resign(child, self.signer.as_ref())?;
+            }
+        }
+        for version in self.versions.values_mut() {
+            if version.merge_parent == Some(*hash) {
+                version.merge_parent = None;
+                resign(version, self.signer.as_ref())?;
             }
         }
-        if !node.chunks_added.is_empty() {
-            // Decrement refs for chunks that were added
-            registry.decrement_refs(&node.chunks_added)?;
+
+        let heads = self
+            .file_versions
+            .values_mut()
+            .chain(self.branches.values_mut().flat_map(|b| b.values_mut()));
+        let mut orphaned = false;
+        for head in heads.filter(|head| **head == *hash) {
+            match parent {
+                Some(parent) => *head = parent,
+                None => orphaned = true,
+            }
+        }
+        if orphaned {
+            self.file_versions.retain(|_, head| head != hash);
+            for branches in self.branches.values_mut() {
+                branches.retain(|_, head| head != hash);
+            }
         }
+        for tags in self.tags.values_mut() {
+            tags.retain(|_, tagged| tagged != hash);
+        }
+        self.version_files.remove(hash);
 
         Ok(())
     }
 
-    /// Tag a version with a name
-    pub fn tag_version(&mut self, hash: &[u8; 32], tag: impl Into<String>) -> Result<()> {
-        let version = self.versions.get_mut(hash).context("Version not found")?;
+    /// Prune the oldest versions of a file beyond `config.max_versions`
+    ///
+    /// The latest version and branch heads are never pruned, nor are tagged
+    /// versions if `config.keep_tagged` is set. Returns the pruned hashes,
+    /// oldest first.
+    pub fn prune(&mut self, file_id: &[u8; 32], config: &VersionConfig) -> Result<Vec<[u8; 32]>> {
+        let mut history: Vec<&VersionNode> = self.history(file_id).collect();
+        if config.max_versions == 0 || history.len() <= config.max_versions {
+            return Ok(Vec::new());
+        }
+
+        let mut protected: HashSet<[u8; 32]> = self
+            .branches
+            .get(file_id)
+            .map(|b| b.values().copied().collect())
+            .unwrap_or_default();
+        protected.extend(self.file_versions.get(file_id));
+        if config.keep_tagged {
+            protected.extend(self.tags.get(file_id).into_iter().flat_map(|t| t.values()));
+        }
+
+        let excess = history.len() - config.max_versions;
+        history.reverse(); // Oldest first
+        let candidates: Vec<[u8; 32]> = history
+            .into_iter()
+            .filter(|v| !protected.contains(&v.metadata_hash))
+            .map(|v| v.metadata_hash)
+            .take(excess)
+            .collect();
+
+        // Release every pruned version in one update, so a failure or crash
+        // never leaves counts for only some of them
+        let mut chunks = Vec::new();
+        for hash in &candidates {
+            let node = self
+                .versions
+                .get(hash)
+                .ok_or_else(|| version_not_found(hash))?;
+            chunks.extend(self.get_version_chunks(node)?);
+        }
+        self.chunk_registry
+            .apply_refs(&RefUpdate::new().decrement_all(chunks))?;
 
-        if version.local_info.is_none() {
-            version.local_info = Some(LocalVersionInfo::new());
+        for hash in &candidates {
+            let rebased = self.rebase_children(hash)?;
+            self.detach_version(hash, rebased)?;
         }
+        Ok(candidates)
+    }
 
-        if let Some(info) = &mut version.local_info {
-            info.tag = Some(tag.into());
+    /// Tag a version with a name
+    ///
+    /// A version can carry several tags, but a name can only refer to one
+    /// version of a file at a time and cannot also be a branch name.
+    pub fn tag_version(&mut self, hash: &[u8; 32], tag: impl Into<String>) -> Result<()> {
+        let file_id = *self
+            .version_files
+            .get(hash)
+            .ok_or_else(|| version_not_found(hash))?;
+        let tag = tag.into();
+        match self.tags.get(&file_id).and_then(|t| t.get(&tag)) {
+            Some(tagged) if tagged == hash => return Ok(()),
+            Some(_) => return Err(VersionError::NameTaken(tag)),
+            None if self.name_taken(&file_id, &tag) => return Err(VersionError::NameTaken(tag)),
+            None => {}
         }
 
+        self.tags.entry(file_id).or_default().insert(tag, *hash);
+        self.sync_local_tag(hash);
         Ok(())
     }
 
+    /// Point an existing tag of a file at another of its versions
+    pub fn move_tag(&mut self, file_id: &[u8; 32], tag: &str, to: &[u8; 32]) -> Result<()> {
+        self.check_file(file_id, to)?;
+        let target = self
+            .tags
+            .get_mut(file_id)
+            .and_then(|t| t.get_mut(tag))
+            .ok_or_else(|| VersionError::TagNotFound(tag.to_string()))?;
+        let from = std::mem::replace(target, *to);
+        self.sync_local_tag(&from);
+        self.sync_local_tag(to);
+        Ok(())
+    }
+
+    /// Delete a tag of a file, returning the version it named
+    pub fn delete_tag(&mut self, file_id: &[u8; 32], tag: &str) -> Result<[u8; 32]> {
+        let hash = self
+            .tags
+            .get_mut(file_id)
+            .and_then(|t| t.remove(tag))
+            .ok_or_else(|| VersionError::TagNotFound(tag.to_string()))?;
+        self.sync_local_tag(&hash);
+        Ok(hash)
+    }
+
+    /// Tags of a file with the versions they name, by name
+    pub fn list_tags(&self, file_id: &[u8; 32]) -> Vec<(String, [u8; 32])> {
+        self.tags
+            .get(file_id)
+            .map(|t| t.iter().map(|(name, hash)| (name.clone(), *hash)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Tags naming a version
+    pub fn tags_of(&self, hash: &[u8; 32]) -> Vec<&str> {
+        self.version_files
+            .get(hash)
+            .and_then(|file_id| self.tags.get(file_id))
+            .map(|t| {
+                t.iter()
+                    .filter(|(_, tagged)| *tagged == hash)
+                    .map(|(name, _)| name.as_str())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Version of a file named by `tag`
+    pub fn resolve_tag(&self, file_id: &[u8; 32], tag: &str) -> Option<&VersionNode> {
+        self.tags
+            .get(file_id)?
+            .get(tag)
+            .and_then(|hash| self.versions.get(hash))
+    }
+
+    /// Version of a file named by a branch or tag
+    pub fn resolve(&self, file_id: &[u8; 32], name: &str) -> Option<&VersionNode> {
+        self.branch_head(file_id, name)
+            .or_else(|| self.resolve_tag(file_id, name))
+    }
+
     /// Get all tagged versions
     pub fn get_tagged_versions(&self) -> Vec<(&str, &VersionNode)> {
-        self.versions
+        self.tags
             .values()
-            .filter_map(|v| {
-                v.local_info
-                    .as_ref()
-                    .and_then(|info| info.tag.as_ref())
-                    .map(|tag| (tag.as_str(), v))
-            })
+            .flat_map(|t| t.iter())
+            .filter_map(|(tag, hash)| Some((tag.as_str(), self.versions.get(hash)?)))
             .collect()
     }
 
+    /// Whether `name` is already a branch or tag of a file
+    fn name_taken(&self, file_id: &[u8; 32], name: &str) -> bool {
+        [&self.branches, &self.tags]
+            .iter()
+            .any(|names| names.get(file_id).is_some_and(|n| n.contains_key(name)))
+    }
+
+    /// Fail unless `hash` is a version of `file_id`
+    fn check_file(&self, file_id: &[u8; 32], hash: &[u8; 32]) -> Result<()> {
+        match self.version_files.get(hash) {
+            Some(owner) if owner == file_id => Ok(()),
+            Some(_) => Err(VersionError::WrongFile),
+            None => Err(version_not_found(hash)),
+        }
+    }
+
+    /// Mirror the first tag of a version into its local info
+    fn sync_local_tag(&mut self, hash: &[u8; 32]) {
+        let tag = self.tags_of(hash).first().map(|t| t.to_string());
+        if let Some(version) = self.versions.get_mut(hash) {
+            version
+                .local_info
+                .get_or_insert_with(LocalVersionInfo::new)
+                .tag = tag;
+        }
+    }
+
     /// Compute chunk differences between metadata and parent
     fn compute_chunk_diff(
         &self,
@@ -350,9 +1120,10 @@ impl VersionManager {
     /// Get all chunks for a version (traversing up the tree)
     fn get_version_chunks(&self, version: &VersionNode) -> Result<Vec<[u8; 32]>> {
         let mut chunks = HashSet::new();
-        let mut current = Some(version);
+        let lineage: Vec<&VersionNode> = self.lineage(version).collect();
 
-        while let Some(node) = current {
+        // Replay from the oldest version so later removals win
+        for node in lineage.into_iter().rev() {
             // Add chunks from this version
             for chunk_id in &node.chunks_added {
                 chunks.insert(*chunk_id);
@@ -362,17 +1133,68 @@ impl VersionManager {
             for chunk_id in &node.chunks_removed {
                 chunks.remove(chunk_id);
             }
-
-            current = node.parent.as_deref();
         }
 
         Ok(chunks.into_iter().collect())
     }
 }
 
+/// Serialize a version delta, tagged with whether it is compressed
+pub(crate) fn encode_delta<T: Serialize>(value: &T, compress: bool) -> Result<Vec<u8>> {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    let serialized = bincode::serialize(value)?;
+    if !compress {
+        let mut bytes = Vec::with_capacity(serialized.len() + 1);
+        bytes.push(DELTA_RAW);
+        bytes.extend_from_slice(&serialized);
+        return Ok(bytes);
+    }
+
+    let mut encoder = GzEncoder::new(vec![DELTA_GZIP], Compression::default());
+    encoder
+        .write_all(&serialized)
+        .map_err(VersionError::Compression)?;
+    encoder.finish().map_err(VersionError::Compression)
+}
+
+/// Deserialize a version delta written by [`encode_delta`]
+pub(crate) fn decode_delta<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    let (format, body) = bytes.split_first().ok_or(VersionError::EmptyDelta)?;
+    let serialized = match *format {
+        DELTA_RAW => std::borrow::Cow::Borrowed(body),
+        DELTA_GZIP => {
+            let mut decompressed = Vec::new();
+            GzDecoder::new(body)
+                .read_to_end(&mut decompressed)
+                .map_err(VersionError::Compression)?;
+            std::borrow::Cow::Owned(decompressed)
+        }
+        other => return Err(VersionError::UnknownDeltaFormat(other)),
+    };
+    Ok(bincode::deserialize(&serialized)?)
+}
+
+/// Re-sign a version whose parents changed, or drop its stale signature
+fn resign(version: &mut VersionNode, signer: Option<&MetadataSigner>) -> Result<()> {
+    match signer {
+        Some(signer) => version.sign(signer),
+        None => {
+            version.signature = None;
+            Ok(())
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use anyhow::Result;
 
     fn create_test_metadata(file_id: [u8; 32], chunk_ids: Vec<[u8; 32]>) -> FileMetadata {
         use crate::metadata::ChunkReference;
@@ -386,33 +1208,50 @@ mod tests {
         FileMetadata::new(file_id, 1024 * chunks.len() as u64, None, chunks)
     }
 
+    /// Three versions of one file, oldest first
+    fn create_test_chain(manager: &mut VersionManager) -> Vec<VersionNode> {
+        let file_id = [10u8; 32];
+        let mut versions: Vec<VersionNode> = Vec::new();
+        for i in 1..=3u8 {
+            let mut metadata = create_test_metadata(file_id, vec![[i; 32]]);
+            if let Some(parent) = versions.last() {
+                metadata = metadata.with_parent(parent.metadata_hash);
+            }
+            versions.push(manager.create_version(&metadata).unwrap());
+        }
+        versions
+    }
+
     #[test]
     fn test_version_node_depth() {
-        let v1 = VersionNode::new([1u8; 32]);
-        assert_eq!(v1.depth(), 0);
-
-        let v2 = VersionNode::new([2u8; 32]).with_parent(v1.clone());
-        assert_eq!(v2.depth(), 1);
+        let registry = Arc::new(ChunkRegistry::new());
+        let mut manager = VersionManager::new(registry);
+        let versions = create_test_chain(&mut manager);
 
-        let v3 = VersionNode::new([3u8; 32]).with_parent(v2);
-        assert_eq!(v3.depth(), 2);
+        assert_eq!(manager.depth(&versions[0]), 0);
+        assert_eq!(manager.depth(&versions[1]), 1);
+        assert_eq!(manager.depth(&versions[2]), 2);
     }
 
     #[test]
     fn test_version_ancestors() {
-        let v1 = VersionNode::new([1u8; 32]);
-        let v2 = VersionNode::new([2u8; 32]).with_parent(v1.clone());
-        let v3 = VersionNode::new([3u8; 32]).with_parent(v2.clone());
-
-        let ancestors = v3.ancestors();
-        assert_eq!(ancestors.len(), 2);
-        assert_eq!(ancestors[0], [2u8; 32]);
-        assert_eq!(ancestors[1], [1u8; 32]);
+        let registry = Arc::new(ChunkRegistry::new());
+        let mut manager = VersionManager::new(registry);
+        let versions = create_test_chain(&mut manager);
+
+        // Nodes hold only the parent's hash, not the whole chain
+        assert_eq!(versions[2].parent, Some(versions[1].metadata_hash));
+        let ancestors = manager.ancestors(&versions[2]);
+        assert_eq!(
+            ancestors,
+            vec![versions[1].metadata_hash, versions[0].metadata_hash]
+        );
+        assert_eq!(manager.get_history(&[10u8; 32]).len(), 3);
     }
 
     #[test]
     fn test_version_manager_create() {
-        let registry = Arc::new(RwLock::new(ChunkRegistry::new()));
+        let registry = Arc::new(ChunkRegistry::new());
         let mut manager = VersionManager::new(registry);
 
         let metadata = create_test_metadata([10u8; 32], vec![[1u8; 32], [2u8; 32]]);
@@ -425,7 +1264,7 @@ mod tests {
 
     #[test]
     fn test_version_history() {
-        let registry = Arc::new(RwLock::new(ChunkRegistry::new()));
+        let registry = Arc::new(ChunkRegistry::new());
         let mut manager = VersionManager::new(registry);
 
         let file_id = [10u8; 32];
@@ -443,9 +1282,277 @@ mod tests {
         assert_eq!(history.len(), 2);
     }
 
+    #[test]
+    fn test_history_iterator_is_newest_first() {
+        let registry = Arc::new(ChunkRegistry::new());
+        let mut manager = VersionManager::new(registry);
+        let versions = create_test_chain(&mut manager);
+
+        let mut history = manager.history(&[10u8; 32]);
+        assert_eq!(
+            history.next().map(|v| v.metadata_hash),
+            Some(versions[2].metadata_hash)
+        );
+        assert_eq!(history.count(), 2);
+        assert_eq!(manager.history(&[0u8; 32]).count(), 0);
+    }
+
+    #[test]
+    fn test_branch_and_merge() -> Result<()> {
+        let registry = Arc::new(ChunkRegistry::new());
+        let mut manager = VersionManager::new(registry);
+        let file_id = [10u8; 32];
+
+        let base = manager.create_version(&create_test_metadata(
+            file_id,
+            vec![[1u8; 32], [2u8; 32], [3u8; 32]],
+        ))?;
+        manager.create_branch(&file_id, "laptop", &base.metadata_hash)?;
+        manager.create_branch(&file_id, "phone", &base.metadata_hash)?;
+        assert!(manager
+            .create_branch(&file_id, "phone", &base.metadata_hash)
+            .is_err());
+
+        // The laptop drops chunk 2; the phone replaces chunk 3
+        let laptop = manager.commit_to_branch(
+            "laptop",
+            &create_test_metadata(file_id, vec![[1u8; 32], [3u8; 32]]),
+        )?;
+        let phone = manager.commit_to_branch(
+            "phone",
+            &create_test_metadata(file_id, vec![[1u8; 32], [2u8; 32], [4u8; 32]]),
+        )?;
+        assert_eq!(manager.children(&base.metadata_hash).len(), 2);
+        assert_eq!(
+            manager
+                .branch_head(&file_id, "phone")
+                .unwrap()
+                .metadata_hash,
+            phone.metadata_hash
+        );
+
+        let merged = manager.merge(&laptop.metadata_hash, &phone.metadata_hash)?;
+        assert_eq!(merged.base, Some(base.metadata_hash));
+        assert!(merged.is_clean());
+        let mut chunks = merged.chunks.clone();
+        chunks.sort();
+        assert_eq!(chunks, vec![[1u8; 32], [4u8; 32]]);
+
+        // Replacing chunk 3 differently on the laptop too conflicts
+        let laptop = manager.commit_to_branch(
+            "laptop",
+            &create_test_metadata(file_id, vec![[1u8; 32], [5u8; 32]]),
+        )?;
+        let merged = manager.merge(&laptop.metadata_hash, &phone.metadata_hash)?;
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(merged.conflicts[0].base_chunk, [3u8; 32]);
+
+        let resolved = create_test_metadata(file_id, vec![[1u8; 32], [5u8; 32]])
+            .with_parent(laptop.metadata_hash);
+        let node = manager.create_merge_version(&resolved, &phone.metadata_hash)?;
+        assert_eq!(node.merge_parent, Some(phone.metadata_hash));
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune_keeps_tagged_versions() -> Result<()> {
+        let registry = Arc::new(ChunkRegistry::new());
+        let mut manager = VersionManager::new(registry.clone());
+        let versions = create_test_chain(&mut manager);
+        let file_id = [10u8; 32];
+        manager.tag_version(&versions[0].metadata_hash, "release")?;
+
+        let config = VersionConfig {
+            max_versions: 1,
+            ..VersionConfig::default()
+        };
+        let pruned = manager.prune(&file_id, &config)?;
+        assert_eq!(pruned, vec![versions[1].metadata_hash]);
+        assert_eq!(registry.get_ref_count(&[2u8; 32]), Some(0));
+
+        // The survivor is rebased onto the tagged version
+        let history = manager.get_history(&file_id);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].parent, Some(versions[0].metadata_hash));
+        assert_eq!(history[1].chunks_added, vec![[3u8; 32]]);
+        assert_eq!(history[1].chunks_removed, vec![[1u8; 32]]);
+
+        let config = VersionConfig {
+            keep_tagged: false,
+            ..config
+        };
+        assert_eq!(
+            manager.prune(&file_id, &config)?,
+            vec![versions[0].metadata_hash]
+        );
+        let latest = manager.find_previous_version(&file_id).unwrap();
+        assert_eq!(latest.parent, None);
+        assert_eq!(latest.chunks_added, vec![[3u8; 32]]);
+        assert_eq!(registry.get_ref_count(&[1u8; 32]), Some(0));
+        assert_eq!(registry.get_ref_count(&[3u8; 32]), Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_tag_interval() {
+        let registry = Arc::new(ChunkRegistry::new());
+        let mut manager = VersionManager::new(registry).with_auto_tag_interval(2);
+        let versions = create_test_chain(&mut manager);
+
+        let tag = |v: &VersionNode| v.local_info.as_ref().and_then(|i| i.tag.clone());
+        assert_eq!(tag(&versions[0]), None);
+        assert_eq!(tag(&versions[1]), Some("auto-2".to_string()));
+        assert_eq!(tag(&versions[2]), None);
+        assert_eq!(manager.get_tagged_versions().len(), 1);
+    }
+
+    #[test]
+    fn test_signed_versions() -> Result<()> {
+        let signer = MetadataSigner::generate()?;
+        let registry = Arc::new(ChunkRegistry::new());
+        let mut manager = VersionManager::new(registry).with_signer(signer.clone());
+        let versions = create_test_chain(&mut manager);
+        let file_id = [10u8; 32];
+
+        manager.verify_history(&file_id, &[signer.public_key()])?;
+        let other = MetadataSigner::generate()?;
+        assert!(matches!(
+            manager.verify_history(&file_id, &[other.public_key()]),
+            Err(VersionError::UntrustedSigner(_))
+        ));
+
+        // Splicing a version onto another parent breaks its signature
+        let mut spliced = versions[2].clone();
+        spliced.parent = Some(versions[0].metadata_hash);
+        assert!(matches!(
+            spliced.verify_signature(),
+            Err(VersionError::Metadata(MetadataError::InvalidSignature))
+        ));
+
+        // Pruning re-signs the re-parented version
+        manager.remove_version(&versions[1].metadata_hash)?;
+        manager.verify_history(&file_id, &[signer.public_key()])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunk_overlap() -> Result<()> {
+        let registry = Arc::new(ChunkRegistry::new());
+        let mut manager = VersionManager::new(registry);
+        let a =
+            manager.create_version(&create_test_metadata([1u8; 32], vec![[1u8; 32], [2u8; 32]]))?;
+        manager.create_version(&create_test_metadata([2u8; 32], vec![[2u8; 32], [3u8; 32]]))?;
+        manager.create_version(
+            &create_test_metadata([1u8; 32], vec![[1u8; 32], [4u8; 32]])
+                .with_parent(a.metadata_hash),
+        )?;
+
+        let overlap = manager.shared_chunks(&[1u8; 32], &[2u8; 32])?;
+        assert!(overlap.shared.is_empty());
+        let b = manager.find_previous_version(&[2u8; 32]).unwrap();
+        let overlap = manager.shared_version_chunks(&a, b)?;
+        assert_eq!(overlap.shared, vec![[2u8; 32]]);
+        assert_eq!(overlap.bytes_saved, 1024);
+
+        let report = manager.overlap_report()?;
+        assert_eq!(report.versions, 3);
+        assert_eq!(report.unique_chunks, 4);
+        assert_eq!(report.logical_bytes, 6 * 1024);
+        assert_eq!(report.bytes_saved(), 2 * 1024);
+        assert_eq!(report.shared.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_version_at() {
+        let registry = Arc::new(ChunkRegistry::new());
+        let mut manager = VersionManager::new(registry);
+        let versions = create_test_chain(&mut manager);
+        for (version, created_at) in versions.iter().zip([100, 200, 300]) {
+            let node = manager.versions.get_mut(&version.metadata_hash).unwrap();
+            node.local_info.as_mut().unwrap().created_at = created_at;
+        }
+
+        let file_id = [10u8; 32];
+        let at = |t| manager.get_version_at(&file_id, t).map(|v| v.metadata_hash);
+        assert_eq!(at(50), None);
+        assert_eq!(at(100), Some(versions[0].metadata_hash));
+        assert_eq!(at(250), Some(versions[1].metadata_hash));
+        assert_eq!(at(u64::MAX), Some(versions[2].metadata_hash));
+    }
+
+    #[test]
+    fn test_tag_namespace() -> Result<()> {
+        let registry = Arc::new(ChunkRegistry::new());
+        let mut manager = VersionManager::new(registry);
+        let versions = create_test_chain(&mut manager);
+        let file_id = [10u8; 32];
+        let [v1, v2, v3] = [0, 1, 2].map(|i| versions[i].metadata_hash);
+
+        manager.tag_version(&v1, "stable")?;
+        manager.tag_version(&v1, "v1.0")?;
+        assert_eq!(manager.tags_of(&v1), vec!["stable", "v1.0"]);
+        assert!(manager.tag_version(&v2, "stable").is_err());
+
+        manager.create_branch(&file_id, "dev", &v3)?;
+        assert!(manager.tag_version(&v2, "dev").is_err());
+        assert!(manager.create_branch(&file_id, "stable", &v2).is_err());
+        assert!(manager.create_branch(&[0u8; 32], "other", &v2).is_err());
+
+        manager.move_tag(&file_id, "stable", &v2)?;
+        assert_eq!(
+            manager.resolve(&file_id, "stable").map(|v| v.metadata_hash),
+            Some(v2)
+        );
+        assert_eq!(
+            manager.resolve(&file_id, "dev").map(|v| v.metadata_hash),
+            Some(v3)
+        );
+        let info = manager.get_version(&v2).unwrap().local_info.as_ref();
+        assert_eq!(info.and_then(|i| i.tag.as_deref()), Some("stable"));
+
+        assert_eq!(manager.delete_tag(&file_id, "v1.0")?, v1);
+        assert!(manager.tags_of(&v1).is_empty());
+        assert_eq!(
+            manager.list_tags(&file_id),
+            vec![("stable".to_string(), v2)]
+        );
+        manager.delete_branch(&file_id, "dev")?;
+        assert!(manager.resolve(&file_id, "dev").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_node_encoding_compresses_chunk_lists() -> Result<()> {
+        // Long runs of similar chunk IDs, as in a file with many small edits
+        let chunks: Vec<[u8; 32]> = (0..2000u32)
+            .map(|i| {
+                let mut id = [0u8; 32];
+                id[..4].copy_from_slice(&i.to_be_bytes());
+                id
+            })
+            .collect();
+        let node = VersionNode::new([1u8; 32])
+            .with_parent([2u8; 32])
+            .with_added_chunks(chunks.clone())
+            .with_removed_chunks(chunks[..100].to_vec());
+
+        let raw = node.to_bytes(false)?;
+        let compressed = node.to_bytes(true)?;
+        assert!(compressed.len() < raw.len() / 4);
+
+        for bytes in [raw, compressed] {
+            let decoded = VersionNode::from_bytes(&bytes)?;
+            assert_eq!(decoded.chunks_added, chunks);
+            assert_eq!(decoded.parent, Some([2u8; 32]));
+        }
+        assert!(VersionNode::from_bytes(&[7, 0]).is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_version_tagging() {
-        let registry = Arc::new(RwLock::new(ChunkRegistry::new()));
+        let registry = Arc::new(ChunkRegistry::new());
         let mut manager = VersionManager::new(registry);
 
         let metadata = create_test_metadata([10u8; 32], vec![[1u8; 32]]);
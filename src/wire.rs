@@ -0,0 +1,738 @@
+//! Versioned binary container for shards, IDA descriptors and manifests
+//!
+//! Everything this crate hands to other processes or keeps for later can be
+//! wrapped in one self-describing container, so data written today stays
+//! readable by later crate versions and by other implementations. All
+//! integers are little-endian:
+//!
+//! | Offset | Size | Field                                          |
+//! |--------|------|------------------------------------------------|
+//! | 0      | 4    | magic, `SFEC`                                  |
+//! | 4      | 1    | major format version, currently 1              |
+//! | 5      | 1    | minor format version, currently 1              |
+//! | 6      | 1    | kind, listed below                             |
+//! | 7      | 1    | flags                                          |
+//! | 8      | 4    | params length `P`                              |
+//! | 12     | 8    | payload length `L`                             |
+//! | 20     | `P`  | params, fixed fields defined per kind          |
+//! | 20+`P` | `L`  | payload                                        |
+//! | 20+`P`+`L` | 4 | CRC-32 (IEEE) of all preceding bytes          |
+//!
+//! Params by kind:
+//!
+//! - **Shard** (1): header version `u8`, encryption mode `u8` (0 convergent,
+//!   1 convergent with secret, 2 random key), data shares `u8`, parity
+//!   shares `u8`, data size `u32`, nonce `[u8; 32]`, reserved length `u16`
+//!   and the reserved bytes. The payload is the shard data, `data size`
+//!   bytes long; the shard's CID is unchanged by wrapping.
+//! - **IDA descriptor** (2): `k` `u16`, `n` `u16`, stripe size `u32`, file size
+//!   `u64`, checksum `[u8; 32]`, code name length `u8` and the UTF-8 code
+//!   name. The payload is empty.
+//! - **Manifest** (3): file ID `[u8; 32]`, file size `u64` and chunk count
+//!   `u32`, repeated from the payload so a manifest can be identified
+//!   without parsing it. The payload is the [`FileMetadata`] as JSON.
+//! - **FEC shard** (4, since 1.1): index `u16` and CRC-32 (IEEE) `u32` of
+//!   the data. The payload is the data of a [`fec::Shard`].
+//! - **Shard manifest** (5, since 1.1): data shares `u16`, parity shares
+//!   `u16`, symbol size `u32`, original size `u64`, object ID length `u16`
+//!   and the object ID. The payload is the [`fec::ShardManifest`]'s storage
+//!   keys, each a `u16` length followed by the key.
+//!
+//! Parsers are strict: a wrong magic, unknown major version or kind, bad
+//! checksum, truncated field or trailing byte is an error. The rules that
+//! keep newer data readable are:
+//!
+//! - A new minor version may only append fields to the end of a kind's
+//!   params. Readers accept any minor version and skip params they do not
+//!   know, but only in containers of a newer minor version than their own.
+//! - Flag bits 0-3 are required: a reader rejects a container with a
+//!   required bit it does not know. Bits 4-7 are optional and ignored when
+//!   unknown. No flags are defined yet.
+//! - Manifests may gain JSON fields, which older readers ignore.
+//! - A new minor version may add kinds; older readers reject them as
+//!   unknown.
+//! - Anything else, such as a changed field, takes a new major version.
+
+use thiserror::Error;
+
+use crate::config::EncryptionMode;
+use crate::fec;
+use crate::ida::IDADescriptor;
+use crate::metadata::FileMetadata;
+use crate::storage::{Shard, ShardHeader};
+use crate::FecParams;
+
+/// Bytes opening every container
+pub const MAGIC: [u8; 4] = *b"SFEC";
+/// Major format version written and understood
+pub const MAJOR_VERSION: u8 = 1;
+/// Minor format version written
+pub const MINOR_VERSION: u8 = 1;
+
+/// Size of the fixed header before the params
+pub const HEADER_SIZE: usize = 20;
+/// Length of each storage key in a shard manifest
+pub const SHARD_KEY_LEN: usize = 32;
+/// Size of the trailing checksum
+const CHECKSUM_SIZE: usize = 4;
+/// Flag bits a reader must understand
+const REQUIRED_FLAGS: u8 = 0x0f;
+
+/// Errors parsing or writing a container
+#[derive(Debug, Error)]
+pub enum WireError {
+    #[error("Not a saorsa-fec container")]
+    BadMagic,
+
+    #[error("Unsupported container format version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Unknown container kind {0}")]
+    UnknownKind(u8),
+
+    #[error("Expected a {expected:?} container, found {found:?}")]
+    WrongKind { expected: Kind, found: Kind },
+
+    #[error("Container uses unknown required flags {0:#04x}")]
+    UnknownRequiredFlags(u8),
+
+    #[error("Container is truncated")]
+    Truncated,
+
+    #[error("{0} unexpected bytes after the container")]
+    TrailingBytes(usize),
+
+    #[error("{0} unexpected bytes after the params")]
+    TrailingParams(usize),
+
+    #[error("Container checksum mismatch")]
+    ChecksumMismatch,
+
+    #[error("Invalid {0} field")]
+    InvalidField(&'static str),
+
+    #[error("Manifest is not valid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+pub type Result<T> = std::result::Result<T, WireError>;
+
+/// What a container holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// A [`Shard`]
+    Shard,
+    /// An [`IDADescriptor`]
+    IdaDescriptor,
+    /// A file manifest, [`FileMetadata`]
+    Manifest,
+    /// A shard of the C ABI's shard layer, [`fec::Shard`]
+    FecShard,
+    /// The storage layout of an object coded by the shard layer,
+    /// [`fec::ShardManifest`]
+    ShardManifest,
+}
+
+impl Kind {
+    fn code(self) -> u8 {
+        match self {
+            Kind::Shard => 1,
+            Kind::IdaDescriptor => 2,
+            Kind::Manifest => 3,
+            Kind::FecShard => 4,
+            Kind::ShardManifest => 5,
+        }
+    }
+
+    fn from_code(code: u8) -> Result<Self> {
+        match code {
+            1 => Ok(Kind::Shard),
+            2 => Ok(Kind::IdaDescriptor),
+            3 => Ok(Kind::Manifest),
+            4 => Ok(Kind::FecShard),
+            5 => Ok(Kind::ShardManifest),
+            other => Err(WireError::UnknownKind(other)),
+        }
+    }
+}
+
+/// A parsed container
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Container {
+    /// What the container holds
+    pub kind: Kind,
+    /// Minor format version it was written with
+    pub minor_version: u8,
+    /// Flag bits
+    pub flags: u8,
+    /// Kind-specific fixed fields
+    pub params: Vec<u8>,
+    /// Kind-specific body
+    pub payload: Vec<u8>,
+}
+
+impl Container {
+    /// Container of the current format version with no flags
+    pub fn new(kind: Kind, params: Vec<u8>, payload: Vec<u8>) -> Self {
+        Self {
+            kind,
+            minor_version: MINOR_VERSION,
+            flags: 0,
+            params,
+            payload,
+        }
+    }
+
+    /// Serialize with the header and checksum
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(
+            HEADER_SIZE + self.params.len() + self.payload.len() + CHECKSUM_SIZE,
+        );
+        out.extend_from_slice(&MAGIC);
+        out.push(MAJOR_VERSION);
+        out.push(self.minor_version);
+        out.push(self.kind.code());
+        out.push(self.flags);
+        out.extend_from_slice(&(self.params.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(self.payload.len() as u64).to_le_bytes());
+        out.extend_from_slice(&self.params);
+        out.extend_from_slice(&self.payload);
+        let checksum = crc32fast::hash(&out);
+        out.extend_from_slice(&checksum.to_le_bytes());
+        out
+    }
+
+    /// Parse a container, which must span all of `bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        if reader.array::<4>()? != MAGIC {
+            return Err(WireError::BadMagic);
+        }
+        let major = reader.u8()?;
+        if major != MAJOR_VERSION {
+            return Err(WireError::UnsupportedVersion(major));
+        }
+        let minor_version = reader.u8()?;
+        let kind = Kind::from_code(reader.u8()?)?;
+        let flags = reader.u8()?;
+        if flags & REQUIRED_FLAGS != 0 {
+            return Err(WireError::UnknownRequiredFlags(flags & REQUIRED_FLAGS));
+        }
+        let params_len = reader.u32()? as usize;
+        let payload_len = usize::try_from(reader.u64()?).map_err(|_| WireError::Truncated)?;
+        let params = reader.take(params_len)?.to_vec();
+        let payload = reader.take(payload_len)?.to_vec();
+
+        let body_len = reader.pos;
+        let checksum = u32::from_le_bytes(reader.array::<4>()?);
+        if !reader.rest().is_empty() {
+            return Err(WireError::TrailingBytes(reader.rest().len()));
+        }
+        if crc32fast::hash(&bytes[..body_len]) != checksum {
+            return Err(WireError::ChecksumMismatch);
+        }
+
+        Ok(Self {
+            kind,
+            minor_version,
+            flags,
+            params,
+            payload,
+        })
+    }
+
+    /// Parse a container that must hold `expected`
+    fn parse_kind(bytes: &[u8], expected: Kind) -> Result<Self> {
+        let container = Self::from_bytes(bytes)?;
+        if container.kind != expected {
+            return Err(WireError::WrongKind {
+                expected,
+                found: container.kind,
+            });
+        }
+        Ok(container)
+    }
+}
+
+/// Total length of the container starting with `header`
+///
+/// Only the first [`HEADER_SIZE`] bytes are read, so containers written back
+/// to back can be split, or read from a stream, before each is parsed.
+pub fn container_len(header: &[u8]) -> Result<usize> {
+    let mut reader = Reader::new(header);
+    if reader.array::<4>()? != MAGIC {
+        return Err(WireError::BadMagic);
+    }
+    // Versions, kind and flags are checked when the container is parsed
+    reader.take(4)?;
+    let params_len = reader.u32()? as usize;
+    let payload_len = usize::try_from(reader.u64()?).map_err(|_| WireError::Truncated)?;
+    HEADER_SIZE
+        .checked_add(params_len)
+        .and_then(|len| len.checked_add(payload_len))
+        .and_then(|len| len.checked_add(CHECKSUM_SIZE))
+        .ok_or(WireError::Truncated)
+}
+
+/// Types with a container encoding
+pub trait WireFormat: Sized {
+    /// Kind of container holding this type
+    const KIND: Kind;
+
+    /// Wrap in a container
+    fn to_wire(&self) -> Result<Vec<u8>>;
+
+    /// Parse from a container written by any compatible version
+    fn from_wire(bytes: &[u8]) -> Result<Self>;
+}
+
+impl WireFormat for Shard {
+    const KIND: Kind = Kind::Shard;
+
+    fn to_wire(&self) -> Result<Vec<u8>> {
+        let header = &self.header;
+        let reserved_len = u16::try_from(header.reserved.len())
+            .map_err(|_| WireError::InvalidField("reserved"))?;
+        let mut params = Vec::with_capacity(42 + header.reserved.len());
+        params.push(header.version);
+        params.push(match header.encryption_mode {
+            EncryptionMode::Convergent => 0,
+            EncryptionMode::ConvergentWithSecret => 1,
+            EncryptionMode::RandomKey => 2,
+        });
+        params.push(header.nspec.0);
+        params.push(header.nspec.1);
+        params.extend_from_slice(&header.data_size.to_le_bytes());
+        params.extend_from_slice(&header.nonce);
+        params.extend_from_slice(&reserved_len.to_le_bytes());
+        params.extend_from_slice(&header.reserved);
+        Ok(Container::new(Self::KIND, params, self.data.clone()).to_bytes())
+    }
+
+    fn from_wire(bytes: &[u8]) -> Result<Self> {
+        let container = Container::parse_kind(bytes, Self::KIND)?;
+        let mut params = Reader::new(&container.params);
+        let version = params.u8()?;
+        let encryption_mode = match params.u8()? {
+            0 => EncryptionMode::Convergent,
+            1 => EncryptionMode::ConvergentWithSecret,
+            2 => EncryptionMode::RandomKey,
+            _ => return Err(WireError::InvalidField("encryption mode")),
+        };
+        let nspec = (params.u8()?, params.u8()?);
+        if nspec.0 == 0 {
+            return Err(WireError::InvalidField("data shares"));
+        }
+        let data_size = params.u32()?;
+        let nonce = params.array::<32>()?;
+        let reserved_len = params.u16()? as usize;
+        let reserved = params.take(reserved_len)?.to_vec();
+        params.finish(container.minor_version)?;
+        if data_size as usize != container.payload.len() {
+            return Err(WireError::InvalidField("data size"));
+        }
+
+        let header = ShardHeader {
+            version,
+            encryption_mode,
+            nspec,
+            data_size,
+            nonce,
+            reserved,
+        };
+        Ok(Shard::new(header, container.payload))
+    }
+}
+
+impl WireFormat for IDADescriptor {
+    const KIND: Kind = Kind::IdaDescriptor;
+
+    fn to_wire(&self) -> Result<Vec<u8>> {
+        let code_len =
+            u8::try_from(self.code.len()).map_err(|_| WireError::InvalidField("code"))?;
+        let mut params = Vec::with_capacity(49 + self.code.len());
+        params.extend_from_slice(&self.k.to_le_bytes());
+        params.extend_from_slice(&self.n.to_le_bytes());
+        params.extend_from_slice(&self.stripe_size.to_le_bytes());
+        params.extend_from_slice(&self.file_size.to_le_bytes());
+        params.extend_from_slice(&self.checksum);
+        params.push(code_len);
+        params.extend_from_slice(self.code.as_bytes());
+        Ok(Container::new(Self::KIND, params, Vec::new()).to_bytes())
+    }
+
+    fn from_wire(bytes: &[u8]) -> Result<Self> {
+        let container = Container::parse_kind(bytes, Self::KIND)?;
+        if !container.payload.is_empty() {
+            return Err(WireError::InvalidField("payload"));
+        }
+        let mut params = Reader::new(&container.params);
+        let k = params.u16()?;
+        let n = params.u16()?;
+        if k == 0 || k > n {
+            return Err(WireError::InvalidField("k"));
+        }
+        let stripe_size = params.u32()?;
+        let file_size = params.u64()?;
+        let checksum = params.array::<32>()?;
+        let code_len = params.u8()? as usize;
+        let code = std::str::from_utf8(params.take(code_len)?)
+            .map_err(|_| WireError::InvalidField("code"))?
+            .to_string();
+        params.finish(container.minor_version)?;
+
+        Ok(IDADescriptor {
+            k,
+            n,
+            stripe_size,
+            file_size,
+            code,
+            checksum,
+        })
+    }
+}
+
+impl WireFormat for FileMetadata {
+    const KIND: Kind = Kind::Manifest;
+
+    fn to_wire(&self) -> Result<Vec<u8>> {
+        let mut params = Vec::with_capacity(44);
+        params.extend_from_slice(&self.file_id);
+        params.extend_from_slice(&self.file_size.to_le_bytes());
+        params.extend_from_slice(&(self.chunks.len() as u32).to_le_bytes());
+        let payload = serde_json::to_vec(self)?;
+        Ok(Container::new(Self::KIND, params, payload).to_bytes())
+    }
+
+    fn from_wire(bytes: &[u8]) -> Result<Self> {
+        let container = Container::parse_kind(bytes, Self::KIND)?;
+        let mut params = Reader::new(&container.params);
+        let file_id = params.array::<32>()?;
+        let file_size = params.u64()?;
+        let chunk_count = params.u32()? as usize;
+        params.finish(container.minor_version)?;
+
+        let metadata: FileMetadata = serde_json::from_slice(&container.payload)?;
+        if metadata.file_id != file_id {
+            return Err(WireError::InvalidField("file ID"));
+        }
+        if metadata.file_size != file_size {
+            return Err(WireError::InvalidField("file size"));
+        }
+        if metadata.chunks.len() != chunk_count {
+            return Err(WireError::InvalidField("chunk count"));
+        }
+        Ok(metadata)
+    }
+}
+
+impl WireFormat for fec::Shard {
+    const KIND: Kind = Kind::FecShard;
+
+    fn to_wire(&self) -> Result<Vec<u8>> {
+        let mut params = Vec::with_capacity(6);
+        params.extend_from_slice(&self.idx.to_le_bytes());
+        params.extend_from_slice(&self.crc32.to_le_bytes());
+        Ok(Container::new(Self::KIND, params, self.data.clone()).to_bytes())
+    }
+
+    fn from_wire(bytes: &[u8]) -> Result<Self> {
+        let container = Container::parse_kind(bytes, Self::KIND)?;
+        let mut params = Reader::new(&container.params);
+        let idx = params.u16()?;
+        let crc32 = params.u32()?;
+        params.finish(container.minor_version)?;
+
+        // The CRC is kept as written, so callers can tell a damaged shard
+        Ok(fec::Shard {
+            idx,
+            data: container.payload,
+            crc32,
+        })
+    }
+}
+
+impl WireFormat for fec::ShardManifest {
+    const KIND: Kind = Kind::ShardManifest;
+
+    fn to_wire(&self) -> Result<Vec<u8>> {
+        let id_len = u16::try_from(self.object_id.len())
+            .map_err(|_| WireError::InvalidField("object ID"))?;
+        let mut params = Vec::with_capacity(18 + self.object_id.len());
+        params.extend_from_slice(&self.params.data_shares.to_le_bytes());
+        params.extend_from_slice(&self.params.parity_shares.to_le_bytes());
+        params.extend_from_slice(&self.params.symbol_size.to_le_bytes());
+        params.extend_from_slice(&(self.original_size as u64).to_le_bytes());
+        params.extend_from_slice(&id_len.to_le_bytes());
+        params.extend_from_slice(&self.object_id);
+
+        if self.shard_keys.len() != self.params.total_shares() as usize {
+            return Err(WireError::InvalidField("shard keys"));
+        }
+        let mut payload = Vec::with_capacity(self.shard_keys.len() * (2 + SHARD_KEY_LEN));
+        for key in &self.shard_keys {
+            if key.len() != SHARD_KEY_LEN {
+                return Err(WireError::InvalidField("shard key"));
+            }
+            payload.extend_from_slice(&(SHARD_KEY_LEN as u16).to_le_bytes());
+            payload.extend_from_slice(key);
+        }
+        Ok(Container::new(Self::KIND, params, payload).to_bytes())
+    }
+
+    fn from_wire(bytes: &[u8]) -> Result<Self> {
+        let container = Container::parse_kind(bytes, Self::KIND)?;
+        let mut params = Reader::new(&container.params);
+        let data_shares = params.u16()?;
+        let parity_shares = params.u16()?;
+        let symbol_size = params.u32()?;
+        let fec_params = FecParams::new(data_shares, parity_shares)
+            .and_then(|p| p.with_symbol_size(symbol_size))
+            .map_err(|_| WireError::InvalidField("FEC params"))?;
+        let original_size =
+            usize::try_from(params.u64()?).map_err(|_| WireError::InvalidField("original size"))?;
+        let id_len = params.u16()? as usize;
+        let object_id = params.take(id_len)?.to_vec();
+        params.finish(container.minor_version)?;
+
+        let mut keys = Reader::new(&container.payload);
+        let mut shard_keys = Vec::new();
+        while !keys.rest().is_empty() {
+            if keys.u16()? as usize != SHARD_KEY_LEN {
+                return Err(WireError::InvalidField("shard key"));
+            }
+            shard_keys.push(keys.take(SHARD_KEY_LEN)?.to_vec());
+        }
+        if shard_keys.len() != fec_params.total_shares() as usize {
+            return Err(WireError::InvalidField("shard keys"));
+        }
+
+        Ok(fec::ShardManifest {
+            object_id,
+            params: fec_params,
+            original_size,
+            shard_keys,
+        })
+    }
+}
+
+/// Cursor over container bytes
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).ok_or(WireError::Truncated)?;
+        let slice = self.bytes.get(self.pos..end).ok_or(WireError::Truncated)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.bytes[self.pos..]
+    }
+
+    /// Check that all params were read, unless a newer minor version of the
+    /// format may have appended fields after them
+    fn finish(&self, minor_version: u8) -> Result<()> {
+        if minor_version > MINOR_VERSION || self.rest().is_empty() {
+            return Ok(());
+        }
+        Err(WireError::TrailingParams(self.rest().len()))
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::ChunkReference;
+
+    fn test_shard() -> Shard {
+        let header = ShardHeader::new(EncryptionMode::ConvergentWithSecret, (4, 2), 5, [3u8; 32])
+            .with_expiry(1_900_000_000);
+        Shard::new(header, b"share".to_vec())
+    }
+
+    #[test]
+    fn test_wire_roundtrips_keep_identity() {
+        let shard = test_shard();
+        let decoded = Shard::from_wire(&shard.to_wire().unwrap()).unwrap();
+        assert_eq!(decoded.cid().unwrap(), shard.cid().unwrap());
+        assert_eq!(decoded.header.expires_at(), Some(1_900_000_000));
+
+        let descriptor = IDADescriptor {
+            k: 8,
+            n: 10,
+            stripe_size: 65536,
+            file_size: 12345,
+            code: "rs-gf256".into(),
+            checksum: [9u8; 32],
+        };
+        let decoded = IDADescriptor::from_wire(&descriptor.to_wire().unwrap()).unwrap();
+        assert_eq!(decoded.code, "rs-gf256");
+        assert_eq!((decoded.k, decoded.n, decoded.file_size), (8, 10, 12345));
+
+        let chunk = ChunkReference::new([1u8; 32], 0, 0, 100).with_shards(vec![[2u8; 32]]);
+        let manifest = FileMetadata::new([7u8; 32], 100, None, vec![chunk]);
+        let decoded = FileMetadata::from_wire(&manifest.to_wire().unwrap()).unwrap();
+        assert_eq!(decoded.compute_id(), manifest.compute_id());
+
+        let shard = fec::Shard::new(3, b"parity".to_vec());
+        let decoded = fec::Shard::from_wire(&shard.to_wire().unwrap()).unwrap();
+        assert_eq!((decoded.idx, decoded.crc32), (3, shard.crc32));
+        assert!(decoded.verify_crc());
+
+        let params = FecParams::new(3, 2)
+            .and_then(|p| p.with_symbol_size(16))
+            .unwrap();
+        let manifest = fec::ShardManifest::new(b"object".to_vec(), params, 40);
+        let decoded = fec::ShardManifest::from_wire(&manifest.to_wire().unwrap()).unwrap();
+        assert_eq!(decoded.object_id, b"object");
+        assert_eq!(decoded.params, params);
+        assert_eq!(decoded.original_size, 40);
+        assert_eq!(decoded.shard_keys, manifest.shard_keys);
+    }
+
+    #[test]
+    fn test_wire_parser_is_strict() {
+        let bytes = test_shard().to_wire().unwrap();
+
+        let mut corrupted = bytes.clone();
+        corrupted[HEADER_SIZE] ^= 1;
+        assert!(matches!(
+            Shard::from_wire(&corrupted),
+            Err(WireError::ChecksumMismatch)
+        ));
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(matches!(
+            Shard::from_wire(&trailing),
+            Err(WireError::TrailingBytes(1))
+        ));
+        assert!(matches!(
+            Shard::from_wire(&bytes[..bytes.len() - 1]),
+            Err(WireError::Truncated)
+        ));
+        assert!(matches!(
+            IDADescriptor::from_wire(&bytes),
+            Err(WireError::WrongKind {
+                expected: Kind::IdaDescriptor,
+                found: Kind::Shard
+            })
+        ));
+
+        let mut future = Container::from_bytes(&bytes).unwrap();
+        future.flags = 0x01;
+        assert!(matches!(
+            Container::from_bytes(&future.to_bytes()),
+            Err(WireError::UnknownRequiredFlags(0x01))
+        ));
+        let mut extra_params = Container::from_bytes(&bytes).unwrap();
+        extra_params.params.push(0);
+        assert!(matches!(
+            Shard::from_wire(&extra_params.to_bytes()),
+            Err(WireError::TrailingParams(1))
+        ));
+        let mut short_payload = Container::from_bytes(&bytes).unwrap();
+        short_payload.payload.pop();
+        assert!(matches!(
+            Shard::from_wire(&short_payload.to_bytes()),
+            Err(WireError::InvalidField("data size"))
+        ));
+
+        let mut newer_major = bytes.clone();
+        newer_major[4] = 2;
+        assert!(matches!(
+            Shard::from_wire(&newer_major),
+            Err(WireError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn test_shard_manifest_rejects_bad_keys() {
+        let params = FecParams::new(3, 2)
+            .and_then(|p| p.with_symbol_size(16))
+            .unwrap();
+        let bytes = fec::ShardManifest::new(b"object".to_vec(), params, 40)
+            .to_wire()
+            .unwrap();
+
+        // A key longer than 32 bytes would overrun fixed-size key buffers
+        let mut long_key = Container::from_bytes(&bytes).unwrap();
+        long_key.payload = [&64u16.to_le_bytes()[..], &[0xAA; 64]].concat().repeat(5);
+        assert!(matches!(
+            fec::ShardManifest::from_wire(&long_key.to_bytes()),
+            Err(WireError::InvalidField("shard key"))
+        ));
+
+        let mut missing_key = Container::from_bytes(&bytes).unwrap();
+        missing_key.payload.truncate(4 * (2 + SHARD_KEY_LEN));
+        assert!(matches!(
+            fec::ShardManifest::from_wire(&missing_key.to_bytes()),
+            Err(WireError::InvalidField("shard keys"))
+        ));
+    }
+
+    #[test]
+    fn test_container_len_splits_concatenated_containers() {
+        let first = test_shard().to_wire().unwrap();
+        let second = FileMetadata::new([7u8; 32], 0, None, Vec::new())
+            .to_wire()
+            .unwrap();
+        let stream = [first.clone(), second.clone()].concat();
+
+        let len = container_len(&stream[..HEADER_SIZE]).unwrap();
+        assert_eq!(len, first.len());
+        assert!(Shard::from_wire(&stream[..len]).is_ok());
+        assert_eq!(container_len(&stream[len..]).unwrap(), second.len());
+        assert!(matches!(
+            container_len(&stream[1..]),
+            Err(WireError::BadMagic)
+        ));
+        assert!(matches!(
+            container_len(&stream[..HEADER_SIZE - 1]),
+            Err(WireError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn test_wire_reads_newer_minor_versions() {
+        // A later minor version appends params and sets an optional flag
+        let mut container = Container::from_bytes(&test_shard().to_wire().unwrap()).unwrap();
+        container.minor_version = 7;
+        container.flags = 0x10;
+        container.params.extend_from_slice(b"new fields");
+
+        let shard = Shard::from_wire(&container.to_bytes()).unwrap();
+        assert_eq!(shard.cid().unwrap(), test_shard().cid().unwrap());
+    }
+}
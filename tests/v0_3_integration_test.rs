@@ -1,6 +1,7 @@
 //! Integration test for v0.3 API specification compliance
 
 use anyhow::Result;
+use saorsa_fec::keystore::MemoryKeystore;
 use saorsa_fec::{storage::LocalStorage, Config, EncryptionMode, Meta, StoragePipeline};
 use tempfile::TempDir;
 
@@ -84,7 +85,9 @@ async fn test_v0_3_encryption_modes() -> Result<()> {
     {
         let backend = LocalStorage::new(temp_dir.path().join("convergent_secret")).await?;
         let config = Config::default().with_encryption_mode(EncryptionMode::ConvergentWithSecret);
-        let mut pipeline = StoragePipeline::new(config, backend).await?;
+        let mut pipeline = StoragePipeline::builder(config, backend)
+            .keystore(MemoryKeystore::new())
+            .build()?;
 
         let metadata = pipeline.process_file(file_id, data, None).await?;
         let retrieved = pipeline.retrieve_file(&metadata).await?;